@@ -2,7 +2,7 @@ use comfy_table::{
     modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, CellAlignment,
     ContentArrangement, Table,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cmp::Reverse;
 use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
@@ -10,8 +10,9 @@ use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use crate::blobcache::BlobSizeCache;
 use crate::gitutil;
-use crate::opts::{AnalyzeConfig, AnalyzeThresholds, Mode, Options};
+use crate::opts::{AnalyzeConfig, AnalyzeFormat, AnalyzeThresholds, Mode, Options};
 use crate::pathutil::dequote_c_style_bytes;
 use crate::pipes;
 
@@ -57,13 +58,69 @@ pub enum WarningLevel {
     Critical,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl WarningLevel {
+    pub fn severity(self) -> Severity {
+        match self {
+            WarningLevel::Info => Severity::Info,
+            WarningLevel::Warning => Severity::Warn,
+            WarningLevel::Critical => Severity::Error,
+        }
+    }
+}
+
+/// Severity of an analysis warning, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    fn prefix(self) -> &'static str {
+        match self {
+            Severity::Info => "[INFO]",
+            Severity::Warn => "[WARN]",
+            Severity::Error => "[ERROR]",
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warn => "Warnings",
+            Severity::Error => "Errors",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Warning {
     pub level: WarningLevel,
     pub message: String,
     pub recommendation: Option<String>,
 }
 
+impl Warning {
+    pub fn severity(&self) -> Severity {
+        self.level.severity()
+    }
+}
+
+// JSON reports carry `severity` next to the original `level` field
+impl Serialize for Warning {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut warning = serializer.serialize_struct("Warning", 4)?;
+        warning.serialize_field("level", &self.level)?;
+        warning.serialize_field("severity", &self.severity())?;
+        warning.serialize_field("message", &self.message)?;
+        warning.serialize_field("recommendation", &self.recommendation)?;
+        warning.end()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ObjectStat {
     pub oid: String,
@@ -78,6 +135,23 @@ pub struct DirectoryStat {
     pub entries: usize,
 }
 
+/// Blobs across history grouped by the extension of their (first seen) path.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct ExtensionStat {
+    pub extension: String,
+    pub total_bytes: u64,
+    pub blobs: u64,
+}
+
+/// Blobs across history grouped by the top-level directory of their (first
+/// seen) path.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct DirectorySizeStat {
+    pub path: String,
+    pub total_bytes: u64,
+    pub blobs: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct PathStat {
     pub path: String,
@@ -88,6 +162,7 @@ pub struct PathStat {
 pub struct DuplicateBlobStat {
     pub oid: String,
     pub paths: usize,
+    pub size: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub example_path: Option<String>,
 }
@@ -121,22 +196,45 @@ pub struct RepositoryMetrics {
     pub directory_hotspots: Option<DirectoryStat>,
     pub longest_path: Option<PathStat>,
     pub duplicate_blobs: Vec<DuplicateBlobStat>,
+    /// Per-extension totals, largest first; filled with `--analyze-extensions`.
+    pub extension_stats: Vec<ExtensionStat>,
+    /// Total size of the blobs reachable in the analyzed history.
+    pub history_blob_bytes: u64,
+    /// Top-level directories by blob bytes across history, largest first.
+    pub directory_sizes: Vec<DirectorySizeStat>,
     pub max_commit_parents: usize,
     pub oversized_commit_messages: Vec<CommitMessageStat>,
 }
 
+/// Advisory filter command derived from the metrics; never run automatically.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub message: String,
+    pub command: String,
+    pub estimated_bytes_saved: u64,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AnalysisReport {
     pub metrics: RepositoryMetrics,
     pub warnings: Vec<Warning>,
+    pub suggestions: Vec<Suggestion>,
 }
 
 pub fn run(opts: &Options) -> io::Result<()> {
     debug_assert_eq!(opts.mode, Mode::Analyze);
+    crate::migrate::fetch_before_analysis(opts);
     let report = generate_report(opts)?;
     if opts.analyze.json {
         let json = serde_json::to_string_pretty(&report).map_err(to_io_error)?;
         println!("{}", json);
+    } else if opts.analyze.format == AnalyzeFormat::Html {
+        let dir = crate::statedir::ensure(&opts.source, opts.state_dir.as_deref())?;
+        let path = dir.join("analysis.html");
+        std::fs::write(&path, render_html(&report, &opts.analyze))?;
+        if !opts.quiet {
+            println!("Analysis report written to {}", path.display());
+        }
     } else {
         print_human(&report, &opts.analyze);
     }
@@ -147,25 +245,61 @@ pub fn generate_report(opts: &Options) -> io::Result<AnalysisReport> {
     // Avoid Windows verbatim (\\?\) paths which can confuse external tools like Git when
     // passed via command-line flags. Use the provided path directly.
     let repo = opts.source.clone();
-    let metrics = collect_metrics(&repo, &opts.analyze)?;
-    let warnings = evaluate_warnings(&metrics, &opts.analyze.thresholds);
-    Ok(AnalysisReport { metrics, warnings })
+    if let Some(range) = &opts.analyze.range {
+        validate_range(&repo, range)?;
+    }
+    let metrics = collect_metrics(&repo, &opts.analyze, opts.size_cache)?;
+    let mut warnings = evaluate_warnings(&metrics, &opts.analyze.thresholds);
+    warnings.retain(|w| w.severity() >= opts.analyze.min_severity);
+    let suggestions = suggest_actions(&metrics, &opts.analyze.thresholds);
+    Ok(AnalysisReport {
+        metrics,
+        warnings,
+        suggestions,
+    })
 }
 
-fn collect_metrics(repo: &Path, cfg: &AnalyzeConfig) -> io::Result<RepositoryMetrics> {
+fn collect_metrics(
+    repo: &Path,
+    cfg: &AnalyzeConfig,
+    size_cache: bool,
+) -> io::Result<RepositoryMetrics> {
     let mut metrics = RepositoryMetrics::default();
     metrics.workdir = Some(repo.display().to_string());
     gather_footprint(repo, &mut metrics)?;
     gather_refs(repo, &mut metrics)?;
+    // Tree inventory via cat-file for counts and top sizes; with the size
+    // cache the same pass also records every blob size
+    let blob_sizes = gather_tree_inventory(repo, cfg, &mut metrics, size_cache)?;
     // History-wide scan via fast-export for reachable blobs/commits and path mapping
-    gather_history_fast_export(repo, cfg, &mut metrics)?;
-    // Tree inventory via cat-file for counts and top sizes (best-effort)
-    gather_tree_inventory(repo, cfg, &mut metrics)?;
+    gather_history_fast_export(repo, cfg, &mut metrics, blob_sizes.as_ref())?;
     // Keep a quick HEAD snapshot for context
     gather_worktree_snapshot(repo, cfg, &mut metrics)?;
     Ok(metrics)
 }
 
+fn validate_range(repo: &Path, range: &str) -> io::Result<()> {
+    let out = Command::new("git")
+        .current_dir(repo)
+        .arg("rev-parse")
+        .arg(range)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !out.status.success() {
+        let detail = String::from_utf8_lossy(&out.stderr);
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid --analyze-range '{}': {}",
+                range,
+                detail.lines().next().unwrap_or("git rev-parse failed")
+            ),
+        ));
+    }
+    Ok(())
+}
+
 fn gather_footprint(repo: &Path, metrics: &mut RepositoryMetrics) -> io::Result<()> {
     let output = run_git_capture(repo, &["count-objects", "-v"])?;
     for line in output.lines() {
@@ -189,10 +323,46 @@ fn gather_tree_inventory(
     repo: &Path,
     cfg: &AnalyzeConfig,
     metrics: &mut RepositoryMetrics,
-) -> io::Result<()> {
-    let mut largest_trees: BinaryHeap<Reverse<(u64, String)>> = BinaryHeap::new();
+    size_cache: bool,
+) -> io::Result<Option<BlobSizeCache>> {
+    let mut largest_trees: TopObjects = BinaryHeap::new();
     let mut tree_count: u64 = 0;
     let mut tree_total: u64 = 0;
+    let mut record = |oid: &str, typ: &str, size: u64| {
+        if typ == "tree" {
+            tree_count += 1;
+            tree_total = tree_total.saturating_add(size);
+            push_top(&mut largest_trees, cfg.top, size, oid);
+        }
+    };
+    let cache = if size_cache {
+        Some(BlobSizeCache::scan(repo, &mut record)?)
+    } else {
+        scan_object_sizes(repo, &mut record)?;
+        None
+    };
+    if tree_count > 0 {
+        metrics.object_types.insert("tree".to_string(), tree_count);
+    }
+    metrics.tree_total_size_bytes = tree_total;
+    metrics.largest_trees = heap_to_vec(largest_trees);
+    Ok(cache)
+}
+
+/// Ids of every stored blob larger than `max` bytes, for
+/// `--strip-blobs-bigger-than-in-packs`.
+pub(crate) fn blobs_larger_than(repo: &Path, max: u64) -> io::Result<Vec<String>> {
+    let mut oids = Vec::new();
+    scan_object_sizes(repo, &mut |oid, typ, size| {
+        if typ == "blob" && size > max {
+            oids.push(oid.to_string());
+        }
+    })?;
+    Ok(oids)
+}
+
+// One `cat-file --batch-check --batch-all-objects` pass without keeping sizes.
+fn scan_object_sizes(repo: &Path, record: &mut dyn FnMut(&str, &str, u64)) -> io::Result<()> {
     let mut child = Command::new("git")
         .current_dir(repo)
         .arg("cat-file")
@@ -214,11 +384,7 @@ fn gather_tree_inventory(
         let oid = parts.next().unwrap_or("");
         let typ = parts.next().unwrap_or("");
         let size = parts.next().unwrap_or("0").parse::<u64>().unwrap_or(0);
-        if typ == "tree" {
-            tree_count += 1;
-            tree_total = tree_total.saturating_add(size);
-            push_top(&mut largest_trees, cfg.top, size, oid);
-        }
+        record(oid, typ, size);
     }
     let status = child.wait()?;
     if !status.success() {
@@ -227,11 +393,6 @@ fn gather_tree_inventory(
             "git cat-file --batch-check failed",
         ));
     }
-    if tree_count > 0 {
-        metrics.object_types.insert("tree".to_string(), tree_count);
-    }
-    metrics.tree_total_size_bytes = tree_total;
-    metrics.largest_trees = heap_to_vec(largest_trees);
     Ok(())
 }
 
@@ -317,6 +478,7 @@ fn gather_worktree_snapshot(
                 .or_insert_with(|| DuplicateBlobStat {
                     oid: oid.to_string(),
                     paths: 0,
+                    size: 0,
                     example_path: Some(path.to_string()),
                 });
             entry.paths += 1;
@@ -348,14 +510,21 @@ fn gather_worktree_snapshot(
     if !needed.is_empty() {
         history_paths = map_oids_to_paths_from_history(repo, &needed)?;
     }
-    let mut duplicates_vec: Vec<DuplicateBlobStat> = duplicates
-        .into_iter()
-        .filter(|(_, stat)| stat.paths > 1)
-        .map(|(_, stat)| stat)
-        .collect();
-    duplicates_vec.sort_by(|a, b| b.paths.cmp(&a.paths));
-    duplicates_vec.truncate(cfg.top);
-    metrics.duplicate_blobs = duplicates_vec;
+    // A ranged analysis keeps the duplicates found in the in-range history
+    if cfg.range.is_none() {
+        let mut duplicates_vec: Vec<DuplicateBlobStat> = duplicates
+            .into_values()
+            .filter(|stat| stat.paths > 1)
+            .collect();
+        // Sizes are part of the ranking, so look them up before truncating
+        let sizes = batch_check_blob_sizes(repo, duplicates_vec.iter().map(|d| &d.oid))?;
+        for dup in &mut duplicates_vec {
+            dup.size = sizes.get(&dup.oid).copied().unwrap_or(0);
+        }
+        sort_duplicates(&mut duplicates_vec);
+        duplicates_vec.truncate(cfg.top);
+        metrics.duplicate_blobs = duplicates_vec;
+    }
     for blob in metrics
         .largest_blobs
         .iter_mut()
@@ -429,13 +598,16 @@ fn gather_history_fast_export(
     repo: &Path,
     cfg: &AnalyzeConfig,
     metrics: &mut RepositoryMetrics,
+    blob_sizes: Option<&BlobSizeCache>,
 ) -> io::Result<()> {
     let mut fe_opts = Options::default();
     fe_opts.source = repo.to_path_buf();
     fe_opts.no_data = true;
     fe_opts.quotepath = true;
-    let mut cmd = pipes::build_fast_export_cmd(&fe_opts)?;
-    let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+    if let Some(range) = &cfg.range {
+        fe_opts.refs = vec![range.clone()];
+    }
+    let mut child = pipes::build_fast_export_cmd(&fe_opts)?.spawn()?;
     let stdout = child.stdout.take().ok_or_else(|| {
         io::Error::new(
             io::ErrorKind::Other,
@@ -527,9 +699,18 @@ fn gather_history_fast_export(
         .insert("blob".to_string(), blob_paths.len() as u64);
 
     // Fetch sizes for all observed blobs, then compute top lists
-    let sizes = batch_check_blob_sizes(repo, blob_paths.keys())?;
-    let mut largest_blobs: BinaryHeap<Reverse<(u64, String)>> = BinaryHeap::new();
-    let mut threshold_hits: BinaryHeap<Reverse<(u64, String)>> = BinaryHeap::new();
+    let sizes = match blob_sizes {
+        Some(cache) => blob_paths
+            .keys()
+            .filter_map(|oid| cache.get(oid.as_bytes()).map(|size| (oid.clone(), size)))
+            .collect(),
+        None => batch_check_blob_sizes(repo, blob_paths.keys())?,
+    };
+    if let Some(path) = &cfg.dump_blobs {
+        dump_blobs(path, &blob_example_path, &sizes)?;
+    }
+    let mut largest_blobs: TopObjects = BinaryHeap::new();
+    let mut threshold_hits: TopObjects = BinaryHeap::new();
     for (oid, size) in &sizes {
         push_top(&mut largest_blobs, cfg.top, *size, oid);
         if *size >= cfg.thresholds.warn_blob_bytes {
@@ -548,6 +729,11 @@ fn gather_history_fast_export(
             blob.path = Some(path.clone());
         }
     }
+    if cfg.extensions {
+        metrics.extension_stats = extension_stats(&sizes, &blob_example_path, cfg.top);
+    }
+    metrics.history_blob_bytes = sizes.values().sum();
+    metrics.directory_sizes = directory_sizes(&sizes, &blob_example_path, cfg.top);
 
     // Duplicate blobs across history: rank by unique path count
     let mut dups: Vec<DuplicateBlobStat> = blob_paths
@@ -556,22 +742,134 @@ fn gather_history_fast_export(
             let count = paths.len();
             if count > 1 {
                 Some(DuplicateBlobStat {
+                    size: sizes.get(&oid).copied().unwrap_or(0),
+                    example_path: blob_example_path.get(&oid).cloned(),
                     oid,
                     paths: count,
-                    example_path: None,
                 })
             } else {
                 None
             }
         })
         .collect();
-    dups.sort_by(|a, b| b.paths.cmp(&a.paths));
+    sort_duplicates(&mut dups);
     dups.truncate(cfg.top);
     metrics.duplicate_blobs = dups;
 
     Ok(())
 }
 
+#[derive(Serialize)]
+struct BlobRecord<'a> {
+    oid: &'a str,
+    size: u64,
+    path: &'a str,
+}
+
+// `--analyze-dump-blobs`: one NDJSON record per blob, ordered by oid, each
+// serialized straight into the file.
+fn dump_blobs(
+    path: &Path,
+    example_paths: &HashMap<String, String>,
+    sizes: &HashMap<String, u64>,
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+    let mut oids: Vec<&String> = example_paths.keys().collect();
+    oids.sort();
+    for oid in oids {
+        let record = BlobRecord {
+            oid,
+            size: sizes.get(oid).copied().unwrap_or(0),
+            path: &example_paths[oid],
+        };
+        serde_json::to_writer(&mut out, &record).map_err(to_io_error)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+// Most paths first, then largest, then by oid, so equal counts rank the same
+// on every run.
+fn sort_duplicates(dups: &mut [DuplicateBlobStat]) {
+    dups.sort_by(|a, b| {
+        b.paths
+            .cmp(&a.paths)
+            .then_with(|| b.size.cmp(&a.size))
+            .then_with(|| a.oid.cmp(&b.oid))
+    });
+}
+
+fn extension_stats(
+    sizes: &HashMap<String, u64>,
+    example_paths: &HashMap<String, String>,
+    top: usize,
+) -> Vec<ExtensionStat> {
+    let mut by_ext: HashMap<String, ExtensionStat> = HashMap::new();
+    for (oid, size) in sizes {
+        let extension = example_paths
+            .get(oid)
+            .and_then(|p| path_extension(p))
+            .unwrap_or_else(|| "(none)".to_string());
+        let stat = by_ext
+            .entry(extension.clone())
+            .or_insert_with(|| ExtensionStat {
+                extension,
+                ..ExtensionStat::default()
+            });
+        stat.total_bytes = stat.total_bytes.saturating_add(*size);
+        stat.blobs += 1;
+    }
+    let mut stats: Vec<ExtensionStat> = by_ext.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.total_bytes
+            .cmp(&a.total_bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    stats.truncate(top);
+    stats
+}
+
+fn directory_sizes(
+    sizes: &HashMap<String, u64>,
+    example_paths: &HashMap<String, String>,
+    top: usize,
+) -> Vec<DirectorySizeStat> {
+    let mut by_dir: HashMap<&str, DirectorySizeStat> = HashMap::new();
+    for (oid, size) in sizes {
+        let Some(dir) = example_paths.get(oid).and_then(|p| top_level_dir(p)) else {
+            continue;
+        };
+        let stat = by_dir.entry(dir).or_insert_with(|| DirectorySizeStat {
+            path: dir.to_string(),
+            ..DirectorySizeStat::default()
+        });
+        stat.total_bytes = stat.total_bytes.saturating_add(*size);
+        stat.blobs += 1;
+    }
+    let mut stats: Vec<DirectorySizeStat> = by_dir.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.total_bytes
+            .cmp(&a.total_bytes)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    stats.truncate(top);
+    stats
+}
+
+// First path component with its trailing slash; files at the root have none.
+fn top_level_dir(path: &str) -> Option<&str> {
+    path.find('/').map(|slash| &path[..=slash])
+}
+
+// Lowercased extension of the file name; dotfiles such as `.gitignore` have none.
+fn path_extension(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rfind('.') {
+        Some(dot) if dot > 0 && dot + 1 < name.len() => Some(name[dot + 1..].to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
 fn parse_size_after_data(line: &[u8]) -> io::Result<usize> {
     if !line.starts_with(b"data ") {
         return Err(io::Error::new(
@@ -682,6 +980,85 @@ where
 
 // (removed old gather_history_stats; superseded by gather_history_fast_export)
 
+// Ready-to-run filter commands for the biggest offenders, each with the
+// bytes it would save according to the metrics above.
+fn suggest_actions(metrics: &RepositoryMetrics, thresholds: &AnalyzeThresholds) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    if !metrics.blobs_over_threshold.is_empty() {
+        let (limit, label) = round_size_down(thresholds.warn_blob_bytes);
+        let over: Vec<&ObjectStat> = metrics
+            .blobs_over_threshold
+            .iter()
+            .filter(|b| b.size > limit)
+            .collect();
+        if !over.is_empty() {
+            let saved = over.iter().map(|b| b.size).sum();
+            suggestions.push(Suggestion {
+                message: format!(
+                    "{} blob(s) are larger than {}; dropping them saves about {:.2} MiB",
+                    format_count(over.len() as u64),
+                    label,
+                    to_mib(saved)
+                ),
+                command: format!("filter-repo-rs --max-blob-size {}", label),
+                estimated_bytes_saved: saved,
+            });
+        }
+    }
+    if let Some(dir) = metrics.directory_sizes.first() {
+        let total = metrics.history_blob_bytes;
+        if dir.total_bytes * 2 >= total && dir.total_bytes < total {
+            suggestions.push(Suggestion {
+                message: format!(
+                    "{} holds {:.0}% of the blob bytes in history ({:.2} MiB)",
+                    dir.path,
+                    dir.total_bytes as f64 * 100.0 / total as f64,
+                    to_mib(dir.total_bytes)
+                ),
+                command: format!("filter-repo-rs --path {} --invert-paths", dir.path),
+                estimated_bytes_saved: dir.total_bytes,
+            });
+        }
+    }
+    let mut dup_dirs: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+    for dup in &metrics.duplicate_blobs {
+        if let Some(dir) = dup.example_path.as_deref().and_then(top_level_dir) {
+            let entry = dup_dirs.entry(dir).or_default();
+            entry.0 += 1;
+            entry.1 += dup.size * (dup.paths as u64 - 1);
+        }
+    }
+    if let Some((dir, (count, extra))) = dup_dirs.into_iter().max_by_key(|(_, (n, _))| *n) {
+        if count > 1 {
+            suggestions.push(Suggestion {
+                message: format!(
+                    "{} duplicated blobs cluster under {}; their extra copies add {:.2} MiB to each checkout (Git stores one copy)",
+                    format_count(count as u64),
+                    dir,
+                    to_mib(extra)
+                ),
+                command: format!("filter-repo-rs --delete-duplicate-paths '{}**'", dir),
+                estimated_bytes_saved: extra,
+            });
+        }
+    }
+    suggestions
+}
+
+// Largest whole number of MiB (or KiB) not above `bytes`, with its
+// `--max-blob-size` spelling.
+fn round_size_down(bytes: u64) -> (u64, String) {
+    const KIB: u64 = 1024;
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= MIB {
+        ((bytes / MIB) * MIB, format!("{}M", bytes / MIB))
+    } else if bytes >= KIB {
+        ((bytes / KIB) * KIB, format!("{}K", bytes / KIB))
+    } else {
+        (bytes, bytes.to_string())
+    }
+}
+
 fn evaluate_warnings(metrics: &RepositoryMetrics, thresholds: &AnalyzeThresholds) -> Vec<Warning> {
     let mut warnings = Vec::new();
     if metrics.total_size_bytes >= thresholds.crit_total_bytes {
@@ -792,7 +1169,7 @@ fn evaluate_warnings(metrics: &RepositoryMetrics, thresholds: &AnalyzeThresholds
     }
     for msg in &metrics.oversized_commit_messages {
         warnings.push(Warning {
-            level: WarningLevel::Info,
+            level: WarningLevel::Warning,
             message: format!(
                 "Commit {} has a {} byte message (threshold {}).",
                 msg.oid, msg.length, thresholds.warn_commit_msg_bytes
@@ -924,6 +1301,35 @@ fn print_human(report: &AnalysisReport, cfg: &AnalyzeConfig) {
             rows,
         );
     }
+    if !report.metrics.extension_stats.is_empty() {
+        println!(
+            "  By file type (top {}):",
+            format_count(report.metrics.extension_stats.len() as u64)
+        );
+        let rows = report
+            .metrics
+            .extension_stats
+            .iter()
+            .enumerate()
+            .map(|(idx, ext)| {
+                vec![
+                    Cow::Owned(format!("{}", idx + 1)),
+                    Cow::Borrowed(ext.extension.as_str()),
+                    Cow::Owned(format_count(ext.blobs)),
+                    Cow::Owned(format!("{:.2} MiB", to_mib(ext.total_bytes))),
+                ]
+            })
+            .collect();
+        print_table(
+            &[
+                ("#", CellAlignment::Right),
+                ("Extension", CellAlignment::Left),
+                ("Blobs", CellAlignment::Right),
+                ("Size", CellAlignment::Right),
+            ],
+            rows,
+        );
+    }
     // History oddities are summarized above; keep oversized messages as a list
     if !report.metrics.oversized_commit_messages.is_empty() {
         println!("  Oversized commit messages:");
@@ -980,31 +1386,73 @@ fn print_human(report: &AnalysisReport, cfg: &AnalyzeConfig) {
     }
 
     print_section("Warnings");
-    let warning_rows = report
-        .warnings
-        .iter()
-        .map(|warning| {
-            // Replace 40-char OIDs in certain messages with footnote markers.
-            let (msg, _maybe_ref) = humanize_warning_message(&warning.message, report, &mut foot);
-            vec![
-                Cow::Owned(format!("{:?}", warning.level)),
-                Cow::Owned(msg),
-                warning
-                    .recommendation
-                    .as_deref()
-                    .map(Cow::Borrowed)
-                    .unwrap_or(Cow::Borrowed("")),
-            ]
-        })
-        .collect();
-    print_table(
-        &[
-            ("Level", CellAlignment::Center),
-            ("Message", CellAlignment::Left),
-            ("Recommendation", CellAlignment::Left),
-        ],
-        warning_rows,
-    );
+    if report.warnings.is_empty() {
+        println!("  No warnings at or above the configured minimum severity.");
+    }
+    for severity in [Severity::Error, Severity::Warn, Severity::Info] {
+        let group: Vec<&Warning> = report
+            .warnings
+            .iter()
+            .filter(|w| w.severity() == severity)
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+        println!(
+            "  {} {} ({}):",
+            severity.prefix(),
+            severity.heading(),
+            format_count(group.len() as u64)
+        );
+        let warning_rows = group
+            .into_iter()
+            .map(|warning| {
+                // Replace 40-char OIDs in certain messages with footnote markers.
+                let (msg, _maybe_ref) =
+                    humanize_warning_message(&warning.message, report, &mut foot);
+                vec![
+                    Cow::Borrowed(severity.prefix()),
+                    Cow::Owned(msg),
+                    warning
+                        .recommendation
+                        .as_deref()
+                        .map(Cow::Borrowed)
+                        .unwrap_or(Cow::Borrowed("")),
+                ]
+            })
+            .collect();
+        print_table(
+            &[
+                ("Severity", CellAlignment::Center),
+                ("Message", CellAlignment::Left),
+                ("Recommendation", CellAlignment::Left),
+            ],
+            warning_rows,
+        );
+    }
+
+    if !report.suggestions.is_empty() {
+        print_section("Suggested actions");
+        let rows = report
+            .suggestions
+            .iter()
+            .map(|s| {
+                vec![
+                    Cow::Borrowed(s.message.as_str()),
+                    Cow::Borrowed(s.command.as_str()),
+                    Cow::Owned(format!("{:.2} MiB", to_mib(s.estimated_bytes_saved))),
+                ]
+            })
+            .collect();
+        print_table(
+            &[
+                ("Finding", CellAlignment::Left),
+                ("Command", CellAlignment::Left),
+                ("Est. saved", CellAlignment::Right),
+            ],
+            rows,
+        );
+    }
 
     // Print footnotes at the end
     if !foot.is_empty() {
@@ -1113,6 +1561,261 @@ fn parent_directory(path: &str) -> Option<String> {
     })
 }
 
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:2em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+th{background:#f0f0f0;cursor:pointer}\
+td.num{text-align:right}\
+code{font-size:90%}";
+
+// Clicking a header sorts its table; numeric cells sort by their data-sort value.
+const HTML_SCRIPT: &str = "document.querySelectorAll('table.sortable th').forEach(function(th,idx){\
+th.addEventListener('click',function(){\
+var tbody=th.closest('table').querySelector('tbody');\
+var rows=Array.prototype.slice.call(tbody.rows);\
+var asc=th.getAttribute('data-dir')!=='asc';\
+th.setAttribute('data-dir',asc?'asc':'desc');\
+rows.sort(function(a,b){\
+var x=a.cells[idx],y=b.cells[idx];\
+var xv=x.getAttribute('data-sort'),yv=y.getAttribute('data-sort');\
+var c=(xv!==null&&yv!==null)?(parseFloat(xv)-parseFloat(yv)):x.textContent.localeCompare(y.textContent);\
+return asc?c:-c;});\
+rows.forEach(function(r){tbody.appendChild(r);});});});";
+
+/// Render the analysis report as a self-contained HTML page with sortable tables.
+pub fn render_html(report: &AnalysisReport, cfg: &AnalyzeConfig) -> String {
+    let metrics = &report.metrics;
+    let mut out = String::with_capacity(16 * 1024);
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\"/>\n");
+    out.push_str("<title>Repository analysis</title>\n<style>");
+    out.push_str(HTML_STYLE);
+    out.push_str("</style>\n</head>\n<body>\n<h1>Repository analysis</h1>\n");
+    if let Some(path) = &metrics.workdir {
+        out.push_str(&format!("<p><code>{}</code></p>\n", html_escape(path)));
+    }
+
+    out.push_str("<h2>Summary</h2>\n");
+    let summary: Vec<Vec<HtmlCell>> = vec![
+        vec![
+            HtmlCell::text("Total objects"),
+            HtmlCell::num(metrics.total_objects, format_count(metrics.total_objects)),
+        ],
+        vec![
+            HtmlCell::text("Total size"),
+            HtmlCell::num(
+                metrics.total_size_bytes,
+                format!("{:.2} MiB", to_mib(metrics.total_size_bytes)),
+            ),
+        ],
+        vec![
+            HtmlCell::text("Refs"),
+            HtmlCell::num(
+                metrics.refs_total as u64,
+                format_count(metrics.refs_total as u64),
+            ),
+        ],
+        vec![
+            HtmlCell::text("Max commit parents"),
+            HtmlCell::num(
+                metrics.max_commit_parents as u64,
+                format_count(metrics.max_commit_parents as u64),
+            ),
+        ],
+    ];
+    push_html_table(&mut out, &["Metric", "Value"], summary);
+
+    out.push_str("<h2>Largest blobs</h2>\n");
+    let blobs = metrics
+        .largest_blobs
+        .iter()
+        .take(cfg.top)
+        .map(|blob| {
+            vec![
+                HtmlCell::code(&blob.oid),
+                HtmlCell::num(blob.size, format!("{:.2} MiB", to_mib(blob.size))),
+                HtmlCell::text(blob.path.as_deref().unwrap_or("")),
+            ]
+        })
+        .collect();
+    push_html_table(&mut out, &["OID", "Size", "Path"], blobs);
+
+    out.push_str("<h2>Largest trees</h2>\n");
+    let trees = metrics
+        .largest_trees
+        .iter()
+        .take(cfg.top)
+        .map(|tree| {
+            vec![
+                HtmlCell::code(&tree.oid),
+                HtmlCell::num(tree.size, format!("{:.2} KiB", tree.size as f64 / 1024.0)),
+            ]
+        })
+        .collect();
+    push_html_table(&mut out, &["OID", "Size"], trees);
+
+    out.push_str("<h2>Duplicate blobs</h2>\n");
+    let duplicates = metrics
+        .duplicate_blobs
+        .iter()
+        .take(cfg.top)
+        .map(|dup| {
+            vec![
+                HtmlCell::code(&dup.oid),
+                HtmlCell::num(dup.paths as u64, format_count(dup.paths as u64)),
+                HtmlCell::text(dup.example_path.as_deref().unwrap_or("")),
+            ]
+        })
+        .collect();
+    push_html_table(&mut out, &["OID", "Paths", "Example path"], duplicates);
+
+    if !metrics.extension_stats.is_empty() {
+        out.push_str("<h2>By file type</h2>\n");
+        let extensions = metrics
+            .extension_stats
+            .iter()
+            .map(|ext| {
+                vec![
+                    HtmlCell::text(&ext.extension),
+                    HtmlCell::num(ext.blobs, format_count(ext.blobs)),
+                    HtmlCell::num(
+                        ext.total_bytes,
+                        format!("{:.2} MiB", to_mib(ext.total_bytes)),
+                    ),
+                ]
+            })
+            .collect();
+        push_html_table(&mut out, &["Extension", "Blobs", "Size"], extensions);
+    }
+
+    out.push_str("<h2>Directories</h2>\n");
+    let directories = metrics
+        .directory_sizes
+        .iter()
+        .map(|dir| {
+            vec![
+                HtmlCell::text(&dir.path),
+                HtmlCell::num(dir.blobs, format_count(dir.blobs)),
+                HtmlCell::num(
+                    dir.total_bytes,
+                    format!("{:.2} MiB", to_mib(dir.total_bytes)),
+                ),
+            ]
+        })
+        .collect();
+    push_html_table(&mut out, &["Directory", "Blobs", "Size"], directories);
+
+    out.push_str("<h2>Warnings</h2>\n");
+    let warnings = report
+        .warnings
+        .iter()
+        .map(|w| {
+            vec![
+                HtmlCell::num(w.severity() as u64, w.severity().heading().to_string()),
+                HtmlCell::text(&w.message),
+                HtmlCell::text(w.recommendation.as_deref().unwrap_or("")),
+            ]
+        })
+        .collect();
+    push_html_table(
+        &mut out,
+        &["Severity", "Message", "Recommendation"],
+        warnings,
+    );
+
+    if !report.suggestions.is_empty() {
+        out.push_str("<h2>Suggested actions</h2>\n");
+        let suggestions = report
+            .suggestions
+            .iter()
+            .map(|s| {
+                vec![
+                    HtmlCell::text(&s.message),
+                    HtmlCell::code(&s.command),
+                    HtmlCell::num(
+                        s.estimated_bytes_saved,
+                        format!("{:.2} MiB", to_mib(s.estimated_bytes_saved)),
+                    ),
+                ]
+            })
+            .collect();
+        push_html_table(&mut out, &["Finding", "Command", "Est. saved"], suggestions);
+    }
+
+    out.push_str("<script>");
+    out.push_str(HTML_SCRIPT);
+    out.push_str("</script>\n</body>\n</html>\n");
+    out
+}
+
+struct HtmlCell {
+    html: String,
+    sort_key: Option<u64>,
+}
+
+impl HtmlCell {
+    fn text(value: &str) -> Self {
+        Self {
+            html: html_escape(value),
+            sort_key: None,
+        }
+    }
+
+    fn code(value: &str) -> Self {
+        Self {
+            html: format!("<code>{}</code>", html_escape(value)),
+            sort_key: None,
+        }
+    }
+
+    fn num(key: u64, display: String) -> Self {
+        Self {
+            html: html_escape(&display),
+            sort_key: Some(key),
+        }
+    }
+}
+
+fn push_html_table(out: &mut String, headers: &[&str], rows: Vec<Vec<HtmlCell>>) {
+    if rows.is_empty() {
+        out.push_str("<p>None.</p>\n");
+        return;
+    }
+    out.push_str("<table class=\"sortable\">\n<thead>\n<tr>");
+    for h in headers {
+        out.push_str(&format!("<th>{}</th>", html_escape(h)));
+    }
+    out.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            match cell.sort_key {
+                Some(key) => out.push_str(&format!(
+                    "<td class=\"num\" data-sort=\"{}\">{}</td>",
+                    key, cell.html
+                )),
+                None => out.push_str(&format!("<td>{}</td>", cell.html)),
+            }
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+}
+
+fn html_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
 fn to_mib(bytes: u64) -> f64 {
     bytes as f64 / 1024.0 / 1024.0
 }
@@ -1125,10 +1828,15 @@ fn to_io_error(err: serde_json::Error) -> io::Error {
     io::Error::new(io::ErrorKind::Other, err)
 }
 
-fn heap_to_vec(heap: BinaryHeap<Reverse<(u64, String)>>) -> Vec<ObjectStat> {
+// Min-heap of the largest objects seen so far, keyed by size and then by
+// reversed oid: among equal sizes the lowest oids are kept and listed first,
+// whatever order the objects arrive in.
+type TopObjects = BinaryHeap<Reverse<(u64, Reverse<String>)>>;
+
+fn heap_to_vec(heap: TopObjects) -> Vec<ObjectStat> {
     heap.into_sorted_vec()
         .into_iter()
-        .map(|Reverse((size, oid))| ObjectStat {
+        .map(|Reverse((size, Reverse(oid)))| ObjectStat {
             oid,
             size,
             path: None,
@@ -1136,18 +1844,16 @@ fn heap_to_vec(heap: BinaryHeap<Reverse<(u64, String)>>) -> Vec<ObjectStat> {
         .collect()
 }
 
-fn push_top(heap: &mut BinaryHeap<Reverse<(u64, String)>>, limit: usize, size: u64, oid: &str) {
+fn push_top(heap: &mut TopObjects, limit: usize, size: u64, oid: &str) {
     if limit == 0 {
         return;
     }
-    let entry = Reverse((size, oid.to_string()));
+    let entry = Reverse((size, Reverse(oid.to_string())));
     if heap.len() < limit {
         heap.push(entry);
-    } else if let Some(Reverse((min_size, _))) = heap.peek() {
-        if size > *min_size {
-            heap.pop();
-            heap.push(entry);
-        }
+    } else if heap.peek().is_some_and(|min| entry < *min) {
+        heap.pop();
+        heap.push(entry);
     }
 }
 
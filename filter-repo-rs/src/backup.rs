@@ -1,7 +1,8 @@
+use std::collections::HashMap;
 use std::fs;
-use std::io;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use time::format_description::FormatItem;
 use time::macros::format_description;
@@ -14,8 +15,85 @@ pub fn create_backup(opts: &Options) -> io::Result<Option<PathBuf>> {
     if opts.dry_run {
         return Ok(None);
     }
+    let bundle_path = bundle_path(opts)?;
+    bundle_refs(&opts.source, &bundle_path, opts)?;
+    Ok(Some(bundle_path))
+}
+
+/// Bundle the refs as they were in `refs_before`, after the rewrite has
+/// moved them (`--no-backup-if-noop`). A scratch bare repository borrowing
+/// the source's objects recreates the old refs under their own names.
+pub fn create_deferred_backup(
+    opts: &Options,
+    refs_before: &HashMap<String, String>,
+) -> io::Result<Option<PathBuf>> {
+    if opts.dry_run {
+        return Ok(None);
+    }
+    let bundle_path = bundle_path(opts)?;
+    let objects = git_dir(&opts.source)?.join("objects").canonicalize()?;
+    let scratch =
+        crate::statedir::ensure(&opts.target, opts.state_dir.as_deref())?.join("backup-scratch");
+    if scratch.exists() {
+        fs::remove_dir_all(&scratch)?;
+    }
+    fs::create_dir_all(&scratch)?;
+    let result = (|| {
+        run_git(&scratch, &["init", "--quiet", "--bare"], None)?;
+        fs::write(
+            scratch.join("objects").join("info").join("alternates"),
+            format!("{}\n", objects.display()),
+        )?;
+        let mut updates = String::new();
+        for (name, oid) in refs_before {
+            updates.push_str(&format!("update {} {}\n", name, oid));
+        }
+        run_git(
+            &scratch,
+            &["update-ref", "--stdin"],
+            Some(updates.as_bytes()),
+        )?;
+        let head = Command::new("git")
+            .arg("-C")
+            .arg(&opts.source)
+            .args(["symbolic-ref", "-q", "HEAD"])
+            .output()?;
+        if head.status.success() {
+            let head = String::from_utf8_lossy(&head.stdout).trim().to_string();
+            run_git(&scratch, &["symbolic-ref", "HEAD", &head], None)?;
+        }
+        bundle_refs(&scratch, &bundle_path, opts)
+    })();
+    let _ = fs::remove_dir_all(&scratch);
+    result.map(|()| Some(bundle_path))
+}
+
+fn run_git(repo: &Path, args: &[&str], stdin: Option<&[u8]>) -> io::Result<()> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .spawn()?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input)?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "'git {}' failed with status {status}",
+            args.join(" ")
+        )));
+    }
+    Ok(())
+}
 
-    let git_dir = git_dir(&opts.source).map_err(|e| {
+fn bundle_path(opts: &Options) -> io::Result<PathBuf> {
+    let _ = git_dir(&opts.source).map_err(|e| {
         io::Error::new(
             io::ErrorKind::Other,
             format!("failed to resolve git dir for {:?}: {e}", opts.source),
@@ -41,12 +119,8 @@ pub fn create_backup(opts: &Options) -> io::Result<Option<PathBuf>> {
 
     let bundle_path = match &opts.backup_path {
         Some(path) => {
-            let resolved = if path.is_absolute() {
-                path.clone()
-            } else {
-                opts.source.join(path)
-            };
-            if resolved.is_dir() || resolved.extension().is_none() {
+            let resolved = resolve_backup_path(opts, path);
+            if names_directory(&resolved) {
                 fs::create_dir_all(&resolved)?;
                 resolved.join(&bundle_name)
             } else {
@@ -59,12 +133,35 @@ pub fn create_backup(opts: &Options) -> io::Result<Option<PathBuf>> {
             }
         }
         None => {
-            let dest = git_dir.join("filter-repo");
-            fs::create_dir_all(&dest)?;
+            let dest = crate::statedir::ensure(&opts.target, opts.state_dir.as_deref())?;
             dest.join(&bundle_name)
         }
     };
+    Ok(bundle_path)
+}
 
+fn resolve_backup_path(opts: &Options, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        opts.target.join(path)
+    }
+}
+
+// A backup path without an extension is a directory for timestamped bundles
+fn names_directory(path: &Path) -> bool {
+    path.is_dir() || path.extension().is_none()
+}
+
+/// Whether the backup bundle's name comes from the clock rather than from
+/// `--backup-path FILE`.
+pub(crate) fn timestamped_name(opts: &Options) -> bool {
+    opts.backup_path
+        .as_deref()
+        .is_none_or(|path| names_directory(&resolve_backup_path(opts, path)))
+}
+
+fn bundle_refs(repo: &Path, bundle_path: &Path, opts: &Options) -> io::Result<()> {
     if opts.refs.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
@@ -74,10 +171,10 @@ pub fn create_backup(opts: &Options) -> io::Result<Option<PathBuf>> {
 
     let status = Command::new("git")
         .arg("-C")
-        .arg(&opts.source)
+        .arg(repo)
         .arg("bundle")
         .arg("create")
-        .arg(&bundle_path)
+        .arg(bundle_path)
         .args(opts.refs.iter())
         .status()
         .map_err(|e| {
@@ -94,5 +191,5 @@ pub fn create_backup(opts: &Options) -> io::Result<Option<PathBuf>> {
         ));
     }
 
-    Ok(Some(bundle_path))
+    Ok(())
 }
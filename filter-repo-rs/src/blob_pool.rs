@@ -0,0 +1,437 @@
+//! `--replace-text` rewriting of blob payloads, optionally spread over a
+//! worker pool (`--jobs`).
+//!
+//! Results come back strictly in submission order so the filtered stream
+//! keeps fast-export's blob order and mark numbering.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::message::blob_regex::RegexReplacer as BlobRegexReplacer;
+use crate::message::MessageReplacer;
+
+/// Longest symlink target worth keeping for restoring links (`PATH_MAX`).
+const MAX_LINK_TARGET: usize = 4096;
+
+/// Upper bound for the default `--jobs` value.
+pub const MAX_DEFAULT_JOBS: usize = 8;
+
+/// Default worker count: logical cores, capped at [`MAX_DEFAULT_JOBS`].
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_DEFAULT_JOBS)
+}
+
+/// The `--replace-text` literal and regex rules applied to blob contents.
+#[derive(Clone, Debug)]
+pub struct BlobRewriter {
+    literal: Option<MessageReplacer>,
+    regex: Option<BlobRegexReplacer>,
+    settings: RewriteSettings,
+}
+
+/// How a [`BlobRewriter`] treats payloads besides applying its rules.
+#[derive(Clone, Debug, Default)]
+pub struct RewriteSettings {
+    /// Collect matched rule names for the sensitive-data report.
+    pub track_rules: bool,
+    /// Shared with the workers so every rewritten blob is counted once.
+    pub usage: Option<Arc<RuleUsage>>,
+    /// Rewrite UTF-16 blobs as text (`--decode-utf16-for-replace`).
+    pub decode_utf16: bool,
+    /// Keep the original content of rewritten blobs that may be symlink
+    /// targets, so links can be restored (no `--rewrite-symlink-targets`).
+    pub keep_link_targets: bool,
+}
+
+/// A rewritten payload and whether any rule changed it.
+#[derive(Debug)]
+pub struct Rewritten {
+    pub data: Vec<u8>,
+    pub changed: bool,
+    pub matched_rules: Option<Vec<String>>,
+    /// The content before rewriting, for changed blobs short enough to be a
+    /// symlink target
+    pub original: Option<Vec<u8>>,
+}
+
+impl BlobRewriter {
+    pub fn new(
+        literal: Option<MessageReplacer>,
+        regex: Option<BlobRegexReplacer>,
+        mut settings: RewriteSettings,
+    ) -> Option<Self> {
+        if literal.is_none() && regex.is_none() {
+            return None;
+        }
+        settings.track_rules |= settings.usage.is_some();
+        Some(Self {
+            literal,
+            regex,
+            settings,
+        })
+    }
+
+    pub fn literal(&self) -> Option<&MessageReplacer> {
+        self.literal.as_ref()
+    }
+
+    pub fn regex(&self) -> Option<&BlobRegexReplacer> {
+        self.regex.as_ref()
+    }
+
+    /// Apply the literal rules, then the regex rules. UTF-16 blobs are
+    /// rewritten as UTF-8 text when `decode_utf16` is set.
+    pub fn rewrite(&self, payload: Vec<u8>) -> Rewritten {
+        let original = (self.settings.keep_link_targets && payload.len() <= MAX_LINK_TARGET)
+            .then(|| payload.clone());
+        let mut rewritten = match self
+            .settings
+            .decode_utf16
+            .then(|| decode_utf16(&payload))
+            .flatten()
+        {
+            Some((bom, text)) => {
+                let mut rewritten = self.rewrite_bytes(text.into_bytes());
+                rewritten.data = if rewritten.changed {
+                    // Replacements may be arbitrary bytes; keep what is text
+                    encode_utf16(bom, &String::from_utf8_lossy(&rewritten.data))
+                } else {
+                    payload
+                };
+                rewritten
+            }
+            None => self.rewrite_bytes(payload),
+        };
+        rewritten.original = original.filter(|_| rewritten.changed);
+        rewritten
+    }
+
+    fn rewrite_bytes(&self, payload: Vec<u8>) -> Rewritten {
+        let matched_rules = self
+            .settings
+            .track_rules
+            .then(|| matched_replace_rules(&payload, self.literal(), self.regex()));
+        if let (Some(usage), Some(rules)) = (&self.settings.usage, &matched_rules) {
+            usage.record(rules);
+        }
+        let mut data = payload;
+        let mut changed = false;
+        if let Some(r) = &self.literal {
+            let tmp = r.apply(data.clone());
+            changed |= tmp != data;
+            data = tmp;
+        }
+        if let Some(rr) = &self.regex {
+            let tmp = rr.apply_regex(data.clone());
+            changed |= tmp != data;
+            data = tmp;
+        }
+        Rewritten {
+            data,
+            changed,
+            matched_rules,
+            original: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Utf16Bom {
+    Le,
+    Be,
+}
+
+// Text of a blob starting with a UTF-16 BOM, or None when it has no BOM or
+// is not valid UTF-16.
+fn decode_utf16(data: &[u8]) -> Option<(Utf16Bom, String)> {
+    let bom = match data.get(..2)? {
+        [0xFF, 0xFE] => Utf16Bom::Le,
+        [0xFE, 0xFF] => Utf16Bom::Be,
+        _ => return None,
+    };
+    let body = &data[2..];
+    if !body.len().is_multiple_of(2) {
+        return None;
+    }
+    let units = body.chunks_exact(2).map(|pair| match bom {
+        Utf16Bom::Le => u16::from_le_bytes([pair[0], pair[1]]),
+        Utf16Bom::Be => u16::from_be_bytes([pair[0], pair[1]]),
+    });
+    let text = char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()?;
+    Some((bom, text))
+}
+
+fn encode_utf16(bom: Utf16Bom, text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(2 + text.len() * 2);
+    for unit in std::iter::once(0xFEFF).chain(text.encode_utf16()) {
+        out.extend_from_slice(&match bom {
+            Utf16Bom::Le => unit.to_le_bytes(),
+            Utf16Bom::Be => unit.to_be_bytes(),
+        });
+    }
+    out
+}
+
+/// Names of the `--replace-text` rules matching `data` (`literal #N`, `regex #N`).
+pub fn matched_replace_rules(
+    data: &[u8],
+    literal: Option<&MessageReplacer>,
+    regex: Option<&BlobRegexReplacer>,
+) -> Vec<String> {
+    let mut rules = Vec::new();
+    if let Some(r) = literal {
+        rules.extend(
+            r.matching_rules(data)
+                .into_iter()
+                .map(|idx| format!("literal #{}", idx + 1)),
+        );
+    }
+    if let Some(rr) = regex {
+        rules.extend(
+            rr.matching_rules(data)
+                .into_iter()
+                .map(|idx| format!("regex #{}", idx + 1)),
+        );
+    }
+    rules
+}
+
+/// How many blobs each `--replace-text` rule matched, for
+/// `--dump-unmatched-replace-rules`.
+#[derive(Debug)]
+pub struct RuleUsage {
+    /// Each rule line with the names it was compiled into; a `regex:` line
+    /// also yields a literal rule, so a line counts as used if any matched.
+    rules: Vec<(Vec<u8>, Vec<String>)>,
+    hits: Mutex<HashMap<String, usize>>,
+}
+
+impl RuleUsage {
+    /// Name the rules of `content` the way [`matched_replace_rules`] does.
+    pub fn new(content: &[u8]) -> Self {
+        let mut rules = Vec::new();
+        let (mut literal, mut regex) = (0, 0);
+        for raw in content.split(|&b| b == b'\n') {
+            if raw.is_empty() || raw.starts_with(b"#") {
+                continue;
+            }
+            let mut names = Vec::new();
+            let from = match raw.windows(3).position(|w| w == b"==>") {
+                Some(pos) => &raw[..pos],
+                None => raw,
+            };
+            if crate::message::decode_search_term(from).is_ok_and(|f| !f.is_empty()) {
+                literal += 1;
+                names.push(format!("literal #{literal}"));
+            }
+            if raw.starts_with(b"regex:") {
+                regex += 1;
+                names.push(format!("regex #{regex}"));
+            }
+            if !names.is_empty() {
+                rules.push((raw.to_vec(), names));
+            }
+        }
+        Self {
+            rules,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count one blob matched by `matched` rules.
+    pub fn record(&self, matched: &[String]) {
+        let mut hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+        for name in matched {
+            *hits.entry(name.clone()).or_default() += 1;
+        }
+    }
+
+    /// Rule lines that matched no blob, in file order.
+    pub fn unused(&self) -> Vec<Vec<u8>> {
+        let hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+        self.rules
+            .iter()
+            .filter(|(_, names)| names.iter().all(|n| !hits.contains_key(n)))
+            .map(|(line, _)| line.clone())
+            .collect()
+    }
+}
+
+/// A blob ready to be written: its buffered header lines, identity and
+/// rewritten payload.
+#[derive(Debug)]
+pub struct ReadyBlob {
+    pub headers: Vec<Vec<u8>>,
+    pub mark: Option<u32>,
+    pub orig_sha: Option<Vec<u8>>,
+    /// Nothing touched the content before rewriting, so the original id
+    /// still names it when the rewrite changes nothing either
+    pub reusable: bool,
+    pub rewritten: Rewritten,
+}
+
+struct PendingBlob {
+    headers: Vec<Vec<u8>>,
+    mark: Option<u32>,
+    orig_sha: Option<Vec<u8>>,
+    reusable: bool,
+    result: Receiver<Rewritten>,
+}
+
+type Job = (Vec<u8>, Sender<Rewritten>);
+
+/// Bounded pool of threads running a [`BlobRewriter`].
+pub struct BlobPool {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    pending: VecDeque<PendingBlob>,
+    max_pending: usize,
+}
+
+impl BlobPool {
+    pub fn new(jobs: usize, rewriter: &BlobRewriter) -> Self {
+        let jobs = jobs.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Job>(jobs * 2);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..jobs)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let rewriter = rewriter.clone();
+                thread::spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(rx) => rx.recv(),
+                        Err(_) => break,
+                    };
+                    let Ok((payload, reply)) = job else {
+                        break;
+                    };
+                    let _ = reply.send(rewriter.rewrite(payload));
+                })
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+            pending: VecDeque::new(),
+            max_pending: jobs * 4,
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Queue a blob for rewriting. Once too many blobs are in flight the
+    /// oldest one is waited for and returned so the caller can write it.
+    pub fn submit(
+        &mut self,
+        headers: Vec<Vec<u8>>,
+        mark: Option<u32>,
+        orig_sha: Option<Vec<u8>>,
+        reusable: bool,
+        payload: Vec<u8>,
+    ) -> io::Result<Option<ReadyBlob>> {
+        let (reply, result) = mpsc::channel();
+        self.sender
+            .as_ref()
+            .ok_or_else(|| io::Error::other("blob worker pool is shut down"))?
+            .send((payload, reply))
+            .map_err(|_| io::Error::other("blob worker pool exited unexpectedly"))?;
+        self.pending.push_back(PendingBlob {
+            headers,
+            mark,
+            orig_sha,
+            reusable,
+            result,
+        });
+        if self.pending.len() > self.max_pending {
+            return self.next_ready();
+        }
+        Ok(None)
+    }
+
+    /// Wait for the oldest queued blob, if any.
+    pub fn next_ready(&mut self) -> io::Result<Option<ReadyBlob>> {
+        let Some(pending) = self.pending.pop_front() else {
+            return Ok(None);
+        };
+        let rewritten = pending
+            .result
+            .recv()
+            .map_err(|_| io::Error::other("blob worker exited before finishing a blob"))?;
+        Ok(Some(ReadyBlob {
+            headers: pending.headers,
+            mark: pending.mark,
+            orig_sha: pending.orig_sha,
+            reusable: pending.reusable,
+            rewritten,
+        }))
+    }
+}
+
+impl Drop for BlobPool {
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_returns_results_in_submission_order() {
+        let literal = MessageReplacer::from_bytes(b"a==>b\n").unwrap();
+        let rewriter = BlobRewriter::new(Some(literal), None, RewriteSettings::default()).unwrap();
+        let mut pool = BlobPool::new(4, &rewriter);
+        let mut ready = Vec::new();
+        for i in 0..50u32 {
+            let payload = format!("blob {i} aaa").into_bytes();
+            if let Some(blob) = pool
+                .submit(Vec::new(), Some(i), None, false, payload)
+                .unwrap()
+            {
+                ready.push(blob);
+            }
+        }
+        while let Some(blob) = pool.next_ready().unwrap() {
+            ready.push(blob);
+        }
+        let marks: Vec<u32> = ready.iter().filter_map(|b| b.mark).collect();
+        assert_eq!(marks, (0..50).collect::<Vec<_>>());
+        assert_eq!(ready[7].rewritten.data, b"blob 7 bbb".to_vec());
+        assert!(ready.iter().all(|b| b.rewritten.changed));
+    }
+
+    #[test]
+    fn utf16_blobs_keep_their_byte_order() {
+        let literal = MessageReplacer::from_bytes(b"secret==>xx\n").unwrap();
+        let rewriter = BlobRewriter::new(
+            Some(literal),
+            None,
+            RewriteSettings {
+                decode_utf16: true,
+                ..RewriteSettings::default()
+            },
+        )
+        .unwrap();
+        let be = encode_utf16(Utf16Bom::Be, "a secret");
+        let out = rewriter.rewrite(be);
+        assert!(out.changed);
+        assert_eq!(out.data, encode_utf16(Utf16Bom::Be, "a xx"));
+        // No BOM: matched as raw bytes, so the UTF-16 text is not found
+        let bare: Vec<u8> = "secret".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert!(!rewriter.rewrite(bare).changed);
+    }
+}
@@ -0,0 +1,201 @@
+//! Blob sizes for the whole object store, read with a single
+//! `git cat-file --batch-all-objects --batch-check` pass.
+//!
+//! Analysis, `--max-blob-size` and `--strip-blobs-with-ids` all ask for blob
+//! sizes; loading them once avoids a batch pass per consumer and a
+//! `cat-file -s` per stripped blob. `--no-size-cache` turns this off.
+
+use std::cell::Cell;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Blob id -> size, stored as raw object ids packed back to back (sorted)
+/// next to a parallel size vector.
+#[derive(Debug, Default)]
+pub struct BlobSizeCache {
+    oid_len: usize,
+    oids: Vec<u8>,
+    sizes: Vec<u64>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl BlobSizeCache {
+    /// Read every object of `repo`, keeping blob sizes.
+    pub fn load(repo: &Path) -> io::Result<Self> {
+        Self::scan(repo, |_, _, _| {})
+    }
+
+    /// Like [`BlobSizeCache::load`], also handing every object line
+    /// (`oid`, `type`, `size`) to `visit` so callers can gather other
+    /// per-object statistics from the same pass.
+    pub fn scan(repo: &Path, mut visit: impl FnMut(&str, &str, u64)) -> io::Result<Self> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .arg("cat-file")
+            .arg("--batch-all-objects")
+            .arg("--batch-check=%(objectname) %(objecttype) %(objectsize)")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::other(format!("failed to run git cat-file batch: {e}")))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("missing stdout from git cat-file batch"))?;
+        let mut cache = BlobSizeCache::default();
+        let mut sorted = true;
+        let mut raw = Vec::with_capacity(32);
+        for line in BufReader::new(stdout).lines() {
+            let line = line?;
+            let mut it = line.split(' ');
+            let (Some(oid), Some(kind), Some(size)) = (it.next(), it.next(), it.next()) else {
+                continue;
+            };
+            let size = size.trim().parse::<u64>().unwrap_or(0);
+            visit(oid, kind, size);
+            if kind != "blob" || !decode_hex(oid.as_bytes(), &mut raw) {
+                continue;
+            }
+            if cache.oid_len == 0 {
+                cache.oid_len = raw.len();
+            } else if raw.len() != cache.oid_len {
+                continue;
+            }
+            if sorted && cache.sizes.last().is_some() {
+                sorted = cache.oid_at(cache.sizes.len() - 1) < raw.as_slice();
+            }
+            cache.oids.extend_from_slice(&raw);
+            cache.sizes.push(size);
+        }
+        let mut stderr_buf = Vec::new();
+        if let Some(mut err) = child.stderr.take() {
+            err.read_to_end(&mut stderr_buf)?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "git cat-file batch failed: {}",
+                String::from_utf8_lossy(&stderr_buf).trim()
+            )));
+        }
+        // git lists objects by id, so this is normally a no-op
+        if !sorted {
+            cache.sort();
+        }
+        Ok(cache)
+    }
+
+    /// Size of the blob with hex id `oid`, if the object store has it.
+    pub fn get(&self, oid: &[u8]) -> Option<u64> {
+        let mut raw = Vec::with_capacity(32);
+        let found = if decode_hex(oid, &mut raw) && raw.len() == self.oid_len {
+            self.search(&raw).map(|idx| self.sizes[idx])
+        } else {
+            None
+        };
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.set(counter.get() + 1);
+        found
+    }
+
+    /// Number of blobs in the cache.
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// Lookups answered so far, as `(hits, misses)`.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits.get(), self.misses.get())
+    }
+
+    fn oid_at(&self, idx: usize) -> &[u8] {
+        &self.oids[idx * self.oid_len..(idx + 1) * self.oid_len]
+    }
+
+    fn search(&self, raw: &[u8]) -> Option<usize> {
+        let (mut lo, mut hi) = (0usize, self.sizes.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.oid_at(mid).cmp(raw) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    fn sort(&mut self) {
+        let mut order: Vec<usize> = (0..self.sizes.len()).collect();
+        order.sort_by(|&a, &b| self.oid_at(a).cmp(self.oid_at(b)));
+        let mut oids = Vec::with_capacity(self.oids.len());
+        let mut sizes = Vec::with_capacity(self.sizes.len());
+        for idx in order {
+            oids.extend_from_slice(self.oid_at(idx));
+            sizes.push(self.sizes[idx]);
+        }
+        self.oids = oids;
+        self.sizes = sizes;
+    }
+}
+
+fn decode_hex(hex: &[u8], out: &mut Vec<u8>) -> bool {
+    out.clear();
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return false;
+    }
+    for pair in hex.chunks_exact(2) {
+        match (hex_val(pair[0]), hex_val(pair[1])) {
+            (Some(hi), Some(lo)) => out.push((hi << 4) | lo),
+            _ => return false,
+        }
+    }
+    true
+}
+
+pub(crate) fn hex_val(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_of(entries: &[(&str, u64)]) -> BlobSizeCache {
+        let mut cache = BlobSizeCache::default();
+        let mut raw = Vec::new();
+        for (oid, size) in entries {
+            assert!(decode_hex(oid.as_bytes(), &mut raw));
+            cache.oid_len = raw.len();
+            cache.oids.extend_from_slice(&raw);
+            cache.sizes.push(*size);
+        }
+        cache.sort();
+        cache
+    }
+
+    #[test]
+    fn lookups_count_hits_and_misses() {
+        let a = "a".repeat(40);
+        let b = "0".repeat(39) + "b";
+        let cache = cache_of(&[(&a, 7), (&b, 42)]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(b.as_bytes()), Some(42));
+        assert_eq!(cache.get(a.to_uppercase().as_bytes()), Some(7));
+        assert_eq!(cache.get("c".repeat(40).as_bytes()), None);
+        assert_eq!(cache.get(b"not-hex"), None);
+        assert_eq!(cache.stats(), (2, 2));
+    }
+}
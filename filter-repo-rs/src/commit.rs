@@ -1,11 +1,21 @@
-use std::collections::{BTreeSet, HashMap};
-use std::io::BufReader;
-use std::io::{self, Read, Write};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
 use std::process::{ChildStdin, ChildStdout};
 
-use crate::filechange;
-use crate::message::{MessageReplacer, ShortHashMapper};
-use crate::opts::Options;
+use regex::bytes::Regex;
+
+use crate::filechange::{
+    self, DuplicatePaths, RenameCollisions, StaleAttributeFiles, StrippedModes, StrippedPaths,
+    WindowsPaths,
+};
+use crate::identity;
+use crate::logging;
+use crate::message::{MessageAnnotator, MessageReplacer, ShortHashMapper, TrailerStripper};
+use crate::opts::{Options, PruneEmpty};
+use crate::sensitive_report::SensitiveDataTracker;
+use crate::signature::SignatureTracker;
+use crate::stream::StreamReader;
 
 pub fn rename_commit_header_ref(
     line: &[u8],
@@ -21,24 +31,17 @@ pub fn rename_commit_header_ref(
             refname = &refname[..refname.len() - 1];
         }
     }
+    // --protect-ref: protected refs keep their names
+    if opts.is_protected_ref(refname) {
+        return line.to_vec();
+    }
     // tags
     if refname.starts_with(b"refs/tags/") {
-        if let Some((ref old, ref new_)) = opts.tag_rename {
-            let name = &refname[b"refs/tags/".len()..];
-            if name.starts_with(&old[..]) {
-                let mut rebuilt = Vec::with_capacity(
-                    7 + b"refs/tags/".len() + new_.len() + (name.len() - old.len()) + 1,
-                );
-                rebuilt.extend_from_slice(b"commit ");
-                rebuilt.extend_from_slice(b"refs/tags/");
-                rebuilt.extend_from_slice(&new_);
-                rebuilt.extend_from_slice(&name[old.len()..]);
-                rebuilt.push(b'\n');
-                let new_full =
-                    [b"refs/tags/".as_ref(), new_.as_slice(), &name[old.len()..]].concat();
-                ref_renames.insert((refname.to_vec(), new_full));
-                return rebuilt;
-            }
+        if let Some(new_) = opts.renamed_tag(&refname[b"refs/tags/".len()..]) {
+            let new_full = [b"refs/tags/".as_ref(), new_.as_slice()].concat();
+            let rebuilt = [b"commit ".as_ref(), new_full.as_slice(), b"\n"].concat();
+            ref_renames.insert((refname.to_vec(), new_full));
+            return rebuilt;
         }
     }
     // branches
@@ -72,6 +75,9 @@ pub enum CommitAction {
 pub struct ParentLine {
     start: usize,
     end: usize,
+    /// `None` for a parent outside the exported range (`from <sha>`, see
+    /// `--reference-excluded-parents`); such lines are kept verbatim and the
+    /// commit is never pruned into them.
     mark: Option<u32>,
     kind: ParentKind,
 }
@@ -93,6 +99,513 @@ pub enum ParentKind {
     Merge,
 }
 
+/// `--strip-commits-by-author`: drops commits whose author email matches
+/// and replays their filechanges at the start of their children's, so the
+/// children keep the trees they had.
+#[derive(Debug, Default)]
+pub struct StrippedCommits {
+    patterns: Vec<Regex>,
+    strip_merges: bool,
+    /// Filechanges a stripped commit (with what it inherited) passes on, by mark
+    carried: HashMap<u32, Vec<u8>>,
+    /// Offset of the current commit's first filechange in the commit buffer
+    changes_start: Option<usize>,
+    author_matched: bool,
+    pub stripped: usize,
+}
+
+impl StrippedCommits {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            patterns: opts.strip_commits_by_author.clone(),
+            strip_merges: opts.strip_merges_too,
+            ..Self::default()
+        }
+    }
+
+    fn record_author(&mut self, line: &[u8]) {
+        if self.patterns.is_empty() {
+            return;
+        }
+        let email = identity_email(line);
+        self.author_matched = self.patterns.iter().any(|re| re.is_match(email));
+    }
+
+    fn record_change(&mut self, start: usize) {
+        self.changes_start.get_or_insert(start);
+    }
+
+    /// Prepend what the first parent carries, then decide whether the
+    /// commit is stripped (remembering its changes for its children).
+    fn finish_commit(
+        &mut self,
+        commit_buf: &mut Vec<u8>,
+        first_parent_mark: Option<u32>,
+        commit_mark: Option<u32>,
+        parent_count: usize,
+        commit_has_changes: &mut bool,
+    ) -> bool {
+        let start = self.changes_start.take().unwrap_or(commit_buf.len());
+        let author_matched = std::mem::take(&mut self.author_matched);
+        if let Some(carried) = first_parent_mark.and_then(|m| self.carried.get(&m)) {
+            if !carried.is_empty() {
+                commit_buf.splice(start..start, carried.iter().copied());
+                *commit_has_changes = true;
+            }
+        }
+        if !author_matched || (parent_count >= 2 && !self.strip_merges) {
+            return false;
+        }
+        if let Some(m) = commit_mark {
+            self.carried.insert(m, commit_buf[start..].to_vec());
+        }
+        self.stripped += 1;
+        true
+    }
+}
+
+/// `--commits-from`/`--commits-to`: only commits whose committer date falls
+/// in the window have their paths, blobs and messages filtered. Commits
+/// outside it replay their original changes on top of whatever their
+/// (possibly rewritten) parents became.
+#[derive(Debug, Default)]
+pub struct DateWindow {
+    from: Option<i64>,
+    to: Option<i64>,
+    /// Whether the current commit falls outside the window
+    outside: bool,
+}
+
+impl DateWindow {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            from: opts.commit_date_from,
+            to: opts.commit_date_to,
+            outside: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    /// Whether the current commit is filtered.
+    pub fn filters(&self) -> bool {
+        !self.outside
+    }
+
+    fn record_committer(&mut self, line: &[u8]) {
+        if !self.is_active() {
+            return;
+        }
+        self.outside = match identity_date(line) {
+            Some(d) => self.from.is_some_and(|f| d < f) || self.to.is_some_and(|t| d > t),
+            None => false,
+        };
+    }
+
+    fn end_commit(&mut self) {
+        self.outside = false;
+    }
+}
+
+/// `--canonicalize-merge-parents`: moves the merge parent that contains
+/// the branch's previous tip to the front, so `--first-parent` follows the
+/// mainline. Filechanges stay relative to the original first parent, so the
+/// reordered merge starts from that parent's tree and keeps its own.
+#[derive(Debug, Default)]
+pub struct MergeParents {
+    enabled: bool,
+    /// Mark of the last commit emitted on each ref
+    tips: HashMap<Vec<u8>, u32>,
+    /// Parent marks of each emitted commit
+    parents: HashMap<u32, Vec<u32>>,
+    pub reordered: usize,
+}
+
+impl MergeParents {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            enabled: opts.canonicalize_merge_parents,
+            ..Self::default()
+        }
+    }
+
+    /// Reorder a kept merge whose mainline is not its first parent. The
+    /// original first parent's tree comes from fast-import, so without one
+    /// (`--dry-run`) merges are left alone.
+    fn reorder(
+        &mut self,
+        commit_buf: &mut Vec<u8>,
+        fi: Option<(&mut ChildStdin, &mut BufReader<ChildStdout>)>,
+    ) -> io::Result<()> {
+        let (true, Some((fi_in, fi_out))) = (self.enabled, fi) else {
+            return Ok(());
+        };
+        let Some((range, marks)) = parent_block(commit_buf) else {
+            return Ok(());
+        };
+        // Parents outside the exported range cannot be walked
+        let Some(marks) = marks.into_iter().collect::<Option<Vec<u32>>>() else {
+            return Ok(());
+        };
+        let Some(&tip) = self.tips.get(commit_ref(commit_buf)) else {
+            return Ok(());
+        };
+        let Some(mainline) = marks.iter().position(|&m| self.reaches(m, tip)) else {
+            return Ok(());
+        };
+        if mainline == 0 {
+            return Ok(());
+        }
+        let Some(tree) = tree_of_mark(fi_in, fi_out, marks[0])? else {
+            return Ok(());
+        };
+        let mut block = format!("from :{}\n", marks[mainline]).into_bytes();
+        for (i, m) in marks.iter().enumerate() {
+            if i != mainline {
+                block.extend_from_slice(format!("merge :{}\n", m).as_bytes());
+            }
+        }
+        block.extend_from_slice(b"M 040000 ");
+        block.extend_from_slice(&tree);
+        block.extend_from_slice(b" \"\"\n");
+        commit_buf.splice(range, block);
+        self.reordered += 1;
+        Ok(())
+    }
+
+    /// Remember an emitted commit as its ref's tip.
+    fn record_commit(&mut self, commit_buf: &[u8], mark: Option<u32>) {
+        let (true, Some(mark)) = (self.enabled, mark) else {
+            return;
+        };
+        let parents = parent_block(commit_buf)
+            .map(|(_, marks)| marks.into_iter().flatten().collect())
+            .unwrap_or_default();
+        self.parents.insert(mark, parents);
+        self.tips.insert(commit_ref(commit_buf).to_vec(), mark);
+    }
+
+    /// A pruned commit's ref now ends at the commit it was aliased to.
+    fn record_alias(&mut self, commit_buf: &[u8], canonical: u32) {
+        if self.enabled {
+            self.tips.insert(commit_ref(commit_buf).to_vec(), canonical);
+        }
+    }
+
+    // Whether `target` is `from` or one of its ancestors. fast-export emits
+    // parents before children, so marks below the target cannot lead to it.
+    fn reaches(&self, from: u32, target: u32) -> bool {
+        let mut stack = vec![from];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(m) = stack.pop() {
+            if m == target {
+                return true;
+            }
+            if m < target || !seen.insert(m) {
+                continue;
+            }
+            if let Some(parents) = self.parents.get(&m) {
+                stack.extend(parents);
+            }
+        }
+        false
+    }
+}
+
+/// `--squash-consecutive-by-author`: folds a run of linear commits by one
+/// author email, each committed within the window of the one before, into
+/// the run's last commit. That commit gets the run's first parent, every
+/// filechange of the run in order and all the messages, followed by the
+/// original ids. The folded commits are still imported but left behind;
+/// before any tag or reset can name them, their marks are aliased to the
+/// squashed commit and refs they were the last commit of are moved to it,
+/// so refs and the commit-map follow it.
+#[derive(Debug, Default)]
+pub struct SquashRuns {
+    window: Option<i64>,
+    /// Author email and committer date of the current commit
+    email: Vec<u8>,
+    date: Option<i64>,
+    /// The run the last kept commit ended, if it can go on
+    run: Option<SquashRun>,
+    /// Squashed commit marks and the commit they now stand for
+    aliases: BTreeMap<u32, u32>,
+    /// Mark of the last kept commit on each ref
+    tips: BTreeMap<Vec<u8>, u32>,
+    pub squashed: usize,
+}
+
+#[derive(Debug)]
+struct SquashRun {
+    tip: u32,
+    email: Vec<u8>,
+    date: i64,
+    /// Parent lines of the run's first commit
+    parents: Vec<u8>,
+    /// Filechanges of every commit in the run
+    changes: Vec<u8>,
+    /// Original id and message of every commit in the run
+    messages: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Marks of the commits folded into the tip
+    folded: Vec<u32>,
+}
+
+impl SquashRuns {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            window: opts
+                .squash_consecutive_by_author
+                .map(|w| i64::try_from(w).unwrap_or(i64::MAX)),
+            ..Self::default()
+        }
+    }
+
+    fn record_author(&mut self, line: &[u8]) {
+        if self.window.is_some() {
+            self.email = identity_email(line).to_vec();
+        }
+    }
+
+    fn record_committer(&mut self, line: &[u8]) {
+        if self.window.is_some() {
+            self.date = identity_date(line);
+        }
+    }
+
+    /// Fold a kept commit into the run its only parent ended, or start a
+    /// new run with it.
+    fn finish_commit(
+        &mut self,
+        commit_buf: &mut Vec<u8>,
+        mark: Option<u32>,
+        original_oid: Option<&[u8]>,
+    ) {
+        let Some(window) = self.window else {
+            return;
+        };
+        let email = std::mem::take(&mut self.email);
+        let run = self.run.take();
+        let (Some(mark), Some(date)) = (mark, self.date.take()) else {
+            return;
+        };
+        self.tips.insert(commit_ref(commit_buf).to_vec(), mark);
+        let (Some((parents, marks)), Some(message)) =
+            (parent_block(commit_buf), message_range(commit_buf))
+        else {
+            return;
+        };
+        if marks.len() > 1 {
+            return;
+        }
+        let msg_start = message.start
+            + commit_buf[message.clone()]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(0, |i| i + 1);
+        let entry = (
+            original_oid.unwrap_or_default().to_vec(),
+            commit_buf[msg_start..message.end].to_vec(),
+        );
+        let changes = &commit_buf[parents.end..];
+        let Some(mut run) = run.filter(|run| {
+            marks.first() == Some(&Some(run.tip))
+                && run.email == email
+                && (date - run.date).abs() <= window
+        }) else {
+            self.run = Some(SquashRun {
+                tip: mark,
+                email,
+                date,
+                parents: commit_buf[parents].to_vec(),
+                changes: changes.to_vec(),
+                messages: vec![entry],
+                folded: Vec::new(),
+            });
+            return;
+        };
+        run.changes.extend_from_slice(changes);
+        run.messages.push(entry);
+        run.folded.push(run.tip);
+        let msg = squashed_message(&run.messages);
+        let mut rebuilt = commit_buf[..message.start].to_vec();
+        rebuilt.extend_from_slice(format!("data {}\n", msg.len()).as_bytes());
+        rebuilt.extend_from_slice(&msg);
+        rebuilt.extend_from_slice(&run.parents);
+        rebuilt.extend_from_slice(&run.changes);
+        *commit_buf = rebuilt;
+        for &m in &run.folded {
+            self.aliases.insert(m, mark);
+        }
+        run.tip = mark;
+        run.date = date;
+        self.run = Some(run);
+        self.squashed += 1;
+    }
+
+    /// Resets and alias stanzas pointing refs and marks of squashed
+    /// commits at their squashed commits, to emit before the stream names
+    /// refs. Ends the current run.
+    pub fn take_aliases(&mut self) -> Vec<u8> {
+        self.run = None;
+        let aliases = std::mem::take(&mut self.aliases);
+        let mut out = Vec::new();
+        for (refname, tip) in self.tips.iter_mut() {
+            if let Some(&to) = aliases.get(tip) {
+                out.extend_from_slice(b"reset ");
+                out.extend_from_slice(refname);
+                out.extend_from_slice(format!("\nfrom :{}\n\n", to).as_bytes());
+                *tip = to;
+            }
+        }
+        for (from, to) in aliases {
+            out.extend_from_slice(&build_alias(from, to));
+        }
+        out
+    }
+}
+
+// The messages of a squashed run, one paragraph each, then the original
+// ids of its commits.
+fn squashed_message(messages: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (_, msg) in messages {
+        let msg = msg.trim_ascii_end();
+        if !msg.is_empty() {
+            if !out.is_empty() {
+                out.extend_from_slice(b"\n\n");
+            }
+            out.extend_from_slice(msg);
+        }
+    }
+    out.extend_from_slice(b"\n\nSquashed commits:\n");
+    for (oid, _) in messages.iter().filter(|(oid, _)| !oid.is_empty()) {
+        out.extend_from_slice(oid);
+        out.push(b'\n');
+    }
+    out
+}
+
+// The email of an `author`/`committer` line.
+fn identity_email(line: &[u8]) -> &[u8] {
+    line.iter()
+        .position(|&b| b == b'<')
+        .and_then(|open| {
+            let rest = &line[open + 1..];
+            rest.iter()
+                .position(|&b| b == b'>')
+                .map(|close| &rest[..close])
+        })
+        .unwrap_or_default()
+}
+
+// The epoch seconds of an `author`/`committer` line (NAME <EMAIL> SECONDS TZ).
+fn identity_date(line: &[u8]) -> Option<i64> {
+    line.iter().rposition(|&b| b == b'>').and_then(|close| {
+        let rest = std::str::from_utf8(&line[close + 1..]).ok()?;
+        rest.split_whitespace().next()?.parse::<i64>().ok()
+    })
+}
+
+// The ref named by a buffered commit's `commit <ref>` header.
+fn commit_ref(commit_buf: &[u8]) -> &[u8] {
+    let line = commit_buf.split(|&b| b == b'\n').next().unwrap_or_default();
+    line.strip_prefix(b"commit ").unwrap_or(line)
+}
+
+// The `from`/`merge` lines following the message in a buffered commit, with
+// the mark each names.
+fn parent_block(commit_buf: &[u8]) -> Option<(Range<usize>, Vec<Option<u32>>)> {
+    let mut pos = message_range(commit_buf)?.end;
+    if commit_buf[pos..].starts_with(b"\n") {
+        pos += 1;
+    }
+    let start = pos;
+    let mut marks = Vec::new();
+    while pos < commit_buf.len() {
+        let end = commit_buf[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(commit_buf.len(), |i| pos + i + 1);
+        let line = &commit_buf[pos..end];
+        if line.starts_with(b"from ") {
+            marks.push(parse_from_mark(line));
+        } else if line.starts_with(b"merge ") {
+            marks.push(parse_merge_mark(line));
+        } else {
+            break;
+        }
+        pos = end;
+    }
+    Some((start..pos, marks))
+}
+
+// Ask fast-import for the root tree of an imported commit.
+fn tree_of_mark(
+    fi_in: &mut ChildStdin,
+    fi_out: &mut BufReader<ChildStdout>,
+    mark: u32,
+) -> io::Result<Option<Vec<u8>>> {
+    fi_in.write_all(format!("ls :{} \"\"\n", mark).as_bytes())?;
+    fi_in.flush()?;
+    let mut line = Vec::with_capacity(64);
+    loop {
+        line.clear();
+        if fi_out.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if let Some(rest) = line.strip_prefix(b"040000 tree ") {
+            let end = rest
+                .iter()
+                .position(|&b| b == b'\t' || b == b'\n')
+                .unwrap_or(rest.len());
+            return Ok(Some(rest[..end].to_vec()));
+        }
+        if line.starts_with(b"missing ") {
+            return Ok(None);
+        }
+    }
+}
+
+/// The per-run filters a commit passes through on its way to fast-import,
+/// with the counters the run report reads from them afterwards.
+pub struct CommitFilters {
+    pub signatures: SignatureTracker,
+    pub windows: WindowsPaths,
+    pub collisions: RenameCollisions,
+    pub duplicates: DuplicatePaths,
+    pub stale_attributes: StaleAttributeFiles,
+    pub stripped_paths: StrippedPaths,
+    pub stripped_modes: StrippedModes,
+    pub stripped: StrippedCommits,
+    pub merges: MergeParents,
+    pub window: DateWindow,
+    pub annotator: MessageAnnotator,
+    pub trailers: TrailerStripper,
+    pub squash: SquashRuns,
+}
+
+impl CommitFilters {
+    pub fn new(opts: &Options) -> io::Result<Self> {
+        Ok(Self {
+            signatures: SignatureTracker::new(opts),
+            windows: WindowsPaths::new(opts),
+            collisions: RenameCollisions::new(opts),
+            duplicates: DuplicatePaths::new(opts),
+            stale_attributes: StaleAttributeFiles::new(opts),
+            stripped_paths: StrippedPaths::new(opts)?,
+            stripped_modes: StrippedModes::new(opts),
+            stripped: StrippedCommits::new(opts),
+            merges: MergeParents::new(opts),
+            window: DateWindow::new(opts),
+            annotator: MessageAnnotator::new(opts),
+            trailers: TrailerStripper::new(opts),
+            squash: SquashRuns::new(opts),
+        })
+    }
+}
+
 #[allow(dead_code)]
 pub fn start_commit(
     line: &[u8],
@@ -120,10 +633,11 @@ pub fn start_commit(
 pub fn process_commit_line(
     line: &[u8],
     opts: &Options,
-    fe_out: &mut BufReader<ChildStdout>,
+    fe_out: &mut StreamReader,
     orig_file: Option<&mut dyn Write>,
     filt_file: &mut dyn Write,
     mut fi_in: Option<&mut ChildStdin>,
+    fi_out: Option<&mut BufReader<ChildStdout>>,
     replacer: &Option<MessageReplacer>,
     short_mapper: Option<&ShortHashMapper>,
     commit_buf: &mut Vec<u8>,
@@ -137,7 +651,25 @@ pub fn process_commit_line(
     parent_lines: &mut Vec<ParentLine>,
     alias_map: &mut HashMap<u32, u32>,
     emitted_marks: &std::collections::HashSet<u32>,
+    keep_if_empty: bool,
+    sensitive: Option<&mut SensitiveDataTracker>,
+    filters: &mut CommitFilters,
 ) -> io::Result<CommitAction> {
+    let CommitFilters {
+        signatures,
+        windows,
+        collisions,
+        duplicates,
+        stale_attributes,
+        stripped_paths,
+        stripped_modes,
+        stripped,
+        merges,
+        window,
+        annotator,
+        trailers,
+        squash,
+    } = filters;
     // mark line
     if let Some(m) = parse_mark_number(line) {
         commit_buf.extend_from_slice(line);
@@ -156,9 +688,42 @@ pub fn process_commit_line(
         commit_buf.extend_from_slice(line);
         return Ok(CommitAction::Consumed);
     }
+    // signature header and its data block
+    if line.starts_with(b"gpgsig ") {
+        signatures.read_commit_signature(
+            line,
+            fe_out,
+            orig_file,
+            commit_buf,
+            commit_original_oid.as_deref(),
+        )?;
+        return Ok(CommitAction::Consumed);
+    }
     // commit message data
     if line.starts_with(b"data ") {
-        handle_commit_data(line, fe_out, orig_file, commit_buf, replacer, short_mapper)?;
+        let filters = window.filters();
+        let mut msg = handle_commit_data(
+            line,
+            fe_out,
+            orig_file,
+            signatures.original_mut(),
+            if filters { replacer } else { &None },
+            short_mapper,
+            sensitive
+                .filter(|_| filters)
+                .map(|t| (t, commit_original_oid.as_deref())),
+        )?;
+        if filters {
+            msg = trailers.strip(&msg);
+        }
+        if let Some(width) = opts.wrap_message_body.filter(|_| filters) {
+            msg = crate::message::wrap_body(&msg, width);
+        }
+        if let Some(limit) = opts.max_commit_message_bytes.filter(|_| filters) {
+            msg = crate::message::truncate_message(&msg, limit);
+        }
+        commit_buf.extend_from_slice(format!("data {}\n", msg.len()).as_bytes());
+        commit_buf.extend_from_slice(&msg);
         return Ok(CommitAction::Consumed);
     }
     // parents
@@ -193,6 +758,21 @@ pub fn process_commit_line(
         *parent_count = parent_lines.len();
         return Ok(CommitAction::Consumed);
     }
+    // author/committer identities
+    if line.starts_with(b"author ") || line.starts_with(b"committer ") {
+        if line.starts_with(b"author ") {
+            stripped.record_author(line);
+            squash.record_author(line);
+        } else {
+            window.record_committer(line);
+            squash.record_committer(line);
+        }
+        match identity::rewrite_line(line, &opts.replace_identity) {
+            Some(rewritten) => commit_buf.extend_from_slice(&rewritten),
+            None => commit_buf.extend_from_slice(line),
+        }
+        return Ok(CommitAction::Consumed);
+    }
     // file changes with path filtering
     if line.starts_with(b"M ")
         || line.starts_with(b"D ")
@@ -200,14 +780,39 @@ pub fn process_commit_line(
         || line.starts_with(b"R ")
         || line == b"deleteall\n"
     {
-        if let Some(newline) = filechange::handle_file_change_line(line, opts) {
+        let kept = if !window.filters() {
+            Some(line.to_vec())
+        } else if stripped_paths.strips(line) || stripped_modes.strips(line) {
+            None
+        } else {
+            filechange::handle_file_change_line(line, opts, windows, collisions)?
+        };
+        stale_attributes.record(line, kept.as_deref());
+        if let Some(newline) = kept {
+            let start = commit_buf.len();
             commit_buf.extend_from_slice(&newline);
+            duplicates.record(start..commit_buf.len(), &newline);
+            stripped.record_change(start);
             *commit_has_changes = true;
         }
         return Ok(CommitAction::Consumed);
     }
     // end of commit (blank line)
     if line == b"\n" {
+        window.end_commit();
+        windows.end_commit();
+        collisions.end_commit();
+        duplicates.finish_commit(commit_buf);
+        let changes_start = parent_block(commit_buf).map_or(commit_buf.len(), |(r, _)| r.end);
+        stale_attributes.finish_commit(commit_buf, changes_start, commit_has_changes);
+        let strip = stripped.finish_commit(
+            commit_buf,
+            *first_parent_mark,
+            *commit_mark,
+            parent_lines.len(),
+            commit_has_changes,
+        );
+        let parent_marks: Vec<u32> = parent_lines.iter().filter_map(|p| p.mark).collect();
         let kept_parents = finalize_parent_lines(
             commit_buf,
             parent_lines,
@@ -216,13 +821,26 @@ pub fn process_commit_line(
             alias_map,
         );
         *parent_count = kept_parents;
-        if should_keep_commit(
-            *commit_has_changes,
-            *first_parent_mark,
-            *commit_mark,
-            *parent_count,
-        ) {
+        if !strip
+            && (should_keep_commit(
+                *commit_has_changes,
+                *first_parent_mark,
+                *commit_mark,
+                *parent_count,
+                keep_if_empty,
+            ) || tombstone_commit(commit_buf, opts))
+        {
             // keep commit
+            squash.finish_commit(commit_buf, *commit_mark, commit_original_oid.as_deref());
+            merges.reorder(commit_buf, fi_in.as_deref_mut().zip(fi_out))?;
+            merges.record_commit(commit_buf, *commit_mark);
+            if annotator.all {
+                annotator.annotate(commit_buf, commit_original_oid.as_deref(), *commit_mark);
+            }
+            let rewritten = signatures.finish_commit(commit_buf, *commit_mark, &parent_marks);
+            if rewritten && !annotator.all {
+                annotator.annotate(commit_buf, commit_original_oid.as_deref(), *commit_mark);
+            }
             commit_buf.extend_from_slice(b"\n");
             filt_file.write_all(&commit_buf)?;
             if let Some(ref mut fi) = fi_in {
@@ -237,11 +855,14 @@ pub fn process_commit_line(
             // Record mark and original id for later resolution via marks file
             if let Some(old) = commit_original_oid.take() {
                 if let Some(m) = *commit_mark {
+                    logging::trace!("kept commit {} as :{}", String::from_utf8_lossy(&old), m);
                     commit_pairs.push((old, Some(m)));
                 }
             }
         } else {
+            signatures.prune_commit(*commit_mark);
             if let Some(old) = commit_original_oid.take() {
+                logging::trace!("pruned commit {}", String::from_utf8_lossy(&old));
                 commit_pairs.push((old, None));
             }
             // prune commit: only alias if we have both marks and parent mark has been emitted
@@ -249,6 +870,7 @@ pub fn process_commit_line(
                 let canonical = resolve_canonical_mark(parent_mark, alias_map);
                 if emitted_marks.contains(&canonical) {
                     alias_map.insert(old_mark, canonical);
+                    merges.record_alias(commit_buf, canonical);
                     let alias = build_alias(old_mark, canonical);
                     filt_file.write_all(&alias)?;
                     if let Some(ref mut fi) = fi_in {
@@ -342,42 +964,47 @@ fn parse_merge_mark(line: &[u8]) -> Option<u32> {
     }
 }
 
+// Names a commit in stream parse errors.
+pub fn commit_context(original_oid: Option<&[u8]>) -> String {
+    match original_oid {
+        Some(oid) => format!("commit {}", String::from_utf8_lossy(oid)),
+        None => "commit".to_string(),
+    }
+}
+
 // Handle a commit message 'data <n>' header line: read payload from fe_out,
-// mirror to orig_file, apply replacer, and append to commit_buf.
+// mirror to orig_file (and `original`), and return it with the replacer applied.
 pub fn handle_commit_data(
     header_line: &[u8],
-    fe_out: &mut BufReader<ChildStdout>,
+    fe_out: &mut StreamReader,
     orig_file: Option<&mut dyn Write>,
-    commit_buf: &mut Vec<u8>,
+    original: Option<&mut Vec<u8>>,
     replacer: &Option<MessageReplacer>,
     short_mapper: Option<&ShortHashMapper>,
-) -> io::Result<()> {
-    if !header_line.starts_with(b"data ") {
-        return Ok(());
-    }
-    let size_bytes = &header_line[b"data ".len()..];
-    let n = std::str::from_utf8(size_bytes)
-        .ok()
-        .map(|s| s.trim())
-        .and_then(|s| s.parse::<usize>().ok())
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid data header"))?;
-    let mut payload = vec![0u8; n];
-    fe_out.read_exact(&mut payload)?;
+    sensitive: Option<(&mut SensitiveDataTracker, Option<&[u8]>)>,
+) -> io::Result<Vec<u8>> {
+    let payload = fe_out.read_data(header_line, || "commit message".to_string())?;
     if let Some(f) = orig_file {
         f.write_all(&payload)?;
     }
-    let mut new_payload = if let Some(r) = replacer {
-        r.apply(payload)
-    } else {
-        payload
+    if let Some(original) = original {
+        original.extend_from_slice(&payload);
+    }
+    let mut new_payload = match (replacer, sensitive) {
+        (Some(r), Some((tracker, commit))) => {
+            let rewritten = r.apply(payload.clone());
+            if rewritten != payload {
+                tracker.record_commit_message(commit);
+            }
+            rewritten
+        }
+        (Some(r), None) => r.apply(payload),
+        (None, _) => payload,
     };
     if let Some(mapper) = short_mapper {
         new_payload = mapper.rewrite(new_payload);
     }
-    let header = format!("data {}\n", new_payload.len());
-    commit_buf.extend_from_slice(header.as_bytes());
-    commit_buf.extend_from_slice(&new_payload);
-    Ok(())
+    Ok(new_payload)
 }
 
 // Should the commit be kept based on observed properties
@@ -386,9 +1013,64 @@ pub fn should_keep_commit(
     first_parent_mark: Option<u32>,
     commit_mark: Option<u32>,
     parent_count: usize,
+    keep_if_empty: bool,
 ) -> bool {
     let is_merge = parent_count >= 2;
-    commit_has_changes || first_parent_mark.is_none() || commit_mark.is_none() || is_merge
+    commit_has_changes
+        || keep_if_empty
+        || first_parent_mark.is_none()
+        || commit_mark.is_none()
+        || is_merge
+}
+
+// --drop-empty-commits-message: keep a commit that would be pruned as empty,
+// with its message replaced by the tombstone.
+fn tombstone_commit(commit_buf: &mut Vec<u8>, opts: &Options) -> bool {
+    let Some(message) = &opts.empty_commit_tombstone else {
+        return false;
+    };
+    let Some(range) = message_range(commit_buf) else {
+        return false;
+    };
+    let mut replacement = format!("data {}\n", message.len()).into_bytes();
+    replacement.extend_from_slice(message);
+    commit_buf.splice(range, replacement);
+    true
+}
+
+// The commit message's `data` header and payload in a buffered commit,
+// stepping over the data block of a kept `gpgsig` header.
+fn message_range(commit_buf: &[u8]) -> Option<Range<usize>> {
+    let mut pos = 0;
+    let mut after_gpgsig = false;
+    while pos < commit_buf.len() {
+        let end = commit_buf[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(commit_buf.len(), |i| pos + i + 1);
+        let line = &commit_buf[pos..end];
+        if let Some(len) = line.strip_prefix(b"data ") {
+            let len: usize = std::str::from_utf8(len).ok()?.trim().parse().ok()?;
+            if !after_gpgsig {
+                return Some(pos..end + len);
+            }
+            after_gpgsig = false;
+            pos = end + len;
+            continue;
+        }
+        after_gpgsig = line.starts_with(b"gpgsig ");
+        pos = end;
+    }
+    None
+}
+
+// Whether an empty commit survives under the given prune policy
+pub fn keep_empty_commit(policy: PruneEmpty, originally_empty: bool) -> bool {
+    match policy {
+        PruneEmpty::Always => false,
+        PruneEmpty::Auto => originally_empty,
+        PruneEmpty::Never => true,
+    }
 }
 
 // Build an alias stanza to map an old mark to its first parent mark
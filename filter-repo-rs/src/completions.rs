@@ -0,0 +1,220 @@
+//! Shell completion scripts for `--completions SHELL`.
+//!
+//! Scripts are rendered from the flag table that also drives the parser and
+//! `--help`, so every accepted flag is completable.
+
+const BIN: &str = "filter-repo-rs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    pub fn parse(name: &str) -> Option<Shell> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" => Some(Shell::Powershell),
+            _ => None,
+        }
+    }
+}
+
+/// One completable flag: its spellings, the value placeholder (if it takes
+/// one), the values to offer for it and the first line of its help text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionFlag {
+    pub names: Vec<String>,
+    pub value: Option<String>,
+    pub choices: Vec<String>,
+    pub description: String,
+}
+
+/// What the shell should offer for a flag's value.
+enum ValueKind {
+    None,
+    Files,
+    Dirs,
+    Choices(Vec<String>),
+    Any,
+}
+
+impl CompletionFlag {
+    fn value_kind(&self) -> ValueKind {
+        match self.value.as_deref() {
+            None => ValueKind::None,
+            Some(_) if !self.choices.is_empty() => ValueKind::Choices(self.choices.clone()),
+            Some("FILE") | Some("PATH") => ValueKind::Files,
+            Some("DIR") => ValueKind::Dirs,
+            Some(_) => ValueKind::Any,
+        }
+    }
+}
+
+pub fn render(shell: Shell, flags: &[CompletionFlag]) -> String {
+    match shell {
+        Shell::Bash => render_bash(flags),
+        Shell::Zsh => render_zsh(flags),
+        Shell::Fish => render_fish(flags),
+        Shell::Powershell => render_powershell(flags),
+    }
+}
+
+fn render_bash(flags: &[CompletionFlag]) -> String {
+    let all: Vec<&str> = flags
+        .iter()
+        .flat_map(|f| f.names.iter().map(String::as_str))
+        .collect();
+    let mut out = String::new();
+    out.push_str(&format!("_{}() {{\n", BIN.replace('-', "_")));
+    out.push_str("    local cur prev\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    out.push_str("    case \"$prev\" in\n");
+    for flag in flags {
+        let action = match flag.value_kind() {
+            ValueKind::None => continue,
+            ValueKind::Files => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            ValueKind::Dirs => "COMPREPLY=($(compgen -d -- \"$cur\"))".to_string(),
+            ValueKind::Choices(c) => {
+                format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", c.join(" "))
+            }
+            ValueKind::Any => "COMPREPLY=()".to_string(),
+        };
+        out.push_str(&format!(
+            "        {})\n            {}\n            return ;;\n",
+            flag.names.join("|"),
+            action
+        ));
+    }
+    out.push_str("    esac\n");
+    out.push_str(&format!(
+        "    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n",
+        all.join(" ")
+    ));
+    out.push_str("}\n");
+    out.push_str(&format!("complete -F _{} {}\n", BIN.replace('-', "_"), BIN));
+    out
+}
+
+fn render_zsh(flags: &[CompletionFlag]) -> String {
+    let mut out = format!("#compdef {}\n\n_arguments \\\n", BIN);
+    for flag in flags {
+        let desc = flag
+            .description
+            .replace('\'', "'\\''")
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        let action = match flag.value_kind() {
+            ValueKind::None => String::new(),
+            ValueKind::Files => format!(":{}:_files", flag.value.as_deref().unwrap_or("")),
+            ValueKind::Dirs => format!(":{}:_files -/", flag.value.as_deref().unwrap_or("")),
+            ValueKind::Choices(c) => format!(":value:({})", c.join(" ")),
+            ValueKind::Any => format!(":{}: ", flag.value.as_deref().unwrap_or("")),
+        };
+        for name in &flag.names {
+            out.push_str(&format!("  '{}[{}]{}' \\\n", name, desc, action));
+        }
+    }
+    out.push_str("  && return 0\n");
+    out
+}
+
+fn render_fish(flags: &[CompletionFlag]) -> String {
+    let mut out = String::new();
+    for flag in flags {
+        let mut line = format!("complete -c {}", BIN);
+        for name in &flag.names {
+            match name.strip_prefix("--") {
+                Some(long) => line.push_str(&format!(" -l {}", long)),
+                None => line.push_str(&format!(" -s {}", name.trim_start_matches('-'))),
+            }
+        }
+        match flag.value_kind() {
+            ValueKind::None => line.push_str(" -f"),
+            ValueKind::Files | ValueKind::Dirs => line.push_str(" -r -F"),
+            ValueKind::Choices(c) => line.push_str(&format!(" -x -a '{}'", c.join(" "))),
+            ValueKind::Any => line.push_str(" -x"),
+        }
+        line.push_str(&format!(" -d '{}'", flag.description.replace('\'', "\\'")));
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn render_powershell(flags: &[CompletionFlag]) -> String {
+    let mut out = format!(
+        "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{\n",
+        BIN
+    );
+    out.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    out.push_str("    @(\n");
+    for flag in flags {
+        for name in &flag.names {
+            out.push_str(&format!(
+                "        @('{}', '{}')\n",
+                name,
+                flag.description.replace('\'', "''")
+            ));
+        }
+    }
+    out.push_str("    ) | Where-Object { $_[0] -like \"$wordToComplete*\" } | ForEach-Object {\n");
+    out.push_str(
+        "        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterName', $_[1])\n",
+    );
+    out.push_str("    }\n}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<CompletionFlag> {
+        vec![
+            CompletionFlag {
+                names: vec!["--replace-text".into()],
+                value: Some("FILE".into()),
+                choices: Vec::new(),
+                description: "Replace text in blobs".into(),
+            },
+            CompletionFlag {
+                names: vec!["--prune-empty".into()],
+                value: Some("MODE".into()),
+                choices: vec!["always".into(), "auto".into(), "never".into()],
+                description: "Empty-commit policy".into(),
+            },
+            CompletionFlag {
+                names: vec!["--force".into(), "-f".into()],
+                value: None,
+                choices: Vec::new(),
+                description: "Skip sanity checks".into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn value_hints_select_shell_actions() {
+        let bash = render(Shell::Bash, &sample());
+        assert!(bash.contains("--replace-text)\n            COMPREPLY=($(compgen -f"));
+        assert!(bash.contains("compgen -W \"always auto never\""));
+        assert!(bash.contains("complete -F _filter_repo_rs filter-repo-rs"));
+
+        let zsh = render(Shell::Zsh, &sample());
+        assert!(zsh.contains("'--prune-empty[Empty-commit policy]:value:(always auto never)'"));
+
+        let fish = render(Shell::Fish, &sample());
+        assert!(
+            fish.contains("complete -c filter-repo-rs -l force -s f -f -d 'Skip sanity checks'")
+        );
+
+        let ps = render(Shell::Powershell, &sample());
+        assert!(ps.contains("@('-f', 'Skip sanity checks')"));
+    }
+}
@@ -1,6 +1,8 @@
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
+use std::process::ExitStatus;
+use std::time::Duration;
 
 use crate::sanity::SanityCheckError;
 
@@ -17,6 +19,46 @@ pub enum FilterRepoError {
     Sanity(SanityCheckError),
     /// Invalid option or configuration supplied by the caller.
     InvalidOptions(String),
+    /// The `git` executable could not be started.
+    GitNotFound(io::Error),
+    /// `git fast-export` exited unsuccessfully.
+    FastExportFailed(ExitStatus),
+    /// `git fast-import` exited unsuccessfully.
+    FastImportFailed(ExitStatus),
+    /// The fast-export | fast-import pipeline ran past `--pipeline-timeout`
+    /// and was killed.
+    PipelineTimeout(Duration),
+    /// The pipeline read nothing from fast-export for `--subprocess-timeout`
+    /// and was killed.
+    PipelineStalled {
+        /// What the pipeline was doing: `stream` while copying the stream,
+        /// then `fast-export` or `fast-import` while waiting for it to exit.
+        phase: String,
+        /// Seconds since the stream last moved.
+        last_progress_secs: u64,
+    },
+    /// Creating the `--backup` bundle failed.
+    BackupFailed(io::Error),
+    /// The fast-export stream could not be parsed: an unsupported command,
+    /// a truncated `data` block or a missing `done`.
+    StreamParse {
+        /// 1-based line of the stream the problem was found on.
+        line_number: usize,
+        /// The offending line (lossily decoded, possibly shortened).
+        snippet: String,
+        /// What was wrong with it.
+        reason: String,
+    },
+    /// `--tag-sign-cmd` failed for some tags, as (tag, exit status) pairs.
+    TagSignFailed(Vec<(String, String)>),
+    /// Path renames sent two paths of one commit to the same destination
+    /// under `--on-rename-collision error`.
+    RenameCollision {
+        /// The shared destination path.
+        destination: String,
+        /// The source paths, in the order the commit lists them.
+        sources: (String, String),
+    },
 }
 
 impl fmt::Display for FilterRepoError {
@@ -25,6 +67,53 @@ impl fmt::Display for FilterRepoError {
             FilterRepoError::Io(err) => write!(f, "{err}"),
             FilterRepoError::Sanity(err) => write!(f, "{err}"),
             FilterRepoError::InvalidOptions(msg) => f.write_str(msg),
+            FilterRepoError::GitNotFound(err) => write!(f, "failed to run git: {err}"),
+            FilterRepoError::FastExportFailed(status) => {
+                write!(f, "git fast-export failed: {status}")
+            }
+            FilterRepoError::FastImportFailed(status) => {
+                write!(f, "git fast-import failed: {status}")
+            }
+            FilterRepoError::PipelineTimeout(timeout) => write!(
+                f,
+                "git fast-export | fast-import did not finish within {timeout:?}; killed it"
+            ),
+            FilterRepoError::PipelineStalled {
+                phase,
+                last_progress_secs,
+            } => write!(
+                f,
+                "pipeline stalled in {phase}: no progress for {last_progress_secs}s \
+                 (--subprocess-timeout); killed git fast-export and fast-import"
+            ),
+            FilterRepoError::BackupFailed(err) => write!(f, "backup failed: {err}"),
+            FilterRepoError::StreamParse {
+                line_number,
+                snippet,
+                reason,
+            } => {
+                write!(f, "fast-export stream line {line_number}: {reason}")?;
+                if !snippet.is_empty() {
+                    write!(f, ": {snippet:?}")?;
+                }
+                Ok(())
+            }
+            FilterRepoError::TagSignFailed(failed) => {
+                write!(f, "--tag-sign-cmd failed for {} tag(s):", failed.len())?;
+                for (tag, status) in failed {
+                    write!(f, "\n  {tag}: {status}")?;
+                }
+                Ok(())
+            }
+            FilterRepoError::RenameCollision {
+                destination,
+                sources,
+            } => write!(
+                f,
+                "'{}' and '{}' both rename to '{destination}' in the same commit; rerun \
+                 with --on-rename-collision keep-first or keep-last",
+                sources.0, sources.1
+            ),
         }
     }
 }
@@ -34,13 +123,29 @@ impl StdError for FilterRepoError {
         match self {
             FilterRepoError::Io(err) => Some(err),
             FilterRepoError::Sanity(err) => err.source(),
-            FilterRepoError::InvalidOptions(_) => None,
+            FilterRepoError::GitNotFound(err) | FilterRepoError::BackupFailed(err) => Some(err),
+            FilterRepoError::InvalidOptions(_)
+            | FilterRepoError::FastExportFailed(_)
+            | FilterRepoError::FastImportFailed(_)
+            | FilterRepoError::PipelineTimeout(_)
+            | FilterRepoError::PipelineStalled { .. }
+            | FilterRepoError::StreamParse { .. }
+            | FilterRepoError::TagSignFailed(_)
+            | FilterRepoError::RenameCollision { .. } => None,
         }
     }
 }
 
 impl From<io::Error> for FilterRepoError {
     fn from(err: io::Error) -> Self {
+        // Undo the io::Error wrapping applied for helpers returning io::Result
+        if err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<FilterRepoError>())
+        {
+            let inner = err.into_inner().expect("checked above");
+            return *inner.downcast::<FilterRepoError>().expect("checked above");
+        }
         FilterRepoError::Io(err)
     }
 }
@@ -65,7 +170,255 @@ impl FilterRepoError {
     pub fn invalid_options(msg: impl Into<String>) -> Self {
         FilterRepoError::InvalidOptions(msg.into())
     }
+
+    /// Classify a failure to spawn a git subprocess.
+    pub fn git_spawn(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::NotFound {
+            FilterRepoError::GitNotFound(err)
+        } else {
+            FilterRepoError::Io(err)
+        }
+    }
+
+    /// Stable identifier for scripts, printed as `error: [CODE] message`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            FilterRepoError::Io(_) => "IO_ERROR",
+            FilterRepoError::Sanity(err) => err.code(),
+            FilterRepoError::InvalidOptions(_) => "INVALID_OPTIONS",
+            FilterRepoError::GitNotFound(_) => "GIT_NOT_FOUND",
+            FilterRepoError::FastExportFailed(_) => "FASTEXPORT_FAILED",
+            FilterRepoError::FastImportFailed(_) => "FASTIMPORT_FAILED",
+            FilterRepoError::PipelineTimeout(_) => "PIPELINE_TIMEOUT",
+            FilterRepoError::PipelineStalled { .. } => "PIPELINE_STALLED",
+            FilterRepoError::BackupFailed(_) => "BACKUP_FAILED",
+            FilterRepoError::StreamParse { .. } => "STREAM_PARSE",
+            FilterRepoError::TagSignFailed(_) => "TAG_SIGN_FAILED",
+            FilterRepoError::RenameCollision { .. } => "RENAME_COLLISION",
+        }
+    }
+
+    /// Process exit status for the CLI: 2 for invalid options, 3 for sanity
+    /// check failures, 4 for git subprocess failures and 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FilterRepoError::InvalidOptions(_) => 2,
+            FilterRepoError::Sanity(SanityCheckError::IoError(_)) => 1,
+            FilterRepoError::Sanity(_) => 3,
+            FilterRepoError::GitNotFound(_)
+            | FilterRepoError::FastExportFailed(_)
+            | FilterRepoError::FastImportFailed(_)
+            | FilterRepoError::PipelineTimeout(_)
+            | FilterRepoError::PipelineStalled { .. } => 4,
+            FilterRepoError::Io(_)
+            | FilterRepoError::BackupFailed(_)
+            | FilterRepoError::StreamParse { .. }
+            | FilterRepoError::TagSignFailed(_)
+            | FilterRepoError::RenameCollision { .. } => 1,
+        }
+    }
 }
 
 /// Convenience result alias using [`FilterRepoError`].
 pub type Result<T> = std::result::Result<T, FilterRepoError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[cfg(unix)]
+    fn failed_status() -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(1 << 8)
+    }
+
+    #[cfg(windows)]
+    fn failed_status() -> ExitStatus {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(1)
+    }
+
+    #[test]
+    fn variants_map_to_codes_and_exit_statuses() {
+        let cases: Vec<(FilterRepoError, &str, i32)> = vec![
+            (io::Error::other("boom").into(), "IO_ERROR", 1),
+            (
+                FilterRepoError::invalid_options("bad"),
+                "INVALID_OPTIONS",
+                2,
+            ),
+            (
+                FilterRepoError::git_spawn(io::Error::from(io::ErrorKind::NotFound)),
+                "GIT_NOT_FOUND",
+                4,
+            ),
+            (
+                FilterRepoError::git_spawn(io::Error::from(io::ErrorKind::PermissionDenied)),
+                "IO_ERROR",
+                1,
+            ),
+            (
+                FilterRepoError::FastExportFailed(failed_status()),
+                "FASTEXPORT_FAILED",
+                4,
+            ),
+            (
+                FilterRepoError::FastImportFailed(failed_status()),
+                "FASTIMPORT_FAILED",
+                4,
+            ),
+            (
+                FilterRepoError::PipelineTimeout(Duration::from_secs(5)),
+                "PIPELINE_TIMEOUT",
+                4,
+            ),
+            (
+                FilterRepoError::PipelineStalled {
+                    phase: "fast-import".into(),
+                    last_progress_secs: 5,
+                },
+                "PIPELINE_STALLED",
+                4,
+            ),
+            (
+                FilterRepoError::BackupFailed(io::Error::other("disk full")),
+                "BACKUP_FAILED",
+                1,
+            ),
+            (
+                FilterRepoError::StreamParse {
+                    line_number: 3,
+                    snippet: "ls \"a\"".into(),
+                    reason: "unsupported command".into(),
+                },
+                "STREAM_PARSE",
+                1,
+            ),
+            (
+                FilterRepoError::TagSignFailed(vec![("v1.0".into(), "exit status: 1".into())]),
+                "TAG_SIGN_FAILED",
+                1,
+            ),
+            (
+                FilterRepoError::RenameCollision {
+                    destination: "c.txt".into(),
+                    sources: ("a.txt".into(), "b.txt".into()),
+                },
+                "RENAME_COLLISION",
+                1,
+            ),
+        ];
+        for (err, code, exit) in cases {
+            assert_eq!(err.code(), code, "{err:?}");
+            assert_eq!(err.exit_code(), exit, "{err:?}");
+        }
+    }
+
+    #[test]
+    fn sanity_variants_map_to_codes() {
+        let cases: Vec<(SanityCheckError, &str)> = vec![
+            (
+                SanityCheckError::GitDirStructure {
+                    expected: ".git".into(),
+                    actual: "x".into(),
+                    is_bare: false,
+                },
+                "SANITY_GIT_DIR",
+            ),
+            (
+                SanityCheckError::ReferenceConflict {
+                    conflict_type: crate::sanity::ConflictType::CaseInsensitive,
+                    conflicts: Vec::new(),
+                },
+                "SANITY_REF_CONFLICT",
+            ),
+            (
+                SanityCheckError::ReflogTooManyEntries {
+                    problematic_reflogs: Vec::new(),
+                },
+                "SANITY_REFLOG",
+            ),
+            (
+                SanityCheckError::UnpushedChanges {
+                    unpushed_branches: Vec::new(),
+                },
+                "SANITY_UNPUSHED",
+            ),
+            (
+                SanityCheckError::NotFreshlyPacked {
+                    packs: 2,
+                    loose_count: 0,
+                    replace_refs_count: 0,
+                },
+                "SANITY_NOT_FRESHLY_PACKED",
+            ),
+            (
+                SanityCheckError::MultipleWorktrees { count: 2 },
+                "SANITY_WORKTREES",
+            ),
+            (SanityCheckError::StashedChanges, "SANITY_STASHED"),
+            (
+                SanityCheckError::WorkingTreeNotClean {
+                    staged_dirty: true,
+                    unstaged_dirty: false,
+                },
+                "SANITY_DIRTY",
+            ),
+            (
+                SanityCheckError::UntrackedFiles { files: Vec::new() },
+                "SANITY_UNTRACKED",
+            ),
+            (
+                SanityCheckError::InvalidRemotes {
+                    remotes: Vec::new(),
+                },
+                "SANITY_REMOTES",
+            ),
+            (
+                SanityCheckError::AlreadyRan {
+                    ran_file: PathBuf::from("already_ran"),
+                    age_hours: 1,
+                    user_confirmed: false,
+                },
+                "SANITY_ALREADY_RAN",
+            ),
+            (
+                SanityCheckError::SensitiveDataIncompatible {
+                    option: "--x".into(),
+                    suggestion: "y".into(),
+                },
+                "SANITY_SENSITIVE_INCOMPATIBLE",
+            ),
+            (
+                SanityCheckError::InPlaceRewriteNotConfirmed {
+                    repo: PathBuf::from("."),
+                },
+                "SANITY_IN_PLACE",
+            ),
+            (
+                SanityCheckError::SourceUnreadable {
+                    source: PathBuf::from("src"),
+                    reason: "x".into(),
+                },
+                "SANITY_SOURCE_UNREADABLE",
+            ),
+            (
+                SanityCheckError::TargetNotEmpty {
+                    target: PathBuf::from("dst"),
+                    reason: "x".into(),
+                },
+                "SANITY_TARGET_NOT_EMPTY",
+            ),
+        ];
+        for (err, code) in cases {
+            let err = FilterRepoError::from(err);
+            assert_eq!(err.code(), code);
+            assert_eq!(err.exit_code(), 3, "{code}");
+        }
+
+        let io_err = FilterRepoError::from(SanityCheckError::IoError(io::Error::other("x")));
+        assert_eq!(io_err.code(), "SANITY_IO");
+        assert_eq!(io_err.exit_code(), 1);
+    }
+}
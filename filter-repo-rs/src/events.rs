@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use crate::opts::Options;
+
+/// Coarse stages of a filter run, reported via [`Event::PhaseStarted`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Sanity checks against the source repository.
+    Preflight,
+    /// Writing the `--backup` bundle.
+    Backup,
+    /// Streaming history through fast-export, the filters and fast-import.
+    Rewrite,
+    /// Updating refs, writing maps/reports and cleaning up.
+    Finalize,
+}
+
+/// Why a blob was dropped from the rewritten history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StripReason {
+    /// Larger than `--max-blob-size`.
+    MaxSize,
+    /// Listed in `--strip-blobs-with-ids`.
+    BlobId,
+}
+
+/// Totals reported with [`Event::Finished`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Commits read from fast-export (kept or pruned).
+    pub commits: usize,
+    /// Blob objects dropped from the stream.
+    pub blobs_stripped: usize,
+    /// Refs created, moved or deleted in the target.
+    pub refs_updated: usize,
+}
+
+/// Structured progress event delivered to an [`EventSink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    PhaseStarted(Phase),
+    /// A commit finished processing; `index` counts from 1 in stream order.
+    CommitProcessed {
+        index: usize,
+        old_oid: String,
+    },
+    BlobStripped {
+        reason: StripReason,
+        size: usize,
+    },
+    /// A ref changed in the target; `None` means absent before or after.
+    RefUpdated {
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    BackupCreated(PathBuf),
+    ReportWritten(PathBuf),
+    /// Informational notice (e.g. remote removal).
+    Notice(String),
+    Warning(String),
+    Finished(Summary),
+}
+
+/// Receiver for [`Event`]s, set through `Options::event_sink`.
+///
+/// Without a sink the run reports through [`ConsoleSink`], which prints the
+/// same messages the CLI always has.
+pub trait EventSink: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+impl std::fmt::Debug for dyn EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventSink(..)")
+    }
+}
+
+/// Default sink: prints backup paths to stdout and notices/warnings to
+/// stderr unless `quiet`; other events are silent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConsoleSink {
+    pub quiet: bool,
+}
+
+impl EventSink for ConsoleSink {
+    fn on_event(&self, event: &Event) {
+        if self.quiet {
+            return;
+        }
+        match event {
+            Event::BackupCreated(path) => {
+                println!("Backup bundle saved to {}", path.display())
+            }
+            Event::Notice(msg) | Event::Warning(msg) => eprintln!("{}", msg),
+            _ => {}
+        }
+    }
+}
+
+/// Deliver `event` to the configured sink, or the console when none is set.
+pub(crate) fn emit(opts: &Options, event: Event) {
+    match &opts.event_sink {
+        Some(sink) => sink.on_event(&event),
+        None => ConsoleSink { quiet: opts.quiet }.on_event(&event),
+    }
+}
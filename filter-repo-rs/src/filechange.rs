@@ -1,9 +1,484 @@
-use crate::opts::Options;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::ops::Range;
+
+use crate::error::FilterRepoError;
+use crate::opts::{CollisionPolicy, Options, PathDecision, WindowsPathPolicy};
 use crate::pathutil::{
     dequote_c_style_bytes, enquote_c_style_bytes, glob_match_bytes, needs_c_style_quote,
-    sanitize_fast_import_path_bytes, sanitize_invalid_windows_path_bytes,
+    sanitize_fast_import_path_bytes, sanitize_invalid_windows_path_bytes, windows_safe_path,
 };
 
+/// Applies `--windows-path-policy` to renamed paths and remembers what it
+/// did for the report.
+#[derive(Debug, Default)]
+pub struct WindowsPaths {
+    policy: Option<WindowsPathPolicy>,
+    /// Output path -> the (renamed) path that produced it, for the current commit
+    commit_outputs: HashMap<Vec<u8>, Vec<u8>>,
+    /// Renamed path -> its Windows-safe name
+    pub sanitized: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Paths dropped under `skip`
+    pub skipped: BTreeSet<Vec<u8>>,
+}
+
+impl WindowsPaths {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            policy: opts.windows_path_policy,
+            ..Self::default()
+        }
+    }
+
+    /// Forget the paths of the commit that just ended.
+    pub fn end_commit(&mut self) {
+        self.commit_outputs.clear();
+    }
+
+    // Windows-safe form of `path`, or None when `skip` drops it. Paths a
+    // commit adds (`adds`) must not collide with another one of its paths.
+    fn apply(&mut self, path: Vec<u8>, adds: bool) -> io::Result<Option<Vec<u8>>> {
+        let safe = match self.policy {
+            None => sanitize_invalid_windows_path_bytes(&path),
+            Some(_) => windows_safe_path(&path),
+        };
+        if safe != path {
+            match self.policy {
+                Some(WindowsPathPolicy::Skip) => {
+                    self.skipped.insert(path);
+                    return Ok(None);
+                }
+                Some(WindowsPathPolicy::Error) => {
+                    return Err(io::Error::other(format!(
+                        "path '{}' cannot be checked out on Windows; rerun with \
+                         --windows-path-policy sanitize or skip",
+                        String::from_utf8_lossy(&path)
+                    )));
+                }
+                _ => {
+                    self.sanitized.insert(path.clone(), safe.clone());
+                }
+            }
+        }
+        if adds && self.policy.is_some() {
+            if let Some(other) = self.commit_outputs.get(&safe).filter(|o| **o != path) {
+                return Err(io::Error::other(format!(
+                    "'{}' and '{}' both become '{}' for Windows in the same commit",
+                    String::from_utf8_lossy(other),
+                    String::from_utf8_lossy(&path),
+                    String::from_utf8_lossy(&safe)
+                )));
+            }
+            self.commit_outputs.insert(safe.clone(), path);
+        }
+        Ok(Some(safe))
+    }
+}
+
+/// Applies `--on-rename-collision` when path renames send two paths of one
+/// commit to the same destination, and remembers what it decided for the
+/// report.
+#[derive(Debug, Default)]
+pub struct RenameCollisions {
+    active: bool,
+    policy: CollisionPolicy,
+    /// Renamed path -> the source path it was added from, for the current commit
+    commit_sources: HashMap<Vec<u8>, Vec<u8>>,
+    /// (destination, kept source, dropped source)
+    pub resolved: BTreeSet<(Vec<u8>, Vec<u8>, Vec<u8>)>,
+}
+
+impl RenameCollisions {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            active: !opts.path_renames.is_empty(),
+            policy: opts.on_rename_collision,
+            ..Self::default()
+        }
+    }
+
+    /// Forget the paths of the commit that just ended.
+    pub fn end_commit(&mut self) {
+        self.commit_sources.clear();
+    }
+
+    // Whether the filechange adding `source` as `renamed` is kept. Under
+    // `keep-last` the later filechange simply overrides the earlier one.
+    fn claim(&mut self, source: &[u8], renamed: &[u8]) -> io::Result<bool> {
+        if !self.active {
+            return Ok(true);
+        }
+        let Some(other) = self
+            .commit_sources
+            .get(renamed)
+            .filter(|o| o.as_slice() != source)
+            .cloned()
+        else {
+            self.commit_sources
+                .insert(renamed.to_vec(), source.to_vec());
+            return Ok(true);
+        };
+        let dest = renamed.to_vec();
+        match self.policy {
+            CollisionPolicy::Error => Err(FilterRepoError::RenameCollision {
+                destination: String::from_utf8_lossy(renamed).into_owned(),
+                sources: (
+                    String::from_utf8_lossy(&other).into_owned(),
+                    String::from_utf8_lossy(source).into_owned(),
+                ),
+            }
+            .into()),
+            CollisionPolicy::KeepFirst => {
+                self.resolved.insert((dest, other, source.to_vec()));
+                Ok(false)
+            }
+            CollisionPolicy::KeepLast => {
+                self.resolved.insert((dest.clone(), source.to_vec(), other));
+                self.commit_sources.insert(dest, source.to_vec());
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// `--delete-duplicate-paths`: within a commit, drops the extra paths that
+/// add a blob another path of the same commit already has.
+#[derive(Debug, Default)]
+pub struct DuplicatePaths {
+    globs: Vec<Vec<u8>>,
+    /// `M` lines of the current commit: range in the commit buffer, blob id, path
+    modifies: Vec<(Range<usize>, Vec<u8>, Vec<u8>)>,
+    /// Number of filechanges dropped
+    pub deleted: usize,
+    /// Paths dropped at least once
+    pub paths: BTreeSet<Vec<u8>>,
+}
+
+impl DuplicatePaths {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            globs: opts.delete_duplicate_paths.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Note a filechange line written to `commit_buf[range]`.
+    pub fn record(&mut self, range: Range<usize>, line: &[u8]) {
+        if self.globs.is_empty() {
+            return;
+        }
+        if let Some(FileChange::Modify { mode, id, path }) = parse_file_change_line(line) {
+            if id != b"inline" && mode != b"160000" {
+                self.modifies.push((range, id, path));
+            }
+        }
+    }
+
+    /// Drop duplicate paths matching the globs from the finished commit.
+    /// Per blob, a path outside the globs is preferred as the kept copy,
+    /// then the first one listed.
+    pub fn finish_commit(&mut self, commit_buf: &mut Vec<u8>) {
+        let modifies = std::mem::take(&mut self.modifies);
+        let mut canonical: HashMap<&[u8], usize> = HashMap::new();
+        for (i, (_, id, path)) in modifies.iter().enumerate() {
+            let current = canonical.entry(id).or_insert(i);
+            if self.matches(&modifies[*current].2) && !self.matches(path) {
+                *current = i;
+            }
+        }
+        let mut drop: Vec<&Range<usize>> = Vec::new();
+        for (i, (range, id, path)) in modifies.iter().enumerate() {
+            if canonical[id.as_slice()] != i && self.matches(path) {
+                drop.push(range);
+                self.deleted += 1;
+                self.paths.insert(path.clone());
+            }
+        }
+        for range in drop.into_iter().rev() {
+            commit_buf.drain(range.clone());
+        }
+    }
+
+    fn matches(&self, path: &[u8]) -> bool {
+        self.globs.iter().any(|g| glob_match_bytes(g, path))
+    }
+}
+
+/// `--prune-stale-attribute-files`: drops a directory's `.gitignore` and
+/// `.gitattributes` from a commit once path filtering removed every other
+/// path the commit touched under that directory. Root ones always stay.
+#[derive(Debug, Default)]
+pub struct StaleAttributeFiles {
+    enabled: bool,
+    /// Directories holding other paths of the current commit, before and
+    /// after filtering
+    touched: BTreeSet<Vec<u8>>,
+    survived: BTreeSet<Vec<u8>>,
+    /// Kept attribute file `M` lines of the current commit, by directory
+    candidates: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Number of attribute files dropped
+    pub pruned: usize,
+}
+
+impl StaleAttributeFiles {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            enabled: opts.prune_stale_attribute_files,
+            ..Self::default()
+        }
+    }
+
+    /// Note an original filechange `line` and what filtering kept of it.
+    pub fn record(&mut self, line: &[u8], kept: Option<&[u8]>) {
+        if !self.enabled {
+            return;
+        }
+        let paths = match parse_file_change_line(line) {
+            Some(FileChange::Modify { id, path, .. }) => {
+                if let Some(dir) = attribute_file_dir(&path) {
+                    if let Some(kept) = kept.filter(|_| id != b"inline" && !dir.is_empty()) {
+                        self.candidates.push((dir.to_vec(), kept.to_vec()));
+                    }
+                    return;
+                }
+                vec![path]
+            }
+            Some(FileChange::Delete { path }) => vec![path],
+            Some(FileChange::Copy { src, dst }) | Some(FileChange::Rename { src, dst }) => {
+                vec![src, dst]
+            }
+            Some(FileChange::DeleteAll) | None => return,
+        };
+        for path in paths {
+            if attribute_file_dir(&path).is_some() {
+                continue;
+            }
+            let mut end = path.len();
+            while let Some(slash) = path[..end].iter().rposition(|&b| b == b'/') {
+                self.touched.insert(path[..slash].to_vec());
+                if kept.is_some() {
+                    self.survived.insert(path[..slash].to_vec());
+                }
+                end = slash;
+            }
+        }
+    }
+
+    /// Drop the stale attribute files from the filechanges at
+    /// `commit_buf[changes_start..]`; clears `commit_has_changes` when none
+    /// are left.
+    pub fn finish_commit(
+        &mut self,
+        commit_buf: &mut Vec<u8>,
+        changes_start: usize,
+        commit_has_changes: &mut bool,
+    ) {
+        let touched = std::mem::take(&mut self.touched);
+        let survived = std::mem::take(&mut self.survived);
+        let stale: Vec<Vec<u8>> = std::mem::take(&mut self.candidates)
+            .into_iter()
+            .filter(|(dir, _)| touched.contains(dir) && !survived.contains(dir))
+            .map(|(_, line)| line)
+            .collect();
+        if stale.is_empty() || changes_start > commit_buf.len() {
+            return;
+        }
+        let changes = commit_buf.split_off(changes_start);
+        for line in changes.split_inclusive(|&b| b == b'\n') {
+            if stale.iter().any(|s| s.as_slice() == line) {
+                self.pruned += 1;
+            } else {
+                commit_buf.extend_from_slice(line);
+            }
+        }
+        if commit_buf.len() == changes_start {
+            *commit_has_changes = false;
+        }
+    }
+}
+
+// Directory of a `.gitignore`/`.gitattributes` path (empty at the root).
+fn attribute_file_dir(path: &[u8]) -> Option<&[u8]> {
+    let (dir, name) = match path.iter().rposition(|&b| b == b'/') {
+        Some(slash) => (&path[..slash], &path[slash + 1..]),
+        None => (&path[..0], path),
+    };
+    (name == b".gitignore" || name == b".gitattributes").then_some(dir)
+}
+
+/// `--strip-paths-from-file`: removes every filechange naming one of the
+/// listed paths, compared byte for byte against the original history.
+#[derive(Debug, Default)]
+pub struct StrippedPaths {
+    /// Listed path -> filechanges removed for it
+    counts: HashMap<Vec<u8>, usize>,
+    /// Number of filechanges removed
+    pub removed: usize,
+}
+
+impl StrippedPaths {
+    pub fn new(opts: &Options) -> io::Result<Self> {
+        let Some(file) = &opts.strip_paths_from_file else {
+            return Ok(Self::default());
+        };
+        let content = std::fs::read(file).map_err(|e| {
+            io::Error::other(format!(
+                "failed to read --strip-paths-from-file {}: {e}",
+                file.display()
+            ))
+        })?;
+        let counts = content
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| match line {
+                [b'"', inner @ .., b'"'] => (dequote_c_style_bytes(inner), 0),
+                _ => (line.to_vec(), 0),
+            })
+            .collect();
+        Ok(Self {
+            counts,
+            ..Self::default()
+        })
+    }
+
+    /// Whether the filechange `line` names a listed path and must go.
+    pub fn strips(&mut self, line: &[u8]) -> bool {
+        if self.counts.is_empty() {
+            return false;
+        }
+        let paths = match parse_file_change_line(line) {
+            Some(FileChange::Modify { path, .. }) | Some(FileChange::Delete { path }) => {
+                vec![path]
+            }
+            Some(FileChange::Copy { src, dst }) | Some(FileChange::Rename { src, dst }) => {
+                vec![src, dst]
+            }
+            Some(FileChange::DeleteAll) | None => return false,
+        };
+        let mut hit = false;
+        for path in paths {
+            if let Some(n) = self.counts.get_mut(&path) {
+                *n += 1;
+                hit = true;
+            }
+        }
+        if hit {
+            self.removed += 1;
+        }
+        hit
+    }
+
+    /// Up to `limit` stripped paths, most removed filechanges first.
+    pub fn top(&self, limit: usize) -> Vec<(Vec<u8>, usize)> {
+        let mut top: Vec<(Vec<u8>, usize)> = self
+            .counts
+            .iter()
+            .filter(|(_, n)| **n > 0)
+            .map(|(p, n)| (p.clone(), *n))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(limit);
+        top
+    }
+}
+
+/// `--strip-file-modes`: removes `M` filechanges adding a file with one of
+/// the listed modes, optionally only under `--strip-file-modes-glob`.
+#[derive(Debug, Default)]
+pub struct StrippedModes {
+    modes: Vec<Vec<u8>>,
+    globs: Vec<Vec<u8>>,
+    /// Number of filechanges removed
+    pub removed: usize,
+    /// Paths removed at least once
+    pub paths: BTreeSet<Vec<u8>>,
+}
+
+impl StrippedModes {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            modes: opts.strip_file_modes.clone(),
+            globs: opts.strip_file_modes_globs.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the filechange `line` adds a file with a stripped mode.
+    pub fn strips(&mut self, line: &[u8]) -> bool {
+        if self.modes.is_empty() {
+            return false;
+        }
+        let Some(FileChange::Modify { mode, path, .. }) = parse_file_change_line(line) else {
+            return false;
+        };
+        if !self.matches(&mode, &path) {
+            return false;
+        }
+        self.removed += 1;
+        self.paths.insert(path);
+        true
+    }
+
+    fn matches(&self, mode: &[u8], path: &[u8]) -> bool {
+        mode_stripped(&self.modes, &self.globs, mode, path)
+    }
+}
+
+fn mode_stripped(modes: &[Vec<u8>], globs: &[Vec<u8>], mode: &[u8], path: &[u8]) -> bool {
+    modes.iter().any(|m| m == mode)
+        && (globs.is_empty() || globs.iter().any(|g| glob_match_bytes(g, path)))
+}
+
+/// Mode of gitlink (submodule) filechanges.
+const GITLINK_MODE: &[u8] = b"160000";
+
+/// Mode of symlink filechanges; their blob holds the link target.
+const SYMLINK_MODE: &[u8] = b"120000";
+
+/// Whether `line` is an `M` filechange adding a symlink.
+pub fn is_symlink_modify(line: &[u8]) -> bool {
+    line.strip_prefix(b"M ")
+        .and_then(|rest| rest.strip_prefix(SYMLINK_MODE))
+        .is_some_and(|rest| rest.starts_with(b" "))
+}
+
+/// `line`, an `M` filechange naming its blob by mark or id, changed to take
+/// its content from a data block following it instead. The mode is kept.
+pub fn inline_modify_line(line: &[u8]) -> Option<Vec<u8>> {
+    let Some(FileChange::Modify { mode, path, .. }) = parse_file_change_line(line) else {
+        return None;
+    };
+    let mut rebuilt = Vec::with_capacity(line.len());
+    rebuilt.extend_from_slice(b"M ");
+    rebuilt.extend_from_slice(&mode);
+    rebuilt.extend_from_slice(b" inline ");
+    rebuilt.extend_from_slice(&encode_path(&path));
+    rebuilt.push(b'\n');
+    Some(rebuilt)
+}
+
+/// `line`, an `M` filechange naming its blob by mark, changed to name the
+/// blob's original id from `ids` instead.
+pub fn modify_with_blob_id(line: &[u8], ids: &HashMap<u32, Vec<u8>>) -> Option<Vec<u8>> {
+    let Some(FileChange::Modify { mode, id, path }) = parse_file_change_line(line) else {
+        return None;
+    };
+    let mark: u32 = std::str::from_utf8(id.strip_prefix(b":")?)
+        .ok()?
+        .parse()
+        .ok()?;
+    let blob = ids.get(&mark)?;
+    let mut rebuilt = Vec::with_capacity(line.len() + blob.len());
+    rebuilt.extend_from_slice(b"M ");
+    rebuilt.extend_from_slice(&mode);
+    rebuilt.push(b' ');
+    rebuilt.extend_from_slice(blob);
+    rebuilt.push(b' ');
+    rebuilt.extend_from_slice(&encode_path(&path));
+    rebuilt.push(b'\n');
+    Some(rebuilt)
+}
+
 #[derive(Debug)]
 enum FileChange {
     DeleteAll,
@@ -161,6 +636,11 @@ fn path_matches(path: &[u8], opts: &Options) -> bool {
 }
 
 fn should_keep(paths: &[&[u8]], opts: &Options) -> bool {
+    if let Some(rules) = &opts.ignore_rules {
+        if paths.iter().any(|p| rules.is_ignored(p)) {
+            return false;
+        }
+    }
     if opts.paths.is_empty() && opts.path_globs.is_empty() && opts.path_regexes.is_empty() {
         return true;
     }
@@ -178,8 +658,99 @@ fn rewrite_path(mut path: Vec<u8>, opts: &Options) -> Vec<u8> {
             }
         }
     }
-    let windows_sanitized = sanitize_invalid_windows_path_bytes(&path);
-    sanitize_fast_import_path_bytes(&windows_sanitized)
+    path
+}
+
+/// Whether `--rewrite-gitmodules` has anything to change: path renames to
+/// apply or gitlinks to strip.
+pub fn rewrites_gitmodules(opts: &Options) -> bool {
+    opts.rewrite_gitmodules
+        && (!opts.path_renames.is_empty()
+            || opts.strip_file_modes.iter().any(|m| m == GITLINK_MODE))
+}
+
+/// `--rewrite-gitmodules`: apply the path renames to the `path =` and
+/// `url =` entries of a `.gitmodules` blob, so moved submodules keep
+/// pointing at their new location. Callers pass only blobs recorded at
+/// `.gitmodules`. Sections whose gitlink `--strip-file-modes 160000`
+/// removes are dropped as well.
+pub fn rewrite_gitmodules(data: Vec<u8>, opts: &Options) -> Vec<u8> {
+    if !rewrites_gitmodules(opts) {
+        return data;
+    }
+    let strips_gitlinks = opts.strip_file_modes.iter().any(|m| m == GITLINK_MODE);
+    let mut out = Vec::with_capacity(data.len() + 32);
+    let mut section = Vec::new();
+    let mut drop_section = false;
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        if line.trim_ascii_start().starts_with(b"[") {
+            if !drop_section {
+                out.append(&mut section);
+            }
+            section.clear();
+            drop_section = false;
+        }
+        if strips_gitlinks {
+            if let Some((key, value)) = gitmodules_entry(line) {
+                drop_section |= key.eq_ignore_ascii_case(b"path")
+                    && mode_stripped(
+                        &opts.strip_file_modes,
+                        &opts.strip_file_modes_globs,
+                        GITLINK_MODE,
+                        &line[value],
+                    );
+            }
+        }
+        match rewrite_gitmodules_entry(line, opts) {
+            Some(new_line) => section.extend_from_slice(&new_line),
+            None => section.extend_from_slice(line),
+        }
+    }
+    if !drop_section {
+        out.append(&mut section);
+    }
+    out
+}
+
+/// Key and (unquoted) value range of a `key = value` line of `.gitmodules`.
+fn gitmodules_entry(line: &[u8]) -> Option<(&[u8], Range<usize>)> {
+    let indent = line.len() - line.trim_ascii_start().len();
+    let eq = indent + line[indent..].iter().position(|&b| b == b'=')?;
+    let key = line[indent..eq].trim_ascii_end();
+    let start = line.len() - line[eq + 1..].trim_ascii_start().len();
+    let end = line[..].trim_ascii_end().len().max(start);
+    let (start, end) = match &line[start..end] {
+        [b'"', .., b'"'] => (start + 1, end - 1),
+        _ => (start, end),
+    };
+    Some((key, start..end))
+}
+
+fn rewrite_gitmodules_entry(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
+    let (key, Range { start, end }) = gitmodules_entry(line)?;
+    let is_path = key.eq_ignore_ascii_case(b"path");
+    if !is_path && !key.eq_ignore_ascii_case(b"url") {
+        return None;
+    }
+    let value = &line[start..end];
+    // Only in-tree urls (`url = libs/foo`) follow the rename; remote and
+    // remote-relative urls never start with a rename prefix.
+    if !is_path
+        && !opts
+            .path_renames
+            .iter()
+            .any(|(old, _)| !old.is_empty() && value.starts_with(old))
+    {
+        return None;
+    }
+    let renamed = rewrite_path(value.to_vec(), opts);
+    if renamed == value {
+        return None;
+    }
+    let mut out = line[..start].to_vec();
+    out.extend_from_slice(&renamed);
+    out.extend_from_slice(&line[end..]);
+    Some(out)
 }
 
 fn encode_path(path: &[u8]) -> Vec<u8> {
@@ -190,11 +761,55 @@ fn encode_path(path: &[u8]) -> Vec<u8> {
     }
 }
 
+// Consult the path callback (if any) for every path of the filechange. Returns None
+// when the callback drops any of them; renamed paths replace the originals.
+fn apply_path_callback(change: FileChange, opts: &Options) -> Option<FileChange> {
+    let cb = match &opts.path_callback {
+        Some(cb) => cb,
+        None => return Some(change),
+    };
+    let decide = |path: Vec<u8>| -> Option<Vec<u8>> {
+        match cb.decide(&path) {
+            PathDecision::Keep => Some(path),
+            PathDecision::Drop => None,
+            PathDecision::Rename(new_path) => Some(new_path),
+        }
+    };
+    Some(match change {
+        FileChange::DeleteAll => FileChange::DeleteAll,
+        FileChange::Modify { mode, id, path } => FileChange::Modify {
+            mode,
+            id,
+            path: decide(path)?,
+        },
+        FileChange::Delete { path } => FileChange::Delete {
+            path: decide(path)?,
+        },
+        FileChange::Copy { src, dst } => FileChange::Copy {
+            src: decide(src)?,
+            dst: decide(dst)?,
+        },
+        FileChange::Rename { src, dst } => FileChange::Rename {
+            src: decide(src)?,
+            dst: decide(dst)?,
+        },
+    })
+}
+
 // Return Some(new_line) if the filechange should be kept (possibly rebuilt), None to drop.
-pub fn handle_file_change_line(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
+pub fn handle_file_change_line(
+    line: &[u8],
+    opts: &Options,
+    windows: &mut WindowsPaths,
+    collisions: &mut RenameCollisions,
+) -> io::Result<Option<Vec<u8>>> {
     let parsed = match parse_file_change_line(line) {
         Some(p) => p,
-        None => return Some(line.to_vec()),
+        None => return Ok(Some(line.to_vec())),
+    };
+    // The callback runs first; declarative filters see its (possibly renamed) paths.
+    let Some(parsed) = apply_path_callback(parsed, opts) else {
+        return Ok(None);
     };
 
     let keep = match &parsed {
@@ -206,13 +821,25 @@ pub fn handle_file_change_line(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
         }
     };
     if !keep {
-        return None;
+        return Ok(None);
     }
 
-    match parsed {
+    // Windows naming is judged on the final, renamed paths
+    let mut finish = |path: Vec<u8>, adds: bool| -> io::Result<Option<Vec<u8>>> {
+        let renamed = rewrite_path(path.clone(), opts);
+        if adds && !collisions.claim(&path, &renamed)? {
+            return Ok(None);
+        }
+        Ok(windows
+            .apply(renamed, adds)?
+            .map(|p| sanitize_fast_import_path_bytes(&p)))
+    };
+    Ok(match parsed {
         FileChange::DeleteAll => Some(line.to_vec()),
         FileChange::Modify { mode, id, path } => {
-            let new_path = rewrite_path(path, opts);
+            let Some(new_path) = finish(path, true)? else {
+                return Ok(None);
+            };
             let mut rebuilt = Vec::with_capacity(line.len() + new_path.len());
             rebuilt.extend_from_slice(b"M ");
             rebuilt.extend_from_slice(&mode);
@@ -225,7 +852,9 @@ pub fn handle_file_change_line(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
             Some(rebuilt)
         }
         FileChange::Delete { path } => {
-            let new_path = rewrite_path(path, opts);
+            let Some(new_path) = finish(path, false)? else {
+                return Ok(None);
+            };
             let mut rebuilt = Vec::with_capacity(2 + new_path.len() + 2);
             rebuilt.extend_from_slice(b"D ");
             let enc = encode_path(&new_path);
@@ -234,8 +863,9 @@ pub fn handle_file_change_line(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
             Some(rebuilt)
         }
         FileChange::Copy { src, dst } => {
-            let new_src = rewrite_path(src, opts);
-            let new_dst = rewrite_path(dst, opts);
+            let (Some(new_src), Some(new_dst)) = (finish(src, false)?, finish(dst, true)?) else {
+                return Ok(None);
+            };
             let mut rebuilt = Vec::with_capacity(line.len() + new_src.len() + new_dst.len());
             rebuilt.extend_from_slice(b"C ");
             let enc_src = encode_path(&new_src);
@@ -247,8 +877,9 @@ pub fn handle_file_change_line(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
             Some(rebuilt)
         }
         FileChange::Rename { src, dst } => {
-            let new_src = rewrite_path(src, opts);
-            let new_dst = rewrite_path(dst, opts);
+            let (Some(new_src), Some(new_dst)) = (finish(src, false)?, finish(dst, true)?) else {
+                return Ok(None);
+            };
             let mut rebuilt = Vec::with_capacity(line.len() + new_src.len() + new_dst.len());
             rebuilt.extend_from_slice(b"R ");
             let enc_src = encode_path(&new_src);
@@ -259,5 +890,5 @@ pub fn handle_file_change_line(line: &[u8], opts: &Options) -> Option<Vec<u8>> {
             rebuilt.push(b'\n');
             Some(rebuilt)
         }
-    }
+    })
 }
@@ -1,13 +1,18 @@
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
 use std::process::{Child, ChildStdin, Command, Stdio};
 
+use crate::error::{FilterRepoError, Result as FilterRepoResult};
+use crate::events::{self, Event};
 use crate::gitutil;
+use crate::logging;
 use crate::migrate;
-use crate::opts::Options;
+use crate::opts::{CollisionPolicy, Options, ReplaceRefsPolicy, SignaturePolicy, TrailerPattern};
+use crate::sensitive_report::SensitiveDataTracker;
 use crate::stream::BlobSizeTracker;
+use crate::watchdog::Watchdog;
 
 #[derive(Debug)]
 pub struct ReportData {
@@ -17,10 +22,105 @@ pub struct ReportData {
     pub samples_size: Vec<Vec<u8>>,     // paths
     pub samples_sha: Vec<Vec<u8>>,      // paths
     pub samples_modified: Vec<Vec<u8>>, // paths
+    pub messages_ref_renamed: usize,
+    pub unusual_refs: Vec<crate::sanity::UnusualRef>,
+    /// (renamed path, Windows-safe name) pairs
+    pub windows_sanitized: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Paths dropped by `--windows-path-policy skip`
+    pub windows_skipped: Vec<Vec<u8>>,
+    /// (destination, kept source, dropped source) for rename collisions
+    /// settled by `--on-rename-collision`
+    pub rename_collisions: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>,
+    /// Blobs over `--max-in-memory-blob-size` passed through without
+    /// content rewriting, and some of their ids
+    pub too_large_for_replace: usize,
+    pub samples_too_large: Vec<Vec<u8>>,
+    /// Filechanges dropped by `--delete-duplicate-paths`, and their paths
+    pub duplicate_paths_deleted: usize,
+    pub duplicate_paths: Vec<Vec<u8>>,
+    /// `.gitignore`/`.gitattributes` filechanges dropped by
+    /// `--prune-stale-attribute-files`
+    pub attribute_files_pruned: usize,
+    /// Filechanges dropped by `--strip-paths-from-file`, and the most
+    /// frequently stripped paths with their counts
+    pub paths_stripped: usize,
+    pub top_stripped_paths: Vec<(Vec<u8>, usize)>,
+    /// Filechanges dropped by `--strip-file-modes`, and some of their paths
+    pub modes_stripped: usize,
+    pub stripped_mode_paths: Vec<Vec<u8>>,
+    /// Commits dropped by `--strip-commits-by-author`
+    pub commits_stripped_by_author: usize,
+    pub merge_parents_reordered: usize,
+    /// Commits folded into a later one by `--squash-consecutive-by-author`
+    pub commits_squashed: usize,
+    /// Commit messages given the `--message-annotate` trailer
+    pub messages_annotated: usize,
+    /// Trailers removed by `--strip-trailer`, by key
+    pub trailers_stripped: BTreeMap<Vec<u8>, usize>,
+    /// `--replace-text` rule lines that matched no blob, with
+    /// `--dump-unmatched-replace-rules`
+    pub unused_replace_rules: Option<Vec<Vec<u8>>>,
+    /// Local refs covered by the sensitive-mode fetch from origin
+    pub fetched_refs: Option<usize>,
+}
+
+const DELETE_ORIGINAL_REFS_HINT: &str =
+    "git for-each-ref --format='delete %(refname)' refs/original/ | git update-ref --stdin";
+
+/// Ref values captured before fast-import starts updating refs.
+#[derive(Debug, Default)]
+pub struct RefSnapshot {
+    /// `--protect-ref` targets that must still match after the run.
+    pub protected: BTreeMap<String, String>,
+    /// With `--preserve-original-refs`, refs whose old value is saved under
+    /// `refs/original/` when the rewrite changes them.
+    pub preserved: BTreeMap<String, String>,
+    /// Every ref, compared after the run to spot a rewrite that changed nothing.
+    pub all: HashMap<String, String>,
+}
+
+impl RefSnapshot {
+    pub fn capture(opts: &Options) -> io::Result<Self> {
+        let mut snapshot = Self {
+            all: gitutil::get_all_refs(&opts.target)?,
+            ..Self::default()
+        };
+        if opts.protect_refs.is_empty() && !opts.preserve_original_refs {
+            return Ok(snapshot);
+        }
+        let refs = &snapshot.all;
+        for (name, oid) in refs {
+            if opts.is_protected_ref(name.as_bytes()) {
+                snapshot.protected.insert(name.clone(), oid.clone());
+            } else if opts.preserve_original_refs && !name.starts_with("refs/original/") {
+                snapshot.preserved.insert(name.clone(), oid.clone());
+            }
+        }
+        if opts.preserve_original_refs && !opts.force {
+            let existing: Vec<String> = snapshot
+                .preserved
+                .keys()
+                .map(|name| format!("refs/original/{}", name))
+                .filter(|saved| refs.contains_key(saved))
+                .collect();
+            if !existing.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "refs/original/ already holds {}; delete them or pass --force to overwrite",
+                        existing.join(", ")
+                    ),
+                ));
+            }
+        }
+        Ok(snapshot)
+    }
 }
 
 // Flush buffered lightweight tag resets to outputs prior to sending 'done'.
+// With --annotate-tags each one is written as a new tag object instead.
 pub fn flush_lightweight_tag_resets(
+    opts: &Options,
     buffered_tag_resets: &mut Vec<(Vec<u8>, Vec<u8>)>,
     annotated_tag_refs: &BTreeSet<Vec<u8>>,
     filt_file: &mut dyn Write,
@@ -30,6 +130,10 @@ pub fn flush_lightweight_tag_resets(
     if buffered_tag_resets.is_empty() {
         return Ok(());
     }
+    let tagger = match opts.annotate_tags {
+        Some(_) => Some(crate::gitutil::committer_ident(&opts.source)?),
+        None => None,
+    };
     let mut emitted: BTreeSet<Vec<u8>> = BTreeSet::new();
     let items = std::mem::take(buffered_tag_resets);
     for (ref_full, from_line) in items.into_iter() {
@@ -39,21 +143,22 @@ pub fn flush_lightweight_tag_resets(
         if emitted.contains(&ref_full) {
             continue;
         }
-        let mut reset_line = Vec::with_capacity(7 + ref_full.len() + 1);
-        reset_line.extend_from_slice(b"reset ");
-        reset_line.extend_from_slice(&ref_full);
-        reset_line.push(b'\n');
-        filt_file.write_all(&reset_line)?;
-        filt_file.write_all(&from_line)?;
-        if let Some(ref mut fi) = fi_in {
-            if let Err(e) = fi.write_all(&reset_line) {
-                if e.kind() == io::ErrorKind::BrokenPipe {
-                    *import_broken = true;
-                } else {
-                    return Err(e);
-                }
+        let out = match (&opts.annotate_tags, &tagger) {
+            (Some(template), Some(tagger)) => {
+                crate::tag::annotated_tag_block(&ref_full, &from_line, template, tagger)
             }
-            if let Err(e) = fi.write_all(&from_line) {
+            _ => {
+                let mut out = Vec::with_capacity(7 + ref_full.len() + 1 + from_line.len());
+                out.extend_from_slice(b"reset ");
+                out.extend_from_slice(&ref_full);
+                out.push(b'\n');
+                out.extend_from_slice(&from_line);
+                out
+            }
+        };
+        filt_file.write_all(&out)?;
+        if let Some(ref mut fi) = fi_in {
+            if let Err(e) = fi.write_all(&out) {
                 if e.kind() == io::ErrorKind::BrokenPipe {
                     *import_broken = true;
                 } else {
@@ -71,6 +176,7 @@ pub fn finalize(
     opts: &Options,
     debug_dir: &Path,
     ref_renames: BTreeSet<(Vec<u8>, Vec<u8>)>,
+    ref_table: Vec<(Vec<u8>, Vec<u8>)>,
     commit_pairs: Vec<(Vec<u8>, Option<u32>)>,
     buffered_tag_resets: Vec<(Vec<u8>, Vec<u8>)>,
     annotated_tag_refs: BTreeSet<Vec<u8>>,
@@ -80,16 +186,20 @@ pub fn finalize(
     mut fi_in: Option<ChildStdin>,
     fe: &mut Child,
     fi: Option<&mut Child>,
+    watchdog: Option<&Watchdog>,
     mut import_broken: bool,
     allow_flush_tag_resets: bool,
     report: Option<ReportData>,
     blob_sizes: &BlobSizeTracker,
-) -> io::Result<()> {
+    sensitive: Option<SensitiveDataTracker>,
+    ref_snapshot: RefSnapshot,
+) -> FilterRepoResult<()> {
     // Emit buffered lightweight tag resets if any remain (ideally flushed before 'done')
     if allow_flush_tag_resets {
         let mut buffered = buffered_tag_resets;
         if !buffered.is_empty() {
             flush_lightweight_tag_resets(
+                opts,
                 &mut buffered,
                 &annotated_tag_refs,
                 filt_file,
@@ -106,23 +216,62 @@ pub fn finalize(
     if import_broken {
         let _ = fe.kill();
     }
+    if let Some(watchdog) = watchdog {
+        watchdog.await_exit("fast-export");
+    }
     let fe_status = fe.wait()?;
     if !fe_status.success() {
-        eprintln!("fast-export failed: {}", fe_status);
-        std::process::exit(fe_status.code().unwrap_or(1));
+        return Err(FilterRepoError::FastExportFailed(fe_status));
     }
     if let Some(child) = fi {
+        if let Some(watchdog) = watchdog {
+            watchdog.await_exit("fast-import");
+        }
         let fi_status = child.wait()?;
         if !fi_status.success() {
-            eprintln!("fast-import failed: {}", fi_status);
-            std::process::exit(fi_status.code().unwrap_or(1));
+            logging::error!(
+                "fast-import failed; the stream it was fed is in {}",
+                debug_dir.join("fast-export.filtered").display()
+            );
+            return Err(FilterRepoError::FastImportFailed(fi_status));
         }
     }
+    // Both processes finished; ref updates below must not be cut short, and
+    // must not happen at all if the watchdog killed either of them meanwhile
+    if let Some(fired) = watchdog.and_then(Watchdog::disarm) {
+        return Err(fired.into());
+    }
 
     // Ensure the filtered stream is flushed before any reads from it (e.g., commit-map fallback)
     let _ = filt_file.flush();
 
-    let refs: Vec<(Vec<u8>, Vec<u8>)> = ref_renames.into_iter().collect();
+    // Only reached past the preflight check with --force (or sanity off)
+    let branch_collisions = resolve_branch_collisions(opts, &ref_snapshot.all, &ref_table)?;
+
+    // --ref-rename-file entries replace any prefix rename of the same ref; the
+    // ref is moved from wherever fast-import left it
+    let mut renamed: BTreeMap<Vec<u8>, Vec<u8>> = ref_renames.into_iter().collect();
+    let mut ref_moves: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> = Vec::new();
+    for (old, new_) in ref_table {
+        if opts.is_protected_ref(&old) {
+            eprintln!(
+                "warning: not renaming protected ref {}",
+                String::from_utf8_lossy(&old)
+            );
+            continue;
+        }
+        let current = renamed.get(&old).cloned().unwrap_or_else(|| old.clone());
+        renamed.insert(old.clone(), new_.clone());
+        // A discarded collision source is only deleted
+        if branch_collisions
+            .iter()
+            .any(|c| c.discarded.iter().any(|d| d.as_bytes() == old.as_slice()))
+        {
+            continue;
+        }
+        ref_moves.push((old, current, new_));
+    }
+    let refs: Vec<(Vec<u8>, Vec<u8>)> = renamed.into_iter().collect();
     if !refs.is_empty() {
         let mut f = File::create(debug_dir.join("ref-map"))?;
         for (old, new_) in &refs {
@@ -161,6 +310,30 @@ pub fn finalize(
         }
         let mut update_payload: Vec<u8> = Vec::new();
         let repo_refs_before = gitutil::get_all_refs(&opts.target)?;
+        for (old, current, new_) in &ref_moves {
+            let current_str = String::from_utf8_lossy(current).to_string();
+            // A pending branch reset for the ref moves with it
+            let oid = match resolved_updates.remove(current) {
+                Some(oid) => String::from_utf8_lossy(&oid).to_string(),
+                None => match repo_refs_before.get(&current_str) {
+                    Some(oid) => oid.clone(),
+                    None => {
+                        eprintln!(
+                            "warning: not renaming {} because it does not exist",
+                            String::from_utf8_lossy(old)
+                        );
+                        continue;
+                    }
+                },
+            };
+            update_payload.extend_from_slice(
+                format!("update {} {}\n", String::from_utf8_lossy(new_), oid).as_bytes(),
+            );
+            // `old` itself is deleted below along with the other renamed refs
+            if current != old && current != new_ && repo_refs_before.contains_key(&current_str) {
+                update_payload.extend_from_slice(format!("delete {}\n", current_str).as_bytes());
+            }
+        }
         for (refname, oid) in &resolved_updates {
             let ref_str = String::from_utf8_lossy(refname);
             let oid_str = String::from_utf8_lossy(oid);
@@ -168,7 +341,7 @@ pub fn finalize(
                 .extend_from_slice(format!("update {} {}\n", ref_str, oid_str).as_bytes());
         }
         for (old, new_) in &refs {
-            if old == new_ {
+            if old == new_ || opts.is_protected_ref(old) {
                 continue;
             }
             let old_ref = String::from_utf8_lossy(old).to_string();
@@ -199,29 +372,19 @@ pub fn finalize(
             }
         }
         if !update_payload.is_empty() {
-            let mut child = Command::new("git")
-                .arg("-C")
-                .arg(&opts.target)
-                .arg("update-ref")
-                .arg("--no-deref")
-                .arg("--stdin")
-                .stdin(Stdio::piped())
-                .spawn()
-                .map_err(|e| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("failed to run git update-ref: {e}"),
-                    )
-                })?;
-            if let Some(mut sin) = child.stdin.take() {
-                sin.write_all(&update_payload)?;
-            }
-            let status = child.wait()?;
-            if !status.success() {
-                eprintln!("warning: git update-ref operations failed: {}", status);
-            }
+            logging::debug!(
+                "applying {} ref update(s)",
+                update_payload.iter().filter(|&&b| b == b'\n').count()
+            );
+            update_refs(opts, &update_payload)?;
         }
+        verify_protected_refs(opts, &ref_snapshot.protected)?;
     }
+    let saved_original_refs = if opts.dry_run {
+        Vec::new()
+    } else {
+        save_original_refs(opts, &ref_snapshot.preserved)?
+    };
 
     // Write commit-map (old -> new) using exported marks. If in-memory pairs empty,
     // fall back to scanning the filtered stream for commit mark/original-oid pairs.
@@ -299,29 +462,136 @@ pub fn finalize(
         }
     }
 
+    // --incremental: commits from earlier runs were not exported again, so
+    // their entries carry over ahead of this run's
+    let prior_commit_map = if opts.incremental {
+        std::fs::read(debug_dir.join("commit-map")).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
     // Always create commit-map (even if empty) for user tooling parity
     {
         let mut f = File::create(debug_dir.join("commit-map"))?;
-        for (old, mark) in pairs {
+        let exported: HashSet<&[u8]> = pairs.iter().map(|(old, _)| old.as_slice()).collect();
+        for line in prior_commit_map
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+        {
+            let old = line.split(|&b| b == b' ').next().unwrap_or_default();
+            if exported.contains(old) {
+                continue;
+            }
+            f.write_all(line)?;
+            f.write_all(b"\n")?;
+        }
+        for (old, mark) in &pairs {
             match mark {
                 Some(m) => {
-                    if let Some(newid) = mark_to_id.get(&m) {
-                        f.write_all(&old)?;
+                    if let Some(newid) = mark_to_id.get(m) {
+                        f.write_all(old)?;
                         f.write_all(b" ")?;
                         f.write_all(newid)?;
                         f.write_all(b"\n")?;
                     }
                 }
                 None => {
-                    f.write_all(&old)?;
+                    f.write_all(old)?;
                     f.write_all(b" 0000000000000000000000000000000000000000\n")?;
                 }
             }
         }
     }
+    crate::maplookup::write_reverse_commit_map(debug_dir)?;
+
+    if !branch_collisions.is_empty() && !opts.dry_run {
+        let mut payload: Vec<u8> = Vec::new();
+        for collision in &branch_collisions {
+            let new_oid = pairs
+                .iter()
+                .find(|(old, _)| old.as_slice() == collision.kept_oid.as_bytes())
+                .and_then(|(_, mark)| mark.as_ref())
+                .and_then(|mark| mark_to_id.get(mark));
+            match new_oid {
+                Some(oid) => payload.extend_from_slice(
+                    format!(
+                        "update {} {}\n",
+                        collision.target,
+                        String::from_utf8_lossy(oid)
+                    )
+                    .as_bytes(),
+                ),
+                None => eprintln!(
+                    "warning: no rewritten commit for {}; leaving {} as imported",
+                    collision.kept, collision.target
+                ),
+            }
+        }
+        if !payload.is_empty() {
+            update_refs(opts, &payload)?;
+        }
+    }
+
+    // A detached HEAD follows its commit before any reset or cleanup
+    let head_remapped = !opts.dry_run && remap_detached_head(opts, &pairs, &mark_to_id)?;
+
+    if opts.write_parent_map && !opts.dry_run {
+        write_parent_map(opts, debug_dir, &pairs, &mark_to_id)?;
+    }
+    if !opts.dry_run {
+        apply_replace_refs_policy(opts, &pairs, &mark_to_id)?;
+    }
+
+    let hash_stability = (opts.verify_hash_stability && !opts.dry_run)
+        .then(|| HashStability::from_commit_map(&pairs, &mark_to_id));
+    let pruned_commits = opts
+        .write_report
+        .then(|| PrunedCommits::from_commit_map(opts, &pairs));
+    // Every commit kept its id and no ref moved: the rewrite was a no-op
+    let history_unchanged = !opts.dry_run
+        && pairs.iter().all(|(old, mark)| {
+            mark.and_then(|m| mark_to_id.get(&m))
+                .is_some_and(|new_| new_ == old)
+        })
+        && gitutil::get_all_refs(&opts.target)? == ref_snapshot.all;
 
-    // Optional reset --hard on target
-    if !opts.dry_run && opts.reset {
+    if !ref_snapshot.protected.is_empty() {
+        let rewritten: HashSet<Vec<u8>> = pairs
+            .iter()
+            .filter(|(old, mark)| match mark {
+                Some(m) => mark_to_id.get(m).is_some_and(|new_| new_ != old),
+                None => true,
+            })
+            .map(|(old, _)| old.clone())
+            .collect();
+        warn_protected_refs_reaching_rewritten(opts, &ref_snapshot.protected, &rewritten);
+    }
+
+    let rewrite_notes = opts.rewrite_notes && !opts.dry_run;
+    let mut rewritten_notes = Vec::new();
+    if sensitive.is_some() || rewrite_notes {
+        let mut commit_map: HashMap<Vec<u8>, Option<Vec<u8>>> = HashMap::new();
+        for (old, mark) in pairs {
+            match mark {
+                Some(m) => {
+                    if let Some(newid) = mark_to_id.get(&m) {
+                        commit_map.insert(old, Some(newid.clone()));
+                    }
+                }
+                None => {
+                    commit_map.insert(old, None);
+                }
+            }
+        }
+        if rewrite_notes {
+            rewritten_notes = crate::notes::rewrite_notes(opts, &commit_map)?;
+        }
+        if let Some(tracker) = sensitive {
+            crate::sensitive_report::write_report(opts, debug_dir, tracker, &commit_map)?;
+        }
+    }
+
+    // Optional reset --hard on target (a bare target has no worktree to reset)
+    if !opts.dry_run && opts.reset && !gitutil::is_bare_repository(&opts.target).unwrap_or(false) {
         let mut reset = Command::new("git");
         reset.arg("-C").arg(&opts.target).arg("reset");
         if opts.quiet {
@@ -335,7 +605,7 @@ pub fn finalize(
     }
 
     // Optional post-import cleanup
-    if !opts.dry_run {
+    if !opts.dry_run && !history_unchanged {
         match opts.cleanup {
             crate::opts::CleanupMode::None => {}
             crate::opts::CleanupMode::Standard => {
@@ -348,11 +618,41 @@ pub fn finalize(
     }
 
     // Optional reporting
-    if opts.write_report {
+    if opts.write_report || opts.report_unused_replace_rules || hash_stability.is_some() {
         // Ensure debug filtered stream is flushed before scanning
         let _ = filt_file.flush();
         let mut f = File::create(debug_dir.join("report.txt"))?;
-        if let Some(r) = report {
+        write_report_header(&mut f, opts)?;
+        if !saved_original_refs.is_empty() {
+            writeln!(
+                f,
+                "Original refs saved under refs/original/: {}",
+                saved_original_refs.len()
+            )?;
+            for r in &saved_original_refs {
+                writeln!(f, "  {}", r)?;
+            }
+            writeln!(f, "Delete them once the rewrite is verified with:")?;
+            writeln!(f, "  {}\n", DELETE_ORIGINAL_REFS_HINT)?;
+        }
+        if !rewritten_notes.is_empty() {
+            writeln!(f, "Notes rewritten:")?;
+            for n in &rewritten_notes {
+                writeln!(
+                    f,
+                    "  {}: {} moved, {} dropped",
+                    n.refname, n.moved, n.dropped
+                )?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(stability) = &hash_stability {
+            stability.write_section(&mut f)?;
+        }
+        if let Some(pruned) = &pruned_commits {
+            pruned.write_section(&mut f)?;
+        }
+        if let Some(mut r) = report {
             // Augment sampling: when max-blob-size is set, scan streams for dropped paths and oversize refs
             let mut size_samples = r.samples_size;
             if opts.max_blob_size.is_some() {
@@ -530,10 +830,147 @@ pub fn finalize(
                     }
                 }
             }
+            if let Some(n) = r.fetched_refs {
+                writeln!(f, "Refs fetched from origin: {}", n)?;
+            }
+            if opts.deterministic {
+                size_samples.sort();
+                r.samples_sha.sort();
+                r.samples_modified.sort();
+                r.samples_too_large.sort();
+            }
             let size_count = std::cmp::max(r.stripped_by_size, size_samples.len());
             writeln!(f, "Blobs stripped by size: {}", size_count)?;
             writeln!(f, "Blobs stripped by SHA: {}", r.stripped_by_sha)?;
             writeln!(f, "Blobs modified by replace-text: {}", r.modified_blobs)?;
+            if r.too_large_for_replace > 0 {
+                writeln!(
+                    f,
+                    "Blob too large for replace-text (passed through): {}",
+                    r.too_large_for_replace
+                )?;
+                for id in &r.samples_too_large {
+                    writeln!(f, "  {}", String::from_utf8_lossy(id))?;
+                }
+            }
+            if let Some(unused) = &r.unused_replace_rules {
+                writeln!(f, "Replace-text rules that never matched: {}", unused.len())?;
+                for rule in unused {
+                    writeln!(f, "  {}", String::from_utf8_lossy(rule))?;
+                }
+            }
+            if opts.update_message_refs {
+                writeln!(
+                    f,
+                    "Messages updated for renamed refs: {}",
+                    r.messages_ref_renamed
+                )?;
+            }
+            if !r.unusual_refs.is_empty() {
+                writeln!(f, "Refs not pointing at commits: {}", r.unusual_refs.len())?;
+                for u in &r.unusual_refs {
+                    writeln!(f, "  {}", u)?;
+                }
+            }
+            if !r.windows_sanitized.is_empty() {
+                writeln!(
+                    f,
+                    "Paths sanitized for Windows: {}",
+                    r.windows_sanitized.len()
+                )?;
+                for (from, to) in &r.windows_sanitized {
+                    writeln!(
+                        f,
+                        "  {} -> {}",
+                        String::from_utf8_lossy(from),
+                        String::from_utf8_lossy(to)
+                    )?;
+                }
+            }
+            if !r.rename_collisions.is_empty() {
+                writeln!(
+                    f,
+                    "Rename collisions resolved ({:?}): {}",
+                    opts.on_rename_collision,
+                    r.rename_collisions.len()
+                )?;
+                for (dest, kept, dropped) in &r.rename_collisions {
+                    writeln!(
+                        f,
+                        "  {}: kept {}, dropped {}",
+                        String::from_utf8_lossy(dest),
+                        String::from_utf8_lossy(kept),
+                        String::from_utf8_lossy(dropped)
+                    )?;
+                }
+            }
+            if !opts.delete_duplicate_paths.is_empty() {
+                writeln!(
+                    f,
+                    "Duplicate paths deleted: {} ({} paths)",
+                    r.duplicate_paths_deleted,
+                    r.duplicate_paths.len()
+                )?;
+                for p in &r.duplicate_paths {
+                    writeln!(f, "  {}", String::from_utf8_lossy(p))?;
+                }
+            }
+            if opts.prune_stale_attribute_files {
+                writeln!(f, "Attribute files pruned: {}", r.attribute_files_pruned)?;
+            }
+            if opts.strip_paths_from_file.is_some() {
+                writeln!(
+                    f,
+                    "Filechanges removed by --strip-paths-from-file: {}",
+                    r.paths_stripped
+                )?;
+                for (p, n) in &r.top_stripped_paths {
+                    writeln!(f, "  {} {}", n, String::from_utf8_lossy(p))?;
+                }
+            }
+            if !opts.strip_file_modes.is_empty() {
+                writeln!(f, "Filechanges stripped by mode: {}", r.modes_stripped)?;
+                for p in &r.stripped_mode_paths {
+                    writeln!(f, "  {}", String::from_utf8_lossy(p))?;
+                }
+            }
+            if !opts.strip_commits_by_author.is_empty() {
+                writeln!(
+                    f,
+                    "Commits stripped by author: {}",
+                    r.commits_stripped_by_author
+                )?;
+            }
+            if opts.canonicalize_merge_parents {
+                writeln!(
+                    f,
+                    "Merges with parents reordered: {}",
+                    r.merge_parents_reordered
+                )?;
+            }
+            if opts.squash_consecutive_by_author.is_some() {
+                writeln!(f, "Commits squashed: {}", r.commits_squashed)?;
+            }
+            if opts.message_annotate.is_some() {
+                writeln!(f, "Commit messages annotated: {}", r.messages_annotated)?;
+            }
+            if !opts.strip_trailers.is_empty() {
+                let total: usize = r.trailers_stripped.values().sum();
+                writeln!(f, "Trailers stripped: {}", total)?;
+                for (key, count) in &r.trailers_stripped {
+                    writeln!(f, "  {} {}", count, String::from_utf8_lossy(key))?;
+                }
+            }
+            if !r.windows_skipped.is_empty() {
+                writeln!(
+                    f,
+                    "Paths skipped as invalid on Windows: {}",
+                    r.windows_skipped.len()
+                )?;
+                for p in &r.windows_skipped {
+                    writeln!(f, "  {}", String::from_utf8_lossy(p))?;
+                }
+            }
             if !size_samples.is_empty() {
                 writeln!(f, "\nSample paths (size):")?;
                 for p in size_samples {
@@ -558,10 +995,12 @@ pub fn finalize(
         } else {
             writeln!(f, "No report data collected.")?;
         }
+        events::emit(opts, Event::ReportWritten(debug_dir.join("report.txt")));
     }
 
-    // Finalize HEAD: if HEAD points to a non-existent branch, try to remap;
-    // if detached or missing, prefer first updated branch or first existing branch.
+    // Finalize HEAD: if HEAD points to a non-existent branch, follow its rename,
+    // else fall back to the default branch; if detached on a commit the run did
+    // not map, prefer first updated branch.
     // Get HEAD symbolic ref (if any)
     let head_ref = Command::new("git")
         .arg("-C")
@@ -577,41 +1016,35 @@ pub fn finalize(
         if head_ref.status.success() {
             let head = String::from_utf8_lossy(&head_ref.stdout).trim().to_string();
             if !repo_refs_after.contains_key(&head) {
-                let mut updated_head: Option<String> = None;
-                if let Some((ref old, ref new_)) = opts.branch_rename {
-                    if let Some(tail) = head.strip_prefix("refs/heads/") {
-                        let tail_b = tail.as_bytes();
-                        if tail_b.starts_with(&old[..]) {
-                            let mut new_full = Vec::with_capacity(
-                                "refs/heads/".len()
-                                    + new_.len()
-                                    + (tail_b.len().saturating_sub(old.len())),
+                let mut updated_head: Option<String> = ref_moves
+                    .iter()
+                    .find(|(old, _, _)| old.as_slice() == head.as_bytes())
+                    .map(|(_, _, new_)| String::from_utf8_lossy(new_).to_string())
+                    .filter(|new_| repo_refs_after.contains_key(new_));
+                if updated_head.is_none() {
+                    updated_head = head
+                        .strip_prefix("refs/heads/")
+                        .and_then(|tail| opts.renamed_branch(tail.as_bytes()))
+                        .map(|tail| format!("refs/heads/{}", String::from_utf8_lossy(&tail)))
+                        .filter(|new_| repo_refs_after.contains_key(new_));
+                }
+                let fallback = match updated_head {
+                    Some(renamed) => Some(renamed),
+                    None => {
+                        let fallback =
+                            fallback_head_branch(opts, &repo_refs_after, &updated_branch_refs);
+                        if let Some(branch) = &fallback {
+                            events::emit(
+                                opts,
+                                Event::Notice(format!(
+                                    "HEAD pointed at {}, which no longer exists; HEAD now points at {}",
+                                    head, branch
+                                )),
                             );
-                            new_full.extend_from_slice(b"refs/heads/");
-                            new_full.extend_from_slice(&new_);
-                            new_full.extend_from_slice(&tail_b[old.len()..]);
-                            let new_str = String::from_utf8_lossy(&new_full).to_string();
-                            if repo_refs_after.contains_key(&new_str) {
-                                updated_head = Some(new_str);
-                            }
                         }
+                        fallback
                     }
-                }
-                let fallback = updated_head
-                    .or_else(|| {
-                        updated_branch_refs
-                            .iter()
-                            .next()
-                            .map(|b| String::from_utf8_lossy(b).to_string())
-                    })
-                    .or_else(|| {
-                        let mut branches: Vec<&String> = repo_refs_after
-                            .keys()
-                            .filter(|name| name.starts_with("refs/heads/"))
-                            .collect();
-                        branches.sort();
-                        branches.into_iter().next().map(|name| name.clone())
-                    });
+                };
                 if let Some(refstr) = fallback.filter(|s| !s.is_empty()) {
                     let status = Command::new("git")
                         .arg("-C")
@@ -625,7 +1058,7 @@ pub fn finalize(
                     }
                 }
             }
-        } else if let Some(first) = updated_branch_refs.iter().next() {
+        } else if let Some(first) = updated_branch_refs.iter().next().filter(|_| !head_remapped) {
             let refstr = String::from_utf8_lossy(first).to_string();
             let status = Command::new("git")
                 .arg("-C")
@@ -640,14 +1073,834 @@ pub fn finalize(
         }
     }
 
-    if !opts.quiet {
-        eprintln!(
+    crate::tag::run_tag_sign_cmd(opts, &annotated_tag_refs, &ref_snapshot.all)?;
+
+    events::emit(
+        opts,
+        Event::Notice(format!(
             "New history written (prototype Rust pipeline). Debug files in {:?}",
             debug_dir
+        )),
+    );
+    if let Some(stability) = &hash_stability {
+        events::emit(opts, Event::Notice(stability.summary()));
+    }
+    if !saved_original_refs.is_empty() {
+        events::emit(
+            opts,
+            Event::Notice(format!(
+                "Saved {} original ref(s) under refs/original/; remove them with: {}",
+                saved_original_refs.len(),
+                DELETE_ORIGINAL_REFS_HINT
+            )),
         );
     }
+    if history_unchanged {
+        events::emit(
+            opts,
+            Event::Notice(
+                "History unchanged: every commit kept its id; skipped remote removal and cleanup"
+                    .to_string(),
+            ),
+        );
+        return Ok(());
+    }
     // Post-run remote cleanup (non-sensitive parity): remove origin
-    migrate::remove_origin_remote_if_applicable(opts);
+    migrate::remove_remotes_if_applicable(opts);
+    Ok(())
+}
+
+/// Commits the run pruned (null in the commit-map), for `--write-report`.
+struct PrunedCommits {
+    oids: Vec<String>,
+    /// Subjects of the sampled commits, read from the source
+    subjects: HashMap<String, String>,
+}
+
+impl PrunedCommits {
+    const SAMPLE_LIMIT: usize = 20;
+
+    // Run before cleanup, which may drop the pruned commits from the object
+    // store along with their subjects.
+    fn from_commit_map(opts: &Options, pairs: &[(Vec<u8>, Option<u32>)]) -> Self {
+        let oids: Vec<String> = pairs
+            .iter()
+            .filter(|(_, mark)| mark.is_none())
+            .map(|(old, _)| String::from_utf8_lossy(old).into_owned())
+            .collect();
+        let sample: Vec<&str> = oids
+            .iter()
+            .take(Self::SAMPLE_LIMIT)
+            .map(String::as_str)
+            .collect();
+        let subjects = if sample.is_empty() {
+            HashMap::new()
+        } else {
+            gitutil::commit_subjects(&opts.source, &sample).unwrap_or_else(|e| {
+                logging::debug!("could not read pruned commit subjects: {e}");
+                HashMap::new()
+            })
+        };
+        PrunedCommits { oids, subjects }
+    }
+
+    fn write_section(&self, f: &mut dyn Write) -> io::Result<()> {
+        writeln!(f, "Commits pruned: {}", self.oids.len())?;
+        for oid in self.oids.iter().take(Self::SAMPLE_LIMIT) {
+            match self.subjects.get(oid) {
+                Some(subject) => writeln!(f, "  {oid} {subject}")?,
+                None => writeln!(f, "  {oid}")?,
+            }
+        }
+        if self.oids.len() > Self::SAMPLE_LIMIT {
+            writeln!(
+                f,
+                "  ... and {} more (see commit-map)",
+                self.oids.len() - Self::SAMPLE_LIMIT
+            )?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Commit-map comparison for `--verify-hash-stability`.
+struct HashStability {
+    unchanged: usize,
+    changed: Vec<(Vec<u8>, Vec<u8>)>,
+    pruned: Vec<Vec<u8>>,
+}
+
+impl HashStability {
+    const SAMPLE_LIMIT: usize = 20;
+
+    fn from_commit_map(
+        pairs: &[(Vec<u8>, Option<u32>)],
+        mark_to_id: &HashMap<u32, Vec<u8>>,
+    ) -> Self {
+        let mut stability = HashStability {
+            unchanged: 0,
+            changed: Vec::new(),
+            pruned: Vec::new(),
+        };
+        for (old, mark) in pairs {
+            match mark.and_then(|m| mark_to_id.get(&m)) {
+                Some(new_) if new_ == old => stability.unchanged += 1,
+                Some(new_) => stability.changed.push((old.clone(), new_.clone())),
+                None if mark.is_none() => stability.pruned.push(old.clone()),
+                None => {}
+            }
+        }
+        stability
+    }
+
+    fn summary(&self) -> String {
+        let total = self.unchanged + self.changed.len() + self.pruned.len();
+        if self.changed.is_empty() && self.pruned.is_empty() {
+            format!("Hash stability: all {} commit id(s) unchanged", total)
+        } else {
+            format!(
+                "Hash stability: {} of {} commit id(s) changed, {} pruned; see report.txt",
+                self.changed.len(),
+                total,
+                self.pruned.len()
+            )
+        }
+    }
+
+    fn write_section(&self, f: &mut dyn Write) -> io::Result<()> {
+        writeln!(f, "Hash stability:")?;
+        writeln!(f, "  unchanged commits: {}", self.unchanged)?;
+        writeln!(f, "  changed commits: {}", self.changed.len())?;
+        writeln!(f, "  pruned commits: {}", self.pruned.len())?;
+        for (old, new_) in self.changed.iter().take(Self::SAMPLE_LIMIT) {
+            writeln!(
+                f,
+                "  changed {} -> {}",
+                String::from_utf8_lossy(old),
+                String::from_utf8_lossy(new_)
+            )?;
+        }
+        for old in self.pruned.iter().take(Self::SAMPLE_LIMIT) {
+            writeln!(f, "  pruned {}", String::from_utf8_lossy(old))?;
+        }
+        let listed =
+            self.changed.len().min(Self::SAMPLE_LIMIT) + self.pruned.len().min(Self::SAMPLE_LIMIT);
+        let total = self.changed.len() + self.pruned.len();
+        if total > listed {
+            writeln!(f, "  ... and {} more (see commit-map)", total - listed)?;
+        }
+        writeln!(f)
+    }
+}
+
+// Point a detached HEAD at the rewritten id of its commit, as recorded in
+// the commit-map. fast-export lists a detached HEAD with `--all`, so the
+// import may have moved it already. Returns whether HEAD is detached on a
+// kept commit.
+fn remap_detached_head(
+    opts: &Options,
+    pairs: &[(Vec<u8>, Option<u32>)],
+    mark_to_id: &HashMap<u32, Vec<u8>>,
+) -> io::Result<bool> {
+    let symbolic = Command::new("git")
+        .arg("-C")
+        .arg(&opts.target)
+        .args(["symbolic-ref", "-q", "HEAD"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if symbolic.success() {
+        return Ok(false);
+    }
+    let head = Command::new("git")
+        .arg("-C")
+        .arg(&opts.target)
+        .args(["rev-parse", "-q", "--verify", "HEAD"])
+        .stderr(Stdio::null())
+        .output()?;
+    if !head.status.success() {
+        return Ok(false);
+    }
+    let old = head.stdout.trim_ascii();
+    let new_id = pairs
+        .iter()
+        .find(|(o, _)| o.as_slice() == old)
+        .and_then(|(_, mark)| mark.as_ref())
+        .and_then(|mark| mark_to_id.get(mark));
+    let Some(new_id) = new_id else {
+        return Ok(mark_to_id.values().any(|id| id.as_slice() == old));
+    };
+    if new_id.as_slice() != old {
+        let new_hex = String::from_utf8_lossy(new_id).to_string();
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&opts.target)
+            .args(["update-ref", "--no-deref", "HEAD", &new_hex])
+            .status()?;
+        if !status.success() {
+            eprintln!(
+                "warning: failed to update detached HEAD to {}: {}",
+                new_hex, status
+            );
+            return Ok(false);
+        }
+        events::emit(
+            opts,
+            Event::Notice(format!(
+                "Detached HEAD moved from {} to {}",
+                String::from_utf8_lossy(old),
+                new_hex
+            )),
+        );
+    }
+    Ok(true)
+}
+
+// Branch for a HEAD whose branch is gone: `init.defaultBranch`, `main` or
+// `master` if it survived, else the first updated branch, else the first one.
+fn fallback_head_branch(
+    opts: &Options,
+    refs: &HashMap<String, String>,
+    updated_branch_refs: &BTreeSet<Vec<u8>>,
+) -> Option<String> {
+    let default_branch =
+        crate::git_config::GitConfig::get_string_config(&opts.target, "init.defaultBranch")
+            .ok()
+            .flatten();
+    let preferred = default_branch
+        .iter()
+        .map(String::as_str)
+        .chain(["main", "master"])
+        .map(|name| format!("refs/heads/{}", name))
+        .find(|name| refs.contains_key(name));
+    preferred
+        .or_else(|| {
+            updated_branch_refs
+                .iter()
+                .map(|b| String::from_utf8_lossy(b).to_string())
+                .find(|name| refs.contains_key(name))
+        })
+        .or_else(|| {
+            refs.keys()
+                .filter(|name| name.starts_with("refs/heads/"))
+                .min()
+                .cloned()
+        })
+}
+
+/// A `--force`d branch rename collision: `kept` (the source whose original
+/// commit is newest) ends up at `target`, the other sources are dropped.
+struct BranchCollision {
+    target: String,
+    kept: String,
+    kept_oid: String,
+    discarded: Vec<String>,
+}
+
+// Pick the newest source of every branch rename collision and report the rest.
+fn resolve_branch_collisions(
+    opts: &Options,
+    refs: &HashMap<String, String>,
+    ref_table: &[(Vec<u8>, Vec<u8>)],
+) -> io::Result<Vec<BranchCollision>> {
+    let collisions = crate::sanity::branch_rename_collisions(opts, refs, ref_table);
+    if collisions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let oids: Vec<&str> = collisions
+        .iter()
+        .flat_map(|(_, sources)| sources.iter().filter_map(|name| refs.get(name)))
+        .map(String::as_str)
+        .collect();
+    let times = gitutil::commit_timestamps(&opts.source, &oids)?;
+    let mut resolved = Vec::with_capacity(collisions.len());
+    for (target, sources) in collisions {
+        let time_of = |name: &String| refs.get(name).and_then(|oid| times.get(oid)).copied();
+        // Sources are sorted, so ties go to the first name
+        let Some(kept) = sources
+            .iter()
+            .max_by(|a, b| time_of(a).cmp(&time_of(b)).then_with(|| b.cmp(a)))
+            .cloned()
+        else {
+            continue;
+        };
+        let discarded: Vec<String> = sources.into_iter().filter(|s| *s != kept).collect();
+        events::emit(
+            opts,
+            Event::Warning(format!(
+                "warning: branch rename collision on {}: keeping {} (newest commit), discarding {}",
+                target,
+                kept,
+                discarded.join(", ")
+            )),
+        );
+        resolved.push(BranchCollision {
+            kept_oid: refs.get(&kept).cloned().unwrap_or_default(),
+            target,
+            kept,
+            discarded,
+        });
+    }
+    Ok(resolved)
+}
+
+/// Parse a `--ref-rename-file`: one `old-ref new-ref` pair per line, both
+/// full ref names. Blank lines and `#` comments are skipped.
+pub(crate) fn read_ref_rename_table(path: &Path) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let data = std::fs::read(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to read --ref-rename-file {}: {e}", path.display()),
+        )
+    })?;
+    let mut table: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    for (idx, line) in data.split(|&b| b == b'\n').enumerate() {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |why: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {why}: {line}", path.display(), idx + 1),
+            )
+        };
+        let mut parts = line.split_whitespace();
+        let (Some(old), Some(new_), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid("expected 'old-ref new-ref'"));
+        };
+        if !old.starts_with("refs/") || !new_.starts_with("refs/") {
+            return Err(invalid("ref names must start with refs/"));
+        }
+        if table.iter().any(|(o, _)| o == old.as_bytes()) {
+            return Err(invalid("ref is renamed more than once"));
+        }
+        table.push((old.as_bytes().to_vec(), new_.as_bytes().to_vec()));
+    }
+    Ok(table)
+}
+
+// Apply an update-ref payload; `--deterministic` orders it by ref name so it
+// never follows hash map iteration.
+fn update_refs(opts: &Options, payload: &[u8]) -> io::Result<()> {
+    let transactional = opts.git_caps.update_ref_transactions;
+    if !opts.deterministic {
+        return gitutil::update_refs(&opts.target, payload, transactional);
+    }
+    let mut lines: Vec<&[u8]> = payload.split_inclusive(|&b| b == b'\n').collect();
+    lines.sort_by_key(|line| {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        line.split(|&b| b == b' ').nth(1).unwrap_or_default()
+    });
+    gitutil::update_refs(&opts.target, &lines.concat(), transactional)
+}
+
+// Record the pre-rewrite value of every ref the run changed under refs/original/.
+fn save_original_refs(
+    opts: &Options,
+    preserved: &BTreeMap<String, String>,
+) -> io::Result<Vec<String>> {
+    if preserved.is_empty() {
+        return Ok(Vec::new());
+    }
+    let after = gitutil::get_all_refs(&opts.target)?;
+    let mut saved = Vec::new();
+    let mut payload: Vec<u8> = Vec::new();
+    for (name, oid) in preserved {
+        if after.get(name) == Some(oid) {
+            continue;
+        }
+        let saved_ref = format!("refs/original/{}", name);
+        payload.extend_from_slice(format!("update {} {}\n", saved_ref, oid).as_bytes());
+        saved.push(saved_ref);
+    }
+    if payload.is_empty() {
+        return Ok(saved);
+    }
+    update_refs(opts, &payload).map_err(|e| {
+        io::Error::other(format!(
+            "failed to save original refs under refs/original/: {e}"
+        ))
+    })?;
+    Ok(saved)
+}
+
+// Bring refs/replace/* in line with --replace-refs. Existing replace refs are
+// keyed by the pre-rewrite commit id, so updating renames them to the new id
+// (and retargets them); pruned commits lose theirs.
+fn apply_replace_refs_policy(
+    opts: &Options,
+    pairs: &[(Vec<u8>, Option<u32>)],
+    mark_to_id: &HashMap<u32, Vec<u8>>,
+) -> io::Result<()> {
+    let policy = opts.replace_refs_policy;
+    let mut commit_map: HashMap<&[u8], Option<&[u8]>> = HashMap::new();
+    for (old, mark) in pairs {
+        let new_ = mark.and_then(|m| mark_to_id.get(&m)).map(|v| v.as_slice());
+        if mark.is_some() && new_.is_none() {
+            continue;
+        }
+        commit_map.insert(old.as_slice(), new_);
+    }
+    let existing: BTreeMap<String, String> = gitutil::get_all_refs(&opts.target)?
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("refs/replace/"))
+        .collect();
+
+    // Work out the wanted refs/replace/* state, then diff it against what exists
+    let mut wanted: BTreeMap<String, String> = BTreeMap::new();
+    if matches!(
+        policy,
+        ReplaceRefsPolicy::UpdateNoAdd | ReplaceRefsPolicy::UpdateOrAdd
+    ) {
+        let rewritten = |oid: &str| match commit_map.get(oid.as_bytes()) {
+            Some(Some(new_)) => Some(String::from_utf8_lossy(new_).into_owned()),
+            Some(None) => None,
+            None => Some(oid.to_string()),
+        };
+        for (name, value) in &existing {
+            let replaced = &name["refs/replace/".len()..];
+            if let Some(target) = rewritten(replaced) {
+                let value = rewritten(value).unwrap_or_else(|| value.clone());
+                wanted.insert(format!("refs/replace/{}", target), value);
+            }
+        }
+    }
+    if matches!(
+        policy,
+        ReplaceRefsPolicy::Delete | ReplaceRefsPolicy::UpdateOrAdd
+    ) {
+        for (old, new_) in &commit_map {
+            let Some(new_) = new_ else { continue };
+            let name = format!("refs/replace/{}", String::from_utf8_lossy(old));
+            let had_one = policy == ReplaceRefsPolicy::UpdateOrAdd && existing.contains_key(&name);
+            if old != new_ && !had_one {
+                wanted
+                    .entry(name)
+                    .or_insert_with(|| String::from_utf8_lossy(new_).into_owned());
+            }
+        }
+    }
+
+    let mut payload: Vec<u8> = Vec::new();
+    for name in existing.keys() {
+        if !wanted.contains_key(name) {
+            payload.extend_from_slice(format!("delete {}\n", name).as_bytes());
+        }
+    }
+    for (name, value) in &wanted {
+        if existing.get(name) != Some(value) {
+            payload.extend_from_slice(format!("update {} {}\n", name, value).as_bytes());
+        }
+    }
+    if payload.is_empty() {
+        return Ok(());
+    }
+    update_refs(opts, &payload)
+        .map_err(|e| io::Error::other(format!("failed to update refs/replace/: {e}")))
+}
+
+// Write parent-map: `<old> <new> <new-parent>...` per rewritten commit, and
+// `<old> 0000…` for pruned commits, mirroring the commit-map convention.
+fn write_parent_map(
+    opts: &Options,
+    debug_dir: &Path,
+    pairs: &[(Vec<u8>, Option<u32>)],
+    mark_to_id: &HashMap<u32, Vec<u8>>,
+) -> io::Result<()> {
+    let new_ids: Vec<&Vec<u8>> = pairs
+        .iter()
+        .filter_map(|(_, mark)| mark.and_then(|m| mark_to_id.get(&m)))
+        .collect();
+    let mut parents: HashMap<Vec<u8>, Vec<Vec<u8>>> = HashMap::new();
+    if !new_ids.is_empty() {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(&opts.target)
+            .arg("rev-list")
+            .arg("--no-walk=unsorted")
+            .arg("--parents")
+            .arg("--stdin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        if let Some(mut sin) = child.stdin.take() {
+            for id in &new_ids {
+                sin.write_all(id)?;
+                sin.write_all(b"\n")?;
+            }
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "failed to read parents for parent-map: {}",
+                output.status
+            )));
+        }
+        for line in output.stdout.split(|&b| b == b'\n') {
+            let mut it = line.split(|&b| b == b' ').filter(|s| !s.is_empty());
+            if let Some(commit) = it.next() {
+                parents.insert(commit.to_vec(), it.map(|p| p.to_vec()).collect());
+            }
+        }
+    }
+
+    let mut f = File::create(debug_dir.join("parent-map"))?;
+    for (old, mark) in pairs {
+        match mark {
+            Some(m) => {
+                if let Some(newid) = mark_to_id.get(m) {
+                    f.write_all(old)?;
+                    f.write_all(b" ")?;
+                    f.write_all(newid)?;
+                    for p in parents.get(newid).into_iter().flatten() {
+                        f.write_all(b" ")?;
+                        f.write_all(p)?;
+                    }
+                    f.write_all(b"\n")?;
+                }
+            }
+            None => {
+                f.write_all(old)?;
+                f.write_all(b" 0000000000000000000000000000000000000000\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Protected refs must still point at their original objects; restore any that moved.
+fn verify_protected_refs(opts: &Options, protected: &BTreeMap<String, String>) -> io::Result<()> {
+    if protected.is_empty() {
+        return Ok(());
+    }
+    let after = gitutil::get_all_refs(&opts.target)?;
+    let mut changed: Vec<String> = Vec::new();
+    for (name, oid) in protected {
+        if after.get(name) == Some(oid) {
+            continue;
+        }
+        let restored = Command::new("git")
+            .arg("-C")
+            .arg(&opts.target)
+            .arg("update-ref")
+            .arg("--no-deref")
+            .arg(name)
+            .arg(oid)
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        let outcome = if restored {
+            "restored"
+        } else {
+            "rollback failed"
+        };
+        changed.push(format!("{} ({} to {})", name, outcome, oid));
+    }
+    if changed.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "protected refs were modified: {}",
+            changed.join(", ")
+        )))
+    }
+}
+
+// Protected refs keep pointing at original history, so rewritten commits they reach stay in the repo.
+fn warn_protected_refs_reaching_rewritten(
+    opts: &Options,
+    protected: &BTreeMap<String, String>,
+    rewritten: &HashSet<Vec<u8>>,
+) {
+    if rewritten.is_empty() {
+        return;
+    }
+    for (name, oid) in protected {
+        let Ok(reachable) = gitutil::rev_list_oids(&opts.target, oid) else {
+            continue;
+        };
+        let count = reachable.iter().filter(|c| rewritten.contains(*c)).count();
+        if count > 0 {
+            eprintln!(
+                "warning: protected ref {} still reaches {} rewritten commit(s); their original objects will remain in the repository",
+                name, count
+            );
+        }
+    }
+}
+
+// Record tool/git versions and the effective options so a report can be reproduced.
+fn write_report_header(f: &mut dyn Write, opts: &Options) -> io::Result<()> {
+    let git_version = gitutil::git_version().unwrap_or_else(|_| "unknown".to_string());
+    writeln!(f, "filter-repo-rs {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(f, "{}", git_version)?;
+    if opts.deterministic {
+        writeln!(f, "deterministic mode")?;
+    }
+    writeln!(f, "Options:")?;
+    writeln!(f, "  source: {}", opts.source.display())?;
+    writeln!(f, "  target: {}", opts.target.display())?;
+    writeln!(f, "  refs: {}", opts.refs.join(" "))?;
+    let lossy = |v: &[u8]| String::from_utf8_lossy(v).to_string();
+    let join = |items: &[Vec<u8>]| {
+        items
+            .iter()
+            .map(|p| lossy(p))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !opts.paths.is_empty() {
+        writeln!(f, "  paths: {}", join(&opts.paths))?;
+    }
+    if !opts.path_globs.is_empty() {
+        writeln!(f, "  path-globs: {}", join(&opts.path_globs))?;
+    }
+    if !opts.path_regexes.is_empty() {
+        let regexes: Vec<&str> = opts.path_regexes.iter().map(|r| r.as_str()).collect();
+        writeln!(f, "  path-regexes: {}", regexes.join(", "))?;
+    }
+    if opts.invert_paths {
+        writeln!(f, "  invert-paths: true")?;
+    }
+    if let Some(p) = &opts.ignore_file {
+        writeln!(f, "  ignore-file: {}", p.display())?;
+    }
+    for (from, to) in &opts.path_renames {
+        writeln!(f, "  path-rename: {}:{}", lossy(from), lossy(to))?;
+    }
+    if opts.rewrite_gitmodules {
+        writeln!(f, "  rewrite-gitmodules: true")?;
+    }
+    if !opts.delete_duplicate_paths.is_empty() {
+        writeln!(
+            f,
+            "  delete-duplicate-paths: {}",
+            join(&opts.delete_duplicate_paths)
+        )?;
+    }
+    if opts.prune_stale_attribute_files {
+        writeln!(f, "  prune-stale-attribute-files: true")?;
+    }
+    if let Some(p) = &opts.strip_paths_from_file {
+        writeln!(f, "  strip-paths-from-file: {}", p.display())?;
+    }
+    if !opts.strip_file_modes.is_empty() {
+        writeln!(f, "  strip-file-modes: {}", join(&opts.strip_file_modes))?;
+    }
+    if !opts.strip_file_modes_globs.is_empty() {
+        writeln!(
+            f,
+            "  strip-file-modes-glob: {}",
+            join(&opts.strip_file_modes_globs)
+        )?;
+    }
+    if let Some(policy) = opts.windows_path_policy {
+        writeln!(f, "  windows-path-policy: {:?}", policy)?;
+    }
+    if opts.on_rename_collision != CollisionPolicy::Error {
+        writeln!(f, "  on-rename-collision: {:?}", opts.on_rename_collision)?;
+    }
+    if opts.path_callback.is_some() {
+        writeln!(f, "  path-callback: set")?;
+    }
+    if let Some((old, new_)) = &opts.branch_rename {
+        writeln!(f, "  branch-rename: {}:{}", lossy(old), lossy(new_))?;
+    }
+    if let Some((old, new_)) = &opts.tag_rename {
+        writeln!(f, "  tag-rename: {}:{}", lossy(old), lossy(new_))?;
+    }
+    if opts.mirror_branch_rename_to_tags {
+        writeln!(f, "  rename-tags-to-match-branches: true")?;
+    }
+    for (old, new_) in &opts.tag_rename_map {
+        writeln!(f, "  tag-rename-file: {} -> {}", lossy(old), lossy(new_))?;
+    }
+    if let Some(p) = &opts.ref_rename_file {
+        writeln!(f, "  ref-rename-file: {}", p.display())?;
+    }
+    if opts.replace_refs_policy != ReplaceRefsPolicy::DeleteNoAdd {
+        writeln!(f, "  replace-refs: {:?}", opts.replace_refs_policy)?;
+    }
+    if opts.signed_commits != SignaturePolicy::Strip {
+        writeln!(f, "  signed-commits: {:?}", opts.signed_commits)?;
+    }
+    if opts.signed_tags != SignaturePolicy::Strip {
+        writeln!(f, "  signed-tags: {:?}", opts.signed_tags)?;
+    }
+    if let Some(p) = &opts.replace_text_file {
+        writeln!(f, "  replace-text: {}", p.display())?;
+    }
+    if !opts.replace_text_rules.is_empty() {
+        writeln!(
+            f,
+            "  replace-text (inline rules): {}",
+            opts.replace_text_rules.len()
+        )?;
+    }
+    if let Some(p) = &opts.replace_message_file {
+        writeln!(f, "  replace-message: {}", p.display())?;
+    }
+    if let Some(p) = &opts.replace_tag_message_file {
+        writeln!(f, "  replace-tag-message: {}", p.display())?;
+    }
+    for (re, replacement) in &opts.replace_identity {
+        writeln!(f, "  replace-identity: {}==>{}", re.as_str(), replacement)?;
+    }
+    if let Some(cols) = opts.wrap_message_body {
+        writeln!(f, "  wrap-body: {}", cols)?;
+    }
+    if let Some(limit) = opts.max_commit_message_bytes {
+        writeln!(f, "  max-commit-message-size: {}", limit)?;
+    }
+    if let Some(template) = &opts.message_annotate {
+        writeln!(
+            f,
+            "  message-annotate: {}",
+            String::from_utf8_lossy(template)
+        )?;
+    }
+    if opts.message_annotate_all {
+        writeln!(f, "  message-annotate-all: true")?;
+    }
+    for pattern in &opts.strip_trailers {
+        match pattern {
+            TrailerPattern::Key(key) => writeln!(f, "  strip-trailer: {}", lossy(key))?,
+            TrailerPattern::Regex(re) => writeln!(f, "  strip-trailer: regex:{}", re.as_str())?,
+        }
+    }
+    if opts.lightweight_tags {
+        writeln!(f, "  lightweight-tags: true")?;
+    }
+    if let Some(template) = &opts.annotate_tags {
+        writeln!(f, "  annotate-tags: {}", lossy(template))?;
+    }
+    if let Some(max) = opts.max_blob_size {
+        writeln!(f, "  max-blob-size: {}", max)?;
+    }
+    if opts.max_in_memory_blob_bytes != Options::default().max_in_memory_blob_bytes {
+        writeln!(
+            f,
+            "  max-in-memory-blob-size: {}",
+            opts.max_in_memory_blob_bytes
+        )?;
+    }
+    if let Some(p) = &opts.strip_blobs_with_ids {
+        writeln!(f, "  strip-blobs-with-ids: {}", p.display())?;
+    }
+    if let Some(p) = &opts.keep_blobs_with_ids {
+        writeln!(f, "  keep-blobs-with-ids: {}", p.display())?;
+    }
+    if let Some(max) = opts.strip_oversized_from_analysis {
+        writeln!(f, "  strip-blobs-bigger-than-in-packs: {}", max)?;
+    }
+    if opts.strict_blob_ids {
+        writeln!(f, "  strict-blob-ids: true")?;
+    }
+    writeln!(f, "  prune-empty: {:?}", opts.prune_empty)?;
+    if let Some(range) = &opts.prune_empty_in_range {
+        writeln!(f, "  strip-empty-commits-in-range: {}", range)?;
+    }
+    if let Some(message) = &opts.empty_commit_tombstone {
+        writeln!(
+            f,
+            "  drop-empty-commits-message: {}",
+            String::from_utf8_lossy(message).trim_end()
+        )?;
+    }
+    for re in &opts.strip_commits_by_author {
+        writeln!(f, "  strip-commits-by-author: {}", re.as_str())?;
+    }
+    if opts.strip_merges_too {
+        writeln!(f, "  strip-merges-too: true")?;
+    }
+    if opts.canonicalize_merge_parents {
+        writeln!(f, "  canonicalize-merge-parents: true")?;
+    }
+    if let Some(window) = opts.squash_consecutive_by_author {
+        writeln!(f, "  squash-consecutive-by-author: {}", window)?;
+    }
+    if let Some(from) = opts.commit_date_from {
+        writeln!(f, "  commits-from: {}", from)?;
+    }
+    if let Some(to) = opts.commit_date_to {
+        writeln!(f, "  commits-to: {}", to)?;
+    }
+    writeln!(f, "  cleanup: {:?}", opts.cleanup)?;
+    if opts.verify_hash_stability {
+        writeln!(f, "  verify-hash-stability: true")?;
+    }
+    if opts.report_unused_replace_rules {
+        writeln!(f, "  dump-unmatched-replace-rules: true")?;
+    }
+    if opts.incremental {
+        writeln!(f, "  incremental: true")?;
+    }
+    if let Some(timeout) = opts.pipeline_timeout {
+        writeln!(f, "  pipeline-timeout: {}s", timeout.as_secs())?;
+    }
+    if let Some(timeout) = opts.subprocess_timeout {
+        writeln!(f, "  subprocess-timeout: {}s", timeout.as_secs())?;
+    }
+    for arg in &opts.fe_args {
+        writeln!(f, "  fe-arg: {}", arg)?;
+    }
+    for arg in &opts.fi_args {
+        writeln!(f, "  fi-arg: {}", arg)?;
+    }
+    writeln!(
+        f,
+        "  dry-run: {}, partial: {}, sensitive: {}",
+        opts.dry_run, opts.partial, opts.sensitive
+    )?;
+    for spec in &opts.sensitive_refspecs {
+        writeln!(f, "  sensitive-refspec: {}", spec)?;
+    }
+    if !opts.skip_checks.is_empty() {
+        writeln!(f, "  skipped-checks: {}", opts.skip_checks.join(", "))?;
+    }
+    writeln!(f)?;
     Ok(())
 }
 
@@ -712,8 +1965,8 @@ fn resolve_reset_target(
             if let Some(oid) = mark_to_id.get(&num) {
                 return Ok(Some(oid.clone()));
             }
-            eprintln!(
-                "warning: mark :{} not found in target marks; skipping ref update",
+            logging::warn!(
+                "mark :{} not found in target marks; skipping ref update",
                 num
             );
             return Ok(None);
@@ -30,6 +30,19 @@ pub struct GitConfig {
     pub origin_url: Option<String>,
 }
 
+/// Defaults for filter-repo-rs options read from `filterRepo.*` git config keys
+///
+/// Values are seeded into `Options` before command-line flags are parsed, so
+/// the precedence is: built-in default < git config < CLI flag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterRepoDefaults {
+    /// Raw value of `filterRepo.maxBlobSize` (same syntax as `--max-blob-size`)
+    pub max_blob_size: Option<String>,
+
+    /// Value of `filterRepo.backup`
+    pub backup: Option<bool>,
+}
+
 impl GitConfig {
     /// Read Git configuration from a repository
     ///
@@ -71,6 +84,18 @@ impl GitConfig {
         })
     }
 
+    /// Read `filterRepo.*` option defaults from a repository's git config
+    ///
+    /// Keys that are not set are left as `None`. The usual git config
+    /// lookup applies, so values may come from the repository, global or
+    /// system configuration.
+    pub fn read_filter_repo_defaults(repo_path: &Path) -> io::Result<FilterRepoDefaults> {
+        Ok(FilterRepoDefaults {
+            max_blob_size: Self::get_string_config(repo_path, "filterRepo.maxBlobSize")?,
+            backup: Self::get_bool_config(repo_path, "filterRepo.backup")?,
+        })
+    }
+
     /// Get a boolean configuration value from Git
     ///
     /// Retrieves a boolean configuration value using `git config --bool`.
@@ -349,4 +374,21 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_read_filter_repo_defaults() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        assert_eq!(
+            GitConfig::read_filter_repo_defaults(temp_repo.path())?,
+            FilterRepoDefaults::default()
+        );
+
+        set_git_config(temp_repo.path(), "filterRepo.maxBlobSize", "10M")?;
+        set_git_config(temp_repo.path(), "filterRepo.backup", "yes")?;
+        let defaults = GitConfig::read_filter_repo_defaults(temp_repo.path())?;
+        assert_eq!(defaults.max_blob_size.as_deref(), Some("10M"));
+        assert_eq!(defaults.backup, Some(true));
+
+        Ok(())
+    }
 }
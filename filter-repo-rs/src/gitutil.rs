@@ -1,5 +1,5 @@
 use std::collections::{HashMap, HashSet};
-use std::io;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
@@ -10,6 +10,10 @@ pub struct GitCapabilities {
     pub fast_export_reencode: bool,
     pub diff_tree_combined_all_paths: bool,
     pub cat_file_batch_command: bool,
+    /// `git fast-export --signed-commits` (git >= 2.50).
+    pub fast_export_signed_commits: bool,
+    /// `git update-ref --stdin` understands `start`/`prepare`/`commit` (git >= 2.27).
+    pub update_ref_transactions: bool,
 }
 
 impl Default for GitCapabilities {
@@ -20,6 +24,8 @@ impl Default for GitCapabilities {
             fast_export_reencode: true,
             diff_tree_combined_all_paths: true,
             cat_file_batch_command: true,
+            fast_export_signed_commits: true,
+            update_ref_transactions: true,
         }
     }
 }
@@ -37,6 +43,7 @@ impl GitCapabilities {
             fast_export_help.contains("--reencode") || fast_export_help.contains("--[no-]reencode");
         let diff_tree_combined_all_paths = diff_tree_help.contains("--combined-all-paths");
         let cat_file_batch_command = cat_file_help.contains("--batch-command");
+        let fast_export_signed_commits = fast_export_help.contains("--signed-commits");
 
         Self {
             fast_export_anonymize_map,
@@ -44,6 +51,8 @@ impl GitCapabilities {
             fast_export_reencode,
             diff_tree_combined_all_paths,
             cat_file_batch_command,
+            fast_export_signed_commits,
+            update_ref_transactions: true,
         }
     }
 }
@@ -73,11 +82,128 @@ pub fn probe_git_capabilities() -> io::Result<GitCapabilities> {
     let diff_tree_help = capture_git_help(&["diff-tree", "-h"])?;
     let cat_file_help = capture_git_help(&["cat-file", "-h"])?;
 
-    Ok(GitCapabilities::from_help_texts(
-        &fast_export_help,
-        &diff_tree_help,
-        &cat_file_help,
-    ))
+    let mut caps =
+        GitCapabilities::from_help_texts(&fast_export_help, &diff_tree_help, &cat_file_help);
+    // `update-ref -h` does not list the --stdin verbs, so go by version
+    caps.update_ref_transactions = git_version_at_least(&git_version()?, (2, 27));
+    Ok(caps)
+}
+
+/// Whether `git --version` output (`git version 2.43.0`, possibly with a
+/// vendor suffix) is at least `major.minor`. Unparseable versions count as new.
+pub fn git_version_at_least(version: &str, (major, minor): (u32, u32)) -> bool {
+    let mut parts = version
+        .trim()
+        .trim_start_matches("git version ")
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|p| p.parse::<u32>().ok());
+    match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(ma), Some(mi)) => (ma, mi) >= (major, minor),
+        _ => true,
+    }
+}
+
+/// Apply `update-ref --stdin` commands (`update`, `create`, `delete` lines) to
+/// `repo` as one unit. With `transactional`, the commands are wrapped in
+/// `start`/`prepare`/`commit` so a failing ref aborts every change; without
+/// it, the error lists the refs that already hold their new value.
+pub fn update_refs(repo: &Path, commands: &[u8], transactional: bool) -> io::Result<()> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("update-ref")
+        .arg("--no-deref")
+        .arg("--stdin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::other(format!("failed to run git update-ref: {e}")))?;
+    if let Some(mut sin) = child.stdin.take() {
+        if transactional {
+            sin.write_all(b"start\n")?;
+        }
+        sin.write_all(commands)?;
+        if transactional {
+            sin.write_all(b"prepare\ncommit\n")?;
+        }
+    }
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        let outcome = if transactional {
+            "no refs were changed".to_string()
+        } else {
+            match applied_ref_commands(repo, commands) {
+                Ok(refs) if refs.is_empty() => "refs updated before the failure: none".to_string(),
+                Ok(refs) => format!("refs updated before the failure: {}", refs.join(", ")),
+                Err(e) => format!("could not tell which refs were updated: {e}"),
+            }
+        };
+        return Err(io::Error::other(format!(
+            "git update-ref failed ({}); {}: {}",
+            out.status,
+            outcome,
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+// Refs in `commands` whose current value is the one the command asked for:
+// the new id for `update`/`create`, absence for `delete`.
+fn applied_ref_commands(repo: &Path, commands: &[u8]) -> io::Result<Vec<String>> {
+    let refs = get_all_refs(repo)?;
+    let commands = String::from_utf8_lossy(commands);
+    let applied = commands
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split(' ');
+            let (verb, name) = (parts.next()?, parts.next()?);
+            let done = match verb {
+                "update" | "create" => refs.get(name).map(String::as_str) == parts.next(),
+                "delete" => !refs.contains_key(name),
+                _ => false,
+            };
+            done.then(|| name.to_string())
+        })
+        .collect();
+    Ok(applied)
+}
+
+/// Output of `git --version`, e.g. `git version 2.43.0`.
+pub fn git_version() -> io::Result<String> {
+    let out = Command::new("git")
+        .arg("--version")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other("'git --version' failed"));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// `git var GIT_COMMITTER_IDENT` for `repo`: `Name <email> <epoch> <tz>`.
+pub fn committer_ident(repo: &Path) -> io::Result<Vec<u8>> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("var")
+        .arg("GIT_COMMITTER_IDENT")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "'git var GIT_COMMITTER_IDENT' failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    let mut ident = out.stdout;
+    while ident.last() == Some(&b'\n') {
+        ident.pop();
+    }
+    Ok(ident)
 }
 
 pub fn git_dir(repo: &Path) -> io::Result<PathBuf> {
@@ -105,6 +231,52 @@ pub fn git_dir(repo: &Path) -> io::Result<PathBuf> {
     }
 }
 
+/// Whether `path` is the root of a repository: its worktree or, when bare,
+/// its git dir, rather than just some directory inside another repository.
+pub fn is_repository_root(path: &Path) -> bool {
+    let Ok(root) = path.canonicalize() else {
+        return false;
+    };
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .arg("rev-parse")
+        .arg("--absolute-git-dir")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output();
+    match out {
+        Ok(out) if out.status.success() => {
+            let git_dir = PathBuf::from(String::from_utf8_lossy(&out.stdout).trim());
+            let git_dir = git_dir.canonicalize().unwrap_or(git_dir);
+            git_dir == root || git_dir == root.join(".git")
+        }
+        _ => false,
+    }
+}
+
+/// Create a new repository at `path` with `git init`, bare when asked.
+pub fn init_repository(path: &Path, bare: bool) -> io::Result<()> {
+    let mut cmd = Command::new("git");
+    cmd.arg("init").arg("-q");
+    if bare {
+        cmd.arg("--bare");
+    }
+    let out = cmd
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "'git init {}' failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
 /// Get all references in the repository
 ///
 /// Retrieves all Git references (branches, tags, etc.) and their corresponding
@@ -161,6 +333,293 @@ pub fn get_all_refs(repo_path: &Path) -> io::Result<HashMap<String, String>> {
     Ok(refs)
 }
 
+/// List the commits selected by a revision range
+///
+/// Runs `git rev-list <range>` and collects the full object ids of every
+/// commit in the range.
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+/// * `range` - Revision range understood by `git rev-list` (e.g. `HEAD~3..HEAD`)
+///
+/// # Returns
+///
+/// Returns the set of commit ids (as hex bytes), or an error if the range
+/// cannot be resolved.
+pub fn rev_list_oids(repo_path: &Path, range: &str) -> io::Result<HashSet<Vec<u8>>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("rev-list")
+        .arg(range)
+        .arg("--")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git rev-list {}' failed: {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output
+        .stdout
+        .split(|&b| b == b'\n')
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_vec())
+        .collect())
+}
+
+/// Ids of every blob recorded at `path` in the history of `revs`
+///
+/// Runs `git log --raw` limited to `path` over `revs` (merges included) and
+/// collects the post-image blob id of each change that does not delete it.
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+/// * `revs` - Revisions understood by `git log` (e.g. `--all`)
+/// * `path` - Path of the file, relative to the repository root
+///
+/// # Returns
+///
+/// Returns the set of blob ids (as hex bytes), or an error if git fails.
+pub fn blob_ids_at_path(
+    repo_path: &Path,
+    revs: &[String],
+    path: &str,
+) -> io::Result<HashSet<Vec<u8>>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args([
+            "log",
+            "--format=",
+            "--raw",
+            "--no-abbrev",
+            "--no-renames",
+            "-m",
+            "--root",
+        ])
+        .args(revs)
+        .arg("--")
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git log -- {}' failed: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // :<old mode> <new mode> <old id> <new id> <status>\t<path>
+    Ok(output
+        .stdout
+        .split(|&b| b == b'\n')
+        .filter_map(|l| l.strip_prefix(b":"))
+        .filter_map(|l| l.split(|&b| b == b' ').nth(3))
+        .filter(|id| id.iter().any(|&b| b != b'0'))
+        .map(|id| id.to_vec())
+        .collect())
+}
+
+/// Committer timestamps of the given commits
+///
+/// Runs `git log --no-walk` over `oids` and maps each commit id to its
+/// committer date in seconds since the epoch.
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+/// * `oids` - Full commit ids
+///
+/// # Returns
+///
+/// Returns the timestamp of every commit that could be read.
+pub fn commit_timestamps(repo_path: &Path, oids: &[&str]) -> io::Result<HashMap<String, i64>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("log")
+        .arg("--no-walk=unsorted")
+        .arg("--format=%H %ct")
+        .args(oids)
+        .arg("--")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git log --no-walk' failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (oid, time) = line.split_once(' ')?;
+            Some((oid.to_string(), time.parse().ok()?))
+        })
+        .collect())
+}
+
+/// Read the subject line of each commit
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+/// * `oids` - Full commit ids
+///
+/// # Returns
+///
+/// Returns the subject of every commit that could be read, by id.
+pub fn commit_subjects(repo_path: &Path, oids: &[&str]) -> io::Result<HashMap<String, String>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("log")
+        .arg("--no-walk=unsorted")
+        .arg("--format=%H %s")
+        .args(oids)
+        .arg("--")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git log --no-walk' failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (oid, subject) = line.split_once(' ').unwrap_or((line, ""));
+            (!oid.is_empty()).then(|| (oid.to_string(), subject.to_string()))
+        })
+        .collect())
+}
+
+/// What an abbreviated object id resolves to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OidMatch {
+    /// Exactly one object, with its full id
+    Unique(String),
+    /// More than one object starts with the prefix
+    Ambiguous,
+    /// No object starts with the prefix
+    Missing,
+}
+
+/// Resolve abbreviated object ids in one pass
+///
+/// Feeds every prefix to a single `git cat-file --batch-check`, which
+/// answers each input line in order.
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+/// * `prefixes` - Abbreviated object ids (hex digits only)
+///
+/// # Returns
+///
+/// Returns one match per prefix, in the order given.
+pub fn resolve_oid_prefixes(repo_path: &Path, prefixes: &[String]) -> io::Result<Vec<OidMatch>> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("cat-file")
+        .arg("--batch-check=%(objectname)")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| io::Error::other("missing stdin for git cat-file --batch-check"))?;
+    let input: Vec<u8> = prefixes
+        .iter()
+        .flat_map(|p| p.bytes().chain(std::iter::once(b'\n')))
+        .collect();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| io::Error::other("git cat-file --batch-check writer panicked"))??;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git cat-file --batch-check' failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut lines = text.lines();
+    prefixes
+        .iter()
+        .map(|prefix| match lines.next() {
+            Some(line) if line.ends_with(" ambiguous") => Ok(OidMatch::Ambiguous),
+            Some(line) if line.ends_with(" missing") => Ok(OidMatch::Missing),
+            Some(oid) => Ok(OidMatch::Unique(oid.to_string())),
+            None => Err(io::Error::other(format!(
+                "'git cat-file --batch-check' gave no answer for {prefix}"
+            ))),
+        })
+        .collect()
+}
+
+/// List every object whose id starts with `prefix`
+///
+/// Runs `git rev-parse --disambiguate=<prefix>`, which needs at least four
+/// hex digits. More than one result means the abbreviation is ambiguous.
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+/// * `prefix` - Abbreviated object id
+///
+/// # Returns
+///
+/// Returns the full ids of all matching objects (possibly none).
+pub fn disambiguate_oid(repo_path: &Path, prefix: &str) -> io::Result<Vec<String>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("rev-parse")
+        .arg(format!("--disambiguate={}", prefix))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git rev-parse --disambiguate={}' failed: {}",
+            prefix,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
 /// Check if the repository is bare
 ///
 /// Determines whether the repository is a bare repository (no working directory)
@@ -336,6 +795,21 @@ usage: git fast-export [<options>] <revision-range>\n\
         assert!(caps.fast_export_mark_tags);
         assert!(caps.fast_export_reencode);
     }
+
+    #[test]
+    fn update_ref_transactions_follow_git_version() {
+        assert!(git_version_at_least("git version 2.43.0", (2, 27)));
+        assert!(git_version_at_least(
+            "git version 2.27.0.windows.1",
+            (2, 27)
+        ));
+        assert!(git_version_at_least("git version 3.0.0", (2, 27)));
+        assert!(!git_version_at_least(
+            "git version 2.26.2 (Apple Git-1)",
+            (2, 27)
+        ));
+        assert!(git_version_at_least("unknown", (2, 27)));
+    }
 }
 
 /// Get replace references in the repository
@@ -516,6 +990,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn failed_update_without_transaction_lists_applied_refs() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        create_commit(temp_repo.path())?;
+        let refs = get_all_refs(temp_repo.path())?;
+        let (branch, head) = refs.iter().next().expect("branch after commit");
+        let commands =
+            format!("update {branch} {head}\ndelete refs/tags/gone\ncreate refs/tags/new {head}\n");
+        assert_eq!(
+            applied_ref_commands(temp_repo.path(), commands.as_bytes())?,
+            vec![branch.clone(), "refs/tags/gone".to_string()]
+        );
+
+        fs::write(temp_repo.path().join(".git/refs/tags/new.lock"), "")?;
+        let create = format!("create refs/tags/new {head}\n");
+        let err = update_refs(temp_repo.path(), create.as_bytes(), false).unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.contains("refs updated before the failure: none"),
+            "{msg}"
+        );
+        assert!(!msg.contains("no refs were changed"), "{msg}");
+        let err = update_refs(temp_repo.path(), create.as_bytes(), true).unwrap_err();
+        assert!(err.to_string().contains("no refs were changed"), "{err}");
+        Ok(())
+    }
+
     #[test]
     fn test_get_all_refs_empty_repo() -> io::Result<()> {
         let temp_repo = create_test_repo()?;
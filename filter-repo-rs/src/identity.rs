@@ -0,0 +1,86 @@
+//! `--replace-identity`: regex rewrites of the name and email in `author`,
+//! `committer` and `tagger` lines.
+
+use regex::bytes::Regex;
+
+/// Parse a `PATTERN==>REPLACEMENT` rule; `$1`/`${name}` in the replacement
+/// refer to groups of the pattern.
+pub fn parse_rule(rule: &str) -> Result<(Regex, String), String> {
+    let (pattern, replacement) = rule
+        .split_once("==>")
+        .ok_or_else(|| format!("expected PATTERN==>REPLACEMENT, got '{rule}'"))?;
+    let re = Regex::new(pattern).map_err(|e| format!("invalid pattern '{pattern}': {e}"))?;
+    Ok((re, replacement.to_string()))
+}
+
+/// Rewrite an identity header (`author Name <email> date\n`), returning
+/// `None` when the line is not one or no rule changed it. Rules run in
+/// order, each on the name and the email separately.
+pub fn rewrite_line(line: &[u8], rules: &[(Regex, String)]) -> Option<Vec<u8>> {
+    if rules.is_empty() {
+        return None;
+    }
+    let kind_end = line.iter().position(|&b| b == b' ')?;
+    if !matches!(&line[..kind_end], b"author" | b"committer" | b"tagger") {
+        return None;
+    }
+    let open = kind_end + 1 + line[kind_end + 1..].iter().position(|&b| b == b'<')?;
+    let close = open + line[open..].iter().position(|&b| b == b'>')?;
+    let name = line[kind_end + 1..open]
+        .strip_suffix(b" ")
+        .unwrap_or(&line[kind_end + 1..open]);
+    let email = &line[open + 1..close];
+
+    let mut new_name = name.to_vec();
+    let mut new_email = email.to_vec();
+    for (re, replacement) in rules {
+        new_name = re
+            .replace_all(&new_name, replacement.as_bytes())
+            .into_owned();
+        new_email = re
+            .replace_all(&new_email, replacement.as_bytes())
+            .into_owned();
+    }
+    if new_name == name && new_email == email {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(line.len() + 16);
+    out.extend_from_slice(&line[..=kind_end]);
+    if !new_name.is_empty() {
+        out.extend_from_slice(&new_name);
+        out.push(b' ');
+    }
+    out.push(b'<');
+    out.extend_from_slice(&new_email);
+    out.extend_from_slice(&line[close..]);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_email_domain_with_groups() {
+        let rules = vec![parse_rule(r"^(.*)@internal\.corp$==>$1@example.com").unwrap()];
+        let line = b"author Jane Doe <jane@internal.corp> 1700000000 +0000\n";
+        assert_eq!(
+            rewrite_line(line, &rules).unwrap(),
+            b"author Jane Doe <jane@example.com> 1700000000 +0000\n"
+        );
+        assert!(rewrite_line(b"committer Bob <bob@example.org> 1 +0000\n", &rules).is_none());
+    }
+
+    #[test]
+    fn rewrites_name_and_ignores_other_lines() {
+        let rules = vec![parse_rule("^Jane Doe$==>Contributor").unwrap()];
+        assert_eq!(
+            rewrite_line(b"tagger Jane Doe <j@x> 1 +0000\n", &rules).unwrap(),
+            b"tagger Contributor <j@x> 1 +0000\n"
+        );
+        assert!(rewrite_line(b"from Jane Doe <j@x>\n", &rules).is_none());
+        assert!(parse_rule("no-separator").is_err());
+        assert!(parse_rule("(==>x").is_err());
+    }
+}
@@ -1,25 +1,186 @@
 pub mod analysis;
 mod backup;
+mod blob_pool;
+mod blobcache;
 mod commit;
+mod completions;
 pub mod error;
+pub mod events;
 mod filechange;
 mod finalize;
 pub mod git_config;
 pub mod gitutil;
+mod identity;
+mod logging;
+mod maplookup;
 mod message;
 mod migrate;
+mod notes;
 pub mod opts;
 pub mod pathutil;
 mod pipes;
 pub mod sanity;
+mod sensitive_report;
+mod signature;
+mod statedir;
 mod stream;
 mod tag;
+mod watchdog;
+
+use std::collections::{BTreeSet, HashMap};
+
+use events::{Event, Phase};
 
 pub use self::error::{FilterRepoError, Result as FilterRepoResult};
-pub use opts::{AnalyzeConfig, AnalyzeThresholds, Mode, Options};
+pub use events::{ConsoleSink, EventSink};
+pub use logging::LogLevel;
+pub use opts::{
+    AnalyzeConfig, AnalyzeFormat, AnalyzeThresholds, CollisionPolicy, Mode, Options, PathCallback,
+    PathDecision, ReplaceRefsPolicy, SignaturePolicy, ThresholdProfile, TrailerPattern,
+    WindowsPathPolicy,
+};
 pub use pathutil::dequote_c_style_bytes;
 
 fn validate_options(opts: &Options) -> FilterRepoResult<()> {
+    if opts.update_message_refs
+        && opts.tag_rename.is_none()
+        && opts.tag_rename_map.is_empty()
+        && opts.branch_rename.is_none()
+    {
+        return Err(FilterRepoError::invalid_options(
+            "update-message-refs requires --tag-rename, --tag-rename-file or --branch-rename",
+        ));
+    }
+
+    if opts.mirror_branch_rename_to_tags && opts.branch_rename.is_none() {
+        return Err(FilterRepoError::invalid_options(
+            "--rename-tags-to-match-branches requires --branch-rename",
+        ));
+    }
+
+    let stdin = Some(std::path::Path::new("-"));
+    if opts.strip_blobs_with_ids.as_deref() == stdin && opts.keep_blobs_with_ids.as_deref() == stdin
+    {
+        return Err(FilterRepoError::invalid_options(
+            "--strip-blobs-with-ids and --keep-blobs-with-ids cannot both read stdin",
+        ));
+    }
+
+    if let (Some(from), Some(to)) = (opts.commit_date_from, opts.commit_date_to) {
+        if from > to {
+            return Err(FilterRepoError::invalid_options(
+                "--commits-from must not be later than --commits-to",
+            ));
+        }
+    }
+    if (opts.commit_date_from.is_some() || opts.commit_date_to.is_some())
+        && !crate::sanity::same_repository(opts)
+    {
+        return Err(FilterRepoError::invalid_options(
+            "--commits-from/--commits-to reuse original blobs outside the window, \
+             so they need filtering in place (no separate --target)",
+        ));
+    }
+
+    if opts.no_rewrite_if_unchanged && !crate::sanity::same_repository(opts) {
+        return Err(FilterRepoError::invalid_options(
+            "--no-rewrite-if-unchanged names blobs by their original id, \
+             so it needs filtering in place (no separate --target)",
+        ));
+    }
+
+    if opts.message_annotate_all && opts.message_annotate.is_none() {
+        return Err(FilterRepoError::invalid_options(
+            "--message-annotate-all requires --message-annotate",
+        ));
+    }
+
+    if opts.report_unused_replace_rules && !opts.has_replace_text() {
+        return Err(FilterRepoError::invalid_options(
+            "--dump-unmatched-replace-rules requires --replace-text",
+        ));
+    }
+
+    if !opts.strip_file_modes_globs.is_empty() && opts.strip_file_modes.is_empty() {
+        return Err(FilterRepoError::invalid_options(
+            "--strip-file-modes-glob requires --strip-file-modes",
+        ));
+    }
+
+    if !opts.keep_remotes.is_empty() && !opts.partial {
+        return Err(FilterRepoError::invalid_options(
+            "keep-remotes only applies in --partial mode",
+        ));
+    }
+
+    for spec in &opts.sensitive_refspecs {
+        crate::migrate::validate_refspec(spec).map_err(FilterRepoError::invalid_options)?;
+    }
+
+    if opts.skip_backup_on_noop && opts.cleanup != opts::CleanupMode::None {
+        return Err(FilterRepoError::invalid_options(
+            "--no-backup-if-noop cannot be combined with --cleanup, which prunes the objects the deferred backup needs",
+        ));
+    }
+
+    if opts.pipeline_timeout.is_some_and(|t| t.is_zero()) {
+        return Err(FilterRepoError::invalid_options(
+            "--pipeline-timeout must be at least 1 second",
+        ));
+    }
+
+    for (fast_export, args) in [(true, &opts.fe_args), (false, &opts.fi_args)] {
+        for arg in args {
+            opts::check_passthrough_arg(fast_export, arg)
+                .map_err(FilterRepoError::invalid_options)?;
+        }
+    }
+
+    if opts.deterministic {
+        if opts.backup && backup::timestamped_name(opts) {
+            return Err(FilterRepoError::invalid_options(
+                "--deterministic needs a fixed backup name; pass --backup-path FILE.bundle",
+            ));
+        }
+        if opts
+            .message_annotate
+            .as_deref()
+            .is_some_and(|t| crate::message::find_subslice(t, b"{date}").is_some())
+        {
+            return Err(FilterRepoError::invalid_options(
+                "--deterministic cannot be combined with a --message-annotate {date}, which is the current time",
+            ));
+        }
+        if opts.annotate_tags.is_some() {
+            return Err(FilterRepoError::invalid_options(
+                "--deterministic cannot be combined with --annotate-tags, whose tagger date is the current time",
+            ));
+        }
+        if opts.tag_sign_cmd.is_some() {
+            return Err(FilterRepoError::invalid_options(
+                "--deterministic cannot be combined with --tag-sign-cmd, whose signatures are not reproducible",
+            ));
+        }
+    }
+
+    if opts.assume_yes && opts.assume_no {
+        return Err(FilterRepoError::invalid_options(
+            "--assume-yes and --assume-no are mutually exclusive",
+        ));
+    }
+
+    if opts.quiet && opts.progress {
+        return Err(FilterRepoError::invalid_options(
+            "--quiet and --progress are mutually exclusive",
+        ));
+    }
+
+    if opts.detect_renames.is_some_and(|pct| pct > 100) {
+        return Err(FilterRepoError::invalid_options(
+            "detect-renames similarity must be between 0 and 100",
+        ));
+    }
+
     if let Some(max) = opts.max_blob_size {
         if max == 0 || max == usize::MAX {
             return Err(FilterRepoError::invalid_options(
@@ -28,6 +189,12 @@ fn validate_options(opts: &Options) -> FilterRepoResult<()> {
         }
     }
 
+    if opts.strip_oversized_from_analysis == Some(0) {
+        return Err(FilterRepoError::invalid_options(
+            "strip-blobs-bigger-than-in-packs must be greater than zero",
+        ));
+    }
+
     const MAX_PATH_BYTES: usize = 4096;
     for entry in &opts.paths {
         if entry.len() > MAX_PATH_BYTES {
@@ -53,20 +220,287 @@ fn validate_options(opts: &Options) -> FilterRepoResult<()> {
     Ok(())
 }
 
+// True when no option would change history and no report was asked for, so
+// the rewrite can be skipped outright. Rule files that parse to no rules count
+// as unset; unreadable ones fall through so the rewrite reports the error. A
+// separate target always runs, since it has to be created and filled.
+fn nothing_to_do(opts: &Options) -> bool {
+    if !crate::sanity::same_repository(opts) {
+        return false;
+    }
+    let no_rules = |path: &Option<std::path::PathBuf>| match path {
+        Some(p) => std::fs::read(p)
+            .ok()
+            .and_then(|content| message::MessageReplacer::from_bytes(&content).ok())
+            .map(|rules| rules.pairs.is_empty())
+            .unwrap_or(false),
+        None => true,
+    };
+    let no_text_rules = match opts.replace_text_source() {
+        Ok(Some(content)) => message::MessageReplacer::from_bytes(&content)
+            .map(|rules| rules.pairs.is_empty())
+            .unwrap_or(false),
+        Ok(None) => true,
+        Err(_) => false,
+    };
+    let structural = !opts.paths.is_empty()
+        || !opts.path_globs.is_empty()
+        || !opts.path_regexes.is_empty()
+        || opts.invert_paths
+        || opts.ignore_file.is_some()
+        || opts.ignore_rules.is_some()
+        || !opts.path_renames.is_empty()
+        || opts.path_callback.is_some()
+        || opts.windows_path_policy.is_some()
+        || !opts.delete_duplicate_paths.is_empty()
+        || opts.prune_stale_attribute_files
+        || opts.strip_paths_from_file.is_some()
+        || !opts.strip_file_modes.is_empty()
+        || opts.max_blob_size.is_some()
+        || opts.strips_blobs_by_id()
+        || opts.normalize_eol.is_some();
+    let rewrites = !opts.replace_identity.is_empty()
+        || opts.wrap_message_body.is_some()
+        || opts.max_commit_message_bytes.is_some()
+        || opts.message_annotate.is_some()
+        || !opts.strip_trailers.is_empty()
+        || opts.tag_rename.is_some()
+        || !opts.tag_rename_map.is_empty()
+        || opts.branch_rename.is_some()
+        || opts.ref_rename_file.is_some()
+        || opts.lightweight_tags
+        || opts.annotate_tags.is_some()
+        || opts.prune_empty != opts::PruneEmpty::Always
+        || opts.prune_empty_in_range.is_some()
+        || !opts.strip_commits_by_author.is_empty()
+        || opts.canonicalize_merge_parents
+        || opts.squash_consecutive_by_author.is_some()
+        // Policies that act on an otherwise unchanged history: replace refs
+        // are deleted or carried over, signed objects fail the run
+        || opts.replace_refs_policy != opts::ReplaceRefsPolicy::DeleteNoAdd
+        || opts.signed_commits == opts::SignaturePolicy::Abort
+        || opts.signed_tags == opts::SignaturePolicy::Abort
+        || opts.fe_stream_override.is_some()
+        // Passthrough arguments can change the exported stream or the import
+        || !opts.fe_args.is_empty()
+        || !opts.fi_args.is_empty();
+    let reports = opts.write_report || opts.verify_hash_stability;
+    !structural
+        && !rewrites
+        && !reports
+        && no_text_rules
+        && no_rules(&opts.replace_message_file)
+        && no_rules(&opts.replace_tag_message_file)
+}
+
+// Report every ref whose target differs between the two snapshots.
+fn emit_ref_updates(
+    opts: &Options,
+    before: &HashMap<String, String>,
+    after: &HashMap<String, String>,
+) -> usize {
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut updated = 0;
+    for name in names {
+        let (old, new_) = (before.get(name), after.get(name));
+        if old == new_ {
+            continue;
+        }
+        updated += 1;
+        events::emit(
+            opts,
+            Event::RefUpdated {
+                name: name.clone(),
+                old: old.cloned(),
+                new: new_.cloned(),
+            },
+        );
+    }
+    updated
+}
+
+// --show-refs-before-after: one row per created, deleted or retargeted ref,
+// with abbreviated ids before and after the run.
+fn ref_change_table(before: &HashMap<String, String>, after: &HashMap<String, String>) -> String {
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let short = |oid: Option<&String>| oid.map_or("-", |o| &o[..o.len().min(12)]).to_string();
+    let rows: Vec<(&str, &String, String, String)> = names
+        .into_iter()
+        .filter_map(|name| {
+            let (old, new_) = (before.get(name), after.get(name));
+            let change = match (old, new_) {
+                (None, Some(_)) => "created",
+                (Some(_), None) => "deleted",
+                (Some(o), Some(n)) if o != n => "retargeted",
+                _ => return None,
+            };
+            Some((change, name, short(old), short(new_)))
+        })
+        .collect();
+    if rows.is_empty() {
+        return "Ref changes: none\n".to_string();
+    }
+    let width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0);
+    let mut table = format!("Ref changes ({}):\n", rows.len());
+    for (change, name, old, new_) in rows {
+        table.push_str(&format!(
+            "  {change:<10}  {name:<width$}  {old:<12} -> {new_}\n"
+        ));
+    }
+    table
+}
+
+// A separate --target that does not exist yet (or is an empty directory) is
+// created: bare when its name ends in `.git`, with a worktree otherwise.
+fn prepare_target(opts: &Options) -> FilterRepoResult<()> {
+    if gitutil::is_repository_root(&opts.target) {
+        return Ok(());
+    }
+    let parent = opts.target.parent().filter(|p| !p.as_os_str().is_empty());
+    if parent.is_some_and(|p| !p.is_dir()) {
+        return Err(FilterRepoError::invalid_options(format!(
+            "--target {}: parent directory does not exist",
+            opts.target.display()
+        )));
+    }
+    let bare = opts.target.extension().is_some_and(|ext| ext == "git");
+    gitutil::init_repository(&opts.target, bare)?;
+    Ok(())
+}
+
+// --check: option validation plus every preflight check, one line (or JSON
+// object) per check. Passes when nothing failed, or under --force when every
+// failure is one --force would skip.
+fn run_checks(opts: &Options) -> FilterRepoResult<()> {
+    let validation = validate_options(opts);
+    let outcomes = sanity::check_all(opts)?;
+    let status = |detail: &Option<String>| if detail.is_some() { "fail" } else { "pass" };
+    let option_detail = validation.as_ref().err().map(ToString::to_string);
+    let mut rows: Vec<(&str, &str, Option<String>)> =
+        vec![("option_validation", status(&option_detail), option_detail)];
+    rows.extend(outcomes.iter().map(|outcome| {
+        let detail = outcome.result.as_ref().err().map(ToString::to_string);
+        let status = if outcome.skipped {
+            "skipped"
+        } else {
+            status(&detail)
+        };
+        (outcome.check, status, detail)
+    }));
+    if opts.analyze.json {
+        let entries: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|(check, status, detail)| {
+                serde_json::json!({
+                    "check": check,
+                    "status": status,
+                    "detail": detail,
+                })
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&entries).map_err(std::io::Error::other)?;
+        println!("{json}");
+    } else {
+        let width = rows
+            .iter()
+            .map(|(check, ..)| check.len())
+            .max()
+            .unwrap_or(0);
+        for (check, status, detail) in &rows {
+            println!("{check:<width$}  {status}");
+            for line in detail.iter().flat_map(|d| d.lines()) {
+                println!("    {line}");
+            }
+        }
+    }
+
+    validation?;
+    let failures: Vec<_> = outcomes
+        .into_iter()
+        .filter_map(|o| o.result.err())
+        .collect();
+    if opts.force && failures.iter().all(|e| e.is_force_bypassable()) {
+        return Ok(());
+    }
+    match failures.into_iter().next() {
+        Some(first) => Err(FilterRepoError::Sanity(first)),
+        None => Ok(()),
+    }
+}
+
 pub fn run(opts: &Options) -> FilterRepoResult<()> {
+    logging::init(opts);
     match opts.mode {
         Mode::Filter => {
             validate_options(opts)?;
+            if nothing_to_do(opts) {
+                // A requested backup is still taken; only the rewrite is skipped
+                if opts.backup && !opts.skip_backup_on_noop {
+                    events::emit(opts, Event::PhaseStarted(Phase::Backup));
+                    if let Some(bundle_path) =
+                        crate::backup::create_backup(opts).map_err(FilterRepoError::BackupFailed)?
+                    {
+                        events::emit(opts, Event::BackupCreated(bundle_path));
+                    }
+                }
+                events::emit(
+                    opts,
+                    Event::Notice(
+                        "Nothing to do: no filters or rewrite rules given; repository left untouched"
+                            .to_string(),
+                    ),
+                );
+                return Ok(());
+            }
+            events::emit(opts, Event::PhaseStarted(Phase::Preflight));
             crate::sanity::preflight(opts)?;
-            if opts.backup {
-                if let Some(bundle_path) = crate::backup::create_backup(opts)? {
-                    println!("Backup bundle saved to {}", bundle_path.display());
+            let in_place = crate::sanity::same_repository(opts);
+            if !in_place {
+                prepare_target(opts)?;
+            }
+            let defer_backup = opts.backup && opts.skip_backup_on_noop;
+            let backup_refs = if defer_backup {
+                Some(gitutil::get_all_refs(&opts.source)?)
+            } else {
+                None
+            };
+            if opts.backup && !defer_backup {
+                events::emit(opts, Event::PhaseStarted(Phase::Backup));
+                if let Some(bundle_path) =
+                    crate::backup::create_backup(opts).map_err(FilterRepoError::BackupFailed)?
+                {
+                    events::emit(opts, Event::BackupCreated(bundle_path));
                 }
             }
-            crate::migrate::fetch_all_refs_if_needed(opts);
-            crate::migrate::migrate_origin_to_heads(opts)?;
-            stream::run(opts)
+            events::emit(opts, Event::PhaseStarted(Phase::Rewrite));
+            let refs_before = gitutil::get_all_refs(&opts.target)?;
+            // Both write to the source, which a separate target leaves untouched
+            let fetched_refs = if in_place {
+                let fetched = crate::migrate::fetch_all_refs_if_needed(opts);
+                crate::migrate::migrate_origin_to_heads(opts)?;
+                fetched
+            } else {
+                None
+            };
+            let mut summary = stream::run(opts, fetched_refs)?;
+            let refs_after = gitutil::get_all_refs(&opts.target)?;
+            summary.refs_updated = emit_ref_updates(opts, &refs_before, &refs_after);
+            if let Some(backup_refs) = backup_refs.filter(|_| summary.refs_updated > 0) {
+                events::emit(opts, Event::PhaseStarted(Phase::Backup));
+                if let Some(bundle_path) = crate::backup::create_deferred_backup(opts, &backup_refs)
+                    .map_err(FilterRepoError::BackupFailed)?
+                {
+                    events::emit(opts, Event::BackupCreated(bundle_path));
+                }
+            }
+            if opts.show_ref_changes {
+                print!("{}", ref_change_table(&refs_before, &refs_after));
+            }
+            events::emit(opts, Event::Finished(summary));
+            Ok(())
         }
         Mode::Analyze => Ok(analysis::run(opts)?),
+        Mode::Check => run_checks(opts),
+        Mode::MapLookup => maplookup::run(opts),
     }
 }
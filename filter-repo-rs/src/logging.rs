@@ -0,0 +1,135 @@
+//! Leveled diagnostics on stderr.
+//!
+//! The level comes from `--log-level`, else `FRRS_LOG`, else `debug` in debug
+//! mode and `warn` otherwise. Lines look like
+//! `[DEBUG] [   12.34ms] stream: message`, timed from the first log call, and
+//! never touch stdout, which may carry report data.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::opts::Options;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const NAMES: &'static str = "error, warn, info, debug, trace";
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Warn as u8);
+
+fn start_time() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+    *START.get_or_init(Instant::now)
+}
+
+/// Set the process-wide level for a run with `opts`.
+pub(crate) fn init(opts: &Options) {
+    let level = opts.log_level.unwrap_or(if opts.debug_mode {
+        LogLevel::Debug
+    } else {
+        LogLevel::Warn
+    });
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+    start_time();
+}
+
+pub(crate) fn enabled(level: LogLevel) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Milliseconds since logging started, as printed in every line.
+pub(crate) fn elapsed_ms() -> f64 {
+    start_time().elapsed().as_secs_f64() * 1000.0
+}
+
+// Called through the macros below, which skip formatting when `level` is off.
+pub(crate) fn log(level: LogLevel, module: &str, args: fmt::Arguments<'_>) {
+    let module = module.strip_prefix("filter_repo_rs::").unwrap_or(module);
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(
+        stderr,
+        "[{}] [{:>8.2}ms] {}: {}",
+        level.label(),
+        elapsed_ms(),
+        module,
+        args
+    );
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Error) {
+            $crate::logging::log($crate::logging::LogLevel::Error, module_path!(), format_args!($($arg)*))
+        }
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Warn) {
+            $crate::logging::log($crate::logging::LogLevel::Warn, module_path!(), format_args!($($arg)*))
+        }
+    };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Info) {
+            $crate::logging::log($crate::logging::LogLevel::Info, module_path!(), format_args!($($arg)*))
+        }
+    };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Debug) {
+            $crate::logging::log($crate::logging::LogLevel::Debug, module_path!(), format_args!($($arg)*))
+        }
+    };
+}
+
+macro_rules! log_trace {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Trace) {
+            $crate::logging::log($crate::logging::LogLevel::Trace, module_path!(), format_args!($($arg)*))
+        }
+    };
+}
+
+// `log_` names because `warn` clashes with the builtin attribute; callers
+// write `logging::warn!` and so on
+pub(crate) use {
+    log_debug as debug, log_error as error, log_info as info, log_trace as trace, log_warn as warn,
+};
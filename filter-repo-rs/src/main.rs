@@ -5,12 +5,12 @@ use std::process;
 fn main() {
     let opts = fr::opts::parse_args();
     if let Err(err) = fr::run(&opts) {
-        eprintln!("{err}");
+        eprintln!("error: [{}] {err}", err.code());
         let mut source = err.source();
         while let Some(cause) = source {
             eprintln!("Caused by: {cause}");
             source = cause.source();
         }
-        process::exit(1);
+        process::exit(err.exit_code());
     }
 }
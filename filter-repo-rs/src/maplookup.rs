@@ -0,0 +1,134 @@
+//! `--map-lookup`: answer "what did this commit become?" (or "where did it
+//! come from?") from the commit-map a previous run left in the state dir.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+
+use crate::error::{FilterRepoError, Result as FilterRepoResult};
+use crate::opts::Options;
+
+/// New id the commit-map records for a pruned commit.
+const PRUNED: &str = "0000000000000000000000000000000000000000";
+
+/// Rewrite `commit-map-reverse` (`<new> <old>` lines sorted by new id) from
+/// the commit-map in `debug_dir`. Pruned commits have no new id and are left
+/// out; several old ids share a new one when commits were squashed.
+pub(crate) fn write_reverse_commit_map(debug_dir: &Path) -> io::Result<()> {
+    let map = std::fs::read_to_string(debug_dir.join("commit-map"))?;
+    let mut pairs: Vec<(&str, &str)> = entries(&map)
+        .filter(|(_, new)| *new != PRUNED)
+        .map(|(old, new)| (new, old))
+        .collect();
+    pairs.sort_unstable();
+    let mut out = String::with_capacity(map.len());
+    for (new, old) in pairs {
+        out.push_str(new);
+        out.push(' ');
+        out.push_str(old);
+        out.push('\n');
+    }
+    std::fs::write(debug_dir.join("commit-map-reverse"), out)
+}
+
+/// Print the counterparts of `opts.map_lookup` from the target's commit-map:
+/// `<old> -> <new>` (or `-> pruned`) when it names an original commit, and
+/// `<new> <- <old>` for every original commit that became it.
+pub(crate) fn run(opts: &Options) -> FilterRepoResult<()> {
+    let query = opts
+        .map_lookup
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if query.len() < 4
+        || query.len() > PRUNED.len()
+        || !query.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return Err(FilterRepoError::invalid_options(format!(
+            "--map-lookup expects a commit id or a prefix of at least 4 hex digits, got '{query}'"
+        )));
+    }
+    let dir = crate::statedir::resolve(&opts.target, opts.state_dir.as_deref())?;
+    let path = dir.join("commit-map");
+    let map = std::fs::read_to_string(&path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "cannot read {} (has filter-repo-rs run here?): {e}",
+                path.display()
+            ),
+        )
+    })?;
+    for line in lookup(&map, &query)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn lookup(map: &str, query: &str) -> FilterRepoResult<Vec<String>> {
+    let pairs: Vec<(&str, &str)> = entries(map).collect();
+    let matched: BTreeSet<&str> = pairs
+        .iter()
+        .flat_map(|&(old, new)| [old, new])
+        .filter(|oid| *oid != PRUNED && oid.starts_with(query))
+        .collect();
+    let oid = match matched.len() {
+        0 => {
+            return Err(FilterRepoError::invalid_options(format!(
+                "{query} is not in the commit-map"
+            )))
+        }
+        1 => matched.into_iter().next().unwrap_or_default(),
+        _ => {
+            return Err(FilterRepoError::invalid_options(format!(
+                "{query} is ambiguous in the commit-map: {}",
+                matched.into_iter().collect::<Vec<_>>().join(", ")
+            )))
+        }
+    };
+    let mut lines = Vec::new();
+    for &(old, new) in &pairs {
+        if old == oid {
+            let new = if new == PRUNED { "pruned" } else { new };
+            lines.push(format!("{old} -> {new}"));
+        } else if new == oid {
+            lines.push(format!("{new} <- {old}"));
+        }
+    }
+    Ok(lines)
+}
+
+fn entries(map: &str) -> impl Iterator<Item = (&str, &str)> {
+    map.lines().filter_map(|line| line.split_once(' '))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "\
+1111111111111111111111111111111111111111 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
+2222222222222222222222222222222222222222 0000000000000000000000000000000000000000
+3333333333333333333333333333333333333333 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
+1112222222222222222222222222222222222222 bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb
+";
+
+    #[test]
+    fn lookup_follows_both_directions() {
+        assert_eq!(
+            lookup(MAP, "2222").unwrap(),
+            vec!["2222222222222222222222222222222222222222 -> pruned"]
+        );
+        assert_eq!(
+            lookup(MAP, "aaaaaaa").unwrap(),
+            vec![
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa <- 1111111111111111111111111111111111111111",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa <- 3333333333333333333333333333333333333333",
+            ]
+        );
+        let err = lookup(MAP, "111").unwrap_err().to_string();
+        assert!(err.contains("ambiguous"), "{err}");
+        assert!(lookup(MAP, "0000").is_err());
+    }
+}
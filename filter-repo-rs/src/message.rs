@@ -1,16 +1,32 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{self, BufRead};
 use std::path::Path;
 
+use crate::opts::TrailerPattern;
+
 #[derive(Clone, Debug, Default)]
 pub struct MessageReplacer {
     pub pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Regex rules borrowed from `--replace-text` when it also targets messages.
+    pub regex: Option<blob_regex::RegexReplacer>,
+    /// Short ref names renamed by `--tag-rename`/`--branch-rename`, longest
+    /// first; only replaced where they stand as a whole word.
+    pub ref_renames: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Number of messages changed by `ref_renames`.
+    pub ref_rename_hits: Cell<usize>,
 }
 
 impl MessageReplacer {
     pub fn from_file(path: &std::path::Path) -> io::Result<Self> {
-        let content = std::fs::read(path)?;
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Parse rules in `--replace-text`/`--replace-message` file syntax.
+    ///
+    /// A search term written as `base64:DATA` or `hex:DATA` is decoded and the
+    /// resulting raw bytes are searched for.
+    pub fn from_bytes(content: &[u8]) -> io::Result<Self> {
         let mut pairs = Vec::new();
         for raw in content.split(|&b| b == b'\n') {
             if raw.is_empty() {
@@ -19,28 +35,101 @@ impl MessageReplacer {
             if raw.starts_with(b"#") {
                 continue;
             }
-            if let Some(pos) = find_subslice(raw, b"==>") {
-                let from = raw[..pos].to_vec();
-                let to = raw[pos + 3..].to_vec();
-                if !from.is_empty() {
-                    pairs.push((from, to));
-                }
-            } else {
-                let from = raw.to_vec();
-                if !from.is_empty() {
-                    pairs.push((from, b"***REMOVED***".to_vec()));
-                }
+            let (from, to) = match find_subslice(raw, b"==>") {
+                Some(pos) => (&raw[..pos], raw[pos + 3..].to_vec()),
+                None => (raw, b"***REMOVED***".to_vec()),
+            };
+            let from = decode_search_term(from)?;
+            if !from.is_empty() {
+                pairs.push((from, to));
+            }
+        }
+        Ok(Self {
+            pairs,
+            ..Self::default()
+        })
+    }
+
+    /// Append the `--replace-text` literal and regex rules so they also apply to messages.
+    pub fn extend_with_text_rules(
+        &mut self,
+        literal: Option<&MessageReplacer>,
+        regex: Option<&blob_regex::RegexReplacer>,
+    ) {
+        if let Some(l) = literal {
+            self.pairs.extend(l.pairs.iter().cloned());
+        }
+        if let Some(rr) = regex {
+            match &mut self.regex {
+                Some(existing) => existing.rules.extend(rr.rules.iter().cloned()),
+                None => self.regex = Some(rr.clone()),
             }
         }
-        Ok(Self { pairs })
     }
 
     pub fn apply(&self, mut data: Vec<u8>) -> Vec<u8> {
         for (from, to) in &self.pairs {
             data = replace_all_bytes(&data, from, to);
         }
+        if let Some(rr) = &self.regex {
+            data = rr.apply_regex(data);
+        }
+        if !self.ref_renames.is_empty() {
+            let renamed = replace_ref_names(&data, &self.ref_renames);
+            if renamed != data {
+                self.ref_rename_hits.set(self.ref_rename_hits.get() + 1);
+                data = renamed;
+            }
+        }
         data
     }
+
+    /// Build old → new short-name pairs for every existing ref that a
+    /// tag or branch prefix rename, or an exact tag rename, will touch.
+    pub fn ref_rename_pairs<'a>(
+        refnames: impl Iterator<Item = &'a str>,
+        tag_rename_map: &[(Vec<u8>, Vec<u8>)],
+        tag_rename: Option<&(Vec<u8>, Vec<u8>)>,
+        branch_rename: Option<&(Vec<u8>, Vec<u8>)>,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut pairs = Vec::new();
+        for name in refnames {
+            let (short, exact, rename) = if let Some(s) = name.strip_prefix("refs/tags/") {
+                (s, tag_rename_map, tag_rename)
+            } else if let Some(s) = name.strip_prefix("refs/heads/") {
+                (s, &[][..], branch_rename)
+            } else {
+                continue;
+            };
+            let short = short.as_bytes();
+            if let Some((_, new_)) = exact.iter().find(|(old, _)| old == short) {
+                pairs.push((short.to_vec(), new_.clone()));
+                continue;
+            }
+            let Some((old, new_)) = rename else {
+                continue;
+            };
+            if old.is_empty() || !short.starts_with(old) {
+                continue;
+            }
+            let mut renamed = new_.clone();
+            renamed.extend_from_slice(&short[old.len()..]);
+            pairs.push((short.to_vec(), renamed));
+        }
+        pairs.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        pairs.dedup_by(|a, b| a.0 == b.0);
+        pairs
+    }
+
+    /// Indices of the rules whose pattern occurs in `data`.
+    pub fn matching_rules(&self, data: &[u8]) -> Vec<usize> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, (from, _))| find_subslice(data, from).is_some())
+            .map(|(idx, _)| idx)
+            .collect()
+    }
 }
 
 const MIN_SHORT_HASH_LEN: usize = 7;
@@ -50,11 +139,37 @@ const NULL_OID: &[u8] = b"0000000000000000000000000000000000000000";
 pub struct ShortHashMapper {
     lookup: HashMap<Vec<u8>, Option<Vec<u8>>>,
     prefix_index: HashMap<Vec<u8>, Vec<Vec<u8>>>,
-    cache: RefCell<HashMap<Vec<u8>, Option<Vec<u8>>>>,
+    cache: RefCell<HashMap<Vec<u8>, HashMention>>,
+    /// Candidates that matched no known commit when seen; they may be forward
+    /// references to commits exported later in the stream.
+    unresolved: RefCell<HashSet<Vec<u8>>>,
     regex: regex::bytes::Regex,
 }
 
+/// How a hex string found in a message relates to the commit map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum HashMention {
+    /// Abbreviated new id, same length as the mention.
+    Rewritten(Vec<u8>),
+    /// The referenced commit was pruned.
+    Pruned,
+    /// Not a known commit (or an ambiguous prefix).
+    Unknown,
+}
+
 impl ShortHashMapper {
+    pub fn new() -> io::Result<Self> {
+        let regex = regex::bytes::Regex::new(r"(?i)\b[0-9a-f]{7,40}\b")
+            .map_err(|e| io::Error::other(format!("invalid short-hash regex: {e}")))?;
+        Ok(Self {
+            lookup: HashMap::new(),
+            prefix_index: HashMap::new(),
+            cache: RefCell::new(HashMap::new()),
+            unresolved: RefCell::new(HashSet::new()),
+            regex,
+        })
+    }
+
     pub fn from_debug_dir(dir: &Path) -> io::Result<Option<Self>> {
         let map_path = dir.join("commit-map");
         let file = match std::fs::File::open(&map_path) {
@@ -62,8 +177,7 @@ impl ShortHashMapper {
             Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
             Err(e) => return Err(e),
         };
-        let mut lookup: HashMap<Vec<u8>, Option<Vec<u8>>> = HashMap::new();
-        let mut prefix_index: HashMap<Vec<u8>, Vec<Vec<u8>>> = HashMap::new();
+        let mut mapper = Self::new()?;
         let mut rdr = std::io::BufReader::new(file);
         let mut line = Vec::with_capacity(128);
         let mut has_any = false;
@@ -90,105 +204,548 @@ impl ShortHashMapper {
                     continue;
                 }
             };
-            let old_norm = old.to_ascii_lowercase();
             let new_entry = if new == NULL_OID {
                 None
             } else {
                 Some(new.to_ascii_lowercase())
             };
-            prefix_index
-                .entry(old_norm[..MIN_SHORT_HASH_LEN.min(old_norm.len())].to_vec())
-                .or_default()
-                .push(old_norm.clone());
-            lookup.insert(old_norm, new_entry);
+            mapper.insert(old, new_entry);
             has_any = true;
             line.clear();
         }
         if !has_any {
             return Ok(None);
         }
-        let regex = regex::bytes::Regex::new(r"(?i)\b[0-9a-f]{7,40}\b").map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("invalid short-hash regex: {e}"),
-            )
-        })?;
-        Ok(Some(Self {
-            lookup,
-            prefix_index,
-            cache: RefCell::new(HashMap::new()),
-            regex,
-        }))
+        Ok(Some(mapper))
     }
 
+    /// Rewrite hash mentions to their new ids; mentions of pruned commits are
+    /// kept as-is and listed in a trailing note.
     pub fn rewrite(&self, data: Vec<u8>) -> Vec<u8> {
-        self.regex
+        let mut pruned: Vec<Vec<u8>> = Vec::new();
+        let mut out = self
+            .regex
             .replace_all(&data, |caps: &regex::bytes::Captures| {
-                let m = caps.get(0).expect("short hash match");
-                self.translate(m.as_bytes())
-                    .unwrap_or_else(|| m.as_bytes().to_vec())
+                let m = caps.get(0).expect("short hash match").as_bytes();
+                match self.translate(m) {
+                    HashMention::Rewritten(new_) => new_,
+                    HashMention::Pruned => {
+                        if !pruned.iter().any(|p| p == m) {
+                            pruned.push(m.to_vec());
+                        }
+                        m.to_vec()
+                    }
+                    HashMention::Unknown => m.to_vec(),
+                }
             })
-            .into_owned()
+            .into_owned();
+        if !pruned.is_empty() {
+            if !out.ends_with(b"\n") {
+                out.push(b'\n');
+            }
+            out.push(b'\n');
+            for p in &pruned {
+                out.extend_from_slice(b"Note: referenced commit ");
+                out.extend_from_slice(p);
+                out.extend_from_slice(b" was pruned by filter-repo-rs\n");
+            }
+        }
+        out
     }
 
-    fn translate(&self, candidate: &[u8]) -> Option<Vec<u8>> {
+    fn translate(&self, candidate: &[u8]) -> HashMention {
         if candidate.len() < MIN_SHORT_HASH_LEN {
-            return None;
+            return HashMention::Unknown;
         }
         let key = candidate.to_ascii_lowercase();
-        let mut cache = self.cache.borrow_mut();
-        if let Some(entry) = cache.get(&key) {
+        if let Some(entry) = self.cache.borrow().get(&key) {
             return entry.clone();
         }
-        let resolved = if candidate.len() == 40 {
-            self.lookup.get(&key).cloned().flatten()
+        let resolved = self.resolve(&key);
+        if resolved == HashMention::Unknown {
+            self.unresolved.borrow_mut().insert(key.clone());
+        }
+        self.cache.borrow_mut().insert(key, resolved.clone());
+        resolved
+    }
+
+    fn resolve(&self, key: &[u8]) -> HashMention {
+        let entry = if key.len() == 40 {
+            self.lookup.get(key)
         } else {
-            self.lookup_prefix(&key, candidate.len())
+            self.lookup_prefix(key)
         };
-        cache.insert(key, resolved.clone());
-        resolved
+        match entry {
+            Some(Some(new_full)) => HashMention::Rewritten(new_full[..key.len()].to_vec()),
+            Some(None) => HashMention::Pruned,
+            None => HashMention::Unknown,
+        }
     }
 
     pub fn update_mapping(&mut self, old_full: &[u8], new_full: &[u8]) {
         if old_full.is_empty() || new_full.is_empty() {
             return;
         }
+        self.insert(old_full, Some(new_full.to_ascii_lowercase()));
+        self.cache.borrow_mut().clear();
+    }
+
+    pub fn mark_pruned(&mut self, old_full: &[u8]) {
+        if old_full.is_empty() {
+            return;
+        }
+        self.insert(old_full, None);
+        self.cache.borrow_mut().clear();
+    }
+
+    /// Mentions that were unknown when their message was written but resolve
+    /// now that the whole stream has been mapped.
+    pub fn forward_references(&self) -> Vec<Vec<u8>> {
+        let mut refs: Vec<Vec<u8>> = self
+            .unresolved
+            .borrow()
+            .iter()
+            .filter(
+                |key| matches!(self.resolve(key), HashMention::Rewritten(new_) if &new_ != *key),
+            )
+            .cloned()
+            .collect();
+        refs.sort();
+        refs
+    }
+
+    fn insert(&mut self, old_full: &[u8], new_entry: Option<Vec<u8>>) {
         let old_norm = old_full.to_ascii_lowercase();
-        let new_norm = new_full.to_ascii_lowercase();
         let prefix_len = MIN_SHORT_HASH_LEN.min(old_norm.len());
-        let prefix = old_norm[..prefix_len].to_vec();
-        let entry = self.prefix_index.entry(prefix).or_default();
+        let entry = self
+            .prefix_index
+            .entry(old_norm[..prefix_len].to_vec())
+            .or_default();
         if !entry.iter().any(|existing| existing == &old_norm) {
             entry.push(old_norm.clone());
         }
-        self.lookup.insert(old_norm, Some(new_norm));
-        self.cache.borrow_mut().clear();
+        self.lookup.insert(old_norm, new_entry);
     }
 
-    fn lookup_prefix(&self, short: &[u8], orig_len: usize) -> Option<Vec<u8>> {
+    fn lookup_prefix(&self, short: &[u8]) -> Option<&Option<Vec<u8>>> {
         if short.len() < MIN_SHORT_HASH_LEN {
             return None;
         }
         let key = short[..MIN_SHORT_HASH_LEN].to_vec();
-        let entries = match self.prefix_index.get(&key) {
-            Some(v) => v,
-            None => return None,
-        };
+        let entries = self.prefix_index.get(&key)?;
         let mut matches_iter = entries
             .iter()
-            .filter(|full| full.len() >= orig_len && &full[..orig_len] == short);
-        let full_old = match matches_iter.next() {
-            Some(m) => m,
-            None => return None,
-        };
+            .filter(|full| full.len() >= short.len() && &full[..short.len()] == short);
+        let full_old = matches_iter.next()?;
         if matches_iter.next().is_some() {
             return None;
         }
-        match self.lookup.get(full_old) {
-            Some(Some(new_full)) => Some(new_full[..orig_len].to_vec()),
-            _ => None,
+        self.lookup.get(full_old)
+    }
+}
+
+/// Reflow a commit message body to `width` columns for `--wrap-body`.
+///
+/// The subject paragraph, fenced code blocks, indented or list paragraphs and
+/// trailer blocks (`Key: value` lines) are kept as-is, as is any paragraph
+/// that already fits.
+pub fn wrap_body(data: &[u8], width: usize) -> Vec<u8> {
+    let trailing_newline = data.ends_with(b"\n");
+    let body = if trailing_newline {
+        &data[..data.len() - 1]
+    } else {
+        data
+    };
+    let lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+    // The subject runs up to the first blank line
+    let subject_end = lines
+        .iter()
+        .position(|l| is_blank(l))
+        .unwrap_or(lines.len());
+    let mut out: Vec<Vec<u8>> = lines[..subject_end].iter().map(|l| l.to_vec()).collect();
+    let mut in_fence = false;
+    let mut para: Vec<&[u8]> = Vec::new();
+    for line in &lines[subject_end..] {
+        let fence = line.trim_ascii_start().starts_with(b"```")
+            || line.trim_ascii_start().starts_with(b"~~~");
+        if in_fence || fence || is_blank(line) {
+            flush_paragraph(&mut para, width, &mut out);
+            out.push(line.to_vec());
+            if fence {
+                in_fence = !in_fence;
+            }
+        } else {
+            para.push(line);
         }
     }
+    flush_paragraph(&mut para, width, &mut out);
+    let mut result = out.join(&b'\n');
+    if trailing_newline {
+        result.push(b'\n');
+    }
+    result
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(|b| b.is_ascii_whitespace())
+}
+
+fn display_width(text: &[u8]) -> usize {
+    std::str::from_utf8(text)
+        .map(|s| s.chars().count())
+        .unwrap_or(text.len())
+}
+
+// A `Token: value` line as found in trailer blocks (Signed-off-by, Co-authored-by, ...)
+fn is_trailer_line(line: &[u8]) -> bool {
+    match find_subslice(line, b": ") {
+        Some(pos) if pos > 0 => line[..pos]
+            .iter()
+            .all(|&b| b.is_ascii_alphanumeric() || b == b'-'),
+        _ => false,
+    }
+}
+
+fn flush_paragraph(para: &mut Vec<&[u8]>, width: usize, out: &mut Vec<Vec<u8>>) {
+    if para.is_empty() {
+        return;
+    }
+    let verbatim = para.iter().all(|l| display_width(l) <= width)
+        || para.iter().all(|l| is_trailer_line(l))
+        || para.iter().any(|l| {
+            l.first().is_some_and(|b| b.is_ascii_whitespace())
+                || l.starts_with(b"- ")
+                || l.starts_with(b"* ")
+        });
+    if verbatim {
+        out.extend(para.iter().map(|l| l.to_vec()));
+        para.clear();
+        return;
+    }
+    let mut cur: Vec<u8> = Vec::new();
+    let mut cur_width = 0;
+    for word in para
+        .iter()
+        .flat_map(|l| l.split(|b| b.is_ascii_whitespace()))
+        .filter(|w| !w.is_empty())
+    {
+        let w = display_width(word);
+        if !cur.is_empty() && cur_width + 1 + w > width {
+            out.push(std::mem::take(&mut cur));
+            cur_width = 0;
+        }
+        if !cur.is_empty() {
+            cur.push(b' ');
+            cur_width += 1;
+        }
+        cur.extend_from_slice(word);
+        cur_width += w;
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    para.clear();
+}
+
+/// Marker closing a message cut by `--max-commit-message-size`.
+const TRUNCATION_MARKER: &[u8] = b"\n...\n";
+
+/// Cut a message to at most `limit` bytes for `--max-commit-message-size`,
+/// ending it with a `...` line. The subject line is kept whole when it fits,
+/// and the cut never splits a UTF-8 sequence.
+pub fn truncate_message(data: &[u8], limit: usize) -> Vec<u8> {
+    if data.len() <= limit {
+        return data.to_vec();
+    }
+    let Some(budget) = limit.checked_sub(TRUNCATION_MARKER.len()) else {
+        return data[..char_boundary(data, limit)].to_vec();
+    };
+    let kept = data[..char_boundary(data, budget)].trim_ascii_end();
+    let mut out = Vec::with_capacity(kept.len() + TRUNCATION_MARKER.len());
+    out.extend_from_slice(kept);
+    out.extend_from_slice(TRUNCATION_MARKER);
+    out
+}
+
+// Largest index <= `at` that does not fall inside a UTF-8 sequence
+fn char_boundary(data: &[u8], mut at: usize) -> usize {
+    while at > 0 && at < data.len() && (data[at] & 0xC0) == 0x80 {
+        at -= 1;
+    }
+    at
+}
+
+/// `--message-annotate`: the trailer template and when it applies.
+/// `{old_oid}`, `{new_mark}` and `{date}` (the time of the run, UTC) are
+/// expanded per commit.
+#[derive(Debug, Default)]
+pub struct MessageAnnotator {
+    template: Option<Vec<u8>>,
+    /// Annotate every kept commit, not only rewritten ones
+    pub all: bool,
+    date: Vec<u8>,
+    /// Number of messages that received the trailer
+    pub annotated: usize,
+}
+
+impl MessageAnnotator {
+    pub fn new(opts: &crate::opts::Options) -> Self {
+        let Some(template) = &opts.message_annotate else {
+            return Self::default();
+        };
+        let format =
+            time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+        let date = time::OffsetDateTime::now_utc()
+            .format(&format)
+            .unwrap_or_default();
+        Self {
+            template: Some(template.clone()),
+            all: opts.message_annotate_all,
+            date: date.into_bytes(),
+            annotated: 0,
+        }
+    }
+
+    /// Append the trailer to the message of the commit in `commit_buf`,
+    /// fixing up its `data` length.
+    pub fn annotate(
+        &mut self,
+        commit_buf: &mut Vec<u8>,
+        old_oid: Option<&[u8]>,
+        mark: Option<u32>,
+    ) {
+        let Some(template) = &self.template else {
+            return;
+        };
+        let Some((header, body)) = message_range(commit_buf) else {
+            return;
+        };
+        let mark = mark.map(|m| format!(":{m}")).unwrap_or_default();
+        let trailer = replace_all_bytes(template, b"{old_oid}", old_oid.unwrap_or_default());
+        let trailer = replace_all_bytes(&trailer, b"{new_mark}", mark.as_bytes());
+        let trailer = replace_all_bytes(&trailer, b"{date}", &self.date);
+        let msg = append_trailer(&commit_buf[body.clone()], &trailer);
+        if msg == commit_buf[body.clone()] {
+            return;
+        }
+        let mut data = format!("data {}\n", msg.len()).into_bytes();
+        data.extend_from_slice(&msg);
+        commit_buf.splice(header.start..body.end, data);
+        self.annotated += 1;
+    }
+}
+
+// The `data` header line and the message of a buffered commit, skipping the
+// data block of a kept `gpgsig` header.
+fn message_range(commit_buf: &[u8]) -> Option<(std::ops::Range<usize>, std::ops::Range<usize>)> {
+    let mut pos = 0;
+    let mut signature = false;
+    while pos < commit_buf.len() {
+        let end = commit_buf[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(commit_buf.len(), |i| pos + i + 1);
+        let line = &commit_buf[pos..end];
+        if let Some(len) = line.strip_prefix(b"data ") {
+            let len: usize = std::str::from_utf8(len).ok()?.trim().parse().ok()?;
+            let body = end..(end + len).min(commit_buf.len());
+            if !signature {
+                return Some((pos..end, body));
+            }
+            signature = false;
+            pos = body.end;
+            continue;
+        }
+        signature = line.starts_with(b"gpgsig ");
+        pos = end;
+    }
+    None
+}
+
+/// Append `trailer` to a commit message as a trailer line: into the
+/// message's closing trailer block when it has one, else as a new paragraph.
+/// A message whose trailer block already has the trailer's key is returned
+/// as it is.
+pub fn append_trailer(data: &[u8], trailer: &[u8]) -> Vec<u8> {
+    let trailer = trailer.trim_ascii();
+    let body = data.trim_ascii_end();
+    if body.is_empty() {
+        return [trailer, b"\n"].concat();
+    }
+    let lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+    // The subject paragraph never counts as a trailer block
+    let last_para = lines
+        .iter()
+        .rposition(|l| is_blank(l))
+        .map(|i| &lines[i + 1..]);
+    let in_block = last_para.is_some_and(|para| para.iter().all(|l| is_trailer_line(l)));
+    if in_block {
+        let key = find_subslice(trailer, b": ").map(|i| &trailer[..=i]);
+        let present = key.is_some_and(|key| {
+            last_para
+                .into_iter()
+                .flatten()
+                .any(|l| l.len() >= key.len() && l[..key.len()].eq_ignore_ascii_case(key))
+        });
+        if present {
+            return data.to_vec();
+        }
+    }
+    let mut out = Vec::with_capacity(body.len() + trailer.len() + 3);
+    out.extend_from_slice(body);
+    out.extend_from_slice(if in_block { b"\n" } else { b"\n\n" });
+    out.extend_from_slice(trailer);
+    out.push(b'\n');
+    out
+}
+
+/// `--strip-trailer`: removes matching lines from the closing trailer block
+/// of commit messages, counting them per trailer key. Lines elsewhere in the
+/// message that merely look like trailers are left alone.
+#[derive(Debug, Default)]
+pub struct TrailerStripper {
+    patterns: Vec<TrailerPattern>,
+    /// Number of trailers removed, by key as written in the message
+    pub stripped: BTreeMap<Vec<u8>, usize>,
+}
+
+impl TrailerStripper {
+    pub fn new(opts: &crate::opts::Options) -> Self {
+        Self {
+            patterns: opts.strip_trailers.clone(),
+            stripped: BTreeMap::new(),
+        }
+    }
+
+    /// The message with its matching trailers removed. A trailer block left
+    /// empty goes away together with the blank line before it.
+    pub fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        if self.patterns.is_empty() {
+            return data.to_vec();
+        }
+        let body = data.trim_ascii_end();
+        let lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+        // The subject paragraph never counts as a trailer block
+        let Some(blank) = lines.iter().rposition(|l| is_blank(l)) else {
+            return data.to_vec();
+        };
+        let block = &lines[blank + 1..];
+        let continuation = |l: &[u8]| l.first().is_some_and(|&b| b == b' ' || b == b'\t');
+        let is_block = block.first().is_some_and(|l| is_trailer_line(l))
+            && block.iter().all(|l| is_trailer_line(l) || continuation(l));
+        if !is_block {
+            return data.to_vec();
+        }
+        let mut kept: Vec<&[u8]> = Vec::with_capacity(block.len());
+        let mut dropping = false;
+        let mut removed = false;
+        for &line in block {
+            if is_trailer_line(line) {
+                dropping = self.matches(line);
+                if dropping {
+                    let key = &line[..find_subslice(line, b": ").unwrap_or(0)];
+                    *self.stripped.entry(key.to_vec()).or_default() += 1;
+                }
+            }
+            // Continuation lines go with the trailer they belong to
+            if dropping {
+                removed = true;
+            } else {
+                kept.push(line);
+            }
+        }
+        if !removed {
+            return data.to_vec();
+        }
+        let mut out_lines: Vec<&[u8]> = lines[..=blank].to_vec();
+        if kept.is_empty() {
+            while out_lines.last().is_some_and(|l| is_blank(l)) {
+                out_lines.pop();
+            }
+        } else {
+            out_lines.extend(kept);
+        }
+        let mut out = out_lines.join(&b'\n');
+        if data.ends_with(b"\n") && !out.is_empty() {
+            out.push(b'\n');
+        }
+        out
+    }
+
+    fn matches(&self, line: &[u8]) -> bool {
+        let key = &line[..find_subslice(line, b": ").unwrap_or(0)];
+        self.patterns.iter().any(|p| match p {
+            TrailerPattern::Key(k) => key.eq_ignore_ascii_case(k),
+            TrailerPattern::Regex(re) => re.is_match(line),
+        })
+    }
+}
+
+// Decode a `base64:`/`hex:` prefixed search term; other terms are literal.
+pub(crate) fn decode_search_term(term: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |kind: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid {kind} search term: {}",
+                String::from_utf8_lossy(term)
+            ),
+        )
+    };
+    if let Some(data) = term.strip_prefix(b"base64:") {
+        decode_base64(data).ok_or_else(|| invalid("base64"))
+    } else if let Some(data) = term.strip_prefix(b"hex:") {
+        if !data.len().is_multiple_of(2) {
+            return Err(invalid("hex"));
+        }
+        data.chunks_exact(2)
+            .map(|pair| {
+                match (
+                    crate::blobcache::hex_val(pair[0]),
+                    crate::blobcache::hex_val(pair[1]),
+                ) {
+                    (Some(hi), Some(lo)) => Ok((hi << 4) | lo),
+                    _ => Err(invalid("hex")),
+                }
+            })
+            .collect()
+    } else {
+        Ok(term.to_vec())
+    }
+}
+
+// Standard alphabet; trailing `=` padding is optional.
+fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    let data = match data.iter().position(|&b| b == b'=') {
+        Some(pad) if data[pad..].iter().all(|&b| b == b'=') && data.len() - pad <= 2 => {
+            &data[..pad]
+        }
+        Some(_) => return None,
+        None => data,
+    };
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &b in data {
+        let v = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
 }
 
 pub fn find_subslice(h: &[u8], n: &[u8]) -> Option<usize> {
@@ -198,6 +755,47 @@ pub fn find_subslice(h: &[u8], n: &[u8]) -> Option<usize> {
     h.windows(n.len()).position(|w| w == n)
 }
 
+fn is_ref_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'/')
+}
+
+// A ref name ends at a non-name byte, or at punctuation that is not followed
+// by more of the name (e.g. the full stop in "Prepare v1.0.").
+fn ends_ref_name(data: &[u8], at: usize) -> bool {
+    match data.get(at) {
+        None => true,
+        Some(&b) if !is_ref_name_byte(b) => true,
+        Some(&b) if b.is_ascii_alphanumeric() || b == b'_' => false,
+        Some(_) => !data.get(at + 1).is_some_and(|&n| n.is_ascii_alphanumeric()),
+    }
+}
+
+fn replace_ref_names(data: &[u8], renames: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let starts_word = i == 0 || !is_ref_name_byte(data[i - 1]);
+        let hit = if starts_word {
+            renames.iter().find(|(from, _)| {
+                data[i..].starts_with(from) && ends_ref_name(data, i + from.len())
+            })
+        } else {
+            None
+        };
+        match hit {
+            Some((from, to)) => {
+                out.extend_from_slice(to);
+                i += from.len();
+            }
+            None => {
+                out.push(data[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
 pub fn replace_all_bytes(h: &[u8], n: &[u8], r: &[u8]) -> Vec<u8> {
     if n.is_empty() {
         return h.to_vec();
@@ -229,8 +827,7 @@ pub mod blob_regex {
     }
 
     impl RegexReplacer {
-        pub fn from_file(path: &std::path::Path) -> io::Result<Option<Self>> {
-            let content = std::fs::read(path)?;
+        pub fn from_bytes(content: &[u8]) -> io::Result<Option<Self>> {
             let mut rules: Vec<(Regex, Vec<u8>, bool)> = Vec::new();
             for raw in content.split(|&b| b == b'\n') {
                 if raw.is_empty() {
@@ -288,6 +885,16 @@ pub mod blob_regex {
             }
             cur
         }
+
+        /// Indices of the regex rules that match somewhere in `data`.
+        pub fn matching_rules(&self, data: &[u8]) -> Vec<usize> {
+            self.rules
+                .iter()
+                .enumerate()
+                .filter(|(_, (re, _, _))| re.is_match(data))
+                .map(|(idx, _)| idx)
+                .collect()
+        }
     }
 
     fn expand_bytes_template(tpl: &[u8], caps: &Captures) -> Vec<u8> {
@@ -1,34 +1,108 @@
 use std::io::{self, Write};
 use std::process::{Command, Stdio};
 
+use crate::events::{self, Event};
 use crate::git_config::GitConfig;
 use crate::gitutil;
 use crate::opts::Options;
 
-#[allow(dead_code)]
-pub fn fetch_all_refs_if_needed(opts: &Options) {
-    if !opts.sensitive || opts.no_fetch || opts.dry_run {
-        return;
-    }
-    // Check that origin exists
-    let remotes = Command::new("git")
-        .arg("-C")
-        .arg(&opts.source)
-        .arg("remote")
-        .output();
-    if let Ok(out) = remotes {
-        if !out.status.success() {
-            return;
+const DEFAULT_SENSITIVE_REFSPEC: &str = "+refs/*:refs/*";
+
+/// Check a `--sensitive-refspec` value: `[+]SRC:DST` with both sides under
+/// `refs/` and either no `*` or exactly one on each side.
+pub fn validate_refspec(spec: &str) -> Result<(), String> {
+    let body = spec.strip_prefix('+').unwrap_or(spec);
+    let (src, dst) = body
+        .split_once(':')
+        .ok_or_else(|| format!("refspec '{spec}' must be SRC:DST"))?;
+    for side in [src, dst] {
+        if !side.starts_with("refs/") || side.contains(':') {
+            return Err(format!(
+                "refspec '{spec}': '{side}' is not a ref under refs/"
+            ));
         }
-        let r = String::from_utf8_lossy(&out.stdout);
-        if !r.lines().any(|l| l.trim() == "origin") {
-            return;
+        let valid = Command::new("git")
+            .args(["check-ref-format", "--refspec-pattern", side])
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if !valid {
+            return Err(format!(
+                "refspec '{spec}': '{side}' is not a valid ref pattern"
+            ));
+        }
+    }
+    if src.matches('*').count() != dst.matches('*').count() {
+        return Err(format!(
+            "refspec '{spec}' must use '*' on both sides or neither"
+        ));
+    }
+    Ok(())
+}
+
+fn refspec_destination_matches(spec: &str, refname: &str) -> bool {
+    let dst = spec.rsplit_once(':').map_or(spec, |(_, dst)| dst);
+    match dst.split_once('*') {
+        Some((prefix, suffix)) => {
+            refname.len() >= prefix.len() + suffix.len()
+                && refname.starts_with(prefix)
+                && refname.ends_with(suffix)
         }
+        None => refname == dst,
+    }
+}
+
+/// In sensitive mode, fetch `--sensitive-refspec` (default: every ref) from
+/// origin. Returns how many local refs the refspecs cover afterwards, or
+/// `None` when nothing was fetched.
+pub fn fetch_all_refs_if_needed(opts: &Options) -> Option<usize> {
+    if !opts.sensitive || opts.no_fetch || opts.dry_run || !has_origin(opts) {
+        return None;
+    }
+    let refspecs: Vec<&str> = if opts.sensitive_refspecs.is_empty() {
+        // Fetch all refs to ensure sensitive-history coverage
+        events::emit(
+            opts,
+            Event::Notice(
+                "NOTICE: Fetching all refs from origin to ensure full sensitive-history coverage"
+                    .to_string(),
+            ),
+        );
+        vec![DEFAULT_SENSITIVE_REFSPEC]
     } else {
-        return;
+        let specs: Vec<&str> = opts.sensitive_refspecs.iter().map(String::as_str).collect();
+        events::emit(
+            opts,
+            Event::Notice(format!(
+                "NOTICE: Fetching {} from origin; refs outside these refspecs are not covered",
+                specs.join(" ")
+            )),
+        );
+        specs
+    };
+    fetch_from_origin(opts, &refspecs)
+}
+
+/// With `--analyze-fetch-first`, fetch every ref from origin so the analysis
+/// sees what a sensitive-mode rewrite would.
+pub fn fetch_before_analysis(opts: &Options) -> Option<usize> {
+    if !opts.analyze.fetch_first || opts.no_fetch || !has_origin(opts) {
+        return None;
     }
-    // Fetch all refs to ensure sensitive-history coverage
-    eprintln!("NOTICE: Fetching all refs from origin to ensure full sensitive-history coverage");
+    events::emit(
+        opts,
+        Event::Notice("NOTICE: Fetching all refs from origin before analysis".to_string()),
+    );
+    fetch_from_origin(opts, &[DEFAULT_SENSITIVE_REFSPEC])
+}
+
+fn has_origin(opts: &Options) -> bool {
+    list_remotes(&opts.source).iter().any(|r| r == "origin")
+}
+
+// Fetch `refspecs` from origin into the source; returns how many local refs
+// they cover afterwards.
+fn fetch_from_origin(opts: &Options, refspecs: &[&str]) -> Option<usize> {
     let _ = Command::new("git")
         .arg("-C")
         .arg(&opts.source)
@@ -36,30 +110,75 @@ pub fn fetch_all_refs_if_needed(opts: &Options) {
         .arg("-q")
         .arg("--prune")
         .arg("--update-head-ok")
+        .arg("--no-tags")
         .arg("--refmap")
         .arg("")
         .arg("origin")
-        .arg("+refs/*:refs/*")
+        .args(refspecs)
         .status();
+    let refs = gitutil::get_all_refs(&opts.source).ok()?;
+    Some(
+        refs.keys()
+            .filter(|name| {
+                refspecs
+                    .iter()
+                    .any(|spec| refspec_destination_matches(spec, name))
+            })
+            .count(),
+    )
+}
+
+fn list_remotes(repo: &std::path::Path) -> Vec<String> {
+    match Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("remote")
+        .output()
+    {
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
 #[allow(dead_code)]
 pub fn migrate_origin_to_heads(opts: &Options) -> io::Result<()> {
-    if opts.partial || opts.dry_run {
+    if opts.dry_run || (opts.partial && opts.keep_remotes.is_empty()) {
         return Ok(());
     }
-    // List refs under refs/remotes/origin/*
+    // List refs under refs/remotes/*
     let refs = match gitutil::get_all_refs(&opts.source) {
         Ok(refs) => refs,
         Err(_) => return Ok(()),
     };
+    // Tracking refs of other remotes dropped by --keep-remote are deleted outright
+    let dropped_prefixes: Vec<String> = if opts.partial {
+        list_remotes(&opts.source)
+            .into_iter()
+            .filter(|name| name != "origin" && opts.cleans_up_remote(name))
+            .map(|name| format!("refs/remotes/{}/", name))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let migrate_origin = opts.cleans_up_remote("origin");
     let mut to_create: Vec<(String, String)> = Vec::new();
     let mut to_delete: Vec<(String, String)> = Vec::new();
     for (refname, hash) in refs
         .iter()
-        .filter(|(name, _)| name.starts_with("refs/remotes/origin/"))
+        .filter(|(name, _)| name.starts_with("refs/remotes/"))
     {
         let hash = hash.clone();
+        if dropped_prefixes.iter().any(|p| refname.starts_with(p)) {
+            to_delete.push((refname.clone(), hash));
+            continue;
+        }
+        if !migrate_origin || !refname.starts_with("refs/remotes/origin/") {
+            continue;
+        }
         if refname == "refs/remotes/origin/HEAD" {
             to_delete.push((refname.clone(), hash));
             continue;
@@ -99,42 +218,37 @@ pub fn migrate_origin_to_heads(opts: &Options) -> io::Result<()> {
     Ok(())
 }
 
-pub fn remove_origin_remote_if_applicable(opts: &Options) {
-    if opts.sensitive || opts.partial || opts.dry_run {
+pub fn remove_remotes_if_applicable(opts: &Options) {
+    if opts.sensitive || opts.dry_run {
         return;
     }
-    // Check that origin exists
-    let remotes = Command::new("git")
-        .arg("-C")
-        .arg(&opts.target)
-        .arg("remote")
-        .output();
-    if let Ok(out) = remotes {
-        if !out.status.success() {
-            return;
-        }
-        let r = String::from_utf8_lossy(&out.stdout);
-        if !r.lines().any(|l| l.trim() == "origin") {
-            return;
+    for remote in list_remotes(&opts.target) {
+        if opts.cleans_up_remote(&remote) {
+            remove_remote(opts, &remote);
         }
-    } else {
-        return;
     }
+}
+
+fn remove_remote(opts: &Options, remote: &str) {
     // Print URL for context if available
-    let url = GitConfig::get_string_config(&opts.target, "remote.origin.url")
+    let url = GitConfig::get_string_config(&opts.target, &format!("remote.{}.url", remote))
         .ok()
         .and_then(|value| value)
         .unwrap_or_default();
-    if url.is_empty() {
-        eprintln!("NOTICE: Removing 'origin' remote; see docs if you want to push back there.");
+    let notice = if url.is_empty() {
+        format!(
+            "NOTICE: Removing '{}' remote; see docs if you want to push back there.",
+            remote
+        )
     } else {
-        eprintln!("NOTICE: Removing 'origin' remote (was: {})", url);
-    }
+        format!("NOTICE: Removing '{}' remote (was: {})", remote, url)
+    };
+    events::emit(opts, Event::Notice(notice));
     let _ = Command::new("git")
         .arg("-C")
         .arg(&opts.target)
         .arg("remote")
         .arg("rm")
-        .arg("origin")
+        .arg(remote)
         .status();
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::gitutil;
+use crate::opts::Options;
+
+const NOTES_COMMIT_MESSAGE: &str = "Notes rewritten by filter-repo-rs\n";
+
+/// Result of re-attaching notes for a single `refs/notes/*` ref.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NotesRewrite {
+    pub refname: String,
+    pub moved: usize,
+    pub dropped: usize,
+}
+
+/// Re-attach every note under `refs/notes/*` from its original commit to the
+/// rewritten one, using the finished commit-map (`None` marks pruned commits).
+///
+/// Notes refs are kept out of the main export when `--rewrite-notes` is set,
+/// so each one still points at its pre-filter notes tree here. A single
+/// fast-import commit per ref replaces that tree with the remapped entries.
+pub fn rewrite_notes(
+    opts: &Options,
+    commit_map: &HashMap<Vec<u8>, Option<Vec<u8>>>,
+) -> io::Result<Vec<NotesRewrite>> {
+    let refs = gitutil::get_all_refs(&opts.target)?;
+    let mut notes_refs: Vec<(&String, &String)> = refs
+        .iter()
+        .filter(|(name, _)| {
+            name.starts_with("refs/notes/") && !opts.is_protected_ref(name.as_bytes())
+        })
+        .collect();
+    notes_refs.sort();
+
+    let mut results = Vec::new();
+    for (refname, tip) in notes_refs {
+        let mut result = NotesRewrite {
+            refname: refname.clone(),
+            ..NotesRewrite::default()
+        };
+        let mut entries: Vec<(String, String)> = Vec::new();
+        for (note, object) in list_notes(opts, refname)? {
+            match commit_map.get(object.as_bytes()) {
+                Some(Some(new_)) => {
+                    let new_ = String::from_utf8_lossy(new_).into_owned();
+                    if new_ != object {
+                        result.moved += 1;
+                    }
+                    entries.push((note, new_));
+                }
+                Some(None) => result.dropped += 1,
+                // Notes on objects outside the export (blobs, excluded refs) stay put
+                None => entries.push((note, object)),
+            }
+        }
+        if result.moved == 0 && result.dropped == 0 {
+            continue;
+        }
+        write_notes_commit(opts, refname, tip, &entries)?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn list_notes(opts: &Options, refname: &str) -> io::Result<Vec<(String, String)>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&opts.target)
+        .arg("notes")
+        .arg(format!("--ref={}", refname))
+        .arg("list")
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "failed to list notes in {}",
+            refname
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut it = line.split_whitespace();
+            Some((it.next()?.to_string(), it.next()?.to_string()))
+        })
+        .collect())
+}
+
+fn committer_ident(opts: &Options) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&opts.target)
+        .arg("var")
+        .arg("GIT_COMMITTER_IDENT")
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            "failed to determine committer identity for rewritten notes",
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn write_notes_commit(
+    opts: &Options,
+    refname: &str,
+    tip: &str,
+    entries: &[(String, String)],
+) -> io::Result<()> {
+    let mut stream = Vec::new();
+    stream.extend_from_slice(format!("commit {}\n", refname).as_bytes());
+    stream.extend_from_slice(format!("committer {}\n", committer_ident(opts)?).as_bytes());
+    stream.extend_from_slice(format!("data {}\n", NOTES_COMMIT_MESSAGE.len()).as_bytes());
+    stream.extend_from_slice(NOTES_COMMIT_MESSAGE.as_bytes());
+    stream.extend_from_slice(format!("from {}\n", tip).as_bytes());
+    stream.extend_from_slice(b"deleteall\n");
+    for (note, object) in entries {
+        stream.extend_from_slice(format!("N {} {}\n", note, object).as_bytes());
+    }
+    stream.extend_from_slice(b"\n");
+
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(&opts.target)
+        .arg("fast-import")
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut sin) = child.stdin.take() {
+        sin.write_all(&stream)?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "failed to rewrite notes in {}: fast-import exited with {}",
+            refname, status
+        )));
+    }
+    Ok(())
+}
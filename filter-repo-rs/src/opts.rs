@@ -2,17 +2,27 @@ use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
 use regex::bytes::Regex;
 use serde::Deserialize;
 
+use crate::analysis::Severity;
+use crate::completions::{self, CompletionFlag, Shell};
+use crate::error::FilterRepoError;
+use crate::events::EventSink;
+use crate::git_config::GitConfig;
 use crate::gitutil::{self, GitCapabilities};
+use crate::logging::LogLevel;
+use crate::pathutil::IgnoreRules;
 
 /// Stage-3 toggle: set to `false` to error out instead of accepting legacy cleanup syntax.
 const LEGACY_CLEANUP_SYNTAX_ALLOWED: bool = true;
 /// Stage-3 toggle: set to `false` to disable legacy --analyze-*-warn overrides entirely.
 const LEGACY_ANALYZE_THRESHOLD_FLAGS_ALLOWED: bool = true;
+/// Default for `--max-in-memory-blob-size`.
+const DEFAULT_MAX_IN_MEMORY_BLOB_BYTES: usize = 128 * 1024 * 1024;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -22,11 +32,99 @@ pub enum CleanupMode {
     Aggressive,
 }
 
+/// Policy for commits left without file changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneEmpty {
+    /// Prune every empty non-merge commit, including ones that were empty originally.
+    Always,
+    /// Prune commits that became empty through filtering; keep originally empty ones.
+    Auto,
+    /// Never prune empty commits.
+    Never,
+}
+
+/// Line-ending conversion applied to text blobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EolMode {
+    /// Convert CRLF to LF.
+    Lf,
+    /// Convert LF (and CRLF) to CRLF.
+    CrLf,
+}
+
+/// What happens to `refs/replace/*` (see git-replace(1)), mirroring
+/// git-filter-repo's `--replace-refs`. History is always exported without
+/// applying replacements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplaceRefsPolicy {
+    /// Delete existing replace refs, then add `refs/replace/<old>` -> new
+    /// for every rewritten commit (`delete-and-add`).
+    Delete,
+    /// Delete existing replace refs and add none.
+    DeleteNoAdd,
+    /// Carry existing replace refs over to the rewritten commits; add none.
+    UpdateNoAdd,
+    /// Carry existing replace refs over, and add `refs/replace/<old>` -> new
+    /// for rewritten commits that do not already have one.
+    UpdateOrAdd,
+}
+
+/// What to do with commit and tag signatures, which rewriting invalidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Drop signatures.
+    Strip,
+    /// Fail the run at the first signed object.
+    Abort,
+    /// Keep signatures on objects that come out unchanged; strip the rest.
+    Keep,
+}
+
+/// What to do with paths Windows cannot check out (`aux/`, `a:b`, a trailing
+/// dot), judged after every path rename. Without a policy such paths are
+/// only sanitized when running on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowsPathPolicy {
+    /// Rewrite them to valid names; fail if two paths of one commit end up
+    /// with the same name.
+    Sanitize,
+    /// Drop them from the commits that touch them.
+    Skip,
+    /// Fail the run at the first one.
+    Error,
+}
+
+/// What to do when path renames send two different paths of one commit to
+/// the same destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// Fail the run, naming both source paths.
+    #[default]
+    Error,
+    /// Keep the path listed first in the commit and drop the other.
+    KeepFirst,
+    /// Keep the path listed last in the commit and drop the other.
+    KeepLast,
+}
+
+/// A `--strip-trailer` pattern.
+#[derive(Debug, Clone)]
+pub enum TrailerPattern {
+    /// A trailer key, compared case-insensitively (`Signed-off-by`).
+    Key(Vec<u8>),
+    /// A regex matched against the whole `Key: value` line.
+    Regex(Regex),
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Filter,
     Analyze,
+    /// Run the sanity checks only and report each result (`--check`).
+    Check,
+    /// Print the commit-map counterparts of `Options::map_lookup` and exit.
+    MapLookup,
 }
 
 #[allow(dead_code)]
@@ -61,37 +159,177 @@ impl Default for AnalyzeThresholds {
     }
 }
 
+/// Curated threshold presets for `--analyze-threshold-profile`. A profile
+/// seeds every threshold; values set explicitly (config file, environment,
+/// CLI flags) still win.
+///
+/// | threshold               | `Strict` | `Default` | `Lenient` |
+/// |-------------------------|----------|-----------|-----------|
+/// | `warn_total_bytes`      | 512 MiB  | 1 GiB     | 4 GiB     |
+/// | `crit_total_bytes`      | 2 GiB    | 5 GiB     | 20 GiB    |
+/// | `warn_blob_bytes`       | 1 MiB    | 10 MiB    | 100 MiB   |
+/// | `warn_ref_count`        | 5,000    | 20,000    | 100,000   |
+/// | `warn_object_count`     | 2M       | 10M       | 50M       |
+/// | `warn_tree_entries`     | 1,000    | 2,000     | 10,000    |
+/// | `warn_path_length`      | 150      | 200       | 400       |
+/// | `warn_duplicate_paths`  | 100      | 1,000     | 10,000    |
+/// | `warn_commit_msg_bytes` | 4,000    | 10,000    | 100,000   |
+/// | `warn_max_parents`      | 2        | 8         | 32        |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdProfile {
+    Strict,
+    Default,
+    Lenient,
+}
+
+impl ThresholdProfile {
+    pub fn thresholds(self) -> AnalyzeThresholds {
+        match self {
+            ThresholdProfile::Strict => AnalyzeThresholds {
+                warn_total_bytes: 512 * 1024 * 1024,
+                crit_total_bytes: 2 * 1024 * 1024 * 1024,
+                warn_blob_bytes: 1024 * 1024,
+                warn_ref_count: 5_000,
+                warn_object_count: 2_000_000,
+                warn_tree_entries: 1_000,
+                warn_path_length: 150,
+                warn_duplicate_paths: 100,
+                warn_commit_msg_bytes: 4_000,
+                warn_max_parents: 2,
+            },
+            ThresholdProfile::Default => AnalyzeThresholds::default(),
+            ThresholdProfile::Lenient => AnalyzeThresholds {
+                warn_total_bytes: 4 * 1024 * 1024 * 1024,
+                crit_total_bytes: 20 * 1024 * 1024 * 1024,
+                warn_blob_bytes: 100 * 1024 * 1024,
+                warn_ref_count: 100_000,
+                warn_object_count: 50_000_000,
+                warn_tree_entries: 10_000,
+                warn_path_length: 400,
+                warn_duplicate_paths: 10_000,
+                warn_commit_msg_bytes: 100_000,
+                warn_max_parents: 32,
+            },
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "strict" => Some(ThresholdProfile::Strict),
+            "default" => Some(ThresholdProfile::Default),
+            "lenient" => Some(ThresholdProfile::Lenient),
+            _ => None,
+        }
+    }
+}
+
+/// Output renderer for analysis mode (`json` takes precedence when set).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyzeFormat {
+    Human,
+    Html,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct AnalyzeConfig {
     pub json: bool,
+    /// Written to `.git/filter-repo/analysis.html` when set to `Html`.
+    pub format: AnalyzeFormat,
     pub top: usize,
+    /// Preset the thresholds were seeded from, if any.
+    pub profile: Option<ThresholdProfile>,
     pub thresholds: AnalyzeThresholds,
+    /// Warnings below this severity are dropped from the report.
+    pub min_severity: Severity,
+    /// Revision range (e.g. `main..feature`) limiting the history walk.
+    pub range: Option<String>,
+    /// Break blob counts and sizes down by file extension.
+    pub extensions: bool,
+    /// Fetch every ref from origin before analyzing (skipped with `--no-fetch`).
+    pub fetch_first: bool,
+    /// Write every blob seen in history to this file as NDJSON
+    /// (`{"oid", "size", "path"}` per line), regardless of `top`.
+    pub dump_blobs: Option<PathBuf>,
 }
 
 impl Default for AnalyzeConfig {
     fn default() -> Self {
         Self {
             json: false,
+            format: AnalyzeFormat::Human,
             top: 10,
+            profile: None,
             thresholds: AnalyzeThresholds::default(),
+            min_severity: Severity::Info,
+            range: None,
+            extensions: false,
+            fetch_first: false,
+            dump_blobs: None,
         }
     }
 }
 
 #[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct FileAnalyzeConfig {
     json: Option<bool>,
+    format: Option<AnalyzeFormat>,
     top: Option<usize>,
+    min_severity: Option<Severity>,
+    profile: Option<ThresholdProfile>,
     thresholds: Option<AnalyzeThresholdOverrides>,
 }
 
+/// `[filter]` table of the config file; CLI flags take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileFilterConfig {
+    paths: Option<Vec<String>>,
+    path_globs: Option<Vec<String>>,
+    /// `OLD:NEW` entries, as for `--path-rename`.
+    path_renames: Option<Vec<String>>,
+    max_blob_size: Option<ConfigSize>,
+    replace_text: Option<ConfigRules>,
+    replace_message: Option<PathBuf>,
+    backup: Option<bool>,
+    backup_path: Option<PathBuf>,
+}
+
+/// Replacement rules given either as a file reference or inline, one rule
+/// per entry in `--replace-text` syntax.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ConfigRules {
+    File(PathBuf),
+    Inline(Vec<String>),
+}
+
+/// Byte count given either as an integer or as a string with a K/M/G suffix.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ConfigSize {
+    Bytes(u64),
+    Text(String),
+}
+
 #[derive(Debug, Default, Deserialize)]
-struct FileConfig {
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
     analyze: Option<FileAnalyzeConfig>,
+    filter: Option<FileFilterConfig>,
+}
+
+/// Path bytes from a config string: UTF-8 text with C-style backslash escapes
+/// (`\ooo` octal for raw bytes, as in git's quoted paths).
+fn decode_config_bytes(s: &str) -> Vec<u8> {
+    crate::pathutil::dequote_c_style_bytes(s.as_bytes())
 }
 
 #[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct AnalyzeThresholdOverrides {
     warn_total_bytes: Option<u64>,
     crit_total_bytes: Option<u64>,
@@ -147,6 +385,42 @@ impl AnalyzeOverrides {
     }
 }
 
+/// Result of a path callback for a single filechange path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathDecision {
+    Keep,
+    Drop,
+    Rename(Vec<u8>),
+}
+
+type PathCallbackFn = dyn FnMut(&[u8]) -> PathDecision + Send;
+
+/// Library hook consulted for every filechange path before the declarative
+/// `paths`/`path_globs`/`path_regexes` filters and `path_renames`.
+///
+/// The callback sees dequoted path bytes. `Drop` removes the filechange outright;
+/// `Keep` and `Rename` hand the (possibly renamed) path on to the declarative
+/// filters, which still apply afterwards.
+#[derive(Clone)]
+pub struct PathCallback(Arc<Mutex<Box<PathCallbackFn>>>);
+
+impl PathCallback {
+    pub fn new(f: impl FnMut(&[u8]) -> PathDecision + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(f))))
+    }
+
+    pub fn decide(&self, path: &[u8]) -> PathDecision {
+        let mut f = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        f(path)
+    }
+}
+
+impl std::fmt::Debug for PathCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PathCallback(..)")
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct Options {
@@ -154,22 +428,174 @@ pub struct Options {
     pub target: PathBuf,
     pub refs: Vec<String>,
     pub date_order: bool,
+    /// Rename detection for fast-export (`-M<N>%`); the similarity threshold is 0–100.
+    pub detect_renames: Option<u32>,
+    /// Copy detection for fast-export (`-C` with `--find-copies-harder`).
+    pub detect_copies: bool,
     pub no_data: bool,
+    /// Extra arguments appended to the `git fast-export` command line.
+    pub fe_args: Vec<String>,
+    /// Extra arguments appended to the `git fast-import` command line.
+    pub fi_args: Vec<String>,
     pub quiet: bool,
+    /// Report the number of parsed commits on stderr while filtering.
+    pub progress: bool,
     pub reset: bool,
     pub replace_message_file: Option<PathBuf>,
+    /// Replacement rules applied to annotated tag messages only (same format as `--replace-message`).
+    pub replace_tag_message_file: Option<PathBuf>,
+    /// Convert annotated tags to lightweight tags pointing at their (rewritten) target.
+    pub lightweight_tags: bool,
+    /// Promote lightweight tags to annotated tags with this message template
+    /// (`%(tag)` expands to the tag name), tagged by the current committer.
+    pub annotate_tags: Option<Vec<u8>>,
+    /// Regex rewrites (`PATTERN==>REPLACEMENT`) of author, committer and
+    /// tagger names and emails, applied in order.
+    pub replace_identity: Vec<(Regex, String)>,
+    /// Reflow commit message bodies (never the subject) to this many columns.
+    pub wrap_message_body: Option<usize>,
+    /// Cut commit and annotated tag messages longer than this many bytes,
+    /// ending them with a `...` line.
+    pub max_commit_message_bytes: Option<usize>,
+    /// Trailer appended to the messages of rewritten commits (`{old_oid}`,
+    /// `{new_mark}` and `{date}` are expanded); every commit with
+    /// `message_annotate_all`.
+    pub message_annotate: Option<Vec<u8>>,
+    pub message_annotate_all: bool,
+    /// Trailers removed from commit messages (`--strip-trailer`).
+    pub strip_trailers: Vec<TrailerPattern>,
+    /// Rewrite abbreviated commit ids in messages to their rewritten ids.
+    pub update_hash_mentions: bool,
     pub replace_text_file: Option<PathBuf>,
+    /// Inline `--replace-text` rules (from the config file), applied after the file's.
+    pub replace_text_rules: Vec<Vec<u8>>,
+    /// Match `--replace-text` rules against UTF-16 blobs (detected by their
+    /// BOM) as UTF-8 text, writing the result back in the original encoding.
+    pub decode_utf16_for_replace: bool,
+    /// Let `--replace-text` rewrite symlink targets too; by default links
+    /// keep their original target.
+    pub rewrite_symlink_targets: bool,
+    /// Worker threads for `--replace-text` blob rewriting; 1 keeps it serial.
+    pub jobs: usize,
     pub paths: Vec<Vec<u8>>,
     pub invert_paths: bool,
     pub path_globs: Vec<Vec<u8>>,
     pub path_regexes: Vec<Regex>,
+    /// `.filterrepoignore`-style file of gitignore patterns (with `!` negation)
+    /// whose matches are dropped from every commit.
+    pub ignore_file: Option<PathBuf>,
+    /// Patterns loaded from `ignore_file`; filled in when the run starts.
+    pub ignore_rules: Option<IgnoreRules>,
     pub path_renames: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Apply `path_renames` to the `path`/`url` entries of `.gitmodules` blobs.
+    pub rewrite_gitmodules: bool,
+    /// Handling of paths that are invalid on Windows, on any host.
+    pub windows_path_policy: Option<WindowsPathPolicy>,
+    /// Handling of path renames that send two paths of a commit to one destination.
+    pub on_rename_collision: CollisionPolicy,
+    pub path_callback: Option<PathCallback>,
+    /// Library hook receiving structured progress events; `None` prints to the console.
+    pub event_sink: Option<Arc<dyn EventSink>>,
     pub tag_rename: Option<(Vec<u8>, Vec<u8>)>,
+    /// Exact tag renames (short names) from `--tag-rename-file`, checked
+    /// before the `tag_rename` prefix rule.
+    pub tag_rename_map: Vec<(Vec<u8>, Vec<u8>)>,
     pub branch_rename: Option<(Vec<u8>, Vec<u8>)>,
+    /// Rename tags with the `branch_rename` prefix rule too, unless
+    /// `tag_rename` is set.
+    pub mirror_branch_rename_to_tags: bool,
+    /// File of `old-ref new-ref` lines; each listed ref is renamed as a whole
+    /// after import, overriding `tag_rename`/`branch_rename` for that ref.
+    pub ref_rename_file: Option<PathBuf>,
+    /// Rewrite whole-word mentions of renamed tags/branches in messages.
+    pub update_message_refs: bool,
+    /// Ref globs that must never be rewritten (`release/*` or `refs/heads/release/*`).
+    pub protect_refs: Vec<Vec<u8>>,
+    /// Save the pre-rewrite value of every changed ref under `refs/original/`.
+    pub preserve_original_refs: bool,
+    /// Handling of `refs/replace/*` after the rewrite.
+    pub replace_refs_policy: ReplaceRefsPolicy,
+    /// Handling of `gpgsig` commit signatures.
+    pub signed_commits: SignaturePolicy,
+    /// Handling of signatures in annotated tag messages.
+    pub signed_tags: SignaturePolicy,
+    /// Shell command run with the tag name and target id for every rewritten
+    /// annotated tag, so it can re-sign it.
+    pub tag_sign_cmd: Option<String>,
+    /// Re-attach notes under `refs/notes/*` to the rewritten commits.
+    pub rewrite_notes: bool,
     pub max_blob_size: Option<usize>,
+    /// Blobs larger than this are never loaded into memory: `--replace-text`
+    /// and `--normalize-eol` skip them and they are copied through as read.
+    pub max_in_memory_blob_bytes: usize,
+    /// Leave blobs no content filter changed out of the import and name them
+    /// by their original id, which the (same) repository already stores.
+    pub no_rewrite_if_unchanged: bool,
+    /// Load all blob sizes with one `cat-file --batch-all-objects` pass and
+    /// share them between analysis and the size/strip-by-id filters.
+    pub size_cache: bool,
+    /// Normalize line endings in text blobs before `--replace-text` runs.
+    pub normalize_eol: Option<EolMode>,
     pub strip_blobs_with_ids: Option<PathBuf>,
+    /// Fail on `--strip-blobs-with-ids` lines that are neither an id nor an
+    /// abbreviation of one, instead of skipping them.
+    pub strict_blob_ids: bool,
+    /// Blob ids (same list format) that are never stripped, whatever their
+    /// size or `--strip-blobs-with-ids` say.
+    pub keep_blobs_with_ids: Option<PathBuf>,
+    /// Strip every blob in the object store larger than this many bytes,
+    /// found by the analysis size scan, as if listed in `--strip-blobs-with-ids`.
+    pub strip_oversized_from_analysis: Option<usize>,
+    /// Globs of paths dropped from a commit when another path of that commit
+    /// adds the same blob.
+    pub delete_duplicate_paths: Vec<Vec<u8>>,
+    /// Drop a directory's `.gitignore`/`.gitattributes` from a commit once
+    /// filtering removed every other path the commit touched under it.
+    pub prune_stale_attribute_files: bool,
+    /// File of exact paths (one per line, C-style quoting allowed) whose
+    /// filechanges are removed from every commit.
+    pub strip_paths_from_file: Option<PathBuf>,
+    /// File modes (`100755`, `120000`, `160000`) whose `M` filechanges are
+    /// dropped, limited to paths matching `strip_file_modes_globs` if any.
+    pub strip_file_modes: Vec<Vec<u8>>,
+    pub strip_file_modes_globs: Vec<Vec<u8>>,
     pub write_report: bool,
+    /// Record in the report which commits kept their id and which changed,
+    /// to confirm that a filtering setup is a no-op.
+    pub verify_hash_stability: bool,
+    /// List `--replace-text` rules that matched no blob in report.txt.
+    pub report_unused_replace_rules: bool,
+    /// Make repeated runs reproducible: refuse clock-dependent options and
+    /// keep reports and ref updates in a stable order.
+    pub deterministic: bool,
+    /// Where run artifacts go instead of `.git/filter-repo` (relative to the target repo).
+    pub state_dir: Option<PathBuf>,
+    /// Reuse the previous run's marks (with its commit-map) so only objects
+    /// new since then are exported and imported.
+    pub incremental: bool,
+    /// Also write `.git/filter-repo/parent-map` with each rewritten commit's new parents.
+    pub write_parent_map: bool,
+    /// Print the refs a run created, deleted or retargeted once it finishes.
+    pub show_ref_changes: bool,
     pub cleanup: CleanupMode,
+    pub prune_empty: PruneEmpty,
+    pub prune_empty_in_range: Option<String>,
+    /// Keep commits that would be pruned as empty, with this message instead.
+    pub empty_commit_tombstone: Option<Vec<u8>>,
+    /// Drop commits whose author email matches one of these, replaying
+    /// their changes onto their children.
+    pub strip_commits_by_author: Vec<Regex>,
+    /// Let `strip_commits_by_author` drop merge commits too.
+    pub strip_merges_too: bool,
+    /// Put the merge parent that continues a branch's previous tip first.
+    pub canonicalize_merge_parents: bool,
+    /// Fold runs of linear commits by one author email, each committed at
+    /// most this many seconds after the one before, into a single commit.
+    pub squash_consecutive_by_author: Option<u64>,
+    /// Only filter commits whose committer date (epoch seconds) is at or
+    /// after `commit_date_from` and at or before `commit_date_to`.
+    pub commit_date_from: Option<i64>,
+    pub commit_date_to: Option<i64>,
     pub reencode: bool,
     pub reencode_requested: Option<bool>,
     pub quotepath: bool,
@@ -178,15 +604,46 @@ pub struct Options {
     pub fe_stream_override: Option<PathBuf>,
     pub force: bool,
     pub enforce_sanity: bool,
+    /// Preflight checks (ids from `--list-checks`) left out by `--skip-check`.
+    pub skip_checks: Vec<String>,
+    /// Answer "yes" to the in-place rewrite and continue-after-an-old-run
+    /// prompts without asking.
+    pub assume_yes: bool,
+    /// Answer "no" to those prompts without asking.
+    pub assume_no: bool,
+    /// Never prompt (`FRRS_NONINTERACTIVE`); prompts are answered "no"
+    /// unless `assume_yes` is set.
+    pub non_interactive: bool,
     pub dry_run: bool,
     pub partial: bool,
+    /// Remotes kept in `--partial` mode; when non-empty, other remotes are cleaned up.
+    pub keep_remotes: Vec<String>,
     pub sensitive: bool,
+    /// Apply `--replace-text` rules to commit and tag messages as well.
+    /// `None` means enabled when both `--sensitive` and `--replace-text` are set.
+    pub sensitive_apply_to_messages: Option<bool>,
+    /// Refspecs fetched from origin in sensitive mode; empty fetches `+refs/*:refs/*`.
+    pub sensitive_refspecs: Vec<String>,
     pub no_fetch: bool,
     pub backup: bool,
     pub backup_path: Option<PathBuf>,
+    /// With `--backup`, bundle the original refs only after the rewrite has
+    /// changed at least one ref.
+    pub skip_backup_on_noop: bool,
+    /// Kill `git fast-export`/`git fast-import` and fail the run when they
+    /// have not finished after this long.
+    pub pipeline_timeout: Option<Duration>,
+    /// Kill them and fail the run when the fast-export stream has not moved
+    /// for this long; `None` waits forever.
+    pub subprocess_timeout: Option<Duration>,
     pub mode: Mode,
+    /// Old or new commit id (full or a unique prefix) for `--map-lookup`.
+    pub map_lookup: Option<String>,
     pub analyze: AnalyzeConfig,
     pub debug_mode: bool,
+    /// Diagnostics level (`--log-level`/`FRRS_LOG`); `None` means `debug` in
+    /// debug mode and `warn` otherwise.
+    pub log_level: Option<LogLevel>,
     pub git_caps: GitCapabilities,
 }
 
@@ -197,22 +654,87 @@ impl Default for Options {
             target: PathBuf::from("."),
             refs: vec!["--all".to_string()],
             date_order: false,
+            detect_renames: None,
+            detect_copies: false,
             no_data: false,
+            fe_args: Vec::new(),
+            fi_args: Vec::new(),
             quiet: false,
+            progress: false,
             reset: true,
             replace_message_file: None,
+            replace_tag_message_file: None,
+            lightweight_tags: false,
+            annotate_tags: None,
+            replace_identity: Vec::new(),
+            wrap_message_body: None,
+            max_commit_message_bytes: None,
+            message_annotate: None,
+            message_annotate_all: false,
+            strip_trailers: Vec::new(),
+            update_hash_mentions: true,
             replace_text_file: None,
+            replace_text_rules: Vec::new(),
+            decode_utf16_for_replace: false,
+            rewrite_symlink_targets: false,
+            jobs: crate::blob_pool::default_jobs(),
             paths: Vec::new(),
             invert_paths: false,
             path_globs: Vec::new(),
             path_regexes: Vec::new(),
+            ignore_file: None,
+            ignore_rules: None,
             path_renames: Vec::new(),
+            rewrite_gitmodules: false,
+            windows_path_policy: None,
+            on_rename_collision: CollisionPolicy::Error,
+            path_callback: None,
+            event_sink: None,
             tag_rename: None,
+            mirror_branch_rename_to_tags: false,
+            tag_rename_map: Vec::new(),
             branch_rename: None,
+            ref_rename_file: None,
+            update_message_refs: false,
+            protect_refs: Vec::new(),
+            preserve_original_refs: false,
+            replace_refs_policy: ReplaceRefsPolicy::DeleteNoAdd,
+            signed_commits: SignaturePolicy::Strip,
+            signed_tags: SignaturePolicy::Strip,
+            tag_sign_cmd: None,
+            rewrite_notes: false,
+            normalize_eol: None,
             max_blob_size: None,
+            max_in_memory_blob_bytes: DEFAULT_MAX_IN_MEMORY_BLOB_BYTES,
+            no_rewrite_if_unchanged: false,
+            size_cache: true,
             strip_blobs_with_ids: None,
+            strict_blob_ids: false,
+            keep_blobs_with_ids: None,
+            strip_oversized_from_analysis: None,
+            delete_duplicate_paths: Vec::new(),
+            prune_stale_attribute_files: false,
+            strip_paths_from_file: None,
+            strip_file_modes: Vec::new(),
+            strip_file_modes_globs: Vec::new(),
             write_report: false,
+            verify_hash_stability: false,
+            report_unused_replace_rules: false,
+            deterministic: false,
+            state_dir: None,
+            incremental: false,
+            write_parent_map: false,
+            show_ref_changes: false,
             cleanup: CleanupMode::None,
+            prune_empty: PruneEmpty::Always,
+            prune_empty_in_range: None,
+            empty_commit_tombstone: None,
+            strip_commits_by_author: Vec::new(),
+            commit_date_from: None,
+            commit_date_to: None,
+            strip_merges_too: false,
+            canonicalize_merge_parents: false,
+            squash_consecutive_by_author: None,
             reencode: true,
             reencode_requested: None,
             quotepath: true,
@@ -221,21 +743,132 @@ impl Default for Options {
             fe_stream_override: None,
             force: false,
             enforce_sanity: true,
+            skip_checks: Vec::new(),
+            assume_yes: false,
+            assume_no: false,
+            non_interactive: false,
             dry_run: false,
             partial: false,
+            keep_remotes: Vec::new(),
             sensitive: false,
+            sensitive_apply_to_messages: None,
+            sensitive_refspecs: Vec::new(),
             no_fetch: false,
             backup: false,
             backup_path: None,
+            skip_backup_on_noop: false,
+            pipeline_timeout: None,
+            subprocess_timeout: None,
             mode: Mode::Filter,
+            map_lookup: None,
             analyze: AnalyzeConfig::default(),
             debug_mode: false,
+            log_level: None,
             git_caps: GitCapabilities::default(),
         }
     }
 }
 
 impl Options {
+    /// New short name for tag `name`: an exact `tag_rename_map` entry wins
+    /// over the `tag_rename` prefix rule. `None` leaves the tag as is.
+    pub fn renamed_tag(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if let Some((_, new_)) = self.tag_rename_map.iter().find(|(old, _)| old == name) {
+            return Some(new_.clone());
+        }
+        let (old, new_) = self.tag_prefix_rename()?;
+        let rest = name.strip_prefix(old.as_slice())?;
+        Some([new_.as_slice(), rest].concat())
+    }
+
+    /// The tag prefix rule in effect: `tag_rename`, else `branch_rename`
+    /// with `mirror_branch_rename_to_tags`.
+    pub fn tag_prefix_rename(&self) -> Option<&(Vec<u8>, Vec<u8>)> {
+        self.tag_rename.as_ref().or(self
+            .branch_rename
+            .as_ref()
+            .filter(|_| self.mirror_branch_rename_to_tags))
+    }
+
+    /// New short name for branch `name` under the `branch_rename` prefix
+    /// rule. `None` leaves the branch as is.
+    pub fn renamed_branch(&self, name: &[u8]) -> Option<Vec<u8>> {
+        let (old, new_) = self.branch_rename.as_ref()?;
+        let rest = name.strip_prefix(old.as_slice())?;
+        Some([new_.as_slice(), rest].concat())
+    }
+
+    /// Whether `refname` matches a `--protect-ref` glob. Globs starting with
+    /// `refs/` match the full name; others match branch or tag short names.
+    pub fn is_protected_ref(&self, refname: &[u8]) -> bool {
+        self.protect_refs.iter().any(|glob| {
+            if glob.starts_with(b"refs/") {
+                return crate::pathutil::glob_match_bytes(glob, refname);
+            }
+            [b"refs/heads/".as_ref(), b"refs/tags/".as_ref()]
+                .iter()
+                .filter_map(|prefix| refname.strip_prefix(*prefix))
+                .any(|short| crate::pathutil::glob_match_bytes(glob, short))
+        })
+    }
+
+    /// Whether `refname` must be left out of the export: protected refs and,
+    /// with `--preserve-original-refs`, the saved `refs/original/*` refs.
+    pub fn is_excluded_from_export(&self, refname: &[u8]) -> bool {
+        self.is_protected_ref(refname)
+            || (self.preserve_original_refs && refname.starts_with(b"refs/original/"))
+            || (self.rewrite_notes && refname.starts_with(b"refs/notes/"))
+    }
+
+    /// Whether `remote` gets the full-mode treatment: its tracking refs are
+    /// migrated or dropped before the run and the remote is removed afterwards.
+    pub fn cleans_up_remote(&self, remote: &str) -> bool {
+        if self.partial {
+            !self.keep_remotes.is_empty() && !self.keep_remotes.iter().any(|r| r == remote)
+        } else {
+            remote == "origin"
+        }
+    }
+
+    /// Whether `--skip-check` names the preflight check `check`.
+    pub fn skips_check(&self, check: &str) -> bool {
+        self.skip_checks.iter().any(|c| c == check)
+    }
+
+    /// Whether blobs are stripped by id, from a list or from the size scan.
+    pub fn strips_blobs_by_id(&self) -> bool {
+        self.strip_blobs_with_ids.is_some() || self.strip_oversized_from_analysis.is_some()
+    }
+
+    /// Whether any `--replace-text` rules are configured (file or inline).
+    pub fn has_replace_text(&self) -> bool {
+        self.replace_text_file.is_some() || !self.replace_text_rules.is_empty()
+    }
+
+    /// `--replace-text` rule bytes: the file's lines followed by the inline
+    /// rules, or `None` when neither is set.
+    pub fn replace_text_source(&self) -> std::io::Result<Option<Vec<u8>>> {
+        if !self.has_replace_text() {
+            return Ok(None);
+        }
+        let mut content = match &self.replace_text_file {
+            Some(p) => fs::read(p)?,
+            None => Vec::new(),
+        };
+        for rule in &self.replace_text_rules {
+            if !content.is_empty() && !content.ends_with(b"\n") {
+                content.push(b'\n');
+            }
+            content.extend_from_slice(rule);
+        }
+        Ok(Some(content))
+    }
+
+    /// Whether `--replace-text` rules should also rewrite commit and tag messages.
+    pub fn replace_text_applies_to_messages(&self) -> bool {
+        self.has_replace_text() && self.sensitive_apply_to_messages.unwrap_or(self.sensitive)
+    }
+
     pub fn apply_git_capabilities(&mut self, caps: GitCapabilities) -> Result<(), String> {
         self.git_caps = caps;
 
@@ -257,6 +890,12 @@ impl Options {
             self.mark_tags = false;
         }
 
+        if self.signed_commits != SignaturePolicy::Strip
+            && !self.git_caps.fast_export_signed_commits
+        {
+            return Err("need git >= 2.50.0: git fast-export lacks --signed-commits".to_string());
+        }
+
         if self.sensitive && !self.git_caps.cat_file_batch_command {
             return Err(
                 "need git >= 2.36.0: --sensitive requires 'git cat-file --batch-command'"
@@ -271,6 +910,197 @@ impl Options {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn docs_example_config_populates_filter_options() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/examples/filter-repo-rs.toml");
+        let mut opts = Options {
+            debug_mode: true,
+            ..Options::default()
+        };
+        apply_config_from_file(&mut opts, &path).unwrap();
+
+        assert_eq!(opts.paths, vec![b"src/".to_vec(), b"docs/".to_vec()]);
+        assert_eq!(
+            opts.path_renames,
+            vec![(b"docs/".to_vec(), b"manual/".to_vec())]
+        );
+        assert_eq!(opts.max_blob_size, Some(10 * 1024 * 1024));
+        assert!(opts.backup);
+        assert_eq!(opts.replace_text_file, None);
+        assert_eq!(opts.analyze.top, 10);
+        assert_eq!(opts.analyze.thresholds.warn_blob_bytes, 10_485_760);
+    }
+
+    #[test]
+    fn config_bytes_and_replace_files_resolve() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("frrs.toml");
+        fs::write(
+            &path,
+            "[filter]\npaths = ['caf\\303\\251/']\nmax_blob_size = 2048\nreplace_text = 'rules.txt'\n",
+        )
+        .unwrap();
+        let mut opts = Options::default();
+        apply_config_from_file(&mut opts, &path).unwrap();
+
+        assert_eq!(opts.paths, vec!["café/".as_bytes().to_vec()]);
+        assert_eq!(opts.max_blob_size, Some(2048));
+        assert_eq!(opts.replace_text_file, Some(dir.path().join("rules.txt")));
+    }
+
+    #[test]
+    fn docs_example_commented_keys_are_valid() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/examples/filter-repo-rs.toml");
+        // Uncomment every `# key = value` line so the documented-but-disabled
+        // keys are checked against the schema too
+        let raw = fs::read_to_string(&path).unwrap();
+        let uncommented: String = raw
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(rest) if rest.split_whitespace().nth(1) == Some("=") => rest,
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let dir = tempfile::TempDir::new().unwrap();
+        let copy = dir.path().join("frrs.toml");
+        fs::write(&copy, uncommented).unwrap();
+        let mut opts = Options {
+            debug_mode: true,
+            ..Options::default()
+        };
+        apply_config_from_file(&mut opts, &copy).unwrap();
+
+        assert_eq!(opts.path_globs, vec![b"*.md".to_vec()]);
+        assert_eq!(opts.backup_path, Some(dir.path().join("backups/")));
+        assert_eq!(
+            opts.replace_text_file,
+            Some(dir.path().join("replacements.txt"))
+        );
+        assert_eq!(opts.analyze.format, AnalyzeFormat::Html);
+    }
+
+    #[test]
+    fn config_inline_replace_text_rules() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("frrs.toml");
+        fs::write(
+            &path,
+            "[filter]\nreplace_text = ['secret==>xxx', 'regex:tok_\\w+']\n",
+        )
+        .unwrap();
+        let mut opts = Options::default();
+        apply_config_from_file(&mut opts, &path).unwrap();
+
+        assert_eq!(opts.replace_text_file, None);
+        assert!(opts.has_replace_text());
+        assert_eq!(
+            opts.replace_text_source().unwrap().unwrap(),
+            b"secret==>xxx\nregex:tok_\\w+".to_vec()
+        );
+    }
+
+    fn env_lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn env_overrides_populate_analyze_config() {
+        let mut opts = Options {
+            debug_mode: true,
+            ..Options::default()
+        };
+        let env = env_lookup(&[
+            ("FRRS_WARN_BLOB_BYTES", "2_048"),
+            ("FRRS_WARN_COMMIT_MSG_BYTES", "512"),
+            ("FRRS_WARN_MAX_PARENTS", "3"),
+            ("FRRS_TOP", "7"),
+            ("FRRS_JSON", "1"),
+            ("FRRS_NONINTERACTIVE", "1"),
+        ]);
+        apply_env_overrides(&mut opts, env).unwrap();
+
+        assert_eq!(opts.analyze.thresholds.warn_blob_bytes, 2048);
+        assert_eq!(opts.analyze.thresholds.warn_commit_msg_bytes, 512);
+        assert_eq!(opts.analyze.thresholds.warn_max_parents, 3);
+        assert_eq!(opts.analyze.top, 7);
+        assert!(opts.analyze.json);
+        assert!(opts.non_interactive);
+    }
+
+    #[test]
+    fn env_overrides_reject_invalid_and_gated_values() {
+        let mut opts = Options {
+            debug_mode: true,
+            ..Options::default()
+        };
+        let err = apply_env_overrides(&mut opts, env_lookup(&[("FRRS_TOP", "ten")])).unwrap_err();
+        assert!(err.contains("FRRS_TOP"), "{err}");
+
+        let mut opts = Options::default();
+        let err = apply_env_overrides(&mut opts, env_lookup(&[("FRRS_WARN_MAX_PARENTS", "3")]))
+            .unwrap_err();
+        assert!(err.contains("FRRS_WARN_MAX_PARENTS"), "{err}");
+        assert!(err.contains("debug mode"), "{err}");
+        assert_eq!(
+            opts.analyze.thresholds.warn_max_parents,
+            AnalyzeThresholds::default().warn_max_parents
+        );
+    }
+
+    #[test]
+    fn threshold_profile_seeds_thresholds_under_explicit_values() {
+        let strict = ThresholdProfile::Strict.thresholds();
+        let default = ThresholdProfile::Default.thresholds();
+        assert!(strict.warn_blob_bytes < default.warn_blob_bytes);
+        assert!(strict.warn_commit_msg_bytes < default.warn_commit_msg_bytes);
+        assert_eq!(
+            default.warn_blob_bytes,
+            AnalyzeThresholds::default().warn_blob_bytes
+        );
+        assert!(ThresholdProfile::Lenient.thresholds().warn_max_parents > default.warn_max_parents);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("frrs.toml");
+        fs::write(
+            &path,
+            "[analyze]\nprofile = \"strict\"\n[analyze.thresholds]\nwarn_blob_bytes = 4096\n",
+        )
+        .unwrap();
+        let mut opts = Options {
+            debug_mode: true,
+            ..Options::default()
+        };
+        apply_config_from_file(&mut opts, &path).unwrap();
+        assert_eq!(opts.analyze.profile, Some(ThresholdProfile::Strict));
+        assert_eq!(opts.analyze.thresholds.warn_blob_bytes, 4096);
+        assert_eq!(
+            opts.analyze.thresholds.warn_commit_msg_bytes,
+            strict.warn_commit_msg_bytes
+        );
+    }
+
+    #[test]
+    fn config_unknown_key_is_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("frrs.toml");
+        fs::write(&path, "[filter]\nmax_blob_sise = 1024\n").unwrap();
+        let mut opts = Options::default();
+        match apply_config_from_file(&mut opts, &path) {
+            Err(ConfigError::Parse(err)) => {
+                assert!(err.to_string().contains("max_blob_sise"), "{err}")
+            }
+            other => panic!("expected parse error, got {other:?}"),
+        }
+    }
 
     #[test]
     fn apply_git_capabilities_disables_defaults() {
@@ -310,12 +1140,144 @@ mod tests {
             .expect_err("sensitive should require batch-command");
         assert!(err.contains("git >= 2.36.0"), "unexpected error: {err}");
     }
+
+    #[test]
+    fn protect_ref_matches_short_and_full_globs() {
+        let mut opts = Options::default();
+        opts.protect_refs = vec![b"release/*".to_vec(), b"refs/tags/v1.*".to_vec()];
+
+        assert!(opts.is_protected_ref(b"refs/heads/release/1.0"));
+        assert!(opts.is_protected_ref(b"refs/tags/release/2.0"));
+        assert!(opts.is_protected_ref(b"refs/tags/v1.2"));
+        assert!(!opts.is_protected_ref(b"refs/heads/main"));
+        assert!(!opts.is_protected_ref(b"refs/remotes/origin/release/1.0"));
+        assert!(!opts.is_protected_ref(b"refs/heads/v1.2"));
+    }
+
+    fn compat(args: &[&str]) -> Result<Vec<String>, String> {
+        translate_compat_args(args.iter().map(|a| a.to_string()).collect())
+    }
+
+    #[test]
+    fn compat_args_map_upstream_flags_onto_native_ones() {
+        assert_eq!(
+            compat(&[
+                "--compat-filter-repo",
+                "--path",
+                "src/",
+                "--path-glob=*.rs",
+                "--path-rename",
+                "old/:new/",
+                "--invert-paths",
+                "--strip-blobs-bigger-than",
+                "10M",
+                "--replace-text=rules.txt",
+                "--tag-rename",
+                "v:release-",
+                "-f",
+                "--dry-run",
+            ])
+            .unwrap(),
+            [
+                "--path",
+                "src/",
+                "--path-glob",
+                "*.rs",
+                "--path-rename",
+                "old/:new/",
+                "--invert-paths",
+                "--max-blob-size",
+                "10M",
+                "--replace-text",
+                "rules.txt",
+                "--tag-rename",
+                "v:release-",
+                "--force",
+                "--dry-run",
+            ]
+        );
+        assert_eq!(compat(&["--analyze"]).unwrap(), ["--analyze"]);
+    }
+
+    #[test]
+    fn compat_args_reject_flags_without_native_equivalent() {
+        assert_eq!(
+            compat(&["--mailmap", "m.txt"]).unwrap_err(),
+            "unsupported in compat mode: --mailmap"
+        );
+        assert_eq!(
+            compat(&["--max-blob-size=10"]).unwrap_err(),
+            "unsupported in compat mode: --max-blob-size"
+        );
+        assert_eq!(compat(&["--path"]).unwrap_err(), "--path requires a value");
+        assert_eq!(
+            compat(&["--force=yes"]).unwrap_err(),
+            "--force does not take a value"
+        );
+    }
+
+    #[test]
+    fn upstream_argv0_enables_compat_mode() {
+        assert!(is_upstream_argv0("/usr/libexec/git-core/git-filter-repo"));
+        assert!(is_upstream_argv0("git-filter-repo.exe"));
+        assert!(!is_upstream_argv0("filter-repo-rs"));
+    }
+
+    #[test]
+    fn flag_table_spellings_are_unique_and_cover_other_flag_lists() {
+        let mut seen = std::collections::HashSet::new();
+        for flag in FLAG_SECTIONS.iter().flat_map(|section| section.flags) {
+            for name in flag.spellings() {
+                assert!(seen.insert(name), "{name} is declared twice");
+            }
+        }
+        for name in ANALYZE_CONFLICTS {
+            assert!(find_flag(name).is_some(), "{name} is not a known flag");
+        }
+        for (upstream, native, takes_value) in COMPAT_FLAGS {
+            let flag = find_flag(native).unwrap_or_else(|| panic!("{native} is not a known flag"));
+            assert_eq!(flag.value.is_some(), *takes_value, "{upstream} arity");
+        }
+    }
+
+    #[test]
+    fn flag_table_renders_help_names_and_completion_choices() {
+        let name = |arg: &str| find_flag(arg).unwrap().help_name();
+        assert_eq!(name("--prune-empty"), "--prune-empty MODE");
+        assert_eq!(
+            name("--commits-to"),
+            "--commits-from SECONDS, --commits-to SECONDS"
+        );
+        assert_eq!(
+            name("--no-sensitive-apply-to-messages"),
+            "--[no-]sensitive-apply-to-messages"
+        );
+        assert!(find_flag("--ref").is_some());
+
+        let flags = completion_flags(false);
+        let prune = flags.iter().find(|f| f.names == ["--prune-empty"]).unwrap();
+        assert_eq!(prune.choices, ["always", "auto", "never"]);
+        assert!(flags.iter().all(|f| !f.names.contains(&"--ref".into())));
+        assert!(flags
+            .iter()
+            .all(|f| !f.names.contains(&"--date-order".into())));
+        assert!(completion_flags(true)
+            .iter()
+            .any(|f| f.names == ["--date-order"]));
+    }
 }
 
 #[allow(dead_code)]
 pub fn parse_args() -> Options {
     use std::env;
     let mut args: Vec<String> = env::args().skip(1).collect();
+    let invoked_as_upstream = env::args().next().is_some_and(|a| is_upstream_argv0(&a));
+    if invoked_as_upstream || args.iter().any(|a| a == "--compat-filter-repo") {
+        args = translate_compat_args(args).unwrap_or_else(|msg| {
+            eprintln!("error: {msg}");
+            std::process::exit(2);
+        });
+    }
     let mut config_override = env::var("FILTER_REPO_RS_CONFIG").ok().map(PathBuf::from);
 
     let mut idx = 0;
@@ -340,32 +1302,122 @@ pub fn parse_args() -> Options {
         idx += 1;
     }
 
+    // The threshold profile seeds the thresholds that the config file,
+    // environment and flags below may override
+    let mut profile = None;
+    let mut idx = 0;
+    while idx < args.len() {
+        let value = if args[idx] == "--analyze-threshold-profile" {
+            if idx + 1 >= args.len() {
+                eprintln!("error: --analyze-threshold-profile requires strict, default or lenient");
+                std::process::exit(2);
+            }
+            args.remove(idx + 1)
+        } else if let Some(v) = args[idx].strip_prefix("--analyze-threshold-profile=") {
+            v.to_string()
+        } else {
+            idx += 1;
+            continue;
+        };
+        args.remove(idx);
+        profile = Some(ThresholdProfile::parse(&value).unwrap_or_else(|| {
+            eprintln!(
+                "error: --analyze-threshold-profile expects strict, default or lenient, got '{value}'"
+            );
+            std::process::exit(2);
+        }));
+    }
+
     let mut opts = Options::default();
     opts.debug_mode = debug_mode_enabled(&args);
+    if let Some(profile) = profile {
+        opts.analyze.profile = Some(profile);
+        opts.analyze.thresholds = profile.thresholds();
+    }
+    let source = source_arg(&args);
+    apply_git_config_defaults(&mut opts, &source);
+    let config_target = match config_override {
+        Some(path) => (path, true),
+        None => (source.join(".filter-repo-rs.toml"), false),
+    };
+    load_config_or_exit(&mut opts, &config_target.0, config_target.1);
+    if let Err(msg) = apply_env_overrides(&mut opts, |name| env::var(name).ok()) {
+        eprintln!("error: {msg}");
+        std::process::exit(2);
+    }
+    // List-valued flags replace, rather than extend, the config file's lists
+    let config_paths = std::mem::take(&mut opts.paths);
+    let config_path_globs = std::mem::take(&mut opts.path_globs);
+    let config_path_renames = std::mem::take(&mut opts.path_renames);
+    // `--refs` replaces the default `--all`
+    let default_refs = std::mem::take(&mut opts.refs);
     let mut overrides = AnalyzeOverrides::default();
+    let mut filter_flags: Vec<String> = Vec::new();
     let mut it = args.into_iter();
     while let Some(arg) = it.next() {
+        if ANALYZE_CONFLICTS.contains(&arg.as_str()) && !filter_flags.contains(&arg) {
+            filter_flags.push(arg.clone());
+        }
+        // `--cleanup=MODE` is the legacy spelling of --cleanup
+        if let Some(value) = arg.strip_prefix("--cleanup=") {
+            if value.is_empty() {
+                eprintln!("--cleanup= requires a value of none|standard|aggressive");
+                std::process::exit(2);
+            }
+            parse_legacy_cleanup_value(value, &mut opts);
+            continue;
+        }
+        let Some(flag) = find_flag(&arg) else {
+            eprintln!("Unknown argument: {}", arg);
+            print_help(opts.debug_mode);
+            std::process::exit(2);
+        };
+        if flag.debug_only {
+            guard_debug(&arg, opts.debug_mode);
+        }
+        let value = match flag.value {
+            Some(placeholder) => it.next().unwrap_or_else(|| {
+                eprintln!("error: {arg} requires {placeholder}");
+                std::process::exit(2);
+            }),
+            None => String::new(),
+        };
         match arg.as_str() {
             "--analyze" => opts.mode = Mode::Analyze,
-            "--analyze-json" => {
+            "--check" => opts.mode = Mode::Check,
+            "--map-lookup" => {
+                opts.map_lookup = Some(value);
+                opts.mode = Mode::MapLookup;
+            }
+            "--analyze-json" | "--json" => {
                 opts.analyze.json = true;
                 overrides.json = Some(true);
             }
-            "--analyze-top" => {
-                let v = it.next().expect("--analyze-top requires COUNT");
-                let n = parse_usize(&v, "--analyze-top");
+            "--analyze-top" | "--top" => {
+                let n = parse_usize(&value, "--analyze-top");
                 let top = n.max(1);
                 opts.analyze.top = top;
                 overrides.top = Some(top);
             }
+            "--analyze-range" => {
+                opts.analyze.range = Some(value);
+            }
+            "--analyze-extensions" => {
+                opts.analyze.extensions = true;
+            }
+            "--analyze-fetch-first" => {
+                opts.analyze.fetch_first = true;
+            }
+            "--analyze-dump-blobs" => {
+                opts.analyze.dump_blobs = Some(PathBuf::from(value));
+            }
             "--analyze-total-warn" => {
                 enforce_legacy_analyze_flag_allowed("--analyze-total-warn", opts.debug_mode);
                 warn_legacy_analyze_threshold(
                     "--analyze-total-warn",
                     "analyze.thresholds.warn_total_bytes",
                 );
-                let v = it.next().expect("--analyze-total-warn requires BYTES");
-                let parsed = parse_u64(&v, "--analyze-total-warn");
+                let parsed = parse_u64(&value, "--analyze-total-warn");
                 opts.analyze.thresholds.warn_total_bytes = parsed;
                 overrides.thresholds.warn_total_bytes = Some(parsed);
             }
@@ -375,8 +1427,7 @@ pub fn parse_args() -> Options {
                     "--analyze-total-critical",
                     "analyze.thresholds.crit_total_bytes",
                 );
-                let v = it.next().expect("--analyze-total-critical requires BYTES");
-                let parsed = parse_u64(&v, "--analyze-total-critical");
+                let parsed = parse_u64(&value, "--analyze-total-critical");
                 opts.analyze.thresholds.crit_total_bytes = parsed;
                 overrides.thresholds.crit_total_bytes = Some(parsed);
             }
@@ -386,8 +1437,7 @@ pub fn parse_args() -> Options {
                     "--analyze-large-blob",
                     "analyze.thresholds.warn_blob_bytes",
                 );
-                let v = it.next().expect("--analyze-large-blob requires BYTES");
-                let parsed = parse_u64(&v, "--analyze-large-blob");
+                let parsed = parse_u64(&value, "--analyze-large-blob");
                 opts.analyze.thresholds.warn_blob_bytes = parsed;
                 overrides.thresholds.warn_blob_bytes = Some(parsed);
             }
@@ -397,8 +1447,7 @@ pub fn parse_args() -> Options {
                     "--analyze-ref-warn",
                     "analyze.thresholds.warn_ref_count",
                 );
-                let v = it.next().expect("--analyze-ref-warn requires COUNT");
-                let parsed = parse_usize(&v, "--analyze-ref-warn");
+                let parsed = parse_usize(&value, "--analyze-ref-warn");
                 opts.analyze.thresholds.warn_ref_count = parsed;
                 overrides.thresholds.warn_ref_count = Some(parsed);
             }
@@ -408,8 +1457,7 @@ pub fn parse_args() -> Options {
                     "--analyze-object-warn",
                     "analyze.thresholds.warn_object_count",
                 );
-                let v = it.next().expect("--analyze-object-warn requires COUNT");
-                let parsed = parse_usize(&v, "--analyze-object-warn");
+                let parsed = parse_usize(&value, "--analyze-object-warn");
                 opts.analyze.thresholds.warn_object_count = parsed;
                 overrides.thresholds.warn_object_count = Some(parsed);
             }
@@ -419,8 +1467,7 @@ pub fn parse_args() -> Options {
                     "--analyze-tree-entries",
                     "analyze.thresholds.warn_tree_entries",
                 );
-                let v = it.next().expect("--analyze-tree-entries requires COUNT");
-                let parsed = parse_usize(&v, "--analyze-tree-entries");
+                let parsed = parse_usize(&value, "--analyze-tree-entries");
                 opts.analyze.thresholds.warn_tree_entries = parsed;
                 overrides.thresholds.warn_tree_entries = Some(parsed);
             }
@@ -430,8 +1477,7 @@ pub fn parse_args() -> Options {
                     "--analyze-path-length",
                     "analyze.thresholds.warn_path_length",
                 );
-                let v = it.next().expect("--analyze-path-length requires LENGTH");
-                let parsed = parse_usize(&v, "--analyze-path-length");
+                let parsed = parse_usize(&value, "--analyze-path-length");
                 opts.analyze.thresholds.warn_path_length = parsed;
                 overrides.thresholds.warn_path_length = Some(parsed);
             }
@@ -441,8 +1487,7 @@ pub fn parse_args() -> Options {
                     "--analyze-duplicate-paths",
                     "analyze.thresholds.warn_duplicate_paths",
                 );
-                let v = it.next().expect("--analyze-duplicate-paths requires COUNT");
-                let parsed = parse_usize(&v, "--analyze-duplicate-paths");
+                let parsed = parse_usize(&value, "--analyze-duplicate-paths");
                 opts.analyze.thresholds.warn_duplicate_paths = parsed;
                 overrides.thresholds.warn_duplicate_paths = Some(parsed);
             }
@@ -452,8 +1497,7 @@ pub fn parse_args() -> Options {
                     "--analyze-commit-msg-warn",
                     "analyze.thresholds.warn_commit_msg_bytes",
                 );
-                let v = it.next().expect("--analyze-commit-msg-warn requires BYTES");
-                let parsed = parse_usize(&v, "--analyze-commit-msg-warn");
+                let parsed = parse_usize(&value, "--analyze-commit-msg-warn");
                 opts.analyze.thresholds.warn_commit_msg_bytes = parsed;
                 overrides.thresholds.warn_commit_msg_bytes = Some(parsed);
             }
@@ -463,10 +1507,7 @@ pub fn parse_args() -> Options {
                     "--analyze-max-parents-warn",
                     "analyze.thresholds.warn_max_parents",
                 );
-                let v = it
-                    .next()
-                    .expect("--analyze-max-parents-warn requires COUNT");
-                let parsed = parse_usize(&v, "--analyze-max-parents-warn");
+                let parsed = parse_usize(&value, "--analyze-max-parents-warn");
                 opts.analyze.thresholds.warn_max_parents = parsed;
                 overrides.thresholds.warn_max_parents = Some(parsed);
             }
@@ -474,51 +1515,156 @@ pub fn parse_args() -> Options {
                 opts.debug_mode = true;
                 continue;
             }
-            "--source" => opts.source = PathBuf::from(it.next().expect("--source requires value")),
-            "--target" => opts.target = PathBuf::from(it.next().expect("--target requires value")),
-            "--ref" | "--refs" => opts.refs.push(it.next().expect("--ref requires value")),
+            "--source" => opts.source = PathBuf::from(value),
+            "--target" => opts.target = PathBuf::from(value),
+            "--ref" | "--refs" => opts.refs.push(value),
             "--date-order" => {
-                guard_debug("--date-order", opts.debug_mode);
                 opts.date_order = true;
             }
+            "--detect-renames" => {
+                let pct = parse_u64(&value, "--detect-renames");
+                if pct > 100 {
+                    eprintln!("--detect-renames expects a similarity between 0 and 100");
+                    std::process::exit(2);
+                }
+                opts.detect_renames = Some(pct as u32);
+            }
+            "--detect-copies" => {
+                opts.detect_copies = true;
+            }
             "--no-data" => opts.no_data = true,
+            "--fe-arg" | "--fi-arg" => {
+                let fast_export = arg == "--fe-arg";
+                if let Err(msg) = check_passthrough_arg(fast_export, &value) {
+                    eprintln!("error: {msg}");
+                    std::process::exit(2);
+                }
+                if fast_export {
+                    opts.fe_args.push(value);
+                } else {
+                    opts.fi_args.push(value);
+                }
+            }
             "--quiet" => opts.quiet = true,
+            "--log-level" => {
+                let Some(level) = LogLevel::parse(&value) else {
+                    eprintln!(
+                        "error: --log-level expects one of {}, got '{}'",
+                        LogLevel::NAMES,
+                        value
+                    );
+                    std::process::exit(2);
+                };
+                opts.log_level = Some(level);
+            }
+            "--progress" => opts.progress = true,
+            "--pipeline-timeout" => {
+                let secs = parse_u64(&value, "--pipeline-timeout");
+                opts.pipeline_timeout = Some(Duration::from_secs(secs));
+            }
+            "--subprocess-timeout" => {
+                let secs = parse_u64(&value, "--subprocess-timeout");
+                opts.subprocess_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+            }
             "--no-reset" => {
-                guard_debug("--no-reset", opts.debug_mode);
                 opts.reset = false;
             }
+            "--no-update-hash-mentions" => {
+                opts.update_hash_mentions = false;
+            }
             "--replace-message" => {
-                let p = it.next().expect("--replace-message requires file");
-                opts.replace_message_file = Some(PathBuf::from(p));
+                opts.replace_message_file = Some(PathBuf::from(value));
+            }
+            "--lightweight-tags" => opts.lightweight_tags = true,
+            "--wrap-body" => {
+                let cols = parse_usize(&value, "--wrap-body");
+                if cols == 0 {
+                    eprintln!("--wrap-body expects a column count greater than 0");
+                    std::process::exit(2);
+                }
+                opts.wrap_message_body = Some(cols);
+            }
+            "--max-commit-message-size" => {
+                opts.max_commit_message_bytes =
+                    Some(parse_usize(&value, "--max-commit-message-size"));
+            }
+            "--message-annotate" => {
+                opts.message_annotate = Some(value.into_bytes());
+            }
+            "--message-annotate-all" => {
+                opts.message_annotate_all = true;
+            }
+            "--strip-trailer" => {
+                if let Some(expr) = value.strip_prefix("regex:") {
+                    match Regex::new(expr) {
+                        Ok(re) => opts.strip_trailers.push(TrailerPattern::Regex(re)),
+                        Err(err) => {
+                            eprintln!("invalid --strip-trailer regex '{}': {}", expr, err);
+                            std::process::exit(2);
+                        }
+                    }
+                } else {
+                    let key = value.trim().trim_end_matches(':').trim_end();
+                    if key.is_empty() {
+                        eprintln!("--strip-trailer requires a non-empty key");
+                        std::process::exit(2);
+                    }
+                    opts.strip_trailers
+                        .push(TrailerPattern::Key(key.as_bytes().to_vec()));
+                }
+            }
+            "--annotate-tags" => {
+                opts.annotate_tags = Some(value.into_bytes());
+            }
+            "--replace-identity" => match crate::identity::parse_rule(&value) {
+                Ok(rule) => opts.replace_identity.push(rule),
+                Err(err) => {
+                    eprintln!("invalid --replace-identity: {}", err);
+                    std::process::exit(2);
+                }
+            },
+            "--replace-tag-message" => {
+                opts.replace_tag_message_file = Some(PathBuf::from(value));
             }
             "--replace-text" => {
-                let p = it.next().expect("--replace-text requires file");
-                opts.replace_text_file = Some(PathBuf::from(p));
+                opts.replace_text_file = Some(PathBuf::from(value));
+                opts.replace_text_rules.clear();
+            }
+            "--decode-utf16-for-replace" => {
+                opts.decode_utf16_for_replace = true;
+            }
+            "--rewrite-symlink-targets" => {
+                opts.rewrite_symlink_targets = true;
+            }
+            "--jobs" => {
+                let jobs = parse_usize(&value, "--jobs");
+                if jobs == 0 {
+                    eprintln!("--jobs expects a thread count greater than 0");
+                    std::process::exit(2);
+                }
+                opts.jobs = jobs;
             }
             "--path" => {
-                let p = it.next().expect("--path requires value");
-                opts.paths.push(p.into_bytes());
+                opts.paths.push(value.into_bytes());
             }
             "--invert-paths" => {
                 opts.invert_paths = true;
             }
             "--path-glob" => {
-                let p = it.next().expect("--path-glob requires value");
-                opts.path_globs.push(p.into_bytes());
-            }
-            "--path-regex" => {
-                let p = it.next().expect("--path-regex requires value");
-                match Regex::new(&p) {
-                    Ok(re) => opts.path_regexes.push(re),
-                    Err(err) => {
-                        eprintln!("invalid --path-regex '{}': {}", p, err);
-                        std::process::exit(2);
-                    }
+                opts.path_globs.push(value.into_bytes());
+            }
+            "--path-regex" => match Regex::new(&value) {
+                Ok(re) => opts.path_regexes.push(re),
+                Err(err) => {
+                    eprintln!("invalid --path-regex '{}': {}", value, err);
+                    std::process::exit(2);
                 }
+            },
+            "--ignore-file" => {
+                opts.ignore_file = Some(PathBuf::from(value));
             }
             "--path-rename" => {
-                let v = it.next().expect("--path-rename requires OLD:NEW");
-                let parts: Vec<&str> = v.splitn(2, ':').collect();
+                let parts: Vec<&str> = value.splitn(2, ':').collect();
                 if parts.len() != 2 {
                     eprintln!("--path-rename expects OLD:NEW");
                     std::process::exit(2);
@@ -526,9 +1672,33 @@ pub fn parse_args() -> Options {
                 opts.path_renames
                     .push((parts[0].as_bytes().to_vec(), parts[1].as_bytes().to_vec()));
             }
+            "--rewrite-gitmodules" => {
+                opts.rewrite_gitmodules = true;
+            }
+            "--windows-path-policy" => {
+                opts.windows_path_policy = Some(match value.as_str() {
+                    "sanitize" => WindowsPathPolicy::Sanitize,
+                    "skip" => WindowsPathPolicy::Skip,
+                    "error" => WindowsPathPolicy::Error,
+                    other => {
+                        eprintln!("--windows-path-policy: unknown policy '{}'", other);
+                        std::process::exit(2);
+                    }
+                });
+            }
+            "--on-rename-collision" => {
+                opts.on_rename_collision = match value.as_str() {
+                    "error" => CollisionPolicy::Error,
+                    "keep-first" => CollisionPolicy::KeepFirst,
+                    "keep-last" => CollisionPolicy::KeepLast,
+                    other => {
+                        eprintln!("--on-rename-collision: unknown policy '{}'", other);
+                        std::process::exit(2);
+                    }
+                };
+            }
             "--subdirectory-filter" => {
-                let dir = it.next().expect("--subdirectory-filter requires DIRECTORY");
-                let mut d = dir.as_bytes().to_vec();
+                let mut d = value.as_bytes().to_vec();
                 if !d.ends_with(b"/") {
                     d.push(b'/');
                 }
@@ -536,20 +1706,14 @@ pub fn parse_args() -> Options {
                 opts.path_renames.push((d, Vec::new()));
             }
             "--to-subdirectory-filter" => {
-                let dir = it
-                    .next()
-                    .expect("--to-subdirectory-filter requires DIRECTORY");
-                let mut d = dir.as_bytes().to_vec();
+                let mut d = value.as_bytes().to_vec();
                 if !d.ends_with(b"/") {
                     d.push(b'/');
                 }
                 opts.path_renames.push((Vec::new(), d));
             }
             "--tag-rename" => {
-                let v = it
-                    .next()
-                    .expect("--tag-rename requires OLD:NEW (either may be empty)");
-                let parts: Vec<&str> = v.splitn(2, ':').collect();
+                let parts: Vec<&str> = value.splitn(2, ':').collect();
                 if parts.len() != 2 {
                     eprintln!("--tag-rename expects OLD:NEW");
                     std::process::exit(2);
@@ -557,11 +1721,15 @@ pub fn parse_args() -> Options {
                 opts.tag_rename =
                     Some((parts[0].as_bytes().to_vec(), parts[1].as_bytes().to_vec()));
             }
+            "--tag-rename-file" => match crate::tag::read_tag_rename_file(Path::new(&value)) {
+                Ok(table) => opts.tag_rename_map = table,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(2);
+                }
+            },
             "--branch-rename" => {
-                let v = it
-                    .next()
-                    .expect("--branch-rename requires OLD:NEW (either may be empty)");
-                let parts: Vec<&str> = v.splitn(2, ':').collect();
+                let parts: Vec<&str> = value.splitn(2, ':').collect();
                 if parts.len() != 2 {
                     eprintln!("--branch-rename expects OLD:NEW");
                     std::process::exit(2);
@@ -569,26 +1737,207 @@ pub fn parse_args() -> Options {
                 opts.branch_rename =
                     Some((parts[0].as_bytes().to_vec(), parts[1].as_bytes().to_vec()));
             }
-            "--max-blob-size" => {
-                let v = it.next().expect("--max-blob-size requires BYTES");
-                let n = parse_max_blob_size(&v).unwrap_or_else(|_| {
-                    eprintln!(
-                        "--max-blob-size expects an integer number of bytes (optionally suffixed with K, M, or G)"
-                    );
-                    std::process::exit(2);
-                });
-                opts.max_blob_size = Some(n);
+            "--rename-tags-to-match-branches" => {
+                opts.mirror_branch_rename_to_tags = true;
             }
-            "--strip-blobs-with-ids" => {
-                let p = it.next().expect("--strip-blobs-with-ids requires FILE");
-                opts.strip_blobs_with_ids = Some(PathBuf::from(p));
+            "--ref-rename-file" => {
+                opts.ref_rename_file = Some(PathBuf::from(value));
             }
-            "--write-report" => {
-                opts.write_report = true;
+            "--update-message-refs" => {
+                opts.update_message_refs = true;
             }
-            "--cleanup" => {
-                if let Some(next) = it.clone().next() {
-                    if matches!(next.as_str(), "none" | "standard" | "aggressive") {
+            "--protect-ref" => {
+                opts.protect_refs.push(value.into_bytes());
+            }
+            "--preserve-original-refs" => {
+                opts.preserve_original_refs = true;
+            }
+            "--rewrite-notes" => {
+                opts.rewrite_notes = true;
+            }
+            "--prune-empty" => {
+                opts.prune_empty = match value.as_str() {
+                    "always" => PruneEmpty::Always,
+                    "auto" => PruneEmpty::Auto,
+                    "never" => PruneEmpty::Never,
+                    other => {
+                        eprintln!("--prune-empty: unknown mode '{}'", other);
+                        std::process::exit(2);
+                    }
+                };
+            }
+            "--strip-empty-commits-in-range" => {
+                opts.prune_empty_in_range = Some(value);
+            }
+            "--drop-empty-commits-message" => {
+                let mut message = value.into_bytes();
+                if !message.ends_with(b"\n") {
+                    message.push(b'\n');
+                }
+                opts.empty_commit_tombstone = Some(message);
+            }
+            "--strip-commits-by-author" => {
+                let pattern = match value.strip_prefix("regex:") {
+                    Some(re) => re.to_string(),
+                    None => format!("^{}$", regex::escape(&value)),
+                };
+                match Regex::new(&pattern) {
+                    Ok(re) => opts.strip_commits_by_author.push(re),
+                    Err(e) => {
+                        eprintln!("invalid --strip-commits-by-author '{}': {}", value, e);
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--strip-merges-too" => {
+                opts.strip_merges_too = true;
+            }
+            "--canonicalize-merge-parents" => {
+                opts.canonicalize_merge_parents = true;
+            }
+            "--squash-consecutive-by-author" => {
+                opts.squash_consecutive_by_author =
+                    Some(parse_u64(&value, "--squash-consecutive-by-author"));
+            }
+            "--commits-from" => {
+                opts.commit_date_from = Some(parse_epoch(&value, "--commits-from"));
+            }
+            "--commits-to" => {
+                opts.commit_date_to = Some(parse_epoch(&value, "--commits-to"));
+            }
+            "--replace-refs" => {
+                opts.replace_refs_policy = match value.as_str() {
+                    "delete-and-add" => ReplaceRefsPolicy::Delete,
+                    "delete-no-add" => ReplaceRefsPolicy::DeleteNoAdd,
+                    "update-no-add" => ReplaceRefsPolicy::UpdateNoAdd,
+                    "update-or-add" => ReplaceRefsPolicy::UpdateOrAdd,
+                    other => {
+                        eprintln!("--replace-refs: unknown policy '{}'", other);
+                        std::process::exit(2);
+                    }
+                };
+            }
+            "--signed-commits" | "--signed-tags" => {
+                let policy = match value.as_str() {
+                    "strip" => SignaturePolicy::Strip,
+                    "abort" => SignaturePolicy::Abort,
+                    "keep" => SignaturePolicy::Keep,
+                    other => {
+                        eprintln!("{}: unknown policy '{}'", arg, other);
+                        std::process::exit(2);
+                    }
+                };
+                if arg == "--signed-commits" {
+                    opts.signed_commits = policy;
+                } else {
+                    opts.signed_tags = policy;
+                }
+            }
+            "--tag-sign-cmd" => {
+                opts.tag_sign_cmd = Some(value);
+            }
+            "--normalize-eol" => {
+                opts.normalize_eol = Some(match value.as_str() {
+                    "lf" => EolMode::Lf,
+                    "crlf" => EolMode::CrLf,
+                    other => {
+                        eprintln!("--normalize-eol: unknown mode '{}'", other);
+                        std::process::exit(2);
+                    }
+                });
+            }
+            "--max-blob-size" => {
+                let n = parse_max_blob_size(&value).unwrap_or_else(|_| {
+                    eprintln!(
+                        "--max-blob-size expects an integer number of bytes (optionally suffixed with K, M, or G)"
+                    );
+                    std::process::exit(2);
+                });
+                opts.max_blob_size = Some(n);
+            }
+            "--max-in-memory-blob-size" => {
+                opts.max_in_memory_blob_bytes = parse_max_blob_size(&value).unwrap_or_else(|_| {
+                    eprintln!(
+                        "--max-in-memory-blob-size expects an integer number of bytes (optionally suffixed with K, M, or G)"
+                    );
+                    std::process::exit(2);
+                });
+            }
+            "--no-size-cache" => {
+                opts.size_cache = false;
+            }
+            "--no-rewrite-if-unchanged" => {
+                opts.no_rewrite_if_unchanged = true;
+            }
+            "--strip-blobs-with-ids" => {
+                opts.strip_blobs_with_ids = Some(PathBuf::from(value));
+            }
+            "--keep-blobs-with-ids" => {
+                opts.keep_blobs_with_ids = Some(PathBuf::from(value));
+            }
+            "--strip-blobs-bigger-than-in-packs" => {
+                let n = parse_max_blob_size(&value).unwrap_or_else(|_| {
+                    eprintln!(
+                        "--strip-blobs-bigger-than-in-packs expects an integer number of bytes (optionally suffixed with K, M, or G)"
+                    );
+                    std::process::exit(2);
+                });
+                opts.strip_oversized_from_analysis = Some(n);
+            }
+            "--strict-blob-ids" => {
+                opts.strict_blob_ids = true;
+            }
+            "--delete-duplicate-paths" => {
+                opts.delete_duplicate_paths.push(value.into_bytes());
+            }
+            "--prune-stale-attribute-files" => {
+                opts.prune_stale_attribute_files = true;
+            }
+            "--strip-paths-from-file" => {
+                opts.strip_paths_from_file = Some(PathBuf::from(value));
+            }
+            "--strip-file-modes" => {
+                for mode in value.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+                    if !matches!(mode, "100755" | "120000" | "160000") {
+                        eprintln!(
+                            "--strip-file-modes: unsupported mode '{}' (expected 100755, 120000 or 160000)",
+                            mode
+                        );
+                        std::process::exit(2);
+                    }
+                    opts.strip_file_modes.push(mode.as_bytes().to_vec());
+                }
+            }
+            "--strip-file-modes-glob" => {
+                opts.strip_file_modes_globs.push(value.into_bytes());
+            }
+            "--write-report" => {
+                opts.write_report = true;
+            }
+            "--verify-hash-stability" => {
+                opts.verify_hash_stability = true;
+            }
+            "--dump-unmatched-replace-rules" => {
+                opts.report_unused_replace_rules = true;
+            }
+            "--deterministic" => {
+                opts.deterministic = true;
+            }
+            "--state-dir" => {
+                opts.state_dir = Some(PathBuf::from(value));
+            }
+            "--incremental" => {
+                opts.incremental = true;
+            }
+            "--write-parent-map" => {
+                opts.write_parent_map = true;
+            }
+            "--show-refs-before-after" => {
+                opts.show_ref_changes = true;
+            }
+            "--cleanup" => {
+                if let Some(next) = it.clone().next() {
+                    if matches!(next.as_str(), "none" | "standard" | "aggressive") {
                         let legacy = it.next().expect("--cleanup legacy value consumed");
                         parse_legacy_cleanup_value(&legacy, &mut opts);
                         continue;
@@ -596,34 +1945,21 @@ pub fn parse_args() -> Options {
                 }
                 opts.cleanup = CleanupMode::Standard;
             }
-            arg if arg.starts_with("--cleanup=") => {
-                let value = &arg[10..];
-                if value.is_empty() {
-                    eprintln!("--cleanup= requires a value of none|standard|aggressive");
-                    std::process::exit(2);
-                }
-                parse_legacy_cleanup_value(value, &mut opts);
-            }
             "--cleanup-aggressive" => {
-                guard_debug("--cleanup-aggressive", opts.debug_mode);
                 opts.cleanup = CleanupMode::Aggressive;
             }
             "--no-reencode" => {
-                guard_debug("--no-reencode", opts.debug_mode);
                 opts.reencode = false;
                 opts.reencode_requested = Some(false);
             }
             "--no-quotepath" => {
-                guard_debug("--no-quotepath", opts.debug_mode);
                 opts.quotepath = false;
             }
             "--no-mark-tags" => {
-                guard_debug("--no-mark-tags", opts.debug_mode);
                 opts.mark_tags = false;
                 opts.mark_tags_requested = Some(false);
             }
             "--mark-tags" => {
-                guard_debug("--mark-tags", opts.debug_mode);
                 opts.mark_tags = true;
                 opts.mark_tags_requested = Some(true);
             }
@@ -633,83 +1969,139 @@ pub fn parse_args() -> Options {
             "--enforce-sanity" => {
                 opts.enforce_sanity = true;
             }
+            "--skip-check" => {
+                if !crate::sanity::PREFLIGHT_CHECKS
+                    .iter()
+                    .any(|(id, _)| *id == value)
+                {
+                    let valid: Vec<&str> = crate::sanity::PREFLIGHT_CHECKS
+                        .iter()
+                        .map(|(id, _)| *id)
+                        .collect();
+                    eprintln!(
+                        "error: unknown check '{value}' for --skip-check; valid checks: {}",
+                        valid.join(", ")
+                    );
+                    std::process::exit(2);
+                }
+                opts.skip_checks.push(value);
+            }
+            "--assume-yes" | "--yes" => {
+                opts.assume_yes = true;
+            }
+            "--assume-no" => {
+                opts.assume_no = true;
+            }
             "--dry-run" => {
                 opts.dry_run = true;
             }
             "--partial" => {
                 opts.partial = true;
             }
+            "--keep-remote" => {
+                opts.partial = true;
+                opts.keep_remotes.push(value);
+            }
             "--sensitive" | "--sensitive-data-removal" => {
                 opts.sensitive = true;
             }
+            "--sensitive-refspec" => {
+                opts.sensitive_refspecs.push(value);
+            }
+            "--sensitive-apply-to-messages" => {
+                opts.sensitive_apply_to_messages = Some(true);
+            }
+            "--no-sensitive-apply-to-messages" => {
+                opts.sensitive_apply_to_messages = Some(false);
+            }
             "--no-fetch" => {
                 opts.no_fetch = true;
             }
             "--backup" => {
                 opts.backup = true;
             }
+            "--no-backup" => {
+                opts.backup = false;
+            }
+            "--no-backup-if-noop" => {
+                opts.skip_backup_on_noop = true;
+            }
             "--backup-path" => {
-                if let Some(p) = it.next() {
-                    opts.backup_path = Some(PathBuf::from(p));
-                } else {
-                    eprintln!("error: --backup-path requires a value");
-                    std::process::exit(2);
-                }
+                opts.backup_path = Some(PathBuf::from(value));
             }
             "--fe_stream_override" => {
-                guard_debug("--fe_stream_override", opts.debug_mode);
-                let p = it.next().expect("--fe_stream_override requires FILE");
-                opts.fe_stream_override = Some(PathBuf::from(p));
+                opts.fe_stream_override = Some(PathBuf::from(value));
             }
             "-h" | "--help" => {
                 print_help(opts.debug_mode);
                 std::process::exit(0);
             }
-            other => {
-                eprintln!("Unknown argument: {}", other);
-                print_help(opts.debug_mode);
-                std::process::exit(2);
+            "--list-checks" => {
+                let width = crate::sanity::PREFLIGHT_CHECKS
+                    .iter()
+                    .map(|(id, _)| id.len())
+                    .max()
+                    .unwrap_or(0);
+                for (id, description) in crate::sanity::PREFLIGHT_CHECKS {
+                    println!("{id:<width$}  {description}");
+                }
+                std::process::exit(0);
             }
-        }
-    }
-
-    let config_target = if let Some(path) = config_override {
-        Some((path, true))
-    } else {
-        Some((opts.source.join(".filter-repo-rs.toml"), false))
-    };
-
-    if let Some((path, explicit)) = config_target {
-        match apply_config_from_file(&mut opts, &path) {
-            Ok(()) => {}
-            Err(ConfigError::Io(err)) => {
-                use std::io::ErrorKind;
-                if explicit || err.kind() != ErrorKind::NotFound {
+            "--completions" => {
+                let Some(shell) = Shell::parse(&value) else {
                     eprintln!(
-                        "error: failed to read config at {}: {}",
-                        path.display(),
-                        err
+                        "--completions: unknown shell '{}' (expected bash, zsh, fish or powershell)",
+                        value
                     );
                     std::process::exit(2);
-                }
-            }
-            Err(ConfigError::Parse(err)) => {
-                eprintln!(
-                    "error: failed to parse config at {}: {}",
-                    path.display(),
-                    err
+                };
+                print!(
+                    "{}",
+                    completions::render(shell, &completion_flags(opts.debug_mode))
                 );
-                eprintln!(
-          "note: example key: analyze.thresholds.warn_total_bytes (see docs/cli-convergence.md)"
-        );
-                std::process::exit(2);
+                std::process::exit(0);
             }
+            // --config and --analyze-threshold-profile are consumed above
+            other => unreachable!("{other} is in FLAG_SECTIONS but not handled"),
         }
     }
 
+    if opts.mode == Mode::Analyze && !filter_flags.is_empty() {
+        eprintln!(
+            "error: --analyze cannot be combined with filtering flags: {}",
+            filter_flags.join(", ")
+        );
+        std::process::exit(2);
+    }
+    if opts.mode == Mode::MapLookup && !filter_flags.is_empty() {
+        eprintln!(
+            "error: --map-lookup cannot be combined with filtering flags: {}",
+            filter_flags.join(", ")
+        );
+        std::process::exit(2);
+    }
+
+    if opts.paths.is_empty() {
+        opts.paths = config_paths;
+    }
+    if opts.path_globs.is_empty() {
+        opts.path_globs = config_path_globs;
+    }
+    if opts.path_renames.is_empty() {
+        opts.path_renames = config_path_renames;
+    }
+    if opts.refs.is_empty() {
+        opts.refs = default_refs;
+    }
+
     overrides.apply(&mut opts.analyze);
     let caps = match gitutil::probe_git_capabilities() {
         Ok(caps) => caps,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let err = FilterRepoError::git_spawn(err);
+            eprintln!("error: [{}] {err}", err.code());
+            std::process::exit(err.exit_code());
+        }
         Err(err) => {
             eprintln!("error: failed to probe git capabilities: {err}");
             std::process::exit(2);
@@ -723,22 +2115,297 @@ pub fn parse_args() -> Options {
     opts
 }
 
+/// Filtering flags that make no sense together with `--analyze`.
+const ANALYZE_CONFLICTS: &[&str] = &[
+    "--path",
+    "--path-glob",
+    "--path-regex",
+    "--invert-paths",
+    "--ignore-file",
+    "--path-rename",
+    "--subdirectory-filter",
+    "--to-subdirectory-filter",
+    "--rewrite-gitmodules",
+    "--replace-text",
+    "--replace-message",
+    "--replace-tag-message",
+    "--replace-identity",
+    "--wrap-body",
+    "--max-commit-message-size",
+    "--message-annotate",
+    "--message-annotate-all",
+    "--strip-trailer",
+    "--lightweight-tags",
+    "--annotate-tags",
+    "--tag-rename",
+    "--tag-rename-file",
+    "--branch-rename",
+    "--rename-tags-to-match-branches",
+    "--ref-rename-file",
+    "--normalize-eol",
+    "--max-blob-size",
+    "--strip-blobs-with-ids",
+    "--keep-blobs-with-ids",
+    "--fe-arg",
+    "--fi-arg",
+    "--strip-blobs-bigger-than-in-packs",
+    "--delete-duplicate-paths",
+    "--prune-stale-attribute-files",
+    "--strip-paths-from-file",
+    "--strip-file-modes",
+    "--strip-file-modes-glob",
+    "--prune-empty",
+    "--strip-empty-commits-in-range",
+    "--drop-empty-commits-message",
+    "--strip-commits-by-author",
+    "--squash-consecutive-by-author",
+    "--sensitive",
+    "--sensitive-data-removal",
+];
+
+/// Arguments `--fe-arg`/`--fi-arg` may not pass: the marks files, the
+/// `done` handshake and the output toggles the pipeline manages itself.
+const FE_ARG_DENYLIST: &[&str] = &[
+    "--export-marks",
+    "--import-marks",
+    "--import-marks-if-exists",
+    "--use-done-feature",
+    "--show-original-ids",
+    "--anonymize",
+];
+const FI_ARG_DENYLIST: &[&str] = &[
+    "--export-marks",
+    "--import-marks",
+    "--import-marks-if-exists",
+    "--quiet",
+    "--stats",
+    "--done",
+    "--cat-blob-fd",
+];
+
+/// Reject an `--fe-arg` (`fast_export`) or `--fi-arg` value that would
+/// conflict with the pipeline's own arguments.
+pub(crate) fn check_passthrough_arg(fast_export: bool, arg: &str) -> Result<(), String> {
+    let (flag, denied) = if fast_export {
+        ("--fe-arg", FE_ARG_DENYLIST)
+    } else {
+        ("--fi-arg", FI_ARG_DENYLIST)
+    };
+    let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+    if denied.contains(&name) {
+        return Err(format!(
+            "{flag} {arg}: {name} is managed by filter-repo-rs and cannot be passed through"
+        ));
+    }
+    Ok(())
+}
+
+/// Upstream `git filter-repo` flags accepted in compat mode, with the native
+/// flag each maps onto and whether it takes a value.
+const COMPAT_FLAGS: &[(&str, &str, bool)] = &[
+    ("--path", "--path", true),
+    ("--path-glob", "--path-glob", true),
+    ("--path-rename", "--path-rename", true),
+    ("--invert-paths", "--invert-paths", false),
+    ("--strip-blobs-bigger-than", "--max-blob-size", true),
+    ("--replace-text", "--replace-text", true),
+    ("--tag-rename", "--tag-rename", true),
+    ("--force", "--force", false),
+    ("-f", "--force", false),
+    ("--dry-run", "--dry-run", false),
+    ("--analyze", "--analyze", false),
+];
+
+/// Whether the binary was started under the upstream name, e.g. installed as
+/// `git-filter-repo` so that `git filter-repo` runs it.
+fn is_upstream_argv0(argv0: &str) -> bool {
+    Path::new(argv0)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.ends_with("git-filter-repo"))
+}
+
+/// Rewrite upstream `git filter-repo` arguments into native ones. Both
+/// `--flag VALUE` and `--flag=VALUE` are accepted; any flag without a native
+/// equivalent is an error.
+fn translate_compat_args(args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut out = Vec::with_capacity(args.len());
+    let mut it = args.into_iter();
+    while let Some(arg) = it.next() {
+        if arg == "--compat-filter-repo" {
+            continue;
+        }
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let Some(&(_, native, takes_value)) = COMPAT_FLAGS.iter().find(|(f, _, _)| *f == flag)
+        else {
+            return Err(format!("unsupported in compat mode: {flag}"));
+        };
+        out.push(native.to_string());
+        match (takes_value, inline_value) {
+            (true, Some(value)) => out.push(value),
+            (true, None) => out.push(
+                it.next()
+                    .ok_or_else(|| format!("{flag} requires a value"))?,
+            ),
+            (false, Some(_)) => return Err(format!("{flag} does not take a value")),
+            (false, None) => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Source repository named on the command line (`--source`), or the current
+/// directory. Used to find git config defaults and `.filter-repo-rs.toml`
+/// before the flags themselves are parsed.
+fn source_arg(args: &[String]) -> PathBuf {
+    args.iter()
+        .position(|a| a == "--source")
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Seed `opts` from `filterRepo.*` git config keys. Runs before the CLI
+/// flags are parsed, so precedence is: built-in default < git config < CLI.
+fn apply_git_config_defaults(opts: &mut Options, repo: &Path) {
+    let defaults = match GitConfig::read_filter_repo_defaults(repo) {
+        Ok(defaults) => defaults,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(2);
+        }
+    };
+    if let Some(v) = defaults.max_blob_size {
+        let n = parse_max_blob_size(&v).unwrap_or_else(|_| {
+            eprintln!(
+                "error: filterRepo.maxBlobSize expects an integer number of bytes (optionally suffixed with K, M, or G), got '{}'",
+                v
+            );
+            std::process::exit(2);
+        });
+        opts.max_blob_size = Some(n);
+    }
+    if let Some(backup) = defaults.backup {
+        opts.backup = backup;
+    }
+}
+
+/// Apply the config file at `path`; a missing default file is not an error.
+fn load_config_or_exit(opts: &mut Options, path: &Path, explicit: bool) {
+    match apply_config_from_file(opts, path) {
+        Ok(()) => {}
+        Err(ConfigError::Io(err)) => {
+            use std::io::ErrorKind;
+            if explicit || err.kind() != ErrorKind::NotFound {
+                eprintln!(
+                    "error: failed to read config at {}: {}",
+                    path.display(),
+                    err
+                );
+                std::process::exit(2);
+            }
+        }
+        Err(ConfigError::Parse(err)) => {
+            eprintln!(
+                "error: failed to parse config at {}: {}",
+                path.display(),
+                err
+            );
+            eprintln!(
+                "note: example key: analyze.thresholds.warn_total_bytes (see docs/cli-convergence.md)"
+            );
+            std::process::exit(2);
+        }
+        Err(ConfigError::Invalid(msg)) => {
+            eprintln!("error: invalid config at {}: {}", path.display(), msg);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[derive(Debug)]
 enum ConfigError {
     Io(std::io::Error),
     Parse(toml::de::Error),
+    Invalid(String),
 }
 
 fn apply_config_from_file(opts: &mut Options, path: &Path) -> Result<(), ConfigError> {
     let raw = fs::read_to_string(path).map_err(ConfigError::Io)?;
-    let config: FileConfig = toml::from_str(&raw).map_err(ConfigError::Parse)?;
+    let config: ConfigFile = toml::from_str(&raw).map_err(ConfigError::Parse)?;
+
+    if let Some(filter) = config.filter {
+        // Files named in the config are relative to the config file itself
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        if let Some(paths) = filter.paths {
+            opts.paths = paths.iter().map(|p| decode_config_bytes(p)).collect();
+        }
+        if let Some(globs) = filter.path_globs {
+            opts.path_globs = globs.into_iter().map(String::into_bytes).collect();
+        }
+        if let Some(renames) = filter.path_renames {
+            opts.path_renames = renames
+                .iter()
+                .map(|v| match v.split_once(':') {
+                    Some((old, new_)) => Ok((decode_config_bytes(old), decode_config_bytes(new_))),
+                    None => Err(ConfigError::Invalid(format!(
+                        "filter.path_renames expects OLD:NEW, got '{}'",
+                        v
+                    ))),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(size) = filter.max_blob_size {
+            let n = match size {
+                ConfigSize::Bytes(n) => usize::try_from(n).ok(),
+                ConfigSize::Text(v) => parse_max_blob_size(&v).ok(),
+            };
+            opts.max_blob_size = Some(n.ok_or_else(|| {
+                ConfigError::Invalid(
+                    "filter.max_blob_size expects an integer number of bytes (optionally suffixed with K, M, or G)"
+                        .to_string(),
+                )
+            })?);
+        }
+        match filter.replace_text {
+            Some(ConfigRules::File(file)) => opts.replace_text_file = Some(base.join(file)),
+            Some(ConfigRules::Inline(rules)) => {
+                opts.replace_text_rules = rules.into_iter().map(String::into_bytes).collect()
+            }
+            None => {}
+        }
+        if let Some(file) = filter.replace_message {
+            opts.replace_message_file = Some(base.join(file));
+        }
+        if let Some(backup) = filter.backup {
+            opts.backup = backup;
+        }
+        if let Some(file) = filter.backup_path {
+            opts.backup_path = Some(base.join(file));
+        }
+    }
 
     if let Some(analyze) = config.analyze {
         if let Some(json) = analyze.json {
             opts.analyze.json = json;
         }
+        if let Some(format) = analyze.format {
+            opts.analyze.format = format;
+        }
         if let Some(top) = analyze.top {
             opts.analyze.top = top.max(1);
         }
+        if let Some(min_severity) = analyze.min_severity {
+            opts.analyze.min_severity = min_severity;
+        }
+        // A profile given on the command line wins over the file's
+        if let Some(profile) = analyze.profile.filter(|_| opts.analyze.profile.is_none()) {
+            opts.analyze.profile = Some(profile);
+            opts.analyze.thresholds = profile.thresholds();
+        }
         if let Some(thresholds) = analyze.thresholds {
             guard_debug("analyze.thresholds.*", opts.debug_mode);
             thresholds.apply(&mut opts.analyze.thresholds);
@@ -836,13 +2503,13 @@ fn warn_legacy_analyze_threshold(flag: &str, config_key: &str) {
 
 fn debug_mode_enabled(args: &[String]) -> bool {
     use std::env;
-    if matches!(env::var("FRRS_DEBUG"), Ok(val) if debug_env_flag_enabled(&val)) {
+    if matches!(env::var("FRRS_DEBUG"), Ok(val) if env_flag_enabled(&val)) {
         return true;
     }
     args.iter().any(|arg| arg == "--debug-mode")
 }
 
-fn debug_env_flag_enabled(raw: &str) -> bool {
+fn env_flag_enabled(raw: &str) -> bool {
     let normalized = raw.trim().to_ascii_lowercase();
     if normalized.is_empty() {
         return false;
@@ -850,6 +2517,57 @@ fn debug_env_flag_enabled(raw: &str) -> bool {
     !matches!(normalized.as_str(), "0" | "false" | "no" | "off")
 }
 
+/// Apply `FRRS_*` environment overrides. Runs after the config file and
+/// before CLI flags, so precedence is: config file < environment < CLI.
+/// Threshold variables are debug-gated like `analyze.thresholds.*`.
+fn apply_env_overrides(
+    opts: &mut Options,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    fn parse<T: std::str::FromStr>(name: &str, raw: &str) -> Result<T, String> {
+        parse_integer_allowing_underscores::<T>(raw.trim())
+            .map_err(|_| format!("{name} expects an integer number, got '{raw}'"))
+    }
+    let gated = |name: &str| -> Result<Option<String>, String> {
+        match var(name) {
+            Some(_) if !opts.debug_mode => Err(format!(
+                "{name} is gated behind debug mode. Set FRRS_DEBUG=1 or pass --debug-mode to access debug-only flags."
+            )),
+            other => Ok(other),
+        }
+    };
+    let blob_bytes = gated("FRRS_WARN_BLOB_BYTES")?;
+    let commit_msg_bytes = gated("FRRS_WARN_COMMIT_MSG_BYTES")?;
+    let max_parents = gated("FRRS_WARN_MAX_PARENTS")?;
+
+    let analyze = &mut opts.analyze;
+    if let Some(v) = blob_bytes {
+        analyze.thresholds.warn_blob_bytes = parse("FRRS_WARN_BLOB_BYTES", &v)?;
+    }
+    if let Some(v) = commit_msg_bytes {
+        analyze.thresholds.warn_commit_msg_bytes = parse("FRRS_WARN_COMMIT_MSG_BYTES", &v)?;
+    }
+    if let Some(v) = max_parents {
+        analyze.thresholds.warn_max_parents = parse("FRRS_WARN_MAX_PARENTS", &v)?;
+    }
+    if let Some(v) = var("FRRS_TOP") {
+        analyze.top = parse::<usize>("FRRS_TOP", &v)?.max(1);
+    }
+    if let Some(v) = var("FRRS_JSON") {
+        analyze.json = env_flag_enabled(&v);
+    }
+    if let Some(v) = var("FRRS_NONINTERACTIVE") {
+        opts.non_interactive = env_flag_enabled(&v);
+    }
+    if let Some(v) = var("FRRS_LOG") {
+        opts.log_level =
+            Some(LogLevel::parse(&v).ok_or_else(|| {
+                format!("FRRS_LOG expects one of {}, got '{v}'", LogLevel::NAMES)
+            })?);
+    }
+    Ok(())
+}
+
 fn guard_debug(flag: &str, debug_mode: bool) {
     if !debug_mode {
         eprintln!(
@@ -907,6 +2625,15 @@ fn parse_u64(s: &str, flag: &str) -> u64 {
     })
 }
 
+fn parse_epoch(s: &str, flag: &str) -> i64 {
+    s.trim_start_matches('@')
+        .parse::<i64>()
+        .unwrap_or_else(|_| {
+            eprintln!("{} expects seconds since the epoch", flag);
+            std::process::exit(2);
+        })
+}
+
 fn parse_usize(s: &str, flag: &str) -> usize {
     parse_integer_allowing_underscores::<usize>(s).unwrap_or_else(|_| {
         eprintln!("{} expects an integer number", flag);
@@ -985,285 +2712,1069 @@ fn format_help_section(section: &HelpSection) -> String {
     result
 }
 
-fn get_base_help_sections() -> Vec<HelpSection> {
-    vec![
-        HelpSection {
-            title: "Repository & ref selection:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--source DIR".to_string(),
-                    description: vec!["Source Git working directory (default .)".to_string()],
-                },
-                HelpOption {
-                    name: "--target DIR".to_string(),
-                    description: vec!["Target Git working directory (default .)".to_string()],
-                },
-                HelpOption {
-                    name: "--refs REF".to_string(),
-                    description: vec!["Ref to export (repeatable; defaults to --all)".to_string()],
-                },
-                HelpOption {
-                    name: "--no-data".to_string(),
-                    description: vec!["Do not include blob data in fast-export".to_string()],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Path selection & rewriting:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--path PREFIX".to_string(),
-                    description: vec!["Include-only files under PREFIX (repeatable)".to_string()],
-                },
-                HelpOption {
-                    name: "--path-glob GLOB".to_string(),
-                    description: vec!["Include by glob (repeatable)".to_string()],
-                },
-                HelpOption {
-                    name: "--path-regex REGEX".to_string(),
-                    description: vec!["Include by Rust regex (repeatable)".to_string()],
-                },
-                HelpOption {
-                    name: "--invert-paths".to_string(),
-                    description: vec!["Invert path selection (drop matches)".to_string()],
-                },
-                HelpOption {
-                    name: "--path-rename OLD:NEW".to_string(),
-                    description: vec!["Rename path prefix in file changes".to_string()],
-                },
-                HelpOption {
-                    name: "--subdirectory-filter D".to_string(),
-                    description: vec!["Equivalent to --path D/ --path-rename D/:".to_string()],
-                },
-                HelpOption {
-                    name: "--to-subdirectory-filter D".to_string(),
-                    description: vec!["Equivalent to --path-rename :D/".to_string()],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Blob filtering & redaction:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--replace-text FILE".to_string(),
-                    description: vec![
-                        "Literal/regex (feature-gated) replacements for blobs".to_string()
-                    ],
-                },
-                HelpOption {
-                    name: "--max-blob-size BYTES".to_string(),
-                    description: vec!["Drop blobs larger than BYTES".to_string()],
-                },
-                HelpOption {
-                    name: "--strip-blobs-with-ids FILE".to_string(),
-                    description: vec!["Drop blobs by 40-hex id (one per line)".to_string()],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Commit, tag & ref updates:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--replace-message FILE".to_string(),
-                    description: vec!["Literal replacements in commit/tag messages".to_string()],
-                },
-                HelpOption {
-                    name: "--tag-rename OLD:NEW".to_string(),
-                    description: vec!["Rename tags with given prefix".to_string()],
-                },
-                HelpOption {
-                    name: "--branch-rename OLD:NEW".to_string(),
-                    description: vec!["Rename branches with given prefix".to_string()],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Execution behavior & output:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--write-report".to_string(),
-                    description: vec!["Write .git/filter-repo/report.txt summary".to_string()],
-                },
-                HelpOption {
-                    name: "--cleanup".to_string(),
-                    description: vec![
-                        "Run post-import cleanup (reflog expire + git gc)".to_string(),
-                        "(disabled by default)".to_string(),
-                    ],
-                },
-                HelpOption {
-                    name: "--quiet".to_string(),
-                    description: vec!["Reduce output noise".to_string()],
-                },
-                HelpOption {
-                    name: "--force, -f".to_string(),
-                    description: vec![
-                        "Bypass safety prompts and checks where applicable".to_string()
-                    ],
-                },
-                HelpOption {
-                    name: "--enforce-sanity".to_string(),
-                    description: vec![
-                        "Explicitly enable safety checks (default behavior)".to_string()
-                    ],
-                },
-                HelpOption {
-                    name: "--dry-run".to_string(),
-                    description: vec!["Prepare and validate without writing changes".to_string()],
-                },
-                HelpOption {
-                    name: "--partial".to_string(),
-                    description: vec!["Only rewrite current repo; skip remote cleanup".to_string()],
-                },
-                HelpOption {
-                    name: "--sensitive".to_string(),
-                    description: vec![
-                        "Enable sensitive-history mode (fetch all refs,".to_string(),
-                        "avoid remote cleanup; see --no-fetch)".to_string(),
-                    ],
-                },
-                HelpOption {
-                    name: "--no-fetch".to_string(),
-                    description: vec![
-                        "In sensitive mode, skip fetching refs from origin".to_string()
-                    ],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Safety & backup:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--backup".to_string(),
-                    description: vec![
-                        "Create a backup bundle of selected refs before".to_string(),
-                        "rewriting (skipped with --dry-run)".to_string(),
-                    ],
-                },
-                HelpOption {
-                    name: "--backup-path PATH".to_string(),
-                    description: vec![
-                        "Destination directory or file for the bundle.".to_string(),
-                        "If PATH is a directory, a timestamped filename".to_string(),
-                        "is generated. If PATH has an extension, that".to_string(),
-                        "exact file is written. Defaults to".to_string(),
-                        ".git/filter-repo/backup-<timestamp>.bundle".to_string(),
-                    ],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Repository analysis:".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--analyze".to_string(),
-                    description: vec!["Collect repository metrics instead of rewriting".to_string()],
-                },
-                HelpOption {
-                    name: "--analyze-json".to_string(),
-                    description: vec!["Emit JSON-formatted analysis report".to_string()],
-                },
-                HelpOption {
-                    name: "--analyze-top N".to_string(),
-                    description: vec![
-                        "Number of largest blobs/trees to show (default 10)".to_string()
-                    ],
-                },
-            ],
-        },
-    ]
-}
-
-fn get_debug_help_sections() -> Vec<HelpSection> {
-    vec![
-        HelpSection {
-            title: "Debug / fast-export passthrough (require --debug-mode or FRRS_DEBUG=1):"
-                .to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--date-order".to_string(),
-                    description: vec![
-                        "Request date-order traversal from git fast-export".to_string()
-                    ],
-                },
-                HelpOption {
-                    name: "--no-reencode".to_string(),
-                    description: vec!["Disable re-encoding of commit/tag messages".to_string()],
-                },
-                HelpOption {
-                    name: "--no-quotepath".to_string(),
-                    description: vec!["Disable Git's path quoting for non-ASCII".to_string()],
-                },
-                HelpOption {
-                    name: "--no-mark-tags".to_string(),
-                    description: vec!["Do not mark annotated tags in fast-export".to_string()],
-                },
-                HelpOption {
-                    name: "--mark-tags".to_string(),
-                    description: vec!["Explicitly mark annotated tags in fast-export".to_string()],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Debug / analysis thresholds (require --debug-mode or FRRS_DEBUG=1):"
-                .to_string(),
-            options: vec![HelpOption {
-                name: "".to_string(), // Empty name for description-only line
-                description: vec![
-                    "Configure analyze.thresholds.* via .filter-repo-rs.toml or --config."
-                        .to_string(),
-                    "Legacy --analyze-*-warn CLI flags remain for compatibility but emit warnings."
-                        .to_string(),
+/// One command-line flag. The parser takes arity and debug gating from this
+/// table, and `--help` and `--completions` are rendered from it, so a flag
+/// is declared exactly once.
+#[derive(Debug, Clone, Copy)]
+struct FlagSpec {
+    /// Spellings listed in `--help` and offered by completions
+    names: &'static [&'static str],
+    /// Spellings accepted but not advertised
+    aliases: &'static [&'static str],
+    /// Placeholder for the value the flag takes, if it takes one
+    value: Option<&'static str>,
+    /// Values completions offer for the flag
+    choices: &'static [&'static str],
+    /// Refused unless --debug-mode or FRRS_DEBUG=1 is set
+    debug_only: bool,
+    help: &'static [&'static str],
+}
+
+impl FlagSpec {
+    const fn switch(names: &'static [&'static str], help: &'static [&'static str]) -> Self {
+        FlagSpec {
+            names,
+            aliases: &[],
+            value: None,
+            choices: &[],
+            debug_only: false,
+            help,
+        }
+    }
+
+    const fn with_value(
+        names: &'static [&'static str],
+        value: &'static str,
+        help: &'static [&'static str],
+    ) -> Self {
+        FlagSpec {
+            value: Some(value),
+            ..FlagSpec::switch(names, help)
+        }
+    }
+
+    const fn choices(self, choices: &'static [&'static str]) -> Self {
+        FlagSpec { choices, ..self }
+    }
+
+    const fn aliases(self, aliases: &'static [&'static str]) -> Self {
+        FlagSpec { aliases, ..self }
+    }
+
+    const fn debug_only(self) -> Self {
+        FlagSpec {
+            debug_only: true,
+            ..self
+        }
+    }
+
+    fn spellings(&self) -> impl Iterator<Item = &'static str> {
+        self.names.iter().chain(self.aliases).copied()
+    }
+
+    /// Name column for `--help`; `--x` followed by `--no-x` prints as `--[no-]x`.
+    fn help_name(&self) -> String {
+        if let [on, off] = self.names {
+            if let Some(rest) = on.strip_prefix("--") {
+                if off.strip_prefix("--no-") == Some(rest) {
+                    return format!("--[no-]{rest}");
+                }
+            }
+        }
+        self.names
+            .iter()
+            .map(|name| match self.value {
+                Some(value) => format!("{name} {value}"),
+                None => name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Legacy `--analyze-*` threshold flags: still accepted in debug mode, but
+/// undocumented in favour of `analyze.thresholds.*` in the config file.
+const fn legacy_threshold_flag(names: &'static [&'static str], value: &'static str) -> FlagSpec {
+    FlagSpec::with_value(&[], value, &[])
+        .aliases(names)
+        .debug_only()
+}
+
+struct FlagSection {
+    title: &'static str,
+    /// Description-only lines printed under the title
+    notes: &'static [&'static str],
+    flags: &'static [FlagSpec],
+}
+
+const FLAG_SECTIONS: &[FlagSection] = &[
+    FlagSection {
+        title: "Repository & ref selection:",
+        notes: &[],
+        flags: &[
+            FlagSpec::with_value(
+                &["--source"],
+                "DIR",
+                &["Source Git working directory (default .)"],
+            ),
+            FlagSpec::with_value(
+                &["--target"],
+                "DIR",
+                &["Target Git working directory (default .)"],
+            ),
+            FlagSpec::with_value(
+                &["--refs"],
+                "REF",
+                &[
+                    "Ref or range to export (repeatable; defaults to --all);",
+                    "A..B rewrites only A..B onto A's untouched history",
                 ],
-            }],
-        },
-        HelpSection {
-            title: "Debug / cleanup behavior (require --debug-mode or FRRS_DEBUG=1):".to_string(),
-            options: vec![
-                HelpOption {
-                    name: "--no-reset".to_string(),
-                    description: vec!["Skip final 'git reset --hard' in target".to_string()],
-                },
-                HelpOption {
-                    name: "--cleanup-aggressive".to_string(),
-                    description: vec![
-                        "Extend cleanup with git gc --aggressive and".to_string(),
-                        "--expire-unreachable=now".to_string(),
-                    ],
-                },
-            ],
-        },
-        HelpSection {
-            title: "Debug / stream overrides (require --debug-mode or FRRS_DEBUG=1):".to_string(),
-            options: vec![HelpOption {
-                name: "--fe_stream_override FILE".to_string(),
-                description: vec!["Read fast-export stream from FILE instead of git".to_string()],
-            }],
-        },
-    ]
-}
-
-fn get_misc_help_section() -> HelpSection {
-    HelpSection {
-        title: "Misc:".to_string(),
-        options: vec![
-            HelpOption {
-                name: "--config FILE".to_string(),
-                description: vec![
-                    "Load options from TOML config file (default".to_string(),
-                    "<source>/.filter-repo-rs.toml)".to_string(),
+            )
+            .aliases(&["--ref"]),
+            FlagSpec::switch(&["--no-data"], &["Do not include blob data in fast-export"]),
+            FlagSpec::with_value(
+                &["--fe-arg", "--fi-arg"],
+                "ARG",
+                &[
+                    "Append ARG to git fast-export / fast-import (repeatable;",
+                    "marks files and flags the pipeline relies on are refused)",
                 ],
-            },
-            HelpOption {
-                name: "--debug-mode".to_string(),
-                description: vec!["Enable debug/test flags (same as FRRS_DEBUG=1)".to_string()],
-            },
-            HelpOption {
-                name: "-h, --help".to_string(),
-                description: vec!["Show this help message".to_string()],
-            },
+            ),
         ],
-    }
+    },
+    FlagSection {
+        title: "Path selection & rewriting:",
+        notes: &[],
+        flags: &[
+            FlagSpec::with_value(
+                &["--path"],
+                "PREFIX",
+                &["Include-only files under PREFIX (repeatable)"],
+            ),
+            FlagSpec::with_value(&["--path-glob"], "GLOB", &["Include by glob (repeatable)"]),
+            FlagSpec::with_value(
+                &["--path-regex"],
+                "REGEX",
+                &["Include by Rust regex (repeatable)"],
+            ),
+            FlagSpec::switch(
+                &["--invert-paths"],
+                &["Invert path selection (drop matches)"],
+            ),
+            FlagSpec::with_value(
+                &["--ignore-file"],
+                "FILE",
+                &[
+                    "Drop paths matching gitignore-style patterns in FILE",
+                    "(! re-includes a path; e.g. .filterrepoignore)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--path-rename"],
+                "OLD:NEW",
+                &["Rename path prefix in file changes"],
+            ),
+            FlagSpec::with_value(
+                &["--subdirectory-filter"],
+                "D",
+                &["Equivalent to --path D/ --path-rename D/:"],
+            ),
+            FlagSpec::with_value(
+                &["--to-subdirectory-filter"],
+                "D",
+                &["Equivalent to --path-rename :D/"],
+            ),
+            FlagSpec::switch(
+                &["--rewrite-gitmodules"],
+                &["Apply path renames to .gitmodules path/url entries"],
+            ),
+            FlagSpec::with_value(
+                &["--windows-path-policy"],
+                "MODE",
+                &[
+                    "sanitize|skip|error paths Windows cannot check out",
+                    "(checked after renames; default: sanitize on Windows)",
+                ],
+            )
+            .choices(&["sanitize", "skip", "error"]),
+            FlagSpec::with_value(
+                &["--on-rename-collision"],
+                "MODE",
+                &[
+                    "error|keep-first|keep-last when renames send two paths",
+                    "of one commit to the same place (default: error)",
+                ],
+            )
+            .choices(&["error", "keep-first", "keep-last"]),
+        ],
+    },
+    FlagSection {
+        title: "Blob filtering & redaction:",
+        notes: &[],
+        flags: &[
+            FlagSpec::with_value(
+                &["--replace-text"],
+                "FILE",
+                &["Literal/regex (feature-gated) replacements for blobs"],
+            ),
+            FlagSpec::switch(
+                &["--decode-utf16-for-replace"],
+                &[
+                    "Apply --replace-text to UTF-16 blobs with a BOM as",
+                    "text, re-encoding them as UTF-16 with the same BOM",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--rewrite-symlink-targets"],
+                &[
+                    "Apply --replace-text to symlink targets too",
+                    "(by default links keep their original target)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--jobs"],
+                "N",
+                &[
+                    "Threads for --replace-text blob rewriting",
+                    "(default: logical cores, at most 8)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--normalize-eol"],
+                "lf|crlf",
+                &[
+                    "Convert line endings in text blobs (binary",
+                    "blobs with NUL bytes are left alone)",
+                ],
+            )
+            .choices(&["lf", "crlf"]),
+            FlagSpec::with_value(
+                &["--max-blob-size"],
+                "BYTES",
+                &[
+                    "Drop blobs larger than BYTES. Default comes from",
+                    "git config filterRepo.maxBlobSize",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--strip-blobs-with-ids"],
+                "FILE",
+                &[
+                    "Drop blobs by id (first word per line; - reads",
+                    "stdin); abbreviations of 7+ digits are resolved,",
+                    "ambiguous ones are an error",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--keep-blobs-with-ids"],
+                "FILE",
+                &[
+                    "Never strip these blob ids (same format), even",
+                    "over --max-blob-size or listed for stripping",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--strip-blobs-bigger-than-in-packs"],
+                "BYTES",
+                &[
+                    "Scan the object store like --analyze and strip",
+                    "every blob over BYTES by id (supports K/M/G)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--strict-blob-ids"],
+                &[
+                    "Fail on --strip/--keep-blobs-with-ids lines",
+                    "that are not ids instead of skipping them",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--delete-duplicate-paths"],
+                "GLOB",
+                &[
+                    "Drop paths matching GLOB that add a blob another",
+                    "path of the same commit also adds (repeatable)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--prune-stale-attribute-files"],
+                &[
+                    "Drop a directory's .gitignore/.gitattributes from",
+                    "commits whose other paths under it were all",
+                    "filtered out (never the root ones)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--strip-paths-from-file"],
+                "FILE",
+                &[
+                    "Remove filechanges whose path exactly matches a line",
+                    "of FILE (C-style quoted lines allowed), in every commit",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--strip-file-modes"],
+                "MODE[,MODE]",
+                &[
+                    "Drop files added with these modes (100755, 120000,",
+                    "160000); with --rewrite-gitmodules, stripped gitlinks",
+                    "also leave .gitmodules",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--strip-file-modes-glob"],
+                "GLOB",
+                &["Only strip modes under paths matching GLOB (repeatable)"],
+            ),
+            FlagSpec::with_value(
+                &["--max-in-memory-blob-size"],
+                "BYTES",
+                &[
+                    "Copy blobs over BYTES through without --replace-text",
+                    "instead of loading them (default 128M)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--no-size-cache"],
+                &[
+                    "Do not keep every blob size in memory; look sizes up",
+                    "per blob instead (for memory-constrained machines)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--no-rewrite-if-unchanged"],
+                &[
+                    "Do not re-import blobs no filter changed; commits name",
+                    "them by their original id (in-place runs only)",
+                ],
+            ),
+        ],
+    },
+    FlagSection {
+        title: "Commit, tag & ref updates:",
+        notes: &[],
+        flags: &[
+            FlagSpec::with_value(
+                &["--replace-message"],
+                "FILE",
+                &["Literal replacements in commit/tag messages"],
+            ),
+            FlagSpec::with_value(
+                &["--wrap-body"],
+                "COLS",
+                &[
+                    "Reflow commit message bodies to COLS columns",
+                    "(subject, trailers and code blocks are kept)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--max-commit-message-size"],
+                "BYTES",
+                &[
+                    "Truncate longer commit/tag messages, keeping the",
+                    "subject line and ending them with '...'",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--message-annotate"],
+                "TEMPLATE",
+                &[
+                    "Add a trailer to rewritten commits; {old_oid},",
+                    "{new_mark} and {date} are expanded",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--message-annotate-all"],
+                &["Add the --message-annotate trailer to every commit"],
+            ),
+            FlagSpec::with_value(
+                &["--strip-trailer"],
+                "KEY",
+                &[
+                    "Remove trailers with this key from commit messages; repeatable",
+                    "Prefix with 'regex:' to match the whole 'Key: value' line",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--lightweight-tags"],
+                &[
+                    "Convert annotated tags to lightweight tags on",
+                    "the rewritten commit (tag objects are dropped)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--annotate-tags"],
+                "TEMPLATE",
+                &[
+                    "Promote lightweight tags to annotated tags with",
+                    "message TEMPLATE (%(tag) expands to the tag name)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--replace-tag-message"],
+                "FILE",
+                &[
+                    "Like --replace-message, but only for annotated",
+                    "tag messages",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--replace-identity"],
+                "'REGEX==>REPL'",
+                &[
+                    "Rewrite author/committer/tagger names and emails",
+                    "matching REGEX ($1 refers to groups); repeatable",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--no-update-hash-mentions"],
+                &[
+                    "Keep commit ids mentioned in messages as-is",
+                    "(default: rewrite them to the new ids)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--tag-rename"],
+                "OLD:NEW",
+                &["Rename tags with given prefix"],
+            ),
+            FlagSpec::with_value(
+                &["--tag-rename-file"],
+                "FILE",
+                &[
+                    "Rename tags listed as 'old<TAB>new' lines; applied",
+                    "before the --tag-rename prefix rule",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--branch-rename"],
+                "OLD:NEW",
+                &["Rename branches with given prefix"],
+            ),
+            FlagSpec::switch(
+                &["--rename-tags-to-match-branches"],
+                &[
+                    "Apply the --branch-rename prefix rule to tags too",
+                    "(an explicit --tag-rename wins)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--ref-rename-file"],
+                "FILE",
+                &[
+                    "Rename exact refs from 'old-ref new-ref' lines",
+                    "(wins over --tag-rename/--branch-rename)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--update-message-refs"],
+                &[
+                    "Also rename whole-word mentions of renamed",
+                    "tags/branches in commit and tag messages",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--prune-empty"],
+                "MODE",
+                &[
+                    "Empty-commit policy: always|auto|never (default:",
+                    "always; auto keeps commits that were empty before",
+                    "filtering)",
+                ],
+            )
+            .choices(&["always", "auto", "never"]),
+            FlagSpec::with_value(
+                &["--strip-empty-commits-in-range"],
+                "RANGE",
+                &[
+                    "Prune all empty commits in RANGE (e.g. HEAD~3..HEAD);",
+                    "commits outside RANGE follow --prune-empty",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--drop-empty-commits-message"],
+                "MSG",
+                &[
+                    "Keep commits that would be pruned as empty, as",
+                    "empty commits with message MSG",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--strip-commits-by-author"],
+                "PATTERN",
+                &[
+                    "Drop commits by this author email (or regex:RE),",
+                    "folding their changes into their children (repeatable)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--strip-merges-too"],
+                &["Also drop merge commits matched by --strip-commits-by-author"],
+            ),
+            FlagSpec::switch(
+                &["--canonicalize-merge-parents"],
+                &[
+                    "Reorder merge parents so the one continuing the branch's",
+                    "previous tip comes first; trees are unchanged",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--squash-consecutive-by-author"],
+                "SECONDS",
+                &[
+                    "Squash runs of linear commits by the same author email,",
+                    "each within SECONDS of the previous, into one commit",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--commits-from", "--commits-to"],
+                "SECONDS",
+                &[
+                    "Only filter commits whose committer date is in this",
+                    "window (inclusive); others keep their changes",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--protect-ref"],
+                "GLOB",
+                &[
+                    "Never rewrite refs matching GLOB (repeatable);",
+                    "e.g. release/* or refs/heads/release/*",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--preserve-original-refs"],
+                &[
+                    "Keep old values of rewritten refs under",
+                    "refs/original/ (not allowed with --sensitive)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--signed-commits"],
+                "MODE",
+                &[
+                    "Commit signatures: strip (default), abort, or keep",
+                    "(kept only on commits that come out unchanged)",
+                ],
+            )
+            .choices(&["strip", "abort", "keep"]),
+            FlagSpec::with_value(
+                &["--signed-tags"],
+                "MODE",
+                &["Tag signatures: strip (default), abort, or keep"],
+            )
+            .choices(&["strip", "abort", "keep"]),
+            FlagSpec::with_value(
+                &["--tag-sign-cmd"],
+                "CMD",
+                &[
+                    "Run CMD TAG OID (env FRRS_TAG, FRRS_OID) for each",
+                    "rewritten annotated tag, e.g. to re-sign it",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--replace-refs"],
+                "POLICY",
+                &[
+                    "What to do with refs/replace/*: delete-and-add,",
+                    "delete-no-add (default), update-no-add, update-or-add",
+                ],
+            )
+            .choices(&[
+                "delete-and-add",
+                "delete-no-add",
+                "update-no-add",
+                "update-or-add",
+            ]),
+            FlagSpec::switch(
+                &["--rewrite-notes"],
+                &[
+                    "Move notes under refs/notes/* to rewritten",
+                    "commits; drop notes on pruned commits",
+                ],
+            ),
+        ],
+    },
+    FlagSection {
+        title: "Execution behavior & output:",
+        notes: &[],
+        flags: &[
+            FlagSpec::switch(
+                &["--write-report"],
+                &["Write .git/filter-repo/report.txt summary"],
+            ),
+            FlagSpec::switch(
+                &["--verify-hash-stability"],
+                &[
+                    "List commits whose id changed in report.txt",
+                    "(check that a filter setup is a no-op)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--dump-unmatched-replace-rules"],
+                &[
+                    "List --replace-text rules that matched no blob",
+                    "in report.txt (written even without --write-report)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--deterministic"],
+                &[
+                    "Reproducible runs: stable report and ref update order;",
+                    "refuses clock-dependent options (timestamped backups,",
+                    "--annotate-tags, --tag-sign-cmd)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--state-dir"],
+                "PATH",
+                &[
+                    "Write commit-map, reports, marks and default",
+                    "backups to PATH instead of .git/filter-repo",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--incremental"],
+                &[
+                    "Seed marks from the previous run's commit-map and",
+                    "marks; only export what is new since then",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--write-parent-map"],
+                &[
+                    "Write .git/filter-repo/parent-map (old, new and",
+                    "new parent ids per commit)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--show-refs-before-after"],
+                &[
+                    "After the run, print a table of created, deleted",
+                    "and retargeted refs",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--cleanup"],
+                &[
+                    "Run post-import cleanup (reflog expire + git gc)",
+                    "(disabled by default)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--quiet"],
+                &[
+                    "Suppress informational output; errors still go",
+                    "to stderr",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--progress"],
+                &[
+                    "Show a running commit count on stderr",
+                    "(conflicts with --quiet)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--log-level"],
+                "LEVEL",
+                &[
+                    "Diagnostics on stderr: error, warn (default), info,",
+                    "debug or trace (env: FRRS_LOG)",
+                ],
+            )
+            .choices(&["error", "warn", "info", "debug", "trace"]),
+            FlagSpec::with_value(
+                &["--pipeline-timeout"],
+                "SECONDS",
+                &[
+                    "Kill fast-export/fast-import and fail when they",
+                    "have not finished after SECONDS",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--subprocess-timeout"],
+                "SECONDS",
+                &[
+                    "Kill fast-export/fast-import and fail when the stream",
+                    "makes no progress for SECONDS (0: unlimited, default)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--force", "-f"],
+                &["Bypass safety prompts and checks where applicable"],
+            ),
+            FlagSpec::switch(
+                &["--enforce-sanity"],
+                &["Explicitly enable safety checks (default behavior)"],
+            ),
+            FlagSpec::with_value(
+                &["--skip-check"],
+                "NAME",
+                &[
+                    "Skip one preflight check (repeatable; names",
+                    "from --list-checks) and keep the others",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--yes", "--assume-yes", "--assume-no"],
+                &[
+                    "Answer the in-place rewrite and old-run prompts",
+                    "without asking; with neither, a run without",
+                    "a terminal (or FRRS_NONINTERACTIVE=1) answers no",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--check"],
+                &[
+                    "Run only option validation and the preflight checks,",
+                    "printing pass/fail per check (--json for JSON);",
+                    "never modifies the repository",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--map-lookup"],
+                "OID",
+                &[
+                    "Print what an old or new commit id (or unique",
+                    "prefix) maps to in the last run's commit-map",
+                    "and exit without rewriting",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--dry-run"],
+                &["Prepare and validate without writing changes"],
+            ),
+            FlagSpec::switch(
+                &["--partial"],
+                &["Only rewrite current repo; skip remote cleanup"],
+            ),
+            FlagSpec::with_value(
+                &["--keep-remote"],
+                "NAME",
+                &[
+                    "Partial mode that keeps only the named remotes",
+                    "(repeatable); other remotes are cleaned up",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--sensitive"],
+                &[
+                    "Enable sensitive-history mode (fetch all refs,",
+                    "avoid remote cleanup; see --no-fetch)",
+                ],
+            )
+            .aliases(&["--sensitive-data-removal"]),
+            FlagSpec::switch(
+                &[
+                    "--sensitive-apply-to-messages",
+                    "--no-sensitive-apply-to-messages",
+                ],
+                &[
+                    "Also apply --replace-text rules to commit and tag",
+                    "messages (default: on with --sensitive)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--sensitive-refspec"],
+                "SPEC",
+                &[
+                    "Fetch only SPEC from origin in sensitive mode",
+                    "(repeatable; e.g. +refs/heads/*:refs/heads/*)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--no-fetch"],
+                &["In sensitive mode, skip fetching refs from origin"],
+            ),
+        ],
+    },
+    FlagSection {
+        title: "Safety & backup:",
+        notes: &[],
+        flags: &[
+            FlagSpec::switch(
+                &["--backup"],
+                &[
+                    "Create a backup bundle of selected refs before",
+                    "rewriting (skipped with --dry-run). Default",
+                    "comes from git config filterRepo.backup",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--no-backup"],
+                &[
+                    "Skip the backup bundle even if filterRepo.backup",
+                    "is set in git config",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--no-backup-if-noop"],
+                &[
+                    "With --backup, bundle the original refs after",
+                    "the rewrite, and only if it changed a ref",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--backup-path"],
+                "PATH",
+                &[
+                    "Destination directory or file for the bundle.",
+                    "If PATH is a directory, a timestamped filename",
+                    "is generated. If PATH has an extension, that",
+                    "exact file is written. Defaults to",
+                    ".git/filter-repo/backup-<timestamp>.bundle",
+                ],
+            ),
+        ],
+    },
+    FlagSection {
+        title: "Repository analysis:",
+        notes: &[],
+        flags: &[
+            FlagSpec::switch(
+                &["--analyze"],
+                &[
+                    "Collect repository metrics instead of rewriting",
+                    "(of --source; filtering flags are rejected)",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--analyze-json", "--json"],
+                &["Emit JSON-formatted analysis report (env: FRRS_JSON=1)"],
+            ),
+            FlagSpec::with_value(
+                &["--analyze-top", "--top"],
+                "N",
+                &[
+                    "Number of largest blobs/trees to show (default 10)",
+                    "(env: FRRS_TOP)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--analyze-threshold-profile"],
+                "PROFILE",
+                &[
+                    "Seed all warning thresholds from a preset: strict,",
+                    "default or lenient (explicit thresholds still win)",
+                ],
+            )
+            .choices(&["strict", "default", "lenient"]),
+            FlagSpec::with_value(
+                &["--analyze-range"],
+                "RANGE",
+                &["Only analyze commits in RANGE (e.g. main..feature)"],
+            ),
+            FlagSpec::switch(
+                &["--analyze-extensions"],
+                &["Add a by-file-type breakdown of blob counts and sizes"],
+            ),
+            FlagSpec::switch(
+                &["--analyze-fetch-first"],
+                &[
+                    "Fetch all refs from origin before analyzing",
+                    "(skipped with --no-fetch)",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--analyze-dump-blobs"],
+                "FILE",
+                &[
+                    "Write every blob in history to FILE as NDJSON",
+                    "(oid, size and first-seen path per line)",
+                ],
+            ),
+        ],
+    },
+    FlagSection {
+        title: "Debug / fast-export passthrough (require --debug-mode or FRRS_DEBUG=1):",
+        notes: &[],
+        flags: &[
+            FlagSpec::switch(
+                &["--date-order"],
+                &["Request date-order traversal from git fast-export"],
+            )
+            .debug_only(),
+            FlagSpec::with_value(
+                &["--detect-renames"],
+                "PERCENT",
+                &[
+                    "Emit R lines for renames at PERCENT similarity",
+                    "(0-100; fast-export -M)",
+                ],
+            )
+            .debug_only(),
+            FlagSpec::switch(
+                &["--detect-copies"],
+                &[
+                    "Emit C lines for copied files (fast-export -C",
+                    "--find-copies-harder; uses --detect-renames threshold)",
+                ],
+            )
+            .debug_only(),
+            FlagSpec::switch(
+                &["--no-reencode"],
+                &["Disable re-encoding of commit/tag messages"],
+            )
+            .debug_only(),
+            FlagSpec::switch(
+                &["--no-quotepath"],
+                &["Disable Git's path quoting for non-ASCII"],
+            )
+            .debug_only(),
+            FlagSpec::switch(
+                &["--no-mark-tags"],
+                &["Do not mark annotated tags in fast-export"],
+            )
+            .debug_only(),
+            FlagSpec::switch(
+                &["--mark-tags"],
+                &["Explicitly mark annotated tags in fast-export"],
+            )
+            .debug_only(),
+        ],
+    },
+    FlagSection {
+        title: "Debug / analysis thresholds (require --debug-mode or FRRS_DEBUG=1):",
+        notes: &[
+            "Configure analyze.thresholds.* via .filter-repo-rs.toml or --config.",
+            "Legacy --analyze-*-warn CLI flags remain for compatibility but emit warnings.",
+            "FRRS_WARN_BLOB_BYTES, FRRS_WARN_COMMIT_MSG_BYTES and FRRS_WARN_MAX_PARENTS",
+            "override the config file; CLI flags still take precedence.",
+        ],
+        flags: &[
+            legacy_threshold_flag(&["--analyze-total-warn"], "BYTES"),
+            legacy_threshold_flag(&["--analyze-total-critical"], "BYTES"),
+            legacy_threshold_flag(&["--analyze-large-blob"], "BYTES"),
+            legacy_threshold_flag(&["--analyze-ref-warn"], "COUNT"),
+            legacy_threshold_flag(&["--analyze-object-warn"], "COUNT"),
+            legacy_threshold_flag(&["--analyze-tree-entries"], "COUNT"),
+            legacy_threshold_flag(&["--analyze-path-length"], "LENGTH"),
+            legacy_threshold_flag(&["--analyze-duplicate-paths"], "COUNT"),
+            legacy_threshold_flag(&["--analyze-commit-msg-warn"], "BYTES"),
+            legacy_threshold_flag(&["--analyze-max-parents-warn"], "COUNT"),
+        ],
+    },
+    FlagSection {
+        title: "Debug / cleanup behavior (require --debug-mode or FRRS_DEBUG=1):",
+        notes: &[],
+        flags: &[
+            FlagSpec::switch(
+                &["--no-reset"],
+                &["Skip final 'git reset --hard' in target"],
+            )
+            .debug_only(),
+            FlagSpec::switch(
+                &["--cleanup-aggressive"],
+                &[
+                    "Extend cleanup with git gc --aggressive and",
+                    "--expire-unreachable=now",
+                ],
+            )
+            .debug_only(),
+        ],
+    },
+    FlagSection {
+        title: "Debug / stream overrides (require --debug-mode or FRRS_DEBUG=1):",
+        notes: &[],
+        flags: &[FlagSpec::with_value(
+            &["--fe_stream_override"],
+            "FILE",
+            &["Read fast-export stream from FILE instead of git"],
+        )
+        .debug_only()],
+    },
+    FlagSection {
+        title: "Misc:",
+        notes: &[],
+        flags: &[
+            FlagSpec::with_value(
+                &["--config"],
+                "FILE",
+                &[
+                    "Load options from TOML config file (default",
+                    "<source>/.filter-repo-rs.toml); CLI flags take",
+                    "precedence, unknown keys are errors",
+                ],
+            ),
+            FlagSpec::switch(
+                &["--debug-mode"],
+                &["Enable debug/test flags (same as FRRS_DEBUG=1)"],
+            ),
+            FlagSpec::switch(
+                &["--compat-filter-repo"],
+                &[
+                    "Accept upstream git filter-repo flag spellings",
+                    "(implied when run as git-filter-repo); other",
+                    "flags are rejected",
+                ],
+            ),
+            FlagSpec::with_value(
+                &["--completions"],
+                "bash|zsh|fish|powershell",
+                &[
+                    "Print a shell completion script and exit",
+                    "(debug-only flags need FRRS_DEBUG=1)",
+                ],
+            )
+            .choices(&["bash", "zsh", "fish", "powershell"]),
+            FlagSpec::switch(
+                &["--list-checks"],
+                &["List the preflight sanity checks and exit"],
+            ),
+            FlagSpec::switch(&["-h", "--help"], &["Show this help message"]),
+        ],
+    },
+];
+
+fn find_flag(arg: &str) -> Option<&'static FlagSpec> {
+    FLAG_SECTIONS
+        .iter()
+        .flat_map(|section| section.flags)
+        .find(|flag| flag.spellings().any(|name| name == arg))
+}
+
+/// Help sections for the flags usable in this mode. A section whose flags
+/// all need debug mode is left out entirely without it.
+fn flag_help_sections(debug_mode: bool) -> Vec<HelpSection> {
+    FLAG_SECTIONS
+        .iter()
+        .filter(|section| debug_mode || section.flags.iter().any(|flag| !flag.debug_only))
+        .map(|section| {
+            let notes = (!section.notes.is_empty()).then(|| HelpOption {
+                name: String::new(),
+                description: section.notes.iter().map(|line| line.to_string()).collect(),
+            });
+            let flags = section
+                .flags
+                .iter()
+                .filter(|flag| !flag.names.is_empty() && (debug_mode || !flag.debug_only))
+                .map(|flag| HelpOption {
+                    name: flag.help_name(),
+                    description: flag.help.iter().map(|line| line.to_string()).collect(),
+                });
+            HelpSection {
+                title: section.title.to_string(),
+                options: notes.into_iter().chain(flags).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Flags for `--completions`; debug-only flags are included only when
+/// `include_debug` is set.
+fn completion_flags(include_debug: bool) -> Vec<CompletionFlag> {
+    FLAG_SECTIONS
+        .iter()
+        .flat_map(|section| section.flags)
+        .filter(|flag| !flag.names.is_empty() && (include_debug || !flag.debug_only))
+        .map(|flag| CompletionFlag {
+            names: flag.names.iter().map(|name| name.to_string()).collect(),
+            value: flag.value.map(str::to_string),
+            choices: flag.choices.iter().map(|c| c.to_string()).collect(),
+            description: flag.help.first().map(|d| d.to_string()).unwrap_or_default(),
+        })
+        .collect()
 }
 
 #[allow(dead_code)]
@@ -1272,18 +3783,47 @@ pub fn print_help(debug_mode: bool) {
     println!("Usage: filter-repo-rs [options]");
     println!();
 
-    // Print base help sections
-    for section in get_base_help_sections() {
+    for section in flag_help_sections(debug_mode) {
         print!("{}", format_help_section(&section));
     }
+    print!("{}", format_help_section(&get_exit_code_help_section()));
+}
 
-    // Print debug sections if in debug mode
-    if debug_mode {
-        for section in get_debug_help_sections() {
-            print!("{}", format_help_section(&section));
-        }
+fn get_exit_code_help_section() -> HelpSection {
+    let code = |name: &str, description: &[&str]| HelpOption {
+        name: name.to_string(),
+        description: description.iter().map(|d| d.to_string()).collect(),
+    };
+    HelpSection {
+        title: "Exit codes (errors print as 'error: [CODE] message'):".to_string(),
+        options: vec![
+            code(
+                "1",
+                &[
+                    "Other failures: IO_ERROR, BACKUP_FAILED, SANITY_IO,",
+                    "STREAM_PARSE, TAG_SIGN_FAILED",
+                ],
+            ),
+            code("2", &["Usage or invalid options: INVALID_OPTIONS"]),
+            code(
+                "3",
+                &[
+                    "Sanity check failed: SANITY_UNPUSHED, SANITY_DIRTY,",
+                    "SANITY_UNTRACKED, SANITY_STASHED, SANITY_REFLOG,",
+                    "SANITY_NOT_FRESHLY_PACKED, SANITY_WORKTREES,",
+                    "SANITY_REMOTES, SANITY_GIT_DIR, SANITY_REF_CONFLICT,",
+                    "SANITY_ALREADY_RAN, SANITY_SENSITIVE_INCOMPATIBLE,",
+                    "SANITY_IN_PLACE, SANITY_SOURCE_UNREADABLE,",
+                    "SANITY_TARGET_NOT_EMPTY",
+                ],
+            ),
+            code(
+                "4",
+                &[
+                    "git subprocess failed: GIT_NOT_FOUND, FASTEXPORT_FAILED,",
+                    "FASTIMPORT_FAILED, PIPELINE_TIMEOUT, PIPELINE_STALLED",
+                ],
+            ),
+        ],
     }
-
-    // Print misc section
-    print!("{}", format_help_section(&get_misc_help_section()));
 }
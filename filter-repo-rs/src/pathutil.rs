@@ -36,6 +36,48 @@ pub fn sanitize_invalid_windows_path_bytes(p: &[u8]) -> Vec<u8> {
     p.to_vec()
 }
 
+/// Device names Windows reserves in every directory, with or without an
+/// extension (`aux`, `nul.txt`).
+const WINDOWS_RESERVED_NAMES: [&[u8]; 22] = [
+    b"con", b"prn", b"aux", b"nul", b"com1", b"com2", b"com3", b"com4", b"com5", b"com6", b"com7",
+    b"com8", b"com9", b"lpt1", b"lpt2", b"lpt3", b"lpt4", b"lpt5", b"lpt6", b"lpt7", b"lpt8",
+    b"lpt9",
+];
+
+/// Rewrite every component of `p` into a name Windows can check out, on any
+/// host: reserved characters become `_`, trailing dots and spaces are
+/// trimmed and device names get a `_` after the stem (`aux.c` -> `aux_.c`).
+pub fn windows_safe_path(p: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(p.len() + 1);
+    for (i, comp) in p.split(|&b| b == b'/').enumerate() {
+        if i > 0 {
+            out.push(b'/');
+        }
+        let mut name: Vec<u8> = comp
+            .iter()
+            .map(|&b| match b {
+                b'<' | b'>' | b':' | b'"' | b'|' | b'?' | b'*' | b'\\' => b'_',
+                _ => b,
+            })
+            .collect();
+        while name.last().is_some_and(|&c| c == b'.' || c == b' ') {
+            name.pop();
+        }
+        if name.is_empty() {
+            name.push(b'_');
+        }
+        let stem_len = name.iter().position(|&b| b == b'.').unwrap_or(name.len());
+        if WINDOWS_RESERVED_NAMES
+            .iter()
+            .any(|r| name[..stem_len].eq_ignore_ascii_case(r))
+        {
+            name.insert(stem_len, b'_');
+        }
+        out.extend_from_slice(&name);
+    }
+    out
+}
+
 #[allow(dead_code)]
 pub fn dequote_c_style_bytes(s: &[u8]) -> Vec<u8> {
     // Minimal C-style unescape: handles \\ \" \n \t \r and octal \ooo
@@ -240,3 +282,104 @@ pub fn glob_match_bytes(pat: &[u8], text: &[u8]) -> bool {
     }
     match_from(pat, text)
 }
+
+/// One gitignore-style line: a glob, optionally negated (`!`), anchored to
+/// the repository root (contains `/`) or limited to directories (trailing `/`).
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    glob: Vec<u8>,
+    negated: bool,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &[u8]) -> Option<Self> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() || line[0] == b'#' {
+            return None;
+        }
+        let mut pat = line;
+        while pat.ends_with(b" ") {
+            pat = &pat[..pat.len() - 1];
+        }
+        let negated = pat.first() == Some(&b'!');
+        // `\!` and `\#` stand for a literal leading `!` or `#`
+        if negated || pat.starts_with(b"\\!") || pat.starts_with(b"\\#") {
+            pat = &pat[1..];
+        }
+        let dir_only = pat.ends_with(b"/");
+        if dir_only {
+            pat = &pat[..pat.len() - 1];
+        }
+        let anchored = pat.contains(&b'/');
+        let pat = pat.strip_prefix(b"/").unwrap_or(pat);
+        if pat.is_empty() {
+            return None;
+        }
+        Some(Self {
+            glob: pat.to_vec(),
+            negated,
+            anchored,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &[u8], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            return glob_match_bytes(&self.glob, path);
+        }
+        let base = match path.iter().rposition(|&b| b == b'/') {
+            Some(idx) => &path[idx + 1..],
+            None => path,
+        };
+        glob_match_bytes(&self.glob, base)
+    }
+}
+
+/// Exclusion rules read from a `.filterrepoignore` file with gitignore
+/// semantics: the last matching pattern wins, `!` re-includes, and a file
+/// inside an excluded directory cannot be re-included.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let patterns = data
+            .split(|&b| b == b'\n')
+            .filter_map(IgnorePattern::parse)
+            .collect();
+        Self { patterns }
+    }
+
+    pub fn from_file(path: &std::path::Path) -> std::io::Result<Self> {
+        Ok(Self::from_bytes(&std::fs::read(path)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the file at `path` (repository-relative, `/`-separated) is excluded.
+    pub fn is_ignored(&self, path: &[u8]) -> bool {
+        for (idx, &b) in path.iter().enumerate() {
+            if b == b'/' && self.decide(&path[..idx], true) == Some(true) {
+                return true;
+            }
+        }
+        self.decide(path, false).unwrap_or(false)
+    }
+
+    fn decide(&self, path: &[u8], is_dir: bool) -> Option<bool> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(path, is_dir))
+            .map(|p| !p.negated)
+    }
+}
@@ -1,11 +1,38 @@
-use std::io;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
 
-use crate::gitutil::git_dir;
-use crate::opts::Options;
+use crate::gitutil;
+use crate::opts::{Options, SignaturePolicy};
 
-pub fn build_fast_export_cmd(opts: &Options) -> io::Result<Command> {
+/// git fast-export, ready to spawn. An expanded `--all` reaches it through
+/// `--stdin` rather than argv, which would overflow the command-line limit in
+/// repositories with many refs.
+#[derive(Debug)]
+pub struct FastExportCmd {
+    pub cmd: Command,
+    stdin_refs: Option<Vec<String>>,
+}
+
+impl FastExportCmd {
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        let mut child = self.cmd.spawn()?;
+        if let Some(refs) = self.stdin_refs.take() {
+            // fast-export reads the whole list before it writes any output
+            let mut stdin = child.stdin.take().expect("fast-export stdin is piped");
+            let written = refs.iter().try_for_each(|r| writeln!(stdin, "{r}"));
+            drop(stdin);
+            if let Err(e) = written {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        }
+        Ok(child)
+    }
+}
+
+pub fn build_fast_export_cmd(opts: &Options) -> io::Result<FastExportCmd> {
     // Test override: if provided in opts, read a prebuilt stream from that file
     if let Some(stream_path) = &opts.fe_stream_override {
         if !opts.debug_mode {
@@ -24,7 +51,10 @@ pub fn build_fast_export_cmd(opts: &Options) -> io::Result<Command> {
             } else {
                 Stdio::inherit()
             });
-            return Ok(cmd);
+            return Ok(FastExportCmd {
+                cmd,
+                stdin_refs: None,
+            });
         }
         #[cfg(not(windows))]
         {
@@ -36,27 +66,64 @@ pub fn build_fast_export_cmd(opts: &Options) -> io::Result<Command> {
             } else {
                 Stdio::inherit()
             });
-            return Ok(cmd);
+            return Ok(FastExportCmd {
+                cmd,
+                stdin_refs: None,
+            });
         }
     }
     let mut cmd = Command::new("git");
     cmd.arg("-C").arg(&opts.source);
+    // Export the real history; refs/replace/* is handled by --replace-refs
+    cmd.arg("--no-replace-objects");
     if opts.quotepath {
         cmd.arg("-c").arg("core.quotepath=false");
     }
     cmd.arg("fast-export");
-    for r in &opts.refs {
-        cmd.arg(r);
+    let stdin_refs = export_refs(opts)?;
+    if stdin_refs.is_some() {
+        cmd.arg("--stdin").stdin(Stdio::piped());
+    } else {
+        cmd.args(&opts.refs);
+    }
+    cmd.arg("--show-original-ids");
+    // Signatures are stripped, checked or kept by the signature module
+    if opts.signed_tags == SignaturePolicy::Strip {
+        cmd.arg("--signed-tags=strip");
+    } else {
+        cmd.arg("--signed-tags=verbatim");
     }
-    cmd.arg("--show-original-ids")
-        .arg("--signed-tags=strip")
-        .arg("--tag-of-filtered-object=rewrite")
+    if opts.signed_commits != SignaturePolicy::Strip && opts.git_caps.fast_export_signed_commits {
+        cmd.arg("--signed-commits=verbatim");
+    }
+    cmd.arg("--tag-of-filtered-object=rewrite")
         .arg("--fake-missing-tagger")
         .arg("--reference-excluded-parents")
         .arg("--use-done-feature");
+    if opts.incremental {
+        let (marks, seeded) = incremental_marks(opts)?;
+        if seeded {
+            cmd.arg(format!("--import-marks={}", marks.to_string_lossy()));
+        }
+        // In place, the rewritten objects are the next run's source: fast-import's marks cover them
+        if opts.source != opts.target {
+            cmd.arg(format!("--export-marks={}", marks.to_string_lossy()));
+        }
+    }
     if opts.date_order {
         cmd.arg("--date-order");
     }
+    if let Some(pct) = opts.detect_renames {
+        cmd.arg(format!("-M{}%", pct));
+    }
+    if opts.detect_copies {
+        match opts.detect_renames {
+            Some(pct) => cmd.arg(format!("-C{}%", pct)),
+            None => cmd.arg("-C"),
+        };
+        // Plain -C only considers sources modified in the same commit
+        cmd.arg("--find-copies-harder");
+    }
     // Emit --no-data only when explicitly requested or clearly safe and useful
     // Safe auto-enable criteria:
     // - Writing back into the same repository (object store available)
@@ -64,8 +131,8 @@ pub fn build_fast_export_cmd(opts: &Options) -> io::Result<Command> {
     // - Performing blob filtering by id/size (no need to see blob payloads)
     let auto_no_data = {
         let same_repo = opts.source == opts.target;
-        let no_content_replace = opts.replace_text_file.is_none();
-        let id_or_size_filters = opts.max_blob_size.is_some() || opts.strip_blobs_with_ids.is_some();
+        let no_content_replace = !opts.has_replace_text() && opts.normalize_eol.is_none();
+        let id_or_size_filters = opts.max_blob_size.is_some() || opts.strips_blobs_by_id();
         same_repo && no_content_replace && id_or_size_filters
     };
     if opts.no_data || auto_no_data {
@@ -101,13 +168,62 @@ pub fn build_fast_export_cmd(opts: &Options) -> io::Result<Command> {
             "error: git fast-export lacks --mark-tags; need git >= 2.24.0",
         ));
     }
+    // Last, so they override the defaults above (e.g. --signed-tags)
+    cmd.args(&opts.fe_args);
     cmd.stdout(Stdio::piped());
     cmd.stderr(if opts.quiet {
         Stdio::null()
     } else {
         Stdio::inherit()
     });
-    Ok(cmd)
+    Ok(FastExportCmd { cmd, stdin_refs })
+}
+
+/// `--incremental`: the marks file fast-export reads and writes, and whether
+/// a previous run left marks and a commit-map to seed this one from.
+///
+/// Rewriting in place, the next run exports the rewritten objects, which
+/// fast-import's `target-marks` already names; otherwise fast-export keeps
+/// its own `source-marks` for the source repository.
+pub(crate) fn incremental_marks(opts: &Options) -> io::Result<(PathBuf, bool)> {
+    let dir = crate::statedir::for_target(opts)?;
+    let target_marks = dir.join("target-marks");
+    let marks = if opts.source == opts.target {
+        target_marks.clone()
+    } else {
+        dir.join("source-marks")
+    };
+    let seeded = marks.is_file() && target_marks.is_file() && dir.join("commit-map").is_file();
+    Ok((marks, seeded))
+}
+
+// Refs fast-export reads from stdin, or None when `opts.refs` can be passed
+// as is. With --protect-ref, --preserve-original-refs or --rewrite-notes,
+// `--all` is expanded to the explicit list of refs that may be rewritten.
+fn export_refs(opts: &Options) -> io::Result<Option<Vec<String>>> {
+    if opts.protect_refs.is_empty() && !opts.preserve_original_refs && !opts.rewrite_notes {
+        return Ok(None);
+    }
+    let mut out = Vec::new();
+    for r in &opts.refs {
+        if r == "--all" {
+            let mut all: Vec<String> = gitutil::get_all_refs(&opts.source)?
+                .into_keys()
+                .filter(|name| !opts.is_excluded_from_export(name.as_bytes()))
+                .collect();
+            all.sort();
+            out.extend(all);
+        } else if !opts.is_excluded_from_export(r.as_bytes()) {
+            out.push(r.clone());
+        }
+    }
+    if out.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "error: every selected ref is protected by --protect-ref; nothing to rewrite",
+        ));
+    }
+    Ok(Some(out))
 }
 
 pub fn build_fast_import_cmd(opts: &Options) -> Command {
@@ -121,10 +237,14 @@ pub fn build_fast_import_cmd(opts: &Options) -> Command {
         cmd.arg("--date-format=raw-permissive");
     }
     // Export marks so we can build commit-map without in-stream get-mark
-    if let Ok(gd) = git_dir(&opts.target) {
-        let marks_path = Path::new(&gd).join("filter-repo").join("target-marks");
+    if let Ok(dir) = crate::statedir::for_target(opts) {
+        let marks_path = dir.join("target-marks");
+        if opts.incremental && matches!(incremental_marks(opts), Ok((_, true))) {
+            cmd.arg(format!("--import-marks={}", marks_path.to_string_lossy()));
+        }
         cmd.arg(format!("--export-marks={}", marks_path.to_string_lossy()));
     }
+    cmd.args(&opts.fi_args);
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::inherit());
@@ -151,8 +271,8 @@ mod tests {
         opts.git_caps.fast_export_reencode = false;
         opts.git_caps.fast_export_mark_tags = false;
 
-        let cmd = build_fast_export_cmd(&opts).expect("command");
-        let args = args_as_strings(&cmd);
+        let fe = build_fast_export_cmd(&opts).expect("command");
+        let args = args_as_strings(&fe.cmd);
         assert!(
             !args.iter().any(|arg| arg == "--reencode=yes"),
             "expected --reencode=yes to be omitted"
@@ -163,6 +283,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fast_export_passes_rename_and_copy_thresholds() {
+        let mut opts = Options::default();
+        opts.detect_renames = Some(60);
+        opts.detect_copies = true;
+
+        let args = args_as_strings(&build_fast_export_cmd(&opts).expect("command").cmd);
+        assert!(args.iter().any(|arg| arg == "-M60%"));
+        assert!(args.iter().any(|arg| arg == "-C60%"));
+        assert!(args.iter().any(|arg| arg == "--find-copies-harder"));
+    }
+
     #[test]
     fn fast_export_errors_when_mark_tags_requested_without_support() {
         let mut opts = Options::default();
@@ -218,4 +350,41 @@ mod tests {
             "expected raw-permissive to be skipped"
         );
     }
+
+    #[test]
+    fn expanded_refs_reach_fast_export_through_stdin() {
+        let temp = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=A", "-c", "user.email=a@example.com"])
+                .args(args)
+                .current_dir(temp.path())
+                .status()
+                .expect("run git");
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "root"]);
+        for i in 0..50 {
+            git(&["branch", &format!("topic/{i}")]);
+        }
+        git(&["checkout", "-q", "-b", "keep"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "protected"]);
+
+        let mut opts = Options::default();
+        opts.source = temp.path().to_path_buf();
+        opts.protect_refs.push(b"refs/heads/keep".to_vec());
+        let mut fe = build_fast_export_cmd(&opts).expect("command");
+        let args = args_as_strings(&fe.cmd);
+        assert!(args.iter().any(|arg| arg == "--stdin"));
+        assert!(!args
+            .iter()
+            .any(|arg| arg.starts_with("refs/") || arg == "--all"));
+
+        let out = fe.spawn().expect("spawn").wait_with_output().unwrap();
+        let stream = String::from_utf8_lossy(&out.stdout);
+        assert!(stream.contains("reset refs/heads/topic/49"));
+        assert!(stream.contains("refs/heads/main"));
+        assert!(!stream.contains("protected"), "{stream}");
+    }
 }
@@ -14,6 +14,7 @@
 //! * **Unpushed Changes**: Verifies local branches match their remote counterparts
 //! * **Working Tree State**: Checks for uncommitted changes, untracked files, stashes
 //! * **Replace References**: Handles Git replace references in freshness calculations
+//! * **Unusual References**: Lists refs to trees/blobs and tags of tags (a note, not a check)
 //!
 //! # Architecture
 //!
@@ -108,10 +109,10 @@
 //! }
 //! ```
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::thread;
@@ -120,8 +121,10 @@ use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use unicode_normalization::UnicodeNormalization;
 
 use crate::error::Result as FilterRepoResult;
+use crate::events::{self, Event};
 use crate::git_config::GitConfig;
 use crate::gitutil;
+use crate::logging::{self, LogLevel};
 use crate::opts::Options;
 
 /// Comprehensive error types for sanity check failures
@@ -195,6 +198,12 @@ pub enum SanityCheckError {
     },
     /// Sensitive data removal mode incompatibility error
     SensitiveDataIncompatible { option: String, suggestion: String },
+    /// Rewriting a repository in place (source == target) was not confirmed
+    InPlaceRewriteNotConfirmed { repo: PathBuf },
+    /// The source of a separate `--target` run cannot be read
+    SourceUnreadable { source: PathBuf, reason: String },
+    /// A separate `--target` already has content
+    TargetNotEmpty { target: PathBuf, reason: String },
 }
 
 /// Types of reference conflicts that can occur on different filesystems
@@ -214,6 +223,18 @@ pub enum ConflictType {
     /// Occurs when references have different Unicode normalization forms
     /// that would be treated as the same filename on some filesystems.
     UnicodeNormalization,
+
+    /// Tag rename collision
+    ///
+    /// Occurs when `--tag-rename` or `--tag-rename-file` would give two tags
+    /// the same name, or move a tag onto an existing tag that keeps its name.
+    TagRename,
+
+    /// Branch rename collision
+    ///
+    /// Occurs when `--branch-rename` or `--ref-rename-file` would give two
+    /// branches the same name, including an existing branch that keeps it.
+    BranchRename,
 }
 
 /// Information about a branch with unpushed changes
@@ -232,6 +253,13 @@ pub struct UnpushedBranch {
     pub remote_hash: Option<String>,
 }
 
+impl SanityCheckError {
+    /// Whether `--force` skips past this failure; I/O errors are never skipped.
+    pub fn is_force_bypassable(&self) -> bool {
+        !matches!(self, SanityCheckError::IoError(_))
+    }
+}
+
 impl fmt::Display for SanityCheckError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -280,6 +308,12 @@ impl fmt::Display for SanityCheckError {
                             "Reference name conflicts detected (Unicode normalization):\n"
                         )?;
                     }
+                    ConflictType::TagRename => {
+                        writeln!(f, "Tag rename collisions detected:")?;
+                    }
+                    ConflictType::BranchRename => {
+                        writeln!(f, "Branch rename collisions detected:")?;
+                    }
                 }
                 for (normalized, conflicting_refs) in conflicts {
                     write!(
@@ -289,10 +323,15 @@ impl fmt::Display for SanityCheckError {
                         conflicting_refs.join(", ")
                     )?;
                 }
-                write!(
-                    f,
-                    "These conflicts could cause issues on this filesystem.\n"
-                )?;
+                if !matches!(
+                    conflict_type,
+                    ConflictType::TagRename | ConflictType::BranchRename
+                ) {
+                    write!(
+                        f,
+                        "These conflicts could cause issues on this filesystem.\n"
+                    )?;
+                }
                 match conflict_type {
                     ConflictType::CaseInsensitive => {
                         write!(f, "Rename conflicting references to have unique case-insensitive names.\n")?;
@@ -308,6 +347,26 @@ impl fmt::Display for SanityCheckError {
                             "This typically occurs with accented characters in reference names.\n"
                         )?;
                     }
+                    ConflictType::TagRename => {
+                        writeln!(
+                            f,
+                            "The renamed tags would overwrite each other in the rewritten history."
+                        )?;
+                        writeln!(
+                            f,
+                            "Adjust --tag-rename/--tag-rename-file or delete the existing tags first."
+                        )?;
+                    }
+                    ConflictType::BranchRename => {
+                        writeln!(
+                            f,
+                            "The renamed branches would overwrite each other in the rewritten history."
+                        )?;
+                        writeln!(
+                            f,
+                            "Adjust --branch-rename/--ref-rename-file, or pass --force to keep the branch with the newest commit."
+                        )?;
+                    }
                 }
                 write!(f, "Use --force to bypass this check.")
             }
@@ -466,12 +525,49 @@ impl fmt::Display for SanityCheckError {
                 if !user_confirmed {
                     write!(
                         f,
-                        "Use --force to bypass this check or confirm continuation when prompted."
+                        "Use --force to bypass this check, or --assume-yes (or answer the prompt) to continue."
                     )
                 } else {
                     write!(f, "User declined to continue with existing state.")
                 }
             }
+            SanityCheckError::InPlaceRewriteNotConfirmed { repo } => {
+                writeln!(
+                    f,
+                    "Refusing to rewrite the history of {} in place without confirmation.",
+                    repo.display()
+                )?;
+                writeln!(
+                    f,
+                    "The rewritten refs replace the originals; the old history cannot be recovered without a backup (--backup)."
+                )?;
+                write!(
+                    f,
+                    "Use --yes (or --force) to proceed, or run from a terminal and answer the prompt."
+                )
+            }
+            SanityCheckError::SourceUnreadable { source, reason } => {
+                writeln!(
+                    f,
+                    "Cannot read the source repository {}: {}",
+                    source.display(),
+                    reason
+                )?;
+                write!(f, "Point --source at an existing git repository.")
+            }
+            SanityCheckError::TargetNotEmpty { target, reason } => {
+                writeln!(
+                    f,
+                    "The target {} is not empty: {}",
+                    target.display(),
+                    reason
+                )?;
+                writeln!(
+                    f,
+                    "A separate --target must be missing, an empty directory or a repository without refs."
+                )?;
+                write!(f, "Use --force to bypass this check.")
+            }
             SanityCheckError::SensitiveDataIncompatible { option, suggestion } => {
                 write!(
                     f,
@@ -644,8 +740,8 @@ impl From<io::Error> for GitCommandError {
 ///
 /// This struct provides structured debug logging for sanity check operations,
 /// including execution timing, context details, and check reasoning explanations.
-/// It integrates with the existing `debug_mode` flag from Options to provide
-/// comprehensive troubleshooting information when enabled.
+/// Lines go through the leveled logger at `debug` level (stderr), which
+/// debug mode, `FRRS_LOG=debug` or `--log-level debug` turn on.
 ///
 /// # Features
 ///
@@ -671,7 +767,6 @@ impl From<io::Error> for GitCommandError {
 /// ```
 pub struct DebugOutputManager {
     enabled: bool,
-    start_time: Instant,
 }
 
 impl DebugOutputManager {
@@ -683,11 +778,10 @@ impl DebugOutputManager {
     ///
     /// # Returns
     ///
-    /// Returns a new `DebugOutputManager` with the current time as start time.
+    /// Returns a new `DebugOutputManager`.
     pub fn new(debug_enabled: bool) -> Self {
         DebugOutputManager {
             enabled: debug_enabled,
-            start_time: Instant::now(),
         }
     }
 
@@ -703,52 +797,33 @@ impl DebugOutputManager {
             return;
         }
 
-        let elapsed = self.start_time.elapsed();
-        println!(
-            "[DEBUG] [{:>8.2}ms] Context created for repository: {}",
-            elapsed.as_secs_f64() * 1000.0,
+        logging::debug!(
+            "Context created for repository: {}",
             context.repo_path.display()
         );
 
-        println!(
-            "[DEBUG] [{:>8.2}ms]   Repository type: {}",
-            elapsed.as_secs_f64() * 1000.0,
+        logging::debug!(
+            "  Repository type: {}",
             if context.is_bare { "bare" } else { "non-bare" }
         );
 
-        println!(
-            "[DEBUG] [{:>8.2}ms]   References found: {}",
-            elapsed.as_secs_f64() * 1000.0,
-            context.refs.len()
-        );
+        logging::debug!("  References found: {}", context.refs.len());
 
         if !context.replace_refs.is_empty() {
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Replace references: {}",
-                elapsed.as_secs_f64() * 1000.0,
-                context.replace_refs.len()
-            );
+            logging::debug!("  Replace references: {}", context.replace_refs.len());
         }
 
-        println!(
-            "[DEBUG] [{:>8.2}ms]   Case-insensitive filesystem: {}",
-            elapsed.as_secs_f64() * 1000.0,
+        logging::debug!(
+            "  Case-insensitive filesystem: {}",
             context.config.ignore_case
         );
 
         if context.config.precompose_unicode {
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Unicode precomposition enabled",
-                elapsed.as_secs_f64() * 1000.0
-            );
+            logging::debug!("  Unicode precomposition enabled");
         }
 
         if let Some(ref remote_url) = context.config.origin_url {
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Remote origin URL: {}",
-                elapsed.as_secs_f64() * 1000.0,
-                remote_url
-            );
+            logging::debug!("  Remote origin URL: {}", remote_url);
         }
     }
 
@@ -771,7 +846,6 @@ impl DebugOutputManager {
             return;
         }
 
-        let elapsed = self.start_time.elapsed();
         let command_str = format!("git {}", args.join(" "));
 
         match result {
@@ -782,34 +856,24 @@ impl DebugOutputManager {
                     output.clone()
                 };
 
-                println!(
-                    "[DEBUG] [{:>8.2}ms] Git command succeeded in {:>6.2}ms: {}",
-                    elapsed.as_secs_f64() * 1000.0,
+                logging::debug!(
+                    "Git command succeeded in {:>6.2}ms: {}",
                     duration.as_secs_f64() * 1000.0,
                     command_str
                 );
 
                 if !output.trim().is_empty() {
-                    println!(
-                        "[DEBUG] [{:>8.2}ms]   Output: {}",
-                        elapsed.as_secs_f64() * 1000.0,
-                        output_preview
-                    );
+                    logging::debug!("  Output: {}", output_preview);
                 }
             }
             Err(e) => {
-                println!(
-                    "[DEBUG] [{:>8.2}ms] Git command failed in {:>6.2}ms: {}",
-                    elapsed.as_secs_f64() * 1000.0,
+                logging::debug!(
+                    "Git command failed in {:>6.2}ms: {}",
                     duration.as_secs_f64() * 1000.0,
                     command_str
                 );
 
-                println!(
-                    "[DEBUG] [{:>8.2}ms]   Error: {}",
-                    elapsed.as_secs_f64() * 1000.0,
-                    e
-                );
+                logging::debug!("  Error: {}", e);
             }
         }
     }
@@ -827,96 +891,61 @@ impl DebugOutputManager {
             return;
         }
 
-        let elapsed = self.start_time.elapsed();
-
         match result {
             Ok(()) => {
-                println!(
-                    "[DEBUG] [{:>8.2}ms] Sanity check PASSED: {}",
-                    elapsed.as_secs_f64() * 1000.0,
-                    check_name
-                );
+                logging::debug!("Sanity check PASSED: {}", check_name);
 
                 // Add reasoning for why the check passed
                 match check_name {
                     "git_dir_structure" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Git directory structure is valid",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: Git directory structure is valid");
                     }
                     "reference_conflicts" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: No reference name conflicts detected",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: No reference name conflicts detected");
                     }
                     "reflog_entries" => {
-                        println!("[DEBUG] [{:>8.2}ms]   Reason: Repository appears fresh (acceptable reflog entries)", 
-                                 elapsed.as_secs_f64() * 1000.0);
+                        logging::debug!(
+                            "  Reason: Repository appears fresh (acceptable reflog entries)"
+                        );
                     }
                     "unpushed_changes" => {
-                        println!("[DEBUG] [{:>8.2}ms]   Reason: All local branches match their remote counterparts", 
-                                 elapsed.as_secs_f64() * 1000.0);
+                        logging::debug!(
+                            "  Reason: All local branches match their remote counterparts"
+                        );
                     }
                     "freshly_packed" => {
-                        println!("[DEBUG] [{:>8.2}ms]   Reason: Repository is freshly packed with acceptable object count", 
-                                 elapsed.as_secs_f64() * 1000.0);
+                        logging::debug!(
+                            "  Reason: Repository is freshly packed with acceptable object count"
+                        );
                     }
                     "remote_configuration" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Remote configuration is valid",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: Remote configuration is valid");
                     }
                     "stash_presence" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: No stashed changes found",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: No stashed changes found");
                     }
                     "working_tree_cleanliness" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Working tree is clean",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: Working tree is clean");
                     }
                     "untracked_files" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: No untracked files found",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: No untracked files found");
                     }
                     "worktree_count" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Single worktree detected",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: Single worktree detected");
                     }
                     "already_ran_detection" => {
-                        println!("[DEBUG] [{:>8.2}ms]   Reason: Already ran detection completed successfully", 
-                                 elapsed.as_secs_f64() * 1000.0);
+                        logging::debug!("  Reason: Already ran detection completed successfully");
                     }
                     "sensitive_mode_validation" => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Sensitive mode options are compatible",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: Sensitive mode options are compatible");
                     }
                     _ => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Check completed successfully",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
+                        logging::debug!("  Reason: Check completed successfully");
                     }
                 }
             }
             Err(e) => {
-                println!(
-                    "[DEBUG] [{:>8.2}ms] Sanity check FAILED: {}",
-                    elapsed.as_secs_f64() * 1000.0,
-                    check_name
-                );
+                logging::debug!("Sanity check FAILED: {}", check_name);
 
                 // Add reasoning for why the check failed
                 match e {
@@ -925,13 +954,9 @@ impl DebugOutputManager {
                         actual,
                         is_bare,
                     } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Git directory structure mismatch",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Expected: {}, Found: {}, Bare: {}",
-                            elapsed.as_secs_f64() * 1000.0,
+                        logging::debug!("  Reason: Git directory structure mismatch");
+                        logging::debug!(
+                            "  Expected: {}, Found: {}, Bare: {}",
                             expected,
                             actual,
                             is_bare
@@ -941,13 +966,9 @@ impl DebugOutputManager {
                         conflict_type,
                         conflicts,
                     } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Reference name conflicts detected",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Conflict type: {:?}, Count: {}",
-                            elapsed.as_secs_f64() * 1000.0,
+                        logging::debug!("  Reason: Reference name conflicts detected");
+                        logging::debug!(
+                            "  Conflict type: {:?}, Count: {}",
                             conflict_type,
                             conflicts.len()
                         );
@@ -955,37 +976,21 @@ impl DebugOutputManager {
                     SanityCheckError::ReflogTooManyEntries {
                         problematic_reflogs,
                     } => {
-                        println!("[DEBUG] [{:>8.2}ms]   Reason: Repository not fresh (too many reflog entries)", 
-                                 elapsed.as_secs_f64() * 1000.0);
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Problematic reflogs: {}",
-                            elapsed.as_secs_f64() * 1000.0,
-                            problematic_reflogs.len()
-                        );
+                        logging::debug!("  Reason: Repository not fresh (too many reflog entries)");
+                        logging::debug!("  Problematic reflogs: {}", problematic_reflogs.len());
                     }
                     SanityCheckError::UnpushedChanges { unpushed_branches } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Unpushed changes detected",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Unpushed branches: {}",
-                            elapsed.as_secs_f64() * 1000.0,
-                            unpushed_branches.len()
-                        );
+                        logging::debug!("  Reason: Unpushed changes detected");
+                        logging::debug!("  Unpushed branches: {}", unpushed_branches.len());
                     }
                     SanityCheckError::NotFreshlyPacked {
                         packs,
                         loose_count,
                         replace_refs_count,
                     } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Repository not freshly packed",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Packs: {}, Loose objects: {}, Replace refs: {}",
-                            elapsed.as_secs_f64() * 1000.0,
+                        logging::debug!("  Reason: Repository not freshly packed");
+                        logging::debug!(
+                            "  Packs: {}, Loose objects: {}, Replace refs: {}",
                             packs,
                             loose_count,
                             replace_refs_count
@@ -995,56 +1000,27 @@ impl DebugOutputManager {
                         staged_dirty,
                         unstaged_dirty,
                     } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Working tree not clean",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Staged dirty: {}, Unstaged dirty: {}",
-                            elapsed.as_secs_f64() * 1000.0,
+                        logging::debug!("  Reason: Working tree not clean");
+                        logging::debug!(
+                            "  Staged dirty: {}, Unstaged dirty: {}",
                             staged_dirty,
                             unstaged_dirty
                         );
                     }
                     SanityCheckError::UntrackedFiles { files } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Untracked files present",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Untracked file count: {}",
-                            elapsed.as_secs_f64() * 1000.0,
-                            files.len()
-                        );
+                        logging::debug!("  Reason: Untracked files present");
+                        logging::debug!("  Untracked file count: {}", files.len());
                     }
                     SanityCheckError::AlreadyRan { age_hours, .. } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Already ran detection triggered",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Age: {} hours",
-                            elapsed.as_secs_f64() * 1000.0,
-                            age_hours
-                        );
+                        logging::debug!("  Reason: Already ran detection triggered");
+                        logging::debug!("  Age: {} hours", age_hours);
                     }
                     SanityCheckError::SensitiveDataIncompatible { option, .. } => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: Sensitive mode incompatibility",
-                            elapsed.as_secs_f64() * 1000.0
-                        );
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Incompatible option: {}",
-                            elapsed.as_secs_f64() * 1000.0,
-                            option
-                        );
+                        logging::debug!("  Reason: Sensitive mode incompatibility");
+                        logging::debug!("  Incompatible option: {}", option);
                     }
                     _ => {
-                        println!(
-                            "[DEBUG] [{:>8.2}ms]   Reason: {}",
-                            elapsed.as_secs_f64() * 1000.0,
-                            e
-                        );
+                        logging::debug!("  Reason: {}", e);
                     }
                 }
             }
@@ -1064,61 +1040,30 @@ impl DebugOutputManager {
             return;
         }
 
-        let elapsed = self.start_time.elapsed();
-        println!(
-            "[DEBUG] [{:>8.2}ms] ========================================",
-            elapsed.as_secs_f64() * 1000.0
-        );
-        println!(
-            "[DEBUG] [{:>8.2}ms] Preflight checks completed",
-            elapsed.as_secs_f64() * 1000.0
-        );
-        println!(
-            "[DEBUG] [{:>8.2}ms]   Total duration: {:>6.2}ms",
-            elapsed.as_secs_f64() * 1000.0,
+        logging::debug!("========================================");
+        logging::debug!("Preflight checks completed");
+        logging::debug!(
+            "  Total duration: {:>6.2}ms",
             total_duration.as_secs_f64() * 1000.0
         );
-        println!(
-            "[DEBUG] [{:>8.2}ms]   Checks performed: {}",
-            elapsed.as_secs_f64() * 1000.0,
-            checks_performed
-        );
+        logging::debug!("  Checks performed: {}", checks_performed);
 
         if checks_performed > 0 {
             let avg_duration = total_duration.as_secs_f64() * 1000.0 / checks_performed as f64;
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Average check duration: {:>6.2}ms",
-                elapsed.as_secs_f64() * 1000.0,
-                avg_duration
-            );
+            logging::debug!("  Average check duration: {:>6.2}ms", avg_duration);
         }
 
         // Performance assessment
         let total_ms = total_duration.as_secs_f64() * 1000.0;
         if total_ms > 100.0 {
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Performance: SLOW (>{:.0}ms threshold)",
-                elapsed.as_secs_f64() * 1000.0,
-                100.0
-            );
+            logging::debug!("  Performance: SLOW (>{:.0}ms threshold)", 100.0);
         } else if total_ms > 50.0 {
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Performance: MODERATE (>{:.0}ms threshold)",
-                elapsed.as_secs_f64() * 1000.0,
-                50.0
-            );
+            logging::debug!("  Performance: MODERATE (>{:.0}ms threshold)", 50.0);
         } else {
-            println!(
-                "[DEBUG] [{:>8.2}ms]   Performance: FAST (<{:.0}ms threshold)",
-                elapsed.as_secs_f64() * 1000.0,
-                50.0
-            );
+            logging::debug!("  Performance: FAST (<{:.0}ms threshold)", 50.0);
         }
 
-        println!(
-            "[DEBUG] [{:>8.2}ms] ========================================",
-            elapsed.as_secs_f64() * 1000.0
-        );
+        logging::debug!("========================================");
     }
 
     /// Log a general debug message with timing
@@ -1133,12 +1078,7 @@ impl DebugOutputManager {
             return;
         }
 
-        let elapsed = self.start_time.elapsed();
-        println!(
-            "[DEBUG] [{:>8.2}ms] {}",
-            elapsed.as_secs_f64() * 1000.0,
-            message
-        );
+        logging::debug!("{}", message);
     }
 
     /// Check if debug output is enabled
@@ -1454,6 +1394,28 @@ impl GitCommandExecutor {
 }
 
 impl SanityCheckError {
+    /// Stable identifier for this failure, surfaced via `FilterRepoError::code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            SanityCheckError::GitDirStructure { .. } => "SANITY_GIT_DIR",
+            SanityCheckError::ReferenceConflict { .. } => "SANITY_REF_CONFLICT",
+            SanityCheckError::ReflogTooManyEntries { .. } => "SANITY_REFLOG",
+            SanityCheckError::UnpushedChanges { .. } => "SANITY_UNPUSHED",
+            SanityCheckError::NotFreshlyPacked { .. } => "SANITY_NOT_FRESHLY_PACKED",
+            SanityCheckError::MultipleWorktrees { .. } => "SANITY_WORKTREES",
+            SanityCheckError::StashedChanges => "SANITY_STASHED",
+            SanityCheckError::WorkingTreeNotClean { .. } => "SANITY_DIRTY",
+            SanityCheckError::UntrackedFiles { .. } => "SANITY_UNTRACKED",
+            SanityCheckError::InvalidRemotes { .. } => "SANITY_REMOTES",
+            SanityCheckError::IoError(_) => "SANITY_IO",
+            SanityCheckError::AlreadyRan { .. } => "SANITY_ALREADY_RAN",
+            SanityCheckError::SensitiveDataIncompatible { .. } => "SANITY_SENSITIVE_INCOMPATIBLE",
+            SanityCheckError::InPlaceRewriteNotConfirmed { .. } => "SANITY_IN_PLACE",
+            SanityCheckError::SourceUnreadable { .. } => "SANITY_SOURCE_UNREADABLE",
+            SanityCheckError::TargetNotEmpty { .. } => "SANITY_TARGET_NOT_EMPTY",
+        }
+    }
+
     /// Detect if the remote configuration indicates a local clone
     ///
     /// Local clones often have filesystem paths as remote URLs, which can
@@ -1553,15 +1515,14 @@ impl AlreadyRanChecker {
     /// Returns a new `AlreadyRanChecker` instance or an IO error if the
     /// `.git/filter-repo` directory cannot be created.
     pub fn new(repo_path: &Path) -> io::Result<Self> {
-        let git_dir = gitutil::git_dir(repo_path)?;
-        let tmp_dir = git_dir.join("filter-repo");
-        let ran_file = tmp_dir.join("already_ran");
-
-        // Ensure the filter-repo directory exists
-        if !tmp_dir.exists() {
-            fs::create_dir_all(&tmp_dir)?;
-        }
+        Self::with_state_dir(repo_path, None)
+    }
 
+    /// Create an AlreadyRanChecker whose marker lives in `--state-dir`
+    /// (or `.git/filter-repo` when `state_dir` is `None`).
+    pub fn with_state_dir(repo_path: &Path, state_dir: Option<&Path>) -> io::Result<Self> {
+        let tmp_dir = crate::statedir::ensure(repo_path, state_dir)?;
+        let ran_file = tmp_dir.join("already_ran");
         Ok(AlreadyRanChecker { ran_file })
     }
 
@@ -1657,6 +1618,49 @@ impl AlreadyRanChecker {
     }
 }
 
+/// Source of the answer to the "continue after an old run?" question, so
+/// preflight can be answered by flags or tests instead of a terminal.
+pub trait OldRunPrompt {
+    /// Whether to continue on a repository last filtered `age_hours` ago.
+    fn confirm_old_run(&self, checker: &AlreadyRanChecker, age_hours: u64) -> io::Result<bool>;
+}
+
+/// Asks on stdin; refuses without asking when stdin is not a terminal, so
+/// CI runs fail instead of hanging.
+pub struct TerminalPrompt {
+    pub stdin_is_terminal: bool,
+}
+
+impl OldRunPrompt for TerminalPrompt {
+    fn confirm_old_run(&self, checker: &AlreadyRanChecker, age_hours: u64) -> io::Result<bool> {
+        if !self.stdin_is_terminal {
+            return Ok(false);
+        }
+        checker.prompt_user_for_old_run(age_hours)
+    }
+}
+
+/// A preset answer, from `--assume-yes`/`--assume-no` or `FRRS_NONINTERACTIVE`.
+pub struct FixedAnswer(pub bool);
+
+impl OldRunPrompt for FixedAnswer {
+    fn confirm_old_run(&self, _checker: &AlreadyRanChecker, _age_hours: u64) -> io::Result<bool> {
+        Ok(self.0)
+    }
+}
+
+fn old_run_prompt(opts: &Options) -> Box<dyn OldRunPrompt> {
+    if opts.assume_yes {
+        Box::new(FixedAnswer(true))
+    } else if opts.assume_no || opts.non_interactive {
+        Box::new(FixedAnswer(false))
+    } else {
+        Box::new(TerminalPrompt {
+            stdin_is_terminal: io::stdin().is_terminal(),
+        })
+    }
+}
+
 /// Sensitive mode validation system
 ///
 /// This struct provides validation for option compatibility when using sensitive data removal mode.
@@ -1681,6 +1685,7 @@ impl SensitiveModeValidator {
     /// 1. `--sensitive` + `--fe_stream_override` → Error (stream override could leak sensitive data)
     /// 2. `--sensitive` + non-default `--source` → Error (non-default source could be unsafe)
     /// 3. `--sensitive` + non-default `--target` → Error (non-default target could be unsafe)
+    /// 4. `--sensitive` + `--preserve-original-refs` → Error (saved refs keep sensitive objects reachable)
     pub fn validate_options(opts: &Options) -> Result<(), SanityCheckError> {
         // Skip validation if not in sensitive mode
         if !opts.sensitive {
@@ -1698,6 +1703,25 @@ impl SensitiveModeValidator {
         // Check for source/target incompatibility
         Self::check_source_target_compatibility(opts)?;
 
+        // Check for preserved original refs incompatibility
+        Self::check_preserve_original_refs_compatibility(opts)?;
+
+        Ok(())
+    }
+
+    /// Check for `--preserve-original-refs` incompatibility
+    ///
+    /// Refs saved under `refs/original/` keep the pre-rewrite history reachable,
+    /// which would retain exactly the objects sensitive mode is meant to remove.
+    fn check_preserve_original_refs_compatibility(opts: &Options) -> Result<(), SanityCheckError> {
+        if opts.preserve_original_refs {
+            return Err(SanityCheckError::SensitiveDataIncompatible {
+                option: "--preserve-original-refs".to_string(),
+                suggestion:
+                    "Use --backup instead; preserved refs would keep the removed data reachable"
+                        .to_string(),
+            });
+        }
         Ok(())
     }
 
@@ -1821,6 +1845,111 @@ fn check_case_insensitive_conflicts(
     Ok(())
 }
 
+/// Check that tag renames do not map two tags of the source onto one name
+///
+/// Simulates `--tag-rename-file` and `--tag-rename` over every tag; a tag
+/// that keeps its name (no rule applies, or it is protected) still claims it.
+fn check_tag_rename_collisions(opts: &Options) -> Result<(), SanityCheckError> {
+    if opts.tag_prefix_rename().is_none() && opts.tag_rename_map.is_empty() {
+        return Ok(());
+    }
+    let refs = gitutil::get_all_refs(&opts.source)?;
+    let mut tags: Vec<&str> = refs
+        .keys()
+        .filter_map(|name| name.strip_prefix("refs/tags/"))
+        .collect();
+    tags.sort_unstable();
+
+    let mut claims: BTreeMap<Vec<u8>, Vec<String>> = BTreeMap::new();
+    for tag in tags {
+        let full = format!("refs/tags/{tag}");
+        let renamed = if opts.is_protected_ref(full.as_bytes()) {
+            None
+        } else {
+            opts.renamed_tag(tag.as_bytes())
+        };
+        claims
+            .entry(renamed.unwrap_or_else(|| tag.as_bytes().to_vec()))
+            .or_default()
+            .push(full);
+    }
+
+    let conflicts: Vec<(String, Vec<String>)> = claims
+        .into_iter()
+        .filter(|(_, sources)| sources.len() > 1)
+        .map(|(name, sources)| {
+            (
+                format!("refs/tags/{}", String::from_utf8_lossy(&name)),
+                sources,
+            )
+        })
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(SanityCheckError::ReferenceConflict {
+            conflict_type: ConflictType::TagRename,
+            conflicts,
+        });
+    }
+    Ok(())
+}
+
+/// Branches that `--branch-rename` and `--ref-rename-file` would give the
+/// same name, as (target ref, sorted source refs) pairs
+///
+/// A `ref_table` entry replaces the prefix rule for its ref; protected refs
+/// and branches no rule applies to keep their names and still claim them.
+pub(crate) fn branch_rename_collisions(
+    opts: &Options,
+    refs: &HashMap<String, String>,
+    ref_table: &[(Vec<u8>, Vec<u8>)],
+) -> Vec<(String, Vec<String>)> {
+    let mut claims: BTreeMap<Vec<u8>, Vec<String>> = BTreeMap::new();
+    for name in refs.keys() {
+        let full = name.as_bytes();
+        let renamed = if opts.is_protected_ref(full) {
+            None
+        } else if let Some((_, new_)) = ref_table.iter().find(|(old, _)| old == full) {
+            Some(new_.clone())
+        } else {
+            full.strip_prefix(b"refs/heads/")
+                .and_then(|short| opts.renamed_branch(short))
+                .map(|short| [b"refs/heads/".as_ref(), &short].concat())
+        };
+        let target = renamed.unwrap_or_else(|| full.to_vec());
+        if target.starts_with(b"refs/heads/") {
+            claims.entry(target).or_default().push(name.clone());
+        }
+    }
+    claims
+        .into_iter()
+        .filter(|(_, sources)| sources.len() > 1)
+        .map(|(target, mut sources)| {
+            sources.sort();
+            (String::from_utf8_lossy(&target).into_owned(), sources)
+        })
+        .collect()
+}
+
+/// Check that branch renames do not map two refs of the source onto one branch
+fn check_branch_rename_collisions(opts: &Options) -> Result<(), SanityCheckError> {
+    if opts.branch_rename.is_none() && opts.ref_rename_file.is_none() {
+        return Ok(());
+    }
+    let ref_table = match &opts.ref_rename_file {
+        Some(path) => crate::finalize::read_ref_rename_table(path)?,
+        None => Vec::new(),
+    };
+    let refs = gitutil::get_all_refs(&opts.source)?;
+    let conflicts = branch_rename_collisions(opts, &refs, &ref_table);
+    if !conflicts.is_empty() {
+        return Err(SanityCheckError::ReferenceConflict {
+            conflict_type: ConflictType::BranchRename,
+            conflicts,
+        });
+    }
+    Ok(())
+}
+
 /// Check for Unicode normalization conflicts
 fn check_unicode_normalization_conflicts(
     refs: &HashMap<String, String>,
@@ -1969,6 +2098,56 @@ fn check_replace_refs_in_loose_objects_with_context(
 }
 
 /// Check remote configuration using context
+fn check_freshly_packed_with_context(
+    ctx: &SanityCheckContext,
+    debug_manager: &DebugOutputManager,
+) -> Result<(), SanityCheckError> {
+    let executor = GitCommandExecutor::new(&ctx.repo_path);
+    let git_start = Instant::now();
+    match executor.run_command(&["count-objects", "-v"]) {
+        Ok(output) => {
+            debug_manager.log_git_command(
+                &["count-objects", "-v"],
+                git_start.elapsed(),
+                &Ok(output.clone()),
+            );
+
+            let mut packs = 0usize;
+            let mut count = 0usize;
+            for line in output.lines() {
+                if let Some(v) = line.strip_prefix("packs: ") {
+                    packs = v.trim().parse().unwrap_or(0);
+                }
+                if let Some(v) = line.strip_prefix("count: ") {
+                    count = v.trim().parse().unwrap_or(0);
+                }
+            }
+
+            // Use context-based replace references validation for freshness check
+            if check_replace_refs_in_loose_objects_with_context(ctx, packs, count) {
+                Ok(())
+            } else {
+                Err(SanityCheckError::NotFreshlyPacked {
+                    packs,
+                    loose_count: count,
+                    replace_refs_count: ctx.replace_refs.len(),
+                })
+            }
+        }
+        Err(e) => {
+            debug_manager.log_git_command(
+                &["count-objects", "-v"],
+                git_start.elapsed(),
+                &Err(e.clone()),
+            );
+            Err(SanityCheckError::IoError(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to count objects: {e}"),
+            )))
+        }
+    }
+}
+
 fn check_remote_configuration_with_context(
     ctx: &SanityCheckContext,
 ) -> Result<(), SanityCheckError> {
@@ -2243,6 +2422,128 @@ fn build_branch_mappings(refs: &HashMap<String, String>) -> io::Result<BranchMap
     })
 }
 
+/// A ref that does not lead to a commit through at most one tag
+///
+/// fast-export skips refs to trees and blobs (and tags of them), so those are
+/// left untouched by a rewrite. Tags of tags are exported with their whole
+/// chain, which the tag handling has to keep intact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusualRef {
+    /// Full reference name (e.g., "refs/tags/outer")
+    pub refname: String,
+
+    /// Type of the object behind the ref, after peeling one tag: `tree`,
+    /// `blob`, or `tag` for a tag of a tag
+    pub target_type: String,
+
+    /// Id of that object
+    pub target: String,
+
+    /// For a tag of a tag, the tag ref that points at the inner tag, if any
+    pub inner_tag_ref: Option<String>,
+}
+
+impl UnusualRef {
+    /// Whether fast-export skips this ref, leaving it untouched
+    pub fn passed_through(&self) -> bool {
+        self.target_type != "tag"
+    }
+}
+
+impl fmt::Display for UnusualRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed_through() {
+            write!(
+                f,
+                "{} -> {} {} (left untouched)",
+                self.refname, self.target_type, self.target
+            )
+        } else {
+            match &self.inner_tag_ref {
+                Some(inner) => write!(
+                    f,
+                    "{} -> tag {} (tag of {})",
+                    self.refname, self.target, inner
+                ),
+                None => write!(
+                    f,
+                    "{} -> tag {} (tag of a tag without a ref; peeled)",
+                    self.refname, self.target
+                ),
+            }
+        }
+    }
+}
+
+/// List refs that point at trees or blobs (directly or through a tag) and
+/// tags that point at other tags
+///
+/// # Arguments
+///
+/// * `repo_path` - Path to the Git repository
+///
+/// # Returns
+///
+/// Returns the unusual refs sorted by name; empty for ordinary repositories.
+pub fn find_unusual_refs(repo_path: &Path) -> io::Result<Vec<UnusualRef>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .arg("for-each-ref")
+        .arg("--format=%(objecttype) %(objectname) %(*objecttype) %(*objectname) %(refname)")
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'git -C {:?} for-each-ref' failed: {}",
+            repo_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut tag_refs: HashMap<&str, &str> = HashMap::new();
+    let mut unusual = Vec::new();
+    for line in stdout.lines() {
+        let fields: Vec<&str> = line.splitn(5, ' ').collect();
+        let [kind, oid, peeled_kind, peeled_oid, refname] = fields[..] else {
+            continue;
+        };
+        if refname.starts_with("refs/tags/") {
+            tag_refs.entry(oid).or_insert(refname);
+        }
+        let (target_type, target) = match kind {
+            "tree" | "blob" => (kind, oid),
+            "tag" if matches!(peeled_kind, "tree" | "blob" | "tag") => (peeled_kind, peeled_oid),
+            _ => continue,
+        };
+        unusual.push(UnusualRef {
+            refname: refname.to_string(),
+            target_type: target_type.to_string(),
+            target: target.to_string(),
+            inner_tag_ref: None,
+        });
+    }
+    for r in &mut unusual {
+        if !r.passed_through() {
+            r.inner_tag_ref = tag_refs.get(r.target.as_str()).map(|s| s.to_string());
+        }
+    }
+    Ok(unusual)
+}
+
+/// Warn about refs from [`find_unusual_refs`] before the export starts
+pub fn note_unusual_refs(opts: &Options, refs: &[UnusualRef]) {
+    if refs.is_empty() {
+        return;
+    }
+    let mut msg = String::from("Warning: refs not pointing at commits:");
+    for r in refs {
+        msg.push_str("\n  ");
+        msg.push_str(&r.to_string());
+    }
+    events::emit(opts, Event::Warning(msg));
+}
+
 /// Perform comprehensive sanity checks on a Git repository before filtering
 ///
 /// This function validates that a Git repository is in a safe state for
@@ -2299,12 +2600,52 @@ pub fn preflight(opts: &Options) -> FilterRepoResult<()> {
         return Ok(());
     }
     // Only enforce when requested
-    if !opts.enforce_sanity {
+    if opts.enforce_sanity {
+        do_preflight_checks(opts)?;
+        // Asked last, so a failing check is not preceded by a pointless question
+        confirm_in_place_rewrite(opts, io::stdin().is_terminal())?;
+    }
+    Ok(())
+}
+
+/// Ask before rewriting a repository in place (source and target are the
+/// same repository, the default). `--yes` answers for the user and
+/// `--force` skips the question; without a terminal, or with `--assume-no`
+/// or `FRRS_NONINTERACTIVE`, the run is refused instead of hanging.
+fn confirm_in_place_rewrite(
+    opts: &Options,
+    stdin_is_terminal: bool,
+) -> Result<(), SanityCheckError> {
+    if opts.force || opts.assume_yes || opts.dry_run || !same_repository(opts) {
         return Ok(());
     }
+    let confirmed = if opts.assume_no || opts.non_interactive || !stdin_is_terminal {
+        false
+    } else {
+        eprintln!(
+            "This rewrites the history of {} in place; the original history cannot be recovered without a backup.",
+            opts.target.display()
+        );
+        eprint!("Continue? [y/N]: ");
+        io::stderr().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+    };
+    if confirmed {
+        Ok(())
+    } else {
+        Err(SanityCheckError::InPlaceRewriteNotConfirmed {
+            repo: opts.target.clone(),
+        })
+    }
+}
 
-    do_preflight_checks(opts)?;
-    Ok(())
+pub(crate) fn same_repository(opts: &Options) -> bool {
+    match (opts.source.canonicalize(), opts.target.canonicalize()) {
+        (Ok(source), Ok(target)) => source == target,
+        _ => opts.source == opts.target,
+    }
 }
 
 /// Check for already ran detection
@@ -2312,15 +2653,20 @@ pub fn preflight(opts: &Options) -> FilterRepoResult<()> {
 /// This function implements the already ran detection logic according to requirements:
 /// - Check for existence of `.git/filter-repo/already_ran` file
 /// - Handle age-based logic with 24-hour threshold
-/// - Prompt user for confirmation on old runs
+/// - Ask `prompt` for confirmation on old runs
 /// - Bypass check when force flag is used
-fn check_already_ran_detection(repo_path: &Path, force: bool) -> Result<(), SanityCheckError> {
+fn check_already_ran_detection(
+    repo_path: &Path,
+    state_dir: Option<&Path>,
+    force: bool,
+    prompt: &dyn OldRunPrompt,
+) -> Result<(), SanityCheckError> {
     // Skip check if force flag is used
     if force {
         return Ok(());
     }
 
-    let checker = AlreadyRanChecker::new(repo_path)?;
+    let checker = AlreadyRanChecker::with_state_dir(repo_path, state_dir)?;
     let state = checker.check_already_ran()?;
 
     match state {
@@ -2334,8 +2680,8 @@ fn check_already_ran_detection(repo_path: &Path, force: bool) -> Result<(), Sani
             Ok(())
         }
         AlreadyRanState::OldRan { age_hours } => {
-            // Old run (>= 24 hours), prompt user for confirmation
-            let user_confirmed = checker.prompt_user_for_old_run(age_hours)?;
+            // Old run (>= 24 hours), ask for confirmation
+            let user_confirmed = prompt.confirm_old_run(&checker, age_hours)?;
 
             if user_confirmed {
                 // User wants to continue, update timestamp and proceed
@@ -2353,28 +2699,147 @@ fn check_already_ran_detection(repo_path: &Path, force: bool) -> Result<(), Sani
     }
 }
 
+/// Preflight checks in the order they run: the identifier used in debug
+/// output (and listed by `--list-checks`) with a one-line description.
+pub const PREFLIGHT_CHECKS: &[(&str, &str)] = &[
+    (
+        "already_ran_detection",
+        "Ask before re-running on a repository filtered more than a day ago",
+    ),
+    (
+        "sensitive_mode_validation",
+        "Options are compatible with --sensitive",
+    ),
+    (
+        "tag_rename_collisions",
+        "Tag renames do not give two tags the same name",
+    ),
+    (
+        "branch_rename_collisions",
+        "Branch renames do not give two branches the same name",
+    ),
+    (
+        "git_dir_structure",
+        "Running from the repository root (GIT_DIR is .git, or . when bare)",
+    ),
+    (
+        "reference_conflicts",
+        "No refs differ only by case or Unicode normalization",
+    ),
+    (
+        "reflog_entries",
+        "Every reflog has at most one entry (fresh clone)",
+    ),
+    (
+        "unpushed_changes",
+        "Local branches match their origin counterparts",
+    ),
+    (
+        "freshly_packed",
+        "Objects are packed (at most one pack, few loose objects)",
+    ),
+    ("remote_configuration", "The only remote is origin"),
+    ("stash_presence", "No stashed changes"),
+    ("working_tree_cleanliness", "No staged or unstaged changes"),
+    ("untracked_files", "No untracked files"),
+    ("worktree_count", "No worktrees besides the main one"),
+    (
+        "source_readable",
+        "With a separate --target: the source repository can be read",
+    ),
+    (
+        "target_empty",
+        "With a separate --target: the target is missing, empty or has no refs",
+    ),
+];
+
 fn do_preflight_checks(opts: &Options) -> Result<(), SanityCheckError> {
     let dir = &opts.target;
     let preflight_start = Instant::now();
+    let separate_target = !same_repository(opts);
     let mut checks_performed = 0;
 
     // Initialize debug output manager
-    let debug_manager = DebugOutputManager::new(opts.debug_mode);
+    let debug_manager = DebugOutputManager::new(logging::enabled(LogLevel::Debug));
     debug_manager.log_message("Starting preflight checks");
+    let skip = |check: &str| {
+        let skipped = opts.skips_check(check);
+        if skipped {
+            debug_manager.log_message(&format!("Skipping {check} (--skip-check)"));
+        }
+        skipped
+    };
+
+    // A separate target leaves the source untouched, so the source only has
+    // to be readable; the fresh-clone checks below guard in-place rewrites
+    if separate_target {
+        for (check, run) in [
+            (
+                "sensitive_mode_validation",
+                SensitiveModeValidator::validate_options as fn(&Options) -> _,
+            ),
+            ("source_readable", check_source_readable),
+            ("target_empty", check_target_empty),
+            ("tag_rename_collisions", check_tag_rename_collisions),
+            ("branch_rename_collisions", check_branch_rename_collisions),
+        ] {
+            if !skip(check) {
+                debug_manager.log_message(&format!("Running {check}"));
+                let result = run(opts);
+                debug_manager.log_sanity_check(check, &result);
+                result?;
+                checks_performed += 1;
+            }
+        }
+        if gitutil::is_repository_root(dir) && !skip("git_dir_structure") {
+            let ctx = SanityCheckContext::new(dir)?;
+            let result = check_git_dir_structure_with_context(&ctx);
+            debug_manager.log_sanity_check("git_dir_structure", &result);
+            result?;
+            checks_performed += 1;
+        }
+        debug_manager.log_preflight_summary(preflight_start.elapsed(), checks_performed);
+        return Ok(());
+    }
 
     // Check for already ran detection first (before other checks)
-    debug_manager.log_message("Checking already ran detection");
-    let result = check_already_ran_detection(dir, opts.force);
-    debug_manager.log_sanity_check("already_ran_detection", &result);
-    result?;
-    checks_performed += 1;
+    if !skip("already_ran_detection") {
+        debug_manager.log_message("Checking already ran detection");
+        let result = check_already_ran_detection(
+            dir,
+            opts.state_dir.as_deref(),
+            opts.force,
+            old_run_prompt(opts).as_ref(),
+        );
+        debug_manager.log_sanity_check("already_ran_detection", &result);
+        result?;
+        checks_performed += 1;
+    }
 
     // Validate sensitive mode option compatibility
-    debug_manager.log_message("Validating sensitive mode options");
-    let result = SensitiveModeValidator::validate_options(opts);
-    debug_manager.log_sanity_check("sensitive_mode_validation", &result);
-    result?;
-    checks_performed += 1;
+    if !skip("sensitive_mode_validation") {
+        debug_manager.log_message("Validating sensitive mode options");
+        let result = SensitiveModeValidator::validate_options(opts);
+        debug_manager.log_sanity_check("sensitive_mode_validation", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("tag_rename_collisions") {
+        debug_manager.log_message("Checking tag rename collisions");
+        let result = check_tag_rename_collisions(opts);
+        debug_manager.log_sanity_check("tag_rename_collisions", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("branch_rename_collisions") {
+        debug_manager.log_message("Checking branch rename collisions");
+        let result = check_branch_rename_collisions(opts);
+        debug_manager.log_sanity_check("branch_rename_collisions", &result);
+        result?;
+        checks_performed += 1;
+    }
 
     // Create context once to avoid repeated Git command executions
     debug_manager.log_message("Creating sanity check context");
@@ -2382,112 +2847,86 @@ fn do_preflight_checks(opts: &Options) -> Result<(), SanityCheckError> {
     debug_manager.log_context_creation(&ctx);
 
     // Run all context-based checks with enhanced error handling
-    debug_manager.log_message("Checking Git directory structure");
-    let result = check_git_dir_structure_with_context(&ctx);
-    debug_manager.log_sanity_check("git_dir_structure", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking reference conflicts");
-    let result = check_reference_conflicts_with_context(&ctx);
-    debug_manager.log_sanity_check("reference_conflicts", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking reflog entries");
-    let result = check_reflog_entries_with_context(&ctx);
-    debug_manager.log_sanity_check("reflog_entries", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking unpushed changes");
-    let result = check_unpushed_changes_with_context(&ctx);
-    debug_manager.log_sanity_check("unpushed_changes", &result);
-    result?;
-    checks_performed += 1;
-
-    // Continue with existing loose object counting logic using context
-    debug_manager.log_message("Checking repository freshness (object packing)");
-    let executor = GitCommandExecutor::new(dir);
-    let git_start = Instant::now();
-    match executor.run_command(&["count-objects", "-v"]) {
-        Ok(output) => {
-            debug_manager.log_git_command(
-                &["count-objects", "-v"],
-                git_start.elapsed(),
-                &Ok(output.clone()),
-            );
+    if !skip("git_dir_structure") {
+        debug_manager.log_message("Checking Git directory structure");
+        let result = check_git_dir_structure_with_context(&ctx);
+        debug_manager.log_sanity_check("git_dir_structure", &result);
+        result?;
+        checks_performed += 1;
+    }
 
-            let mut packs = 0usize;
-            let mut count = 0usize;
-            for line in output.lines() {
-                if let Some(v) = line.strip_prefix("packs: ") {
-                    packs = v.trim().parse().unwrap_or(0);
-                }
-                if let Some(v) = line.strip_prefix("count: ") {
-                    count = v.trim().parse().unwrap_or(0);
-                }
-            }
+    if !skip("reference_conflicts") {
+        debug_manager.log_message("Checking reference conflicts");
+        let result = check_reference_conflicts_with_context(&ctx);
+        debug_manager.log_sanity_check("reference_conflicts", &result);
+        result?;
+        checks_performed += 1;
+    }
 
-            // Use context-based replace references validation for freshness check
-            let freshly_packed =
-                check_replace_refs_in_loose_objects_with_context(&ctx, packs, count);
-            let result = if freshly_packed {
-                Ok(())
-            } else {
-                Err(SanityCheckError::NotFreshlyPacked {
-                    packs,
-                    loose_count: count,
-                    replace_refs_count: ctx.replace_refs.len(),
-                })
-            };
-            debug_manager.log_sanity_check("freshly_packed", &result);
-            result?;
-            checks_performed += 1;
-        }
-        Err(e) => {
-            debug_manager.log_git_command(
-                &["count-objects", "-v"],
-                git_start.elapsed(),
-                &Err(e.clone()),
-            );
-            return Err(SanityCheckError::IoError(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to count objects: {e}"),
-            )));
-        }
+    if !skip("reflog_entries") {
+        debug_manager.log_message("Checking reflog entries");
+        let result = check_reflog_entries_with_context(&ctx);
+        debug_manager.log_sanity_check("reflog_entries", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("unpushed_changes") {
+        debug_manager.log_message("Checking unpushed changes");
+        let result = check_unpushed_changes_with_context(&ctx);
+        debug_manager.log_sanity_check("unpushed_changes", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("freshly_packed") {
+        debug_manager.log_message("Checking repository freshness (object packing)");
+        let result = check_freshly_packed_with_context(&ctx, &debug_manager);
+        debug_manager.log_sanity_check("freshly_packed", &result);
+        result?;
+        checks_performed += 1;
     }
 
     // Continue with remaining existing checks...
-    debug_manager.log_message("Checking remote configuration");
-    let result = check_remote_configuration_with_context(&ctx);
-    debug_manager.log_sanity_check("remote_configuration", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking stash presence");
-    let result = check_stash_presence_with_context(&ctx);
-    debug_manager.log_sanity_check("stash_presence", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking working tree cleanliness");
-    let result = check_working_tree_cleanliness_with_context(&ctx);
-    debug_manager.log_sanity_check("working_tree_cleanliness", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking untracked files");
-    let result = check_untracked_files_with_context(&ctx);
-    debug_manager.log_sanity_check("untracked_files", &result);
-    result?;
-    checks_performed += 1;
-
-    debug_manager.log_message("Checking worktree count");
-    let result = check_worktree_count_with_context(&ctx);
-    debug_manager.log_sanity_check("worktree_count", &result);
-    result?;
-    checks_performed += 1;
+    if !skip("remote_configuration") {
+        debug_manager.log_message("Checking remote configuration");
+        let result = check_remote_configuration_with_context(&ctx);
+        debug_manager.log_sanity_check("remote_configuration", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("stash_presence") {
+        debug_manager.log_message("Checking stash presence");
+        let result = check_stash_presence_with_context(&ctx);
+        debug_manager.log_sanity_check("stash_presence", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("working_tree_cleanliness") {
+        debug_manager.log_message("Checking working tree cleanliness");
+        let result = check_working_tree_cleanliness_with_context(&ctx);
+        debug_manager.log_sanity_check("working_tree_cleanliness", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("untracked_files") {
+        debug_manager.log_message("Checking untracked files");
+        let result = check_untracked_files_with_context(&ctx);
+        debug_manager.log_sanity_check("untracked_files", &result);
+        result?;
+        checks_performed += 1;
+    }
+
+    if !skip("worktree_count") {
+        debug_manager.log_message("Checking worktree count");
+        let result = check_worktree_count_with_context(&ctx);
+        debug_manager.log_sanity_check("worktree_count", &result);
+        result?;
+        checks_performed += 1;
+    }
 
     // Log preflight summary
     let total_duration = preflight_start.elapsed();
@@ -2495,6 +2934,166 @@ fn do_preflight_checks(opts: &Options) -> Result<(), SanityCheckError> {
 
     Ok(())
 }
+
+/// Result of one preflight check in `--check` mode.
+#[derive(Debug)]
+pub struct CheckOutcome {
+    /// Identifier from [`PREFLIGHT_CHECKS`].
+    pub check: &'static str,
+    /// Named by `--skip-check`, so not run; `result` is then `Ok`.
+    pub skipped: bool,
+    pub result: Result<(), SanityCheckError>,
+}
+
+/// Run every preflight check, in [`PREFLIGHT_CHECKS`] order, without stopping
+/// at the first failure and regardless of `--force`.
+///
+/// Nothing is written: the already_ran marker is only read, and a run older
+/// than a day is reported as a failure instead of prompting.
+pub fn check_all(opts: &Options) -> Result<Vec<CheckOutcome>, SanityCheckError> {
+    let dir = &opts.target;
+    let debug_manager = DebugOutputManager::new(logging::enabled(LogLevel::Debug));
+    let mut outcomes = Vec::with_capacity(PREFLIGHT_CHECKS.len());
+    let mut record = |check: &'static str, run: &dyn Fn() -> Result<(), SanityCheckError>| {
+        let skipped = opts.skips_check(check);
+        let result = if skipped {
+            debug_manager.log_message(&format!("Skipping {check} (--skip-check)"));
+            Ok(())
+        } else {
+            let result = run();
+            debug_manager.log_sanity_check(check, &result);
+            result
+        };
+        outcomes.push(CheckOutcome {
+            check,
+            skipped,
+            result,
+        });
+    };
+
+    if !same_repository(opts) {
+        record("sensitive_mode_validation", &|| {
+            SensitiveModeValidator::validate_options(opts)
+        });
+        record("source_readable", &|| check_source_readable(opts));
+        record("target_empty", &|| check_target_empty(opts));
+        record("tag_rename_collisions", &|| {
+            check_tag_rename_collisions(opts)
+        });
+        record("branch_rename_collisions", &|| {
+            check_branch_rename_collisions(opts)
+        });
+        return Ok(outcomes);
+    }
+
+    record("already_ran_detection", &|| {
+        check_already_ran_read_only(dir, opts.state_dir.as_deref())
+    });
+    record("sensitive_mode_validation", &|| {
+        SensitiveModeValidator::validate_options(opts)
+    });
+    record("tag_rename_collisions", &|| {
+        check_tag_rename_collisions(opts)
+    });
+    record("branch_rename_collisions", &|| {
+        check_branch_rename_collisions(opts)
+    });
+
+    let ctx = SanityCheckContext::new(dir)?;
+    record("git_dir_structure", &|| {
+        check_git_dir_structure_with_context(&ctx)
+    });
+    record("reference_conflicts", &|| {
+        check_reference_conflicts_with_context(&ctx)
+    });
+    record("reflog_entries", &|| {
+        check_reflog_entries_with_context(&ctx)
+    });
+    record("unpushed_changes", &|| {
+        check_unpushed_changes_with_context(&ctx)
+    });
+    record("freshly_packed", &|| {
+        check_freshly_packed_with_context(&ctx, &debug_manager)
+    });
+    record("remote_configuration", &|| {
+        check_remote_configuration_with_context(&ctx)
+    });
+    record("stash_presence", &|| {
+        check_stash_presence_with_context(&ctx)
+    });
+    record("working_tree_cleanliness", &|| {
+        check_working_tree_cleanliness_with_context(&ctx)
+    });
+    record("untracked_files", &|| {
+        check_untracked_files_with_context(&ctx)
+    });
+    record("worktree_count", &|| {
+        check_worktree_count_with_context(&ctx)
+    });
+
+    Ok(outcomes)
+}
+
+// The source of a separate --target run must be a repository whose refs can be listed.
+fn check_source_readable(opts: &Options) -> Result<(), SanityCheckError> {
+    let unreadable = |reason: String| SanityCheckError::SourceUnreadable {
+        source: opts.source.clone(),
+        reason,
+    };
+    if !gitutil::is_repository_root(&opts.source) {
+        return Err(unreadable("not a git repository".to_string()));
+    }
+    gitutil::get_all_refs(&opts.source).map_err(|e| unreadable(e.to_string()))?;
+    Ok(())
+}
+
+// A separate --target is created when missing; an existing one must hold no
+// refs (a repository) or nothing at all (a plain directory).
+fn check_target_empty(opts: &Options) -> Result<(), SanityCheckError> {
+    let target = &opts.target;
+    let not_empty = |reason: String| SanityCheckError::TargetNotEmpty {
+        target: target.clone(),
+        reason,
+    };
+    if !target.exists() {
+        return Ok(());
+    }
+    if gitutil::is_repository_root(target) {
+        let refs = gitutil::get_all_refs(target)?;
+        if !refs.is_empty() {
+            return Err(not_empty(format!("it already has {} ref(s)", refs.len())));
+        }
+        return Ok(());
+    }
+    if !target.is_dir() {
+        return Err(not_empty("it is not a directory".to_string()));
+    }
+    if fs::read_dir(target)?.next().is_some() {
+        return Err(not_empty(
+            "it is a directory with files but not a git repository".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Like `check_already_ran_detection`, but never creates the state directory,
+// writes the marker or prompts.
+fn check_already_ran_read_only(
+    repo_path: &Path,
+    state_dir: Option<&Path>,
+) -> Result<(), SanityCheckError> {
+    let checker = AlreadyRanChecker {
+        ran_file: crate::statedir::resolve(repo_path, state_dir)?.join("already_ran"),
+    };
+    match checker.check_already_ran()? {
+        AlreadyRanState::NotRan | AlreadyRanState::RecentRan => Ok(()),
+        AlreadyRanState::OldRan { age_hours } => Err(SanityCheckError::AlreadyRan {
+            ran_file: checker.ran_file,
+            age_hours,
+            user_confirmed: false,
+        }),
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2780,7 +3379,7 @@ mod tests {
         fs::write(&checker.ran_file, old_timestamp.to_string())?;
 
         // Should succeed with force=true
-        let result = check_already_ran_detection(temp_repo.path(), true);
+        let result = check_already_ran_detection(temp_repo.path(), None, true, &FixedAnswer(false));
         assert!(result.is_ok());
 
         Ok(())
@@ -2791,7 +3390,8 @@ mod tests {
         let temp_repo = create_test_repo()?;
 
         // Should succeed and mark as ran
-        let result = check_already_ran_detection(temp_repo.path(), false);
+        let result =
+            check_already_ran_detection(temp_repo.path(), None, false, &FixedAnswer(false));
         assert!(result.is_ok());
 
         // Should have created the marker file
@@ -2888,8 +3488,97 @@ mod tests {
         checker.mark_as_ran()?;
 
         // Should succeed without prompting
-        let result = check_already_ran_detection(temp_repo.path(), false);
+        let result =
+            check_already_ran_detection(temp_repo.path(), None, false, &FixedAnswer(false));
+        assert!(result.is_ok());
+
+        Ok(())
+    }
+
+    fn write_old_marker(repo: &Path) -> io::Result<AlreadyRanChecker> {
+        let checker = AlreadyRanChecker::new(repo)?;
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let old_timestamp = current_time - (25 * 3600); // 25 hours ago
+        fs::write(&checker.ran_file, old_timestamp.to_string())?;
+        Ok(checker)
+    }
+
+    #[test]
+    fn test_already_ran_detection_old_run_assume_yes() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        let checker = write_old_marker(temp_repo.path())?;
+
+        let result = check_already_ran_detection(temp_repo.path(), None, false, &FixedAnswer(true));
         assert!(result.is_ok());
+        // Continuing refreshes the marker
+        assert_eq!(checker.check_already_ran()?, AlreadyRanState::RecentRan);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_already_ran_detection_old_run_assume_no() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        let checker = write_old_marker(temp_repo.path())?;
+
+        let result =
+            check_already_ran_detection(temp_repo.path(), None, false, &FixedAnswer(false));
+        assert!(matches!(
+            result,
+            Err(SanityCheckError::AlreadyRan { age_hours: 25, .. })
+        ));
+        assert!(matches!(
+            checker.check_already_ran()?,
+            AlreadyRanState::OldRan { .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_already_ran_detection_old_run_without_terminal_refuses() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        write_old_marker(temp_repo.path())?;
+
+        // Must return instead of waiting on stdin
+        let prompt = TerminalPrompt {
+            stdin_is_terminal: false,
+        };
+        let result = check_already_ran_detection(temp_repo.path(), None, false, &prompt);
+        assert!(matches!(result, Err(SanityCheckError::AlreadyRan { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preflight_old_run_follows_assume_flags() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        write_old_marker(temp_repo.path())?;
+
+        let opts = Options {
+            source: temp_repo.path().to_path_buf(),
+            target: temp_repo.path().to_path_buf(),
+            enforce_sanity: true,
+            non_interactive: true,
+            ..Default::default()
+        };
+        let result = preflight(&opts);
+        assert!(matches!(
+            result,
+            Err(crate::error::FilterRepoError::Sanity(
+                SanityCheckError::AlreadyRan { .. }
+            ))
+        ));
+
+        // --assume-yes wins over FRRS_NONINTERACTIVE
+        let opts = Options {
+            assume_yes: true,
+            ..opts
+        };
+        assert!(preflight(&opts).is_ok());
 
         Ok(())
     }
@@ -2899,9 +3588,11 @@ mod tests {
         let temp_repo = create_test_repo()?;
 
         let opts = Options {
+            source: temp_repo.path().to_path_buf(),
             target: temp_repo.path().to_path_buf(),
             force: false,
             enforce_sanity: true,
+            assume_yes: true,
             ..Default::default()
         };
 
@@ -3025,6 +3716,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn in_place_rewrite_is_refused_without_a_terminal_unless_forced() -> io::Result<()> {
+        let temp_repo = create_test_repo()?;
+        let opts = Options {
+            source: temp_repo.path().to_path_buf(),
+            target: temp_repo.path().to_path_buf(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            confirm_in_place_rewrite(&opts, false),
+            Err(SanityCheckError::InPlaceRewriteNotConfirmed { .. })
+        ));
+
+        let forced = Options {
+            force: true,
+            ..opts.clone()
+        };
+        assert!(confirm_in_place_rewrite(&forced, false).is_ok());
+        assert!(preflight(&forced).is_ok());
+
+        // Library callers that opt out of sanity checks are not prompted
+        let unchecked = Options {
+            enforce_sanity: false,
+            ..opts
+        };
+        assert!(preflight(&unchecked).is_ok());
+        Ok(())
+    }
+
     #[test]
     fn test_check_reference_conflicts_no_conflicts() -> io::Result<()> {
         let temp_repo = create_test_repo()?;
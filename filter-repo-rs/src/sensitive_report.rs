@@ -0,0 +1,354 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::opts::Options;
+
+const REPORT_TXT: &str = "sensitive-data-report.txt";
+const REPORT_JSON: &str = "sensitive-data-report.json";
+
+/// Tracks which blobs and commits contained data removed by `--replace-text`
+/// while running in `--sensitive` mode.
+#[derive(Debug, Default)]
+pub struct SensitiveDataTracker {
+    blobs: Vec<BlobHit>,
+    blob_by_mark: HashMap<u32, usize>,
+    commit_rules: BTreeMap<Vec<u8>, BTreeSet<String>>,
+    commit_messages: BTreeSet<Vec<u8>>,
+    tag_messages: BTreeSet<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct BlobHit {
+    oid: Option<Vec<u8>>,
+    path: Option<Vec<u8>>,
+    rules: BTreeSet<String>,
+    commits: BTreeSet<Vec<u8>>,
+}
+
+impl SensitiveDataTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a standalone blob whose content was rewritten.
+    pub fn record_blob(&mut self, mark: Option<u32>, oid: Option<&[u8]>, rules: Vec<String>) {
+        let idx = self.blobs.len();
+        self.blobs.push(BlobHit {
+            oid: oid.map(|o| o.to_vec()),
+            path: None,
+            rules: rules.into_iter().collect(),
+            commits: BTreeSet::new(),
+        });
+        if let Some(m) = mark {
+            self.blob_by_mark.insert(m, idx);
+        }
+    }
+
+    /// Correlate a previously recorded blob with the commit referencing it.
+    pub fn record_blob_reference(&mut self, mark: u32, commit: Option<&[u8]>, path: &[u8]) {
+        let Some(&idx) = self.blob_by_mark.get(&mark) else {
+            return;
+        };
+        let hit = &mut self.blobs[idx];
+        if hit.path.is_none() {
+            hit.path = Some(path.to_vec());
+        }
+        if let Some(c) = commit {
+            hit.commits.insert(c.to_vec());
+            self.commit_rules
+                .entry(c.to_vec())
+                .or_default()
+                .extend(hit.rules.iter().cloned());
+        }
+    }
+
+    /// Record inline file content (no blob oid) rewritten inside a commit.
+    pub fn record_inline(&mut self, commit: Option<&[u8]>, path: &[u8], rules: Vec<String>) {
+        let mut hit = BlobHit {
+            oid: None,
+            path: Some(path.to_vec()),
+            rules: rules.into_iter().collect(),
+            commits: BTreeSet::new(),
+        };
+        if let Some(c) = commit {
+            hit.commits.insert(c.to_vec());
+            self.commit_rules
+                .entry(c.to_vec())
+                .or_default()
+                .extend(hit.rules.iter().cloned());
+        }
+        self.blobs.push(hit);
+    }
+
+    /// Record a commit whose message was rewritten by the replace-text rules.
+    pub fn record_commit_message(&mut self, commit: Option<&[u8]>) {
+        if let Some(c) = commit {
+            self.commit_messages.insert(c.to_vec());
+        }
+    }
+
+    /// Record an annotated tag whose message was rewritten by the replace-text rules.
+    pub fn record_tag_message(&mut self, tag: &[u8]) {
+        self.tag_messages.insert(tag.to_vec());
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SensitiveReport {
+    blobs: Vec<BlobEntry>,
+    commits: Vec<CommitEntry>,
+    messages: MessageEntry,
+    refs: Vec<String>,
+    force_push: Vec<ForcePushEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct BlobEntry {
+    oid: Option<String>,
+    path: Option<String>,
+    rules: Vec<String>,
+    commits: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CommitEntry {
+    original: String,
+    rewritten: Option<String>,
+    status: &'static str,
+    rules: Vec<String>,
+    refs: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct MessageEntry {
+    commits: Vec<String>,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ForcePushEntry {
+    remote: String,
+    #[serde(rename = "ref")]
+    refname: String,
+}
+
+/// Write `sensitive-data-report.txt` and `sensitive-data-report.json` into the
+/// debug directory. `commit_map` maps original commit ids to their rewritten id
+/// (`None` when the commit was pruned).
+pub fn write_report(
+    opts: &Options,
+    debug_dir: &Path,
+    tracker: SensitiveDataTracker,
+    commit_map: &HashMap<Vec<u8>, Option<Vec<u8>>>,
+) -> io::Result<()> {
+    let mut commits = Vec::with_capacity(tracker.commit_rules.len());
+    for (old, rules) in &tracker.commit_rules {
+        let original = String::from_utf8_lossy(old).to_string();
+        let (rewritten, status) = if opts.dry_run {
+            (None, "dry-run")
+        } else {
+            match commit_map.get(old) {
+                Some(Some(new_)) => (Some(String::from_utf8_lossy(new_).to_string()), "rewritten"),
+                Some(None) => (None, "pruned"),
+                None => (None, "unknown"),
+            }
+        };
+        commits.push(CommitEntry {
+            original,
+            rewritten,
+            status,
+            rules: rules.iter().cloned().collect(),
+            refs: Vec::new(),
+        });
+    }
+    // In dry-run mode refs still point at the original history.
+    let lookup = |c: &CommitEntry| {
+        if opts.dry_run {
+            Some(c.original.clone())
+        } else {
+            c.rewritten.clone()
+        }
+    };
+    let wanted: HashSet<String> = commits.iter().filter_map(lookup).collect();
+    let containing = refs_containing(&opts.target, &wanted)?;
+    let mut all_refs: BTreeSet<String> = BTreeSet::new();
+    for commit in &mut commits {
+        if let Some(refs) = lookup(commit).and_then(|oid| containing.get(&oid).cloned()) {
+            all_refs.extend(refs.iter().cloned());
+            commit.refs = refs;
+        }
+    }
+
+    let blobs: Vec<BlobEntry> = tracker
+        .blobs
+        .iter()
+        .map(|b| BlobEntry {
+            oid: b
+                .oid
+                .as_ref()
+                .map(|o| String::from_utf8_lossy(o).to_string()),
+            path: b
+                .path
+                .as_ref()
+                .map(|p| String::from_utf8_lossy(p).to_string()),
+            rules: b.rules.iter().cloned().collect(),
+            commits: b
+                .commits
+                .iter()
+                .map(|c| String::from_utf8_lossy(c).to_string())
+                .collect(),
+        })
+        .collect();
+
+    let mut force_push = Vec::new();
+    for remote in list_remotes(&opts.target) {
+        for refname in all_refs
+            .iter()
+            .filter(|r| r.starts_with("refs/heads/") || r.starts_with("refs/tags/"))
+        {
+            force_push.push(ForcePushEntry {
+                remote: remote.clone(),
+                refname: refname.clone(),
+            });
+        }
+    }
+
+    let lossy = |v: &Vec<u8>| String::from_utf8_lossy(v).to_string();
+    let messages = MessageEntry {
+        commits: tracker.commit_messages.iter().map(lossy).collect(),
+        tags: tracker.tag_messages.iter().map(lossy).collect(),
+    };
+
+    let report = SensitiveReport {
+        blobs,
+        commits,
+        messages,
+        refs: all_refs.into_iter().collect(),
+        force_push,
+    };
+
+    let mut f = File::create(debug_dir.join(REPORT_TXT))?;
+    writeln!(f, "Sensitive data removal report")?;
+    writeln!(f, "\nBlobs containing removed data: {}", report.blobs.len())?;
+    for b in &report.blobs {
+        writeln!(
+            f,
+            "  {} {} (rules: {})",
+            b.oid.as_deref().unwrap_or("inline"),
+            b.path.as_deref().unwrap_or("<unknown path>"),
+            b.rules.join(", ")
+        )?;
+        for c in &b.commits {
+            writeln!(f, "    in commit {}", c)?;
+        }
+    }
+    writeln!(
+        f,
+        "\nCommits containing removed data: {}",
+        report.commits.len()
+    )?;
+    for c in &report.commits {
+        let new_ = c.rewritten.as_deref().unwrap_or("-");
+        writeln!(f, "  {} -> {} ({})", c.original, new_, c.status)?;
+        for r in &c.refs {
+            writeln!(f, "    reachable from {}", r)?;
+        }
+    }
+    writeln!(
+        f,
+        "\nCommit messages rewritten: {}",
+        report.messages.commits.len()
+    )?;
+    for c in &report.messages.commits {
+        writeln!(f, "  {}", c)?;
+    }
+    writeln!(
+        f,
+        "\nTag messages rewritten: {}",
+        report.messages.tags.len()
+    )?;
+    for t in &report.messages.tags {
+        writeln!(f, "  {}", t)?;
+    }
+    writeln!(f, "\nRefs reaching removed data: {}", report.refs.len())?;
+    for r in &report.refs {
+        writeln!(f, "  {}", r)?;
+    }
+    writeln!(
+        f,
+        "\nRemote refs requiring force-push: {}",
+        report.force_push.len()
+    )?;
+    for p in &report.force_push {
+        writeln!(f, "  {} {}", p.remote, p.refname)?;
+    }
+
+    let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+    std::fs::write(debug_dir.join(REPORT_JSON), json)?;
+    Ok(())
+}
+
+// The refs that contain each of `commits`, in refname order. Walks every ref
+// once instead of asking `for-each-ref --contains` per commit.
+fn refs_containing(
+    repo: &Path,
+    commits: &HashSet<String>,
+) -> io::Result<HashMap<String, Vec<String>>> {
+    let mut containing: HashMap<String, Vec<String>> = HashMap::new();
+    if commits.is_empty() {
+        return Ok(containing);
+    }
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("for-each-ref")
+        .arg("--format=%(refname)")
+        .output()?;
+    if !out.status.success() {
+        return Ok(containing);
+    }
+    let refs = String::from_utf8_lossy(&out.stdout);
+    for refname in refs.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let out = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .arg("rev-list")
+            .arg(refname)
+            .arg("--")
+            .output()?;
+        // Refs to trees or blobs contain no commits
+        if !out.status.success() {
+            continue;
+        }
+        for oid in String::from_utf8_lossy(&out.stdout).lines() {
+            if commits.contains(oid) {
+                containing
+                    .entry(oid.to_string())
+                    .or_default()
+                    .push(refname.to_string());
+            }
+        }
+    }
+    Ok(containing)
+}
+
+fn list_remotes(repo: &Path) -> Vec<String> {
+    match Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("remote")
+        .output()
+    {
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
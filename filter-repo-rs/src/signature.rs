@@ -0,0 +1,226 @@
+//! `--signed-commits` / `--signed-tags`: what happens to GPG/SSH signatures.
+//!
+//! Commit signatures arrive as `gpgsig <algo> <format>` headers followed by a
+//! data block; tag signatures are appended to the tag message. Rewriting an
+//! object invalidates its signature, so `keep` only keeps it when the object
+//! is emitted byte for byte as it was read and everything it points at is
+//! unchanged too. `--message-annotate` relies on the same bookkeeping to
+//! tell rewritten commits apart.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use crate::opts::{Options, SignaturePolicy};
+use crate::stream::StreamReader;
+
+const SIGNATURE_MARKERS: [&[u8]; 3] = [
+    b"-----BEGIN PGP SIGNATURE-----",
+    b"-----BEGIN SSH SIGNATURE-----",
+    b"-----BEGIN SIGNED MESSAGE-----",
+];
+
+/// Per-run signature state: the policies, the commit being buffered and the
+/// marks of commits that did not survive unchanged.
+#[derive(Debug)]
+pub struct SignatureTracker {
+    commits: SignaturePolicy,
+    tags: SignaturePolicy,
+    /// `--message-annotate` needs to know which commits were rewritten
+    annotate: bool,
+    /// Original bytes of the current commit after its `commit` line, kept
+    /// only when some policy is `keep`.
+    original: Option<Vec<u8>>,
+    /// Kept `gpgsig` blocks in the commit buffer.
+    kept: Vec<Range<usize>>,
+    /// The commit references a blob whose content was rewritten.
+    content_changed: bool,
+    rewritten: HashSet<u32>,
+}
+
+impl SignatureTracker {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            commits: opts.signed_commits,
+            tags: opts.signed_tags,
+            annotate: opts.message_annotate.is_some(),
+            original: None,
+            kept: Vec::new(),
+            content_changed: false,
+            rewritten: HashSet::new(),
+        }
+    }
+
+    fn tracking(&self) -> bool {
+        self.annotate || self.commits == SignaturePolicy::Keep || self.tags == SignaturePolicy::Keep
+    }
+
+    /// Reset per-commit state at a `commit` line.
+    pub fn start_commit(&mut self) {
+        self.original = self.tracking().then(Vec::new);
+        self.kept.clear();
+        self.content_changed = false;
+    }
+
+    /// Record bytes of the current commit as read from fast-export.
+    pub fn record_original(&mut self, bytes: &[u8]) {
+        if let Some(original) = self.original.as_mut() {
+            original.extend_from_slice(bytes);
+        }
+    }
+
+    /// Buffer for original bytes read outside the stream loop (message data).
+    pub fn original_mut(&mut self) -> Option<&mut Vec<u8>> {
+        self.original.as_mut()
+    }
+
+    /// The commit uses a blob whose content was rewritten.
+    pub fn content_changed(&mut self) {
+        self.content_changed = true;
+    }
+
+    /// Consume a `gpgsig` header and its data block, appending it to
+    /// `commit_buf` under `keep` and failing under `abort`.
+    pub fn read_commit_signature(
+        &mut self,
+        line: &[u8],
+        fe_out: &mut StreamReader,
+        orig_file: Option<&mut dyn Write>,
+        commit_buf: &mut Vec<u8>,
+        commit_oid: Option<&[u8]>,
+    ) -> io::Result<()> {
+        let mut data_line = Vec::with_capacity(32);
+        fe_out.read_until(b'\n', &mut data_line)?;
+        let payload = fe_out.read_data(&data_line, || {
+            format!("signature of {}", crate::commit::commit_context(commit_oid))
+        })?;
+        if let Some(f) = orig_file {
+            f.write_all(&data_line)?;
+            f.write_all(&payload)?;
+        }
+        self.record_original(&data_line);
+        self.record_original(&payload);
+        match self.commits {
+            SignaturePolicy::Strip => {}
+            SignaturePolicy::Abort => {
+                let oid = commit_oid.map(String::from_utf8_lossy);
+                return Err(io::Error::other(format!(
+                    "signed commit {} encountered; rerun with --signed-commits strip or keep",
+                    oid.as_deref().unwrap_or("(unknown)")
+                )));
+            }
+            SignaturePolicy::Keep => {
+                let start = commit_buf.len();
+                commit_buf.extend_from_slice(line);
+                commit_buf.extend_from_slice(&data_line);
+                commit_buf.extend_from_slice(&payload);
+                self.kept.push(start..commit_buf.len());
+            }
+        }
+        Ok(())
+    }
+
+    /// Decide the fate of a kept commit before it is written: drop its
+    /// signature if it changed, and remember its mark as rewritten. Returns
+    /// whether it was rewritten; untracked commits always count as such.
+    pub fn finish_commit(
+        &mut self,
+        commit_buf: &mut Vec<u8>,
+        mark: Option<u32>,
+        parents: &[u32],
+    ) -> bool {
+        let Some(original) = self.original.take() else {
+            return true;
+        };
+        let body_start = commit_buf
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(commit_buf.len(), |i| i + 1);
+        let original = original.strip_suffix(b"\n").unwrap_or(&original);
+        let unchanged = !self.content_changed
+            && commit_buf[body_start..] == *original
+            && parents.iter().all(|p| !self.rewritten.contains(p));
+        if unchanged {
+            return false;
+        }
+        for range in self.kept.drain(..).rev() {
+            commit_buf.drain(range);
+        }
+        if let Some(m) = mark {
+            self.rewritten.insert(m);
+        }
+        true
+    }
+
+    /// A commit was pruned; anything pointing at its mark now points elsewhere.
+    pub fn prune_commit(&mut self, mark: Option<u32>) {
+        self.original = None;
+        if let (true, Some(m)) = (self.tracking(), mark) {
+            self.rewritten.insert(m);
+        }
+    }
+
+    /// Split a trailing signature off a tag message. Under `strip` it is
+    /// dropped; under `abort` its presence is an error.
+    pub fn split_tag_signature(
+        &self,
+        mut payload: Vec<u8>,
+        tag: &[u8],
+    ) -> io::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let Some(start) = signature_start(&payload) else {
+            return Ok((payload, None));
+        };
+        match self.tags {
+            SignaturePolicy::Strip => {
+                payload.truncate(start);
+                Ok((payload, None))
+            }
+            SignaturePolicy::Abort => Err(io::Error::other(format!(
+                "signed tag {} encountered; rerun with --signed-tags strip or keep",
+                String::from_utf8_lossy(tag)
+            ))),
+            SignaturePolicy::Keep => {
+                let signature = payload.split_off(start);
+                Ok((payload, Some(signature)))
+            }
+        }
+    }
+
+    /// Whether a tag pointing at `from_line` (`from :<mark>`) would now point
+    /// at a different object.
+    pub fn tag_target_rewritten(&self, from_line: &[u8]) -> bool {
+        from_line
+            .strip_prefix(b"from :")
+            .and_then(|m| std::str::from_utf8(m).ok())
+            .and_then(|m| m.trim().parse::<u32>().ok())
+            .is_some_and(|m| self.rewritten.contains(&m))
+    }
+}
+
+// Offset of the last line that opens a signature block, like git's
+// parse_signed_buffer.
+fn signature_start(payload: &[u8]) -> Option<usize> {
+    let mut start = None;
+    let mut offset = 0;
+    for line in payload.split_inclusive(|&b| b == b'\n') {
+        if SIGNATURE_MARKERS.iter().any(|m| line.starts_with(m)) {
+            start = Some(offset);
+        }
+        offset += line.len();
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_last_signature_block() {
+        let msg = b"release\n-----BEGIN PGP SIGNATURE-----\nabc\n-----END PGP SIGNATURE-----\n";
+        assert_eq!(signature_start(msg), Some(8));
+        assert_eq!(signature_start(b"no signature here\n"), None);
+        let quoted = b"see -----BEGIN PGP SIGNATURE----- below\n";
+        assert_eq!(signature_start(quoted), None);
+    }
+}
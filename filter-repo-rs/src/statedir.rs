@@ -0,0 +1,53 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::gitutil;
+use crate::opts::Options;
+
+/// Directory for the working files of a run against `repo` (commit-map,
+/// ref-map, reports, stream dumps, marks, the already_ran marker, default
+/// backups).
+///
+/// Defaults to `<git-dir>/filter-repo`. An explicit `--state-dir` is used
+/// as-is when absolute and resolved against `repo` when relative.
+pub fn resolve(repo: &Path, state_dir: Option<&Path>) -> io::Result<PathBuf> {
+    match state_dir {
+        Some(dir) if dir.is_absolute() => Ok(dir.to_path_buf()),
+        Some(dir) => Ok(repo.join(dir)),
+        None => Ok(gitutil::git_dir(repo)?.join("filter-repo")),
+    }
+}
+
+/// Like [`resolve`], creating the directory (and any parents) if needed.
+pub fn ensure(repo: &Path, state_dir: Option<&Path>) -> io::Result<PathBuf> {
+    let dir = resolve(repo, state_dir)?;
+    fs::create_dir_all(&dir).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to create state directory {}: {e}", dir.display()),
+        )
+    })?;
+    Ok(dir)
+}
+
+/// State directory for the rewrite target.
+pub fn for_target(opts: &Options) -> io::Result<PathBuf> {
+    ensure(&opts.target, opts.state_dir.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_state_dir_resolves_against_repo() {
+        let repo = Path::new("/work/repo");
+        assert_eq!(
+            resolve(repo, Some(Path::new("state/frrs"))).unwrap(),
+            PathBuf::from("/work/repo/state/frrs")
+        );
+        let abs = std::env::temp_dir().join("frrs-state");
+        assert_eq!(resolve(repo, Some(&abs)).unwrap(), abs);
+    }
+}
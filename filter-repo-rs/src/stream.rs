@@ -1,23 +1,43 @@
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::{BTreeSet, HashMap, HashSet};
-use std::fs::{create_dir_all, File, OpenOptions};
+use std::fs::{File, OpenOptions};
 use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{ChildStdout, Command, Stdio};
 use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::error::Result as FilterRepoResult;
-use crate::gitutil::git_dir;
+use crate::blob_pool::{BlobPool, BlobRewriter, ReadyBlob, RewriteSettings, Rewritten, RuleUsage};
+use crate::blobcache::{hex_val, BlobSizeCache};
+use crate::error::{FilterRepoError, Result as FilterRepoResult};
+use crate::events::{self, Event, Phase, StripReason, Summary};
+use crate::gitutil::{git_dir, OidMatch};
+use crate::logging;
 use crate::message::blob_regex::RegexReplacer as BlobRegexReplacer;
 use crate::message::{MessageReplacer, ShortHashMapper};
-use crate::opts::Options;
+use crate::opts::{EolMode, Options, PruneEmpty, ReplaceRefsPolicy};
+use crate::pathutil::IgnoreRules;
+use crate::watchdog::{Progress, Watchdog};
 
 const REPORT_SAMPLE_LIMIT: usize = 20;
+const PROGRESS_INTERVAL: usize = 100;
 const SHA_HEX_LEN: usize = 40;
 const SHA_BIN_LEN: usize = 20;
+// Shortest abbreviation accepted in blob id lists (git's default).
+const MIN_ABBREV_LEN: usize = 7;
 const STRIP_SHA_ON_DISK_THRESHOLD: usize = 100_000;
+const SNIPPET_LIMIT: usize = 80;
+// Top-level commands forwarded verbatim; `from` follows a `reset`.
+const PASSTHROUGH_COMMANDS: [&[u8]; 6] = [
+    b"feature ",
+    b"option ",
+    b"progress ",
+    b"checkpoint\n",
+    b"reset ",
+    b"from ",
+];
 
 type ShaBytes = [u8; SHA_BIN_LEN];
 
@@ -30,36 +50,34 @@ enum StripShaLookup {
 }
 
 impl StripShaLookup {
-    fn empty() -> Self {
-        StripShaLookup::Empty
-    }
-
-    fn from_path(path: &Path) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let mut entries: Vec<ShaBytes> = Vec::new();
-        for line in reader.lines() {
-            let line = line?;
-            if line.trim().is_empty() || line.trim_start().starts_with('#') {
-                continue;
-            }
-            match parse_sha_line(&line) {
-                Some(bytes) => entries.push(bytes),
-                None => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        format!("invalid SHA entry in {}: {line}", path.display()),
+    /// Blob ids from `--strip-blobs-with-ids` plus, with
+    /// `--strip-blobs-bigger-than-in-packs`, every stored blob over the limit,
+    /// less the ids in `keep`.
+    fn load(opts: &Options, keep: &KeepShaList) -> FilterRepoResult<Self> {
+        let mut entries = match &opts.strip_blobs_with_ids {
+            Some(path) => read_sha_list(path, "--strip-blobs-with-ids", opts)?,
+            None => Vec::new(),
+        };
+        if let Some(max) = opts.strip_oversized_from_analysis {
+            let oids =
+                crate::analysis::blobs_larger_than(&opts.source, max as u64).map_err(|e| {
+                    io::Error::other(format!(
+                        "failed to scan blobs for --strip-blobs-bigger-than-in-packs: {e}"
                     ))
-                }
-            }
+                })?;
+            entries.extend(
+                oids.iter()
+                    .filter_map(|oid| parse_sha_bytes(oid.as_bytes())),
+            );
         }
+        entries.retain(|sha| !keep.contains(sha));
         if entries.is_empty() {
             return Ok(StripShaLookup::Empty);
         }
         entries.sort_unstable();
         entries.dedup();
         if entries.len() > STRIP_SHA_ON_DISK_THRESHOLD {
-            TempSortedFile::from_entries(entries).map(StripShaLookup::OnDisk)
+            Ok(TempSortedFile::from_entries(entries).map(StripShaLookup::OnDisk)?)
         } else {
             Ok(StripShaLookup::InMemory(entries))
         }
@@ -81,6 +99,81 @@ impl StripShaLookup {
     }
 }
 
+/// Blob ids from `--keep-blobs-with-ids`, which no size or id rule strips.
+#[derive(Default)]
+pub(crate) struct KeepShaList(Vec<ShaBytes>);
+
+impl KeepShaList {
+    fn load(opts: &Options) -> FilterRepoResult<Self> {
+        let mut entries = match &opts.keep_blobs_with_ids {
+            Some(path) => read_sha_list(path, "--keep-blobs-with-ids", opts)?,
+            None => Vec::new(),
+        };
+        entries.sort_unstable();
+        entries.dedup();
+        Ok(KeepShaList(entries))
+    }
+
+    fn contains(&self, sha: &ShaBytes) -> bool {
+        self.0.binary_search(sha).is_ok()
+    }
+
+    fn contains_hex(&self, sha_hex: &[u8]) -> bool {
+        !self.0.is_empty() && parse_sha_bytes(sha_hex).is_some_and(|sha| self.contains(&sha))
+    }
+}
+
+/// Read a blob id list given to `flag`, or standard input for `-`. Only the
+/// first word of a line counts, so `<sha> <size> <path>` listings work as-is.
+/// Abbreviated ids are resolved against the source repository; an ambiguous
+/// one is an error, while lines that are not ids are skipped unless
+/// `--strict-blob-ids`.
+fn read_sha_list(path: &Path, flag: &str, opts: &Options) -> FilterRepoResult<Vec<ShaBytes>> {
+    let read = || -> FilterRepoResult<Vec<ShaBytes>> {
+        let (source, content) = if path == Path::new("-") {
+            let mut buf = String::new();
+            io::stdin().lock().read_to_string(&mut buf)?;
+            ("stdin".to_string(), buf)
+        } else {
+            (path.display().to_string(), std::fs::read_to_string(path)?)
+        };
+        let ids: Vec<&str> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        let resolved = resolve_abbreviated_shas(&source, &ids, &opts.source)?;
+        let mut entries: Vec<ShaBytes> = Vec::new();
+        for id in ids {
+            let parsed =
+                parse_sha_line(id).or_else(|| resolved.get(&id.to_ascii_lowercase()).copied());
+            match parsed {
+                Some(bytes) => entries.push(bytes),
+                None if opts.strict_blob_ids => {
+                    return Err(FilterRepoError::invalid_options(format!(
+                        "invalid SHA entry in {source}: {id}"
+                    )))
+                }
+                None => events::emit(
+                    opts,
+                    Event::Warning(format!(
+                        "Warning: skipping invalid SHA entry in {source}: {id}"
+                    )),
+                ),
+            }
+        }
+        Ok(entries)
+    };
+    read().map_err(|e| match e {
+        FilterRepoError::Io(e) => FilterRepoError::Io(io::Error::new(
+            io::ErrorKind::Other,
+            format!("failed to load {flag}: {e}"),
+        )),
+        e => e,
+    })
+}
+
 struct TempSortedFile {
     path: PathBuf,
     file: RefCell<File>,
@@ -128,6 +221,148 @@ impl Drop for TempSortedFile {
     }
 }
 
+/// fast-export output that counts the lines it hands out, so parse errors
+/// can name the offending line.
+pub(crate) struct StreamReader<R = BufReader<ChildStdout>> {
+    inner: R,
+    lines: usize,
+    progress: Option<Progress>,
+}
+
+impl<R: BufRead> StreamReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        StreamReader {
+            inner,
+            lines: 0,
+            progress: None,
+        }
+    }
+
+    /// Tell the watchdog about every byte consumed (`--subprocess-timeout`).
+    pub(crate) fn report_progress(&mut self, progress: Progress) {
+        self.progress = Some(progress);
+    }
+
+    /// A `StreamParse` error for `line`, the last line read.
+    pub(crate) fn error(&self, line: &[u8], reason: impl Into<String>) -> io::Error {
+        stream_parse_error(self.lines, line, reason.into())
+    }
+
+    /// Length declared by a `data <n>` header.
+    pub(crate) fn data_len(
+        &self,
+        header: &[u8],
+        context: impl Fn() -> String,
+    ) -> io::Result<usize> {
+        header
+            .strip_prefix(b"data ")
+            .and_then(|s| std::str::from_utf8(s).ok())
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .ok_or_else(|| self.error(header, format!("invalid data header in {}", context())))
+    }
+
+    /// Read the payload of the `data <n>` block whose header was just read.
+    /// `context` names the object it belongs to (e.g. `blob :3`) in errors.
+    pub(crate) fn read_data(
+        &mut self,
+        header: &[u8],
+        context: impl Fn() -> String,
+    ) -> io::Result<Vec<u8>> {
+        let n = self.data_len(header, &context)?;
+        let header_line = self.lines;
+        let mut payload = Vec::with_capacity(n.min(1 << 20));
+        self.take(n as u64).read_to_end(&mut payload)?;
+        if payload.len() < n {
+            return Err(truncated_data(
+                header_line,
+                header,
+                n,
+                payload.len(),
+                context,
+            ));
+        }
+        Ok(payload)
+    }
+
+    /// Hand the `n`-byte payload of `header` to `sink` chunk by chunk
+    /// instead of loading it into memory.
+    pub(crate) fn copy_data(
+        &mut self,
+        header: &[u8],
+        n: usize,
+        context: impl Fn() -> String,
+        mut sink: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let header_line = self.lines;
+        let mut left = n;
+        while left > 0 {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Err(truncated_data(header_line, header, n, n - left, context));
+            }
+            let chunk = buf.len().min(left);
+            sink(&buf[..chunk])?;
+            self.consume(chunk);
+            left -= chunk;
+        }
+        Ok(())
+    }
+}
+
+fn truncated_data(
+    header_line: usize,
+    header: &[u8],
+    declared: usize,
+    got: usize,
+    context: impl Fn() -> String,
+) -> io::Error {
+    let reason = format!(
+        "{}: data block declares {declared} bytes but the stream ended after {got}",
+        context()
+    );
+    stream_parse_error(header_line, header, reason)
+}
+
+fn stream_parse_error(line_number: usize, line: &[u8], reason: String) -> io::Error {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let snippet = &line[..line.len().min(SNIPPET_LIMIT)];
+    FilterRepoError::StreamParse {
+        line_number,
+        snippet: String::from_utf8_lossy(snippet).into_owned(),
+        reason,
+    }
+    .into()
+}
+
+impl<R: BufRead> Read for StreamReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.inner.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for StreamReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt > 0 {
+            if let Ok(buf) = self.inner.fill_buf() {
+                let consumed = &buf[..amt.min(buf.len())];
+                self.lines += consumed.iter().filter(|&&b| b == b'\n').count();
+            }
+            if let Some(progress) = &self.progress {
+                progress.advance(amt);
+            }
+        }
+        self.inner.consume(amt);
+    }
+}
+
 fn create_temp_file(prefix: &str) -> io::Result<(PathBuf, File)> {
     let temp_dir = std::env::temp_dir();
     for attempt in 0..1000 {
@@ -164,6 +399,48 @@ fn parse_sha_line(line: &str) -> Option<ShaBytes> {
     parse_sha_bytes(line.trim().as_bytes())
 }
 
+// Expand the abbreviated ids (7 to 39 hex digits) among `ids` with one
+// `git cat-file --batch-check` pass; ids matching nothing are left out.
+fn resolve_abbreviated_shas(
+    source: &str,
+    ids: &[&str],
+    repo: &Path,
+) -> FilterRepoResult<HashMap<String, ShaBytes>> {
+    let abbrevs: Vec<String> = ids
+        .iter()
+        .map(|id| id.to_ascii_lowercase())
+        .filter(|id| {
+            (MIN_ABBREV_LEN..SHA_HEX_LEN).contains(&id.len())
+                && id.bytes().all(|b| b.is_ascii_hexdigit())
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if abbrevs.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let matches = crate::gitutil::resolve_oid_prefixes(repo, &abbrevs)?;
+    let mut resolved = HashMap::new();
+    for (abbrev, found) in abbrevs.into_iter().zip(matches) {
+        match found {
+            OidMatch::Unique(oid) => {
+                if let Some(bytes) = parse_sha_bytes(oid.as_bytes()) {
+                    resolved.insert(abbrev, bytes);
+                }
+            }
+            OidMatch::Ambiguous => {
+                let candidates = crate::gitutil::disambiguate_oid(repo, &abbrev)?;
+                return Err(FilterRepoError::invalid_options(format!(
+                    "ambiguous abbreviated SHA in {source}: {abbrev} matches {}",
+                    candidates.join(", ")
+                )));
+            }
+            OidMatch::Missing => {}
+        }
+    }
+    Ok(resolved)
+}
+
 fn parse_sha_bytes(bytes: &[u8]) -> Option<ShaBytes> {
     if bytes.len() != SHA_HEX_LEN {
         return None;
@@ -177,20 +454,16 @@ fn parse_sha_bytes(bytes: &[u8]) -> Option<ShaBytes> {
     Some(out)
 }
 
-fn hex_val(b: u8) -> Option<u8> {
-    match b {
-        b'0'..=b'9' => Some(b - b'0'),
-        b'a'..=b'f' => Some(b - b'a' + 10),
-        b'A'..=b'F' => Some(b - b'A' + 10),
-        _ => None,
-    }
-}
-
 pub(crate) struct BlobSizeTracker {
     source: PathBuf,
     max_blob_size: Option<usize>,
-    oversize: HashSet<Vec<u8>>,
+    /// Every blob size of the source, unless `--no-size-cache`
+    cache: Option<BlobSizeCache>,
+    /// Oversized blob ids with their sizes (without the cache)
+    oversize: HashMap<Vec<u8>, usize>,
     prefetch_ok: bool,
+    /// Ids from `--keep-blobs-with-ids`, never reported as oversized
+    keep: KeepShaList,
 }
 
 impl BlobSizeTracker {
@@ -198,17 +471,34 @@ impl BlobSizeTracker {
         let mut tracker = BlobSizeTracker {
             source: opts.source.clone(),
             max_blob_size: opts.max_blob_size,
-            oversize: HashSet::new(),
+            cache: None,
+            oversize: HashMap::new(),
             prefetch_ok: false,
+            keep: KeepShaList::default(),
         };
-        if opts.max_blob_size.is_some() {
+        let wants_sizes = opts.max_blob_size.is_some() || opts.strips_blobs_by_id();
+        if opts.size_cache && wants_sizes {
+            match BlobSizeCache::load(&opts.source) {
+                Ok(cache) => {
+                    tracker.cache = Some(cache);
+                    tracker.prefetch_ok = true;
+                }
+                Err(e) => events::emit(
+                    opts,
+                    Event::Warning(format!(
+                        "Warning: loading blob sizes failed ({e}), falling back to on-demand sizing"
+                    )),
+                ),
+            }
+        } else if opts.max_blob_size.is_some() {
             if let Err(e) = tracker.prefetch_oversize() {
                 tracker.oversize.clear();
-                if !opts.quiet {
-                    eprintln!(
-            "Warning: batch blob size pre-computation failed ({e}), falling back to on-demand sizing"
-          );
-                }
+                events::emit(
+                    opts,
+                    Event::Warning(format!(
+                        "Warning: batch blob size pre-computation failed ({e}), falling back to on-demand sizing"
+                    )),
+                );
             }
         }
         tracker
@@ -276,7 +566,7 @@ impl BlobSizeTracker {
                 .and_then(|s| s.trim().parse::<usize>().ok())
                 .unwrap_or(0);
             if size > max {
-                self.oversize.insert(sha.to_vec());
+                self.oversize.insert(sha.to_vec(), size);
             }
         }
         let mut stderr_buf = Vec::new();
@@ -295,17 +585,69 @@ impl BlobSizeTracker {
         Ok(())
     }
 
+    /// Exempt the `--keep-blobs-with-ids` list from the size limit.
+    pub(crate) fn keeping(mut self, keep: KeepShaList) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Whether `sha` is on the `--keep-blobs-with-ids` list.
+    pub(crate) fn is_kept(&self, sha: &[u8]) -> bool {
+        self.keep.contains_hex(sha)
+    }
+
     pub(crate) fn is_oversize(&mut self, sha: &[u8]) -> bool {
         let max = match self.max_blob_size {
             Some(m) => m,
             None => return false,
         };
-        if self.oversize.contains(sha) {
+        if self.is_kept(sha) {
+            return false;
+        }
+        if let Some(cache) = &self.cache {
+            return cache.get(sha).is_some_and(|size| size > max as u64);
+        }
+        if self.oversize.contains_key(sha) {
             return true;
         }
         if self.prefetch_ok {
             return false;
         }
+        let size = self.blob_size(sha);
+        if size > max {
+            self.oversize.insert(sha.to_vec(), size);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn known_oversize(&self, sha: &[u8]) -> bool {
+        if self.is_kept(sha) {
+            return false;
+        }
+        match (&self.cache, self.max_blob_size) {
+            (Some(cache), Some(max)) => cache.get(sha).is_some_and(|size| size > max as u64),
+            _ => self.oversize.contains_key(sha),
+        }
+    }
+
+    /// Cache size and `(hits, misses)` for debug output, when the cache is in use.
+    pub(crate) fn cache_stats(&self) -> Option<(usize, u64, u64)> {
+        self.cache.as_ref().map(|cache| {
+            let (hits, misses) = cache.stats();
+            (cache.len(), hits, misses)
+        })
+    }
+
+    /// Size of a blob in the source repository (0 if it cannot be read).
+    pub(crate) fn blob_size(&self, sha: &[u8]) -> usize {
+        if let Some(size) = self.cache.as_ref().and_then(|cache| cache.get(sha)) {
+            return size as usize;
+        }
+        if let Some(&size) = self.oversize.get(sha) {
+            return size;
+        }
         let sha_str = String::from_utf8_lossy(sha).to_string();
         let output = Command::new("git")
             .arg("-C")
@@ -314,33 +656,215 @@ impl BlobSizeTracker {
             .arg("-s")
             .arg(&sha_str)
             .output();
-        let size = match output {
+        match output {
             Ok(out) if out.status.success() => std::str::from_utf8(&out.stdout)
                 .ok()
                 .and_then(|s| s.trim().parse::<usize>().ok())
                 .unwrap_or(0),
             _ => 0,
-        };
-        if size > max {
-            self.oversize.insert(sha.to_vec());
-            true
-        } else {
-            false
         }
     }
 
-    pub(crate) fn known_oversize(&self, sha: &[u8]) -> bool {
-        self.oversize.contains(sha)
-    }
-
     #[cfg(test)]
     pub(crate) fn prefetch_success(&self) -> bool {
         self.prefetch_ok
     }
 }
 
-pub fn run(opts: &Options) -> FilterRepoResult<()> {
-    let target_git_dir = git_dir(&opts.target).map_err(|e| {
+/// `fetched_refs` is how many refs the sensitive-mode fetch covered, for the report.
+pub fn run(opts: &Options, fetched_refs: Option<usize>) -> FilterRepoResult<Summary> {
+    let with_ignore_rules;
+    let opts = match &opts.ignore_file {
+        Some(path) if opts.ignore_rules.is_none() => {
+            let rules = IgnoreRules::from_file(path).map_err(|e| {
+                io::Error::other(format!(
+                    "failed to read --ignore-file {}: {e}",
+                    path.display()
+                ))
+            })?;
+            with_ignore_rules = Options {
+                ignore_rules: Some(rules),
+                ..opts.clone()
+            };
+            &with_ignore_rules
+        }
+        _ => opts,
+    };
+    let (forward_refs, summary) = run_pass(opts, fetched_refs)?;
+    if forward_refs.is_empty() || opts.dry_run {
+        return Ok(summary);
+    }
+    if opts.refs.iter().any(|r| r != "--all") {
+        events::emit(
+            opts,
+            Event::Notice(format!(
+                "NOTICE: {} commit id(s) in messages refer to commits exported later; \
+                 run again to rewrite them",
+                forward_refs.len()
+            )),
+        );
+        return Ok(summary);
+    }
+    rewrite_forward_hash_mentions(opts)?;
+    Ok(summary)
+}
+
+// Second pass for messages that mention commits fast-export emitted after
+// them: re-run over the target with only hash rewriting enabled, seeded by
+// the first pass's commit-map, then fold the two maps together.
+fn rewrite_forward_hash_mentions(opts: &Options) -> FilterRepoResult<()> {
+    let debug_dir = crate::statedir::for_target(opts)?;
+    let first_commit_map = std::fs::read(debug_dir.join("commit-map"))?;
+    let first_parent_map = if opts.write_parent_map {
+        Some(std::fs::read(debug_dir.join("parent-map"))?)
+    } else {
+        None
+    };
+    // --incremental seeds the next run from the first pass's mark numbers
+    let first_marks = if opts.incremental {
+        Some(std::fs::read(debug_dir.join("target-marks"))?)
+    } else {
+        None
+    };
+
+    let mut second = Options {
+        source: opts.target.clone(),
+        target: opts.target.clone(),
+        no_data: true,
+        force: true,
+        quiet: true,
+        partial: true,
+        reset: opts.reset,
+        debug_mode: opts.debug_mode,
+        git_caps: opts.git_caps.clone(),
+        rewrite_notes: opts.rewrite_notes,
+        write_parent_map: opts.write_parent_map,
+        protect_refs: opts.protect_refs.clone(),
+        state_dir: opts.state_dir.clone(),
+        // The first pass already applied the policies; only follow the rewrite
+        replace_refs_policy: ReplaceRefsPolicy::UpdateNoAdd,
+        prune_empty: PruneEmpty::Never,
+        signed_commits: opts.signed_commits,
+        signed_tags: opts.signed_tags,
+        pipeline_timeout: opts.pipeline_timeout,
+        subprocess_timeout: opts.subprocess_timeout,
+        ..Options::default()
+    };
+    if opts.preserve_original_refs {
+        second.protect_refs.push(b"refs/original/*".to_vec());
+    }
+    run_pass(&second, None)?;
+
+    if let Some(first) = first_marks {
+        remap_marks(&debug_dir, &first)?;
+    }
+    compose_map(&debug_dir.join("commit-map"), &first_commit_map)?;
+    crate::maplookup::write_reverse_commit_map(&debug_dir)?;
+    if let Some(first) = first_parent_map {
+        compose_map(&debug_dir.join("parent-map"), &first)?;
+    }
+    Ok(())
+}
+
+// Point the first pass's `:<mark> <mid>` lines at the second pass's commits,
+// using its `<mid> <new>` commit-map, and make them the target-marks again.
+fn remap_marks(debug_dir: &Path, first: &[u8]) -> io::Result<()> {
+    let second = std::fs::read(debug_dir.join("commit-map"))?;
+    let by_mid: HashMap<&[u8], &[u8]> = second
+        .split(|&b| b == b'\n')
+        .filter_map(|l| {
+            let sp = l.iter().position(|&b| b == b' ')?;
+            Some((&l[..sp], &l[sp + 1..]))
+        })
+        .collect();
+    let mut out = Vec::with_capacity(first.len());
+    for line in first.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        match line.iter().position(|&b| b == b' ') {
+            Some(sp) if by_mid.contains_key(&line[sp + 1..]) => {
+                out.extend_from_slice(&line[..=sp]);
+                out.extend_from_slice(by_mid[&line[sp + 1..]]);
+            }
+            _ => out.extend_from_slice(line),
+        }
+        out.push(b'\n');
+    }
+    std::fs::write(debug_dir.join("target-marks"), out)
+}
+
+// `path` holds `<mid> <rest...>` lines from the second pass; rewrite it as
+// `<old> <rest...>` by following each `<old> <mid> ...` line of `first`.
+fn compose_map(path: &Path, first: &[u8]) -> io::Result<()> {
+    let second = std::fs::read(path)?;
+    let mut by_mid: HashMap<&[u8], &[u8]> = HashMap::new();
+    for line in second.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        let mid_len = line.iter().position(|&b| b == b' ').unwrap_or(line.len());
+        by_mid.insert(&line[..mid_len], &line[(mid_len + 1).min(line.len())..]);
+    }
+    let mut out = Vec::with_capacity(first.len());
+    for line in first.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        let mut parts = line.splitn(3, |&b| b == b' ');
+        let (old, mid) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        match by_mid.get(mid) {
+            Some(rest) => {
+                out.extend_from_slice(old);
+                out.push(b' ');
+                out.extend_from_slice(rest);
+            }
+            None => out.extend_from_slice(line),
+        }
+        out.push(b'\n');
+    }
+    std::fs::write(path, out)
+}
+
+// Returns the hash mentions that only resolved after their message was written.
+fn run_pass(
+    opts: &Options,
+    fetched_refs: Option<usize>,
+) -> FilterRepoResult<(Vec<Vec<u8>>, Summary)> {
+    let watchdog = Watchdog::arm(opts.pipeline_timeout, opts.subprocess_timeout);
+    let result = run_pipeline(opts, fetched_refs, watchdog.as_ref());
+    // Killed processes surface as a broken stream or a failed exit status
+    match watchdog
+        .as_ref()
+        .filter(|_| result.is_err())
+        .and_then(Watchdog::disarm)
+    {
+        Some(fired) => {
+            remove_partial_import(opts);
+            Err(fired.into())
+        }
+        None => result,
+    }
+}
+
+// A killed fast-import leaves its half-written pack behind; refs are only
+// updated once the stream completes, so removing the pack is enough.
+fn remove_partial_import(opts: &Options) {
+    let Ok(dir) = git_dir(&opts.target) else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(dir.join("objects").join("pack")) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("tmp_pack_") || name.starts_with("tmp_idx_") {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn run_pipeline(
+    opts: &Options,
+    fetched_refs: Option<usize>,
+    watchdog: Option<&Watchdog>,
+) -> FilterRepoResult<(Vec<Vec<u8>>, Summary)> {
+    let _ = git_dir(&opts.target).map_err(|e| {
         io::Error::new(
             io::ErrorKind::Other,
             format!("Target {:?} is not a git repo: {e}", opts.target),
@@ -353,10 +877,27 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         )
     })?;
 
-    let debug_dir = target_git_dir.join("filter-repo");
-    if !debug_dir.exists() {
-        create_dir_all(&debug_dir)?;
-    }
+    // Commits inside --strip-empty-commits-in-range always use PruneEmpty::Always
+    let prune_range_oids: Option<HashSet<Vec<u8>>> = match &opts.prune_empty_in_range {
+        Some(range) => Some(
+            crate::gitutil::rev_list_oids(&opts.source, range).map_err(|e| {
+                io::Error::other(format!(
+                    "failed to resolve --strip-empty-commits-in-range: {e}"
+                ))
+            })?,
+        ),
+        None => None,
+    };
+
+    // Read --ref-rename-file up front so a bad line fails before anything is rewritten
+    let ref_table = match &opts.ref_rename_file {
+        Some(path) => crate::finalize::read_ref_rename_table(path)?,
+        None => Vec::new(),
+    };
+    // Snapshot refs before fast-import moves them (--protect-ref, --preserve-original-refs)
+    let ref_snapshot = crate::finalize::RefSnapshot::capture(opts)?;
+
+    let debug_dir = crate::statedir::for_target(opts)?;
     // Always produce filtered stream for downstream tooling/tests
     let mut filt_file = BufWriter::new(File::create(debug_dir.join("fast-export.filtered"))?);
     // Original stream is heavy I/O; only write when useful for debugging/reporting
@@ -369,19 +910,34 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         None
     };
 
+    // fast-export skips refs to trees and blobs; tags of tags need their chain kept
+    let unusual_refs = crate::sanity::find_unusual_refs(&opts.source)?;
+    crate::sanity::note_unusual_refs(opts, &unusual_refs);
+    let mut tag_chains = crate::tag::TagChains::new(&opts.source, &unusual_refs)?;
+
     let mut fe_cmd = crate::pipes::build_fast_export_cmd(opts)?;
-    let mut fe = fe_cmd.spawn().expect("failed to spawn git fast-export");
+    logging::debug!("spawning {:?}", fe_cmd.cmd);
+    let mut fe = fe_cmd.spawn().map_err(FilterRepoError::git_spawn)?;
     let mut fi = if opts.dry_run {
         None
     } else {
-        Some(
-            crate::pipes::build_fast_import_cmd(opts)
-                .spawn()
-                .expect("failed to spawn git fast-import"),
-        )
+        let mut fi_cmd = crate::pipes::build_fast_import_cmd(opts);
+        logging::debug!("spawning {:?}", fi_cmd);
+        Some(fi_cmd.spawn().map_err(FilterRepoError::git_spawn)?)
     };
+    if let Some(watchdog) = watchdog {
+        watchdog.watch(&fe);
+        if let Some(child) = fi.as_ref() {
+            watchdog.watch(child);
+        }
+    }
 
-    let mut fe_out = BufReader::new(fe.stdout.take().expect("no stdout from fast-export"));
+    let mut fe_out = StreamReader::new(BufReader::new(
+        fe.stdout.take().expect("no stdout from fast-export"),
+    ));
+    if let Some(watchdog) = watchdog {
+        fe_out.report_progress(watchdog.progress());
+    }
     let mut fi_in_opt: Option<std::process::ChildStdin> = if let Some(ref mut child) = fi {
         child.stdin.take()
     } else {
@@ -394,7 +950,7 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
             None
         };
 
-    let replacer = match &opts.replace_message_file {
+    let mut replacer = match &opts.replace_message_file {
         Some(p) => Some(MessageReplacer::from_file(p).map_err(|e| {
             io::Error::new(
                 io::ErrorKind::Other,
@@ -403,18 +959,36 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         })?),
         None => None,
     };
-    let mut short_hash_mapper = ShortHashMapper::from_debug_dir(&debug_dir)?;
-    let content_replacer = match &opts.replace_text_file {
-        Some(p) => Some(MessageReplacer::from_file(p).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("failed to read --replace-text: {e}"),
-            )
-        })?),
-        None => None,
+    let tag_replacer =
+        match &opts.replace_tag_message_file {
+            Some(p) => Some(MessageReplacer::from_file(p).map_err(|e| {
+                io::Error::other(format!("failed to read --replace-tag-message: {e}"))
+            })?),
+            None => None,
+        };
+    // Hash mentions map through the previous run's commit-map (if any) plus
+    // every commit of this run as fast-import assigns its new id
+    let mut short_hash_mapper = if opts.update_hash_mentions {
+        match ShortHashMapper::from_debug_dir(&debug_dir)? {
+            Some(mapper) => Some(mapper),
+            None => Some(ShortHashMapper::new()?),
+        }
+    } else {
+        None
     };
-    let content_regex_replacer: Option<BlobRegexReplacer> = match &opts.replace_text_file {
-        Some(p) => BlobRegexReplacer::from_file(p).map_err(|e| {
+    let replace_text_rules = opts.replace_text_source().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("failed to read --replace-text: {e}"),
+        )
+    })?;
+    let content_replacer = replace_text_rules
+        .as_deref()
+        .map(MessageReplacer::from_bytes)
+        .transpose()
+        .map_err(|e| io::Error::other(format!("failed to read --replace-text: {e}")))?;
+    let content_regex_replacer: Option<BlobRegexReplacer> = match &replace_text_rules {
+        Some(rules) => BlobRegexReplacer::from_bytes(rules).map_err(|e| {
             io::Error::new(
                 io::ErrorKind::Other,
                 format!("failed to read --replace-text: {e}"),
@@ -422,12 +996,36 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         })?,
         None => None,
     };
+    // Optionally reuse the --replace-text rules for commit and tag messages
+    let track_messages = opts.replace_text_applies_to_messages();
+    if track_messages {
+        replacer
+            .get_or_insert_with(MessageReplacer::default)
+            .extend_with_text_rules(content_replacer.as_ref(), content_regex_replacer.as_ref());
+    }
+    // Optionally follow --tag-rename/--branch-rename into message text
+    if opts.update_message_refs {
+        let refs = crate::gitutil::get_all_refs(&opts.source)?;
+        let pairs = MessageReplacer::ref_rename_pairs(
+            refs.keys().map(String::as_str),
+            &opts.tag_rename_map,
+            opts.tag_prefix_rename(),
+            opts.branch_rename.as_ref(),
+        );
+        if !pairs.is_empty() {
+            replacer
+                .get_or_insert_with(MessageReplacer::default)
+                .ref_renames = pairs;
+        }
+    }
 
     // minimal stream state is tracked via local booleans and buffers
     // Commit buffering state for pruning
     let mut in_commit = false;
     let mut commit_buf: Vec<u8> = Vec::with_capacity(8192);
     let mut commit_has_changes = false;
+    // Whether the original (unfiltered) commit carried any file changes
+    let mut commit_orig_has_changes = false;
     let mut commit_mark: Option<u32> = None;
     let mut first_parent_mark: Option<u32> = None;
     let mut commit_original_oid: Option<Vec<u8>> = None;
@@ -436,8 +1034,6 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
     let mut parent_lines: Vec<crate::commit::ParentLine> = Vec::new();
     let mut alias_map: HashMap<u32, u32> = HashMap::new();
     let mut import_broken = false;
-    // If we skip a duplicate annotated tag header, swallow the rest of its block
-    let mut skipping_tag_block: bool = false;
     let mut ref_renames: BTreeSet<(Vec<u8>, Vec<u8>)> = BTreeSet::new();
     // Track which refs we have updated (to avoid multiple updates of same ref via tag blocks)
     let mut updated_refs: BTreeSet<Vec<u8>> = BTreeSet::new();
@@ -459,32 +1055,86 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
     let mut last_blob_mark: Option<u32> = None;
     let mut oversize_marks: HashSet<u32> = HashSet::new();
     let mut oversize_shas: HashSet<Vec<u8>> = HashSet::new();
-    let strip_sha_lookup = match &opts.strip_blobs_with_ids {
-        Some(path) => StripShaLookup::from_path(path).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("failed to load --strip-blobs-with-ids: {e}"),
-            )
-        })?,
-        None => StripShaLookup::empty(),
-    };
+    let keep_sha_list = KeepShaList::load(opts)?;
+    let strip_sha_lookup = StripShaLookup::load(opts, &keep_sha_list)?;
     let mut last_blob_orig_sha: Option<Vec<u8>> = None;
-    let mut blob_size_tracker = BlobSizeTracker::new(opts);
+    let mut blob_size_tracker = BlobSizeTracker::new(opts).keeping(keep_sha_list);
     // Reporting accumulators
     let mut suppressed_marks_by_size: HashSet<u32> = HashSet::new();
     let mut suppressed_marks_by_sha: HashSet<u32> = HashSet::new();
     let mut suppressed_shas_by_size: HashSet<Vec<u8>> = HashSet::new();
     let mut suppressed_shas_by_sha: HashSet<Vec<u8>> = HashSet::new();
-    let mut modified_marks: HashSet<u32> = HashSet::new();
+    // Marks of blobs --replace-text changed, with the original content of
+    // those short enough to be a symlink target
+    let mut modified_marks: HashMap<u32, Option<Vec<u8>>> = HashMap::new();
+    // --rewrite-gitmodules: ids of the blobs history records at .gitmodules,
+    // so a file quoting a submodule section elsewhere is left alone
+    let gitmodules_ids = if crate::filechange::rewrites_gitmodules(opts) {
+        crate::gitutil::blob_ids_at_path(&opts.source, &opts.refs, ".gitmodules")?
+    } else {
+        HashSet::new()
+    };
     let mut samples_size: Vec<Vec<u8>> = Vec::new();
     let mut samples_sha: Vec<Vec<u8>> = Vec::new();
     let mut samples_modified: Vec<Vec<u8>> = Vec::new();
+    // Blobs over --max-in-memory-blob-size that skipped --replace-text/--normalize-eol
+    let mut too_large_for_replace: usize = 0;
+    let mut samples_too_large: Vec<Vec<u8>> = Vec::new();
     let mut inline_modified_paths: HashSet<Vec<u8>> = HashSet::new();
+    // Sensitive-data report: which blobs/commits contained removed data
+    let mut sensitive_tracker =
+        if opts.sensitive && (content_replacer.is_some() || content_regex_replacer.is_some()) {
+            Some(crate::sensitive_report::SensitiveDataTracker::new())
+        } else {
+            None
+        };
+    // --dump-unmatched-replace-rules: blobs matched per --replace-text rule
+    let rule_usage = match &replace_text_rules {
+        Some(rules) if opts.report_unused_replace_rules => Some(Arc::new(RuleUsage::new(rules))),
+        _ => None,
+    };
+    let blob_rewriter = BlobRewriter::new(
+        content_replacer,
+        content_regex_replacer,
+        RewriteSettings {
+            track_rules: sensitive_tracker.is_some(),
+            usage: rule_usage.clone(),
+            decode_utf16: opts.decode_utf16_for_replace,
+            keep_link_targets: !opts.rewrite_symlink_targets,
+        },
+    );
+    // --jobs: rewrite blob payloads on worker threads, written back in stream order
+    let mut blob_pool = match &blob_rewriter {
+        Some(rewriter) if opts.jobs > 1 => Some(BlobPool::new(opts.jobs, rewriter)),
+        _ => None,
+    };
     let mut line = Vec::with_capacity(8192);
-    // Track if the previous M-line used inline content; store commit_buf position and path bytes
-    let mut pending_inline: Option<(usize, Vec<u8>)> = None;
+    // Track if the previous M-line used inline content; store commit_buf position, path bytes
+    // and whether it adds a symlink
+    let mut pending_inline: Option<(usize, Vec<u8>, bool)> = None;
     // Track marks that have been emitted to avoid referencing undeclared marks in aliases
     let mut emitted_marks: HashSet<u32> = HashSet::new();
+    // --incremental: marks imported from the previous run are already defined
+    if opts.incremental && opts.fe_stream_override.is_none() {
+        if let (marks, true) = crate::pipes::incremental_marks(opts)? {
+            let marks = std::fs::read(&marks)?;
+            emitted_marks.extend(marks.split(|&b| b == b'\n').filter_map(|l| {
+                let num = l.strip_prefix(b":")?.split(|&b| b == b' ').next()?;
+                std::str::from_utf8(num).ok()?.parse::<u32>().ok()
+            }));
+        }
+    }
+    // --progress: number of commits parsed so far
+    let mut commits_seen: usize = 0;
+    // --signed-commits / --signed-tags
+    let mut filters = crate::commit::CommitFilters::new(opts)?;
+    // --commits-from/--commits-to: original blob id by mark
+    let mut blob_original_ids: HashMap<u32, Vec<u8>> = HashMap::new();
+    // --no-rewrite-if-unchanged: original id of untouched blobs by mark
+    let mut reused_blob_ids: HashMap<u32, Vec<u8>> = HashMap::new();
+    let mut blobs_stripped: usize = 0;
+    // `feature done` was seen and its `done` has not arrived yet
+    let mut expect_done = false;
 
     loop {
         line.clear();
@@ -498,33 +1148,43 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
             f.write_all(&line)?;
         }
 
-        // If swallowing a skipped annotated tag block, consume its lines and payload
-        if skipping_tag_block {
-            if line.starts_with(b"data ") {
-                let size_bytes = &line[b"data ".len()..];
-                let n = std::str::from_utf8(size_bytes)
-                    .ok()
-                    .map(|s| s.trim())
-                    .and_then(|s| s.parse::<usize>().ok())
-                    .ok_or_else(|| {
-                        io::Error::new(io::ErrorKind::InvalidData, "invalid data header")
-                    })?;
-                let mut payload = vec![0u8; n];
-                fe_out.read_exact(&mut payload)?;
-                // Mirror original payload to debug file (when enabled)
-                if let Some(ref mut f) = orig_file_opt {
-                    f.write_all(&payload)?;
+        // Anything but the next blob must follow every queued blob in the output
+        if !in_blob && line != b"blob\n" {
+            if let Some(pool) = blob_pool.as_mut().filter(|p| p.has_pending()) {
+                while let Some(ready) = pool.next_ready()? {
+                    if let Some((m, sha)) = write_ready_blob(
+                        ready,
+                        &mut filt_file,
+                        fi_in_opt.as_mut(),
+                        &mut import_broken,
+                        &mut modified_marks,
+                        sensitive_tracker.as_mut(),
+                        &mut emitted_marks,
+                    )? {
+                        reused_blob_ids.insert(m, sha);
+                    }
                 }
-                // Done skipping this tag block
-                skipping_tag_block = false;
             }
-            continue;
         }
 
-        // Pre-check for duplicate annotated tag: if target ref already updated, swallow this tag block
-        if crate::tag::precheck_duplicate_tag(&line, opts, &updated_refs) {
-            skipping_tag_block = true;
-            continue;
+        // Squashed commits follow their run's last commit before refs and
+        // tags can name them
+        if !in_commit
+            && (line.starts_with(b"tag ") || line.starts_with(b"reset ") || line == b"done\n")
+        {
+            let aliases = filters.squash.take_aliases();
+            if !aliases.is_empty() {
+                filt_file.write_all(&aliases)?;
+                if let Some(ref mut fi_in) = fi_in_opt {
+                    if let Err(e) = fi_in.write_all(&aliases) {
+                        if e.kind() == io::ErrorKind::BrokenPipe {
+                            import_broken = true;
+                        } else {
+                            return Err(e.into());
+                        }
+                    }
+                }
+            }
         }
 
         // In blob header: record and ignore original-oid lines (fast-import does not accept them outside commits/tags)
@@ -540,6 +1200,9 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     *b = *b + 32;
                 }
             }
+            if let Some(m) = last_blob_mark.filter(|_| filters.window.is_active()) {
+                blob_original_ids.insert(m, v.clone());
+            }
             last_blob_orig_sha = Some(v);
             continue;
         }
@@ -611,24 +1274,34 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     None
                 },
                 &replacer,
+                tag_replacer.as_ref(),
                 short_mapper,
                 opts,
                 &mut updated_refs,
                 &mut annotated_tag_refs,
                 &mut ref_renames,
                 &mut emitted_marks,
+                sensitive_tracker.as_mut().filter(|_| track_messages),
+                &filters.signatures,
+                &mut tag_chains,
             )?;
             continue;
         }
 
         if line.starts_with(b"commit ") {
+            commits_seen += 1;
+            if opts.progress && commits_seen.is_multiple_of(PROGRESS_INTERVAL) {
+                eprint!("\rParsed {} commits", commits_seen);
+            }
             // Start buffering a commit using possibly renamed header
             in_commit = true;
             commit_buf.clear();
             commit_has_changes = false;
+            commit_orig_has_changes = false;
             commit_mark = None;
             first_parent_mark = None;
             parent_lines.clear();
+            filters.signatures.start_commit();
             let hdr = crate::commit::rename_commit_header_ref(&line, opts, &mut ref_renames);
             commit_buf.extend_from_slice(&hdr);
             // Track final branch ref (post-rename) for HEAD updates
@@ -653,7 +1326,14 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                 || line.starts_with(b"blob")
                 || line == b"done\n"
             {
+                let keep_if_empty = keep_empty_commit_for(
+                    opts,
+                    prune_range_oids.as_ref(),
+                    commit_original_oid.as_deref(),
+                    commit_orig_has_changes,
+                );
                 let short_mapper = short_hash_mapper.as_ref();
+                let pairs_before = commit_pairs.len();
                 match crate::commit::process_commit_line(
                     b"\n",
                     opts,
@@ -665,6 +1345,7 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     } else {
                         None
                     },
+                    fi_out_opt.as_mut(),
                     &replacer,
                     short_mapper,
                     &mut commit_buf,
@@ -678,27 +1359,23 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     &mut parent_lines,
                     &mut alias_map,
                     &emitted_marks,
+                    keep_if_empty,
+                    sensitive_tracker.as_mut().filter(|_| track_messages),
+                    &mut filters,
                 )? {
                     crate::commit::CommitAction::Consumed => {} // Should not happen with synthetic newline
                     crate::commit::CommitAction::Ended => {
+                        emit_commit_processed(opts, commits_seen, &commit_pairs, pairs_before);
                         // Record emitted commit mark
                         if let Some(m) = commit_mark {
                             emitted_marks.insert(m);
-                            if let (Some(mapper), Some(ref mut fi_in), Some(ref mut fi_out)) = (
+                            record_commit_mapping(
                                 short_hash_mapper.as_mut(),
                                 fi_in_opt.as_mut(),
                                 fi_out_opt.as_mut(),
-                            ) {
-                                if let Some((old, Some(mark))) = commit_pairs.last() {
-                                    if *mark == m {
-                                        if let Some(new_id) =
-                                            resolve_mark_oid(fi_in, fi_out, *mark)?
-                                        {
-                                            mapper.update_mapping(old, &new_id);
-                                        }
-                                    }
-                                }
-                            }
+                                &commit_pairs,
+                                m,
+                            )?;
                         }
                         in_commit = false;
                     }
@@ -706,26 +1383,39 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
             }
         }
         if in_commit {
+            // --no-rewrite-if-unchanged: untouched blobs are named by id,
+            // which leaves the commit as it was
+            if let Some(by_id) = crate::filechange::modify_with_blob_id(&line, &reused_blob_ids) {
+                line = by_id;
+            }
+            filters.signatures.record_original(&line);
+            if line.starts_with(b"M ")
+                || line.starts_with(b"D ")
+                || line.starts_with(b"C ")
+                || line.starts_with(b"R ")
+                || line == b"deleteall\n"
+            {
+                commit_orig_has_changes = true;
+            }
             // If the previous M-line declared inline content, handle its following data block here
             if line.starts_with(b"data ") {
-                if let Some((pos, path_bytes)) = pending_inline.take() {
-                    // Parse size and read payload
-                    let size_bytes = &line[b"data ".len()..];
-                    let n = std::str::from_utf8(size_bytes)
-                        .ok()
-                        .map(|s| s.trim())
-                        .and_then(|s| s.parse::<usize>().ok())
-                        .ok_or_else(|| {
-                            io::Error::new(io::ErrorKind::InvalidData, "invalid data header")
-                        })?;
-                    let mut payload = vec![0u8; n];
-                    fe_out.read_exact(&mut payload)?;
+                if let Some((pos, path_bytes, is_link)) = pending_inline.take() {
+                    let payload = fe_out.read_data(&line, || {
+                        format!(
+                            "inline file {} of {}",
+                            String::from_utf8_lossy(&path_bytes),
+                            crate::commit::commit_context(commit_original_oid.as_deref())
+                        )
+                    })?;
+                    let n = payload.len();
                     // Mirror original payload to debug file (when enabled)
                     if let Some(ref mut f) = orig_file_opt {
                         f.write_all(&payload)?;
                     }
+                    filters.signatures.record_original(&payload);
+                    let in_window = filters.window.filters();
                     let mut drop_inline = false;
-                    if let Some(max) = opts.max_blob_size {
+                    if let Some(max) = opts.max_blob_size.filter(|_| in_window) {
                         if n > max {
                             drop_inline = true;
                         }
@@ -733,10 +1423,8 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     if drop_inline {
                         // Replace previously appended M inline line with a sanitized deletion
                         commit_buf.truncate(pos);
-                        let decoded =
-                            crate::pathutil::decode_fast_export_path_bytes(&path_bytes);
-                        let enc =
-                            crate::pathutil::sanitize_and_encode_path_for_import(&decoded);
+                        let decoded = crate::pathutil::decode_fast_export_path_bytes(&path_bytes);
+                        let enc = crate::pathutil::sanitize_and_encode_path_for_import(&decoded);
                         commit_buf.extend_from_slice(b"D ");
                         commit_buf.extend_from_slice(&enc);
                         commit_buf.push(b'\n');
@@ -749,38 +1437,54 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                         }
                         continue;
                     } else {
-                        // Keep inline content: apply --replace-text (literal then regex) and append
-                        if content_replacer.is_none() && content_regex_replacer.is_none() {
-                            let header = format!("data {}\n", payload.len());
-                            commit_buf.extend_from_slice(header.as_bytes());
-                            commit_buf.extend_from_slice(&payload);
+                        let (payload, rewriter) = if in_window {
+                            let payload = normalize_eol(payload, opts.normalize_eol);
+                            let payload = if path_bytes == b".gitmodules" {
+                                crate::filechange::rewrite_gitmodules(payload, opts)
+                            } else {
+                                payload
+                            };
+                            let rewriter = blob_rewriter
+                                .as_ref()
+                                .filter(|_| !is_link || opts.rewrite_symlink_targets);
+                            (payload, rewriter)
                         } else {
-                            let mut new_payload = payload;
-                            let mut changed = false;
-                            if let Some(r) = &content_replacer {
-                                let tmp = r.apply(new_payload.clone());
-                                if !changed {
-                                    changed = tmp != new_payload;
-                                }
-                                new_payload = tmp;
-                            }
-                            if let Some(rr) = &content_regex_replacer {
-                                let tmp = rr.apply_regex(new_payload.clone());
-                                if !changed {
-                                    changed = tmp != new_payload;
-                                }
-                                new_payload = tmp;
+                            (payload, None)
+                        };
+                        // Keep inline content: apply --replace-text (literal then regex) and append
+                        match rewriter {
+                            None => {
+                                let header = format!("data {}\n", payload.len());
+                                commit_buf.extend_from_slice(header.as_bytes());
+                                commit_buf.extend_from_slice(&payload);
                             }
-                            let header = format!("data {}\n", new_payload.len());
-                            commit_buf.extend_from_slice(header.as_bytes());
-                            commit_buf.extend_from_slice(&new_payload);
-                            if changed {
-                                if samples_modified.len() < REPORT_SAMPLE_LIMIT
-                                    && !samples_modified.iter().any(|p| p == &path_bytes)
-                                {
-                                    samples_modified.push(path_bytes.clone());
+                            Some(rewriter) => {
+                                let Rewritten {
+                                    data: new_payload,
+                                    changed,
+                                    matched_rules,
+                                    ..
+                                } = rewriter.rewrite(payload);
+                                let header = format!("data {}\n", new_payload.len());
+                                commit_buf.extend_from_slice(header.as_bytes());
+                                commit_buf.extend_from_slice(&new_payload);
+                                if changed {
+                                    if samples_modified.len() < REPORT_SAMPLE_LIMIT
+                                        && !samples_modified.iter().any(|p| p == &path_bytes)
+                                    {
+                                        samples_modified.push(path_bytes.clone());
+                                    }
+                                    inline_modified_paths.insert(path_bytes.clone());
+                                    if let (Some(t), Some(rules)) =
+                                        (sensitive_tracker.as_mut(), matched_rules)
+                                    {
+                                        t.record_inline(
+                                            commit_original_oid.as_deref(),
+                                            &path_bytes,
+                                            rules,
+                                        );
+                                    }
                                 }
-                                inline_modified_paths.insert(path_bytes.clone());
                             }
                         }
                         commit_has_changes = true;
@@ -788,6 +1492,8 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     }
                 }
             }
+            // Original target of a symlink whose blob --replace-text changed
+            let mut link_target: Option<Vec<u8>> = None;
             // Pre-check for oversized blobs referenced by this filechange
             if line.starts_with(b"M ") {
                 // Detect inline and record path for the immediately following data block
@@ -816,9 +1522,23 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                                 p.pop();
                             }
                         }
-                        pending_inline = Some((commit_buf.len(), p));
+                        pending_inline = Some((
+                            commit_buf.len(),
+                            p,
+                            crate::filechange::is_symlink_modify(bytes),
+                        ));
                     }
                 }
+            }
+            if !filters.window.filters() {
+                // Outside the window blobs keep their original content: refer
+                // to them by id, whatever this run did to their marks
+                if let Some(by_id) =
+                    crate::filechange::modify_with_blob_id(&line, &blob_original_ids)
+                {
+                    line = by_id;
+                }
+            } else if line.starts_with(b"M ") {
                 let bytes = &line;
                 // find end of mode and id
                 let mut i = 2; // after 'M '
@@ -865,8 +1585,24 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                         reason_size = suppressed_marks_by_size.contains(&num);
                         reason_sha = suppressed_marks_by_sha.contains(&num);
                     }
-                    if seen && modified_marks.contains(&num) {
+                    // Symlinks keep their original target unless
+                    // --rewrite-symlink-targets is given
+                    let restore = modified_marks
+                        .get(&num)
+                        .and_then(Option::as_ref)
+                        .filter(|_| seen && crate::filechange::is_symlink_modify(bytes));
+                    if let Some(target) = restore {
+                        link_target = Some(target.clone());
+                    } else if seen && modified_marks.contains_key(&num) {
+                        filters.signatures.content_changed();
                         let path_bytes = &bytes[path_start..].to_vec();
+                        if let Some(t) = sensitive_tracker.as_mut() {
+                            let mut p = path_bytes.clone();
+                            if p.last() == Some(&b'\n') {
+                                p.pop();
+                            }
+                            t.record_blob_reference(num, commit_original_oid.as_deref(), &p);
+                        }
                         if samples_modified.len() < REPORT_SAMPLE_LIMIT
                             && !samples_modified.iter().any(|p| p == path_bytes)
                         {
@@ -880,10 +1616,29 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     if strip_sha_lookup.contains_hex(&sha)? {
                         drop_path = true;
                         reason_sha = true;
-                        suppressed_shas_by_sha.insert(sha.clone());
+                        if suppressed_shas_by_sha.insert(sha.clone()) {
+                            blobs_stripped += 1;
+                            events::emit(
+                                opts,
+                                Event::BlobStripped {
+                                    reason: StripReason::BlobId,
+                                    size: blob_size_tracker.blob_size(&sha),
+                                },
+                            );
+                        }
                     }
                     if blob_size_tracker.is_oversize(&sha) {
                         oversize_shas.insert(sha.clone());
+                        if !reason_sha && !suppressed_shas_by_size.contains(&sha) {
+                            blobs_stripped += 1;
+                            events::emit(
+                                opts,
+                                Event::BlobStripped {
+                                    reason: StripReason::MaxSize,
+                                    size: blob_size_tracker.blob_size(&sha),
+                                },
+                            );
+                        }
                         suppressed_shas_by_size.insert(sha);
                         drop_path = true;
                         reason_size = true;
@@ -930,7 +1685,21 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     continue;
                 }
             }
+            let keep_if_empty = line == b"\n"
+                && keep_empty_commit_for(
+                    opts,
+                    prune_range_oids.as_ref(),
+                    commit_original_oid.as_deref(),
+                    commit_orig_has_changes,
+                );
+            if link_target.is_some() {
+                if let Some(inline) = crate::filechange::inline_modify_line(&line) {
+                    line = inline;
+                }
+            }
             let short_mapper = short_hash_mapper.as_ref();
+            let pairs_before = commit_pairs.len();
+            let buf_before = commit_buf.len();
             match crate::commit::process_commit_line(
                 &line,
                 opts,
@@ -942,6 +1711,7 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                 } else {
                     None
                 },
+                fi_out_opt.as_mut(),
                 &replacer,
                 short_mapper,
                 &mut commit_buf,
@@ -955,26 +1725,29 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                 &mut parent_lines,
                 &mut alias_map,
                 &emitted_marks,
+                keep_if_empty,
+                sensitive_tracker.as_mut().filter(|_| track_messages),
+                &mut filters,
             )? {
                 crate::commit::CommitAction::Consumed => {
+                    // A kept link takes its original target inline
+                    if let Some(target) = link_target.filter(|_| commit_buf.len() > buf_before) {
+                        commit_buf.extend_from_slice(format!("data {}\n", target.len()).as_bytes());
+                        commit_buf.extend_from_slice(&target);
+                    }
                     continue;
                 }
                 crate::commit::CommitAction::Ended => {
+                    emit_commit_processed(opts, commits_seen, &commit_pairs, pairs_before);
                     if let Some(m) = commit_mark {
                         emitted_marks.insert(m);
-                        if let (Some(mapper), Some(ref mut fi_in), Some(ref mut fi_out)) = (
+                        record_commit_mapping(
                             short_hash_mapper.as_mut(),
                             fi_in_opt.as_mut(),
                             fi_out_opt.as_mut(),
-                        ) {
-                            if let Some((old, Some(mark))) = commit_pairs.last() {
-                                if *mark == m {
-                                    if let Some(new_id) = resolve_mark_oid(fi_in, fi_out, *mark)? {
-                                        mapper.update_mapping(old, &new_id);
-                                    }
-                                }
-                            }
-                        }
+                            &commit_pairs,
+                            m,
+                        )?;
                     }
                     in_commit = false;
                 }
@@ -983,23 +1756,49 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
 
         // Generic data blocks (e.g., blob): forward exact payload bytes
         if line.starts_with(b"data ") {
-            let size_bytes = &line[b"data ".len()..];
-            let n = std::str::from_utf8(size_bytes)
-                .ok()
-                .map(|s| s.trim())
-                .and_then(|s| s.parse::<usize>().ok())
-                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid data header"))?;
-            let mut payload = vec![0u8; n];
-            fe_out.read_exact(&mut payload)?;
+            let blob_mark = last_blob_mark;
+            let context = move || match (in_blob, blob_mark) {
+                (true, Some(m)) => format!("blob :{m}"),
+                (true, None) => "blob".to_string(),
+                (false, _) => "data block".to_string(),
+            };
+            let n = fe_out.data_len(&line, context)?;
+            // Blobs over the in-memory cap are never loaded: they are
+            // stripped or copied through while being read
+            let streamed = in_blob && n > opts.max_in_memory_blob_bytes;
+            let payload = if streamed {
+                Vec::new()
+            } else {
+                fe_out.read_data(&line, context)?
+            };
             // Always mirror to original (when enabled)
             if let Some(ref mut f) = orig_file_opt {
                 f.write_all(&payload)?;
             }
             if in_blob {
+                let original = (opts.no_rewrite_if_unchanged
+                    && (opts.normalize_eol.is_some() || opts.rewrite_gitmodules))
+                    .then(|| payload.clone());
+                let payload = normalize_eol(payload, opts.normalize_eol);
+                let payload = match &last_blob_orig_sha {
+                    Some(sha) if gitmodules_ids.contains(sha) => {
+                        crate::filechange::rewrite_gitmodules(payload, opts)
+                    }
+                    _ => payload,
+                };
+                // --no-rewrite-if-unchanged: the original id still names
+                // content no filter has touched so far
+                let reusable = opts.no_rewrite_if_unchanged
+                    && original.is_none_or(|o| o == payload)
+                    && last_blob_mark.is_some()
+                    && last_blob_orig_sha.is_some();
                 let mut skip_blob = false;
                 let mut reason_size = false;
                 let mut reason_sha = false;
-                if let Some(max) = opts.max_blob_size {
+                let kept = last_blob_orig_sha
+                    .as_ref()
+                    .is_some_and(|sha| blob_size_tracker.is_kept(sha));
+                if let Some(max) = opts.max_blob_size.filter(|_| !kept) {
                     if n > max {
                         // Pre-record oversize by mark/sha so commit M-lines using marks can be dropped later.
                         if let Some(m) = last_blob_mark {
@@ -1023,6 +1822,24 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     }
                 }
                 if skip_blob {
+                    if streamed {
+                        fe_out.copy_data(&line, n, context, |chunk| match orig_file_opt {
+                            Some(ref mut f) => f.write_all(chunk),
+                            None => Ok(()),
+                        })?;
+                    }
+                    blobs_stripped += 1;
+                    events::emit(
+                        opts,
+                        Event::BlobStripped {
+                            reason: if reason_size {
+                                StripReason::MaxSize
+                            } else {
+                                StripReason::BlobId
+                            },
+                            size: n,
+                        },
+                    );
                     if let Some(m) = last_blob_mark.take() {
                         oversize_marks.insert(m);
                         if reason_size {
@@ -1044,64 +1861,118 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                     last_blob_mark = None;
                     // Do not forward to filtered/import
                     continue;
-                } else {
-                    // Emit buffered blob header lines, then header and payload
-                    for h in blob_buf.drain(..) {
-                        filt_file.write_all(&h)?;
-                        if let Some(ref mut fi_in) = fi_in_opt {
-                            if let Err(e) = fi_in.write_all(&h) {
-                                if e.kind() == io::ErrorKind::BrokenPipe {
-                                    import_broken = true;
-                                } else {
-                                    return Err(e.into());
-                                }
-                            }
+                } else if streamed {
+                    if blob_rewriter.is_some() || opts.normalize_eol.is_some() {
+                        too_large_for_replace += 1;
+                        if samples_too_large.len() < REPORT_SAMPLE_LIMIT {
+                            samples_too_large.push(match &last_blob_orig_sha {
+                                Some(sha) => sha.clone(),
+                                None => format!("blob :{}", blob_mark.unwrap_or(0)).into_bytes(),
+                            });
                         }
                     }
-                    if content_replacer.is_none() && content_regex_replacer.is_none() {
-                        let header = format!("data {}\n", payload.len());
-                        filt_file.write_all(header.as_bytes())?;
-                        if let Some(ref mut fi_in) = fi_in_opt {
-                            if let Err(e) = fi_in.write_all(header.as_bytes()) {
-                                if e.kind() == io::ErrorKind::BrokenPipe {
-                                    import_broken = true;
-                                } else {
-                                    return Err(e.into());
-                                }
+                    if reusable {
+                        fe_out.copy_data(&line, n, context, |chunk| match orig_file_opt {
+                            Some(ref mut f) => f.write_all(chunk),
+                            None => Ok(()),
+                        })?;
+                        if let (Some(m), Some(sha)) =
+                            (last_blob_mark.take(), last_blob_orig_sha.clone())
+                        {
+                            reused_blob_ids.insert(m, sha);
+                        }
+                        in_blob = false;
+                        blob_buf.clear();
+                        continue;
+                    }
+                    // Queued blobs go out first to keep stream order
+                    if let Some(pool) = blob_pool.as_mut() {
+                        while let Some(ready) = pool.next_ready()? {
+                            if let Some((m, sha)) = write_ready_blob(
+                                ready,
+                                &mut filt_file,
+                                fi_in_opt.as_mut(),
+                                &mut import_broken,
+                                &mut modified_marks,
+                                sensitive_tracker.as_mut(),
+                                &mut emitted_marks,
+                            )? {
+                                reused_blob_ids.insert(m, sha);
                             }
                         }
-                        filt_file.write_all(&payload)?;
-                        if let Some(ref mut fi_in) = fi_in_opt {
-                            if let Err(e) = fi_in.write_all(&payload) {
-                                if e.kind() == io::ErrorKind::BrokenPipe {
-                                    import_broken = true;
-                                } else {
-                                    return Err(e.into());
+                    }
+                    let mut emit = |chunk: &[u8]| -> io::Result<()> {
+                        filt_file.write_all(chunk)?;
+                        if let Some(fi_in) = fi_in_opt.as_mut().filter(|_| !import_broken) {
+                            if let Err(e) = fi_in.write_all(chunk) {
+                                if e.kind() != io::ErrorKind::BrokenPipe {
+                                    return Err(e);
                                 }
+                                import_broken = true;
                             }
                         }
-                    } else {
-                        // Forward header/payload with replacements; track whether modified
-                        let mut new_payload = payload;
-                        let mut changed = false;
-                        if let Some(r) = &content_replacer {
-                            let tmp = r.apply(new_payload.clone());
-                            if !changed {
-                                changed = tmp != new_payload;
-                            }
-                            new_payload = tmp;
+                        Ok(())
+                    };
+                    for h in blob_buf.drain(..) {
+                        emit(&h)?;
+                    }
+                    emit(format!("data {n}\n").as_bytes())?;
+                    fe_out.copy_data(&line, n, context, |chunk| {
+                        if let Some(ref mut f) = orig_file_opt {
+                            f.write_all(chunk)?;
                         }
-                        if let Some(rr) = &content_regex_replacer {
-                            let tmp = rr.apply_regex(new_payload.clone());
-                            if !changed {
-                                changed = tmp != new_payload;
-                            }
-                            new_payload = tmp;
+                        emit(chunk)
+                    })?;
+                    if let Some(m) = last_blob_mark.take() {
+                        emitted_marks.insert(m);
+                    }
+                    in_blob = false;
+                    continue;
+                } else if let Some(rewriter) = &blob_rewriter {
+                    // Apply --replace-text, on the worker pool when --jobs allows
+                    let headers = std::mem::take(&mut blob_buf);
+                    let mark = last_blob_mark.take();
+                    let orig_sha = last_blob_orig_sha.clone();
+                    let ready = match blob_pool.as_mut() {
+                        Some(pool) => pool.submit(headers, mark, orig_sha, reusable, payload)?,
+                        None => Some(ReadyBlob {
+                            headers,
+                            mark,
+                            orig_sha,
+                            reusable,
+                            rewritten: rewriter.rewrite(payload),
+                        }),
+                    };
+                    if let Some(ready) = ready {
+                        if let Some((m, sha)) = write_ready_blob(
+                            ready,
+                            &mut filt_file,
+                            fi_in_opt.as_mut(),
+                            &mut import_broken,
+                            &mut modified_marks,
+                            sensitive_tracker.as_mut(),
+                            &mut emitted_marks,
+                        )? {
+                            reused_blob_ids.insert(m, sha);
                         }
-                        let header = format!("data {}\n", new_payload.len());
-                        filt_file.write_all(header.as_bytes())?;
+                    }
+                    in_blob = false;
+                    continue;
+                } else if reusable {
+                    if let (Some(m), Some(sha)) =
+                        (last_blob_mark.take(), last_blob_orig_sha.clone())
+                    {
+                        reused_blob_ids.insert(m, sha);
+                    }
+                    in_blob = false;
+                    blob_buf.clear();
+                    continue;
+                } else {
+                    // Emit buffered blob header lines, then header and payload
+                    for h in blob_buf.drain(..) {
+                        filt_file.write_all(&h)?;
                         if let Some(ref mut fi_in) = fi_in_opt {
-                            if let Err(e) = fi_in.write_all(header.as_bytes()) {
+                            if let Err(e) = fi_in.write_all(&h) {
                                 if e.kind() == io::ErrorKind::BrokenPipe {
                                     import_broken = true;
                                 } else {
@@ -1109,19 +1980,25 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                                 }
                             }
                         }
-                        filt_file.write_all(&new_payload)?;
-                        if let Some(ref mut fi_in) = fi_in_opt {
-                            if let Err(e) = fi_in.write_all(&new_payload) {
-                                if e.kind() == io::ErrorKind::BrokenPipe {
-                                    import_broken = true;
-                                } else {
-                                    return Err(e.into());
-                                }
+                    }
+                    let header = format!("data {}\n", payload.len());
+                    filt_file.write_all(header.as_bytes())?;
+                    if let Some(ref mut fi_in) = fi_in_opt {
+                        if let Err(e) = fi_in.write_all(header.as_bytes()) {
+                            if e.kind() == io::ErrorKind::BrokenPipe {
+                                import_broken = true;
+                            } else {
+                                return Err(e.into());
                             }
                         }
-                        if changed {
-                            if let Some(m) = last_blob_mark {
-                                modified_marks.insert(m);
+                    }
+                    filt_file.write_all(&payload)?;
+                    if let Some(ref mut fi_in) = fi_in_opt {
+                        if let Err(e) = fi_in.write_all(&payload) {
+                            if e.kind() == io::ErrorKind::BrokenPipe {
+                                import_broken = true;
+                            } else {
+                                return Err(e.into());
                             }
                         }
                     }
@@ -1164,7 +2041,9 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
 
         // Handle end-of-stream marker; flush buffered lightweight tag resets before 'done'
         if line == b"done\n" {
+            expect_done = false;
             crate::finalize::flush_lightweight_tag_resets(
+                opts,
                 &mut buffered_tag_resets,
                 &annotated_tag_refs,
                 &mut filt_file as &mut dyn Write,
@@ -1249,6 +2128,19 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         if line == b"\n" {
             continue;
         }
+        // Commands needing a reply from fast-import (ls, cat-blob, get-mark)
+        // would stall the import; anything unknown is a broken stream
+        if !PASSTHROUGH_COMMANDS.iter().any(|c| line.starts_with(c)) {
+            if let Some(child) = fi.as_mut() {
+                let _ = child.kill();
+            }
+            return Err(fe_out
+                .error(&line, "unsupported fast-export command")
+                .into());
+        }
+        if line == b"feature done\n" {
+            expect_done = true;
+        }
         filt_file.write_all(&line)?;
         if let Some(ref mut fi_in) = fi_in_opt {
             if let Err(e) = fi_in.write_all(&line) {
@@ -1262,7 +2154,43 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         }
     }
 
+    // With `feature done`, a stream without `done` means fast-export died
+    // part way; fast-import must not finish the partial history either
+    if expect_done && !import_broken {
+        if let Some(child) = fi.as_mut() {
+            let _ = child.kill();
+        }
+        return Err(fe_out
+            .error(
+                b"",
+                "stream ended without `done` (fast-export stopped early?)",
+            )
+            .into());
+    }
+
+    // Stream ended without 'done': write any blobs still queued
+    if let Some(pool) = blob_pool.as_mut() {
+        while let Some(ready) = pool.next_ready()? {
+            if let Some((m, sha)) = write_ready_blob(
+                ready,
+                &mut filt_file,
+                fi_in_opt.as_mut(),
+                &mut import_broken,
+                &mut modified_marks,
+                sensitive_tracker.as_mut(),
+                &mut emitted_marks,
+            )? {
+                reused_blob_ids.insert(m, sha);
+            }
+        }
+    }
+    drop(blob_pool);
+
     drop(fi_out_opt);
+    if opts.progress {
+        eprintln!("\rParsed {} commits", commits_seen);
+    }
+    logging::info!("parsed {} commits from fast-export", commits_seen);
 
     // Finalize run: flush buffered tags (if any remain), wait, write maps, optional reset
     // Flush original stream (if present) so finalize can read it for reporting/sampling
@@ -1270,10 +2198,12 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         of.flush()?;
     }
     let allow_flush_tag_resets = !buffered_tag_resets.is_empty();
+    events::emit(opts, Event::PhaseStarted(Phase::Finalize));
     crate::finalize::finalize(
         opts,
         &debug_dir,
         ref_renames,
+        ref_table,
         commit_pairs,
         buffered_tag_resets,
         annotated_tag_refs,
@@ -1283,6 +2213,7 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
         fi_in_opt,
         &mut fe,
         fi.as_mut().map(|c| c),
+        watchdog,
         import_broken,
         allow_flush_tag_resets,
         {
@@ -1304,11 +2235,195 @@ pub fn run(opts: &Options) -> FilterRepoResult<()> {
                 samples_size,
                 samples_sha,
                 samples_modified,
+                messages_ref_renamed: replacer.as_ref().map_or(0, |r| r.ref_rename_hits.get()),
+                unusual_refs,
+                windows_sanitized: filters.windows.sanitized.into_iter().collect(),
+                windows_skipped: filters.windows.skipped.into_iter().collect(),
+                rename_collisions: filters.collisions.resolved.into_iter().collect(),
+                too_large_for_replace,
+                samples_too_large,
+                duplicate_paths_deleted: filters.duplicates.deleted,
+                duplicate_paths: filters.duplicates.paths.into_iter().collect(),
+                attribute_files_pruned: filters.stale_attributes.pruned,
+                paths_stripped: filters.stripped_paths.removed,
+                top_stripped_paths: filters.stripped_paths.top(REPORT_SAMPLE_LIMIT),
+                modes_stripped: filters.stripped_modes.removed,
+                stripped_mode_paths: filters
+                    .stripped_modes
+                    .paths
+                    .into_iter()
+                    .take(REPORT_SAMPLE_LIMIT)
+                    .collect(),
+                commits_stripped_by_author: filters.stripped.stripped,
+                merge_parents_reordered: filters.merges.reordered,
+                commits_squashed: filters.squash.squashed,
+                messages_annotated: filters.annotator.annotated,
+                trailers_stripped: filters.trailers.stripped,
+                unused_replace_rules: rule_usage.map(|u| u.unused()),
+                fetched_refs,
             })
         },
         &blob_size_tracker,
+        sensitive_tracker,
+        ref_snapshot,
     )?;
+    if let Some((blobs, hits, misses)) = blob_size_tracker.cache_stats() {
+        logging::debug!("blob size cache: {blobs} blobs, {hits} hits, {misses} misses");
+    }
+
+    Ok((
+        short_hash_mapper
+            .map(|mapper| mapper.forward_references())
+            .unwrap_or_default(),
+        Summary {
+            commits: commits_seen,
+            blobs_stripped,
+            refs_updated: 0,
+        },
+    ))
+}
+
+// A finished commit records its original id in `commit_pairs`; commits
+// without one (no mark and not pruned) are not reported.
+fn emit_commit_processed(
+    opts: &Options,
+    index: usize,
+    commit_pairs: &[(Vec<u8>, Option<u32>)],
+    pairs_before: usize,
+) {
+    if commit_pairs.len() > pairs_before {
+        if let Some((old, _)) = commit_pairs.last() {
+            events::emit(
+                opts,
+                Event::CommitProcessed {
+                    index,
+                    old_oid: String::from_utf8_lossy(old).into_owned(),
+                },
+            );
+        }
+    }
+}
+
+// Bytes git inspects when deciding whether a blob is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+// Convert line endings of a text blob; like git, a NUL byte in the first
+// few KB marks the blob as binary and leaves it untouched.
+fn normalize_eol(payload: Vec<u8>, mode: Option<EolMode>) -> Vec<u8> {
+    let Some(mode) = mode else {
+        return payload;
+    };
+    if payload[..payload.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+        return payload;
+    }
+    let mut out = Vec::with_capacity(payload.len());
+    let mut i = 0;
+    while i < payload.len() {
+        let b = payload[i];
+        if b == b'\r' && payload.get(i + 1) == Some(&b'\n') {
+            i += 1;
+            continue;
+        }
+        if b == b'\n' && mode == EolMode::CrLf {
+            out.push(b'\r');
+        }
+        out.push(b);
+        i += 1;
+    }
+    out
+}
+
+// Write a (possibly rewritten) blob to the filtered and import streams and
+// record what --replace-text changed. A reusable blob the rewrite left alone
+// is not written; its mark and original id are returned instead.
+fn write_ready_blob(
+    ready: ReadyBlob,
+    filt_file: &mut dyn Write,
+    mut fi_in: Option<&mut std::process::ChildStdin>,
+    import_broken: &mut bool,
+    modified_marks: &mut HashMap<u32, Option<Vec<u8>>>,
+    sensitive_tracker: Option<&mut crate::sensitive_report::SensitiveDataTracker>,
+    emitted_marks: &mut HashSet<u32>,
+) -> io::Result<Option<(u32, Vec<u8>)>> {
+    if ready.reusable && !ready.rewritten.changed {
+        if let (Some(m), Some(sha)) = (ready.mark, &ready.orig_sha) {
+            return Ok(Some((m, sha.clone())));
+        }
+    }
+    let header = format!("data {}\n", ready.rewritten.data.len());
+    let chunks = ready
+        .headers
+        .iter()
+        .map(Vec::as_slice)
+        .chain([header.as_bytes(), ready.rewritten.data.as_slice()]);
+    for chunk in chunks {
+        filt_file.write_all(chunk)?;
+        if let Some(fi) = fi_in.as_mut() {
+            if let Err(e) = fi.write_all(chunk) {
+                if e.kind() == io::ErrorKind::BrokenPipe {
+                    *import_broken = true;
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+    }
+    if ready.rewritten.changed {
+        if let Some(m) = ready.mark {
+            modified_marks.insert(m, ready.rewritten.original);
+        }
+        if let (Some(t), Some(rules)) = (sensitive_tracker, ready.rewritten.matched_rules) {
+            t.record_blob(ready.mark, ready.orig_sha.as_deref(), rules);
+        }
+    }
+    if let Some(m) = ready.mark {
+        emitted_marks.insert(m);
+    }
+    Ok(None)
+}
 
+// Decide whether the current commit may stay even if it ends up without file changes
+fn keep_empty_commit_for(
+    opts: &Options,
+    prune_range_oids: Option<&HashSet<Vec<u8>>>,
+    original_oid: Option<&[u8]>,
+    orig_has_changes: bool,
+) -> bool {
+    let in_range = match (prune_range_oids, original_oid) {
+        (Some(set), Some(oid)) => set.contains(oid),
+        _ => false,
+    };
+    let policy = if in_range {
+        PruneEmpty::Always
+    } else {
+        opts.prune_empty
+    };
+    crate::commit::keep_empty_commit(policy, !orig_has_changes)
+}
+
+// Feed the hash-mention mapper with the commit that just ended: resolve the
+// new id of a kept commit through fast-import, or remember a pruned one.
+fn record_commit_mapping(
+    mapper: Option<&mut ShortHashMapper>,
+    fi_in: Option<&mut std::process::ChildStdin>,
+    fi_out: Option<&mut BufReader<std::process::ChildStdout>>,
+    commit_pairs: &[(Vec<u8>, Option<u32>)],
+    commit_mark: u32,
+) -> io::Result<()> {
+    let Some(mapper) = mapper else {
+        return Ok(());
+    };
+    match commit_pairs.last() {
+        Some((old, Some(mark))) if *mark == commit_mark => {
+            if let (Some(fi_in), Some(fi_out)) = (fi_in, fi_out) {
+                if let Some(new_id) = resolve_mark_oid(fi_in, fi_out, *mark)? {
+                    mapper.update_mapping(old, &new_id);
+                }
+            }
+        }
+        Some((old, None)) => mapper.mark_pruned(old),
+        _ => {}
+    }
     Ok(())
 }
 
@@ -1388,6 +2503,62 @@ mod tests {
         opts
     }
 
+    #[test]
+    fn stream_reader_counts_lines_across_data_blocks() {
+        let mut reader = StreamReader::new(&b"blob\ndata 6\na\nb\nc\ndone\n"[..]);
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line).unwrap();
+        line.clear();
+        reader.read_until(b'\n', &mut line).unwrap();
+        let payload = reader.read_data(&line, || "blob".to_string()).unwrap();
+        assert_eq!(payload, b"a\nb\nc\n");
+        line.clear();
+        reader.read_until(b'\n', &mut line).unwrap();
+        let err = FilterRepoError::from(reader.error(&line, "unexpected"));
+        assert!(
+            matches!(err, FilterRepoError::StreamParse { line_number: 6, ref snippet, .. } if snippet == "done"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn stream_reader_rejects_short_and_malformed_data() {
+        let mut reader = StreamReader::new(&b"data 10\nabc"[..]);
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line).unwrap();
+        match FilterRepoError::from(
+            reader
+                .read_data(&line, || "blob :7".to_string())
+                .unwrap_err(),
+        ) {
+            FilterRepoError::StreamParse {
+                line_number,
+                snippet,
+                reason,
+            } => {
+                assert_eq!((line_number, snippet.as_str()), (1, "data 10"));
+                assert_eq!(
+                    reason,
+                    "blob :7: data block declares 10 bytes but the stream ended after 3"
+                );
+            }
+            other => panic!("expected StreamParse, got {other:?}"),
+        }
+
+        let mut reader = StreamReader::new(&b"data <<EOF\n"[..]);
+        line.clear();
+        reader.read_until(b'\n', &mut line).unwrap();
+        let err = FilterRepoError::from(
+            reader
+                .read_data(&line, || "tag v1".to_string())
+                .unwrap_err(),
+        );
+        assert_eq!(
+            err.to_string(),
+            "fast-export stream line 1: invalid data header in tag v1: \"data <<EOF\""
+        );
+    }
+
     #[test]
     fn test_blob_size_tracker_empty_repo() {
         let temp_dir = TempDir::new().unwrap();
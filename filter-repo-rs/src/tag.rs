@@ -1,50 +1,246 @@
-use std::collections::BTreeSet;
-use std::io::{self, BufRead, BufReader, Read, Write};
-use std::process::{ChildStdin, ChildStdout};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::{ChildStdin, Command, Stdio};
 
+use crate::error::{FilterRepoError, Result as FilterRepoResult};
+use crate::events::{self, Event};
+use crate::gitutil;
+use crate::identity;
 use crate::message::{MessageReplacer, ShortHashMapper};
 use crate::opts::Options;
+use crate::sanity::UnusualRef;
+use crate::sensitive_report::SensitiveDataTracker;
+use crate::signature::SignatureTracker;
+use crate::stream::StreamReader;
 
-pub fn precheck_duplicate_tag(
-    line: &[u8],
+/// Read `--tag-rename-file`: `old<TAB>new` lines of short tag names, with
+/// blank lines and `#` comments ignored.
+pub(crate) fn read_tag_rename_file(path: &Path) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let data = std::fs::read(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to read --tag-rename-file {}: {e}", path.display()),
+        )
+    })?;
+    let mut table: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    for (idx, line) in data.split(|&b| b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(u8::is_ascii_whitespace) || line.starts_with(b"#") {
+            continue;
+        }
+        let invalid = |why: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}:{}: {why}: {}",
+                    path.display(),
+                    idx + 1,
+                    String::from_utf8_lossy(line)
+                ),
+            )
+        };
+        let Some(tab) = line.iter().position(|&b| b == b'\t') else {
+            return Err(invalid("expected 'old<TAB>new'"));
+        };
+        let (old, new_) = (&line[..tab], &line[tab + 1..]);
+        if old.is_empty() || new_.is_empty() || new_.contains(&b'\t') {
+            return Err(invalid("expected 'old<TAB>new'"));
+        }
+        if table.iter().any(|(o, _)| o == old) {
+            return Err(invalid("tag is renamed more than once"));
+        }
+        table.push((old.to_vec(), new_.to_vec()));
+    }
+    Ok(table)
+}
+
+/// `--tag-sign-cmd`: run the command for every annotated tag fast-import
+/// wrote a new object for, passing the short tag name and the id it points
+/// at. Every tag is tried before failing with the ones whose command failed.
+pub(crate) fn run_tag_sign_cmd(
     opts: &Options,
-    updated_refs: &BTreeSet<Vec<u8>>,
-) -> bool {
-    if !line.starts_with(b"tag ") {
-        return false;
+    annotated_tag_refs: &BTreeSet<Vec<u8>>,
+    refs_before: &HashMap<String, String>,
+) -> FilterRepoResult<()> {
+    let Some(cmd) = &opts.tag_sign_cmd else {
+        return Ok(());
+    };
+    if opts.dry_run {
+        for refname in annotated_tag_refs {
+            let refname = String::from_utf8_lossy(refname);
+            let tag = refname.strip_prefix("refs/tags/").unwrap_or(&refname);
+            events::emit(
+                opts,
+                Event::Notice(format!("Would re-sign tag {} with --tag-sign-cmd", tag)),
+            );
+        }
+        return Ok(());
+    }
+
+    // refname -> (tag object, tagged object) for annotated tags only
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(&opts.target)
+        .arg("for-each-ref")
+        .arg("--format=%(objecttype) %(refname) %(objectname) %(*objectname)")
+        .arg("refs/tags/")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "'git for-each-ref refs/tags/' failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ))
+        .into());
     }
-    if let Some((ref old, ref new_)) = opts.tag_rename {
-        let mut name = &line[b"tag ".len()..];
-        if let Some(&last) = name.last() {
-            if last == b'\n' {
-                name = &name[..name.len() - 1];
+    let listing = String::from_utf8_lossy(&out.stdout);
+    let annotated: HashMap<&str, (&str, &str)> = listing
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split(' ');
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some("tag"), Some(name), Some(oid), Some(target)) => Some((name, (oid, target))),
+                _ => None,
             }
+        })
+        .collect();
+
+    let mut signed = 0usize;
+    let mut failed: Vec<(String, String)> = Vec::new();
+    for refname in annotated_tag_refs {
+        let refname = String::from_utf8_lossy(refname);
+        let Some(&(oid, target)) = annotated.get(refname.as_ref()) else {
+            continue;
+        };
+        if refs_before.get(refname.as_ref()).map(String::as_str) == Some(oid) {
+            continue;
         }
-        let mut renamed = name.to_vec();
-        if renamed.starts_with(&old[..]) {
-            let mut v = new_.clone();
-            v.extend_from_slice(&renamed[old.len()..]);
-            renamed = v;
+        let tag = refname.strip_prefix("refs/tags/").unwrap_or(&refname);
+        let mut sign = if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.arg("/C").arg(format!("{cmd} {tag} {target}"));
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c")
+                .arg(format!("{cmd} \"$@\""))
+                .arg("sh")
+                .arg(tag)
+                .arg(target);
+            c
+        };
+        let status = sign
+            .current_dir(&opts.target)
+            .env("FRRS_TAG", tag)
+            .env("FRRS_OID", target)
+            .status();
+        match status {
+            Ok(status) if status.success() => signed += 1,
+            Ok(status) => failed.push((tag.to_string(), status.to_string())),
+            Err(e) => failed.push((tag.to_string(), e.to_string())),
         }
-        let target_ref = [b"refs/tags/".as_ref(), renamed.as_slice()].concat();
-        return updated_refs.contains(&target_ref);
     }
-    false
+    if signed > 0 {
+        events::emit(
+            opts,
+            Event::Notice(format!("Re-signed {} tag(s) with --tag-sign-cmd", signed)),
+        );
+    }
+    if !failed.is_empty() {
+        return Err(FilterRepoError::TagSignFailed(failed));
+    }
+    Ok(())
+}
+
+/// Tags of tags. fast-export emits each inner tag under the outer tag's name
+/// just before the outer tag, so an inner tag is put back under its own ref.
+/// An inner tag without a ref of its own cannot be imported next to the outer
+/// one; it is dropped and the outer tag peeled to its target instead.
+#[derive(Debug, Default)]
+pub struct TagChains {
+    /// Tag object id (hex) -> names of the tag refs pointing at it; empty
+    /// unless the repository has tags of tags
+    names: HashMap<Vec<u8>, Vec<Vec<u8>>>,
+    /// `from` line naming a dropped inner tag -> that tag's own `from` line
+    peeled: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl TagChains {
+    pub fn new(repo: &Path, unusual_refs: &[UnusualRef]) -> io::Result<Self> {
+        let mut chains = Self::default();
+        if unusual_refs.iter().all(|r| r.passed_through()) {
+            return Ok(chains);
+        }
+        for (name, oid) in gitutil::get_all_refs(repo)? {
+            if let Some(tag) = name.strip_prefix("refs/tags/") {
+                chains
+                    .names
+                    .entry(oid.into_bytes())
+                    .or_default()
+                    .push(tag.as_bytes().to_vec());
+            }
+        }
+        for names in chains.names.values_mut() {
+            names.sort();
+        }
+        Ok(chains)
+    }
+
+    // The tag name to emit a block under when fast-export borrowed an outer
+    // tag's name for it.
+    fn own_name(&self, tagname: &[u8], hdrs: &[Vec<u8>]) -> Option<Vec<u8>> {
+        let names = self.names.get(original_oid(hdrs)?)?;
+        (!names.iter().any(|n| n == tagname)).then(|| names[0].clone())
+    }
+
+    // Point a tag at whatever a dropped inner tag pointed at, then report
+    // whether this tag is itself an inner tag without a ref, to be dropped.
+    fn peel(&mut self, hdrs: &mut [Vec<u8>]) -> bool {
+        if self.names.is_empty() {
+            return false;
+        }
+        let Some(from) = hdrs.iter_mut().find(|h| h.starts_with(b"from ")) else {
+            return false;
+        };
+        if let Some(target) = self.peeled.get(from.as_slice()) {
+            *from = target.clone();
+        }
+        let from = from.clone();
+        if original_oid(hdrs).is_none_or(|oid| self.names.contains_key(oid)) {
+            return false;
+        }
+        if let Some(mark) = hdrs.iter().find_map(|h| h.strip_prefix(b"mark ")) {
+            self.peeled.insert([b"from ".as_ref(), mark].concat(), from);
+        }
+        true
+    }
+}
+
+fn original_oid(hdrs: &[Vec<u8>]) -> Option<&[u8]> {
+    hdrs.iter()
+        .find_map(|h| h.strip_prefix(b"original-oid "))
+        .map(|oid| oid.trim_ascii_end())
 }
 
 pub fn process_tag_block(
     first_line: &[u8],
-    fe_out: &mut BufReader<ChildStdout>,
+    fe_out: &mut StreamReader,
     mut orig_file: Option<&mut dyn Write>,
     filt_file: &mut dyn Write,
     mut fi_in: Option<&mut ChildStdin>,
     replacer: &Option<MessageReplacer>,
+    tag_replacer: Option<&MessageReplacer>,
     short_mapper: Option<&ShortHashMapper>,
     opts: &Options,
     updated_refs: &mut BTreeSet<Vec<u8>>,
     annotated_tag_refs: &mut BTreeSet<Vec<u8>>,
     ref_renames: &mut BTreeSet<(Vec<u8>, Vec<u8>)>,
     emitted_marks: &mut std::collections::HashSet<u32>,
+    sensitive: Option<&mut SensitiveDataTracker>,
+    signatures: &SignatureTracker,
+    chains: &mut TagChains,
 ) -> io::Result<()> {
     // Extract tag name
     let mut tagname = &first_line[b"tag ".len()..];
@@ -56,6 +252,7 @@ pub fn process_tag_block(
 
     // Buffer header lines until data
     let mut hdrs: Vec<Vec<u8>> = Vec::new();
+    let mut tagger_rewritten = false;
     loop {
         let mut l = Vec::with_capacity(256);
         let read2 = fe_out.read_until(b'\n', &mut l)?;
@@ -67,28 +264,35 @@ pub fn process_tag_block(
         }
         if l.starts_with(b"data ") {
             // Read payload
-            let size_bytes = &l[b"data ".len()..];
-            let n = std::str::from_utf8(size_bytes)
-                .ok()
-                .map(|s| s.trim())
-                .and_then(|s| s.parse::<usize>().ok())
-                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid data header"))?;
-            let mut payload = vec![0u8; n];
-            fe_out.read_exact(&mut payload)?;
+            let payload =
+                fe_out.read_data(&l, || format!("tag {}", String::from_utf8_lossy(tagname)))?;
             if let Some(f) = orig_file.as_mut() {
                 (*f).write_all(&payload)?;
             }
 
+            // An inner tag emitted under its outer tag's name gets its own back
+            if chains.peel(&mut hdrs) {
+                return Ok(());
+            }
+            let own_name = chains.own_name(tagname, &hdrs);
+            let tagname = own_name.as_deref().unwrap_or(tagname);
+
             // Rename tag name
             let mut renamed = tagname.to_vec();
-            if let Some((ref old, ref new_)) = opts.tag_rename {
-                if renamed.starts_with(&old[..]) {
-                    let mut v = new_.clone();
-                    v.extend_from_slice(&renamed[old.len()..]);
-                    renamed = v;
-                }
+            let protected = opts.is_protected_ref(&[b"refs/tags/".as_ref(), tagname].concat());
+            if let Some(v) = opts.renamed_tag(tagname).filter(|_| !protected) {
+                renamed = v;
             }
             let target_ref = [b"refs/tags/".as_ref(), renamed.as_slice()].concat();
+            let (payload, signature) = signatures.split_tag_signature(payload, tagname)?;
+            // A kept signature must sign exactly the tag object we emit
+            let signature = signature.filter(|_| {
+                renamed == tagname
+                    && !tagger_rewritten
+                    && !hdrs
+                        .iter()
+                        .any(|h| h.starts_with(b"from ") && signatures.tag_target_rewritten(h))
+            });
 
             // Dedupe annotated tags
             if updated_refs.contains(&target_ref) {
@@ -100,6 +304,14 @@ pub fn process_tag_block(
                 let old_full = [b"refs/tags/".as_ref(), tagname].concat();
                 ref_renames.insert((old_full, target_ref.clone()));
             }
+            if opts.lightweight_tags {
+                let out = lightweight_tag_block(&target_ref, &hdrs, emitted_marks);
+                filt_file.write_all(&out)?;
+                if let Some(ref mut fi) = fi_in {
+                    fi.write_all(&out)?;
+                }
+                return Ok(());
+            }
 
             // Emit to filtered/import streams
             let mut out = Vec::with_capacity(5 + renamed.len() + 1);
@@ -133,8 +345,16 @@ pub fn process_tag_block(
                 }
             }
 
-            if replacer.is_none() && short_mapper.is_none() {
+            if replacer.is_none()
+                && tag_replacer.is_none()
+                && short_mapper.is_none()
+                && opts.max_commit_message_bytes.is_none()
+            {
                 // No modifications needed; forward header and payload without cloning
+                let mut payload = payload;
+                if let Some(signature) = signature {
+                    payload.extend_from_slice(&signature);
+                }
                 let header = format!("data {}\n", payload.len());
                 filt_file.write_all(header.as_bytes())?;
                 filt_file.write_all(&payload)?;
@@ -143,14 +363,32 @@ pub fn process_tag_block(
                     fi.write_all(&payload)?;
                 }
             } else {
-                let mut new_payload = if let Some(r) = replacer {
-                    r.apply(payload)
-                } else {
-                    payload
+                let original = signature.as_ref().map(|_| payload.clone());
+                let mut new_payload = match (replacer, sensitive) {
+                    (Some(r), Some(tracker)) => {
+                        let rewritten = r.apply(payload.clone());
+                        if rewritten != payload {
+                            tracker.record_tag_message(tagname);
+                        }
+                        rewritten
+                    }
+                    (Some(r), None) => r.apply(payload),
+                    (None, _) => payload,
                 };
+                if let Some(r) = tag_replacer {
+                    new_payload = r.apply(new_payload);
+                }
                 if let Some(mapper) = short_mapper {
                     new_payload = mapper.rewrite(new_payload);
                 }
+                if let Some(limit) = opts.max_commit_message_bytes {
+                    new_payload = crate::message::truncate_message(&new_payload, limit);
+                }
+                if let (Some(signature), Some(original)) = (signature, original) {
+                    if new_payload == original {
+                        new_payload.extend_from_slice(&signature);
+                    }
+                }
                 let header = format!("data {}\n", new_payload.len());
                 filt_file.write_all(header.as_bytes())?;
                 filt_file.write_all(&new_payload)?;
@@ -160,6 +398,9 @@ pub fn process_tag_block(
                 }
             }
             return Ok(());
+        } else if let Some(rewritten) = identity::rewrite_line(&l, &opts.replace_identity) {
+            tagger_rewritten = true;
+            hdrs.push(rewritten);
         } else {
             hdrs.push(l.clone());
         }
@@ -167,6 +408,67 @@ pub fn process_tag_block(
     Ok(())
 }
 
+// --lightweight-tags: point the tag ref straight at the tag's target and drop
+// the tag object. The tag's mark becomes an alias of the target so anything
+// that referenced the tag (e.g. a tag of this tag) still resolves.
+fn lightweight_tag_block(
+    target_ref: &[u8],
+    hdrs: &[Vec<u8>],
+    emitted_marks: &mut std::collections::HashSet<u32>,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    let Some(from) = hdrs.iter().find(|h| h.starts_with(b"from ")) else {
+        return out;
+    };
+    out.extend_from_slice(b"reset ");
+    out.extend_from_slice(target_ref);
+    out.push(b'\n');
+    out.extend_from_slice(from);
+    out.push(b'\n');
+    let mark = hdrs
+        .iter()
+        .find_map(|h| h.strip_prefix(b"mark :"))
+        .and_then(|m| std::str::from_utf8(m).ok())
+        .and_then(|m| m.trim().parse::<u32>().ok());
+    if let Some(mark) = mark {
+        out.extend_from_slice(format!("alias\nmark :{}\nto ", mark).as_bytes());
+        out.extend_from_slice(&from[b"from ".len()..]);
+        out.push(b'\n');
+        emitted_marks.insert(mark);
+    }
+    out
+}
+
+// --annotate-tags: a tag object wrapping a lightweight tag's target, with the
+// template's `%(tag)` placeholder replaced by the tag name.
+pub fn annotated_tag_block(
+    ref_full: &[u8],
+    from_line: &[u8],
+    template: &[u8],
+    tagger: &[u8],
+) -> Vec<u8> {
+    let name = ref_full.strip_prefix(b"refs/tags/").unwrap_or(ref_full);
+    let mut msg = crate::message::replace_all_bytes(template, b"%(tag)", name);
+    if !msg.ends_with(b"\n") {
+        msg.push(b'\n');
+    }
+    let mut out = Vec::with_capacity(64 + name.len() + tagger.len() + msg.len());
+    out.extend_from_slice(b"tag ");
+    out.extend_from_slice(name);
+    out.push(b'\n');
+    out.extend_from_slice(from_line);
+    if !from_line.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    out.extend_from_slice(b"tagger ");
+    out.extend_from_slice(tagger);
+    out.push(b'\n');
+    out.extend_from_slice(format!("data {}\n", msg.len()).as_bytes());
+    out.extend_from_slice(&msg);
+    out.push(b'\n');
+    out
+}
+
 // If a previous 'reset refs/tags/<name>' was seen, capture the following
 // 'from ' line into the buffered_tag_resets list and indicate the line was handled.
 pub fn maybe_capture_pending_tag_reset(
@@ -204,18 +506,10 @@ pub fn process_reset_header(
         return false;
     }
     let mut ref_full = name.to_vec();
-    if let Some((ref old, ref new_)) = opts.tag_rename {
-        let tagname = &name[b"refs/tags/".len()..];
-        if tagname.starts_with(&old[..]) {
-            let new_full = [
-                b"refs/tags/".as_ref(),
-                new_.as_slice(),
-                &tagname[old.len()..],
-            ]
-            .concat();
-            ref_renames.insert((name.to_vec(), new_full.clone()));
-            ref_full = new_full;
-        }
+    if let Some(new_) = opts.renamed_tag(&name[b"refs/tags/".len()..]) {
+        let new_full = [b"refs/tags/".as_ref(), new_.as_slice()].concat();
+        ref_renames.insert((name.to_vec(), new_full.clone()));
+        ref_full = new_full;
     }
     *pending_tag_reset = Some(ref_full);
     true
@@ -0,0 +1,204 @@
+//! Deadline and stall detection for the fast-export | fast-import pipeline.
+//!
+//! The stream loop blocks on reads from fast-export and writes to
+//! fast-import, so a hung git process would stall the run forever. The
+//! watchdog thread kills the registered processes once the deadline passes
+//! (`--pipeline-timeout`) or once the stream has not moved for the stall
+//! window (`--subprocess-timeout`); the blocked read or write then fails and
+//! the caller reports why. Once the stream has ended only the deadline
+//! applies: a process writing out its pack reports no progress.
+
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::FilterRepoError;
+
+/// Why the watchdog killed the pipeline.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Fired {
+    Deadline(Duration),
+    Stalled {
+        phase: &'static str,
+        last_progress_secs: u64,
+    },
+}
+
+impl From<Fired> for FilterRepoError {
+    fn from(fired: Fired) -> Self {
+        match fired {
+            Fired::Deadline(timeout) => FilterRepoError::PipelineTimeout(timeout),
+            Fired::Stalled {
+                phase,
+                last_progress_secs,
+            } => FilterRepoError::PipelineStalled {
+                phase: phase.to_string(),
+                last_progress_secs,
+            },
+        }
+    }
+}
+
+struct State {
+    pids: Vec<u32>,
+    disarmed: bool,
+    fired: Option<Fired>,
+    phase: &'static str,
+    /// The stream has ended and the pipeline waits for a process to exit
+    awaiting_exit: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+    /// Bytes of the fast-export stream consumed so far
+    progress: AtomicU64,
+}
+
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+/// Handle the stream loop uses to report forward progress.
+#[derive(Clone)]
+pub(crate) struct Progress(Arc<Shared>);
+
+impl Progress {
+    pub(crate) fn advance(&self, bytes: usize) {
+        self.0.progress.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+impl Watchdog {
+    /// Start watching with an absolute `deadline` and/or a `stall` window;
+    /// None when neither is set.
+    pub(crate) fn arm(deadline: Option<Duration>, stall: Option<Duration>) -> Option<Self> {
+        if deadline.is_none() && stall.is_none() {
+            return None;
+        }
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                pids: Vec::new(),
+                disarmed: false,
+                fired: None,
+                phase: "stream",
+                awaiting_exit: false,
+            }),
+            wake: Condvar::new(),
+            progress: AtomicU64::new(0),
+        });
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || watch_loop(&thread_shared, deadline, stall));
+        Some(Watchdog { shared })
+    }
+
+    /// Kill `child` too when the watchdog fires.
+    pub(crate) fn watch(&self, child: &Child) {
+        self.lock().pids.push(child.id());
+    }
+
+    pub(crate) fn progress(&self) -> Progress {
+        Progress(Arc::clone(&self.shared))
+    }
+
+    /// The stream has ended and the pipeline now waits for `process` to
+    /// exit; from here on only the deadline can fire.
+    pub(crate) fn await_exit(&self, process: &'static str) {
+        let mut state = self.lock();
+        state.phase = process;
+        state.awaiting_exit = true;
+    }
+
+    /// Stop the watchdog; returns why it already killed the pipeline, if it did.
+    pub(crate) fn disarm(&self) -> Option<Fired> {
+        let mut state = self.lock();
+        state.disarmed = true;
+        self.shared.wake.notify_all();
+        state.fired
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.disarm();
+    }
+}
+
+fn watch_loop(shared: &Shared, deadline: Option<Duration>, stall: Option<Duration>) {
+    let started = Instant::now();
+    let mut seen = shared.progress.load(Ordering::Relaxed);
+    let mut moved_at = started;
+    // Check for stalls a few times per window so a kill lands close to it
+    let poll = stall.map(|s| (s / 4).clamp(Duration::from_millis(10), Duration::from_secs(1)));
+    let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        let now = Instant::now();
+        let until_deadline = deadline.map(|d| d.saturating_sub(now - started));
+        let wait = match (until_deadline, poll) {
+            (Some(d), Some(p)) => d.min(p),
+            (Some(d), None) => d,
+            (None, Some(p)) => p,
+            (None, None) => return,
+        };
+        state = shared
+            .wake
+            .wait_timeout_while(state, wait, |s| !s.disarmed)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+        if state.disarmed {
+            return;
+        }
+        let now = Instant::now();
+        let fired = if deadline.is_some_and(|d| now - started >= d) {
+            Fired::Deadline(deadline.unwrap_or_default())
+        } else {
+            let current = shared.progress.load(Ordering::Relaxed);
+            if current != seen {
+                seen = current;
+                moved_at = now;
+            }
+            match stall {
+                Some(window) if !state.awaiting_exit && now - moved_at >= window => {
+                    Fired::Stalled {
+                        phase: state.phase,
+                        last_progress_secs: (now - moved_at).as_secs(),
+                    }
+                }
+                _ => continue,
+            }
+        };
+        // Kill while holding the lock so `disarm` cannot race a kill into a
+        // process that was already reaped
+        state.fired = Some(fired);
+        for pid in &state.pids {
+            kill(*pid);
+        }
+        return;
+    }
+}
+
+// `Child::kill` needs the child itself, which the stream loop owns while
+// the watchdog waits; go through the platform's kill command instead.
+fn kill(pid: u32) {
+    let pid = pid.to_string();
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/F", "/T", "/PID", &pid]);
+        cmd
+    } else {
+        let mut cmd = Command::new("kill");
+        cmd.args(["-KILL", &pid]);
+        cmd
+    };
+    let _ = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
@@ -104,6 +104,7 @@ fn test_already_ran_detection_with_preflight() -> Result<(), FilterRepoError> {
     let temp_repo = create_test_repo()?;
 
     let opts = filter_repo_rs::Options {
+        source: temp_repo.path().to_path_buf(),
         target: temp_repo.path().to_path_buf(),
         force: false,
         enforce_sanity: true,
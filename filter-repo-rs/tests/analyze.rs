@@ -118,6 +118,47 @@ fn analyze_mode_limits_top_entries_and_populates_paths() {
     );
 }
 
+#[test]
+fn analyze_duplicate_ranking_is_stable_across_runs() {
+    let repo = init_repo();
+    // Equal path counts and equal sizes, so only the tie-break decides
+    for name in ["A", "B", "C", "D", "E"] {
+        let payload = format!("tied duplicate payload {name}");
+        for copy in 1..=2 {
+            write_file(&repo, &format!("ties/{name}{copy}.txt"), &payload);
+        }
+    }
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-m", "tied duplicates"]).0, 0);
+
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.target = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.force = true; // Use --force to bypass sanity checks for unit tests
+    opts.analyze.top = 3;
+    let duplicates_json = || {
+        let report = fr::analysis::generate_report(&opts).expect("generate analysis report");
+        serde_json::to_string(&report.metrics.duplicate_blobs).expect("serialize duplicates")
+    };
+    let first = duplicates_json();
+    for _ in 0..3 {
+        assert_eq!(first, duplicates_json());
+    }
+
+    let report = fr::analysis::generate_report(&opts).expect("generate analysis report");
+    let oids: Vec<&str> = report
+        .metrics
+        .duplicate_blobs
+        .iter()
+        .map(|d| d.oid.as_str())
+        .collect();
+    assert_eq!(oids.len(), 3);
+    let mut sorted = oids.clone();
+    sorted.sort();
+    assert_eq!(oids, sorted, "ties should be broken by oid");
+}
+
 #[test]
 fn analyze_mode_warns_on_commit_thresholds() {
     let repo = init_repo();
@@ -187,4 +228,327 @@ fn analyze_mode_warns_on_commit_thresholds() {
             .any(|w| w.message.contains("parents")),
         "expected warning about excessive commit parents"
     );
+    let parent_warning = report
+        .warnings
+        .iter()
+        .find(|w| w.message.contains("parents"))
+        .unwrap();
+    assert_eq!(parent_warning.level, fr::analysis::WarningLevel::Info);
+    assert_eq!(parent_warning.severity(), fr::analysis::Severity::Info);
+    let message_warning = report
+        .warnings
+        .iter()
+        .find(|w| w.message.contains(&long_oid))
+        .unwrap();
+    assert_eq!(message_warning.level, fr::analysis::WarningLevel::Warning);
+    assert_eq!(message_warning.severity(), fr::analysis::Severity::Warn);
+
+    let json = serde_json::to_value(&report).expect("serialize report");
+    assert!(json["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|w| w["level"] == "warning" && w["severity"] == "warn"));
+
+    opts.analyze.min_severity = fr::analysis::Severity::Warn;
+    let filtered = fr::analysis::generate_report(&opts).expect("generate analysis report");
+    assert!(filtered
+        .warnings
+        .iter()
+        .all(|w| w.severity() >= fr::analysis::Severity::Warn));
+    assert!(filtered
+        .warnings
+        .iter()
+        .any(|w| w.message.contains(&long_oid)));
+}
+
+#[test]
+fn analyze_mode_writes_html_report() {
+    let repo = init_repo();
+    for i in 0..4 {
+        let contents = "y".repeat((i + 1) * 2048);
+        write_file(&repo, &format!("assets/file{}.bin", i), &contents);
+    }
+    write_file(&repo, "docs/guide.md", "guide\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-m", "add assets"]).0, 0);
+    let blob_oid = |path: &str| {
+        let (_, out, _) = run_git(&repo, &["rev-parse", &format!("HEAD:{}", path)]);
+        out.trim().to_string()
+    };
+    let biggest = blob_oid("assets/file3.bin");
+    let second = blob_oid("assets/file2.bin");
+    let smallest = blob_oid("assets/file0.bin");
+
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.target = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.force = true; // Use --force to bypass sanity checks for unit tests
+    opts.analyze.top = 2;
+    opts.analyze.format = fr::AnalyzeFormat::Html;
+    fr::analysis::run(&opts).expect("html analyze run should succeed");
+
+    let html_path = repo.join(".git").join("filter-repo").join("analysis.html");
+    let html = std::fs::read_to_string(&html_path).expect("analysis.html written");
+    assert!(html.contains(&biggest), "missing largest blob oid");
+    assert!(html.contains(&second), "missing second largest blob oid");
+    assert!(
+        !html.contains(&smallest),
+        "top limit should drop smaller blobs"
+    );
+    assert!(html.contains("<script>"), "expected inline script");
+    assert!(!html.contains("src=\""), "expected no external assets");
+
+    // Every directory in the size list gets a row, not just the busiest one
+    let directories = html.split("<h2>Directories</h2>").nth(1).unwrap();
+    let directories = &directories[..directories.find("</table>").unwrap()];
+    assert_eq!(directories.matches("<tr").count() - 1, 2, "{directories}");
+    assert!(directories.contains(">assets/<"), "{directories}");
+    assert!(directories.contains(">docs/<"), "{directories}");
+
+    // Tables must have balanced structural tags.
+    let mut stack: Vec<String> = Vec::new();
+    let mut rest = html.as_str();
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..].find('>').expect("unterminated tag") + start;
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if !["table", "thead", "tbody", "tr", "th", "td"].contains(&name.as_str()) {
+            continue;
+        }
+        if tag.starts_with('/') {
+            assert_eq!(
+                stack.pop().as_deref(),
+                Some(name.as_str()),
+                "unbalanced </{name}>"
+            );
+        } else {
+            stack.push(name);
+        }
+    }
+    assert!(stack.is_empty(), "unclosed tags: {:?}", stack);
+}
+
+#[test]
+fn analyze_range_limits_blobs_to_range() {
+    let repo = init_repo();
+    let main_branch = current_branch(&repo);
+    assert_eq!(run_git(&repo, &["checkout", "-q", "-b", "feature"]).0, 0);
+    write_file(&repo, "assets/huge.bin", &"F".repeat(64 * 1024));
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add huge"]).0, 0);
+    let (_c, huge_oid, _e) = run_git(&repo, &["rev-parse", "HEAD:assets/huge.bin"]);
+    let huge_oid = huge_oid.trim().to_string();
+    assert_eq!(run_git(&repo, &["checkout", "-q", &main_branch]).0, 0);
+
+    let analyze = |range: &str| {
+        let mut opts = fr::Options::default();
+        opts.source = repo.clone();
+        opts.target = repo.clone();
+        opts.mode = fr::Mode::Analyze;
+        opts.analyze.range = Some(range.to_string());
+        fr::analysis::generate_report(&opts).expect("ranged analysis")
+    };
+
+    let feature = analyze(&format!("{}..feature", main_branch));
+    assert!(
+        feature
+            .metrics
+            .largest_blobs
+            .iter()
+            .any(|b| b.oid == huge_oid),
+        "blob missing from feature range: {:?}",
+        feature.metrics.largest_blobs
+    );
+    assert_eq!(feature.metrics.object_types.get("commit"), Some(&1));
+
+    let main = analyze(&main_branch);
+    assert!(main.metrics.largest_blobs.iter().all(|b| b.oid != huge_oid));
+
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.analyze.range = Some("no-such-branch..feature".to_string());
+    let err = fr::analysis::generate_report(&opts).expect_err("bad range should fail");
+    assert!(err.to_string().contains("--analyze-range"), "{err}");
+}
+
+#[test]
+fn analyze_extensions_groups_blob_sizes_by_file_type() {
+    let repo = init_repo();
+    write_file(&repo, "img/a.png", &"P".repeat(3000));
+    write_file(&repo, "img/b.PNG", &"Q".repeat(2000));
+    write_file(&repo, "notes.txt", &"t".repeat(400));
+    write_file(&repo, "docs/more.txt", &"u".repeat(200));
+    write_file(&repo, "Makefile", &"m".repeat(700));
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.analyze.extensions = true;
+    let report = fr::analysis::generate_report(&opts).expect("extension analysis");
+    let stats = &report.metrics.extension_stats;
+    let find = |ext: &str| {
+        stats
+            .iter()
+            .find(|s| s.extension == ext)
+            .unwrap_or_else(|| panic!("missing {ext} in {stats:?}"))
+    };
+    assert_eq!((find("png").blobs, find("png").total_bytes), (2, 5000));
+    assert_eq!((find("txt").blobs, find("txt").total_bytes), (2, 600));
+    assert_eq!((find("(none)").blobs, find("(none)").total_bytes), (1, 700));
+    assert_eq!(stats[0].extension, "png");
+    assert!(
+        stats
+            .windows(2)
+            .all(|w| w[0].total_bytes >= w[1].total_bytes),
+        "{stats:?}"
+    );
+
+    opts.analyze.top = 1;
+    let report = fr::analysis::generate_report(&opts).expect("truncated analysis");
+    assert_eq!(report.metrics.extension_stats.len(), 1);
+    fr::analysis::run(&opts).expect("human output with extensions");
+}
+
+#[test]
+fn analyze_dump_blobs_writes_every_blob_as_ndjson() {
+    let repo = init_repo();
+    for i in 0..4 {
+        write_file(&repo, &format!("f{i}.txt"), &"x".repeat(100 * (i + 1)));
+    }
+    // Same content under a second path counts once
+    write_file(&repo, "copy.txt", &"x".repeat(100));
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    write_file(&repo, "f0.txt", "changed");
+    assert_eq!(run_git(&repo, &["commit", "-q", "-am", "change f0"]).0, 0);
+
+    let dump = repo.join("blobs.ndjson");
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.analyze.top = 1;
+    opts.analyze.dump_blobs = Some(dump.clone());
+    fr::analysis::generate_report(&opts).expect("analysis with blob dump");
+
+    // No dangling objects here, so every stored blob is in history
+    let (_c, objects, _e) = run_git(
+        &repo,
+        &[
+            "cat-file",
+            "--batch-all-objects",
+            "--batch-check=%(objecttype) %(objectname)",
+        ],
+    );
+    let mut expected: Vec<&str> = objects
+        .lines()
+        .filter_map(|l| l.strip_prefix("blob "))
+        .collect();
+    expected.sort();
+    let content = std::fs::read_to_string(&dump).unwrap();
+    let records: Vec<serde_json::Value> = content
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("valid NDJSON line"))
+        .collect();
+    assert_eq!(records.len(), expected.len(), "{content}");
+    let oids: Vec<&str> = records.iter().map(|r| r["oid"].as_str().unwrap()).collect();
+    assert_eq!(oids, expected);
+    let changed = records
+        .iter()
+        .find(|r| r["size"] == 7)
+        .expect("changed blob present");
+    assert_eq!(changed["path"], "f0.txt");
+}
+
+#[test]
+fn analyze_suggests_filters_for_large_assets() {
+    let repo = init_repo();
+    let big = vec![0x5au8; 5 * 1024 * 1024];
+    std::fs::create_dir_all(repo.join("assets")).unwrap();
+    std::fs::write(repo.join("assets/video.bin"), &big).unwrap();
+    write_file(&repo, "src/main.rs", "fn main() {}\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add assets"]).0, 0);
+
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.analyze.thresholds.warn_blob_bytes = 1024 * 1024;
+    let report = fr::analysis::generate_report(&opts).expect("analysis with suggestions");
+    let big_len = big.len() as u64;
+
+    let dir = report
+        .suggestions
+        .iter()
+        .find(|s| s.command.contains("--invert-paths"))
+        .unwrap_or_else(|| panic!("no directory suggestion: {:?}", report.suggestions));
+    assert_eq!(dir.command, "filter-repo-rs --path assets/ --invert-paths");
+    assert!(dir.message.contains("assets/"), "{}", dir.message);
+    assert_eq!(dir.estimated_bytes_saved, big_len);
+
+    let size = report
+        .suggestions
+        .iter()
+        .find(|s| s.command.contains("--max-blob-size"))
+        .unwrap_or_else(|| panic!("no size suggestion: {:?}", report.suggestions));
+    assert_eq!(size.command, "filter-repo-rs --max-blob-size 1M");
+    assert_eq!(size.estimated_bytes_saved, big_len);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["suggestions"].as_array().map(Vec::len), Some(2));
+    fr::analysis::run(&opts).expect("human output with suggestions");
+}
+
+#[test]
+fn analyze_fetch_first_counts_refs_from_bare_remote() {
+    let bare = mktemp("fr_rs_analyze_bare");
+    std::fs::create_dir_all(&bare).unwrap();
+    assert_eq!(run_git(&bare, &["init", "--bare"]).0, 0);
+
+    let seed = init_repo();
+    assert_eq!(run_git(&seed, &["checkout", "-b", "extra"]).0, 0);
+    write_file(&seed, "extra.txt", "hello\n");
+    run_git(&seed, &["add", "."]).0;
+    run_git(&seed, &["commit", "-m", "extra"]).0;
+    let bare_str = bare.to_string_lossy().to_string();
+    assert_eq!(run_git(&seed, &["remote", "add", "origin", &bare_str]).0, 0);
+    assert_eq!(run_git(&seed, &["push", "-q", "origin", "--all"]).0, 0);
+
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["remote", "add", "origin", &bare_str]).0, 0);
+    let mut opts = fr::Options::default();
+    opts.source = repo.clone();
+    opts.target = repo.clone();
+    opts.mode = fr::Mode::Analyze;
+    opts.analyze.json = true;
+    let refs_before = fr::analysis::generate_report(&opts)
+        .expect("generate analysis report")
+        .metrics
+        .refs_total;
+
+    // --no-fetch wins over fetch-first
+    opts.analyze.fetch_first = true;
+    opts.no_fetch = true;
+    fr::analysis::run(&opts).expect("analyze without fetching");
+    let (code, _o, _e) = run_git(&repo, &["show-ref", "--verify", "refs/heads/extra"]);
+    assert_ne!(code, 0, "--no-fetch must not fetch");
+
+    opts.no_fetch = false;
+    fr::analysis::run(&opts).expect("analyze after fetching");
+    let (code, _o, _e) = run_git(&repo, &["show-ref", "--verify", "refs/heads/extra"]);
+    assert_eq!(code, 0, "fetch-first should fetch the extra branch");
+    let refs_after = fr::analysis::generate_report(&opts)
+        .expect("generate analysis report")
+        .metrics
+        .refs_total;
+    assert_eq!(refs_after, refs_before + 1);
 }
@@ -86,3 +86,153 @@ fn backup_honors_explicit_file_path() {
         expected_path
     );
 }
+
+#[test]
+fn quiet_backup_prints_nothing_to_stdout() {
+    let repo = init_repo();
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--backup", "--quiet"])
+        .output()
+        .expect("run filter-repo-rs --backup --quiet");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        output.stdout.is_empty(),
+        "unexpected stdout: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let bundles = find_bundles_in(&repo.join(".git").join("filter-repo"));
+    assert!(!bundles.is_empty(), "bundle should still be created");
+}
+
+#[test]
+fn quiet_conflicts_with_progress() {
+    let repo = init_repo();
+    let err = run_tool(&repo, |o| {
+        o.quiet = true;
+        o.progress = true;
+    })
+    .expect_err("--quiet with --progress should be rejected");
+    assert!(err.to_string().contains("mutually exclusive"), "{err}");
+}
+
+#[test]
+fn preserve_original_refs_saves_old_branch_values() {
+    let repo = init_repo();
+    let branch = current_branch(&repo);
+    write_file(&repo, "secret.txt", "drop me\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add secret"]).0, 0);
+    let (_c, old_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"secret.txt".to_vec());
+        o.invert_paths = true;
+        o.preserve_original_refs = true;
+        o.write_report = true;
+    });
+
+    let saved_ref = format!("refs/original/refs/heads/{}", branch);
+    let (_c, saved, _e) = run_git(&repo, &["rev-parse", &saved_ref]);
+    assert_eq!(saved.trim(), old_head.trim());
+    let (_c, new_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_ne!(new_head.trim(), old_head.trim());
+
+    let debug_dir = repo.join(".git").join("filter-repo");
+    let report = fs::read_to_string(debug_dir.join("report.txt")).unwrap();
+    assert!(report.contains(&saved_ref), "{report}");
+    assert!(
+        report.contains("refs/original/ | git update-ref --stdin"),
+        "{report}"
+    );
+    let ref_map = fs::read_to_string(debug_dir.join("ref-map")).unwrap_or_default();
+    assert!(!ref_map.contains("refs/original/"), "{ref_map}");
+
+    // A second run leaves the saved refs out of the rewrite
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"README.md".to_vec());
+        o.preserve_original_refs = true;
+    });
+    let (_c, still_saved, _e) = run_git(&repo, &["rev-parse", &saved_ref]);
+    assert_eq!(still_saved.trim(), old_head.trim());
+}
+
+#[test]
+fn preserve_original_refs_refuses_to_overwrite_without_force() {
+    let repo = init_repo();
+    let branch = current_branch(&repo);
+    let saved_ref = format!("refs/original/refs/heads/{}", branch);
+    assert_eq!(run_git(&repo, &["update-ref", &saved_ref, "HEAD"]).0, 0);
+
+    let err = run_tool(&repo, |o| {
+        o.force = false;
+        o.enforce_sanity = false;
+        o.assume_yes = true;
+        o.paths.push(b"README.md".to_vec());
+        o.preserve_original_refs = true;
+    })
+    .expect_err("existing refs/original/ entries should block the run");
+    assert!(err.to_string().contains(&saved_ref), "{err}");
+    assert!(err.to_string().contains("--force"), "{err}");
+}
+
+#[test]
+fn state_dir_relocates_all_working_files() {
+    let repo = init_repo();
+    write_file(&repo, "src/a.txt", "a\n");
+    run_git(&repo, &["add", "."]);
+    run_git(&repo, &["commit", "-q", "-m", "add a"]);
+    run_tool_expect_success(&repo, |o| {
+        o.state_dir = Some(PathBuf::from("ci-state/frrs"));
+        o.backup = true;
+        o.write_report = true;
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+
+    assert!(
+        !repo.join(".git").join("filter-repo").exists(),
+        "nothing should be written under .git/filter-repo"
+    );
+    let state = repo.join("ci-state").join("frrs");
+    assert!(state.join("commit-map").exists());
+    assert!(state.join("report.txt").exists());
+    assert!(!find_bundles_in(&state).is_empty());
+}
+
+#[test]
+fn no_backup_if_noop_defers_bundle_until_refs_change() {
+    let repo = init_repo();
+    let backup_dir = repo.join(".git").join("filter-repo");
+    let (_c, head_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    run_tool_expect_success(&repo, |o| {
+        o.backup = true;
+        o.skip_backup_on_noop = true;
+        o.paths.push(b"README.md".to_vec());
+    });
+    assert!(
+        find_bundles_in(&backup_dir).is_empty(),
+        "no-op run must not create a bundle"
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.backup = true;
+        o.skip_backup_on_noop = true;
+        o.path_renames
+            .push((b"README.md".to_vec(), b"README.txt".to_vec()));
+    });
+    let bundles = find_bundles_in(&backup_dir);
+    assert_eq!(bundles.len(), 1, "{bundles:?}");
+    let bundle = bundles[0].to_string_lossy().to_string();
+    let (code, heads, err) = run_git(&repo, &["bundle", "list-heads", &bundle]);
+    assert_eq!(code, 0, "{err}");
+    let branch = current_branch(&repo);
+    assert!(
+        heads.contains(&format!("{} refs/heads/{}", head_before.trim(), branch)),
+        "bundle should hold the pre-rewrite tip: {heads}"
+    );
+}
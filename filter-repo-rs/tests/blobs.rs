@@ -38,3 +38,142 @@ fn max_blob_size_threshold_boundary() {
     assert!(tree.contains("exact.txt"));
     assert!(!tree.contains("over.txt"));
 }
+
+#[test]
+fn keep_blobs_with_ids_survive_size_and_id_stripping() {
+    for size_cache in [true, false] {
+        let repo = init_repo();
+        std::fs::write(repo.join("keep.bin"), vec![b'K'; 4096]).unwrap();
+        std::fs::write(repo.join("big1.bin"), vec![b'A'; 4096]).unwrap();
+        std::fs::write(repo.join("big2.bin"), vec![b'B'; 4096]).unwrap();
+        std::fs::write(repo.join("small.bin"), vec![b'S'; 10]).unwrap();
+        run_git(&repo, &["add", "."]).0;
+        assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add blobs"]).0, 0);
+        let (_c, keep_oid, _e) = run_git(&repo, &["rev-parse", "HEAD:keep.bin"]);
+        let keep_list = repo.join("keep-ids.txt");
+        std::fs::write(&keep_list, &keep_oid).unwrap();
+        // Also listed for stripping: the allowlist wins
+        let strip_list = repo.join("strip-ids.txt");
+        std::fs::write(&strip_list, &keep_oid).unwrap();
+
+        run_tool_expect_success(&repo, |o| {
+            o.max_blob_size = Some(1024);
+            o.size_cache = size_cache;
+            o.keep_blobs_with_ids = Some(keep_list.clone());
+            o.strip_blobs_with_ids = Some(strip_list.clone());
+        });
+        let (_c2, tree, _e2) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+        assert!(tree.contains("keep.bin"), "size_cache={size_cache}: {tree}");
+        assert!(
+            tree.contains("small.bin"),
+            "size_cache={size_cache}: {tree}"
+        );
+        assert!(
+            !tree.contains("big1.bin"),
+            "size_cache={size_cache}: {tree}"
+        );
+        assert!(
+            !tree.contains("big2.bin"),
+            "size_cache={size_cache}: {tree}"
+        );
+        let (_c3, kept_after, _e3) = run_git(&repo, &["rev-parse", "HEAD:keep.bin"]);
+        assert_eq!(kept_after, keep_oid);
+    }
+}
+
+#[test]
+fn normalize_eol_converts_text_and_skips_binary() {
+    let repo = init_repo();
+    let binary = b"\x00\x01\r\n\xff\r\n".to_vec();
+    std::fs::write(repo.join("mixed.txt"), b"one\r\ntwo\nthree\r\n").unwrap();
+    std::fs::write(repo.join("image.bin"), &binary).unwrap();
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.normalize_eol = Some(filter_repo_rs::opts::EolMode::Lf);
+    });
+
+    let show = |spec: &str| {
+        std::process::Command::new("git")
+            .current_dir(&repo)
+            .args(["cat-file", "blob", spec])
+            .output()
+            .unwrap()
+            .stdout
+    };
+    assert_eq!(show("HEAD:mixed.txt"), b"one\ntwo\nthree\n");
+    assert_eq!(show("HEAD:image.bin"), binary);
+}
+
+#[test]
+fn delete_duplicate_paths_keeps_canonical_copy() {
+    let repo = init_repo();
+    for path in ["vendor/a/util.js", "lib/util.js", "vendor/b/util.js"] {
+        write_file(&repo, path, "export const same = 1;\n");
+    }
+    write_file(&repo, "vendor/c/other.js", "unique\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "vendor copies"]).0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.delete_duplicate_paths = vec![b"vendor/**".to_vec()];
+        o.write_report = true;
+    });
+
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.lines().any(|l| l == "lib/util.js"), "{tree}");
+    assert!(tree.lines().any(|l| l == "vendor/c/other.js"), "{tree}");
+    assert!(
+        !tree.contains("vendor/a/") && !tree.contains("vendor/b/"),
+        "{tree}"
+    );
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains(
+            "Duplicate paths deleted: 2 (2 paths)\n  vendor/a/util.js\n  vendor/b/util.js\n"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+fn no_rewrite_if_unchanged_names_untouched_blobs_by_original_id() {
+    let repo = init_repo();
+    write_file(&repo, "keep.txt", "plain text\n");
+    write_file(&repo, "secret.txt", "token=SECRET-1\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    // One commit adding the untouched blob again next to a rewritten one
+    write_file(&repo, "copy.txt", "plain text\n");
+    write_file(&repo, "notes.txt", "plain text\nSECRET-1\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "mixed"]).0, 0);
+    let blob = |rev: &str| run_git(&repo, &["rev-parse", rev]).1.trim().to_string();
+    let keep = blob("HEAD:keep.txt");
+    let secret = blob("HEAD:secret.txt");
+    let repl = repo.join("repl.txt");
+    std::fs::write(&repl, "SECRET-1==>REDACTED\n").unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(repl.clone());
+        o.no_rewrite_if_unchanged = true;
+    });
+
+    assert_eq!(blob("HEAD:keep.txt"), keep);
+    assert_eq!(blob("HEAD:copy.txt"), keep);
+    assert_ne!(blob("HEAD:secret.txt"), secret);
+    let (_c, notes, _e) = run_git(&repo, &["show", "HEAD:notes.txt"]);
+    assert_eq!(notes, "plain text\nREDACTED\n");
+    let filtered =
+        std::fs::read_to_string(repo.join(".git/filter-repo/fast-export.filtered")).unwrap();
+    assert!(
+        filtered.contains(&format!("M 100644 {keep} copy.txt\n")),
+        "{filtered}"
+    );
+    assert!(!filtered.contains("data 11\nplain text\n"), "{filtered}");
+    assert_eq!(run_git(&repo, &["fsck", "--strict"]).0, 0);
+}
@@ -76,6 +76,124 @@ fn max_blob_size_with_strip_blobs_by_sha() {
     assert!(!tree.contains("file2.txt"));
 }
 
+#[test]
+fn strip_blobs_with_abbreviated_ids() {
+    let repo = init_repo();
+    write_file(&repo, "secret.bin", "topsecret\n");
+    write_file(&repo, "keep.txt", "keep\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    let (_c, blob_id, _e) = run_git(&repo, &["rev-parse", "HEAD:secret.bin"]);
+    let abbrev = blob_id.trim()[..10].to_ascii_uppercase();
+    let list = repo.join("strip-sha.txt");
+    std::fs::write(&list, format!("{abbrev}\nnot-a-sha\n")).unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_blobs_with_ids = Some(list.clone());
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(!tree.contains("secret.bin"), "{tree}");
+    assert!(tree.contains("keep.txt"), "{tree}");
+
+    let err = run_tool(&repo, |o| {
+        o.strip_blobs_with_ids = Some(list.clone());
+        o.strict_blob_ids = true;
+    })
+    .expect_err("--strict-blob-ids should reject the invalid line");
+    assert!(err.to_string().contains("not-a-sha"), "{err}");
+}
+
+#[test]
+fn strip_blobs_with_ids_reads_first_word_listings_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let repo = init_repo();
+    write_file(&repo, "secret.bin", "topsecret\n");
+    write_file(&repo, "keep.txt", "keep\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    let (_c, blob_id, _e) = run_git(&repo, &["rev-parse", "HEAD:secret.bin"]);
+    let listing = format!(
+        "# <sha> <size> <path>\n\n{} 10 secret.bin\n",
+        &blob_id.trim()[..7]
+    );
+
+    let mut child = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--strip-blobs-with-ids", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run filter-repo-rs");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(listing.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("wait for filter-repo-rs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(!stderr.contains("skipping invalid SHA entry"), "{stderr}");
+
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(!tree.contains("secret.bin"), "{tree}");
+    assert!(tree.contains("keep.txt"), "{tree}");
+}
+
+#[test]
+fn strip_blobs_with_ambiguous_abbreviation_lists_matches() {
+    let repo = init_repo();
+    // Both blob ids start with c67420a.
+    write_file(&repo, "a.txt", "ambiguous 4523\n");
+    write_file(&repo, "b.txt", "ambiguous 14795\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    let (_c, a, _e) = run_git(&repo, &["rev-parse", "HEAD:a.txt"]);
+    let (_c, b, _e) = run_git(&repo, &["rev-parse", "HEAD:b.txt"]);
+    assert_eq!(&a[..7], "c67420a");
+    assert_eq!(&b[..7], "c67420a");
+    let list = repo.join("strip-sha.txt");
+    std::fs::write(&list, "c67420a a.txt\n").unwrap();
+
+    let err = run_tool(&repo, |o| {
+        o.strip_blobs_with_ids = Some(list.clone());
+    })
+    .expect_err("an ambiguous abbreviation should stop the run");
+    let msg = err.to_string();
+    assert!(msg.contains("ambiguous abbreviated SHA"), "{msg}");
+    assert!(msg.contains(a.trim()) && msg.contains(b.trim()), "{msg}");
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.contains("a.txt") && tree.contains("b.txt"), "{tree}");
+}
+
+#[test]
+fn strip_blobs_bigger_than_in_packs_strips_by_sha() {
+    let repo = init_repo();
+    write_file(&repo, "big.bin", &"x".repeat(4096));
+    write_file(&repo, "small.txt", "small\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add blobs"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_oversized_from_analysis = Some(1024);
+        o.write_report = true;
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(!tree.contains("big.bin"), "{tree}");
+    assert!(tree.contains("small.txt"), "{tree}");
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(report.contains("Blobs stripped by SHA: 1\n"), "{report}");
+    assert!(report.contains("Blobs stripped by size: 0\n"), "{report}");
+    assert!(report.contains("big.bin"), "{report}");
+    assert!(
+        report.contains("strip-blobs-bigger-than-in-packs: 1024"),
+        "{report}"
+    );
+}
+
 #[test]
 fn max_blob_size_empty_repository() {
     let repo = init_repo();
@@ -299,3 +417,95 @@ fn max_blob_size_precise_threshold_handling() {
     assert!(!tree.contains("exactly_101_bytes.txt"));
     assert!(!tree.contains("just_over_100.txt"));
 }
+
+fn batch_all_objects_spawns(commands: &[Vec<String>]) -> usize {
+    commands
+        .iter()
+        .filter(|cmd| cmd.iter().any(|a| a == "cat-file"))
+        .filter(|cmd| cmd.iter().any(|a| a == "--batch-all-objects"))
+        .count()
+}
+
+fn clone_for_size_cache(src: &std::path::Path, name: &str) -> std::path::PathBuf {
+    let dst = mktemp(name);
+    let (c, _o, e) = run_git(
+        src,
+        &["clone", "-q", "--no-local", ".", dst.to_str().unwrap()],
+    );
+    assert_eq!(c, 0, "clone failed: {e}");
+    assert_eq!(run_git(&dst, &["remote", "remove", "origin"]).0, 0);
+    dst
+}
+
+#[test]
+fn size_cache_matches_uncached_run_with_single_batch_pass() {
+    let src = init_repo();
+    std::fs::write(src.join("big.bin"), vec![b'B'; 4096]).unwrap();
+    write_file(&src, "small.txt", "small");
+    write_file(&src, "secret.txt", "topsecret\n");
+    run_git(&src, &["add", "."]);
+    assert_eq!(run_git(&src, &["commit", "-q", "-m", "add blobs"]).0, 0);
+    let (_c, secret_id, _e) = run_git(&src, &["rev-parse", "HEAD:secret.txt"]);
+    let ids = mktemp("size_cache_ids");
+    std::fs::write(&ids, secret_id).unwrap();
+    let ids_arg = ids.to_str().unwrap();
+    let args = [
+        "--force",
+        "--max-blob-size",
+        "1024",
+        "--strip-blobs-with-ids",
+        ids_arg,
+    ];
+
+    let cached = clone_for_size_cache(&src, "size_cache_on");
+    let (output, invocations) = run_cli_with_git_spy(&cached, &args);
+    assert!(
+        output.status.success(),
+        "cached run failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let commands = git_commands_for_repo(&cached, &invocations);
+    assert_eq!(batch_all_objects_spawns(&commands), 1, "{commands:?}");
+    assert!(
+        !commands
+            .iter()
+            .any(|cmd| cmd.iter().any(|a| a == "cat-file") && cmd.iter().any(|a| a == "-s")),
+        "sizes should come from the cache: {commands:?}"
+    );
+
+    let uncached = clone_for_size_cache(&src, "size_cache_off");
+    let mut uncached_args = args.to_vec();
+    uncached_args.push("--no-size-cache");
+    let (output, _invocations) = run_cli_with_git_spy(&uncached, &uncached_args);
+    assert!(
+        output.status.success(),
+        "uncached run failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let (_c, cached_head, _e) = run_git(&cached, &["rev-parse", "HEAD"]);
+    let (_c, uncached_head, _e) = run_git(&uncached, &["rev-parse", "HEAD"]);
+    assert_eq!(cached_head, uncached_head);
+    let (_c, tree, _e) = run_git(&cached, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.contains("small.txt"), "tree: {tree}");
+    assert!(!tree.contains("big.bin"), "tree: {tree}");
+    assert!(!tree.contains("secret.txt"), "tree: {tree}");
+}
+
+#[test]
+fn analyze_reads_object_sizes_in_one_batch_pass() {
+    let repo = init_repo();
+    std::fs::write(repo.join("big.bin"), vec![b'B'; 4096]).unwrap();
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add blob"]).0, 0);
+    let (output, invocations) = run_cli_with_git_spy(&repo, &["--analyze", "--analyze-json"]);
+    assert!(
+        output.status.success(),
+        "analyze failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let commands = git_commands_for_repo(&repo, &invocations);
+    assert_eq!(batch_all_objects_spawns(&commands), 1, "{commands:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"size\": 4096"), "stdout: {stdout}");
+}
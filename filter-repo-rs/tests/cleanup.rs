@@ -5,8 +5,9 @@ fn run_cleanup_case(
     repo: &std::path::Path,
     args: &[&str],
 ) -> (std::process::Output, Vec<Vec<String>>) {
-    // Add --force to avoid interference from sanity checks in CLI tests
-    let mut full_args = vec!["--force"];
+    // Add --force to avoid interference from sanity checks in CLI tests, and a
+    // rename so the run rewrites history (unchanged runs skip cleanup)
+    let mut full_args = vec!["--force", "--path-rename", "README.md:README.txt"];
     full_args.extend_from_slice(args);
     let (output, invocations) = run_cli_with_git_spy(repo, &full_args);
     (output, git_commands_for_repo(repo, &invocations))
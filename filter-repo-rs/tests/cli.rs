@@ -447,3 +447,592 @@ fn cleanup_flag_supports_new_and_legacy_syntax() {
         stderr_new
     );
 }
+
+#[test]
+fn invalid_options_exit_with_code_and_tag() {
+    let repo = init_repo();
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--quiet", "--progress"])
+        .output()
+        .expect("run filter-repo-rs with conflicting flags");
+
+    assert_eq!(
+        Some(2),
+        output.status.code(),
+        "invalid options should exit with code 2"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: [INVALID_OPTIONS] --quiet and --progress are mutually exclusive"),
+        "unexpected stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn completions_list_base_flags_and_hide_debug_flags() {
+    let output = cli_command()
+        .env_remove("FRRS_DEBUG")
+        .args(["--completions", "bash"])
+        .output()
+        .expect("generate bash completions");
+    assert!(
+        output.status.success(),
+        "completion generation should succeed"
+    );
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.contains("complete -F _filter_repo_rs filter-repo-rs"));
+    assert!(
+        script.contains("--max-blob-size"),
+        "bash completions should list --max-blob-size"
+    );
+    assert!(
+        !script.contains("--fe_stream_override"),
+        "debug-only flags should be omitted without FRRS_DEBUG"
+    );
+
+    let debug = cli_command()
+        .env("FRRS_DEBUG", "1")
+        .args(["--completions", "bash"])
+        .output()
+        .expect("generate bash completions in debug mode");
+    assert!(String::from_utf8_lossy(&debug.stdout).contains("--fe_stream_override"));
+}
+
+#[test]
+fn show_refs_before_after_lists_renamed_branch() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["branch", "feature/x"]).0, 0);
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let short = &head.trim()[..12];
+
+    let output = cli_command()
+        .args([
+            "--force",
+            "--branch-rename",
+            "feature/:exp/",
+            "--show-refs-before-after",
+        ])
+        .current_dir(&repo)
+        .output()
+        .expect("run filter-repo-rs with --show-refs-before-after");
+    assert!(
+        output.status.success(),
+        "branch rename should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .skip_while(|l| !l.starts_with("Ref changes"))
+        .skip(1)
+        .map(|l| l.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            vec!["created", "refs/heads/exp/x", "-", "->", short],
+            vec!["deleted", "refs/heads/feature/x", short, "->", "-"],
+        ],
+        "{stdout}"
+    );
+}
+
+#[test]
+fn refs_range_rewrites_only_recent_commits() {
+    let repo = init_repo();
+    for i in 2..=10 {
+        write_file(&repo, &format!("src/f{i}.txt"), &i.to_string());
+        run_git(&repo, &["add", "."]);
+        assert_eq!(
+            run_git(&repo, &["commit", "-q", "-m", &format!("c{i}")]).0,
+            0
+        );
+    }
+    assert_eq!(run_git(&repo, &["branch", "old", "HEAD~5"]).0, 0);
+    let branch = current_branch(&repo);
+    let (_c, before, _e) = run_git(&repo, &["rev-list", "HEAD"]);
+    let before: Vec<&str> = before.lines().collect();
+    assert_eq!(before.len(), 10);
+
+    let range = format!("{branch}~3..{branch}");
+    let output = cli_command()
+        .args(["--force", "--refs", &range, "--path-rename", "src/:lib/"])
+        .current_dir(&repo)
+        .output()
+        .expect("run filter-repo-rs on a commit range");
+    assert!(
+        output.status.success(),
+        "range rewrite should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let (_c, after, _e) = run_git(&repo, &["rev-list", "HEAD"]);
+    let after: Vec<&str> = after.lines().collect();
+    assert_eq!(after.len(), 10, "ancestry must stay intact");
+    assert_eq!(after[3..], before[3..], "older commits must keep their ids");
+    for (new_, old) in after[..3].iter().zip(&before[..3]) {
+        assert_ne!(new_, old);
+    }
+    let (_c, parent, _e) = run_git(&repo, &["rev-parse", &format!("{}^", after[2])]);
+    assert_eq!(parent.trim(), before[3]);
+    let (_c, files, _e) = run_git(&repo, &["show", "--name-only", "--format=", "HEAD"]);
+    assert_eq!(files.trim(), "lib/f10.txt");
+    let (_c, old, _e) = run_git(&repo, &["rev-parse", "refs/heads/old"]);
+    assert_eq!(old.trim(), before[5]);
+}
+
+#[test]
+fn compat_mode_runs_colon_path_rename() {
+    let repo = init_repo();
+    write_file(&repo, "lib/a.txt", "a\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add lib"]).0, 0);
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args([
+            "--compat-filter-repo",
+            "--force",
+            "--path-rename",
+            "lib/:src/",
+        ])
+        .output()
+        .expect("run filter-repo-rs --compat-filter-repo");
+    assert!(output.status.success(), "{:?}", output);
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.contains("src/a.txt"), "{tree}");
+    assert!(!tree.contains("lib/a.txt"), "{tree}");
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--compat-filter-repo", "--mailmap", "m.txt"])
+        .output()
+        .expect("run filter-repo-rs --compat-filter-repo --mailmap");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unsupported in compat mode: --mailmap"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn analyze_flag_reads_source_repository() {
+    let repo = init_repo();
+    let outside = mktemp("fr_rs_analyze_cwd");
+    std::fs::create_dir_all(&outside).unwrap();
+
+    let output = cli_command()
+        .arg("--analyze")
+        .arg("--source")
+        .arg(&repo)
+        .current_dir(&outside)
+        .output()
+        .expect("run filter-repo-rs --analyze --source");
+    assert!(
+        output.status.success(),
+        "analyze with --source should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let json = cli_command()
+        .args(["--analyze", "--json", "--top", "3", "--source"])
+        .arg(&repo)
+        .current_dir(&outside)
+        .output()
+        .expect("run filter-repo-rs --analyze --json");
+    assert!(json.status.success(), "analyze --json should succeed");
+    let value: serde_json::Value =
+        serde_json::from_slice(&json.stdout).expect("stdout should be a JSON report");
+    assert!(value.get("metrics").is_some(), "report: {}", value);
+}
+
+#[test]
+fn analyze_flag_rejects_filtering_flags() {
+    let repo = init_repo();
+    let output = cli_command()
+        .args(["--analyze", "--path", "src", "--replace-text", "rules.txt"])
+        .current_dir(&repo)
+        .output()
+        .expect("run filter-repo-rs --analyze with filters");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr
+            .contains("--analyze cannot be combined with filtering flags: --path, --replace-text"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn list_checks_prints_check_identifiers() {
+    let dir = mktemp("fr_rs_list_checks");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = cli_command()
+        .arg("--list-checks")
+        .current_dir(&dir)
+        .output()
+        .expect("run filter-repo-rs --list-checks");
+
+    assert!(output.status.success(), "--list-checks should exit 0");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for id in [
+        "already_ran_detection",
+        "freshly_packed",
+        "untracked_files",
+        "worktree_count",
+    ] {
+        assert!(
+            stdout.lines().any(|line| line.starts_with(id)),
+            "missing {id}: {stdout}"
+        );
+    }
+    assert!(
+        std::fs::read_dir(&dir).unwrap().next().is_none(),
+        "--list-checks must not touch the working directory"
+    );
+}
+
+fn fresh_clone(prefix: &str) -> std::path::PathBuf {
+    let src = init_repo();
+    let dst = mktemp(prefix);
+    let (code, _o, e) = run_git(
+        &src,
+        &["clone", "-q", "--no-local", ".", dst.to_str().unwrap()],
+    );
+    assert_eq!(code, 0, "clone failed: {e}");
+    dst
+}
+
+#[test]
+fn in_place_rewrite_needs_confirmation_without_a_terminal() {
+    let repo = fresh_clone("fr_rs_in_place");
+    let (_c, head_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let run = |extra: &[&str]| {
+        cli_command()
+            .args(["--path", "README.md"])
+            .args(extra)
+            .current_dir(&repo)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("run filter-repo-rs")
+    };
+
+    let output = run(&[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{stderr}");
+    assert!(stderr.contains("[SANITY_IN_PLACE]"), "{stderr}");
+    assert!(stderr.contains("--yes"), "{stderr}");
+    let (_c, head_after, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head_before, head_after);
+
+    let output = run(&["--yes"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(repo.join(".git/filter-repo/commit-map").exists());
+}
+
+#[test]
+fn check_mode_passes_on_fresh_clone() {
+    let repo = fresh_clone("fr_rs_check_fresh");
+    let output = cli_command()
+        .arg("--check")
+        .current_dir(&repo)
+        .output()
+        .expect("run filter-repo-rs --check");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stdout: {stdout}\nstderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for id in [
+        "option_validation",
+        "already_ran_detection",
+        "worktree_count",
+    ] {
+        assert!(
+            stdout
+                .lines()
+                .any(|line| line.starts_with(id) && line.ends_with("  pass")),
+            "missing {id}: {stdout}"
+        );
+    }
+    assert!(!stdout.contains("fail"), "{stdout}");
+    assert!(
+        !repo.join(".git").join("filter-repo").exists(),
+        "--check must not create the state directory"
+    );
+}
+
+#[test]
+fn check_mode_json_reports_dirty_working_tree() {
+    let repo = fresh_clone("fr_rs_check_dirty");
+    write_file(&repo, "README.md", "modified\n");
+    let (_c, head_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let output = cli_command()
+        .args(["--check", "--json"])
+        .current_dir(&repo)
+        .output()
+        .expect("run filter-repo-rs --check --json");
+
+    assert_eq!(output.status.code(), Some(3));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("stdout is JSON");
+    let entries = report.as_array().expect("array of checks");
+    let entry = |name: &str| {
+        entries
+            .iter()
+            .find(|e| e["check"] == name)
+            .unwrap_or_else(|| panic!("missing {name}: {report}"))
+    };
+    let dirty = entry("working_tree_cleanliness");
+    assert_eq!(dirty["status"], "fail");
+    assert!(
+        dirty["detail"].as_str().unwrap().contains("not clean"),
+        "{dirty}"
+    );
+    assert_eq!(entry("worktree_count")["status"], "pass");
+    assert!(entry("worktree_count")["detail"].is_null());
+
+    let (_c, head_after, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head_before, head_after);
+    assert!(!repo.join(".git").join("filter-repo").exists());
+}
+
+#[test]
+fn skip_check_bypasses_only_the_named_check() {
+    let repo = fresh_clone("fr_rs_skip_check");
+    // Over 100 loose objects make the clone fail freshly_packed
+    let scratch = mktemp("fr_rs_skip_check_objects");
+    std::fs::create_dir_all(&scratch).unwrap();
+    let files: Vec<String> = (0..120)
+        .map(|i| {
+            let path = scratch.join(format!("obj{i}"));
+            std::fs::write(&path, format!("loose object {i}\n")).unwrap();
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+    let mut args = vec!["hash-object", "-w"];
+    args.extend(files.iter().map(String::as_str));
+    assert_eq!(run_git(&repo, &args).0, 0);
+
+    let run = |extra: &[&str]| {
+        cli_command()
+            .args(["--yes", "--path", "README.md"])
+            .args(extra)
+            .current_dir(&repo)
+            .env("FRRS_LOG", "debug")
+            .output()
+            .expect("run filter-repo-rs")
+    };
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("SANITY_NOT_FRESHLY_PACKED"), "{stderr}");
+
+    write_file(&repo, "untracked.txt", "not added\n");
+    let output = run(&["--skip-check", "freshly_packed"]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("SANITY_UNTRACKED"), "{stderr}");
+    assert!(stderr.contains("Skipping freshly_packed"), "{stderr}");
+
+    std::fs::remove_file(repo.join("untracked.txt")).unwrap();
+    let output = run(&["--skip-check", "freshly_packed"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+}
+
+#[test]
+fn skip_check_rejects_unknown_names() {
+    let repo = init_repo();
+    let output = cli_command()
+        .args(["--skip-check", "freshly-packed", "--path", "README.md"])
+        .current_dir(&repo)
+        .output()
+        .expect("run filter-repo-rs --skip-check");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unknown check 'freshly-packed'"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("freshly_packed") && stderr.contains("worktree_count"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn frrs_log_debug_writes_leveled_lines_to_stderr_only() {
+    let repo = init_repo();
+    let output = cli_command()
+        .current_dir(&repo)
+        .env("FRRS_LOG", "debug")
+        .args(["--yes", "--path", "README.md"])
+        .output()
+        .expect("run filter-repo-rs with FRRS_LOG=debug");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("] sanity: Sanity check PASSED: git_dir_structure"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("] stream: spawning"), "{stderr}");
+    assert!(
+        stderr.lines().any(|l| l.starts_with("[DEBUG] [")),
+        "{stderr}"
+    );
+    assert!(!stdout.contains("[DEBUG]"), "stdout: {stdout}");
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .env("FRRS_LOG", "debug")
+        .args(["--log-level", "warn", "--force", "--path", "README.md"])
+        .output()
+        .expect("run filter-repo-rs with --log-level warn");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        !stderr.contains("[DEBUG]"),
+        "--log-level wins over FRRS_LOG: {stderr}"
+    );
+}
+
+// Every file under `dir` with its contents, to show a run left it untouched.
+fn snapshot_tree(dir: &std::path::Path) -> std::collections::BTreeMap<std::path::PathBuf, Vec<u8>> {
+    let mut files = std::collections::BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let contents = std::fs::read(&path).unwrap();
+                files.insert(path.strip_prefix(dir).unwrap().to_path_buf(), contents);
+            }
+        }
+    }
+    files
+}
+
+#[test]
+fn separate_target_gets_filtered_history_and_source_is_untouched() {
+    let source = fresh_clone("fr_rs_separate_source");
+    write_file(&source, "keep/a.txt", "a\n");
+    write_file(&source, "drop.txt", "secret\n");
+    assert_eq!(run_git(&source, &["add", "."]).0, 0);
+    let (code, _o, err) = run_git(
+        &source,
+        &[
+            "-c",
+            "user.name=A U Thor",
+            "-c",
+            "user.email=a.u.thor@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "add files",
+        ],
+    );
+    assert_eq!(code, 0, "{err}");
+    let branch = current_branch(&source);
+    let before = snapshot_tree(&source);
+
+    let target = mktemp("fr_rs_separate_target");
+    let output = cli_command()
+        .arg("--source")
+        .arg(&source)
+        .arg("--target")
+        .arg(&target)
+        .args(["--path", "keep/"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let (code, files, err) = run_git(&target, &["ls-tree", "-r", "--name-only", &branch]);
+    assert_eq!(code, 0, "{err}");
+    assert_eq!(files.trim(), "keep/a.txt");
+    let (_c, log, _e) = run_git(&target, &["log", "--format=%s", &branch]);
+    assert_eq!(log.lines().next(), Some("add files"));
+    assert!(
+        target.join("keep/a.txt").exists(),
+        "worktree is checked out"
+    );
+    assert_eq!(before, snapshot_tree(&source), "source must not change");
+
+    // A target that already has history is refused
+    let output = cli_command()
+        .arg("--source")
+        .arg(&source)
+        .arg("--target")
+        .arg(&target)
+        .args(["--path", "keep/"])
+        .output()
+        .expect("run filter-repo-rs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{stderr}");
+    assert!(stderr.contains("[SANITY_TARGET_NOT_EMPTY]"), "{stderr}");
+}
+
+#[test]
+fn separate_target_without_filters_gets_a_full_copy() {
+    let source = fresh_clone("fr_rs_copy_source");
+    let branch = current_branch(&source);
+    let (_c, head, _e) = run_git(&source, &["rev-parse", "HEAD"]);
+    let target = mktemp("fr_rs_copy_target");
+    let output = cli_command()
+        .arg("--source")
+        .arg(&source)
+        .arg("--target")
+        .arg(&target)
+        .arg("--force")
+        .output()
+        .expect("run filter-repo-rs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(!stderr.contains("Nothing to do"), "{stderr}");
+
+    let (code, copied, err) = run_git(&target, &["rev-parse", &branch]);
+    assert_eq!(code, 0, "{err}");
+    assert_eq!(copied.trim(), head.trim());
+    assert!(target.join("README.md").exists(), "worktree is checked out");
+}
+
+#[test]
+fn separate_bare_target_is_created_bare() {
+    let source = fresh_clone("fr_rs_separate_bare_source");
+    let target = mktemp("fr_rs_separate_bare").with_extension("git");
+    let output = cli_command()
+        .arg("--source")
+        .arg(&source)
+        .arg("--target")
+        .arg(&target)
+        .args(["--path", "README.md"])
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (_c, bare, _e) = run_git(&target, &["rev-parse", "--is-bare-repository"]);
+    assert_eq!(bare.trim(), "true");
+    let (code, _o, err) = run_git(&target, &["cat-file", "-e", "HEAD:README.md"]);
+    assert_eq!(code, 0, "{err}");
+}
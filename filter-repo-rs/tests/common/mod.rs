@@ -30,6 +30,9 @@ pub struct GitInvocation {
     #[allow(dead_code)]
     pub cwd: String,
     pub args: Vec<String>,
+    /// Input fed to `git update-ref --stdin`, when that is what ran.
+    #[allow(dead_code)]
+    pub stdin: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -117,8 +120,8 @@ fn compile_git_spy(bin_dir: &Path) -> PathBuf {
     let source = r#"
 use std::env;
 use std::fs::OpenOptions;
-use std::io::Write;
-use std::process::Command;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
 
 fn main() {
     let args: Vec<_> = env::args_os().skip(1).collect();
@@ -139,10 +142,48 @@ fn main() {
     for arg in &args {
         fields.push(arg.to_string_lossy().into_owned());
     }
+    // Record what update-ref --stdin is asked to do, then replay it to git
+    let captures_stdin = args.iter().any(|a| a == "update-ref") && args.iter().any(|a| a == "--stdin");
+    let mut input = Vec::new();
+    if captures_stdin {
+        let _ = std::io::stdin().read_to_end(&mut input);
+        let escaped = String::from_utf8_lossy(&input).replace('\\', "\\\\").replace('\n', "\\n");
+        fields.push(format!("stdin={}", escaped));
+    }
     if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&log_path) {
         let _ = writeln!(file, "{}", fields.join("\t"));
     }
-    let status = Command::new(real_git).args(&args).status();
+    // Simulate a hung git: never run the subcommand named in FRRS_GIT_SPY_HANG
+    // (capability probes with `-h` still answer)
+    if let Ok(hang) = env::var("FRRS_GIT_SPY_HANG") {
+        let probe = args.iter().any(|a| a == "-h");
+        if !probe && args.iter().any(|a| a.to_str() == Some(hang.as_str())) {
+            loop {
+                std::thread::sleep(std::time::Duration::from_secs(60));
+            }
+        }
+    }
+    let status = if captures_stdin {
+        Command::new(real_git)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                if let Some(mut sin) = child.stdin.take() {
+                    let _ = sin.write_all(&input);
+                }
+                child.wait()
+            })
+    } else {
+        Command::new(real_git).args(&args).status()
+    };
+    // Simulate a slow exit (e.g. a large pack being written): linger after
+    // the subcommand named in FRRS_GIT_SPY_SLOW_EXIT has finished
+    if let Ok(slow) = env::var("FRRS_GIT_SPY_SLOW_EXIT") {
+        if args.iter().any(|a| a.to_str() == Some(slow.as_str())) {
+            std::thread::sleep(std::time::Duration::from_secs(3));
+        }
+    }
     match status {
         Ok(status) => std::process::exit(status.code().unwrap_or(1)),
         Err(_) => std::process::exit(1),
@@ -203,12 +244,36 @@ fn parse_git_invocations(log_path: &Path) -> Vec<GitInvocation> {
         .map(|line| {
             let mut parts = line.split('\t');
             let cwd = parts.next().unwrap_or("").to_string();
-            let args = parts.map(|s| s.to_string()).collect();
-            GitInvocation { cwd, args }
+            let mut args: Vec<String> = parts.map(|s| s.to_string()).collect();
+            let stdin = match args.last() {
+                Some(last) if last.starts_with("stdin=") => {
+                    args.pop().map(|s| unescape_spy_stdin(&s["stdin=".len()..]))
+                }
+                _ => None,
+            };
+            GitInvocation { cwd, args, stdin }
         })
         .collect()
 }
 
+#[allow(dead_code)]
+fn unescape_spy_stdin(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
 #[allow(dead_code)]
 pub fn git_commands_for_repo(repo: &Path, invocations: &[GitInvocation]) -> Vec<Vec<String>> {
     let repo_abs_buf = canonicalize_for_git(repo);
@@ -244,6 +309,18 @@ pub fn find_git_command<'a>(commands: &'a [Vec<String>], name: &str) -> Option<&
 
 #[allow(dead_code)]
 pub fn run_cli_with_git_spy(repo: &Path, extra_args: &[&str]) -> (Output, Vec<GitInvocation>) {
+    run_cli_with_git_spy_env(repo, extra_args, &[])
+}
+
+/// Like [`run_cli_with_git_spy`], with extra environment for the tool and the spy
+/// (e.g. `FRRS_GIT_SPY_HANG=fast-export` to make that subcommand hang, or
+/// `FRRS_GIT_SPY_SLOW_EXIT=fast-import` to make it exit 3s after finishing).
+#[allow(dead_code)]
+pub fn run_cli_with_git_spy_env(
+    repo: &Path,
+    extra_args: &[&str],
+    envs: &[(&str, &str)],
+) -> (Output, Vec<GitInvocation>) {
     let spy = ensure_git_spy();
     let log_path = next_log_path(&spy.bin_dir);
     if log_path.exists() {
@@ -269,6 +346,9 @@ pub fn run_cli_with_git_spy(repo: &Path, extra_args: &[&str]) -> (Output, Vec<Gi
     cmd.env("PATH", joined);
     cmd.env("FRRS_REAL_GIT", &spy.real_git);
     cmd.env("FRRS_GIT_LOG", &log_path);
+    for (key, value) in envs {
+        cmd.env(key, value);
+    }
     let output = cmd.output().expect("run filter-repo-rs with git spy");
     let invocations = parse_git_invocations(&log_path);
     (output, invocations)
@@ -171,3 +171,257 @@ fn invalid_repo_config_emits_friendly_error() {
         stderr
     );
 }
+
+#[test]
+fn repo_config_min_severity_hides_info_warnings() {
+    let repo = init_repo();
+
+    let baseline = cli_command()
+        .current_dir(&repo)
+        .arg("--analyze")
+        .output()
+        .expect("run analysis without config");
+    assert!(
+        baseline.status.success(),
+        "baseline analysis should succeed"
+    );
+    let stdout_baseline = String::from_utf8_lossy(&baseline.stdout);
+    assert!(
+        stdout_baseline.contains("[INFO]"),
+        "baseline output should group info warnings: {}",
+        stdout_baseline
+    );
+
+    write_file(
+        &repo,
+        ".filter-repo-rs.toml",
+        "[analyze]\nmin_severity = \"warn\"\n",
+    );
+    let filtered = cli_command()
+        .current_dir(&repo)
+        .arg("--analyze")
+        .output()
+        .expect("run analysis with min_severity config");
+    assert!(
+        filtered.status.success(),
+        "filtered analysis should succeed"
+    );
+    let stdout_filtered = String::from_utf8_lossy(&filtered.stdout);
+    assert!(
+        !stdout_filtered.contains("[INFO]"),
+        "info warnings should be hidden by min_severity: {}",
+        stdout_filtered
+    );
+}
+
+#[test]
+fn git_config_max_blob_size_applies_and_cli_overrides() {
+    let repo = init_repo();
+    std::fs::write(repo.join("big.bin"), vec![b'A'; 4096]).unwrap();
+    std::fs::write(repo.join("small.bin"), vec![b'B'; 10]).unwrap();
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add blobs"]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["config", "filterRepo.maxBlobSize", "1024"]).0,
+        0
+    );
+
+    let with_cli_flag = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--max-blob-size", "8K"])
+        .output()
+        .expect("run filter-repo-rs with --max-blob-size");
+    assert!(
+        with_cli_flag.status.success(),
+        "run with --max-blob-size should succeed: {}",
+        String::from_utf8_lossy(&with_cli_flag.stderr)
+    );
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(
+        tree.contains("big.bin"),
+        "CLI flag should override git config"
+    );
+
+    let from_config = cli_command()
+        .current_dir(&repo)
+        .arg("--force")
+        .output()
+        .expect("run filter-repo-rs with git config defaults");
+    assert!(
+        from_config.status.success(),
+        "run with git config defaults should succeed: {}",
+        String::from_utf8_lossy(&from_config.stderr)
+    );
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.contains("small.bin"));
+    assert!(
+        !tree.contains("big.bin"),
+        "filterRepo.maxBlobSize should drop the large blob"
+    );
+}
+
+#[test]
+fn config_file_filter_paths_apply_and_cli_paths_replace_them() {
+    let repo = init_repo();
+    write_file(&repo, "keep/a.txt", "a");
+    write_file(&repo, "other/b.txt", "b");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    let config = repo.join("frrs.toml");
+    std::fs::write(&config, "[filter]\npaths = [\"keep/\", \"other/\"]\n").unwrap();
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--path", "keep/", "--config"])
+        .arg(&config)
+        .output()
+        .expect("run filter-repo-rs with --config");
+    assert!(
+        output.status.success(),
+        "run with --config should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.contains("keep/a.txt"));
+    assert!(
+        !tree.contains("other/b.txt"),
+        "--path should replace the config file's paths: {}",
+        tree
+    );
+}
+
+#[test]
+fn config_file_max_blob_size_is_overridden_by_cli() {
+    let repo = init_repo();
+    std::fs::write(repo.join("big.bin"), vec![b'A'; 4096]).unwrap();
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add blob"]).0, 0);
+    let config = repo.join("frrs.toml");
+    std::fs::write(&config, "[filter]\nmax_blob_size = \"1K\"\n").unwrap();
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--max-blob-size", "8K", "--config"])
+        .arg(&config)
+        .output()
+        .expect("run filter-repo-rs with --config");
+    assert!(
+        output.status.success(),
+        "run with --config should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(
+        tree.contains("big.bin"),
+        "--max-blob-size should override the config file: {}",
+        tree
+    );
+}
+
+#[test]
+fn config_file_unknown_key_names_the_key() {
+    let repo = init_repo();
+    write_file(
+        &repo,
+        ".filter-repo-rs.toml",
+        "[filter]\npath = [\"src/\"]\n",
+    );
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .arg("--force")
+        .output()
+        .expect("run filter-repo-rs with a mistyped config key");
+    assert_eq!(Some(2), output.status.code());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unknown field `path`"),
+        "expected the offending key in stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn cli_arguments_override_env_overrides() {
+    let repo = init_repo();
+    for idx in 0..5 {
+        let file_path = format!("blob-{idx}.bin");
+        std::fs::write(repo.join(&file_path), vec![b'a' + (idx as u8); 1024 + idx])
+            .unwrap_or_else(|e| panic!("failed to write test blob {file_path}: {e}"));
+        run_git(&repo, &["add", &file_path]);
+    }
+    run_git(&repo, &["commit", "-m", "add blobs"]);
+
+    let from_env = cli_command()
+        .current_dir(&repo)
+        .env("FRRS_TOP", "4")
+        .arg("--analyze")
+        .output()
+        .expect("run analysis with FRRS_TOP");
+    let stdout = String::from_utf8_lossy(&from_env.stdout);
+    assert!(
+        stdout.contains("Top 4 blobs by size"),
+        "FRRS_TOP should set the analysis top count: {}",
+        stdout
+    );
+
+    let override_out = cli_command()
+        .current_dir(&repo)
+        .env("FRRS_TOP", "4")
+        .args(["--analyze", "--analyze-top", "2"])
+        .output()
+        .expect("run analysis with FRRS_TOP and --analyze-top");
+    let stdout = String::from_utf8_lossy(&override_out.stdout);
+    assert!(
+        stdout.contains("Top 2 blobs by size"),
+        "--analyze-top should override FRRS_TOP: {}",
+        stdout
+    );
+
+    let invalid = cli_command()
+        .current_dir(&repo)
+        .env("FRRS_TOP", "many")
+        .arg("--analyze")
+        .output()
+        .expect("run analysis with invalid FRRS_TOP");
+    assert_eq!(Some(2), invalid.status.code());
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("FRRS_TOP"));
+}
+
+#[test]
+fn threshold_profile_sets_blob_threshold_and_explicit_flag_overrides_it() {
+    let repo = init_repo();
+    std::fs::write(repo.join("asset.bin"), vec![b'x'; 2 * 1024 * 1024]).unwrap();
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "asset"]).0, 0);
+    let analyze = |args: &[&str]| {
+        let out = cli_command()
+            .current_dir(&repo)
+            .arg("--analyze")
+            .args(args)
+            .output()
+            .expect("run analysis");
+        assert!(out.status.success(), "{out:?}");
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    let default = analyze(&[]);
+    assert!(!default.contains("(threshold"), "{default}");
+    let strict = analyze(&["--analyze-threshold-profile", "strict"]);
+    assert!(strict.contains("(threshold 1.00 MiB)"), "{strict}");
+    let explicit = analyze(&[
+        "--debug-mode",
+        "--analyze-threshold-profile=strict",
+        "--analyze-large-blob",
+        "4194304",
+    ]);
+    assert!(!explicit.contains("(threshold"), "{explicit}");
+
+    let invalid = cli_command()
+        .current_dir(&repo)
+        .args(["--analyze", "--analyze-threshold-profile", "paranoid"])
+        .output()
+        .expect("run analysis with unknown profile");
+    assert_eq!(invalid.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("paranoid"));
+}
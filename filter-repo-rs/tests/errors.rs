@@ -144,6 +144,7 @@ fn error_handling_invalid_sha_format_in_strip_blobs() {
         target: repo.clone(),
         refs: vec!["--all".to_string()],
         strip_blobs_with_ids: Some(invalid_sha_file),
+        strict_blob_ids: true,
         force: true, // Use --force to bypass sanity checks for error handling tests
         ..Default::default()
     };
@@ -281,3 +282,98 @@ fn error_handling_extremely_long_paths() {
         "expected extremely long paths to trigger an error"
     );
 }
+
+#[test]
+fn error_handling_pipeline_timeout_kills_hung_git() {
+    let repo = init_repo();
+    let (_c, head_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    let started = std::time::Instant::now();
+    let (output, _invocations) = run_cli_with_git_spy_env(
+        &repo,
+        &["--force", "--path", "README.md", "--pipeline-timeout", "1"],
+        &[("FRRS_GIT_SPY_HANG", "fast-export")],
+    );
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(30),
+        "watchdog should stop the hung pipeline"
+    );
+
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[PIPELINE_TIMEOUT]"), "stderr: {}", stderr);
+
+    let (_c, head_after, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head_before, head_after, "refs must be left untouched");
+    let pack_dir = repo.join(".git/objects/pack");
+    let leftovers: Vec<_> = std::fs::read_dir(&pack_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("tmp_"))
+                .collect()
+        })
+        .unwrap_or_default();
+    assert!(leftovers.is_empty(), "partial packs left: {:?}", leftovers);
+}
+
+#[test]
+fn error_handling_subprocess_timeout_spares_slow_fast_import_exit() {
+    let repo = init_repo();
+    let (output, _invocations) = run_cli_with_git_spy_env(
+        &repo,
+        &[
+            "--force",
+            "--path",
+            "README.md",
+            "--subprocess-timeout",
+            "1",
+        ],
+        &[("FRRS_GIT_SPY_SLOW_EXIT", "fast-import")],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(!stderr.contains("[PIPELINE_STALLED]"), "stderr: {}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn error_handling_subprocess_timeout_kills_stalled_stream() {
+    let repo = init_repo();
+    let (_c, head_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    // Nobody ever writes to the fifo, so the stream never produces a byte
+    let fifo = repo.join(".git/stalled-stream");
+    let status = std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .expect("run mkfifo");
+    assert!(status.success());
+
+    let started = std::time::Instant::now();
+    let err = run_tool(&repo, |o| {
+        o.debug_mode = true;
+        o.fe_stream_override = Some(fifo.clone());
+        o.subprocess_timeout = Some(std::time::Duration::from_secs(1));
+    })
+    .expect_err("the stalled stream must be killed");
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(30),
+        "watchdog should stop the stalled pipeline"
+    );
+    match &err {
+        fr::FilterRepoError::PipelineStalled {
+            phase,
+            last_progress_secs,
+        } => {
+            assert_eq!(phase, "stream");
+            assert!(*last_progress_secs >= 1, "{err}");
+        }
+        other => panic!("expected PipelineStalled, got {other:?}"),
+    }
+    assert_eq!(err.code(), "PIPELINE_STALLED");
+    assert_eq!(err.exit_code(), 4);
+
+    let (_c, head_after, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head_before, head_after, "refs must be left untouched");
+}
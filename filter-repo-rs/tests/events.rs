@@ -0,0 +1,70 @@
+use filter_repo_rs as fr;
+use filter_repo_rs::events::{Event, Phase, StripReason};
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::*;
+
+#[derive(Default)]
+struct CollectingSink(Mutex<Vec<Event>>);
+
+impl fr::EventSink for CollectingSink {
+    fn on_event(&self, event: &Event) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[test]
+fn event_sink_receives_phases_commits_and_ref_updates() {
+    let repo = init_repo();
+    std::fs::write(repo.join("big.bin"), vec![b'A'; 4096]).unwrap();
+    write_file(&repo, "src/a.txt", "a");
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+    let branch = format!("refs/heads/{}", current_branch(&repo));
+
+    let sink = Arc::new(CollectingSink::default());
+    run_tool_expect_success(&repo, |o| {
+        o.max_blob_size = Some(1024);
+        o.event_sink = Some(sink.clone());
+    });
+
+    let events = sink.0.lock().unwrap();
+    let phases: Vec<Phase> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::PhaseStarted(p) => Some(*p),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        phases,
+        vec![Phase::Preflight, Phase::Rewrite, Phase::Finalize]
+    );
+    let commits = events
+        .iter()
+        .filter(|e| matches!(e, Event::CommitProcessed { .. }))
+        .count();
+    assert_eq!(commits, 2, "events: {:?}", events);
+    assert!(events.contains(&Event::BlobStripped {
+        reason: StripReason::MaxSize,
+        size: 4096,
+    }));
+    assert!(
+        events.iter().any(|e| matches!(
+            e,
+            Event::RefUpdated { name, old: Some(_), new: Some(_) } if *name == branch
+        )),
+        "missing RefUpdated for {}: {:?}",
+        branch,
+        events
+    );
+    match events.last() {
+        Some(Event::Finished(summary)) => {
+            assert_eq!(summary.commits, 2);
+            assert_eq!(summary.blobs_stripped, 1);
+            assert!(summary.refs_updated >= 1);
+        }
+        other => panic!("expected Finished last, got {:?}", other),
+    }
+}
@@ -55,3 +55,180 @@ fn commit_map_records_pruned_commit_as_null() {
     let null_oid = "0000000000000000000000000000000000000000";
     assert!(contents.contains(&format!("{} {}", drop_oid, null_oid)));
 }
+
+#[test]
+fn map_lookup_answers_both_directions_after_the_run() {
+    let repo = init_repo();
+    write_file(&repo, "keep/keep.txt", "keep one");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-m", "add keep file"]).0, 0);
+    let (_c, keep_old, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "drop/drop.txt", "drop me");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-m", "add drop file"]).0, 0);
+    let (_c, drop_old, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"keep".to_vec());
+    });
+    let (_c, keep_new, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let (keep_old, keep_new, drop_old) = (keep_old.trim(), keep_new.trim(), drop_old.trim());
+    assert_ne!(keep_old, keep_new);
+
+    let reverse = std::fs::read_to_string(
+        repo.join(".git")
+            .join("filter-repo")
+            .join("commit-map-reverse"),
+    )
+    .unwrap();
+    assert!(
+        reverse.contains(&format!("{keep_new} {keep_old}\n")),
+        "{reverse}"
+    );
+    assert!(!reverse.contains(drop_old), "{reverse}");
+
+    let lookup = |oid: &str| {
+        let output = cli_command()
+            .current_dir(&repo)
+            .args(["--map-lookup", oid])
+            .output()
+            .expect("run filter-repo-rs --map-lookup");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+    let (ok, out, err) = lookup(keep_old);
+    assert!(ok, "{err}");
+    assert_eq!(out, format!("{keep_old} -> {keep_new}\n"));
+    let (ok, out, err) = lookup(&keep_new[..10]);
+    assert!(ok, "{err}");
+    assert_eq!(out, format!("{keep_new} <- {keep_old}\n"));
+    let (ok, out, err) = lookup(drop_old);
+    assert!(ok, "{err}");
+    assert_eq!(out, format!("{drop_old} -> pruned\n"));
+    let (ok, _out, err) = lookup("deadbeef");
+    assert!(!ok);
+    assert!(err.contains("not in the commit-map"), "{err}");
+    // Looking up never rewrites anything
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head.trim(), keep_new);
+}
+
+#[test]
+fn rewrite_notes_follows_rewritten_commits() {
+    let repo = init_repo();
+    write_file(&repo, "src/lib.rs", "pub fn f() {}\n");
+    run_git(&repo, &["add", "."]);
+    run_git(&repo, &["commit", "-q", "-m", "add lib"]);
+    let (_c, old_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let (c, _o, e) = run_git(&repo, &["notes", "add", "-m", "Reviewed-by: Alice", "HEAD"]);
+    assert_eq!(c, 0, "git notes add failed: {}", e);
+
+    run_tool_expect_success(&repo, |o| {
+        o.rewrite_notes = true;
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+
+    let (_c, new_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_ne!(old_head.trim(), new_head.trim(), "HEAD should be rewritten");
+    let (c, note, e) = run_git(&repo, &["notes", "show", "HEAD"]);
+    assert_eq!(c, 0, "note should follow the rewritten commit: {}", e);
+    assert_eq!(note.trim(), "Reviewed-by: Alice");
+    let (_c, listed, _e) = run_git(&repo, &["notes", "list"]);
+    assert_eq!(listed.lines().count(), 1, "old note entry should be gone");
+}
+
+#[test]
+fn parent_map_lists_all_octopus_parents() {
+    let repo = init_repo();
+    let main = current_branch(&repo);
+    for branch in ["b", "c"] {
+        assert_eq!(
+            run_git(&repo, &["checkout", "-q", "-b", branch, &main]).0,
+            0
+        );
+        write_file(&repo, &format!("src/{}.txt", branch), branch);
+        run_git(&repo, &["add", "."]);
+        assert_eq!(run_git(&repo, &["commit", "-q", "-m", branch]).0, 0);
+    }
+    assert_eq!(run_git(&repo, &["checkout", "-q", &main]).0, 0);
+    write_file(&repo, "src/a.txt", "a");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "a"]).0, 0);
+    let (c, _o, e) = run_git(&repo, &["merge", "-q", "--no-edit", "b", "c"]);
+    assert_eq!(c, 0, "octopus merge failed: {}", e);
+    let (_c, old_merge, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    run_tool_expect_success(&repo, |o| {
+        o.write_parent_map = true;
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+
+    let (_c, new_parents, _e) =
+        run_git(&repo, &["rev-parse", "HEAD", "HEAD^1", "HEAD^2", "HEAD^3"]);
+    let expected = format!(
+        "{} {}",
+        old_merge.trim(),
+        new_parents.split_whitespace().collect::<Vec<_>>().join(" ")
+    );
+    let mut contents = String::new();
+    File::open(repo.join(".git").join("filter-repo").join("parent-map"))
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert!(
+        contents.lines().any(|l| l == expected),
+        "missing merge entry {:?} in:\n{}",
+        expected,
+        contents
+    );
+}
+
+fn exported_commits(repo: &std::path::Path) -> usize {
+    let filtered =
+        std::fs::read_to_string(repo.join(".git/filter-repo/fast-export.filtered")).unwrap();
+    filtered
+        .lines()
+        .filter(|l| l.starts_with("commit "))
+        .count()
+}
+
+#[test]
+fn incremental_rerun_reuses_marks_and_commit_map() {
+    let repo = init_repo();
+    for name in ["a.txt", "b.txt"] {
+        write_file(&repo, name, &format!("{name}\n"));
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", name]);
+    }
+    let configure = |o: &mut filter_repo_rs::Options| {
+        o.incremental = true;
+        o.path_renames = vec![(b"a.txt".to_vec(), b"renamed-a.txt".to_vec())];
+    };
+    let commit_map = repo.join(".git/filter-repo/commit-map");
+
+    run_tool_expect_success(&repo, configure);
+    assert_eq!(exported_commits(&repo), 3);
+    let first_map = std::fs::read_to_string(&commit_map).unwrap();
+    let (_c, first_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    // Nothing new: no commit is exported again and the mapping is unchanged
+    run_tool_expect_success(&repo, configure);
+    assert_eq!(exported_commits(&repo), 0);
+    assert_eq!(std::fs::read_to_string(&commit_map).unwrap(), first_map);
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head, first_head);
+
+    // A new commit is the only one exported, on top of the reused history
+    write_file(&repo, "c.txt", "c\n");
+    run_git(&repo, &["add", "."]);
+    run_git(&repo, &["commit", "-q", "-m", "c.txt"]);
+    run_tool_expect_success(&repo, configure);
+    assert_eq!(exported_commits(&repo), 1);
+    let map = std::fs::read_to_string(&commit_map).unwrap();
+    assert!(map.starts_with(&first_map), "{map}");
+    assert_eq!(map.lines().count(), 4);
+    let (_c, parent, _e) = run_git(&repo, &["rev-parse", "HEAD~1"]);
+    assert_eq!(parent, first_head);
+}
@@ -73,3 +73,82 @@ fn merge_parents_dedup_when_side_branch_pruned() {
         parents
     );
 }
+
+// A repo whose main branch was fast-forwarded onto a merge of main into
+// feature, so the merge's first parent is the feature commit.
+fn merge_with_mainline_second() -> (std::path::PathBuf, String) {
+    let repo = init_repo();
+    let main = current_branch(&repo);
+
+    assert_eq!(run_git(&repo, &["checkout", "-b", "feature"]).0, 0);
+    write_file(&repo, "feature.txt", "feature");
+    assert_eq!(run_git(&repo, &["add", "feature.txt"]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-m", "feature work"]).0, 0);
+
+    assert_eq!(run_git(&repo, &["checkout", &main]).0, 0);
+    write_file(&repo, "main.txt", "main");
+    assert_eq!(run_git(&repo, &["add", "main.txt"]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-m", "main work"]).0, 0);
+
+    // Merge main into feature, then fast-forward main onto the merge: its
+    // first parent is the feature commit, not main's previous tip
+    assert_eq!(run_git(&repo, &["checkout", "feature"]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["merge", "--no-ff", "-m", "merge", &main]).0,
+        0
+    );
+    assert_eq!(run_git(&repo, &["checkout", &main]).0, 0);
+    assert_eq!(run_git(&repo, &["merge", "--ff-only", "feature"]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["branch", "-f", "feature", "feature^1"]).0,
+        0
+    );
+    (repo, main)
+}
+
+fn first_parent_subjects(repo: &std::path::Path, branch: &str) -> Vec<String> {
+    let (code, out, err) = run_git(repo, &["log", "--first-parent", "--format=%s", branch]);
+    assert_eq!(code, 0, "git log failed: {err}");
+    out.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn canonicalize_merge_parents_puts_mainline_first() {
+    let (repo, main) = merge_with_mainline_second();
+    let first_parent_subjects = |repo: &std::path::Path| first_parent_subjects(repo, &main);
+    assert_eq!(first_parent_subjects(&repo)[..2], ["merge", "feature work"]);
+    let tree_before = run_git(&repo, &["rev-parse", &format!("{main}^{{tree}}")]).1;
+
+    run_tool_expect_success(&repo, |o| {
+        o.canonicalize_merge_parents = true;
+        o.write_report = true;
+    });
+
+    let subjects = first_parent_subjects(&repo);
+    assert_eq!(subjects[..2], ["merge", "main work"]);
+    assert_eq!(subjects.len(), 3, "unexpected mainline: {subjects:?}");
+    let (_, parents, _) = run_git(&repo, &["log", "-1", "--format=%P", &main]);
+    assert_eq!(parents.split_whitespace().count(), 2);
+    let (_, second, _) = run_git(&repo, &["log", "-1", "--format=%s", &format!("{main}^2")]);
+    assert_eq!(second.trim(), "feature work");
+    let tree_after = run_git(&repo, &["rev-parse", &format!("{main}^{{tree}}")]).1;
+    assert_eq!(tree_before, tree_after, "merge tree must not change");
+
+    let report = fs::read_to_string(repo.join(".git/filter-repo/report.txt")).expect("read report");
+    assert!(
+        report.contains("Merges with parents reordered: 1"),
+        "report: {report}"
+    );
+}
+
+#[test]
+fn canonicalize_merge_parents_runs_without_other_filters() {
+    let (repo, main) = merge_with_mainline_second();
+    run_tool_expect_success(&repo, |o| {
+        o.canonicalize_merge_parents = true;
+    });
+    assert_eq!(
+        first_parent_subjects(&repo, &main)[..2],
+        ["merge", "main work"]
+    );
+}
@@ -29,6 +29,40 @@ fn replace_message_edits_commit_and_tag_messages() {
     assert!(tag_obj.contains("BAR"));
 }
 
+#[test]
+fn replace_tag_message_leaves_commit_messages_alone() {
+    let repo = init_repo();
+    write_file(&repo, "src/a.txt", "x");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "commit with FOO token"]).0,
+        0
+    );
+    assert_eq!(
+        run_git(&repo, &["tag", "-a", "-m", "tag msg FOO", "v2.0"]).0,
+        0
+    );
+    let repl = repo.join("tag-repl.txt");
+    std::fs::write(&repl, "FOO==>BAR\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.replace_tag_message_file = Some(repl.clone());
+        o.no_data = true;
+    });
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%B"]);
+    assert!(
+        msg.contains("commit with FOO token"),
+        "commit changed: {}",
+        msg
+    );
+    let (_c, tag_obj, _e) = run_git(&repo, &["cat-file", "-p", "refs/tags/v2.0"]);
+    assert!(
+        tag_obj.contains("tag msg BAR"),
+        "tag unchanged: {}",
+        tag_obj
+    );
+    assert!(!tag_obj.contains("FOO"));
+}
+
 #[test]
 fn second_run_rewrites_short_hashes_in_messages() {
     let repo = init_repo();
@@ -104,3 +138,417 @@ fn second_run_rewrites_short_hashes_in_messages() {
     assert!(tag_obj.contains(&new_short));
     assert!(!tag_obj.contains(&old_short));
 }
+
+#[test]
+fn update_message_refs_follows_tag_rename() {
+    let repo = init_repo();
+    write_file(&repo, "src/a.txt", "x");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(
+        run_git(
+            &repo,
+            &["commit", "-q", "-m", "Prepare v1.0 with velocity fixes"]
+        )
+        .0,
+        0
+    );
+    assert_eq!(
+        run_git(&repo, &["tag", "-a", "-m", "Release v1.0.", "v1.0"]).0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.tag_rename = Some((b"v".to_vec(), b"release-".to_vec()));
+        o.update_message_refs = true;
+        o.write_report = true;
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%s", "HEAD"]);
+    assert_eq!(msg.trim(), "Prepare release-1.0 with velocity fixes");
+    let (_c, tag_msg, _e) = run_git(
+        &repo,
+        &[
+            "for-each-ref",
+            "--format=%(contents:subject)",
+            "refs/tags/release-1.0",
+        ],
+    );
+    assert_eq!(tag_msg.trim(), "Release release-1.0.");
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Messages updated for renamed refs: 2"),
+        "unexpected report:\n{}",
+        report
+    );
+}
+
+fn commit_file(repo: &std::path::Path, path: &str, contents: &str, msg: &str) -> String {
+    write_file(repo, path, contents);
+    assert_eq!(run_git(repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(repo, &["commit", "-q", "-m", msg]).0, 0);
+    let (_c, oid, _e) = run_git(repo, &["rev-parse", "HEAD"]);
+    oid.trim().to_string()
+}
+
+#[test]
+fn hash_mentions_follow_rewritten_commits() {
+    let repo = init_repo();
+    let old = commit_file(&repo, "src/a.txt", "a\n", "add a");
+    let old_short = old[..7].to_string();
+    commit_file(&repo, "src/a.txt", "", &format!("Revert {}", old_short));
+
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%s"]);
+    let new_short = msg.trim().strip_prefix("Revert ").unwrap().to_string();
+    assert_eq!(new_short.len(), 7);
+    assert_ne!(new_short, old_short);
+    let (_c, resolved, _e) = run_git(&repo, &["rev-parse", &new_short]);
+    let (_c, parent, _e) = run_git(&repo, &["rev-parse", "HEAD~1"]);
+    assert_eq!(resolved.trim(), parent.trim());
+}
+
+#[test]
+fn no_update_hash_mentions_keeps_old_ids() {
+    let repo = init_repo();
+    let old = commit_file(&repo, "src/a.txt", "a\n", "add a");
+    let old_short = old[..7].to_string();
+    commit_file(&repo, "src/a.txt", "", &format!("Revert {}", old_short));
+
+    run_tool_expect_success(&repo, |o| {
+        o.update_hash_mentions = false;
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%s"]);
+    assert_eq!(msg.trim(), format!("Revert {}", old_short));
+}
+
+#[test]
+fn hash_mentions_of_pruned_commits_get_a_note() {
+    let repo = init_repo();
+    commit_file(&repo, "keep/a.txt", "a\n", "add keep");
+    let dropped = commit_file(&repo, "drop/b.txt", "b\n", "add drop");
+    let dropped_short = dropped[..7].to_string();
+    commit_file(
+        &repo,
+        "keep/a.txt",
+        "a2\n",
+        &format!("Follow up on {}", dropped_short),
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"keep".to_vec());
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%B"]);
+    assert!(msg.starts_with(&format!("Follow up on {}", dropped_short)));
+    assert!(
+        msg.contains(&format!(
+            "Note: referenced commit {} was pruned by filter-repo-rs",
+            dropped_short
+        )),
+        "missing pruned note in:\n{}",
+        msg
+    );
+}
+
+#[test]
+fn forward_hash_mentions_are_rewritten_in_second_pass() {
+    let repo = init_repo();
+    let main = current_branch(&repo);
+    assert_eq!(run_git(&repo, &["checkout", "-q", "-b", "later"]).0, 0);
+    // A future committer date makes fast-export emit this commit after the
+    // main-branch commit that mentions it.
+    write_file(&repo, "src/later.txt", "later\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    let status = std::process::Command::new("git")
+        .current_dir(&repo)
+        .env("GIT_COMMITTER_DATE", "2030-01-01T00:00:00")
+        .args(["commit", "-q", "-m", "later commit"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let (_c, later, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let later_short = later.trim()[..7].to_string();
+    assert_eq!(run_git(&repo, &["checkout", "-q", &main]).0, 0);
+    commit_file(
+        &repo,
+        "src/main.txt",
+        "main\n",
+        &format!("See {}", later_short),
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%s", &main]);
+    let new_short = msg.trim().strip_prefix("See ").unwrap().to_string();
+    assert_ne!(new_short, later_short, "forward mention was not rewritten");
+    let (_c, resolved, _e) = run_git(&repo, &["rev-parse", &new_short]);
+    let (_c, later_new, _e) = run_git(&repo, &["rev-parse", "later"]);
+    assert_eq!(resolved.trim(), later_new.trim());
+
+    let commit_map =
+        std::fs::read_to_string(repo.join(".git").join("filter-repo").join("commit-map")).unwrap();
+    assert!(commit_map
+        .lines()
+        .any(|l| l == format!("{} {}", later.trim(), later_new.trim())));
+}
+
+#[test]
+fn wrap_body_reflows_long_body_and_keeps_subject() {
+    let repo = init_repo();
+    write_file(&repo, "src/a.txt", "x");
+    run_git(&repo, &["add", "."]);
+    let subject = format!("Import {}", "very-long-subject-".repeat(6));
+    let body = "This body was imported as a single enormous line that nobody ever wrapped, \
+                so every log view has to scroll sideways to read the explanation of the change.";
+    let message = format!(
+        "{subject}\n\n{body}\n\n```\nlet keep = \"this fenced line is far longer than the wrap width\";\n```\n\nSigned-off-by: Somebody With A Long Name <somebody@example.invalid>\n"
+    );
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", &message]).0, 0);
+    run_tool_expect_success(&repo, |o| {
+        o.wrap_message_body = Some(40);
+        o.no_data = true;
+    });
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%B"]);
+    let mut lines = msg.lines();
+    assert_eq!(lines.next(), Some(subject.as_str()));
+    let body_lines: Vec<&str> = msg.lines().skip(2).take_while(|l| !l.is_empty()).collect();
+    assert!(body_lines.len() > 1, "body should be wrapped: {msg}");
+    assert!(
+        body_lines.iter().all(|l| l.chars().count() <= 40),
+        "body lines should fit 40 columns: {msg}"
+    );
+    assert_eq!(body_lines.join(" "), body);
+    assert!(msg.contains("let keep = \"this fenced line is far longer than the wrap width\";"));
+    assert!(msg.contains("Signed-off-by: Somebody With A Long Name <somebody@example.invalid>"));
+}
+
+#[test]
+fn replace_identity_rewrites_internal_emails() {
+    let repo = init_repo();
+    for (i, who) in [
+        "alice@internal.corp",
+        "bob@internal.corp",
+        "carol@other.org",
+    ]
+    .iter()
+    .enumerate()
+    {
+        write_file(&repo, &format!("f{i}.txt"), who);
+        run_git(&repo, &["add", "."]);
+        let author = format!("--author=Someone <{who}>");
+        let committer = format!("user.email={who}");
+        assert_eq!(
+            run_git(
+                &repo,
+                &["-c", &committer, "commit", "-q", &author, "-m", "change"]
+            )
+            .0,
+            0
+        );
+    }
+    assert_eq!(
+        run_git(
+            &repo,
+            &[
+                "-c",
+                "user.email=rel@internal.corp",
+                "tag",
+                "-a",
+                "-m",
+                "rel",
+                "v1"
+            ]
+        )
+        .0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_identity = vec![(
+            regex::bytes::Regex::new(r"^(.+)@internal\.corp$").unwrap(),
+            "$1@example.com".to_string(),
+        )];
+    });
+
+    let (_c, authors, _e) = run_git(&repo, &["log", "--format=%ae %ce"]);
+    let authors: Vec<&str> = authors.lines().collect();
+    assert_eq!(
+        authors,
+        [
+            "carol@other.org carol@other.org",
+            "bob@example.com bob@example.com",
+            "alice@example.com alice@example.com",
+            "a.u.thor@example.com a.u.thor@example.com",
+        ]
+    );
+    let (_c, tag, _e) = run_git(&repo, &["cat-file", "-p", "v1"]);
+    assert!(tag.contains("<rel@example.com>"), "{tag}");
+}
+
+#[test]
+fn max_commit_message_size_truncates_keeping_subject() {
+    let repo = init_repo();
+    write_file(&repo, "big.txt", "x");
+    run_git(&repo, &["add", "."]);
+    let subject = "Import the vendored parser";
+    let body = "generated changelog line\n".repeat(200);
+    let message = format!("{subject}\n\n{body}");
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", &message]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", &message, "v1"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.max_commit_message_bytes = Some(120);
+    });
+
+    let (_c, commit, _e) = run_git(&repo, &["cat-file", "commit", "HEAD"]);
+    let (_hdr, msg) = commit.split_once("\n\n").unwrap();
+    assert!(msg.len() <= 120, "{} bytes: {msg:?}", msg.len());
+    assert!(msg.starts_with(&format!("{subject}\n\ngenerated changelog line\n")));
+    assert!(msg.ends_with("\n...\n"), "{msg:?}");
+    let (_c, tag, _e) = run_git(&repo, &["cat-file", "tag", "v1"]);
+    let (_hdr, tag_msg) = tag.split_once("\n\n").unwrap();
+    assert!(tag_msg.len() <= 120, "{} bytes", tag_msg.len());
+    assert!(tag_msg.starts_with(subject) && tag_msg.ends_with("\n...\n"));
+
+    let (_c, first, _e) = run_git(&repo, &["log", "-1", "--format=%B", "HEAD~1"]);
+    assert_eq!(first.trim(), "init commit");
+}
+
+fn commit_message(repo: &std::path::Path, rev: &str) -> String {
+    let (_c, commit, _e) = run_git(repo, &["cat-file", "commit", rev]);
+    commit.split_once("\n\n").unwrap().1.to_string()
+}
+
+#[test]
+fn message_annotate_adds_trailer_to_rewritten_commits_only() {
+    let repo = init_repo();
+    let untouched = commit_file(&repo, "a.txt", "a", "Add a");
+    let secret = commit_file(
+        &repo,
+        "b.txt",
+        "b",
+        "Add b with SECRET\n\nSigned-off-by: Dev <dev@example.com>",
+    );
+    write_file(&repo, "c.txt", "c");
+    run_git(&repo, &["add", "."]);
+    // A message without a trailing newline
+    std::fs::write(repo.join("msg.txt"), "Add c").unwrap();
+    assert_eq!(
+        run_git(
+            &repo,
+            &["commit", "-q", "--cleanup=verbatim", "-F", "msg.txt"]
+        )
+        .0,
+        0
+    );
+    let (_c, child, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let repl = repo.join("repl.txt");
+    std::fs::write(&repl, "SECRET==>REDACTED\n").unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_message_file = Some(repl.clone());
+        o.message_annotate = Some(b"Rewritten-By: filter-repo-rs (original: {old_oid})".to_vec());
+        o.write_report = true;
+    });
+
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD~2"]);
+    assert_eq!(head.trim(), untouched);
+    assert_eq!(commit_message(&repo, "HEAD~2"), "Add a\n");
+    assert_eq!(
+        commit_message(&repo, "HEAD~1"),
+        format!(
+            "Add b with REDACTED\n\nSigned-off-by: Dev <dev@example.com>\n\
+             Rewritten-By: filter-repo-rs (original: {secret})\n"
+        )
+    );
+    // Only remapped through its parent, and stored without a final newline
+    assert_eq!(
+        commit_message(&repo, "HEAD"),
+        format!(
+            "Add c\n\nRewritten-By: filter-repo-rs (original: {})\n",
+            child.trim()
+        )
+    );
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Commit messages annotated: 2\n"),
+        "{report}"
+    );
+}
+
+#[test]
+fn message_annotate_all_does_not_repeat_its_trailer() {
+    let repo = init_repo();
+    commit_file(&repo, "a.txt", "a", "Add a");
+    let annotate = |o: &mut filter_repo_rs::Options| {
+        o.message_annotate = Some(b"Rewritten-By: filter-repo-rs ({new_mark})".to_vec());
+        o.message_annotate_all = true;
+    };
+
+    run_tool_expect_success(&repo, annotate);
+    let once = commit_message(&repo, "HEAD");
+    assert!(
+        once.starts_with("Add a\n\nRewritten-By: filter-repo-rs (:"),
+        "{once:?}"
+    );
+    let (_c, first_run, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, annotate);
+
+    assert_eq!(commit_message(&repo, "HEAD"), once);
+    let (_c, second_run, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(second_run, first_run);
+}
+
+#[test]
+fn strip_trailer_removes_only_trailer_lines() {
+    use filter_repo_rs::TrailerPattern;
+    let repo = init_repo();
+    commit_file(
+        &repo,
+        "a.txt",
+        "a",
+        "Fix the parser\n\n\
+         Reviewed-on: mentions in the body are kept, as is\n\
+         Reviewed-on: https://review.example.com/1 quoted here.\n\n\
+         Reviewed-on: https://review.example.com/2\n\
+         Signed-off-by: Dev <dev@example.com>",
+    );
+    commit_file(
+        &repo,
+        "b.txt",
+        "b",
+        "Add b\n\nKeep this paragraph.\n\nChange-Id: I0123abcd",
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_trailers = vec![
+            TrailerPattern::Key(b"reviewed-on".to_vec()),
+            TrailerPattern::Regex(regex::bytes::Regex::new("^Change-Id: I[0-9a-f]+$").unwrap()),
+        ];
+        o.write_report = true;
+    });
+
+    assert_eq!(
+        commit_message(&repo, "HEAD~1"),
+        "Fix the parser\n\n\
+         Reviewed-on: mentions in the body are kept, as is\n\
+         Reviewed-on: https://review.example.com/1 quoted here.\n\n\
+         Signed-off-by: Dev <dev@example.com>\n"
+    );
+    assert_eq!(
+        commit_message(&repo, "HEAD"),
+        "Add b\n\nKeep this paragraph.\n"
+    );
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Trailers stripped: 2\n  1 Change-Id\n  1 Reviewed-on\n"),
+        "{report}"
+    );
+}
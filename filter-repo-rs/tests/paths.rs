@@ -63,6 +63,73 @@ fn path_rename_applies_to_paths() {
     );
 }
 
+#[test]
+fn rewrite_gitmodules_follows_path_renames() {
+    let repo = init_repo();
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(
+        &repo,
+        ".gitmodules",
+        "[submodule \"libs/foo\"]\n\tpath = libs/foo\n\turl = https://example.com/foo.git\n",
+    );
+    let cacheinfo = format!("160000,{},libs/foo", head.trim());
+    assert_eq!(
+        run_git(&repo, &["update-index", "--add", "--cacheinfo", &cacheinfo]).0,
+        0
+    );
+    run_git(&repo, &["add", ".gitmodules"]);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "add submodule"]).0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames
+            .push((b"libs/".to_vec(), b"vendor/".to_vec()));
+        o.rewrite_gitmodules = true;
+    });
+
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "HEAD"]);
+    assert!(tree.contains("160000 commit"), "{tree}");
+    assert!(tree.contains("\tvendor/foo\n"), "{tree}");
+    let (_c, modules, _e) = run_git(&repo, &["show", "HEAD:.gitmodules"]);
+    assert_eq!(
+        modules,
+        "[submodule \"libs/foo\"]\n\tpath = vendor/foo\n\turl = https://example.com/foo.git\n"
+    );
+}
+
+#[test]
+fn rewrite_gitmodules_leaves_other_files_quoting_a_submodule_section() {
+    let repo = init_repo();
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let modules = "[submodule \"x\"]\n\tpath = libs/x\n\turl = ../libs/x.git\n";
+    let doc = format!("Example .gitmodules:\n\n{modules}");
+    write_file(&repo, ".gitmodules", modules);
+    write_file(&repo, "docs/submodules.md", &doc);
+    let cacheinfo = format!("160000,{},libs/x", head.trim());
+    assert_eq!(
+        run_git(&repo, &["update-index", "--add", "--cacheinfo", &cacheinfo]).0,
+        0
+    );
+    run_git(&repo, &["add", ".gitmodules", "docs/submodules.md"]);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "add submodule docs"]).0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames
+            .push((b"libs/".to_vec(), b"vendor/".to_vec()));
+        o.rewrite_gitmodules = true;
+    });
+
+    let (_c, rewritten, _e) = run_git(&repo, &["show", "HEAD:.gitmodules"]);
+    assert!(rewritten.contains("path = vendor/x"), "{rewritten}");
+    let (_c, kept, _e) = run_git(&repo, &["show", "HEAD:docs/submodules.md"]);
+    assert_eq!(kept, doc);
+}
+
 #[test]
 fn path_glob_selects_md_under_src() {
     let repo = init_repo();
@@ -356,5 +423,401 @@ fn windows_path_policy_sanitizes_or_preserves_bytes() {
         } else {
             assert_eq!(sanitized, input.to_vec());
         }
+        // An explicit --windows-path-policy sanitizes on every platform
+        assert_eq!(
+            fr::pathutil::windows_safe_path(input),
+            expected_windows.to_vec()
+        );
+    }
+    for (input, expected) in [
+        (b"aux/readme.md".as_ref(), b"aux_/readme.md".as_ref()),
+        (b"src/NUL.c".as_ref(), b"src/NUL_.c".as_ref()),
+        (
+            b"com10/lpt1.tar.gz".as_ref(),
+            b"com10/lpt1_.tar.gz".as_ref(),
+        ),
+        (b"docs./auxiliary".as_ref(), b"docs/auxiliary".as_ref()),
+    ] {
+        assert_eq!(fr::pathutil::windows_safe_path(input), expected.to_vec());
+    }
+}
+
+fn repo_with_tools_dir() -> std::path::PathBuf {
+    let repo = init_repo();
+    write_file(&repo, "tools/build.sh", "b");
+    write_file(&repo, "tools/aux_/keep.txt", "k");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "tools"]).0, 0);
+    repo
+}
+
+#[test]
+fn windows_path_policy_applies_to_renamed_paths() {
+    let repo = repo_with_tools_dir();
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames = vec![(b"tools/build.sh".to_vec(), b"aux.sh.".to_vec())];
+        o.windows_path_policy = Some(fr::WindowsPathPolicy::Sanitize);
+        o.write_report = true;
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.lines().any(|l| l == "aux_.sh"), "{tree}");
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Paths sanitized for Windows: 1"),
+        "{report}"
+    );
+    assert!(report.contains("  aux.sh. -> aux_.sh"), "{report}");
+}
+
+#[test]
+fn windows_path_policy_rejects_sanitized_collisions() {
+    let repo = repo_with_tools_dir();
+    let err = run_tool(&repo, |o| {
+        o.path_renames = vec![(b"tools/build.sh".to_vec(), b"tools/aux_/keep.txt.".to_vec())];
+        o.windows_path_policy = Some(fr::WindowsPathPolicy::Sanitize);
+    })
+    .expect_err("the renamed file sanitizes onto an existing one");
+    assert!(
+        err.to_string()
+            .contains("both become 'tools/aux_/keep.txt' for Windows"),
+        "{err}"
+    );
+}
+
+#[test]
+fn windows_path_policy_skip_and_error() {
+    let repo = repo_with_tools_dir();
+    let err = run_tool(&repo, |o| {
+        o.path_renames = vec![(b"tools/build.sh".to_vec(), b"con".to_vec())];
+        o.windows_path_policy = Some(fr::WindowsPathPolicy::Error);
+    })
+    .expect_err("con is reserved on Windows");
+    assert!(err.to_string().contains("'con'"), "{err}");
+
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames = vec![(b"tools/build.sh".to_vec(), b"con".to_vec())];
+        o.windows_path_policy = Some(fr::WindowsPathPolicy::Skip);
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(!tree.contains("build.sh") && !tree.lines().any(|l| l == "con"));
+    assert!(tree.contains("tools/aux_/keep.txt"), "{tree}");
+}
+
+fn repo_with_colliding_renames() -> std::path::PathBuf {
+    let repo = init_repo();
+    write_file(&repo, "a.txt", "from a");
+    write_file(&repo, "b.txt", "from b");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add a and b"]).0, 0);
+    repo
+}
+
+fn rename_both_to_c(o: &mut fr::Options, policy: fr::CollisionPolicy) {
+    o.path_renames = vec![
+        (b"a.txt".to_vec(), b"c.txt".to_vec()),
+        (b"b.txt".to_vec(), b"c.txt".to_vec()),
+    ];
+    o.on_rename_collision = policy;
+    o.write_report = true;
+}
+
+#[test]
+fn rename_collision_policies() {
+    let repo = repo_with_colliding_renames();
+    let err = run_tool(&repo, |o| rename_both_to_c(o, fr::CollisionPolicy::Error))
+        .expect_err("a.txt and b.txt both become c.txt");
+    assert_eq!(err.code(), "RENAME_COLLISION");
+    assert!(
+        err.to_string()
+            .contains("'a.txt' and 'b.txt' both rename to 'c.txt'"),
+        "{err}"
+    );
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert_eq!(
+        tree, "README.md\na.txt\nb.txt\n",
+        "the failed run must not rewrite history"
+    );
+
+    for (policy, kept, dropped) in [
+        (fr::CollisionPolicy::KeepFirst, "a", "b"),
+        (fr::CollisionPolicy::KeepLast, "b", "a"),
+    ] {
+        let repo = repo_with_colliding_renames();
+        run_tool_expect_success(&repo, |o| rename_both_to_c(o, policy));
+        let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+        assert_eq!(tree, "README.md\nc.txt\n", "{policy:?}");
+        let (_c, content, _e) = run_git(&repo, &["show", "HEAD:c.txt"]);
+        assert_eq!(content, format!("from {kept}"), "{policy:?}");
+        let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+        assert!(
+            report.contains(&format!("Rename collisions resolved ({policy:?}): 1")),
+            "{report}"
+        );
+        assert!(
+            report.contains(&format!("  c.txt: kept {kept}.txt, dropped {dropped}.txt")),
+            "{report}"
+        );
+    }
+}
+
+#[test]
+fn path_callback_drops_renames_and_keeps_in_one_hook() {
+    let repo = init_repo();
+    write_file(&repo, "scratch.tmp", "t");
+    write_file(&repo, "old/nested/file.txt", "o");
+    write_file(&repo, "old/cache.tmp", "c");
+    write_file(&repo, "src/main.rs", "fn main() {}");
+    write_file(&repo, "old dir/space name.txt", "s");
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add files"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.path_callback = Some(fr::PathCallback::new(|path: &[u8]| {
+            if path.ends_with(b".tmp") {
+                fr::PathDecision::Drop
+            } else if let Some(rest) = path.strip_prefix(b"old/".as_slice()) {
+                let mut renamed = b"new/".to_vec();
+                renamed.extend_from_slice(rest);
+                fr::PathDecision::Rename(renamed)
+            } else if let Some(rest) = path.strip_prefix(b"old dir/".as_slice()) {
+                let mut renamed = b"new dir/".to_vec();
+                renamed.extend_from_slice(rest);
+                fr::PathDecision::Rename(renamed)
+            } else {
+                fr::PathDecision::Keep
+            }
+        }));
+    });
+
+    let (_c, tree, _e) = run_git(
+        &repo,
+        &[
+            "-c",
+            "core.quotepath=false",
+            "ls-tree",
+            "-r",
+            "--name-only",
+            "HEAD",
+        ],
+    );
+    let files: Vec<&str> = tree.lines().collect();
+    assert!(files.contains(&"README.md"), "tree: {}", tree);
+    assert!(files.contains(&"src/main.rs"), "tree: {}", tree);
+    assert!(files.contains(&"new/nested/file.txt"), "tree: {}", tree);
+    assert!(files.contains(&"new dir/space name.txt"), "tree: {}", tree);
+    assert!(!files.iter().any(|f| f.ends_with(".tmp")), "tree: {}", tree);
+    assert!(
+        !files.iter().any(|f| f.starts_with("old")),
+        "tree: {}",
+        tree
+    );
+}
+
+#[test]
+fn ignore_file_drops_matches_and_honours_negation() {
+    let repo = init_repo();
+    write_file(&repo, "build.log", "b");
+    write_file(&repo, "logs/debug.log", "d");
+    write_file(&repo, "keep.log", "k");
+    write_file(&repo, "src/main.rs", "fn main() {}");
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add logs"]).0, 0);
+    let ignore = repo.join(".git").join("filterrepoignore");
+    std::fs::write(&ignore, "# logs are noise\n*.log\n!keep.log\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.ignore_file = Some(ignore.clone());
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    let logs: Vec<&str> = tree.lines().filter(|p| p.ends_with(".log")).collect();
+    assert_eq!(logs, vec!["keep.log"], "tree: {}", tree);
+    assert!(tree.contains("src/main.rs"), "tree: {}", tree);
+}
+
+#[test]
+fn ignore_file_cannot_reinclude_inside_excluded_directory() {
+    let repo = init_repo();
+    write_file(&repo, "vendor/lib.rs", "v");
+    write_file(&repo, "vendor/LICENSE", "l");
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "vendor"]).0, 0);
+    let ignore = repo.join(".git").join("filterrepoignore");
+    std::fs::write(&ignore, "vendor/\n!vendor/LICENSE\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.ignore_file = Some(ignore.clone());
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(!tree.contains("vendor/"), "tree: {}", tree);
+}
+
+#[test]
+fn strip_paths_from_file_removes_exact_paths_only() {
+    let repo = init_repo();
+    write_file(&repo, "third party/COPYING notice.txt", "gpl");
+    write_file(&repo, "vendor/lib", "stripped");
+    write_file(&repo, "vendor/lib.c", "kept");
+    write_file(&repo, "docs/a b.md", "stripped too");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "vendored"]).0, 0);
+    write_file(&repo, "third party/COPYING notice.txt", "gpl v3");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "bump notice"]).0, 0);
+    let list = repo.join("strip-paths.txt");
+    std::fs::write(
+        &list,
+        "third party/COPYING notice.txt\nvendor/lib\n\"docs/a\\040b.md\"\n",
+    )
+    .unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_paths_from_file = Some(list.clone());
+        o.write_report = true;
+    });
+
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    let files: Vec<&str> = tree.lines().collect();
+    assert_eq!(files, vec!["README.md", "vendor/lib.c"], "tree: {tree}");
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s"]);
+    assert!(
+        !log.contains("bump notice"),
+        "commit touching only stripped paths is pruned: {log}"
+    );
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains(
+            "Filechanges removed by --strip-paths-from-file: 4\n  \
+             2 third party/COPYING notice.txt\n  1 docs/a b.md\n  1 vendor/lib\n"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+fn strip_file_modes_drops_executables_under_glob_only() {
+    let repo = init_repo();
+    write_file(&repo, "scripts/legacy/build.sh", "#!/bin/sh\nmake\n");
+    write_file(&repo, "scripts/legacy/notes.txt", "#!/bin/sh\nmake\n");
+    write_file(&repo, "tools/build.sh", "#!/bin/sh\nmake\n");
+    write_file(&repo, "tools/run.sh", "#!/bin/sh\nrun\n");
+    run_git(&repo, &["add", "."]);
+    for path in ["scripts/legacy/build.sh", "tools/run.sh"] {
+        assert_eq!(run_git(&repo, &["update-index", "--chmod=+x", path]).0, 0);
     }
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "scripts"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_file_modes = vec![b"100755".to_vec()];
+        o.strip_file_modes_globs = vec![b"scripts/legacy/**".to_vec()];
+        o.write_report = true;
+    });
+
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "HEAD"]);
+    let entries: Vec<(&str, &str)> = tree
+        .lines()
+        .map(|l| (&l[..6], l.rsplit('\t').next().unwrap()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("100644", "README.md"),
+            ("100644", "scripts/legacy/notes.txt"),
+            ("100644", "tools/build.sh"),
+            ("100755", "tools/run.sh"),
+        ],
+        "tree: {tree}"
+    );
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Filechanges stripped by mode: 1\n  scripts/legacy/build.sh\n"),
+        "{report}"
+    );
+}
+
+#[test]
+fn strip_file_modes_drops_gitlinks_and_their_gitmodules_entries() {
+    let repo = init_repo();
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(
+        &repo,
+        ".gitmodules",
+        "[submodule \"libs/foo\"]\n\tpath = libs/foo\n\turl = https://example.com/foo.git\n\
+         [submodule \"ext/bar\"]\n\tpath = ext/bar\n\turl = https://example.com/bar.git\n",
+    );
+    for path in ["libs/foo", "ext/bar"] {
+        let cacheinfo = format!("160000,{},{}", head.trim(), path);
+        assert_eq!(
+            run_git(&repo, &["update-index", "--add", "--cacheinfo", &cacheinfo]).0,
+            0
+        );
+    }
+    run_git(&repo, &["add", ".gitmodules"]);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "add submodules"]).0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_file_modes = vec![b"160000".to_vec()];
+        o.strip_file_modes_globs = vec![b"libs/**".to_vec()];
+        o.rewrite_gitmodules = true;
+    });
+
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    let files: Vec<&str> = tree.lines().collect();
+    assert_eq!(
+        files,
+        vec![".gitmodules", "README.md", "ext/bar"],
+        "tree: {tree}"
+    );
+    let (_c, modules, _e) = run_git(&repo, &["show", "HEAD:.gitmodules"]);
+    assert_eq!(
+        modules,
+        "[submodule \"ext/bar\"]\n\tpath = ext/bar\n\turl = https://example.com/bar.git\n"
+    );
+}
+
+#[test]
+fn prune_stale_attribute_files_drops_orphaned_dotfiles() {
+    let setup = || {
+        let repo = init_repo();
+        write_file(&repo, ".gitignore", "target/\n");
+        write_file(&repo, "vendor/.gitignore", "*.min.js\n");
+        write_file(&repo, "vendor/a.js", "a\n");
+        write_file(&repo, "vendor/lib/b.js", "b\n");
+        write_file(&repo, "docs/.gitattributes", "*.md text\n");
+        write_file(&repo, "docs/guide.md", "guide\n");
+        run_git(&repo, &["add", "."]);
+        assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add vendor"]).0, 0);
+        repo
+    };
+    let filter = |o: &mut fr::Options| {
+        o.path_globs.push(b"vendor/**/*.js".to_vec());
+        o.invert_paths = true;
+        o.write_report = true;
+    };
+
+    let repo = setup();
+    run_tool_expect_success(&repo, filter);
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(tree.contains("vendor/.gitignore"), "{tree}");
+
+    let repo = setup();
+    run_tool_expect_success(&repo, |o| {
+        filter(o);
+        o.prune_stale_attribute_files = true;
+    });
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    let files: Vec<&str> = tree.lines().collect();
+    assert_eq!(
+        files,
+        [
+            ".gitignore",
+            "README.md",
+            "docs/.gitattributes",
+            "docs/guide.md"
+        ],
+        "{tree}"
+    );
+    let report =
+        std::fs::read_to_string(repo.join(".git").join("filter-repo").join("report.txt")).unwrap();
+    assert!(report.contains("Attribute files pruned: 1\n"), "{report}");
 }
@@ -170,3 +170,84 @@ fn performance_batch_vs_individual_optimization() {
     assert!(d1 > std::time::Duration::from_micros(0));
     assert!(d2 > std::time::Duration::from_micros(0));
 }
+
+// Create `count` tags on HEAD in two processes: even ones annotated (via
+// fast-import), odd ones lightweight (via update-ref --stdin).
+fn create_many_tags(repo: &std::path::Path, count: usize) {
+    use std::io::Write as _;
+    use std::process::{Command, Stdio};
+    let (_c, head, _e) = run_git(repo, &["rev-parse", "HEAD"]);
+    let head = head.trim();
+    let mut stream = String::new();
+    let mut updates = String::new();
+    for i in 0..count {
+        if i % 2 == 0 {
+            let msg = format!("tag v{i}\n");
+            stream.push_str(&format!(
+                "tag v{i}\nfrom {head}\ntagger A U Thor <a.u.thor@example.com> 1700000000 +0000\ndata {}\n{msg}\n",
+                msg.len()
+            ));
+        } else {
+            updates.push_str(&format!("create refs/tags/v{i} {head}\n"));
+        }
+    }
+    for (args, input) in [
+        (vec!["fast-import", "--quiet"], stream),
+        (vec!["update-ref", "--stdin"], updates),
+    ] {
+        let mut child = Command::new("git")
+            .current_dir(repo)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        assert!(child.wait().unwrap().success(), "git {:?} failed", args);
+    }
+}
+
+#[test]
+fn performance_tag_rename_does_not_spawn_git_per_tag() {
+    let repo = init_repo();
+    let tag_count = 1000;
+    create_many_tags(&repo, tag_count);
+
+    let start = std::time::Instant::now();
+    let (output, invocations) =
+        run_cli_with_git_spy(&repo, &["--force", "--tag-rename", "v:release-"]);
+    let elapsed = start.elapsed();
+    assert!(
+        output.status.success(),
+        "tag rename failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        elapsed < std::time::Duration::from_secs(60),
+        "renaming {tag_count} tags took {elapsed:?}"
+    );
+    let commands = git_commands_for_repo(&repo, &invocations);
+    assert!(
+        commands.len() < 50,
+        "git spawned {} times for {tag_count} tags",
+        commands.len()
+    );
+
+    let (_c, tags, _e) = run_git(&repo, &["tag", "-l"]);
+    let tags: Vec<&str> = tags.lines().collect();
+    assert_eq!(tags.len(), tag_count);
+    assert!(tags.iter().all(|t| t.starts_with("release-")));
+    let (_c, tag_obj, _e) = run_git(&repo, &["cat-file", "-p", "refs/tags/release-0"]);
+    assert!(tag_obj.contains("\ntag release-0\n"), "{tag_obj}");
+    assert!(
+        tag_obj.contains("tagger A U Thor <a.u.thor@example.com> 1700000000 +0000"),
+        "{tag_obj}"
+    );
+    assert!(tag_obj.ends_with("\ntag v0\n"), "{tag_obj}");
+    let (_c, typ, _e) = run_git(&repo, &["cat-file", "-t", "refs/tags/release-1"]);
+    assert_eq!(typ.trim(), "commit");
+}
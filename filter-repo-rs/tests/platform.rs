@@ -298,3 +298,69 @@ fn windows_safe_defaults_match_git_expectations() {
     let repo = init_repo();
     assert_safe_fast_export_defaults(&repo);
 }
+
+#[test]
+fn fe_and_fi_args_reach_only_their_process() {
+    let repo = init_repo();
+    let (output, invocations) = run_cli_with_git_spy(
+        &repo,
+        &[
+            "--force",
+            "--fe-arg",
+            "--signed-tags=warn-strip",
+            "--fi-arg",
+            "--max-pack-size=1g",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let commands = git_commands_for_repo(&repo, &invocations);
+    let fast_export = find_git_command(&commands, "fast-export").expect("fast-export ran");
+    let fast_import = find_git_command(&commands, "fast-import").expect("fast-import ran");
+    let count = |cmd: &[String], arg: &str| cmd.iter().filter(|a| *a == arg).count();
+    assert_eq!(
+        count(fast_export, "--signed-tags=warn-strip"),
+        1,
+        "{fast_export:?}"
+    );
+    assert_eq!(
+        count(fast_export, "--max-pack-size=1g"),
+        0,
+        "{fast_export:?}"
+    );
+    assert_eq!(
+        count(fast_import, "--max-pack-size=1g"),
+        1,
+        "{fast_import:?}"
+    );
+    assert_eq!(
+        count(fast_import, "--signed-tags=warn-strip"),
+        0,
+        "{fast_import:?}"
+    );
+}
+
+#[test]
+fn fe_and_fi_args_reject_managed_flags() {
+    let repo = init_repo();
+    for (flag, arg) in [
+        ("--fe-arg", "--export-marks=/tmp/marks"),
+        ("--fe-arg", "--import-marks"),
+        ("--fi-arg", "--quiet"),
+        ("--fi-arg", "--export-marks=/tmp/marks"),
+    ] {
+        let output = cli_command()
+            .current_dir(&repo)
+            .args(["--force", "--path", "README.md", flag, arg])
+            .output()
+            .expect("run filter-repo-rs");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(2), "{flag} {arg}: {stderr}");
+        assert!(stderr.contains("cannot be passed through"), "{stderr}");
+    }
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s"]);
+    assert_eq!(log.trim(), "init commit");
+}
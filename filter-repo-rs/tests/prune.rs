@@ -0,0 +1,350 @@
+mod common;
+use common::*;
+
+fn commit_map_entry(repo: &std::path::Path, old: &str) -> Option<String> {
+    let map = std::fs::read_to_string(repo.join(".git").join("filter-repo").join("commit-map"))
+        .expect("commit-map should exist");
+    map.lines().find_map(|l| {
+        let mut parts = l.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some(o), Some(n)) if o == old => Some(n.to_string()),
+            _ => None,
+        }
+    })
+}
+
+const NULL_OID: &str = "0000000000000000000000000000000000000000";
+
+#[test]
+fn originally_empty_commit_is_pruned_by_default() {
+    let repo = init_repo();
+    assert_eq!(
+        run_git(&repo, &["commit", "--allow-empty", "-q", "-m", "empty"]).0,
+        0
+    );
+    let (_c, empty, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"README.md".to_vec());
+    });
+    assert_eq!(
+        commit_map_entry(&repo, empty.trim()).as_deref(),
+        Some(NULL_OID)
+    );
+}
+
+#[test]
+fn prune_empty_auto_keeps_originally_empty_commit() {
+    let repo = init_repo();
+    assert_eq!(
+        run_git(&repo, &["commit", "--allow-empty", "-q", "-m", "empty"]).0,
+        0
+    );
+    let (_c, empty, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"README.md".to_vec());
+        o.prune_empty = filter_repo_rs::opts::PruneEmpty::Auto;
+    });
+    let new_ = commit_map_entry(&repo, empty.trim()).expect("empty commit mapped");
+    assert_ne!(new_, NULL_OID);
+}
+
+#[test]
+fn prune_empty_never_keeps_commits_emptied_by_filtering() {
+    let repo = init_repo();
+    write_file(&repo, "other.txt", "other\n");
+    assert_eq!(run_git(&repo, &["add", "other.txt"]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "other"]).0, 0);
+    let (_c, other, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.paths.push(b"README.md".to_vec());
+        o.prune_empty = filter_repo_rs::opts::PruneEmpty::Never;
+    });
+    let new_ = commit_map_entry(&repo, other.trim()).expect("commit mapped");
+    assert_ne!(new_, NULL_OID);
+}
+
+#[test]
+fn report_lists_pruned_commits_with_subjects() {
+    let repo = init_repo();
+    assert_eq!(
+        run_git(
+            &repo,
+            &["commit", "--allow-empty", "-q", "-m", "nothing here"]
+        )
+        .0,
+        0
+    );
+    let (_c, empty, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "kept.txt", "kept\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add kept"]).0, 0);
+    run_tool_expect_success(&repo, |o| {
+        o.prune_empty = filter_repo_rs::opts::PruneEmpty::Always;
+        o.write_report = true;
+    });
+    assert_eq!(
+        commit_map_entry(&repo, empty.trim()).as_deref(),
+        Some(NULL_OID)
+    );
+    let report =
+        std::fs::read_to_string(repo.join(".git").join("filter-repo").join("report.txt")).unwrap();
+    assert!(
+        report.contains(&format!(
+            "Commits pruned: 1\n  {} nothing here\n",
+            empty.trim()
+        )),
+        "{report}"
+    );
+}
+
+#[test]
+fn strip_empty_commits_in_range_only_prunes_inside_range() {
+    let repo = init_repo();
+    assert_eq!(
+        run_git(&repo, &["commit", "--allow-empty", "-q", "-m", "old empty"]).0,
+        0
+    );
+    let (_c, outside, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    write_file(&repo, "a.txt", "a\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add a"]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["commit", "--allow-empty", "-q", "-m", "new empty"]).0,
+        0
+    );
+    let (_c, inside, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "b.txt", "b\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add b"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.prune_empty = filter_repo_rs::opts::PruneEmpty::Auto;
+        o.prune_empty_in_range = Some("HEAD~3..HEAD".to_string());
+    });
+
+    let outside_new = commit_map_entry(&repo, outside.trim()).expect("outside commit mapped");
+    assert_ne!(outside_new, NULL_OID);
+    assert_eq!(
+        commit_map_entry(&repo, inside.trim()).as_deref(),
+        Some(NULL_OID)
+    );
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s"]);
+    assert!(log.contains("old empty"));
+    assert!(!log.contains("new empty"));
+}
+
+#[test]
+fn strip_empty_commits_in_range_rejects_bad_range() {
+    let repo = init_repo();
+    let result = run_tool(&repo, |o| {
+        o.prune_empty_in_range = Some("no-such-ref..HEAD".to_string());
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn strip_commits_by_author_folds_changes_into_children() {
+    let repo = init_repo();
+    write_file(&repo, "vendor.txt", "contractor work\n");
+    write_file(&repo, "README.md", "edited by contractor\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(
+        run_git(
+            &repo,
+            &[
+                "commit",
+                "-q",
+                "-m",
+                "contractor",
+                "--author",
+                "Contractor <dev@contractor.example>",
+            ],
+        )
+        .0,
+        0
+    );
+    let (_c, stripped, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "main.txt", "follow-up\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "follow-up"]).0, 0);
+    let (_c, tree_before, _e) = run_git(&repo, &["rev-parse", "HEAD^{tree}"]);
+
+    run_tool_expect_success(&repo, |o| {
+        o.strip_commits_by_author =
+            vec![regex::bytes::Regex::new(r"^dev@contractor\.example$").unwrap()];
+    });
+
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s %ae"]);
+    assert_eq!(
+        log,
+        "follow-up a.u.thor@example.com\ninit commit a.u.thor@example.com\n"
+    );
+    let (_c, tree_after, _e) = run_git(&repo, &["rev-parse", "HEAD^{tree}"]);
+    assert_eq!(tree_after, tree_before);
+    assert_eq!(
+        commit_map_entry(&repo, stripped.trim()).as_deref(),
+        Some(NULL_OID)
+    );
+}
+
+#[test]
+fn drop_empty_commits_message_keeps_tombstone_commit() {
+    let repo = init_repo();
+    write_file(&repo, "secrets/key.pem", "private\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add key"]).0, 0);
+    let (_c, emptied, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "src/lib.rs", "fn main() {}\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add lib"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.paths = vec![b"secrets/".to_vec()];
+        o.invert_paths = true;
+        o.empty_commit_tombstone = Some(b"[filtered: content removed]\n".to_vec());
+    });
+
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s"]);
+    assert_eq!(log, "add lib\n[filtered: content removed]\ninit commit\n");
+    let new_ = commit_map_entry(&repo, emptied.trim()).expect("tombstone mapped");
+    assert_ne!(new_, NULL_OID);
+    let (_c, files, _e) = run_git(&repo, &["show", "--name-only", "--format=", &new_]);
+    assert_eq!(files.trim(), "");
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(!tree.contains("secrets/"), "{tree}");
+}
+
+// Commit everything staged with author and committer date `epoch`.
+fn commit_at(repo: &std::path::Path, message: &str, epoch: i64) {
+    assert_eq!(run_git(repo, &["add", "."]).0, 0);
+    let date = format!("{epoch} +0000");
+    let status = std::process::Command::new("git")
+        .current_dir(repo)
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .args(["commit", "-q", "-m", message])
+        .status()
+        .expect("run git commit");
+    assert!(status.success());
+}
+
+#[test]
+fn commit_date_window_filters_only_commits_inside_it() {
+    const OLD: i64 = 1_600_000_000;
+    const WINDOW: i64 = 1_650_000_000;
+    let repo = init_repo();
+    write_file(&repo, "old.txt", "SECRET one\n");
+    commit_at(&repo, "old", OLD);
+    write_file(&repo, "mid.txt", "SECRET two\n");
+    commit_at(&repo, "mid", WINDOW);
+    // Backdated (e.g. cherry-picked) commit on top, outside the window again
+    write_file(&repo, "new.txt", "SECRET three\n");
+    commit_at(&repo, "new", OLD);
+    let (_c, old_before, _e) = run_git(&repo, &["rev-parse", "HEAD~2"]);
+    let rules = repo.join("rules.txt");
+    std::fs::write(&rules, "SECRET==>REDACTED\n").unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(rules.clone());
+        o.commit_date_from = Some(WINDOW - 60);
+        o.commit_date_to = Some(WINDOW + 60);
+    });
+
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s"]);
+    assert_eq!(
+        log.lines().take(3).collect::<Vec<_>>(),
+        ["new", "mid", "old"]
+    );
+    let (_c, old_after, _e) = run_git(&repo, &["rev-parse", "HEAD~2"]);
+    assert_eq!(
+        old_after, old_before,
+        "commits before the window keep their ids"
+    );
+    let (_c, old, _e) = run_git(&repo, &["show", "HEAD:old.txt"]);
+    assert_eq!(old, "SECRET one\n");
+    let (_c, mid, _e) = run_git(&repo, &["show", "HEAD:mid.txt"]);
+    assert_eq!(mid, "REDACTED two\n");
+    let (_c, new, _e) = run_git(&repo, &["show", "HEAD:new.txt"]);
+    assert_eq!(new, "SECRET three\n");
+}
+
+// Commit everything staged as `author` with author and committer date `epoch`.
+fn commit_as(repo: &std::path::Path, author: &str, message: &str, epoch: i64) {
+    assert_eq!(run_git(repo, &["add", "."]).0, 0);
+    let date = format!("{epoch} +0000");
+    let status = std::process::Command::new("git")
+        .current_dir(repo)
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .args(["commit", "-q", "-m", message, "--author", author])
+        .status()
+        .expect("run git commit");
+    assert!(status.success());
+}
+
+#[test]
+fn squash_consecutive_by_author_folds_bot_runs() {
+    const T: i64 = 1_700_000_000;
+    const BOT: &str = "Bot <bot@ci.example>";
+    const HUMAN: &str = "A U Thor <a.u.thor@example.com>";
+    let repo = init_repo();
+    write_file(&repo, "deps.txt", "x 1\n");
+    commit_as(&repo, BOT, "Update dependency x to 1", T);
+    let (_c, first, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "deps.txt", "x 2\n");
+    commit_as(&repo, BOT, "Update dependency x to 2", T + 600);
+    let (_c, second, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(run_git(&repo, &["tag", "deps-2"]).0, 0);
+    write_file(&repo, "lock.txt", "y 1\n");
+    commit_as(&repo, BOT, "Update dependency y to 1", T + 1200);
+    let (_c, third, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    write_file(&repo, "src.txt", "feature\n");
+    commit_as(&repo, HUMAN, "Add feature", T + 1500);
+    write_file(&repo, "deps.txt", "x 3\n");
+    commit_as(&repo, BOT, "Update dependency x to 3", T + 1800);
+    // Too long after the previous bot commit to join its run
+    std::fs::remove_file(repo.join("lock.txt")).unwrap();
+    commit_as(&repo, BOT, "Drop y", T + 9000);
+    let (_c, tree_before, _e) = run_git(&repo, &["rev-parse", "HEAD^{tree}"]);
+
+    run_tool_expect_success(&repo, |o| {
+        o.squash_consecutive_by_author = Some(900);
+        o.write_report = true;
+    });
+
+    let (_c, log, _e) = run_git(&repo, &["log", "--format=%s"]);
+    assert_eq!(
+        log,
+        "Drop y\nUpdate dependency x to 3\nAdd feature\nUpdate dependency x to 1\ninit commit\n"
+    );
+    let (_c, tree_after, _e) = run_git(&repo, &["rev-parse", "HEAD^{tree}"]);
+    assert_eq!(tree_after, tree_before);
+    let (_c, squashed, _e) = run_git(&repo, &["rev-parse", "HEAD~3"]);
+    let squashed = squashed.trim();
+    let (_c, body, _e) = run_git(&repo, &["log", "-1", "--format=%B", squashed]);
+    assert_eq!(
+        body.trim_end(),
+        format!(
+            "Update dependency x to 1\n\nUpdate dependency x to 2\n\n\
+             Update dependency y to 1\n\nSquashed commits:\n{}\n{}\n{}",
+            first.trim(),
+            second.trim(),
+            third.trim()
+        )
+    );
+    let (_c, files, _e) = run_git(&repo, &["ls-tree", "--name-only", squashed]);
+    assert_eq!(files, "README.md\ndeps.txt\nlock.txt\n");
+    let (_c, deps, _e) = run_git(&repo, &["show", &format!("{squashed}:deps.txt")]);
+    assert_eq!(deps, "x 2\n");
+    for old in [&first, &second, &third] {
+        assert_eq!(
+            commit_map_entry(&repo, old.trim()).as_deref(),
+            Some(squashed)
+        );
+    }
+    let (_c, tagged, _e) = run_git(&repo, &["rev-parse", "deps-2^{commit}"]);
+    assert_eq!(tagged.trim(), squashed);
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(report.contains("Commits squashed: 2\n"), "{report}");
+}
@@ -31,6 +31,63 @@ fn tag_rename_annotated_produces_tag_object() {
     assert_eq!(typ.trim(), "tag");
 }
 
+#[test]
+fn lightweight_tags_converts_annotated_tag() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "hello tag", "x"]).0, 0);
+    run_tool_expect_success(&repo, |o| {
+        o.no_data = true;
+        o.lightweight_tags = true;
+    });
+    let (_c1, typ, _e1) = run_git(&repo, &["cat-file", "-t", "refs/tags/x"]);
+    assert_eq!(typ.trim(), "commit");
+    let (_c2, tag_oid, _e2) = run_git(&repo, &["rev-parse", "refs/tags/x"]);
+    let (_c3, head_oid, _e3) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(tag_oid.trim(), head_oid.trim());
+}
+
+#[test]
+fn lightweight_tags_with_tag_rename() {
+    let repo = init_repo();
+    assert_eq!(
+        run_git(&repo, &["tag", "-a", "-m", "hello tag", "v1.0"]).0,
+        0
+    );
+    run_tool_expect_success(&repo, |o| {
+        o.no_data = true;
+        o.lightweight_tags = true;
+        o.tag_rename = Some((b"v".to_vec(), b"release-".to_vec()));
+    });
+    let (_c1, typ, _e1) = run_git(&repo, &["cat-file", "-t", "refs/tags/release-1.0"]);
+    assert_eq!(typ.trim(), "commit");
+    let (_c2, out, _e2) = run_git(&repo, &["show-ref", "--tags"]);
+    assert!(!out.contains("refs/tags/v1.0"));
+}
+
+#[test]
+fn annotate_tags_promotes_lightweight_tag() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "v1.0"]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["tag", "-a", "-m", "already annotated", "v2.0"]).0,
+        0
+    );
+    run_tool_expect_success(&repo, |o| {
+        o.no_data = true;
+        o.annotate_tags = Some(b"Release %(tag)".to_vec());
+    });
+    let (_c1, typ, _e1) = run_git(&repo, &["cat-file", "-t", "refs/tags/v1.0"]);
+    assert_eq!(typ.trim(), "tag");
+    let (_c2, body, _e2) = run_git(&repo, &["cat-file", "tag", "refs/tags/v1.0"]);
+    assert!(body.contains("Release v1.0"), "tag body: {}", body);
+    assert!(body.contains("\ntagger "), "tag body: {}", body);
+    let (_c3, target, _e3) = run_git(&repo, &["rev-parse", "refs/tags/v1.0^{commit}"]);
+    let (_c4, head, _e4) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(target.trim(), head.trim());
+    let (_c5, other, _e5) = run_git(&repo, &["cat-file", "tag", "refs/tags/v2.0"]);
+    assert!(other.contains("already annotated"), "tag body: {}", other);
+}
+
 #[test]
 fn branch_rename_updates_ref_and_head() {
     let repo = init_repo();
@@ -209,3 +266,632 @@ fn multi_branch_prefix_rename_maps_head_from_deleted_branch() {
     let (_c1, head_after, _e1) = run_git(&repo, &["symbolic-ref", "HEAD"]);
     assert_eq!(head_after.trim(), "refs/heads/topics/bar");
 }
+
+#[test]
+fn protect_ref_keeps_release_branch_byte_identical() {
+    let repo = init_repo();
+    let main_branch = current_branch(&repo);
+    write_file(&repo, "keep/a.txt", "a\n");
+    write_file(&repo, "drop/b.txt", "b\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add dirs"]).0, 0);
+    assert_eq!(run_git(&repo, &["branch", "release/1.0"]).0, 0);
+    let (_c, release_before, _e) = run_git(&repo, &["rev-parse", "refs/heads/release/1.0"]);
+    let (_c, main_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args([
+            "--force",
+            "--path",
+            "keep/",
+            "--protect-ref",
+            "release/*",
+            "--branch-rename",
+            ":new-",
+        ])
+        .output()
+        .expect("run filter-repo-rs with --protect-ref");
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{err}");
+    assert!(
+        err.contains("protected ref refs/heads/release/1.0"),
+        "{err}"
+    );
+
+    let (_c, release_after, _e) = run_git(&repo, &["rev-parse", "refs/heads/release/1.0"]);
+    assert_eq!(release_after.trim(), release_before.trim());
+    let main_ref = format!("refs/heads/new-{}", main_branch);
+    let (_c, main_after, _e) = run_git(&repo, &["rev-parse", &main_ref]);
+    assert_ne!(main_after.trim(), main_before.trim());
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", &main_ref]);
+    assert!(!tree.contains("drop/b.txt"));
+    let (_c, refs, _e) = run_git(&repo, &["show-ref"]);
+    assert!(!refs.contains("refs/heads/new-release/1.0"), "{refs}");
+}
+
+#[test]
+fn tag_rename_applies_ref_updates_in_one_transaction() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "v1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "v2.0"]).0, 0);
+    let (output, invocations) =
+        run_cli_with_git_spy(&repo, &["--force", "--tag-rename", "v:release-"]);
+    assert!(
+        output.status.success(),
+        "tag rename failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let updates: Vec<&GitInvocation> = invocations
+        .iter()
+        .filter(|inv| inv.args.iter().any(|a| a == "update-ref"))
+        .collect();
+    assert_eq!(updates.len(), 1, "expected one update-ref: {:?}", updates);
+    let stdin = updates[0].stdin.as_deref().unwrap_or("");
+    assert!(stdin.starts_with("start\n"), "stdin: {stdin:?}");
+    assert!(stdin.contains("delete refs/tags/v1.0\n"));
+    assert!(stdin.contains("delete refs/tags/v2.0\n"));
+    assert!(stdin.ends_with("prepare\ncommit\n"), "stdin: {stdin:?}");
+}
+
+#[test]
+fn failed_ref_update_leaves_all_refs_untouched() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "v1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "v2.0"]).0, 0);
+    write_file(&repo, ".git/refs/tags/v1.0.lock", "");
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--tag-rename", "v:release-"])
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(!output.status.success(), "locked ref should fail the run");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no refs were changed"), "stderr: {stderr}");
+    let (_c, out, _e) = run_git(&repo, &["show-ref", "--tags"]);
+    assert!(out.contains("refs/tags/v1.0"));
+    assert!(out.contains("refs/tags/v2.0"), "v2.0 deleted: {out}");
+}
+
+#[test]
+fn ref_rename_file_renames_branch_and_head_follows() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["branch", "-M", "master"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "v1.0"]).0, 0);
+    let table = repo.join(".git").join("ref-renames.txt");
+    std::fs::write(
+        &table,
+        "# old new\nrefs/heads/master refs/heads/main\nrefs/tags/v1.0 refs/tags/first\n",
+    )
+    .unwrap();
+    let (_c, old_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.ref_rename_file = Some(table.clone());
+    });
+    let (_c, main, _e) = run_git(&repo, &["rev-parse", "refs/heads/main"]);
+    assert_eq!(main.trim(), old_head.trim());
+    let (c, _o, _e) = run_git(&repo, &["show-ref", "--verify", "refs/heads/master"]);
+    assert_ne!(c, 0, "refs/heads/master should be gone");
+    let (_c, tags, _e) = run_git(&repo, &["tag", "-l"]);
+    assert_eq!(tags.trim(), "first");
+    let (_c, head, _e) = run_git(&repo, &["symbolic-ref", "HEAD"]);
+    assert_eq!(head.trim(), "refs/heads/main");
+    let ref_map =
+        std::fs::read_to_string(repo.join(".git").join("filter-repo").join("ref-map")).unwrap();
+    assert!(ref_map.contains("refs/heads/master refs/heads/main\n"));
+    assert!(ref_map.contains("refs/tags/v1.0 refs/tags/first\n"));
+}
+
+#[test]
+fn ref_rename_file_wins_over_branch_prefix_rename() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["branch", "-M", "master"]).0, 0);
+    assert_eq!(run_git(&repo, &["branch", "feature"]).0, 0);
+    let table = repo.join(".git").join("ref-renames.txt");
+    std::fs::write(&table, "refs/heads/master refs/heads/trunk\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.branch_rename = Some((Vec::new(), b"old-".to_vec()));
+        o.ref_rename_file = Some(table.clone());
+    });
+    let (_c, branches, _e) = run_git(
+        &repo,
+        &["for-each-ref", "--format=%(refname)", "refs/heads/"],
+    );
+    let branches: Vec<&str> = branches.lines().collect();
+    assert_eq!(branches, vec!["refs/heads/old-feature", "refs/heads/trunk"]);
+    let (_c, head, _e) = run_git(&repo, &["symbolic-ref", "HEAD"]);
+    assert_eq!(head.trim(), "refs/heads/trunk");
+}
+
+#[test]
+fn ref_rename_file_rejects_malformed_lines() {
+    let repo = init_repo();
+    let table = repo.join(".git").join("ref-renames.txt");
+    std::fs::write(&table, "refs/heads/master\n").unwrap();
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--ref-rename-file", table.to_str().unwrap()])
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("expected 'old-ref new-ref'"), "{stderr}");
+}
+
+#[test]
+fn tag_rename_onto_existing_tag_is_rejected() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "v1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "release-1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "v2.0"]).0, 0);
+    let err = run_tool(&repo, |o| {
+        o.force = false;
+        o.tag_rename = Some((b"v".to_vec(), b"release-".to_vec()));
+    })
+    .expect_err("collision should stop the run");
+    assert_eq!(err.code(), "SANITY_REF_CONFLICT");
+    let msg = err.to_string();
+    assert!(msg.contains("Tag rename collisions detected"), "{msg}");
+    assert!(
+        msg.contains("'refs/tags/release-1.0': refs/tags/release-1.0, refs/tags/v1.0"),
+        "{msg}"
+    );
+    assert!(!msg.contains("release-2.0"), "{msg}");
+    let (_c, tags, _e) = run_git(&repo, &["tag", "-l"]);
+    assert_eq!(
+        tags.lines().collect::<Vec<_>>(),
+        ["release-1.0", "v1.0", "v2.0"]
+    );
+}
+
+#[test]
+fn tag_rename_file_applies_before_prefix_rename() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "v1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "second", "v2.0"]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["tag", "-a", "-m", "beta", "v3.0-beta"]).0,
+        0
+    );
+    let table = repo.join(".git").join("tag-renames.txt");
+    std::fs::write(&table, "# old\tnew\nv3.0-beta\tpreview/3\n").unwrap();
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--tag-rename", "v:release-", "--tag-rename-file"])
+        .arg(&table)
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (_c, tags, _e) = run_git(&repo, &["tag", "-l"]);
+    assert_eq!(
+        tags.lines().collect::<Vec<_>>(),
+        ["preview/3", "release-1.0", "release-2.0"]
+    );
+    let (_c, tag_name, _e) = run_git(
+        &repo,
+        &["for-each-ref", "--format=%(tag)", "refs/tags/preview/3"],
+    );
+    assert_eq!(tag_name.trim(), "preview/3");
+    let ref_map =
+        std::fs::read_to_string(repo.join(".git").join("filter-repo").join("ref-map")).unwrap();
+    assert!(
+        ref_map.contains("refs/tags/v3.0-beta refs/tags/preview/3\n"),
+        "{ref_map}"
+    );
+    assert!(
+        ref_map.contains("refs/tags/v2.0 refs/tags/release-2.0\n"),
+        "{ref_map}"
+    );
+}
+
+// Three commits touching a.txt, with the middle one replaced by the first.
+fn repo_with_replace_ref() -> (std::path::PathBuf, String, String) {
+    let repo = init_repo();
+    for i in 1..=3 {
+        write_file(&repo, "a.txt", &format!("v{i}\n"));
+        run_git(&repo, &["add", "."]);
+        assert_eq!(
+            run_git(&repo, &["commit", "-q", "-m", &format!("c{i}")]).0,
+            0
+        );
+    }
+    let (_c, replaced, _e) = run_git(&repo, &["rev-parse", "HEAD~1"]);
+    let (_c, replacement, _e) = run_git(&repo, &["rev-parse", "HEAD~2"]);
+    let (replaced, replacement) = (replaced.trim().to_string(), replacement.trim().to_string());
+    assert_eq!(run_git(&repo, &["replace", &replaced, &replacement]).0, 0);
+    (repo, replaced, replacement)
+}
+
+fn replace_refs(repo: &std::path::Path) -> Vec<(String, String)> {
+    let (_c, out, _e) = run_git(
+        repo,
+        &[
+            "for-each-ref",
+            "--format=%(refname) %(objectname)",
+            "refs/replace/",
+        ],
+    );
+    out.lines()
+        .filter_map(|l| l.split_once(' '))
+        .map(|(name, oid)| (name.to_string(), oid.to_string()))
+        .collect()
+}
+
+fn commit_map(repo: &std::path::Path) -> std::collections::HashMap<String, String> {
+    let map = std::fs::read_to_string(repo.join(".git/filter-repo/commit-map")).unwrap();
+    map.lines()
+        .filter_map(|l| l.split_once(' '))
+        .map(|(old, new_)| (old.to_string(), new_.to_string()))
+        .collect()
+}
+
+#[test]
+fn replace_refs_are_deleted_by_default_and_history_is_not_replaced() {
+    let (repo, _replaced, _replacement) = repo_with_replace_ref();
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames = vec![(b"a.txt".to_vec(), b"b.txt".to_vec())];
+    });
+    assert!(replace_refs(&repo).is_empty());
+    let (_c, count, _e) = run_git(&repo, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count.trim(), "4", "replaced commit must stay in history");
+}
+
+#[test]
+fn replace_refs_update_no_add_follows_rewritten_commits() {
+    let (repo, replaced, replacement) = repo_with_replace_ref();
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames = vec![(b"a.txt".to_vec(), b"b.txt".to_vec())];
+        o.replace_refs_policy = filter_repo_rs::ReplaceRefsPolicy::UpdateNoAdd;
+    });
+    let map = commit_map(&repo);
+    assert_eq!(
+        replace_refs(&repo),
+        vec![(
+            format!("refs/replace/{}", map[&replaced]),
+            map[&replacement].clone()
+        )]
+    );
+}
+
+#[test]
+fn replace_refs_delete_and_add_maps_every_rewritten_commit() {
+    let (repo, _replaced, _replacement) = repo_with_replace_ref();
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames = vec![(b"a.txt".to_vec(), b"b.txt".to_vec())];
+        o.replace_refs_policy = filter_repo_rs::ReplaceRefsPolicy::Delete;
+    });
+    let mut expected: Vec<(String, String)> = commit_map(&repo)
+        .into_iter()
+        .filter(|(old, new_)| old != new_)
+        .map(|(old, new_)| (format!("refs/replace/{old}"), new_))
+        .collect();
+    expected.sort();
+    assert_eq!(expected.len(), 3, "the init commit is not rewritten");
+    assert_eq!(replace_refs(&repo), expected);
+}
+
+fn tag_target(repo: &std::path::Path, tag: &str) -> (String, String) {
+    let (_c, body, _e) = run_git(repo, &["cat-file", "-p", tag]);
+    let object = body.lines().next().unwrap().trim_start_matches("object ");
+    let name = body
+        .lines()
+        .find_map(|l| l.strip_prefix("tag "))
+        .unwrap_or_default();
+    (object.to_string(), name.to_string())
+}
+
+#[test]
+fn tag_rename_keeps_tag_of_tag_chain_and_passes_tree_refs_through() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "inner", "inner"]).0, 0);
+    let nested = ["-c", "advice.nestedTag=false", "tag", "-a", "-m", "outer"];
+    assert_eq!(
+        run_git(&repo, &[&nested[..], &["outer", "inner"]].concat()).0,
+        0
+    );
+    let (_c, tree, _e) = run_git(&repo, &["rev-parse", "HEAD^{tree}"]);
+    let tree = tree.trim().to_string();
+    assert_eq!(
+        run_git(&repo, &["update-ref", "refs/trees/root", &tree]).0,
+        0
+    );
+
+    run_tool_expect_success(&repo, |o| {
+        o.tag_rename = Some((Vec::new(), b"rel-".to_vec()));
+        o.path_renames = vec![(b"README.md".to_vec(), b"docs/README.md".to_vec())];
+        o.write_report = true;
+    });
+
+    let (_c, inner, _e) = run_git(&repo, &["rev-parse", "refs/tags/rel-inner"]);
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(
+        tag_target(&repo, "rel-outer"),
+        (inner.trim().to_string(), "rel-outer".into())
+    );
+    assert_eq!(
+        tag_target(&repo, "rel-inner"),
+        (head.trim().to_string(), "rel-inner".into())
+    );
+    let (_c, root, _e) = run_git(&repo, &["rev-parse", "refs/trees/root"]);
+    assert_eq!(root.trim(), tree);
+
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Refs not pointing at commits: 2"),
+        "{report}"
+    );
+    assert!(report.contains("refs/tags/outer -> tag "), "{report}");
+    assert!(report.contains("(tag of refs/tags/inner)"), "{report}");
+    assert!(
+        report.contains(&format!("refs/trees/root -> tree {tree} (left untouched)")),
+        "{report}"
+    );
+}
+
+#[test]
+fn tag_of_tag_without_inner_ref_is_peeled() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "inner", "inner"]).0, 0);
+    let nested = ["-c", "advice.nestedTag=false", "tag", "-a", "-m", "outer"];
+    assert_eq!(
+        run_git(&repo, &[&nested[..], &["outer", "inner"]].concat()).0,
+        0
+    );
+    assert_eq!(run_git(&repo, &["tag", "-d", "inner"]).0, 0);
+
+    run_tool_expect_success(&repo, |o| {
+        o.tag_rename = Some((Vec::new(), b"rel-".to_vec()));
+    });
+
+    let (_c, tags, _e) = run_git(&repo, &["tag", "-l"]);
+    assert_eq!(tags.trim(), "rel-outer");
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(
+        tag_target(&repo, "rel-outer"),
+        (head.trim().to_string(), "rel-outer".into())
+    );
+    let (_c, body, _e) = run_git(&repo, &["cat-file", "-p", "rel-outer"]);
+    assert!(body.ends_with("\nouter\n"), "{body}");
+}
+
+// Branch `name` off the initial commit with one commit at `epoch`.
+fn branch_with_commit_at(repo: &std::path::Path, name: &str, epoch: u64) {
+    assert_eq!(run_git(repo, &["checkout", "-q", "-b", name, "HEAD"]).0, 0);
+    write_file(repo, &format!("{name}.txt"), name);
+    assert_eq!(run_git(repo, &["add", "."]).0, 0);
+    let date = format!("{epoch} +0000");
+    let status = std::process::Command::new("git")
+        .current_dir(repo)
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .args(["commit", "-q", "-m", name])
+        .status()
+        .expect("run git commit");
+    assert!(status.success());
+    assert_eq!(run_git(repo, &["checkout", "-q", "-"]).0, 0);
+}
+
+#[test]
+fn branch_rename_onto_existing_branch_is_rejected() {
+    let repo = init_repo();
+    branch_with_commit_at(&repo, "feat/x", 1_700_000_000);
+    branch_with_commit_at(&repo, "topic/x", 1_700_000_100);
+    branch_with_commit_at(&repo, "feat/y", 1_700_000_200);
+    let err = run_tool(&repo, |o| {
+        o.force = false;
+        o.branch_rename = Some((b"feat/".to_vec(), b"topic/".to_vec()));
+    })
+    .expect_err("collision should stop the run");
+    assert_eq!(err.code(), "SANITY_REF_CONFLICT");
+    let msg = err.to_string();
+    assert!(msg.contains("Branch rename collisions detected"), "{msg}");
+    assert!(
+        msg.contains("'refs/heads/topic/x': refs/heads/feat/x, refs/heads/topic/x"),
+        "{msg}"
+    );
+    assert!(!msg.contains("topic/y"), "{msg}");
+    let (_c, branches, _e) = run_git(&repo, &["branch", "--format=%(refname:short)"]);
+    assert!(branches.contains("feat/x") && branches.contains("feat/y"));
+}
+
+#[test]
+fn branch_rename_collision_with_force_keeps_newest_branch() {
+    let repo = init_repo();
+    branch_with_commit_at(&repo, "feat/x", 1_700_000_000);
+    branch_with_commit_at(&repo, "feature/x", 1_700_000_100);
+    let table = repo.join(".git").join("ref-renames.txt");
+    std::fs::write(
+        &table,
+        "refs/heads/feat/x refs/heads/topic/x\nrefs/heads/feature/x refs/heads/topic/x\n",
+    )
+    .unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.ref_rename_file = Some(table.clone());
+    });
+    let (_c, branches, _e) = run_git(&repo, &["branch", "--format=%(refname:short)"]);
+    assert!(!branches.contains("feat/x"), "{branches}");
+    assert!(!branches.contains("feature/x"), "{branches}");
+    let (_c, subject, _e) = run_git(&repo, &["log", "-1", "--format=%s", "topic/x"]);
+    assert_eq!(subject.trim(), "feature/x");
+}
+
+#[test]
+fn head_on_vanished_branch_falls_back_to_main() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["branch", "-M", "main"]).0, 0);
+    assert_eq!(run_git(&repo, &["branch", "alpha"]).0, 0);
+    assert_eq!(run_git(&repo, &["checkout", "-q", "--orphan", "gone"]).0, 0);
+    assert_eq!(run_git(&repo, &["rm", "-q", "-r", "--cached", "."]).0, 0);
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--branch-rename", "alpha:beta"])
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (_c, head, _e) = run_git(&repo, &["symbolic-ref", "HEAD"]);
+    assert_eq!(head.trim(), "refs/heads/main");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("HEAD pointed at refs/heads/gone, which no longer exists"),
+        "{stderr}"
+    );
+}
+
+#[cfg(unix)]
+fn write_signer_script(repo: &std::path::Path, body: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let script = repo.join(".git").join("sign-tag.sh");
+    std::fs::write(&script, format!("#!/bin/sh\n{body}\n")).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[cfg(unix)]
+#[test]
+fn tag_sign_cmd_runs_once_per_rewritten_annotated_tag() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "one", "v1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "two", "v2.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "v3.0-light"]).0, 0);
+    let log = repo.join(".git").join("signed.log");
+    let script = write_signer_script(
+        &repo,
+        &format!("echo \"$1 $2 $FRRS_TAG $FRRS_OID\" >> '{}'", log.display()),
+    );
+    run_tool_expect_success(&repo, |o| {
+        o.tag_rename = Some((b"v".to_vec(), b"release-".to_vec()));
+        o.tag_sign_cmd = Some(script.display().to_string());
+    });
+    let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let head = head.trim();
+    let calls = std::fs::read_to_string(&log).unwrap();
+    let mut calls: Vec<&str> = calls.lines().collect();
+    calls.sort();
+    assert_eq!(
+        calls,
+        [
+            format!("release-1.0 {head} release-1.0 {head}"),
+            format!("release-2.0 {head} release-2.0 {head}"),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn tag_sign_cmd_failures_are_collected_after_trying_every_tag() {
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "one", "v1.0"]).0, 0);
+    assert_eq!(run_git(&repo, &["tag", "-a", "-m", "two", "v2.0"]).0, 0);
+    let log = repo.join(".git").join("signed.log");
+    let script = write_signer_script(
+        &repo,
+        &format!("echo \"$1\" >> '{}'\nexit 3", log.display()),
+    );
+    let err = run_tool(&repo, |o| {
+        o.tag_rename = Some((b"v".to_vec(), b"release-".to_vec()));
+        o.tag_sign_cmd = Some(script.display().to_string());
+    })
+    .expect_err("failing signer should fail the run");
+    assert_eq!(err.code(), "TAG_SIGN_FAILED");
+    let msg = err.to_string();
+    assert!(
+        msg.contains("release-1.0") && msg.contains("release-2.0"),
+        "{msg}"
+    );
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn detached_head_follows_its_rewritten_commit() {
+    // HEAD is exported with --all; with an explicit branch only the
+    // commit-map can move it
+    for only_branch in [false, true] {
+        let repo = init_repo();
+        let (_c, branch, _e) = run_git(&repo, &["symbolic-ref", "HEAD"]);
+        write_file(&repo, "secret.txt", "token\n");
+        write_file(&repo, "app.txt", "v1\n");
+        run_git(&repo, &["add", "."]);
+        assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add app"]).0, 0);
+        let (_c, detached, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+        write_file(&repo, "app.txt", "v2\n");
+        assert_eq!(run_git(&repo, &["commit", "-q", "-am", "update app"]).0, 0);
+        assert_eq!(
+            run_git(&repo, &["checkout", "-q", "--detach", "HEAD~1"]).0,
+            0
+        );
+
+        run_tool_expect_success(&repo, |o| {
+            o.paths = vec![b"secret.txt".to_vec()];
+            o.invert_paths = true;
+            if only_branch {
+                o.refs = vec![branch.trim().to_string()];
+            }
+        });
+
+        assert_ne!(run_git(&repo, &["symbolic-ref", "-q", "HEAD"]).0, 0);
+        let map = std::fs::read_to_string(repo.join(".git/filter-repo/commit-map")).unwrap();
+        let rewritten = map
+            .lines()
+            .find_map(|l| l.strip_prefix(&format!("{} ", detached.trim())))
+            .expect("detached commit mapped");
+        assert_ne!(rewritten, detached.trim());
+        let (_c, head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+        assert_eq!(head.trim(), rewritten, "only_branch={only_branch}");
+        let (_c, files, _e) = run_git(&repo, &["ls-tree", "--name-only", "HEAD"]);
+        assert_eq!(files, "README.md\napp.txt\n");
+    }
+}
+
+#[test]
+fn rename_tags_to_match_branches_mirrors_branch_prefix() {
+    let setup = || {
+        let repo = init_repo();
+        assert_eq!(run_git(&repo, &["branch", "v/1.x"]).0, 0);
+        assert_eq!(run_git(&repo, &["tag", "v/1.0"]).0, 0);
+        assert_eq!(run_git(&repo, &["tag", "-a", "-m", "1.1", "v/1.1"]).0, 0);
+        assert_eq!(run_git(&repo, &["tag", "other"]).0, 0);
+        repo
+    };
+
+    let repo = setup();
+    run_tool_expect_success(&repo, |o| {
+        o.branch_rename = Some((b"v/".to_vec(), b"release/".to_vec()));
+        o.mirror_branch_rename_to_tags = true;
+    });
+    let (_c, refs, _e) = run_git(&repo, &["for-each-ref", "--format=%(refname)"]);
+    for name in [
+        "refs/heads/release/1.x",
+        "refs/tags/release/1.0",
+        "refs/tags/release/1.1",
+        "refs/tags/other",
+    ] {
+        assert!(refs.lines().any(|l| l == name), "{name} missing: {refs}");
+    }
+    assert!(!refs.contains("/v/"), "{refs}");
+    let ref_map = std::fs::read_to_string(repo.join(".git/filter-repo/ref-map")).unwrap();
+    for line in [
+        "refs/heads/v/1.x refs/heads/release/1.x",
+        "refs/tags/v/1.0 refs/tags/release/1.0",
+        "refs/tags/v/1.1 refs/tags/release/1.1",
+    ] {
+        assert!(ref_map.lines().any(|l| l == line), "{line}: {ref_map}");
+    }
+
+    // An explicit tag rename wins
+    let repo = setup();
+    run_tool_expect_success(&repo, |o| {
+        o.branch_rename = Some((b"v/".to_vec(), b"release/".to_vec()));
+        o.tag_rename = Some((b"v/".to_vec(), b"tag-".to_vec()));
+        o.mirror_branch_rename_to_tags = true;
+    });
+    let (_c, tags, _e) = run_git(&repo, &["tag", "--list"]);
+    assert_eq!(tags, "other\ntag-1.0\ntag-1.1\n");
+}
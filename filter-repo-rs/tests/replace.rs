@@ -34,3 +34,308 @@ fn replace_text_regex_redacts_blob() {
     assert!(content.contains("X X"));
     assert!(!content.contains("foo123"));
 }
+
+#[test]
+fn replace_text_encoded_terms_match_decoded_bytes() {
+    let repo = init_repo();
+    // A binary key that cannot be written as a plain rule line, next to its
+    // base64 spelling, which the rule must leave alone
+    let mut key = b"key=".to_vec();
+    key.extend_from_slice(&[0x00, 0xff, 0x10, 0x80]);
+    key.extend_from_slice(b"\ntext=AP8QgA==\nhex=534543524554 SECRET\n");
+    std::fs::write(repo.join("key.bin"), &key).unwrap();
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add key"]).0, 0);
+    let repl = repo.join("repl-encoded.txt");
+    std::fs::write(
+        &repl,
+        "base64:AP8QgA====>REDACTED\nhex:534543524554==>HIDDEN\n",
+    )
+    .unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(repl.clone());
+        o.no_data = false;
+    });
+    let (_c2, content, _e2) = run_git(&repo, &["show", "HEAD:key.bin"]);
+    assert_eq!(
+        content,
+        "key=REDACTED\ntext=AP8QgA==\nhex=534543524554 HIDDEN\n"
+    );
+}
+
+#[test]
+fn replace_text_rejects_malformed_encoded_term() {
+    let repo = init_repo();
+    let repl = repo.join("repl-bad.txt");
+    std::fs::write(&repl, "hex:53454==>X\n").unwrap();
+    let err = run_tool(&repo, |o| {
+        o.replace_text_file = Some(repl.clone());
+        o.no_data = false;
+    })
+    .expect_err("odd-length hex term should be rejected");
+    assert!(err.to_string().contains("invalid hex search term"), "{err}");
+}
+
+#[test]
+fn replace_text_output_is_identical_across_job_counts() {
+    let repo = init_repo();
+    for commit in 0..6 {
+        for file in 0..8 {
+            write_file(
+                &repo,
+                &format!("dir{file}/f{file}.txt"),
+                &format!("commit {commit} file {file} SECRET-{commit}{file} foo{file}{commit}\n"),
+            );
+        }
+        run_git(&repo, &["add", "."]);
+        let msg = format!("commit {commit}");
+        assert_eq!(run_git(&repo, &["commit", "-q", "-m", &msg]).0, 0);
+    }
+    let repl = repo.join("repl.txt");
+    std::fs::write(&repl, "SECRET-==>REDACTED-\nregex:foo([0-9]+)==>bar$1\n").unwrap();
+
+    let mut results = Vec::new();
+    for jobs in [1, 4] {
+        let clone = mktemp(&format!("fr-rs-jobs-{jobs}"));
+        let (c, _o, e) = run_git(
+            &repo,
+            &[
+                "clone",
+                "-q",
+                "--no-local",
+                repo.to_str().unwrap(),
+                clone.to_str().unwrap(),
+            ],
+        );
+        assert_eq!(c, 0, "clone failed: {e}");
+        run_tool_expect_success(&clone, |o| {
+            o.replace_text_file = Some(repl.clone());
+            o.jobs = jobs;
+        });
+        let (_c, log, _e) = run_git(&clone, &["log", "--format=%H %T", "HEAD"]);
+        let filtered = std::fs::read(clone.join(".git/filter-repo/fast-export.filtered")).unwrap();
+        results.push((log, filtered));
+    }
+    let (_c, content, _e) = run_git(&repo, &["show", "HEAD:dir3/f3.txt"]);
+    assert!(content.contains("SECRET-53"), "source repo is untouched");
+    assert_eq!(
+        results[0].0, results[1].0,
+        "commit ids differ between --jobs 1 and 4"
+    );
+    assert!(results[0].1 == results[1].1, "filtered streams differ");
+    assert!(String::from_utf8_lossy(&results[1].1).contains("REDACTED-53 bar35"));
+}
+
+#[test]
+fn replace_text_passes_through_blobs_over_in_memory_cap() {
+    let repo = init_repo();
+    let big = format!("SECRET-ABC-123\n{}\n", "x".repeat(2048));
+    write_file(&repo, "big.txt", &big);
+    write_file(&repo, "small.txt", "SECRET-ABC-123\n");
+    write_file(&repo, "huge.bin", &"y".repeat(8192));
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add blobs"]).0, 0);
+    let (_c, big_id, _e) = run_git(&repo, &["rev-parse", "HEAD:big.txt"]);
+    let repl = repo.join("repl-cap.txt");
+    std::fs::write(&repl, "SECRET-ABC-123==>REDACTED\n").unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(repl.clone());
+        o.max_in_memory_blob_bytes = 1024;
+        o.max_blob_size = Some(4096);
+        o.write_report = true;
+    });
+
+    let (_c, small, _e) = run_git(&repo, &["show", "HEAD:small.txt"]);
+    assert_eq!(small, "REDACTED\n");
+    let (_c, big_after, _e) = run_git(&repo, &["rev-parse", "HEAD:big.txt"]);
+    assert_eq!(
+        big_after.trim(),
+        big_id.trim(),
+        "large blob must pass through"
+    );
+    let (_c, tree, _e) = run_git(&repo, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert!(
+        !tree.contains("huge.bin"),
+        "size stripping still applies: {tree}"
+    );
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains(&format!(
+            "Blob too large for replace-text (passed through): 1\n  {}\n",
+            big_id.trim()
+        )),
+        "{report}"
+    );
+}
+
+#[test]
+fn empty_replace_text_rules_leave_repository_untouched() {
+    let repo = init_repo();
+    let (_c, refs_before, _e) = run_git(&repo, &["for-each-ref"]);
+    let repl = repo.join("rules.txt");
+    std::fs::write(&repl, "# nothing to replace yet\n\n").unwrap();
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--replace-text"])
+        .arg(&repl)
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Nothing to do"), "{stderr}");
+
+    let (_c, refs_after, _e) = run_git(&repo, &["for-each-ref"]);
+    assert_eq!(refs_before, refs_after);
+    let state = repo.join(".git").join("filter-repo");
+    assert!(!state.join("already_ran").exists());
+    assert!(!state.join("commit-map").exists());
+}
+
+#[test]
+fn dump_unmatched_replace_rules_lists_only_dead_rules() {
+    let repo = init_repo();
+    write_file(&repo, "a.txt", "password=hunter2\n");
+    write_file(&repo, "b.txt", "api key AKIA1234\n");
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add secrets"]).0, 0);
+    let repl = repo.join("rules.txt");
+    std::fs::write(
+        &repl,
+        "hunter2==>***\nregex:AKIA[0-9]+==>KEY\nnever-present-token\n",
+    )
+    .unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(repl.clone());
+        o.report_unused_replace_rules = true;
+    });
+
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(
+        report.contains("Replace-text rules that never matched: 1\n  never-present-token\n"),
+        "{report}"
+    );
+}
+
+#[test]
+fn decode_utf16_for_replace_redacts_utf16le_blobs() {
+    let repo = init_repo();
+    let utf16le = |text: &str| -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    };
+    std::fs::write(
+        repo.join("settings.ini"),
+        utf16le("[db]\r\npassword=hunter2\r\n"),
+    )
+    .unwrap();
+    run_git(&repo, &["add", "."]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add settings"]).0, 0);
+    let repl = repo.join("rules.txt");
+    std::fs::write(&repl, "hunter2==>REDACTED\n").unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(repl.clone());
+        o.decode_utf16_for_replace = true;
+    });
+
+    let out = std::process::Command::new("git")
+        .current_dir(&repo)
+        .args(["cat-file", "blob", "HEAD:settings.ini"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(out.stdout, utf16le("[db]\r\npassword=REDACTED\r\n"));
+    let units: Vec<u16> = out.stdout[2..]
+        .chunks_exact(2)
+        .map(|p| u16::from_le_bytes([p[0], p[1]]))
+        .collect();
+    let text = String::from_utf16(&units).expect("still valid UTF-16LE");
+    assert!(!text.contains("hunter2"), "{text}");
+}
+
+// Stage a symlink `link` pointing at `target` without touching the filesystem.
+fn add_symlink(repo: &std::path::Path, link: &str, target: &str) {
+    write_file(repo, "link-target.tmp", target);
+    let (_c, blob, _e) = run_git(repo, &["hash-object", "-w", "link-target.tmp"]);
+    std::fs::remove_file(repo.join("link-target.tmp")).unwrap();
+    let info = format!("120000,{},{}", blob.trim(), link);
+    assert_eq!(
+        run_git(repo, &["update-index", "--add", "--cacheinfo", &info]).0,
+        0
+    );
+}
+
+fn repo_with_symlink_to_token() -> std::path::PathBuf {
+    let repo = init_repo();
+    // Same bytes as the link target, so both share one blob
+    write_file(&repo, "notes.txt", "configs/TOKEN.conf");
+    run_git(&repo, &["add", "notes.txt"]);
+    add_symlink(&repo, "current.conf", "configs/TOKEN.conf");
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add link"]).0, 0);
+    std::fs::write(repo.join("rules.txt"), "TOKEN==>XXX\n").unwrap();
+    repo
+}
+
+#[test]
+fn replace_text_leaves_symlink_targets_alone_by_default() {
+    let repo = repo_with_symlink_to_token();
+    let rules = repo.join("rules.txt");
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(rules.clone());
+    });
+
+    let (_c, notes, _e) = run_git(&repo, &["show", "HEAD:notes.txt"]);
+    assert_eq!(notes, "configs/XXX.conf");
+    let (_c, target, _e) = run_git(&repo, &["show", "HEAD:current.conf"]);
+    assert_eq!(target, "configs/TOKEN.conf");
+    let (_c, entry, _e) = run_git(&repo, &["ls-tree", "HEAD", "current.conf"]);
+    assert!(entry.starts_with("120000 blob "), "{entry}");
+}
+
+#[test]
+fn rewrite_symlink_targets_opts_in_to_rewriting_links() {
+    let repo = repo_with_symlink_to_token();
+    let rules = repo.join("rules.txt");
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(rules.clone());
+        o.rewrite_symlink_targets = true;
+    });
+
+    let (_c, target, _e) = run_git(&repo, &["show", "HEAD:current.conf"]);
+    assert_eq!(target, "configs/XXX.conf");
+    let (_c, entry, _e) = run_git(&repo, &["ls-tree", "HEAD", "current.conf"]);
+    assert!(entry.starts_with("120000 blob "), "{entry}");
+}
+
+#[test]
+fn replace_text_keeps_executable_bit_only_changes() {
+    let repo = init_repo();
+    write_file(&repo, "run.sh", "echo SECRET\n");
+    run_git(&repo, &["add", "run.sh"]);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add script"]).0, 0);
+    run_git(&repo, &["update-index", "--chmod=+x", "run.sh"]);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "make executable"]).0,
+        0
+    );
+    let rules = repo.join("rules.txt");
+    std::fs::write(&rules, "SECRET==>REDACTED\n").unwrap();
+
+    run_tool_expect_success(&repo, |o| {
+        o.replace_text_file = Some(rules.clone());
+    });
+
+    let (_c, subject, _e) = run_git(&repo, &["log", "-1", "--format=%s"]);
+    assert_eq!(subject.trim(), "make executable");
+    let (_c, summary, _e) = run_git(&repo, &["show", "--format=", "--summary", "HEAD"]);
+    assert!(
+        summary.contains("mode change 100644 => 100755 run.sh"),
+        "{summary}"
+    );
+    let (_c, script, _e) = run_git(&repo, &["show", "HEAD:run.sh"]);
+    assert_eq!(script, "echo REDACTED\n");
+}
@@ -55,3 +55,123 @@ fn strip_ids_report_written() {
     assert!(s.contains("Blobs stripped by SHA:"));
     assert!(s.contains("secret.bin"));
 }
+
+#[test]
+fn report_records_tool_and_git_versions() {
+    let repo = init_repo();
+    run_tool_expect_success(&repo, |o| {
+        o.write_report = true;
+        o.paths.push(b"src/".to_vec());
+    });
+    let report = repo.join(".git").join("filter-repo").join("report.txt");
+    let s = std::fs::read_to_string(&report).unwrap();
+    let tool_line = format!("filter-repo-rs {}", env!("CARGO_PKG_VERSION"));
+    assert!(s.lines().any(|l| l == tool_line), "{s}");
+    assert!(s.lines().any(|l| l.starts_with("git version ")), "{s}");
+    assert!(s.contains("  paths: src/"), "{s}");
+}
+
+fn read_report(repo: &std::path::Path) -> String {
+    let report = repo.join(".git").join("filter-repo").join("report.txt");
+    let mut s = String::new();
+    File::open(&report).unwrap().read_to_string(&mut s).unwrap();
+    s
+}
+
+#[test]
+fn verify_hash_stability_reports_no_changes_for_noop_filter() {
+    let repo = init_repo();
+    write_file(&repo, "src/lib.rs", "pub fn f() {}\n");
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add lib"]).0, 0);
+    let (_c, head_before, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.verify_hash_stability = true;
+    });
+    let (_c, head_after, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head_before, head_after);
+    let report = read_report(&repo);
+    assert!(report.contains("Hash stability:"), "report: {report}");
+    assert!(
+        report.contains("  unchanged commits: 2"),
+        "report: {report}"
+    );
+    assert!(report.contains("  changed commits: 0"), "report: {report}");
+}
+
+#[test]
+fn verify_hash_stability_lists_changed_commits() {
+    let repo = init_repo();
+    write_file(&repo, "src/lib.rs", "pub fn f() {}\n");
+    run_git(&repo, &["add", "."]).0;
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add lib"]).0, 0);
+    let (_c, old_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    run_tool_expect_success(&repo, |o| {
+        o.verify_hash_stability = true;
+        o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+    });
+    let (_c, new_head, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    let report = read_report(&repo);
+    assert!(
+        report.contains("  unchanged commits: 1"),
+        "report: {report}"
+    );
+    assert!(report.contains("  changed commits: 1"), "report: {report}");
+    assert!(
+        report.contains(&format!(
+            "  changed {} -> {}",
+            old_head.trim(),
+            new_head.trim()
+        )),
+        "report: {report}"
+    );
+}
+
+#[test]
+fn deterministic_runs_on_copies_produce_identical_refs() {
+    let src = init_repo();
+    write_file(&src, "src/lib.rs", "pub fn f() {}\n");
+    write_file(&src, "docs/guide.md", "guide\n");
+    run_git(&src, &["add", "."]).0;
+    assert_eq!(run_git(&src, &["commit", "-q", "-m", "add lib"]).0, 0);
+    assert_eq!(run_git(&src, &["branch", "topic"]).0, 0);
+    assert_eq!(run_git(&src, &["tag", "-a", "v1", "-m", "release"]).0, 0);
+
+    let rewrite = |prefix: &str| {
+        let copy = mktemp(prefix);
+        let (code, _o, e) = run_git(
+            &src,
+            &["clone", "-q", "--no-local", ".", copy.to_str().unwrap()],
+        );
+        assert_eq!(code, 0, "clone failed: {e}");
+        run_tool_expect_success(&copy, |o| {
+            o.deterministic = true;
+            o.write_report = true;
+            o.path_renames.push((b"src/".to_vec(), b"lib/".to_vec()));
+        });
+        assert!(read_report(&copy).contains("deterministic mode"));
+        run_git(&copy, &["rev-parse", "--all"]).1
+    };
+    let first = rewrite("fr_rs_deterministic_a");
+    let second = rewrite("fr_rs_deterministic_b");
+    assert_eq!(first, second);
+    assert_ne!(first, run_git(&src, &["rev-parse", "--all"]).1);
+}
+
+#[test]
+fn deterministic_refuses_timestamped_backups() {
+    let repo = init_repo();
+    let err = run_tool(&repo, |o| {
+        o.deterministic = true;
+        o.backup = true;
+    })
+    .expect_err("timestamped backup name should be refused");
+    assert!(err.to_string().contains("--backup-path"), "{err}");
+
+    run_tool_expect_success(&repo, |o| {
+        o.deterministic = true;
+        o.backup = true;
+        o.backup_path = Some(repo.join("fixed.bundle"));
+    });
+    assert!(repo.join("fixed.bundle").exists());
+}
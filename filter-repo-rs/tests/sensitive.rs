@@ -71,6 +71,7 @@ fn sensitive_fetch_all_from_bare_remote() {
     assert_ne!(c0, 0);
     run_tool_expect_success(&repo, |o| {
         o.sensitive = true;
+        o.replace_text_rules.push(b"hunter2".to_vec());
     });
     let (c1, _o1, _e1) = run_git(&repo, &["show-ref", "--verify", "refs/heads/extra"]);
     assert_eq!(c1, 0);
@@ -78,6 +79,48 @@ fn sensitive_fetch_all_from_bare_remote() {
     assert!(remotes.contains("origin"));
 }
 
+#[test]
+fn sensitive_refspec_limits_fetch_to_heads() {
+    let bare = mktemp("fr_rs_bare");
+    std::fs::create_dir_all(&bare).unwrap();
+    assert_eq!(run_git(&bare, &["init", "--bare"]).0, 0);
+
+    let seed = init_repo();
+    assert_eq!(run_git(&seed, &["checkout", "-b", "extra"]).0, 0);
+    write_file(&seed, "extra.txt", "hello\n");
+    run_git(&seed, &["add", "."]).0;
+    run_git(&seed, &["commit", "-m", "extra"]).0;
+    assert_eq!(run_git(&seed, &["tag", "-a", "v1", "-m", "release"]).0, 0);
+    let bare_str = bare.to_string_lossy().to_string();
+    assert_eq!(run_git(&seed, &["remote", "add", "origin", &bare_str]).0, 0);
+    assert_eq!(run_git(&seed, &["push", "-q", "origin", "--all"]).0, 0);
+    assert_eq!(run_git(&seed, &["push", "-q", "origin", "--tags"]).0, 0);
+
+    let repo = init_repo();
+    assert_eq!(run_git(&repo, &["remote", "add", "origin", &bare_str]).0, 0);
+    run_tool_expect_success(&repo, |o| {
+        o.sensitive = true;
+        o.sensitive_refspecs = vec!["+refs/heads/*:refs/heads/*".to_string()];
+        o.write_report = true;
+    });
+    let (c1, _o1, _e1) = run_git(&repo, &["show-ref", "--verify", "refs/heads/extra"]);
+    assert_eq!(c1, 0);
+    let (_c2, tags, _e2) = run_git(&repo, &["tag", "-l"]);
+    assert_eq!(tags.trim(), "", "tags must not be fetched");
+    let report = std::fs::read_to_string(repo.join(".git/filter-repo/report.txt")).unwrap();
+    assert!(report.contains("Refs fetched from origin: 2\n"), "{report}");
+
+    let err = run_tool(&repo, |o| {
+        o.sensitive = true;
+        o.sensitive_refspecs = vec!["refs/heads/*:refs/heads/main".to_string()];
+    })
+    .expect_err("mismatched wildcard should be rejected");
+    assert!(
+        err.to_string().contains("refs/heads/*:refs/heads/main"),
+        "{err}"
+    );
+}
+
 #[test]
 fn origin_migration_and_removal_nonsensitive() {
     let repo = init_repo();
@@ -90,7 +133,10 @@ fn origin_migration_and_removal_nonsensitive() {
     assert_eq!(run_git(&repo, &["remote", "add", "origin", "."]).0, 0);
     let spec = format!("+{}:refs/remotes/origin/{}", headref, branch);
     assert_eq!(run_git(&repo, &["fetch", "origin", &spec]).0, 0);
-    run_tool_expect_success(&repo, |_o| {});
+    run_tool_expect_success(&repo, |o| {
+        o.path_renames
+            .push((b"README.md".to_vec(), b"README.txt".to_vec()));
+    });
     let (_c2, remotes, _e2) = run_git(&repo, &["remote"]);
     assert!(!remotes.contains("origin"));
 }
@@ -164,3 +210,236 @@ fn sensitive_mode_validation_rejects_custom_paths() {
         "unexpected error: {error_msg}"
     );
 }
+
+#[test]
+fn sensitive_report_lists_commits_blobs_and_refs() {
+    let repo = init_repo();
+    let main_branch = current_branch(&repo);
+    assert_eq!(run_git(&repo, &["remote", "add", "origin", "."]).0, 0);
+
+    write_file(&repo, "config.env", "TOKEN=hunter2-secret\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add config"]).0, 0);
+    let (_c, main_commit, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    assert_eq!(
+        run_git(&repo, &["checkout", "-q", "-b", "feature", "HEAD~1"]).0,
+        0
+    );
+    write_file(&repo, "deploy.sh", "export TOKEN=hunter2-secret\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(run_git(&repo, &["commit", "-q", "-m", "add deploy"]).0, 0);
+    let (_c, feature_commit, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(run_git(&repo, &["checkout", "-q", &main_branch]).0, 0);
+
+    let rules = repo.join("secrets.txt");
+    std::fs::write(&rules, "hunter2-secret\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.sensitive = true;
+        o.no_fetch = true;
+        o.replace_text_file = Some(rules.clone());
+    });
+
+    let debug_dir = repo.join(".git").join("filter-repo");
+    let report = std::fs::read_to_string(debug_dir.join("sensitive-data-report.txt")).unwrap();
+    assert!(report.contains(main_commit.trim()), "{report}");
+    assert!(report.contains(feature_commit.trim()), "{report}");
+    assert!(report.contains("config.env"), "{report}");
+    assert!(report.contains("deploy.sh"), "{report}");
+    let main_ref = format!("refs/heads/{}", main_branch);
+    assert!(report.contains(&main_ref), "{report}");
+    assert!(report.contains("refs/heads/feature"), "{report}");
+
+    let json: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(debug_dir.join("sensitive-data-report.json")).unwrap(),
+    )
+    .unwrap();
+    let commits = json["commits"].as_array().unwrap();
+    assert_eq!(commits.len(), 2);
+    assert!(commits.iter().all(|c| c["status"] == "rewritten"));
+    let refs_of = |original: &str| {
+        let commit = commits
+            .iter()
+            .find(|c| c["original"] == original)
+            .unwrap_or_else(|| panic!("{original} missing from {commits:?}"));
+        commit["refs"].clone()
+    };
+    assert_eq!(refs_of(main_commit.trim()), serde_json::json!([main_ref]));
+    assert_eq!(
+        refs_of(feature_commit.trim()),
+        serde_json::json!(["refs/heads/feature"])
+    );
+    let pushes: Vec<String> = json["force_push"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| {
+            format!(
+                "{} {}",
+                p["remote"].as_str().unwrap(),
+                p["ref"].as_str().unwrap()
+            )
+        })
+        .collect();
+    assert!(pushes.contains(&format!("origin {}", main_ref)));
+    assert!(pushes.contains(&"origin refs/heads/feature".to_string()));
+}
+
+#[test]
+fn sensitive_replace_text_also_redacts_messages() {
+    let repo = init_repo();
+    write_file(&repo, "notes.txt", "password=hunter2-secret\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "rotate hunter2-secret"]).0,
+        0
+    );
+    let (_c, original, _e) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(
+        run_git(
+            &repo,
+            &["tag", "-a", "v1", "-m", "release with hunter2-secret"]
+        )
+        .0,
+        0
+    );
+
+    let rules = repo.join("secrets.txt");
+    std::fs::write(&rules, "hunter2-secret\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.sensitive = true;
+        o.no_fetch = true;
+        o.replace_text_file = Some(rules.clone());
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%B"]);
+    assert!(!msg.contains("hunter2-secret"), "{msg}");
+    assert!(msg.contains("***REMOVED***"), "{msg}");
+    let (_c, tag_msg, _e) = run_git(&repo, &["cat-file", "-p", "refs/tags/v1"]);
+    assert!(!tag_msg.contains("hunter2-secret"), "{tag_msg}");
+    let (_c, blob, _e) = run_git(&repo, &["show", "HEAD:notes.txt"]);
+    assert!(!blob.contains("hunter2-secret"), "{blob}");
+
+    let debug_dir = repo.join(".git").join("filter-repo");
+    let json: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(debug_dir.join("sensitive-data-report.json")).unwrap(),
+    )
+    .unwrap();
+    let commits = json["messages"]["commits"].as_array().unwrap();
+    assert_eq!(commits.len(), 1);
+    assert_eq!(commits[0].as_str().unwrap(), original.trim());
+    assert_eq!(json["messages"]["tags"][0], "v1");
+    assert_eq!(json["blobs"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn sensitive_apply_to_messages_can_be_disabled() {
+    let repo = init_repo();
+    write_file(&repo, "notes.txt", "hunter2-secret\n");
+    assert_eq!(run_git(&repo, &["add", "."]).0, 0);
+    assert_eq!(
+        run_git(&repo, &["commit", "-q", "-m", "keep hunter2-secret"]).0,
+        0
+    );
+
+    let rules = repo.join("secrets.txt");
+    std::fs::write(&rules, "hunter2-secret\n").unwrap();
+    run_tool_expect_success(&repo, |o| {
+        o.sensitive = true;
+        o.no_fetch = true;
+        o.replace_text_file = Some(rules.clone());
+        o.sensitive_apply_to_messages = Some(false);
+    });
+
+    let (_c, msg, _e) = run_git(&repo, &["log", "-1", "--format=%B"]);
+    assert!(msg.contains("hunter2-secret"), "{msg}");
+    let (_c, blob, _e) = run_git(&repo, &["show", "HEAD:notes.txt"]);
+    assert!(!blob.contains("hunter2-secret"), "{blob}");
+}
+
+#[test]
+fn sensitive_mode_validation_rejects_preserve_original_refs() {
+    let opts = filter_repo_rs::Options {
+        sensitive: true,
+        preserve_original_refs: true,
+        ..Default::default()
+    };
+    let error = filter_repo_rs::sanity::SensitiveModeValidator::validate_options(&opts)
+        .expect_err("sensitive mode with preserved original refs should fail");
+
+    let error_msg = error.to_string();
+    assert!(
+        error_msg.contains("--preserve-original-refs"),
+        "unexpected error: {error_msg}"
+    );
+}
+
+#[test]
+fn partial_keep_remote_drops_unnamed_remotes() {
+    let repo = init_repo();
+    let (_c, headref, _e) = run_git(&repo, &["symbolic-ref", "-q", "HEAD"]);
+    let headref = headref.trim().to_string();
+    for remote in ["origin", "upstream"] {
+        assert_eq!(run_git(&repo, &["remote", "add", remote, "."]).0, 0);
+        let spec = format!("+{}:refs/remotes/{}/main", headref, remote);
+        assert_eq!(run_git(&repo, &["fetch", "-q", remote, &spec]).0, 0);
+    }
+    run_tool_expect_success(&repo, |o| {
+        o.partial = true;
+        o.keep_remotes = vec!["upstream".to_string()];
+        o.path_renames
+            .push((b"README.md".to_vec(), b"README.txt".to_vec()));
+    });
+    let (_c2, remotes, _e2) = run_git(&repo, &["remote"]);
+    assert!(remotes.lines().any(|l| l == "upstream"));
+    assert!(!remotes.lines().any(|l| l == "origin"));
+    let (kept, _o, _e) = run_git(
+        &repo,
+        &["show-ref", "--verify", "refs/remotes/upstream/main"],
+    );
+    assert_eq!(kept, 0, "kept remote's tracking ref should remain");
+    let (_c3, origin_refs, _e3) = run_git(
+        &repo,
+        &[
+            "for-each-ref",
+            "--format=%(refname)",
+            "refs/remotes/origin/",
+        ],
+    );
+    assert!(
+        origin_refs.trim().is_empty(),
+        "origin tracking refs should be removed"
+    );
+}
+
+#[test]
+fn identity_rewrite_keeps_origin_remote() {
+    let repo = init_repo();
+    let (_c, headref, _e) = run_git(&repo, &["symbolic-ref", "-q", "HEAD"]);
+    let headref = headref.trim().to_string();
+    let branch = headref
+        .strip_prefix("refs/heads/")
+        .unwrap_or(&headref)
+        .to_string();
+    assert_eq!(run_git(&repo, &["remote", "add", "origin", "."]).0, 0);
+    let spec = format!("+{}:refs/remotes/origin/{}", headref, branch);
+    assert_eq!(run_git(&repo, &["fetch", "origin", &spec]).0, 0);
+    let (_c0, head_before, _e0) = run_git(&repo, &["rev-parse", "HEAD"]);
+
+    let output = cli_command()
+        .current_dir(&repo)
+        .args(["--force", "--path", "README.md"])
+        .output()
+        .expect("run filter-repo-rs");
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("History unchanged"), "{stderr}");
+
+    let (_c1, head_after, _e1) = run_git(&repo, &["rev-parse", "HEAD"]);
+    assert_eq!(head_before, head_after);
+    let (_c2, remotes, _e2) = run_git(&repo, &["remote"]);
+    assert!(remotes.lines().any(|l| l == "origin"), "{remotes}");
+    let map = std::fs::read_to_string(repo.join(".git/filter-repo/commit-map")).unwrap();
+    let old = head_before.trim();
+    assert!(map.contains(&format!("{old} {old}")), "{map}");
+}
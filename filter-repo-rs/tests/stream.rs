@@ -133,3 +133,266 @@ fn fe_stream_override_requires_debug_mode() {
         "gating error should mention FRRS_DEBUG"
     );
 }
+
+fn filtered_stream_for_duplicate(detect_copies: bool) -> String {
+    let repo = init_repo();
+    write_file(&repo, "a.txt", "same contents for copy detection\n");
+    run_git(&repo, &["add", "."]);
+    run_git(&repo, &["commit", "-q", "-m", "add a"]);
+    write_file(&repo, "b.txt", "same contents for copy detection\n");
+    run_git(&repo, &["add", "."]);
+    run_git(&repo, &["commit", "-q", "-m", "copy a to b"]);
+
+    run_tool_expect_success(&repo, |o| {
+        o.debug_mode = true;
+        o.dry_run = true;
+        o.detect_copies = detect_copies;
+        o.path_renames.push((Vec::new(), b"prefix/".to_vec()));
+    });
+
+    std::fs::read_to_string(
+        repo.join(".git")
+            .join("filter-repo")
+            .join("fast-export.filtered"),
+    )
+    .expect("read filtered stream")
+}
+
+#[test]
+fn detect_copies_emits_prefixed_copy_line() {
+    let filtered = filtered_stream_for_duplicate(true);
+    assert!(
+        filtered.contains("C prefix/a.txt prefix/b.txt"),
+        "expected prefixed C line, got:\n{}",
+        filtered
+    );
+}
+
+#[test]
+fn without_copy_detection_duplicate_is_plain_modify() {
+    let filtered = filtered_stream_for_duplicate(false);
+    assert!(!filtered.lines().any(|l| l.starts_with("C ")));
+    assert!(filtered
+        .lines()
+        .any(|l| l.starts_with("M ") && l.ends_with(" prefix/a.txt")));
+    assert!(filtered
+        .lines()
+        .any(|l| l.starts_with("M ") && l.ends_with(" prefix/b.txt")));
+}
+
+const PGP_SIG: &str = "-----BEGIN PGP SIGNATURE-----\nc2lnbmF0dXJl\n-----END PGP SIGNATURE-----\n";
+
+// Two signed commits (touching a.txt, then b.txt) and a signed tag on the second.
+fn signed_stream(repo: &std::path::Path) -> std::path::PathBuf {
+    let data = |s: &str| format!("data {}\n{}", s.len(), s);
+    let commit = |mark: u32, oid: char, parent: Option<u32>, path: &str| {
+        let mut c = format!(
+            "commit refs/heads/main\nmark :{mark}\noriginal-oid {}\n",
+            oid.to_string().repeat(40)
+        );
+        c.push_str("author A <a@example.com> 0 +0000\ncommitter A <a@example.com> 0 +0000\n");
+        c.push_str(&format!("gpgsig sha1 openpgp\n{}", data(PGP_SIG)));
+        c.push_str(&data(&format!("change {path}\n")));
+        if let Some(p) = parent {
+            c.push_str(&format!("from :{p}\n"));
+        }
+        c.push_str(&format!("M 100644 :1 {path}\n\n"));
+        c
+    };
+    let mut s = format!("blob\nmark :1\n{}\n", data("one\n"));
+    s.push_str(&commit(2, 'a', None, "a.txt"));
+    s.push_str(&commit(3, 'b', Some(2), "b.txt"));
+    s.push_str("tag v1\nmark :4\nfrom :3\ntagger A <a@example.com> 0 +0000\n");
+    s.push_str(&data(&format!("release\n{PGP_SIG}")));
+    s.push_str("\ndone\n");
+    let path = repo.join("fe-signed.stream");
+    std::fs::write(&path, s).unwrap();
+    path
+}
+
+fn run_signed(
+    configure: impl FnOnce(&mut filter_repo_rs::Options),
+) -> Result<String, filter_repo_rs::FilterRepoError> {
+    let repo = init_repo();
+    let stream_path = signed_stream(&repo);
+    run_tool(&repo, |o| {
+        o.debug_mode = true;
+        o.dry_run = true;
+        #[allow(deprecated)]
+        {
+            o.fe_stream_override = Some(stream_path.clone());
+        }
+        configure(o);
+    })?;
+    Ok(std::fs::read_to_string(
+        repo.join(".git")
+            .join("filter-repo")
+            .join("fast-export.filtered"),
+    )
+    .expect("read filtered stream"))
+}
+
+#[test]
+fn signatures_are_stripped_by_default() {
+    let filtered = run_signed(|_| {}).expect("run should succeed");
+    assert!(!filtered.contains("gpgsig"), "{filtered}");
+    assert!(!filtered.contains("BEGIN PGP SIGNATURE"), "{filtered}");
+    assert!(filtered.contains("data 8\nrelease\n"), "{filtered}");
+}
+
+#[test]
+fn signed_commits_abort_names_the_commit() {
+    let err = run_signed(|o| o.signed_commits = filter_repo_rs::SignaturePolicy::Abort)
+        .expect_err("signed commit should abort the run");
+    let msg = err.to_string();
+    assert!(msg.contains(&"a".repeat(40)), "{msg}");
+}
+
+#[test]
+fn signed_tags_abort_names_the_tag() {
+    let err = run_signed(|o| o.signed_tags = filter_repo_rs::SignaturePolicy::Abort)
+        .expect_err("signed tag should abort the run");
+    assert!(err.to_string().contains("signed tag v1"), "{err}");
+}
+
+#[test]
+fn signatures_are_kept_on_unmodified_objects() {
+    let filtered = run_signed(|o| {
+        o.signed_commits = filter_repo_rs::SignaturePolicy::Keep;
+        o.signed_tags = filter_repo_rs::SignaturePolicy::Keep;
+    })
+    .expect("run should succeed");
+    assert_eq!(filtered.matches("gpgsig sha1 openpgp\n").count(), 2);
+    assert!(
+        filtered.contains(&format!("data {}\nrelease\n{PGP_SIG}", 8 + PGP_SIG.len())),
+        "{filtered}"
+    );
+}
+
+#[test]
+fn kept_signatures_are_dropped_from_rewritten_objects() {
+    let filtered = run_signed(|o| {
+        o.signed_commits = filter_repo_rs::SignaturePolicy::Keep;
+        o.signed_tags = filter_repo_rs::SignaturePolicy::Keep;
+        o.path_renames
+            .push((b"b.txt".to_vec(), b"renamed.txt".to_vec()));
+    })
+    .expect("run should succeed");
+    // The first commit is untouched; the second and the tag on it are rewritten
+    assert_eq!(filtered.matches("gpgsig sha1 openpgp\n").count(), 1);
+    let second = filtered.find("mark :3").unwrap();
+    assert!(filtered[..second].contains("gpgsig"), "{filtered}");
+    assert!(!filtered
+        .contains("BEGIN PGP SIGNATURE-----\nc2lnbmF0dXJl\n-----END PGP SIGNATURE-----\n\ndone"));
+    assert!(filtered.contains("data 8\nrelease\n"), "{filtered}");
+}
+
+fn run_stream(name: &str, stream: &str) -> Result<String, filter_repo_rs::FilterRepoError> {
+    let repo = init_repo();
+    let stream_path = repo.join(name);
+    std::fs::write(&stream_path, stream).unwrap();
+    run_tool(&repo, |o| {
+        o.debug_mode = true;
+        o.dry_run = true;
+        #[allow(deprecated)]
+        {
+            o.fe_stream_override = Some(stream_path.clone());
+        }
+    })?;
+    Ok(std::fs::read_to_string(
+        repo.join(".git")
+            .join("filter-repo")
+            .join("fast-export.filtered"),
+    )
+    .expect("read filtered stream"))
+}
+
+#[test]
+fn unsupported_command_is_a_stream_parse_error() {
+    let err = run_stream(
+        "fe-ls.stream",
+        "blob\nmark :1\ndata 4\none\n\nls \"a.txt\"\ndone\n",
+    )
+    .expect_err("ls needs a reply channel and must be rejected");
+    match err {
+        filter_repo_rs::FilterRepoError::StreamParse {
+            line_number,
+            ref snippet,
+            ..
+        } => {
+            assert_eq!(line_number, 6);
+            assert_eq!(snippet, "ls \"a.txt\"");
+        }
+        other => panic!("expected StreamParse, got {other:?}"),
+    }
+    assert_eq!(err.code(), "STREAM_PARSE");
+}
+
+#[test]
+fn truncated_data_names_the_blob() {
+    let err = run_stream(
+        "fe-truncated.stream",
+        "feature done\nblob\nmark :1\ndata 100\nshort\n",
+    )
+    .expect_err("truncated blob must fail");
+    match err {
+        filter_repo_rs::FilterRepoError::StreamParse {
+            line_number,
+            snippet,
+            reason,
+        } => {
+            assert_eq!(line_number, 4);
+            assert_eq!(snippet, "data 100");
+            assert!(reason.contains("blob :1"), "{reason}");
+            assert!(reason.contains("ended after 6"), "{reason}");
+        }
+        other => panic!("expected StreamParse, got {other:?}"),
+    }
+}
+
+#[test]
+fn truncated_commit_message_is_reported() {
+    let err = run_stream(
+        "fe-truncated-msg.stream",
+        "commit refs/heads/main\nmark :1\ncommitter A <a@example.com> 0 +0000\ndata 50\nmsg\n",
+    )
+    .expect_err("truncated message must fail");
+    match err {
+        filter_repo_rs::FilterRepoError::StreamParse {
+            line_number,
+            reason,
+            ..
+        } => {
+            assert_eq!(line_number, 4);
+            assert!(reason.contains("commit message"), "{reason}");
+        }
+        other => panic!("expected StreamParse, got {other:?}"),
+    }
+}
+
+#[test]
+fn stream_without_done_after_feature_done_fails() {
+    let err = run_stream(
+        "fe-no-done.stream",
+        "feature done\nblob\nmark :1\ndata 4\none\n",
+    )
+    .expect_err("missing done must fail");
+    assert!(
+        matches!(err, filter_repo_rs::FilterRepoError::StreamParse { ref reason, .. } if reason.contains("without `done`")),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn feature_and_option_lines_pass_through() {
+    let filtered = run_stream(
+        "fe-feature.stream",
+        "feature done\noption git quiet\nblob\nmark :1\ndata 4\none\n\ndone\n",
+    )
+    .expect("well-formed stream should run");
+    assert!(
+        filtered.starts_with("feature done\noption git quiet\n"),
+        "{filtered}"
+    );
+    assert!(filtered.ends_with("done\n"), "{filtered}");
+}